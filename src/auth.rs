@@ -0,0 +1,537 @@
+//! Users, roles and credentials for the management interfaces of a device, i.e. its web UI, its HTTP API
+//! and its MQTT commands.
+//!
+//! Passwords are never stored: only a salted PBKDF2-HMAC-SHA256 hash of each of them, computed with the
+//! `HmacSha256` of the backend.
+
+use core::fmt::{Debug, Write as _};
+
+#[cfg(feature = "use_serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::crypto::{Digest, HmacSha256, Rng};
+use crate::error::{impl_error, ErrorKind};
+use crate::storage::RawStorage;
+
+pub mod token;
+
+/// Each role is granted everything the roles below it are granted
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub enum Role {
+    /// Can read the state and the configuration of the device
+    Viewer,
+    /// Can also run commands, e.g. restart the device or toggle outputs
+    Operator,
+    /// Can also change the configuration and manage users
+    Admin,
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Viewer => "viewer",
+            Self::Operator => "operator",
+            Self::Admin => "admin",
+        }
+    }
+
+    pub fn allows(&self, required: Role) -> bool {
+        *self >= required
+    }
+
+    fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(Self::Viewer),
+            1 => Some(Self::Operator),
+            2 => Some(Self::Admin),
+            _ => None,
+        }
+    }
+}
+
+pub trait Authenticator {
+    type Error: Debug;
+
+    /// Returns the role of `user`, or `None` if the credentials are wrong
+    fn authenticate(&self, user: &str, password: &str) -> Result<Option<Role>, Self::Error>;
+}
+
+impl<A> Authenticator for &A
+where
+    A: Authenticator,
+{
+    type Error = A::Error;
+
+    fn authenticate(&self, user: &str, password: &str) -> Result<Option<Role>, Self::Error> {
+        (*self).authenticate(user, password)
+    }
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum AuthError<E> {
+    /// The credentials are missing or wrong
+    Unauthenticated,
+    /// The role of the user does not allow the operation
+    Forbidden,
+    AuthenticatorError(E),
+}
+
+impl_error! {
+    AuthError<E: Display> {
+        Unauthenticated => "Unauthenticated"; ErrorKind::Unauthorized,
+        Forbidden => "Forbidden"; ErrorKind::Unauthorized,
+        AuthenticatorError(e) => "Authenticator error: {e}"; e.error_kind(),
+    }
+}
+
+/// Checks that `user` is granted `required`, e.g. in an MQTT command handler receiving credentials along
+/// with the command
+pub fn authorize<A>(
+    authenticator: &A,
+    user: &str,
+    password: &str,
+    required: Role,
+) -> Result<Role, AuthError<A::Error>>
+where
+    A: Authenticator,
+{
+    match authenticator
+        .authenticate(user, password)
+        .map_err(AuthError::AuthenticatorError)?
+    {
+        Some(role) if role.allows(required) => Ok(role),
+        Some(_) => Err(AuthError::Forbidden),
+        None => Err(AuthError::Unauthenticated),
+    }
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum UserError<S, R> {
+    StorageError(S),
+    RngError(R),
+    InvalidName,
+    /// There is no room for another user
+    Full,
+}
+
+impl_error! {
+    UserError<S: Debug, R: Debug> {
+        StorageError(e) => "Storage error: {e:?}"; e.error_kind(),
+        RngError(e) => "RNG error: {e:?}"; e.error_kind(),
+        InvalidName => "Invalid user name"; ErrorKind::InvalidInput,
+        Full => "Too many users"; ErrorKind::Unavailable,
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct User {
+    name: heapless::String<16>,
+    role: Role,
+    iterations: u32,
+    salt: [u8; 16],
+    hash: Digest,
+}
+
+impl User {
+    const VERSION: u8 = 1;
+    const MAX_LEN: usize = 3 + 16 + 4 + 16 + 32;
+
+    fn encode<'a>(&self, buf: &'a mut [u8; Self::MAX_LEN]) -> &'a [u8] {
+        let name = self.name.as_bytes();
+        let len = 3 + name.len();
+
+        buf[0] = Self::VERSION;
+        buf[1] = self.role as u8;
+        buf[2] = name.len() as u8;
+        buf[3..len].copy_from_slice(name);
+        buf[len..len + 4].copy_from_slice(&self.iterations.to_le_bytes());
+        buf[len + 4..len + 20].copy_from_slice(&self.salt);
+        buf[len + 20..len + 52].copy_from_slice(&self.hash);
+
+        &buf[..len + 52]
+    }
+
+    fn decode(data: &[u8]) -> Option<Self> {
+        if *data.first()? != Self::VERSION {
+            return None;
+        }
+
+        let role = Role::from_u8(*data.get(1)?)?;
+        let len = 3 + *data.get(2)? as usize;
+        let name = core::str::from_utf8(data.get(3..len)?).ok()?;
+        let rest = data.get(len..len + 52)?;
+
+        let mut user = Self {
+            name: heapless::String::new(),
+            role,
+            iterations: u32::from_le_bytes([rest[0], rest[1], rest[2], rest[3]]),
+            salt: [0; 16],
+            hash: [0; 32],
+        };
+
+        user.name.push_str(name).ok()?;
+        user.salt.copy_from_slice(&rest[4..20]);
+        user.hash.copy_from_slice(&rest[20..]);
+
+        Some(user)
+    }
+}
+
+/// The slot and the user found if any, and the first free slot
+type Found = (Option<(usize, User)>, Option<usize>);
+
+/// Up to `N` users, each stored in a separate blob of a `RawStorage`
+pub struct UserStore<S, H, const N: usize = 8> {
+    storage: S,
+    hmac: H,
+    iterations: u32,
+}
+
+impl<S, H, const N: usize> UserStore<S, H, N>
+where
+    S: RawStorage,
+    H: HmacSha256,
+{
+    const PREFIX: &'static str = "auth";
+
+    /// The PBKDF2 iterations of the passwords set with a store created by `new`; the iterations are stored
+    /// along with each hash, so that they can be raised later
+    pub const DEFAULT_ITERATIONS: u32 = 1000;
+
+    pub const fn new(storage: S, hmac: H) -> Self {
+        Self::with_iterations(storage, hmac, Self::DEFAULT_ITERATIONS)
+    }
+
+    pub const fn with_iterations(storage: S, hmac: H, iterations: u32) -> Self {
+        Self {
+            storage,
+            hmac,
+            iterations,
+        }
+    }
+
+    /// Adds `name`, or changes its password and role if it exists already.
+    ///
+    /// Names are limited to 16 bytes and cannot contain `:`, as they could not be sent with HTTP basic
+    /// authentication otherwise.
+    pub fn set<R>(
+        &mut self,
+        rng: &mut R,
+        name: &str,
+        password: &str,
+        role: Role,
+    ) -> Result<(), UserError<S::Error, R::Error>>
+    where
+        R: Rng,
+    {
+        if name.is_empty() || name.len() > 16 || name.contains(':') {
+            return Err(UserError::InvalidName);
+        }
+
+        let slot = match self.find(name).map_err(UserError::StorageError)? {
+            (Some((slot, _)), _) => slot,
+            (None, Some(free)) => free,
+            (None, None) => return Err(UserError::Full),
+        };
+
+        let mut salt = [0_u8; 16];
+        rng.fill_bytes(&mut salt).map_err(UserError::RngError)?;
+
+        let user = User {
+            name: name.into(),
+            role,
+            iterations: self.iterations,
+            salt,
+            hash: pbkdf2(&self.hmac, password.as_bytes(), &salt, self.iterations),
+        };
+
+        self.save(slot, &user).map_err(UserError::StorageError)
+    }
+
+    /// Returns `false` if there is no such user
+    pub fn set_role(&mut self, name: &str, role: Role) -> Result<bool, S::Error> {
+        match self.find(name)? {
+            (Some((slot, mut user)), _) => {
+                user.role = role;
+
+                self.save(slot, &user)?;
+
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    /// Returns `false` if there is no such user
+    pub fn remove(&mut self, name: &str) -> Result<bool, S::Error> {
+        match self.find(name)? {
+            (Some((slot, _)), _) => self.storage.remove(&Self::slot(slot)),
+            _ => Ok(false),
+        }
+    }
+
+    pub fn role(&self, name: &str) -> Result<Option<Role>, S::Error> {
+        Ok(self.find(name)?.0.map(|(_, user)| user.role))
+    }
+
+    pub fn users(&self) -> Result<heapless::Vec<(heapless::String<16>, Role), N>, S::Error> {
+        let mut users = heapless::Vec::new();
+
+        for slot in 0..N {
+            if let Some(user) = self.load(slot)? {
+                let _ = users.push((user.name, user.role));
+            }
+        }
+
+        Ok(users)
+    }
+
+    pub fn release(self) -> (S, H) {
+        (self.storage, self.hmac)
+    }
+
+    fn find(&self, name: &str) -> Result<Found, S::Error> {
+        let mut free = None;
+
+        for slot in 0..N {
+            match self.load(slot)? {
+                Some(user) if user.name == name => return Ok((Some((slot, user)), free)),
+                Some(_) => (),
+                None => {
+                    free.get_or_insert(slot);
+                }
+            }
+        }
+
+        Ok((None, free))
+    }
+
+    fn load(&self, slot: usize) -> Result<Option<User>, S::Error> {
+        let mut buf = [0_u8; User::MAX_LEN];
+
+        Ok(self
+            .storage
+            .get_raw(&Self::slot(slot), &mut buf)?
+            .and_then(User::decode))
+    }
+
+    fn save(&mut self, slot: usize, user: &User) -> Result<(), S::Error> {
+        let mut buf = [0_u8; User::MAX_LEN];
+
+        self.storage
+            .set_raw(&Self::slot(slot), user.encode(&mut buf))?;
+
+        Ok(())
+    }
+
+    fn slot(slot: usize) -> heapless::String<16> {
+        let mut name = heapless::String::new();
+
+        write!(&mut name, "{}_{slot}", Self::PREFIX).unwrap();
+
+        name
+    }
+}
+
+impl<S, H, const N: usize> Authenticator for UserStore<S, H, N>
+where
+    S: RawStorage,
+    H: HmacSha256,
+{
+    type Error = S::Error;
+
+    fn authenticate(&self, user: &str, password: &str) -> Result<Option<Role>, Self::Error> {
+        match self.find(user)?.0 {
+            Some((_, user)) => {
+                let hash = pbkdf2(&self.hmac, password.as_bytes(), &user.salt, user.iterations);
+
+                Ok(constant_time_eq(&hash, &user.hash).then(|| user.role))
+            }
+            None => {
+                // Takes as long as with a wrong password, not to reveal which users exist
+                pbkdf2(&self.hmac, password.as_bytes(), &[0; 16], self.iterations);
+
+                Ok(None)
+            }
+        }
+    }
+}
+
+/// PBKDF2-HMAC-SHA256 (RFC 8018) with a single block of output
+fn pbkdf2<H>(hmac: &H, password: &[u8], salt: &[u8; 16], iterations: u32) -> Digest
+where
+    H: HmacSha256,
+{
+    let mut block = [0_u8; 20];
+
+    block[..16].copy_from_slice(salt);
+    block[16..].copy_from_slice(&1_u32.to_be_bytes());
+
+    let mut u = hmac.hmac_sha256(password, &block);
+    let mut hash = u;
+
+    for _ in 1..iterations {
+        u = hmac.hmac_sha256(password, &u);
+
+        for (hash, u) in hash.iter_mut().zip(u.iter()) {
+            *hash ^= u;
+        }
+    }
+
+    hash
+}
+
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+#[cfg(feature = "experimental")]
+pub mod server {
+    use core::fmt::Write as _;
+
+    use crate::http::server::{Connection, Handler, HandlerResult, Middleware};
+    use crate::http::Headers;
+    use crate::utils::codec::base64;
+
+    use super::token::TokenValidator;
+    use super::{Authenticator, Role};
+
+    /// Extracts the user and the password of an `Authorization: Basic` header
+    pub fn basic_credentials<'a>(header: &str, buf: &'a mut [u8]) -> Option<(&'a str, &'a str)> {
+        let (scheme, credentials) = header.trim().split_once(' ')?;
+
+        if !scheme.eq_ignore_ascii_case("basic") {
+            return None;
+        }
+
+        let len = base64::decode(credentials.trim(), base64::STANDARD, buf)?;
+
+        core::str::from_utf8(&buf[..len]).ok()?.split_once(':')
+    }
+
+    /// Lets through the requests authenticated with HTTP basic authentication by a user granted `required`.
+    ///
+    /// Responds with 401 to the requests without valid credentials, and with 403 to those of users without
+    /// the required role.
+    pub struct BasicAuthMiddleware<A> {
+        authenticator: A,
+        required: Role,
+        realm: &'static str,
+    }
+
+    impl<A> BasicAuthMiddleware<A> {
+        pub const fn new(authenticator: A, required: Role, realm: &'static str) -> Self {
+            Self {
+                authenticator,
+                required,
+                realm,
+            }
+        }
+    }
+
+    impl<C, A> Middleware<C> for BasicAuthMiddleware<A>
+    where
+        C: Connection,
+        A: Authenticator + Send,
+    {
+        fn handle<'a, H>(&'a self, connection: &'a mut C, handler: &'a H) -> HandlerResult
+        where
+            H: Handler<C>,
+        {
+            let mut buf = [0_u8; 96];
+
+            let role = match connection
+                .split()
+                .0
+                .header("Authorization")
+                .and_then(|header| basic_credentials(header, &mut buf))
+            {
+                Some((user, password)) => self.authenticator.authenticate(user, password)?,
+                None => None,
+            };
+
+            match role {
+                Some(role) if role.allows(self.required) => handler.handle(connection),
+                Some(_) => {
+                    connection.initiate_response(403, None, &[])?;
+
+                    Ok(())
+                }
+                None => {
+                    let mut challenge = heapless::String::<96>::new();
+
+                    write!(&mut challenge, "Basic realm=\"{}\"", self.realm)?;
+
+                    connection.initiate_response(401, None, &[("WWW-Authenticate", &challenge)])?;
+
+                    Ok(())
+                }
+            }
+        }
+    }
+
+    /// Extracts the token of an `Authorization: Bearer` header
+    pub fn bearer_token(header: &str) -> Option<&str> {
+        let (scheme, token) = header.trim().split_once(' ')?;
+
+        scheme.eq_ignore_ascii_case("bearer").then(|| token.trim())
+    }
+
+    /// Lets through the requests carrying a bearer token which grants `required`.
+    ///
+    /// Responds with 401 to the requests without a valid token, and with 403 to those whose token does not
+    /// grant the required role.
+    pub struct BearerAuthMiddleware<V> {
+        validator: V,
+        required: Role,
+    }
+
+    impl<V> BearerAuthMiddleware<V> {
+        pub const fn new(validator: V, required: Role) -> Self {
+            Self {
+                validator,
+                required,
+            }
+        }
+    }
+
+    impl<C, V> Middleware<C> for BearerAuthMiddleware<V>
+    where
+        C: Connection,
+        V: TokenValidator + Send,
+    {
+        fn handle<'a, H>(&'a self, connection: &'a mut C, handler: &'a H) -> HandlerResult
+        where
+            H: Handler<C>,
+        {
+            let role = match connection
+                .split()
+                .0
+                .header("Authorization")
+                .and_then(bearer_token)
+            {
+                Some(token) => self.validator.validate(token)?,
+                None => None,
+            };
+
+            match role {
+                Some(role) if role.allows(self.required) => handler.handle(connection),
+                Some(_) => {
+                    connection.initiate_response(403, None, &[])?;
+
+                    Ok(())
+                }
+                None => {
+                    connection.initiate_response(401, None, &[("WWW-Authenticate", "Bearer")])?;
+
+                    Ok(())
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,377 @@
+//! Bearer tokens, for machine-to-machine access to the HTTP API.
+//!
+//! `TokenStore` issues random tokens which are stored (hashed) and can be revoked one by one.
+//! `SignedTokens` issues HMAC-signed tokens carrying their role and expiry, which need no storage and are
+//! revoked all at once by moving to a new generation.
+
+use core::fmt::{Debug, Write as _};
+use core::marker::PhantomData;
+use core::time::Duration;
+
+use crate::crypto::{Digest, KeyHandle, KeyedHmacSha256, Rng, Sha256};
+use crate::error::{impl_error, ErrorKind};
+use crate::storage::RawStorage;
+use crate::sys_time::SystemTime;
+use crate::utils::codec::base64;
+
+use super::{constant_time_eq, Role};
+
+pub type Token = heapless::String<64>;
+
+/// Version, role, id, expiry and hash of a stored token
+const STORED_LEN: usize = 1 + 1 + 2 + 9 + 32;
+
+/// Version, role, generation and expiry of a signed token
+const PAYLOAD_LEN: usize = 1 + 1 + 4 + 8;
+
+pub trait TokenValidator {
+    type Error: Debug;
+
+    /// Returns the role granted by `token`, or `None` if the token is unknown, revoked or expired
+    fn validate(&self, token: &str) -> Result<Option<Role>, Self::Error>;
+}
+
+impl<V> TokenValidator for &V
+where
+    V: TokenValidator,
+{
+    type Error = V::Error;
+
+    fn validate(&self, token: &str) -> Result<Option<Role>, Self::Error> {
+        (*self).validate(token)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TokenInfo {
+    pub id: u16,
+    pub role: Role,
+    /// Seconds since the UNIX epoch; `None` if the token does not expire
+    pub expires: Option<u64>,
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum TokenError<S, R> {
+    StorageError(S),
+    RngError(R),
+    /// There is no room for another token
+    Full,
+}
+
+impl_error! {
+    TokenError<S: Debug, R: Debug> {
+        StorageError(e) => "Storage error: {e:?}"; e.error_kind(),
+        RngError(e) => "RNG error: {e:?}"; e.error_kind(),
+        Full => "Too many tokens"; ErrorKind::Unavailable,
+    }
+}
+
+/// Up to `N` random tokens, each stored in a separate blob of a `RawStorage` as its SHA-256 hash
+pub struct TokenStore<S, H, T, const N: usize = 8> {
+    storage: S,
+    time: T,
+    _hasher: PhantomData<fn() -> H>,
+}
+
+impl<S, H, T, const N: usize> TokenStore<S, H, T, N>
+where
+    S: RawStorage,
+    H: Sha256 + Default,
+    T: SystemTime,
+{
+    const PREFIX: &'static str = "token";
+
+    pub fn new(storage: S, time: T) -> Self {
+        Self {
+            storage,
+            time,
+            _hasher: PhantomData,
+        }
+    }
+
+    /// Issues a token granting `role` until `expires` (seconds since the UNIX epoch), if any.
+    ///
+    /// The token is returned only here: just its hash is stored.
+    pub fn issue<R>(
+        &mut self,
+        rng: &mut R,
+        role: Role,
+        expires: Option<u64>,
+    ) -> Result<(TokenInfo, Token), TokenError<S::Error, R::Error>>
+    where
+        R: Rng,
+    {
+        let mut free = None;
+        let mut id = 0;
+
+        for slot in 0..N {
+            match self.load(slot).map_err(TokenError::StorageError)? {
+                Some((info, _)) => id = id.max(info.id.wrapping_add(1)),
+                None => {
+                    free.get_or_insert(slot);
+                }
+            }
+        }
+
+        let slot = free.ok_or(TokenError::Full)?;
+
+        let mut secret = [0_u8; 32];
+        rng.fill_bytes(&mut secret).map_err(TokenError::RngError)?;
+
+        let mut token = Token::new();
+        base64::encode(&secret, base64::URL_SAFE, false, &mut token).unwrap();
+
+        let info = TokenInfo { id, role, expires };
+
+        let mut buf = [0_u8; STORED_LEN];
+
+        buf[0] = 1;
+        buf[1] = role as u8;
+        buf[2..4].copy_from_slice(&id.to_le_bytes());
+        buf[4] = expires.is_some() as u8;
+        buf[5..13].copy_from_slice(&expires.unwrap_or(0).to_le_bytes());
+        buf[13..].copy_from_slice(&Self::hash(&token));
+
+        self.storage
+            .set_raw(&Self::slot(slot), &buf)
+            .map_err(TokenError::StorageError)?;
+
+        Ok((info, token))
+    }
+
+    /// Returns `false` if there is no such token
+    pub fn revoke(&mut self, id: u16) -> Result<bool, S::Error> {
+        for slot in 0..N {
+            if matches!(self.load(slot)?, Some((info, _)) if info.id == id) {
+                return self.storage.remove(&Self::slot(slot));
+            }
+        }
+
+        Ok(false)
+    }
+
+    pub fn tokens(&self) -> Result<heapless::Vec<TokenInfo, N>, S::Error> {
+        let mut tokens = heapless::Vec::new();
+
+        for slot in 0..N {
+            if let Some((info, _)) = self.load(slot)? {
+                let _ = tokens.push(info);
+            }
+        }
+
+        Ok(tokens)
+    }
+
+    /// Removes the tokens which expired
+    pub fn purge(&mut self) -> Result<usize, S::Error> {
+        let now = self.time.now().as_secs();
+        let mut count = 0;
+
+        for slot in 0..N {
+            if let Some((
+                TokenInfo {
+                    expires: Some(expires),
+                    ..
+                },
+                _,
+            )) = self.load(slot)?
+            {
+                if expires <= now {
+                    self.storage.remove(&Self::slot(slot))?;
+                    count += 1;
+                }
+            }
+        }
+
+        Ok(count)
+    }
+
+    pub fn release(self) -> (S, T) {
+        (self.storage, self.time)
+    }
+
+    fn load(&self, slot: usize) -> Result<Option<(TokenInfo, Digest)>, S::Error> {
+        let mut buf = [0_u8; STORED_LEN];
+
+        let data = match self.storage.get_raw(&Self::slot(slot), &mut buf)? {
+            Some(data) if data.len() == STORED_LEN && data[0] == 1 => data,
+            _ => return Ok(None),
+        };
+
+        let role = match Role::from_u8(data[1]) {
+            Some(role) => role,
+            None => return Ok(None),
+        };
+
+        let mut expires = [0_u8; 8];
+        expires.copy_from_slice(&data[5..13]);
+
+        let mut hash = [0_u8; 32];
+        hash.copy_from_slice(&data[13..]);
+
+        Ok(Some((
+            TokenInfo {
+                id: u16::from_le_bytes([data[2], data[3]]),
+                role,
+                expires: (data[4] != 0).then(|| u64::from_le_bytes(expires)),
+            },
+            hash,
+        )))
+    }
+
+    fn hash(token: &str) -> Digest {
+        crate::crypto::sha256(H::default(), token.as_bytes())
+    }
+
+    fn slot(slot: usize) -> heapless::String<16> {
+        let mut name = heapless::String::new();
+
+        write!(&mut name, "{}_{slot}", Self::PREFIX).unwrap();
+
+        name
+    }
+}
+
+impl<S, H, T, const N: usize> TokenValidator for TokenStore<S, H, T, N>
+where
+    S: RawStorage,
+    H: Sha256 + Default,
+    T: SystemTime,
+{
+    type Error = S::Error;
+
+    fn validate(&self, token: &str) -> Result<Option<Role>, Self::Error> {
+        let hash = Self::hash(token);
+        let now = self.time.now().as_secs();
+
+        for slot in 0..N {
+            if let Some((info, stored)) = self.load(slot)? {
+                if constant_time_eq(&hash, &stored) {
+                    return Ok(info
+                        .expires
+                        .map_or(true, |expires| now < expires)
+                        .then(|| info.role));
+                }
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+/// Stateless tokens carrying their role, their expiry and their generation, signed with a key held by the
+/// `KeyedHmacSha256` provider.
+///
+/// The tokens of older generations are rejected, so moving to a new generation revokes all the tokens
+/// issued so far; the application should persist the generation.
+pub struct SignedTokens<H, T> {
+    hmac: H,
+    key: KeyHandle,
+    time: T,
+    generation: u32,
+}
+
+impl<H, T> SignedTokens<H, T>
+where
+    H: KeyedHmacSha256,
+    T: SystemTime,
+{
+    const VERSION: u8 = 1;
+
+    pub const fn new(hmac: H, key: KeyHandle, time: T, generation: u32) -> Self {
+        Self {
+            hmac,
+            key,
+            time,
+            generation,
+        }
+    }
+
+    pub fn generation(&self) -> u32 {
+        self.generation
+    }
+
+    /// Revokes all the tokens issued so far; returns the new generation
+    pub fn revoke_all(&mut self) -> u32 {
+        self.generation = self.generation.wrapping_add(1);
+        self.generation
+    }
+
+    /// Issues a token granting `role` for `valid_for`
+    pub fn issue(&self, role: Role, valid_for: Duration) -> Result<Token, H::Error> {
+        let expires = self
+            .time
+            .now()
+            .as_secs()
+            .saturating_add(valid_for.as_secs());
+
+        let mut payload = [0_u8; PAYLOAD_LEN];
+
+        payload[0] = Self::VERSION;
+        payload[1] = role as u8;
+        payload[2..6].copy_from_slice(&self.generation.to_le_bytes());
+        payload[6..].copy_from_slice(&expires.to_le_bytes());
+
+        let signature = self.hmac.hmac_sha256(self.key, &payload)?;
+
+        let mut token = Token::new();
+
+        base64::encode(&payload, base64::URL_SAFE, false, &mut token).unwrap();
+        token.push('.').unwrap();
+        base64::encode(&signature, base64::URL_SAFE, false, &mut token).unwrap();
+
+        Ok(token)
+    }
+
+    pub fn release(self) -> (H, T) {
+        (self.hmac, self.time)
+    }
+}
+
+impl<H, T> TokenValidator for SignedTokens<H, T>
+where
+    H: KeyedHmacSha256,
+    T: SystemTime,
+{
+    type Error = H::Error;
+
+    fn validate(&self, token: &str) -> Result<Option<Role>, Self::Error> {
+        let (payload, signature) = match token.split_once('.') {
+            Some(parts) => parts,
+            None => return Ok(None),
+        };
+
+        let mut payload_buf = [0_u8; PAYLOAD_LEN + 2];
+        let mut signature_buf = [0_u8; 34];
+
+        let (payload, signature) = match (
+            base64::decode(payload, base64::URL_SAFE, &mut payload_buf),
+            base64::decode(signature, base64::URL_SAFE, &mut signature_buf),
+        ) {
+            (Some(PAYLOAD_LEN), Some(32)) => (&payload_buf[..PAYLOAD_LEN], &signature_buf[..32]),
+            _ => return Ok(None),
+        };
+
+        if !constant_time_eq(&self.hmac.hmac_sha256(self.key, payload)?, signature) {
+            return Ok(None);
+        }
+
+        let generation = u32::from_le_bytes([payload[2], payload[3], payload[4], payload[5]]);
+
+        let mut expires = [0_u8; 8];
+        expires.copy_from_slice(&payload[6..]);
+
+        if payload[0] != Self::VERSION
+            || generation != self.generation
+            || u64::from_le_bytes(expires) <= self.time.now().as_secs()
+        {
+            return Ok(None);
+        }
+
+        Ok(Role::from_u8(payload[1]))
+    }
+}
@@ -0,0 +1,97 @@
+//! Runtime discovery of the optional features of service implementations.
+//!
+//! Portable application code checks `Capabilities::supports` before using an optional feature, e.g. falls
+//! back to SoftAP provisioning when WPS is not available, rather than attempting the operation and
+//! handling its unsupported-operation error.
+
+use enumset::*;
+
+#[cfg(feature = "use_serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::wifi;
+
+#[derive(EnumSetType, Debug, PartialOrd)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub enum Capability {
+    WifiClient,
+    WifiAccessPoint,
+    /// Client and access point at the same time
+    WifiMixed,
+    WifiWps,
+    /// Channel state information
+    WifiCsi,
+    WifiSniffer,
+    MqttV5,
+    MqttTls,
+    /// Messages can be enqueued while disconnected, and are sent once connected
+    MqttOfflineQueue,
+    HttpClientTls,
+    HttpServerTls,
+    HttpServerWebSocket,
+    /// TLS client authentication with a certificate
+    TlsClientCertificate,
+    TlsPsk,
+    /// The firmware falls back to the previous slot when the new one is not marked valid
+    OtaRollback,
+    StorageEncryption,
+    /// Keys can be stored and used without ever leaving a secure element or a protected key store
+    SecureKeyStorage,
+}
+
+impl From<wifi::Capability> for Capability {
+    fn from(capability: wifi::Capability) -> Self {
+        match capability {
+            wifi::Capability::Client => Self::WifiClient,
+            wifi::Capability::AccessPoint => Self::WifiAccessPoint,
+            wifi::Capability::Mixed => Self::WifiMixed,
+            wifi::Capability::Csi => Self::WifiCsi,
+            wifi::Capability::Sniffer => Self::WifiSniffer,
+        }
+    }
+}
+
+/// Reported by service implementations; a service supporting only the mandatory parts of its traits
+/// reports no capabilities.
+///
+/// The capabilities are those of the implementation in its current configuration, e.g. a Wi-Fi driver
+/// built without WPS support does not report `WifiWps`, and are not expected to change at runtime.
+pub trait Capabilities {
+    fn capabilities(&self) -> EnumSet<Capability>;
+
+    fn supports(&self, capability: Capability) -> bool {
+        self.capabilities().contains(capability)
+    }
+}
+
+impl<C> Capabilities for &C
+where
+    C: Capabilities,
+{
+    fn capabilities(&self) -> EnumSet<Capability> {
+        (**self).capabilities()
+    }
+
+    fn supports(&self, capability: Capability) -> bool {
+        (**self).supports(capability)
+    }
+}
+
+impl<C> Capabilities for &mut C
+where
+    C: Capabilities,
+{
+    fn capabilities(&self) -> EnumSet<Capability> {
+        (**self).capabilities()
+    }
+
+    fn supports(&self, capability: Capability) -> bool {
+        (**self).supports(capability)
+    }
+}
+
+/// The capabilities reported by `Wifi::get_capabilities`
+pub fn wifi_capabilities(capabilities: EnumSet<wifi::Capability>) -> EnumSet<Capability> {
+    capabilities.iter().map(Capability::from).collect()
+}
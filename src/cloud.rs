@@ -0,0 +1,6 @@
+#[cfg(feature = "cloud_aws")]
+pub mod aws;
+#[cfg(feature = "cloud_azure")]
+pub mod azure;
+#[cfg(feature = "cloud_jwt")]
+pub mod jwt;
@@ -0,0 +1,431 @@
+use core::fmt::{self, Write as _};
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::mqtt::client::{Client, MessageId, Publish, QoS};
+use crate::storage::SerDe;
+use crate::tls::{self, X509};
+
+pub use crate::utils::shadow::ShadowError;
+
+/// The ALPN protocol which allows MQTT over port 443, for networks which block port 8883
+pub const ALPN_MQTT: &str = "x-amzn-mqtt-ca";
+
+pub const PORT_MQTT: u16 = 8883;
+pub const PORT_MQTT_ALPN: u16 = 443;
+
+pub const MAX_TOPIC_LEN: usize = 256;
+
+pub type Topic = heapless::String<MAX_TOPIC_LEN>;
+
+/// The connection parameters of a device registered as an AWS IoT thing.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Thing<'a> {
+    /// The account-specific device data endpoint, e.g. `xxxxxxxxxxxxxx-ats.iot.eu-west-1.amazonaws.com`
+    pub endpoint: &'a str,
+    pub thing_name: &'a str,
+    /// Typically the Amazon Root CA 1 certificate; `None` uses the backend's global CA store
+    pub ca_certificate: Option<X509<'a>>,
+    pub certificate: X509<'a>,
+    pub private_key: X509<'a>,
+    /// Connect on port 443 using the `x-amzn-mqtt-ca` ALPN protocol instead of on port 8883
+    pub use_alpn: bool,
+}
+
+impl<'a> Thing<'a> {
+    pub fn port(&self) -> u16 {
+        if self.use_alpn {
+            PORT_MQTT_ALPN
+        } else {
+            PORT_MQTT
+        }
+    }
+
+    pub fn broker_uri<const N: usize>(&self) -> Result<heapless::String<N>, &'static str> {
+        let mut uri = heapless::String::new();
+
+        write!(&mut uri, "mqtts://{}:{}", self.endpoint, self.port())
+            .map_err(|_| "Broker URI too long")?;
+
+        Ok(uri)
+    }
+
+    /// The MQTT client ID; AWS IoT policies usually require it to be the thing name
+    pub fn client_id(&self) -> &'a str {
+        self.thing_name
+    }
+
+    pub fn tls_configuration(&self) -> tls::ClientConfiguration<'a> {
+        tls::ClientConfiguration {
+            ca_certificate: self.ca_certificate,
+            use_global_ca_store: self.ca_certificate.is_none(),
+            client_certificate: Some(self.certificate),
+            private_key: Some(self.private_key),
+            alpn_protocols: if self.use_alpn { &[ALPN_MQTT] } else { &[] },
+            server_name: Some(self.endpoint),
+            ..Default::default()
+        }
+    }
+
+    /// The classic shadow when `shadow_name` is `None`, or the named shadow otherwise
+    pub fn shadow_topics(
+        &self,
+        shadow_name: Option<&'a str>,
+    ) -> Result<ShadowTopics<'a>, &'static str> {
+        ShadowTopics::new(self.thing_name, shadow_name)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ShadowTopic {
+    Get,
+    GetAccepted,
+    GetRejected,
+    Update,
+    UpdateAccepted,
+    UpdateRejected,
+    UpdateDelta,
+    UpdateDocuments,
+    Delete,
+    DeleteAccepted,
+    DeleteRejected,
+}
+
+impl ShadowTopic {
+    const ALL: [Self; 11] = [
+        Self::Get,
+        Self::GetAccepted,
+        Self::GetRejected,
+        Self::Update,
+        Self::UpdateAccepted,
+        Self::UpdateRejected,
+        Self::UpdateDelta,
+        Self::UpdateDocuments,
+        Self::Delete,
+        Self::DeleteAccepted,
+        Self::DeleteRejected,
+    ];
+
+    pub fn suffix(&self) -> &'static str {
+        match self {
+            Self::Get => "get",
+            Self::GetAccepted => "get/accepted",
+            Self::GetRejected => "get/rejected",
+            Self::Update => "update",
+            Self::UpdateAccepted => "update/accepted",
+            Self::UpdateRejected => "update/rejected",
+            Self::UpdateDelta => "update/delta",
+            Self::UpdateDocuments => "update/documents",
+            Self::Delete => "delete",
+            Self::DeleteAccepted => "delete/accepted",
+            Self::DeleteRejected => "delete/rejected",
+        }
+    }
+}
+
+pub const MAX_THING_NAME_LEN: usize = 128;
+pub const MAX_SHADOW_NAME_LEN: usize = 64;
+
+/// The reserved topics of a device shadow
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ShadowTopics<'a> {
+    thing_name: &'a str,
+    shadow_name: Option<&'a str>,
+}
+
+impl<'a> ShadowTopics<'a> {
+    /// Fails if the names are longer than AWS IoT allows, or contain characters other than
+    /// alphanumerics, `-`, `_` and `:`, so that the topics are always valid and fit in a `Topic`
+    pub fn new(thing_name: &'a str, shadow_name: Option<&'a str>) -> Result<Self, &'static str> {
+        let valid = |name: &str, max_len| {
+            !name.is_empty()
+                && name.len() <= max_len
+                && name
+                    .bytes()
+                    .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_' || b == b':')
+        };
+
+        if !valid(thing_name, MAX_THING_NAME_LEN) {
+            Err("Invalid thing name")
+        } else if !shadow_name.map_or(true, |name| valid(name, MAX_SHADOW_NAME_LEN)) {
+            Err("Invalid shadow name")
+        } else {
+            Ok(Self {
+                thing_name,
+                shadow_name,
+            })
+        }
+    }
+
+    pub fn thing_name(&self) -> &'a str {
+        self.thing_name
+    }
+
+    pub fn shadow_name(&self) -> Option<&'a str> {
+        self.shadow_name
+    }
+
+    pub fn topic(&self, topic: ShadowTopic) -> Topic {
+        let mut string = Topic::new();
+
+        // Cannot fail, as the names were validated by `new`
+        let _ = write!(&mut string, "{self}/{}", topic.suffix());
+
+        string
+    }
+
+    /// Returns which shadow topic `topic` is, if it belongs to this shadow
+    pub fn parse(&self, topic: &str) -> Option<ShadowTopic> {
+        let mut prefix = Topic::new();
+        write!(&mut prefix, "{self}/").ok()?;
+
+        let suffix = topic.strip_prefix(prefix.as_str())?;
+
+        ShadowTopic::ALL
+            .iter()
+            .find(|shadow_topic| shadow_topic.suffix() == suffix)
+            .copied()
+    }
+
+    /// Subscribes to the responses to get and update requests, as well as to the delta notifications
+    pub fn subscribe<C>(&self, client: &mut C) -> Result<(), C::Error>
+    where
+        C: Client,
+    {
+        for topic in [
+            ShadowTopic::GetAccepted,
+            ShadowTopic::GetRejected,
+            ShadowTopic::UpdateAccepted,
+            ShadowTopic::UpdateRejected,
+            ShadowTopic::UpdateDelta,
+        ]
+        .iter()
+        {
+            let topic = self.topic(*topic);
+
+            client.subscribe(&topic, QoS::AtLeastOnce)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<'a> fmt::Display for ShadowTopics<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "$aws/things/{}/shadow", self.thing_name)?;
+
+        if let Some(shadow_name) = self.shadow_name {
+            write!(f, "/name/{shadow_name}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The state section of an update request; `None` sections are left untouched
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct StateUpdate<'a, R, D> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reported: Option<&'a R>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub desired: Option<&'a D>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct UpdateRequest<'a, R, D> {
+    pub state: StateUpdate<'a, R, D>,
+    #[serde(rename = "clientToken", skip_serializing_if = "Option::is_none")]
+    pub client_token: Option<&'a str>,
+    /// When set, the update is rejected if the shadow's version differs
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<u64>,
+}
+
+/// The payload published on `update/delta`, where `T` is (a subset of) the desired state
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+pub struct Delta<T> {
+    pub version: u64,
+    pub timestamp: u64,
+    pub state: T,
+    #[serde(rename = "clientToken", default)]
+    pub client_token: Option<heapless::String<64>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+pub struct DocumentState<R, D> {
+    #[serde(default = "Option::default")]
+    pub reported: Option<R>,
+    #[serde(default = "Option::default")]
+    pub desired: Option<D>,
+    #[serde(default = "Option::default")]
+    pub delta: Option<D>,
+}
+
+/// The payload published on `get/accepted` and `update/accepted`
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+pub struct Document<R, D> {
+    pub state: DocumentState<R, D>,
+    pub version: u64,
+    pub timestamp: u64,
+    #[serde(rename = "clientToken", default)]
+    pub client_token: Option<heapless::String<64>>,
+}
+
+/// The payload published on the `*/rejected` topics
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+pub struct Rejected {
+    pub code: u16,
+    pub message: heapless::String<128>,
+    #[serde(rename = "clientToken", default)]
+    pub client_token: Option<heapless::String<64>>,
+}
+
+/// Publishes shadow requests, serialized with `S` into a buffer of `B` bytes,
+/// and decodes the shadow service responses.
+pub struct Shadow<'a, P, S, const B: usize = 1024> {
+    publisher: P,
+    serde: S,
+    topics: ShadowTopics<'a>,
+}
+
+impl<'a, P, S, const B: usize> Shadow<'a, P, S, B>
+where
+    P: Publish,
+    S: SerDe,
+{
+    pub const fn new(publisher: P, serde: S, topics: ShadowTopics<'a>) -> Self {
+        Self {
+            publisher,
+            serde,
+            topics,
+        }
+    }
+
+    pub fn topics(&self) -> &ShadowTopics<'a> {
+        &self.topics
+    }
+
+    /// The response arrives on `get/accepted` or `get/rejected`
+    pub fn request_get(&mut self) -> Result<MessageId, P::Error> {
+        let topic = self.topics.topic(ShadowTopic::Get);
+
+        self.publisher
+            .publish(&topic, QoS::AtLeastOnce, false, b"{}")
+    }
+
+    pub fn report<R>(
+        &mut self,
+        reported: &R,
+        client_token: Option<&str>,
+    ) -> Result<MessageId, ShadowError<P::Error, S::Error>>
+    where
+        R: Serialize,
+    {
+        self.update::<R, ()>(&UpdateRequest {
+            state: StateUpdate {
+                reported: Some(reported),
+                desired: None,
+            },
+            client_token,
+            version: None,
+        })
+    }
+
+    pub fn update<R, D>(
+        &mut self,
+        request: &UpdateRequest<'_, R, D>,
+    ) -> Result<MessageId, ShadowError<P::Error, S::Error>>
+    where
+        R: Serialize,
+        D: Serialize,
+    {
+        let mut buf = [0_u8; B];
+
+        let payload = self
+            .serde
+            .serialize(&mut buf, request)
+            .map_err(ShadowError::SerdeError)?;
+
+        let topic = self.topics.topic(ShadowTopic::Update);
+
+        self.publisher
+            .publish(&topic, QoS::AtLeastOnce, false, payload)
+            .map_err(ShadowError::PublishError)
+    }
+
+    pub fn parse_delta<T>(&self, data: &[u8]) -> Result<Delta<T>, S::Error>
+    where
+        T: DeserializeOwned,
+    {
+        self.serde.deserialize(data)
+    }
+
+    pub fn parse_document<R, D>(&self, data: &[u8]) -> Result<Document<R, D>, S::Error>
+    where
+        R: DeserializeOwned,
+        D: DeserializeOwned,
+    {
+        self.serde.deserialize(data)
+    }
+
+    pub fn parse_rejected(&self, data: &[u8]) -> Result<Rejected, S::Error> {
+        self.serde.deserialize(data)
+    }
+
+    pub fn release(self) -> (P, S) {
+        (self.publisher, self.serde)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn topics() {
+        let topics = ShadowTopics::new("sensor-1", Some("config")).unwrap();
+
+        assert_eq!(
+            topics.topic(ShadowTopic::UpdateDelta),
+            "$aws/things/sensor-1/shadow/name/config/update/delta"
+        );
+        assert_eq!(
+            topics.parse("$aws/things/sensor-1/shadow/name/config/get/accepted"),
+            Some(ShadowTopic::GetAccepted)
+        );
+        assert_eq!(
+            topics.parse("$aws/things/sensor-1/shadow/get/accepted"),
+            None
+        );
+    }
+
+    #[test]
+    fn longest_topic_fits() {
+        let thing_name = [b'a'; MAX_THING_NAME_LEN];
+        let shadow_name = [b'b'; MAX_SHADOW_NAME_LEN];
+
+        let topics = ShadowTopics::new(
+            core::str::from_utf8(&thing_name).unwrap(),
+            Some(core::str::from_utf8(&shadow_name).unwrap()),
+        )
+        .unwrap();
+
+        assert!(topics
+            .topic(ShadowTopic::UpdateDocuments)
+            .ends_with("/update/documents"));
+    }
+
+    #[test]
+    fn invalid_names() {
+        let too_long = [b'a'; MAX_THING_NAME_LEN + 1];
+
+        assert!(ShadowTopics::new(core::str::from_utf8(&too_long).unwrap(), None).is_err());
+        assert!(ShadowTopics::new("", None).is_err());
+        assert!(ShadowTopics::new("sensor/1", None).is_err());
+        assert!(ShadowTopics::new("sensor-1", Some("#")).is_err());
+    }
+}
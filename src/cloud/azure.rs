@@ -0,0 +1,343 @@
+use core::fmt::{self, Write as _};
+
+use crate::crypto::HmacSha256;
+use crate::mqtt::client::{Client, MessageId, Publish, QoS};
+use crate::utils::codec::base64;
+
+pub const API_VERSION: &str = "2021-04-12";
+
+pub const PORT_MQTT: u16 = 8883;
+
+pub const TOPIC_METHODS_SUBSCRIBE: &str = "$iothub/methods/POST/#";
+pub const TOPIC_TWIN_RESPONSES_SUBSCRIBE: &str = "$iothub/twin/res/#";
+pub const TOPIC_TWIN_DESIRED_SUBSCRIBE: &str = "$iothub/twin/PATCH/properties/desired/#";
+
+const TOPIC_METHODS_PREFIX: &str = "$iothub/methods/POST/";
+
+pub const MAX_TOPIC_LEN: usize = 256;
+
+pub type Topic = heapless::String<MAX_TOPIC_LEN>;
+
+/// A SAS token; typically around 150-250 characters long
+pub type SasToken = heapless::String<384>;
+
+pub const MAX_DEVICE_ID_LEN: usize = 128;
+/// The longest request ID of the direct method invocations answered; IoT Hub sends short numbers
+pub const MAX_REQUEST_ID_LEN: usize = 64;
+
+/// The identity of a device registered in an Azure IoT Hub, authenticating with a symmetric key.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Device<'a> {
+    hub_hostname: &'a str,
+    device_id: &'a str,
+}
+
+impl<'a> Device<'a> {
+    /// `hub_hostname` is e.g. `my-hub.azure-devices.net`.
+    ///
+    /// Fails if `device_id` is longer than IoT Hub allows, or contains characters which it does not,
+    /// so that the device topics always fit in a `Topic`.
+    pub fn new(hub_hostname: &'a str, device_id: &'a str) -> Result<Self, &'static str> {
+        if device_id.is_empty()
+            || device_id.len() > MAX_DEVICE_ID_LEN
+            || !device_id
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || b"-.%_*?!(),:=@$'".contains(&b))
+        {
+            Err("Invalid device ID")
+        } else {
+            Ok(Self {
+                hub_hostname,
+                device_id,
+            })
+        }
+    }
+
+    pub fn hub_hostname(&self) -> &'a str {
+        self.hub_hostname
+    }
+
+    pub fn device_id(&self) -> &'a str {
+        self.device_id
+    }
+
+    pub fn broker_uri<const N: usize>(&self) -> Result<heapless::String<N>, &'static str> {
+        let mut uri = heapless::String::new();
+
+        write!(&mut uri, "mqtts://{}:{PORT_MQTT}", self.hub_hostname)
+            .map_err(|_| "Broker URI too long")?;
+
+        Ok(uri)
+    }
+
+    pub fn client_id(&self) -> &'a str {
+        self.device_id
+    }
+
+    pub fn username<const N: usize>(&self) -> Result<heapless::String<N>, &'static str> {
+        let mut username = heapless::String::new();
+
+        write!(
+            &mut username,
+            "{}/{}/?api-version={API_VERSION}",
+            self.hub_hostname, self.device_id
+        )
+        .map_err(|_| "Username too long")?;
+
+        Ok(username)
+    }
+
+    /// Generates a SAS token for the MQTT password field, valid until `expiry` (in seconds since the UNIX epoch).
+    ///
+    /// `key` is the Base64-encoded device (or shared access policy) key; `policy_name` should only be set
+    /// when authenticating with a shared access policy rather than with a device key.
+    pub fn sas_token<H>(
+        &self,
+        hmac: &H,
+        key: &str,
+        policy_name: Option<&str>,
+        expiry: u64,
+    ) -> Result<SasToken, &'static str>
+    where
+        H: HmacSha256,
+    {
+        let mut key_bytes = [0_u8; 64];
+        let key_len =
+            base64::decode(key.trim(), base64::STANDARD, &mut key_bytes).ok_or("Invalid key")?;
+
+        let mut resource_uri = heapless::String::<192>::new();
+        write!(
+            &mut resource_uri,
+            "{}/devices/{}",
+            self.hub_hostname, self.device_id
+        )
+        .map_err(|_| "Resource URI too long")?;
+
+        let mut string_to_sign = heapless::String::<256>::new();
+        write!(
+            &mut string_to_sign,
+            "{}\n{expiry}",
+            PercentEncoded(&resource_uri)
+        )
+        .map_err(|_| "Resource URI too long")?;
+
+        let signature = hmac.hmac_sha256(&key_bytes[..key_len], string_to_sign.as_bytes());
+
+        let mut signature_base64 = heapless::String::<44>::new();
+        base64::encode(&signature, base64::STANDARD, true, &mut signature_base64).unwrap();
+
+        let mut token = SasToken::new();
+        write!(
+            &mut token,
+            "SharedAccessSignature sr={}&sig={}&se={expiry}",
+            PercentEncoded(&resource_uri),
+            PercentEncoded(&signature_base64)
+        )
+        .map_err(|_| "SAS token too long")?;
+
+        if let Some(policy_name) = policy_name {
+            write!(&mut token, "&skn={}", PercentEncoded(policy_name))
+                .map_err(|_| "SAS token too long")?;
+        }
+
+        Ok(token)
+    }
+
+    /// The device-to-cloud telemetry topic
+    pub fn events_topic(&self) -> Topic {
+        let mut topic = Topic::new();
+
+        // Cannot fail, as the device ID was validated by `new`
+        let _ = write!(&mut topic, "devices/{}/messages/events/", self.device_id);
+
+        topic
+    }
+
+    /// The cloud-to-device (device-bound) messages topic filter
+    pub fn devicebound_topic_filter(&self) -> Topic {
+        let mut topic = Topic::new();
+
+        // Cannot fail, as the device ID was validated by `new`
+        let _ = write!(
+            &mut topic,
+            "devices/{}/messages/devicebound/#",
+            self.device_id
+        );
+
+        topic
+    }
+
+    /// Returns the property bag of a device-bound message; `None` if `topic` is not device-bound
+    pub fn parse_devicebound<'t>(&self, topic: &'t str) -> Option<PropertyBag<'t>> {
+        let properties = topic
+            .strip_prefix("devices/")?
+            .strip_prefix(self.device_id)?
+            .strip_prefix("/messages/devicebound/")?;
+
+        Some(PropertyBag(properties))
+    }
+
+    /// Subscribes to device-bound messages and direct method invocations
+    pub fn subscribe<C>(&self, client: &mut C) -> Result<(), C::Error>
+    where
+        C: Client,
+    {
+        client.subscribe(&self.devicebound_topic_filter(), QoS::AtLeastOnce)?;
+        client.subscribe(TOPIC_METHODS_SUBSCRIBE, QoS::AtMostOnce)?;
+
+        Ok(())
+    }
+}
+
+/// A URL-encoded `key=value&key=value` property bag, as carried by device-bound message topics
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct PropertyBag<'a>(pub &'a str);
+
+impl<'a> PropertyBag<'a> {
+    /// Returns the raw (still URL-encoded) properties
+    pub fn iter(&self) -> impl Iterator<Item = (&'a str, &'a str)> {
+        self.0
+            .split('&')
+            .filter(|property| !property.is_empty())
+            .map(|property| property.split_once('=').unwrap_or((property, "")))
+    }
+
+    pub fn get(&self, key: &str) -> Option<&'a str> {
+        self.iter()
+            .find(|(property, _)| *property == key)
+            .map(|(_, value)| value)
+    }
+}
+
+/// A direct method invocation received on `$iothub/methods/POST/{method name}/?$rid={request id}`
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct MethodRequest<'a> {
+    name: &'a str,
+    request_id: &'a str,
+}
+
+impl<'a> MethodRequest<'a> {
+    /// `None` if `topic` is not a method invocation, or if its request ID is longer than
+    /// `MAX_REQUEST_ID_LEN`
+    pub fn parse(topic: &'a str) -> Option<Self> {
+        let (name, properties) = topic.strip_prefix(TOPIC_METHODS_PREFIX)?.split_once("/?")?;
+
+        let request_id = PropertyBag(properties)
+            .get("$rid")
+            .filter(|request_id| request_id.len() <= MAX_REQUEST_ID_LEN)?;
+
+        Some(Self { name, request_id })
+    }
+
+    pub fn name(&self) -> &'a str {
+        self.name
+    }
+
+    pub fn request_id(&self) -> &'a str {
+        self.request_id
+    }
+
+    pub fn response_topic(&self, status: u16) -> Topic {
+        let mut topic = Topic::new();
+
+        // Cannot fail, as the length of the request ID was checked by `parse`
+        let _ = write!(
+            &mut topic,
+            "$iothub/methods/res/{status}/?$rid={}",
+            self.request_id
+        );
+
+        topic
+    }
+
+    /// Publishes the method result; `payload` should be JSON (possibly empty)
+    pub fn respond<P>(
+        &self,
+        publisher: &mut P,
+        status: u16,
+        payload: &[u8],
+    ) -> Result<MessageId, P::Error>
+    where
+        P: Publish,
+    {
+        let topic = self.response_topic(status);
+
+        publisher.publish(&topic, QoS::AtMostOnce, false, payload)
+    }
+}
+
+struct PercentEncoded<'a>(&'a str);
+
+impl<'a> fmt::Display for PercentEncoded<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for byte in self.0.bytes() {
+            if byte.is_ascii_alphanumeric() || b"-_.~".contains(&byte) {
+                f.write_char(byte as char)?;
+            } else {
+                write!(f, "%{byte:02X}")?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn device_topics() {
+        let device = Device::new("my-hub.azure-devices.net", "sensor-1").unwrap();
+
+        assert_eq!(device.events_topic(), "devices/sensor-1/messages/events/");
+        assert_eq!(
+            device.devicebound_topic_filter(),
+            "devices/sensor-1/messages/devicebound/#"
+        );
+
+        let properties = device
+            .parse_devicebound("devices/sensor-1/messages/devicebound/%24.mid=1&unit=C")
+            .unwrap();
+
+        assert_eq!(properties.get("unit"), Some("C"));
+        assert_eq!(properties.get("$.mid"), None);
+    }
+
+    #[test]
+    fn invalid_device_id() {
+        let too_long = [b'a'; MAX_DEVICE_ID_LEN + 1];
+
+        assert!(Device::new("hub", core::str::from_utf8(&too_long).unwrap()).is_err());
+        assert!(Device::new("hub", "").is_err());
+        assert!(Device::new("hub", "sensor/1").is_err());
+        assert!(Device::new("hub", "sensor#").is_err());
+    }
+
+    #[test]
+    fn method_request() {
+        let request = MethodRequest::parse("$iothub/methods/POST/reboot/?$rid=1f").unwrap();
+
+        assert_eq!(request.name(), "reboot");
+        assert_eq!(request.request_id(), "1f");
+        assert_eq!(
+            request.response_topic(200),
+            "$iothub/methods/res/200/?$rid=1f"
+        );
+
+        let mut topic = Topic::new();
+        write!(
+            &mut topic,
+            "$iothub/methods/POST/reboot/?$rid={:0>1$}",
+            1,
+            MAX_REQUEST_ID_LEN + 1
+        )
+        .unwrap();
+
+        assert_eq!(MethodRequest::parse(&topic), None);
+        assert_eq!(MethodRequest::parse("$iothub/methods/POST/reboot/"), None);
+    }
+}
@@ -0,0 +1,410 @@
+use core::fmt::{self, Debug, Write as _};
+use core::marker::PhantomData;
+use core::time::Duration;
+
+use crate::crypto::{self, EcdsaP256Sign, KeyHandle, Sha256};
+use crate::error::{impl_error, ErrorKind};
+use crate::utils::codec::base64;
+
+/// Large enough for an RS256 token signed with a 4096-bit key
+pub const MAX_TOKEN_LEN: usize = 1024;
+
+pub type Token = heapless::String<MAX_TOKEN_LEN>;
+
+const MAX_SIGNATURE_LEN: usize = 512;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Algorithm {
+    /// ECDSA P-256 with SHA-256; the signature is the raw 64-byte `r || s` pair (not DER)
+    Es256,
+    /// RSASSA-PKCS1-v1_5 with SHA-256
+    Rs256,
+}
+
+impl Algorithm {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Es256 => "ES256",
+            Self::Rs256 => "RS256",
+        }
+    }
+}
+
+/// Signs JWTs; implementable on top of a software crypto library or a secure element which never exposes the key
+pub trait Signer {
+    type Error: Debug;
+
+    fn algorithm(&self) -> Algorithm;
+
+    /// Signs `data` into `signature`, returning the length of the signature; fails if `signature` is too
+    /// short for it
+    fn sign(&self, data: &[u8], signature: &mut [u8]) -> Result<usize, Self::Error>;
+}
+
+impl<S> Signer for &S
+where
+    S: Signer,
+{
+    type Error = S::Error;
+
+    fn algorithm(&self) -> Algorithm {
+        (*self).algorithm()
+    }
+
+    fn sign(&self, data: &[u8], signature: &mut [u8]) -> Result<usize, Self::Error> {
+        (*self).sign(data, signature)
+    }
+}
+
+/// An ES256 signer delegating to the crypto provider, e.g. a secure element holding the device key in `key`
+pub struct EcdsaSigner<C, H> {
+    crypto: C,
+    key: KeyHandle,
+    _hasher: PhantomData<fn() -> H>,
+}
+
+impl<C, H> EcdsaSigner<C, H> {
+    pub const fn new(crypto: C, key: KeyHandle) -> Self {
+        Self {
+            crypto,
+            key,
+            _hasher: PhantomData,
+        }
+    }
+
+    pub fn release(self) -> C {
+        self.crypto
+    }
+}
+
+impl<C, H> Signer for EcdsaSigner<C, H>
+where
+    C: EcdsaP256Sign,
+    H: Sha256 + Default,
+{
+    type Error = SignerError<C::Error>;
+
+    fn algorithm(&self) -> Algorithm {
+        Algorithm::Es256
+    }
+
+    fn sign(&self, data: &[u8], signature: &mut [u8]) -> Result<usize, Self::Error> {
+        let digest = crypto::sha256(H::default(), data);
+        let raw = self
+            .crypto
+            .sign(self.key, &digest)
+            .map_err(SignerError::CryptoError)?;
+
+        signature
+            .get_mut(..raw.len())
+            .ok_or(SignerError::BufferTooSmall)?
+            .copy_from_slice(&raw);
+
+        Ok(raw.len())
+    }
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SignerError<C> {
+    CryptoError(C),
+    /// The signature buffer is shorter than the signature, e.g. than 64 bytes for ES256
+    BufferTooSmall,
+}
+
+impl_error! {
+    SignerError<C: Display> {
+        CryptoError(e) => "Crypto error: {e}"; e.error_kind(),
+        BufferTooSmall => "Signature buffer too small"; ErrorKind::InvalidInput,
+    }
+}
+
+/// The registered claims used for MQTT authentication.
+/// Google Cloud IoT, for example, expects the project ID as the audience.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Claims<'a> {
+    pub issuer: Option<&'a str>,
+    pub subject: Option<&'a str>,
+    pub audience: &'a str,
+    /// Seconds since the UNIX epoch
+    pub issued_at: u64,
+    /// Seconds since the UNIX epoch
+    pub expires_at: u64,
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum JwtError<S> {
+    SignerError(S),
+    TooLong,
+}
+
+impl_error! {
+    JwtError<S: Display> {
+        SignerError(e) => "Signer error: {e}"; e.error_kind(),
+        TooLong => "Token too long"; ErrorKind::InvalidInput,
+    }
+}
+
+/// Produces a signed, compact-serialized JWT
+pub fn encode<S>(signer: &S, claims: &Claims<'_>) -> Result<Token, JwtError<S::Error>>
+where
+    S: Signer,
+{
+    let mut json = heapless::String::<256>::new();
+    let mut token = Token::new();
+
+    write!(
+        &mut json,
+        "{{\"alg\":\"{}\",\"typ\":\"JWT\"}}",
+        signer.algorithm().name()
+    )
+    .map_err(|_| JwtError::TooLong)?;
+
+    base64::encode(json.as_bytes(), base64::URL_SAFE, false, &mut token)
+        .map_err(|_| JwtError::TooLong)?;
+
+    json.clear();
+    write_claims(&mut json, claims).map_err(|_| JwtError::TooLong)?;
+
+    token.push('.').map_err(|_| JwtError::TooLong)?;
+    base64::encode(json.as_bytes(), base64::URL_SAFE, false, &mut token)
+        .map_err(|_| JwtError::TooLong)?;
+
+    let mut signature = [0_u8; MAX_SIGNATURE_LEN];
+    let len = signer
+        .sign(token.as_bytes(), &mut signature)
+        .map_err(JwtError::SignerError)?;
+
+    token.push('.').map_err(|_| JwtError::TooLong)?;
+    base64::encode(&signature[..len], base64::URL_SAFE, false, &mut token)
+        .map_err(|_| JwtError::TooLong)?;
+
+    Ok(token)
+}
+
+fn write_claims(w: &mut impl fmt::Write, claims: &Claims<'_>) -> fmt::Result {
+    write!(w, "{{\"aud\":\"{}\"", JsonEscaped(claims.audience))?;
+
+    if let Some(issuer) = claims.issuer {
+        write!(w, ",\"iss\":\"{}\"", JsonEscaped(issuer))?;
+    }
+
+    if let Some(subject) = claims.subject {
+        write!(w, ",\"sub\":\"{}\"", JsonEscaped(subject))?;
+    }
+
+    write!(
+        w,
+        ",\"iat\":{},\"exp\":{}}}",
+        claims.issued_at, claims.expires_at
+    )
+}
+
+struct JsonEscaped<'a>(&'a str);
+
+impl<'a> fmt::Display for JsonEscaped<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for c in self.0.chars() {
+            match c {
+                '"' => f.write_str("\\\"")?,
+                '\\' => f.write_str("\\\\")?,
+                c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+                c => f.write_char(c)?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Keeps a JWT for the MQTT password field fresh.
+///
+/// Since brokers only check the password when connecting, a new token only takes effect after
+/// a reconnect. The application is therefore expected to call `poll` periodically and, whenever
+/// it returns a new token, to cleanly disconnect and reconnect with it.
+pub struct JwtAuth<'a, S> {
+    signer: S,
+    issuer: Option<&'a str>,
+    subject: Option<&'a str>,
+    audience: &'a str,
+    lifetime: Duration,
+    refresh_margin: Duration,
+    token: Option<(Token, u64)>,
+}
+
+impl<'a, S> JwtAuth<'a, S>
+where
+    S: Signer,
+{
+    /// Tokens are issued for `lifetime` and refreshed `refresh_margin` before they expire
+    pub const fn new(
+        signer: S,
+        audience: &'a str,
+        lifetime: Duration,
+        refresh_margin: Duration,
+    ) -> Self {
+        Self {
+            signer,
+            issuer: None,
+            subject: None,
+            audience,
+            lifetime,
+            refresh_margin,
+            token: None,
+        }
+    }
+
+    pub fn with_issuer(mut self, issuer: &'a str) -> Self {
+        self.issuer = Some(issuer);
+        self
+    }
+
+    pub fn with_subject(mut self, subject: &'a str) -> Self {
+        self.subject = Some(subject);
+        self
+    }
+
+    /// The current token, if any; use it as the MQTT password
+    pub fn token(&self) -> Option<&str> {
+        self.token.as_ref().map(|(token, _)| token.as_str())
+    }
+
+    /// The time (since the UNIX epoch) at which the current token needs to be refreshed
+    pub fn refresh_at(&self) -> Option<Duration> {
+        self.token.as_ref().map(|(_, expires_at)| {
+            Duration::from_secs(*expires_at).saturating_sub(self.refresh_margin)
+        })
+    }
+
+    pub fn needs_refresh(&self, now: Duration) -> bool {
+        self.refresh_at()
+            .map(|refresh_at| now >= refresh_at)
+            .unwrap_or(true)
+    }
+
+    /// Issues a new token if there is none yet or if the current one is about to expire.
+    /// Returns the new token, in which case the MQTT client should reconnect with it.
+    ///
+    /// `now` is the wall-clock time since the UNIX epoch, so the system time must be synchronized first.
+    pub fn poll(&mut self, now: Duration) -> Result<Option<&str>, JwtError<S::Error>> {
+        if !self.needs_refresh(now) {
+            return Ok(None);
+        }
+
+        let issued_at = now.as_secs();
+        let expires_at = (now + self.lifetime).as_secs();
+
+        let token = encode(
+            &self.signer,
+            &Claims {
+                issuer: self.issuer,
+                subject: self.subject,
+                audience: self.audience,
+                issued_at,
+                expires_at,
+            },
+        )?;
+
+        self.token = Some((token, expires_at));
+
+        Ok(self.token())
+    }
+
+    pub fn release(self) -> S {
+        self.signer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::convert::Infallible;
+
+    use crate::crypto::{Digest, EcdsaP256PublicKey, EcdsaP256Signature};
+
+    use super::*;
+
+    #[derive(Default)]
+    struct Length(u8);
+
+    impl Sha256 for Length {
+        fn update(&mut self, data: &[u8]) {
+            self.0 = self.0.wrapping_add(data.len() as u8);
+        }
+
+        fn finish(self) -> Digest {
+            [self.0; 32]
+        }
+    }
+
+    /// Signs with the first byte of the digest
+    struct Crypto;
+
+    impl EcdsaP256Sign for Crypto {
+        type Error = Infallible;
+
+        fn public_key(&self, _key: KeyHandle) -> Result<EcdsaP256PublicKey, Self::Error> {
+            Ok([0; 64])
+        }
+
+        fn sign(
+            &self,
+            _key: KeyHandle,
+            digest: &Digest,
+        ) -> Result<EcdsaP256Signature, Self::Error> {
+            Ok([digest[0]; 64])
+        }
+    }
+
+    #[test]
+    fn ecdsa_signature_buffer() {
+        let signer = EcdsaSigner::<_, Length>::new(Crypto, KeyHandle(0));
+
+        let mut signature = [0_u8; 64];
+        assert_eq!(signer.sign(b"abc", &mut signature).unwrap(), 64);
+        assert_eq!(signature, [3; 64]);
+
+        assert!(matches!(
+            signer.sign(b"abc", &mut signature[..63]),
+            Err(SignerError::BufferTooSmall)
+        ));
+    }
+
+    #[test]
+    fn encode() {
+        let signer = EcdsaSigner::<_, Length>::new(Crypto, KeyHandle(0));
+
+        let token = super::encode(
+            &signer,
+            &Claims {
+                issuer: None,
+                subject: Some("dev\"1"),
+                audience: "project",
+                issued_at: 1,
+                expires_at: 2,
+            },
+        )
+        .unwrap();
+
+        let mut parts = token.split('.');
+
+        let mut header = [0_u8; 64];
+        let len = base64::decode(parts.next().unwrap(), base64::URL_SAFE, &mut header).unwrap();
+        assert_eq!(&header[..len], br#"{"alg":"ES256","typ":"JWT"}"#);
+
+        let mut claims = [0_u8; 128];
+        let len = base64::decode(parts.next().unwrap(), base64::URL_SAFE, &mut claims).unwrap();
+        assert_eq!(
+            &claims[..len],
+            br#"{"aud":"project","sub":"dev\"1","iat":1,"exp":2}"#
+        );
+
+        let mut signature = [0_u8; 64];
+        let len = base64::decode(parts.next().unwrap(), base64::URL_SAFE, &mut signature).unwrap();
+        assert_eq!(len, 64);
+        assert_eq!(parts.next(), None);
+    }
+}
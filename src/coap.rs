@@ -0,0 +1,603 @@
+//! A minimal CoAP (RFC 7252) message codec, and the transports CoAP runs over.
+
+use core::fmt;
+use core::time::Duration;
+
+use crate::io::Io;
+use crate::ipv4::SocketAddrV4;
+use crate::net::dtls::DtlsConnection;
+use crate::net::udp::UdpSocket;
+use crate::sys_time::{Instant, SystemTime};
+
+pub const PORT: u16 = 5683;
+pub const PORT_DTLS: u16 = 5684;
+
+/// The initial retransmission timeout of confirmable messages, doubled on each retransmission
+pub const ACK_TIMEOUT: Duration = Duration::from_secs(2);
+pub const MAX_RETRANSMIT: u8 = 4;
+
+const VERSION: u8 = 1;
+const PAYLOAD_MARKER: u8 = 0xff;
+
+pub mod option {
+    pub const OBSERVE: u16 = 6;
+    pub const LOCATION_PATH: u16 = 8;
+    pub const URI_PATH: u16 = 11;
+    pub const CONTENT_FORMAT: u16 = 12;
+    pub const URI_QUERY: u16 = 15;
+    pub const ACCEPT: u16 = 17;
+}
+
+pub mod content_format {
+    pub const TEXT_PLAIN: u16 = 0;
+    pub const LINK_FORMAT: u16 = 40;
+    pub const OCTET_STREAM: u16 = 42;
+    pub const JSON: u16 = 50;
+    pub const CBOR: u16 = 60;
+    pub const SENML_JSON: u16 = 110;
+    pub const LWM2M_TLV: u16 = 11542;
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum MessageType {
+    Confirmable,
+    NonConfirmable,
+    Acknowledgement,
+    Reset,
+}
+
+/// A request method or a response code, e.g. `2.05`
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Code(pub u8);
+
+impl Code {
+    pub const EMPTY: Self = Self(0x00);
+
+    pub const GET: Self = Self(0x01);
+    pub const POST: Self = Self(0x02);
+    pub const PUT: Self = Self(0x03);
+    pub const DELETE: Self = Self(0x04);
+
+    pub const CREATED: Self = Self::new(2, 1);
+    pub const DELETED: Self = Self::new(2, 2);
+    pub const VALID: Self = Self::new(2, 3);
+    pub const CHANGED: Self = Self::new(2, 4);
+    pub const CONTENT: Self = Self::new(2, 5);
+
+    pub const BAD_REQUEST: Self = Self::new(4, 0);
+    pub const UNAUTHORIZED: Self = Self::new(4, 1);
+    pub const NOT_FOUND: Self = Self::new(4, 4);
+    pub const METHOD_NOT_ALLOWED: Self = Self::new(4, 5);
+    pub const NOT_ACCEPTABLE: Self = Self::new(4, 6);
+    pub const REQUEST_ENTITY_TOO_LARGE: Self = Self::new(4, 13);
+    pub const UNSUPPORTED_CONTENT_FORMAT: Self = Self::new(4, 15);
+
+    pub const INTERNAL_SERVER_ERROR: Self = Self::new(5, 0);
+    pub const SERVICE_UNAVAILABLE: Self = Self::new(5, 3);
+
+    pub const fn new(class: u8, detail: u8) -> Self {
+        Self((class << 5) | (detail & 0x1f))
+    }
+
+    pub const fn class(&self) -> u8 {
+        self.0 >> 5
+    }
+
+    pub const fn detail(&self) -> u8 {
+        self.0 & 0x1f
+    }
+
+    pub const fn is_request(&self) -> bool {
+        self.class() == 0 && self.0 != 0
+    }
+
+    pub const fn is_success(&self) -> bool {
+        self.class() == 2
+    }
+}
+
+impl fmt::Display for Code {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{:02}", self.class(), self.detail())
+    }
+}
+
+/// A parsed CoAP message, borrowing from the datagram it was parsed from
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Message<'a> {
+    pub message_type: MessageType,
+    pub code: Code,
+    pub message_id: u16,
+    pub token: &'a [u8],
+    options: &'a [u8],
+    pub payload: &'a [u8],
+}
+
+impl<'a> Message<'a> {
+    pub fn parse(data: &'a [u8]) -> Option<Self> {
+        let header = data.get(..4)?;
+
+        if header[0] >> 6 != VERSION {
+            return None;
+        }
+
+        let message_type = match (header[0] >> 4) & 0x03 {
+            0 => MessageType::Confirmable,
+            1 => MessageType::NonConfirmable,
+            2 => MessageType::Acknowledgement,
+            _ => MessageType::Reset,
+        };
+
+        let token_len = (header[0] & 0x0f) as usize;
+        if token_len > 8 {
+            return None;
+        }
+
+        let token = data.get(4..4 + token_len)?;
+        let rest = &data[4 + token_len..];
+
+        // Walk the options once, to validate them and to find the payload
+        let mut options = Options::new(rest);
+        while options.next_option().ok()?.is_some() {}
+
+        let options_len = rest.len() - options.data.len();
+
+        let payload = match options.data.split_first() {
+            Some((&PAYLOAD_MARKER, payload)) if !payload.is_empty() => payload,
+            Some(_) => return None,
+            None => &[],
+        };
+
+        Some(Self {
+            message_type,
+            code: Code(header[1]),
+            message_id: u16::from_be_bytes([header[2], header[3]]),
+            token,
+            options: &rest[..options_len],
+            payload,
+        })
+    }
+
+    /// The `(number, value)` pairs of all options
+    pub fn options(&self) -> impl Iterator<Item = (u16, &'a [u8])> {
+        let mut options = Options::new(self.options);
+
+        // The options were validated by `parse`
+        core::iter::from_fn(move || options.next_option().ok().flatten())
+    }
+
+    pub fn option(&self, number: u16) -> Option<&'a [u8]> {
+        self.options()
+            .find(|(option, _)| *option == number)
+            .map(|(_, value)| value)
+    }
+
+    pub fn option_uint(&self, number: u16) -> Option<u32> {
+        self.option(number).map(decode_uint)
+    }
+
+    /// The values of a repeatable string option, e.g. the segments of `Uri-Path`
+    pub fn option_strs(&self, number: u16) -> impl Iterator<Item = &'a str> {
+        self.options()
+            .filter(move |(option, _)| *option == number)
+            .filter_map(|(_, value)| core::str::from_utf8(value).ok())
+    }
+}
+
+struct Options<'a> {
+    data: &'a [u8],
+    /// Option numbers are delta-encoded
+    number: u16,
+}
+
+impl<'a> Options<'a> {
+    const fn new(data: &'a [u8]) -> Self {
+        Self { data, number: 0 }
+    }
+
+    fn next_option(&mut self) -> Result<Option<(u16, &'a [u8])>, ()> {
+        let (first, rest) = match self.data.split_first() {
+            Some((&PAYLOAD_MARKER, _)) | None => return Ok(None),
+            Some((first, rest)) => (*first, rest),
+        };
+
+        let (delta, rest) = Self::extended(first >> 4, rest)?;
+        let (len, rest) = Self::extended(first & 0x0f, rest)?;
+
+        let len = len as usize;
+        if len > rest.len() {
+            return Err(());
+        }
+
+        self.number = self.number.checked_add(delta).ok_or(())?;
+        self.data = &rest[len..];
+
+        Ok(Some((self.number, &rest[..len])))
+    }
+
+    fn extended(nibble: u8, data: &[u8]) -> Result<(u16, &[u8]), ()> {
+        match nibble {
+            13 => {
+                let (byte, rest) = data.split_first().ok_or(())?;
+
+                Ok((*byte as u16 + 13, rest))
+            }
+            14 => {
+                let bytes = data.get(..2).ok_or(())?;
+                let value = u16::from_be_bytes([bytes[0], bytes[1]])
+                    .checked_add(269)
+                    .ok_or(())?;
+
+                Ok((value, &data[2..]))
+            }
+            15 => Err(()),
+            nibble => Ok((nibble as u16, data)),
+        }
+    }
+}
+
+/// Writes a CoAP message into a buffer; options must be added in ascending number order
+pub struct MessageWriter<'a> {
+    buf: &'a mut [u8],
+    len: usize,
+    number: u16,
+}
+
+impl<'a> MessageWriter<'a> {
+    pub fn new(
+        buf: &'a mut [u8],
+        message_type: MessageType,
+        code: Code,
+        message_id: u16,
+        token: &[u8],
+    ) -> Result<Self, &'static str> {
+        if token.len() > 8 {
+            return Err("Token too long");
+        }
+
+        let len = 4 + token.len();
+        if buf.len() < len {
+            return Err("Message too long");
+        }
+
+        let message_type = match message_type {
+            MessageType::Confirmable => 0,
+            MessageType::NonConfirmable => 1,
+            MessageType::Acknowledgement => 2,
+            MessageType::Reset => 3,
+        };
+
+        buf[0] = (VERSION << 6) | (message_type << 4) | token.len() as u8;
+        buf[1] = code.0;
+        buf[2..4].copy_from_slice(&message_id.to_be_bytes());
+        buf[4..len].copy_from_slice(token);
+
+        Ok(Self {
+            buf,
+            len,
+            number: 0,
+        })
+    }
+
+    pub fn option(&mut self, number: u16, value: &[u8]) -> Result<&mut Self, &'static str> {
+        if number < self.number {
+            return Err("Options must be written in ascending order");
+        }
+
+        let mut header = [0_u8; 5];
+        let mut header_len = 1;
+
+        let delta = self.encode_extended(number - self.number, &mut header, &mut header_len);
+        let len = self.encode_extended(value.len() as u16, &mut header, &mut header_len);
+
+        if value.len() > u16::MAX as usize || self.buf.len() < self.len + header_len + value.len() {
+            return Err("Message too long");
+        }
+
+        header[0] = (delta << 4) | len;
+
+        self.buf[self.len..self.len + header_len].copy_from_slice(&header[..header_len]);
+        self.len += header_len;
+
+        self.buf[self.len..self.len + value.len()].copy_from_slice(value);
+        self.len += value.len();
+
+        self.number = number;
+
+        Ok(self)
+    }
+
+    /// Writes an option with an integer value, using as few bytes as possible
+    pub fn option_uint(&mut self, number: u16, value: u32) -> Result<&mut Self, &'static str> {
+        let bytes = value.to_be_bytes();
+        let skip = (value.leading_zeros() / 8) as usize;
+
+        self.option(number, &bytes[skip..])
+    }
+
+    pub fn option_str(&mut self, number: u16, value: &str) -> Result<&mut Self, &'static str> {
+        self.option(number, value.as_bytes())
+    }
+
+    pub fn payload(&mut self, payload: &[u8]) -> Result<&mut Self, &'static str> {
+        if payload.is_empty() {
+            return Ok(self);
+        }
+
+        if self.buf.len() < self.len + 1 + payload.len() {
+            return Err("Message too long");
+        }
+
+        self.buf[self.len] = PAYLOAD_MARKER;
+        self.buf[self.len + 1..self.len + 1 + payload.len()].copy_from_slice(payload);
+        self.len += 1 + payload.len();
+
+        Ok(self)
+    }
+
+    /// The space left for the payload, accounting for the payload marker
+    pub fn payload_capacity(&self) -> usize {
+        self.buf.len().saturating_sub(self.len + 1)
+    }
+
+    pub fn finish(self) -> &'a [u8] {
+        &self.buf[..self.len]
+    }
+
+    fn encode_extended(&self, value: u16, header: &mut [u8; 5], header_len: &mut usize) -> u8 {
+        if value < 13 {
+            value as u8
+        } else if value < 269 {
+            header[*header_len] = (value - 13) as u8;
+            *header_len += 1;
+
+            13
+        } else {
+            header[*header_len..*header_len + 2].copy_from_slice(&(value - 269).to_be_bytes());
+            *header_len += 2;
+
+            14
+        }
+    }
+}
+
+pub fn decode_uint(value: &[u8]) -> u32 {
+    value
+        .iter()
+        .take(4)
+        .fold(0, |uint, byte| (uint << 8) | *byte as u32)
+}
+
+/// Carries CoAP messages to and from a single peer
+pub trait Transport: Io {
+    fn send(&mut self, message: &[u8]) -> Result<(), Self::Error>;
+
+    /// Waits up to `timeout` (or forever, if `None`) for a message.
+    /// Returns `None` if no message was received in the meantime.
+    fn receive(
+        &mut self,
+        buf: &mut [u8],
+        timeout: Option<Duration>,
+    ) -> Result<Option<usize>, Self::Error>;
+}
+
+impl<C> Transport for C
+where
+    C: DtlsConnection,
+{
+    fn send(&mut self, message: &[u8]) -> Result<(), Self::Error> {
+        DtlsConnection::send(self, message).map(|_| ())
+    }
+
+    fn receive(
+        &mut self,
+        buf: &mut [u8],
+        timeout: Option<Duration>,
+    ) -> Result<Option<usize>, Self::Error> {
+        DtlsConnection::receive(self, buf, timeout)
+    }
+}
+
+/// Plain (unsecured) CoAP over a UDP socket; datagrams from other peers are dropped
+pub struct UdpTransport<S, T> {
+    socket: S,
+    peer: SocketAddrV4,
+    clock: T,
+}
+
+impl<S, T> UdpTransport<S, T>
+where
+    S: UdpSocket,
+    T: SystemTime,
+{
+    /// `clock` bounds how long `receive` waits while datagrams from other peers are dropped
+    pub const fn new(socket: S, peer: SocketAddrV4, clock: T) -> Self {
+        Self {
+            socket,
+            peer,
+            clock,
+        }
+    }
+
+    pub fn release(self) -> (S, T) {
+        (self.socket, self.clock)
+    }
+}
+
+impl<S, T> Io for UdpTransport<S, T>
+where
+    S: Io,
+{
+    type Error = S::Error;
+}
+
+impl<S, T> Transport for UdpTransport<S, T>
+where
+    S: UdpSocket,
+    T: SystemTime,
+{
+    fn send(&mut self, message: &[u8]) -> Result<(), Self::Error> {
+        self.socket.send_to(message, self.peer).map(|_| ())
+    }
+
+    fn receive(
+        &mut self,
+        buf: &mut [u8],
+        timeout: Option<Duration>,
+    ) -> Result<Option<usize>, Self::Error> {
+        let deadline = timeout.map(|timeout| Instant::now(&self.clock) + timeout);
+
+        loop {
+            let remaining = match deadline {
+                Some(deadline) => {
+                    match deadline.checked_duration_since(Instant::now(&self.clock)) {
+                        Some(remaining) if !remaining.is_zero() => Some(remaining),
+                        _ => return Ok(None),
+                    }
+                }
+                None => None,
+            };
+
+            match self.socket.receive_from(buf, remaining)? {
+                Some((len, addr)) if addr == self.peer => return Ok(Some(len)),
+                Some(_) => continue,
+                None => return Ok(None),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::Cell;
+    use core::convert::Infallible;
+
+    use crate::ipv4::Ipv4Addr;
+
+    use super::*;
+
+    #[test]
+    fn message() {
+        let mut buf = [0_u8; 64];
+
+        let mut writer = MessageWriter::new(
+            &mut buf,
+            MessageType::Confirmable,
+            Code::GET,
+            0x1234,
+            &[7, 8],
+        )
+        .unwrap();
+        writer
+            .option_str(option::URI_PATH, "sensors")
+            .unwrap()
+            .option_str(option::URI_PATH, "temperature")
+            .unwrap()
+            .option_uint(option::ACCEPT, 50)
+            .unwrap()
+            .option(300, &[1; 20])
+            .unwrap()
+            .payload(b"{}")
+            .unwrap();
+
+        let message = Message::parse(writer.finish()).unwrap();
+
+        assert_eq!(message.message_type, MessageType::Confirmable);
+        assert_eq!(message.code, Code::GET);
+        assert_eq!(message.message_id, 0x1234);
+        assert_eq!(message.token, &[7, 8]);
+        assert!(message
+            .option_strs(option::URI_PATH)
+            .eq(["sensors", "temperature"].iter().copied()));
+        assert_eq!(message.option_uint(option::ACCEPT), Some(50));
+        assert_eq!(message.option(300), Some(&[1; 20][..]));
+        assert_eq!(message.payload, b"{}");
+    }
+
+    #[test]
+    fn invalid_message() {
+        assert_eq!(Message::parse(&[0x40, 0x01, 0x00]), None);
+        // Version 2
+        assert_eq!(Message::parse(&[0x80, 0x01, 0x00, 0x01]), None);
+        // A token longer than the datagram
+        assert_eq!(Message::parse(&[0x42, 0x01, 0x00, 0x01, 0xff]), None);
+    }
+
+    /// Receives datagrams from another peer, each one second after the previous one
+    struct Stranger<'a>(&'a Cell<u64>);
+
+    impl<'a> Io for Stranger<'a> {
+        type Error = Infallible;
+    }
+
+    impl<'a> UdpSocket for Stranger<'a> {
+        fn local_addr(&self) -> Result<SocketAddrV4, Self::Error> {
+            Ok(SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, PORT))
+        }
+
+        fn send_to(&mut self, buf: &[u8], _addr: SocketAddrV4) -> Result<usize, Self::Error> {
+            Ok(buf.len())
+        }
+
+        fn receive_from(
+            &mut self,
+            _buf: &mut [u8],
+            timeout: Option<Duration>,
+        ) -> Result<Option<(usize, SocketAddrV4)>, Self::Error> {
+            if timeout.map_or(false, |timeout| timeout < Duration::from_secs(1)) {
+                return Ok(None);
+            }
+
+            self.0.set(self.0.get() + 1);
+
+            Ok(Some((
+                4,
+                SocketAddrV4::new(Ipv4Addr::new(10, 0, 0, 2), PORT),
+            )))
+        }
+
+        fn set_broadcast(&mut self, _enabled: bool) -> Result<(), Self::Error> {
+            Ok(())
+        }
+
+        fn join_multicast(&mut self, _group: Ipv4Addr) -> Result<(), Self::Error> {
+            Ok(())
+        }
+
+        fn leave_multicast(&mut self, _group: Ipv4Addr) -> Result<(), Self::Error> {
+            Ok(())
+        }
+    }
+
+    struct Clock<'a>(&'a Cell<u64>);
+
+    impl<'a> SystemTime for Clock<'a> {
+        fn now(&self) -> Duration {
+            Duration::from_secs(self.0.get())
+        }
+    }
+
+    #[test]
+    fn receive_deadline() {
+        let secs = Cell::new(0);
+
+        let mut transport = UdpTransport::new(
+            Stranger(&secs),
+            SocketAddrV4::new(Ipv4Addr::new(10, 0, 0, 1), PORT),
+            Clock(&secs),
+        );
+
+        let mut buf = [0_u8; 16];
+
+        assert_eq!(
+            transport.receive(&mut buf, Some(Duration::from_secs(3))),
+            Ok(None)
+        );
+        assert_eq!(secs.get(), 3);
+    }
+}
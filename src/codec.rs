@@ -0,0 +1,224 @@
+//! Compact binary encodings of `serde` values for telemetry payloads: CBOR (RFC 8949) with the `codec_cbor`
+//! feature, through `minicbor-serde`, and MessagePack with the `codec_msgpack` feature, through
+//! `embedded-msgpack`.
+//!
+//! Both encode into a caller-provided buffer without allocating, and decode borrowing strings and byte
+//! strings from the input. Structs are maps keyed by field name, as with JSON.
+//!
+//! Other serializations, e.g. protobuf, plug in by implementing `BodyCodec`.
+
+use core::fmt::Debug;
+
+#[cfg(feature = "use_serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::error::{impl_error, ErrorKind};
+
+pub mod body;
+#[cfg(feature = "codec_cbor")]
+pub mod cbor;
+pub mod framing;
+#[cfg(feature = "codec_msgpack")]
+pub mod msgpack;
+
+/// The media types of the payloads, for the HTTP `Content-Type` and `Accept` headers,
+/// MQTT 5 content types and WebSocket subprotocols
+pub mod content_type {
+    pub const JSON: &str = "application/json";
+    pub const CBOR: &str = "application/cbor";
+    pub const MSGPACK: &str = "application/msgpack";
+    pub const PROTOBUF: &str = "application/x-protobuf";
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub enum Format {
+    Json,
+    Cbor,
+    MsgPack,
+}
+
+impl Format {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Self::Json => content_type::JSON,
+            Self::Cbor => content_type::CBOR,
+            Self::MsgPack => content_type::MSGPACK,
+        }
+    }
+
+    /// The CoAP content format, where one is registered
+    pub fn coap_content_format(&self) -> Option<u16> {
+        match self {
+            Self::Json => Some(crate::coap::content_format::JSON),
+            Self::Cbor => Some(crate::coap::content_format::CBOR),
+            Self::MsgPack => None,
+        }
+    }
+
+    /// Parses a `Content-Type` header value, ignoring its parameters
+    pub fn from_content_type(content_type: &str) -> Option<Self> {
+        let media_type = content_type.split(';').next().unwrap_or("").trim();
+
+        [
+            (content_type::JSON, Self::Json),
+            (content_type::CBOR, Self::Cbor),
+            (content_type::MSGPACK, Self::MsgPack),
+            ("application/x-msgpack", Self::MsgPack),
+            ("application/vnd.msgpack", Self::MsgPack),
+        ]
+        .iter()
+        .find(|(name, _)| media_type.eq_ignore_ascii_case(name))
+        .map(|(_, format)| *format)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum CodecError {
+    BufferTooSmall,
+    UnexpectedEof,
+    TrailingData,
+    InvalidData(&'static str),
+    /// Raised by a `Serialize` or `Deserialize` implementation
+    Custom,
+}
+
+impl_error! {
+    CodecError {
+        BufferTooSmall => "Buffer too small"; ErrorKind::InvalidInput,
+        UnexpectedEof => "Unexpected end of data"; ErrorKind::InvalidInput,
+        TrailingData => "Trailing data"; ErrorKind::InvalidInput,
+        InvalidData(e) => "Invalid data: {e}"; ErrorKind::InvalidInput,
+        Custom => "Serialization error"; ErrorKind::InvalidInput,
+    }
+}
+
+/// Encodes and decodes message bodies of type `T`, so that the HTTP, MQTT and WebSocket layers can carry
+/// messages of any serialization, e.g. protobuf messages generated by prost or micropb, without this crate
+/// depending on a particular implementation.
+pub trait BodyCodec<T> {
+    type Error: Debug;
+
+    /// The media type of the encoded bodies, e.g. `content_type::PROTOBUF`
+    fn content_type(&self) -> &'static str;
+
+    /// Returns the length of the encoded value
+    fn encode(&self, value: &T, buf: &mut [u8]) -> Result<usize, Self::Error>;
+
+    fn decode(&self, data: &[u8]) -> Result<T, Self::Error>;
+}
+
+impl<C, T> BodyCodec<T> for &C
+where
+    C: BodyCodec<T>,
+{
+    type Error = C::Error;
+
+    fn content_type(&self) -> &'static str {
+        (**self).content_type()
+    }
+
+    fn encode(&self, value: &T, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        (**self).encode(value, buf)
+    }
+
+    fn decode(&self, data: &[u8]) -> Result<T, Self::Error> {
+        (**self).decode(data)
+    }
+}
+
+#[cfg(feature = "codec_cbor")]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct CborCodec;
+
+#[cfg(feature = "codec_cbor")]
+impl<T> BodyCodec<T> for CborCodec
+where
+    T: serde::Serialize + serde::de::DeserializeOwned,
+{
+    type Error = CodecError;
+
+    fn content_type(&self) -> &'static str {
+        content_type::CBOR
+    }
+
+    fn encode(&self, value: &T, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        cbor::to_slice(value, buf)
+    }
+
+    fn decode(&self, data: &[u8]) -> Result<T, Self::Error> {
+        cbor::from_slice(data)
+    }
+}
+
+/// For `StorageImpl`
+#[cfg(feature = "codec_cbor")]
+impl crate::storage::SerDe for CborCodec {
+    type Error = CodecError;
+
+    fn serialize<'a, T>(&self, slice: &'a mut [u8], value: &T) -> Result<&'a [u8], Self::Error>
+    where
+        T: serde::Serialize,
+    {
+        let len = cbor::to_slice(value, slice)?;
+
+        Ok(&slice[..len])
+    }
+
+    fn deserialize<T>(&self, slice: &[u8]) -> Result<T, Self::Error>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        cbor::from_slice(slice)
+    }
+}
+
+#[cfg(feature = "codec_msgpack")]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct MsgPackCodec;
+
+#[cfg(feature = "codec_msgpack")]
+impl<T> BodyCodec<T> for MsgPackCodec
+where
+    T: serde::Serialize + serde::de::DeserializeOwned,
+{
+    type Error = CodecError;
+
+    fn content_type(&self) -> &'static str {
+        content_type::MSGPACK
+    }
+
+    fn encode(&self, value: &T, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        msgpack::to_slice(value, buf)
+    }
+
+    fn decode(&self, data: &[u8]) -> Result<T, Self::Error> {
+        msgpack::from_slice(data)
+    }
+}
+
+/// For `StorageImpl`
+#[cfg(feature = "codec_msgpack")]
+impl crate::storage::SerDe for MsgPackCodec {
+    type Error = CodecError;
+
+    fn serialize<'a, T>(&self, slice: &'a mut [u8], value: &T) -> Result<&'a [u8], Self::Error>
+    where
+        T: serde::Serialize,
+    {
+        let len = msgpack::to_slice(value, slice)?;
+
+        Ok(&slice[..len])
+    }
+
+    fn deserialize<T>(&self, slice: &[u8]) -> Result<T, Self::Error>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        msgpack::from_slice(slice)
+    }
+}
@@ -0,0 +1,150 @@
+//! Sending and receiving `BodyCodec` message bodies through the IO and MQTT abstractions, e.g. the request
+//! and response bodies of the HTTP client and server, or MQTT payloads.
+
+use core::fmt::Debug;
+
+use crate::error::{self, impl_error};
+use crate::io::{self, ErrorKind, Read, Write};
+use crate::mqtt::client::{MessageId, Publish, QoS};
+
+use super::framing::{self, FrameError, LengthPrefix};
+use super::BodyCodec;
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum BodyError<C, E> {
+    CodecError(C),
+    IoError(E),
+    /// The body does not fit into the buffer
+    TooLarge,
+    InvalidFrame(&'static str),
+}
+
+impl_error! {
+    BodyError<C: Debug, E: Display> {
+        CodecError(e) => "Codec error: {e:?}"; e.error_kind(),
+        IoError(e) => "IO error: {e}"; e.error_kind(),
+        TooLarge => "Body too large"; error::ErrorKind::InvalidInput,
+        InvalidFrame(e) => "Invalid frame: {e}"; error::ErrorKind::InvalidInput,
+    }
+}
+
+impl<C, E> io::Error for BodyError<C, E>
+where
+    C: Debug,
+    E: io::Error,
+{
+    fn kind(&self) -> ErrorKind {
+        match self {
+            Self::IoError(e) => e.kind(),
+            _ => ErrorKind::Other,
+        }
+    }
+}
+
+impl<C, E> From<FrameError<E>> for BodyError<C, E> {
+    fn from(e: FrameError<E>) -> Self {
+        match e {
+            FrameError::IoError(e) => Self::IoError(e),
+            FrameError::TooLarge => Self::TooLarge,
+            FrameError::InvalidData(e) => Self::InvalidFrame(e),
+        }
+    }
+}
+
+/// Encodes `value` into `buf` and writes it; the `Content-Type` of the body is `codec.content_type()`.
+///
+/// Returns the length of the body.
+pub fn write<T, C, W>(
+    mut writer: W,
+    codec: C,
+    value: &T,
+    buf: &mut [u8],
+) -> Result<usize, BodyError<C::Error, W::Error>>
+where
+    C: BodyCodec<T>,
+    W: Write,
+{
+    let len = codec.encode(value, buf).map_err(BodyError::CodecError)?;
+
+    writer.write_all(&buf[..len]).map_err(BodyError::IoError)?;
+
+    Ok(len)
+}
+
+/// Reads the body until the end of the stream into `buf`, and decodes it
+pub fn read<T, C, R>(
+    mut reader: R,
+    codec: C,
+    buf: &mut [u8],
+) -> Result<T, BodyError<C::Error, R::Error>>
+where
+    C: BodyCodec<T>,
+    R: Read,
+{
+    let len = crate::utils::io::try_read_full(&mut reader, buf)
+        .map_err(|(e, _)| BodyError::IoError(e))?;
+
+    if len == buf.len() && reader.read(&mut [0]).map_err(BodyError::IoError)? > 0 {
+        return Err(BodyError::TooLarge);
+    }
+
+    codec.decode(&buf[..len]).map_err(BodyError::CodecError)
+}
+
+/// Writes `value` as a length-prefixed frame, so that several messages can follow each other on the same stream
+pub fn write_framed<T, C, W>(
+    writer: W,
+    codec: C,
+    prefix: LengthPrefix,
+    value: &T,
+    buf: &mut [u8],
+) -> Result<(), BodyError<C::Error, W::Error>>
+where
+    C: BodyCodec<T>,
+    W: Write,
+{
+    let len = codec.encode(value, buf).map_err(BodyError::CodecError)?;
+
+    Ok(framing::write_frame(writer, prefix, &buf[..len])?)
+}
+
+/// Reads and decodes the next length-prefixed frame, or returns `None` at the end of the stream
+pub fn read_framed<T, C, R>(
+    reader: R,
+    codec: C,
+    prefix: LengthPrefix,
+    buf: &mut [u8],
+) -> Result<Option<T>, BodyError<C::Error, R::Error>>
+where
+    C: BodyCodec<T>,
+    R: Read,
+{
+    match framing::read_frame(reader, prefix, buf)? {
+        Some(frame) => codec.decode(frame).map(Some).map_err(BodyError::CodecError),
+        None => Ok(None),
+    }
+}
+
+/// Encodes `value` into `buf` and publishes it.
+///
+/// Received messages are decoded with `codec.decode(message.data())`, once complete.
+pub fn publish<T, C, P>(
+    mut publisher: P,
+    codec: C,
+    topic: &str,
+    qos: QoS,
+    retain: bool,
+    value: &T,
+    buf: &mut [u8],
+) -> Result<MessageId, BodyError<C::Error, P::Error>>
+where
+    C: BodyCodec<T>,
+    P: Publish,
+{
+    let len = codec.encode(value, buf).map_err(BodyError::CodecError)?;
+
+    publisher
+        .publish(topic, qos, retain, &buf[..len])
+        .map_err(BodyError::IoError)
+}
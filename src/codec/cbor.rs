@@ -0,0 +1,116 @@
+//! CBOR (RFC 8949), encoded and decoded with `minicbor-serde`.
+//!
+//! Sequences and maps of unknown length are encoded with indefinite lengths.
+
+use minicbor::encode::write::Cursor;
+use minicbor_serde::{Deserializer, Serializer};
+use serde::{Deserialize, Serialize};
+
+use super::CodecError;
+
+pub use super::content_type::CBOR as CONTENT_TYPE;
+
+/// Returns the length of the encoded value
+pub fn to_slice<T>(value: &T, buf: &mut [u8]) -> Result<usize, CodecError>
+where
+    T: Serialize + ?Sized,
+{
+    let mut serializer = Serializer::new(Cursor::new(buf));
+    serializer.serialize_unit_as_null(true);
+
+    value.serialize(&mut serializer).map_err(|e| {
+        if e.as_write().is_some() {
+            CodecError::BufferTooSmall
+        } else {
+            CodecError::Custom
+        }
+    })?;
+
+    Ok(serializer.into_encoder().into_writer().position())
+}
+
+/// Fails with `CodecError::TrailingData` if `input` holds more than one value
+pub fn from_slice<'de, T>(input: &'de [u8]) -> Result<T, CodecError>
+where
+    T: Deserialize<'de>,
+{
+    let mut deserializer = Deserializer::new(input);
+
+    let value =
+        T::deserialize(&mut deserializer).map_err(|_| CodecError::InvalidData("Invalid CBOR"))?;
+
+    if deserializer.decoder().position() < input.len() {
+        Err(CodecError::TrailingData)
+    } else {
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use super::*;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum Unit {
+        Celsius,
+        Fahrenheit,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Reading<'a> {
+        sensor: &'a str,
+        value: f32,
+        unit: Unit,
+        offset: i64,
+        samples: [u16; 3],
+        label: Option<&'a str>,
+    }
+
+    const READING: Reading<'static> = Reading {
+        sensor: "t1",
+        value: 21.5,
+        unit: Unit::Celsius,
+        offset: -1000,
+        samples: [1, 300, 65_000],
+        label: None,
+    };
+
+    #[test]
+    fn round_trip() {
+        let mut buf = [0_u8; 128];
+
+        let len = to_slice(&READING, &mut buf).unwrap();
+
+        assert_eq!(from_slice::<Reading>(&buf[..len]), Ok(READING));
+    }
+
+    #[test]
+    fn encoding() {
+        let mut buf = [0_u8; 16];
+
+        let len = to_slice(&(500_u16, -2_i8, "a", true, ()), &mut buf).unwrap();
+
+        assert_eq!(
+            &buf[..len],
+            &[0x85, 0x19, 0x01, 0xf4, 0x21, 0x61, b'a', 0xf5, 0xf6]
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let mut buf = [0_u8; 8];
+
+        assert_eq!(
+            to_slice(&READING, &mut buf),
+            Err(CodecError::BufferTooSmall)
+        );
+        assert_eq!(
+            from_slice::<u8>(&[0x01, 0x02]),
+            Err(CodecError::TrailingData)
+        );
+        assert!(from_slice::<u8>(&[0x19]).is_err());
+        assert!(from_slice::<&str>(&[0x01]).is_err());
+    }
+}
@@ -0,0 +1,193 @@
+//! Length-prefixed framing of messages over byte streams, e.g. of protobuf messages in their
+//! "delimited" form over TCP, or of records appended to a file.
+
+use core::fmt::Debug;
+
+#[cfg(feature = "use_serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::error::{self, impl_error};
+use crate::io::{self, ErrorKind, Read, Write};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub enum LengthPrefix {
+    /// The base 128 varint used by protobuf's `writeDelimitedTo` and prost's `encode_length_delimited`
+    Varint,
+    /// Big endian
+    U16,
+    /// Big endian
+    U32,
+}
+
+impl LengthPrefix {
+    pub const MAX_LEN: usize = 10;
+
+    /// Returns the length of the prefix, or `None` if it does not fit into `buf` or `len` is out of range
+    pub fn encode(&self, len: usize, buf: &mut [u8]) -> Option<usize> {
+        let len = len as u64;
+
+        match self {
+            Self::Varint => {
+                let mut value = len;
+                let mut offset = 0;
+
+                loop {
+                    let byte = buf.get_mut(offset)?;
+
+                    *byte = (value & 0x7f) as u8;
+                    value >>= 7;
+                    offset += 1;
+
+                    if value == 0 {
+                        return Some(offset);
+                    }
+
+                    *byte |= 0x80;
+                }
+            }
+            Self::U16 if len <= u16::MAX as _ => put(buf, &(len as u16).to_be_bytes()),
+            Self::U32 if len <= u32::MAX as _ => put(buf, &(len as u32).to_be_bytes()),
+            _ => None,
+        }
+    }
+
+    /// Returns the length of the frame and the length of the prefix, or `None` if `data` does not hold
+    /// the complete prefix yet
+    pub fn decode(&self, data: &[u8]) -> Result<Option<(usize, usize)>, &'static str> {
+        match self {
+            Self::Varint => {
+                let mut len = 0_u64;
+
+                for (index, byte) in data.iter().enumerate().take(Self::MAX_LEN) {
+                    len |= ((byte & 0x7f) as u64) << (7 * index);
+
+                    if byte & 0x80 == 0 {
+                        return Ok(Some((len as _, index + 1)));
+                    }
+                }
+
+                if data.len() >= Self::MAX_LEN {
+                    Err("Invalid varint length prefix")
+                } else {
+                    Ok(None)
+                }
+            }
+            Self::U16 => Ok(data
+                .get(..2)
+                .map(|prefix| (u16::from_be_bytes([prefix[0], prefix[1]]) as _, 2))),
+            Self::U32 => Ok(data.get(..4).map(|prefix| {
+                (
+                    u32::from_be_bytes([prefix[0], prefix[1], prefix[2], prefix[3]]) as _,
+                    4,
+                )
+            })),
+        }
+    }
+}
+
+fn put(buf: &mut [u8], data: &[u8]) -> Option<usize> {
+    buf.get_mut(..data.len())?.copy_from_slice(data);
+
+    Some(data.len())
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum FrameError<E> {
+    IoError(E),
+    /// The frame does not fit into the buffer
+    TooLarge,
+    InvalidData(&'static str),
+}
+
+impl_error! {
+    FrameError<E: Display> {
+        IoError(e) => "IO error: {e}"; e.error_kind(),
+        TooLarge => "Frame too large"; error::ErrorKind::InvalidInput,
+        InvalidData(e) => "Invalid frame: {e}"; error::ErrorKind::InvalidInput,
+    }
+}
+
+impl<E> io::Error for FrameError<E>
+where
+    E: io::Error,
+{
+    fn kind(&self) -> ErrorKind {
+        match self {
+            Self::IoError(e) => e.kind(),
+            _ => ErrorKind::Other,
+        }
+    }
+}
+
+/// Writes `payload` preceded by its length
+pub fn write_frame<W>(
+    mut writer: W,
+    prefix: LengthPrefix,
+    payload: &[u8],
+) -> Result<(), FrameError<W::Error>>
+where
+    W: Write,
+{
+    let mut header = [0_u8; LengthPrefix::MAX_LEN];
+    let header_len = prefix
+        .encode(payload.len(), &mut header)
+        .ok_or(FrameError::TooLarge)?;
+
+    writer
+        .write_all(&header[..header_len])
+        .map_err(FrameError::IoError)?;
+    writer.write_all(payload).map_err(FrameError::IoError)
+}
+
+/// Reads the next frame into `buf`, returning its payload.
+///
+/// Returns `None` if the stream ended before the frame, and an error if it ended within the frame.
+pub fn read_frame<R>(
+    mut reader: R,
+    prefix: LengthPrefix,
+    buf: &mut [u8],
+) -> Result<Option<&[u8]>, FrameError<R::Error>>
+where
+    R: Read,
+{
+    let mut header = [0_u8; LengthPrefix::MAX_LEN];
+    let mut header_len = 0;
+
+    let len = loop {
+        if let Some((len, _)) = prefix
+            .decode(&header[..header_len])
+            .map_err(FrameError::InvalidData)?
+        {
+            break len;
+        }
+
+        let read = reader
+            .read(&mut header[header_len..header_len + 1])
+            .map_err(FrameError::IoError)?;
+
+        if read == 0 {
+            return if header_len == 0 {
+                Ok(None)
+            } else {
+                Err(FrameError::InvalidData("Truncated frame"))
+            };
+        }
+
+        header_len += 1;
+    };
+
+    let payload = buf.get_mut(..len).ok_or(FrameError::TooLarge)?;
+
+    let read = crate::utils::io::try_read_full(&mut reader, payload)
+        .map_err(|(e, _)| FrameError::IoError(e))?;
+
+    if read < len {
+        Err(FrameError::InvalidData("Truncated frame"))
+    } else {
+        Ok(Some(&buf[..len]))
+    }
+}
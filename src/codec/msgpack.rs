@@ -0,0 +1,107 @@
+//! MessagePack, encoded and decoded with `embedded-msgpack`.
+//!
+//! Unit values, tuple structs and enum variants with data are not supported, nor are sequences and maps
+//! of unknown length. Trailing data after the decoded value is ignored.
+
+use embedded_msgpack::{decode, encode};
+use serde::{Deserialize, Serialize};
+
+use super::CodecError;
+
+pub use super::content_type::MSGPACK as CONTENT_TYPE;
+
+/// Returns the length of the encoded value
+pub fn to_slice<T>(value: &T, buf: &mut [u8]) -> Result<usize, CodecError>
+where
+    T: Serialize + ?Sized,
+{
+    encode::serde::to_array(value, buf).map_err(|e| match e {
+        encode::Error::EndOfBuffer => CodecError::BufferTooSmall,
+        encode::Error::OutOfBounds => CodecError::InvalidData("Value out of range"),
+        encode::Error::InvalidType => CodecError::InvalidData("Unsupported type"),
+    })
+}
+
+pub fn from_slice<'de, T>(input: &'de [u8]) -> Result<T, CodecError>
+where
+    T: Deserialize<'de>,
+{
+    decode::from_slice(input).map_err(|e| match e {
+        decode::Error::EndOfBuffer => CodecError::UnexpectedEof,
+        decode::Error::OutOfBounds => CodecError::InvalidData("Value out of range"),
+        decode::Error::CustomError => CodecError::Custom,
+        decode::Error::NotAscii => CodecError::InvalidData("Invalid string"),
+        _ => CodecError::InvalidData("Unexpected type"),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use super::*;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum Unit {
+        Celsius,
+        Fahrenheit,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Reading<'a> {
+        sensor: &'a str,
+        value: f32,
+        unit: Unit,
+        offset: i64,
+        samples: [u16; 3],
+        label: Option<&'a str>,
+    }
+
+    const READING: Reading<'static> = Reading {
+        sensor: "t1",
+        value: 21.5,
+        unit: Unit::Celsius,
+        offset: -1000,
+        samples: [1, 300, 65_000],
+        label: None,
+    };
+
+    #[test]
+    fn round_trip() {
+        let mut buf = [0_u8; 128];
+
+        let len = to_slice(&READING, &mut buf).unwrap();
+
+        assert_eq!(from_slice::<Reading>(&buf[..len]), Ok(READING));
+    }
+
+    #[test]
+    fn encoding() {
+        let mut buf = [0_u8; 16];
+
+        let len = to_slice(&(500_u16, -2_i8, "a", true), &mut buf).unwrap();
+
+        assert_eq!(
+            &buf[..len],
+            &[0x94, 0xcd, 0x01, 0xf4, 0xfe, 0xa1, b'a', 0xc3]
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let mut buf = [0_u8; 8];
+
+        assert_eq!(
+            to_slice(&READING, &mut buf),
+            Err(CodecError::BufferTooSmall)
+        );
+        assert_eq!(
+            from_slice::<u16>(&[0xcd, 0x01]),
+            Err(CodecError::UnexpectedEof)
+        );
+        assert_eq!(
+            from_slice::<u8>(&[0xcd, 0x01, 0xf4]),
+            Err(CodecError::InvalidData("Value out of range"))
+        );
+    }
+}
@@ -0,0 +1,196 @@
+//! Provider traits for the crypto primitives used by the TLS, cloud and OTA helpers.
+//!
+//! These are implemented by the backend - on top of a secure element (e.g. ATECC608),
+//! a crypto/HMAC peripheral, or a software fallback - so that the helpers never need
+//! to depend on a particular crypto library, nor to see keys which never leave the hardware.
+
+use core::fmt::Debug;
+
+pub(crate) mod der;
+
+pub type Digest = [u8; 32];
+
+/// An uncompressed NIST P-256 public key: the `x || y` coordinates, without the `0x04` prefix
+pub type EcdsaP256PublicKey = [u8; 64];
+
+/// A raw (not DER-encoded) ECDSA P-256 signature: `r || s`
+pub type EcdsaP256Signature = [u8; 64];
+
+/// Converts a DER-encoded `ECDSA-Sig-Value`, as produced by most tools and TLS stacks, into the raw format
+pub fn ecdsa_p256_signature_from_der(der: &[u8]) -> Option<EcdsaP256Signature> {
+    let (sequence, rest) = der::expect(der, der::SEQUENCE)?;
+    if !rest.is_empty() {
+        return None;
+    }
+
+    let (r, rest) = der::expect(sequence.value, der::INTEGER)?;
+    let (s, rest) = der::expect(rest, der::INTEGER)?;
+    if !rest.is_empty() {
+        return None;
+    }
+
+    let mut signature = [0_u8; 64];
+    signature[..32].copy_from_slice(&der::unsigned::<32>(r.value)?);
+    signature[32..].copy_from_slice(&der::unsigned::<32>(s.value)?);
+
+    Some(signature)
+}
+
+/// Identifies a key stored by the provider, e.g. a secure element slot or a HMAC eFuse key block
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct KeyHandle(pub u16);
+
+/// A cryptographically secure random number generator
+pub trait Rng {
+    type Error: Debug;
+
+    fn fill_bytes(&mut self, buf: &mut [u8]) -> Result<(), Self::Error>;
+}
+
+impl<R> Rng for &mut R
+where
+    R: Rng,
+{
+    type Error = R::Error;
+
+    fn fill_bytes(&mut self, buf: &mut [u8]) -> Result<(), Self::Error> {
+        (*self).fill_bytes(buf)
+    }
+}
+
+/// A SHA-256 implementation, as provided by the backend (typically hardware-accelerated) or a software crate
+pub trait Sha256 {
+    fn update(&mut self, data: &[u8]);
+
+    fn finish(self) -> Digest;
+}
+
+/// Hashes `data` in one go
+pub fn sha256<H>(mut hasher: H, data: &[u8]) -> Digest
+where
+    H: Sha256,
+{
+    hasher.update(data);
+    hasher.finish()
+}
+
+/// HMAC-SHA256 with a key supplied by the caller, typically backed by the TLS stack of the backend
+pub trait HmacSha256 {
+    fn hmac_sha256(&self, key: &[u8], data: &[u8]) -> Digest;
+}
+
+impl<H> HmacSha256 for &H
+where
+    H: HmacSha256,
+{
+    fn hmac_sha256(&self, key: &[u8], data: &[u8]) -> Digest {
+        (*self).hmac_sha256(key, data)
+    }
+}
+
+/// HMAC-SHA256 with a key held by the provider, e.g. the ESP32 HMAC peripheral
+pub trait KeyedHmacSha256 {
+    type Error: Debug;
+
+    fn hmac_sha256(&self, key: KeyHandle, data: &[u8]) -> Result<Digest, Self::Error>;
+}
+
+impl<H> KeyedHmacSha256 for &H
+where
+    H: KeyedHmacSha256,
+{
+    type Error = H::Error;
+
+    fn hmac_sha256(&self, key: KeyHandle, data: &[u8]) -> Result<Digest, Self::Error> {
+        (*self).hmac_sha256(key, data)
+    }
+}
+
+/// ECDSA P-256 signing with a private key held by the provider
+pub trait EcdsaP256Sign {
+    type Error: Debug;
+
+    fn public_key(&self, key: KeyHandle) -> Result<EcdsaP256PublicKey, Self::Error>;
+
+    /// Signs a SHA-256 digest (not the message itself)
+    fn sign(&self, key: KeyHandle, digest: &Digest) -> Result<EcdsaP256Signature, Self::Error>;
+}
+
+impl<S> EcdsaP256Sign for &S
+where
+    S: EcdsaP256Sign,
+{
+    type Error = S::Error;
+
+    fn public_key(&self, key: KeyHandle) -> Result<EcdsaP256PublicKey, Self::Error> {
+        (*self).public_key(key)
+    }
+
+    fn sign(&self, key: KeyHandle, digest: &Digest) -> Result<EcdsaP256Signature, Self::Error> {
+        (*self).sign(key, digest)
+    }
+}
+
+/// ECDSA P-256 signature verification; secure elements usually offer it too, but it needs no secrets
+pub trait EcdsaP256Verify {
+    type Error: Debug;
+
+    /// Returns `false` if the signature of `digest` does not match
+    fn verify(
+        &self,
+        public_key: &EcdsaP256PublicKey,
+        digest: &Digest,
+        signature: &EcdsaP256Signature,
+    ) -> Result<bool, Self::Error>;
+}
+
+impl<V> EcdsaP256Verify for &V
+where
+    V: EcdsaP256Verify,
+{
+    type Error = V::Error;
+
+    fn verify(
+        &self,
+        public_key: &EcdsaP256PublicKey,
+        digest: &Digest,
+        signature: &EcdsaP256Signature,
+    ) -> Result<bool, Self::Error> {
+        (*self).verify(public_key, digest, signature)
+    }
+}
+
+/// Provisioning of the keys held by the provider.
+/// Secure elements typically lock their configuration, in which case these fail.
+pub trait KeyStore {
+    type Error: Debug;
+
+    /// Generates a new ECDSA P-256 key pair in `key`; the private key never leaves the provider
+    fn generate_ecdsa_p256(&mut self, key: KeyHandle) -> Result<EcdsaP256PublicKey, Self::Error>;
+
+    fn import_hmac_key(&mut self, key: KeyHandle, secret: &[u8]) -> Result<(), Self::Error>;
+
+    fn erase(&mut self, key: KeyHandle) -> Result<(), Self::Error>;
+}
+
+impl<K> KeyStore for &mut K
+where
+    K: KeyStore,
+{
+    type Error = K::Error;
+
+    fn generate_ecdsa_p256(&mut self, key: KeyHandle) -> Result<EcdsaP256PublicKey, Self::Error> {
+        (*self).generate_ecdsa_p256(key)
+    }
+
+    fn import_hmac_key(&mut self, key: KeyHandle, secret: &[u8]) -> Result<(), Self::Error> {
+        (*self).import_hmac_key(key, secret)
+    }
+
+    fn erase(&mut self, key: KeyHandle) -> Result<(), Self::Error> {
+        (*self).erase(key)
+    }
+}
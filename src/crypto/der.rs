@@ -0,0 +1,78 @@
+//! A minimal, zero-copy DER reader: just enough to walk certificates and ECDSA signatures.
+
+pub(crate) const INTEGER: u8 = 0x02;
+pub(crate) const BIT_STRING: u8 = 0x03;
+pub(crate) const OID: u8 = 0x06;
+pub(crate) const SEQUENCE: u8 = 0x30;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Tlv<'a> {
+    pub tag: u8,
+    pub value: &'a [u8],
+    /// The whole encoding, including the tag and the length
+    pub raw: &'a [u8],
+}
+
+/// Reads the first TLV of `data`, returning it along with the remaining data
+pub(crate) fn read(data: &[u8]) -> Option<(Tlv<'_>, &[u8])> {
+    let tag = *data.first()?;
+    let first = *data.get(1)? as usize;
+
+    let (len, header) = if first < 0x80 {
+        (first, 2)
+    } else {
+        let count = first & 0x7f;
+        if count == 0 || count > 4 {
+            return None;
+        }
+
+        let len = data
+            .get(2..2 + count)?
+            .iter()
+            .fold(0_usize, |len, byte| (len << 8) | *byte as usize);
+
+        (len, 2 + count)
+    };
+
+    let end = header.checked_add(len)?;
+    if end > data.len() {
+        return None;
+    }
+
+    Some((
+        Tlv {
+            tag,
+            value: &data[header..end],
+            raw: &data[..end],
+        },
+        &data[end..],
+    ))
+}
+
+/// Like `read`, but fails unless the TLV has the expected tag
+pub(crate) fn expect(data: &[u8], tag: u8) -> Option<(Tlv<'_>, &[u8])> {
+    let (tlv, rest) = read(data)?;
+
+    if tlv.tag == tag {
+        Some((tlv, rest))
+    } else {
+        None
+    }
+}
+
+/// Converts a DER INTEGER value into a fixed-size big-endian unsigned integer
+pub(crate) fn unsigned<const N: usize>(value: &[u8]) -> Option<[u8; N]> {
+    let mut value = value;
+    while value.len() > 1 && value[0] == 0 {
+        value = &value[1..];
+    }
+
+    if value.len() > N {
+        return None;
+    }
+
+    let mut result = [0_u8; N];
+    result[N - value.len()..].copy_from_slice(value);
+
+    Some(result)
+}
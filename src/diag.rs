@@ -0,0 +1,3 @@
+//! Diagnostics of the device itself, as opposed to the connectivity diagnostics of `net::diag`
+
+pub mod crash;
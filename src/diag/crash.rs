@@ -0,0 +1,458 @@
+//! Capture of panics and abnormal resets, so that they can be reported once the device is back online.
+//!
+//! The report is persisted when the crash happens, either by the panic hook installed with
+//! `install_panic_hook` (std) or by the panic handler or core dump support of the backend calling
+//! `CrashStore::record`. On the next boot, `CrashStore::boot` completes it with the reset reason and
+//! returns it, so that it can be published with a `CrashPublisher`; it stays available to a
+//! `CrashHandler` until `CrashStore::clear` is called.
+
+use core::convert::TryInto;
+use core::fmt::{self, Display, Write as _};
+use core::panic::Location;
+
+#[cfg(feature = "use_serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::error::impl_error;
+use crate::storage::RawStorage;
+
+const NAME: &str = "crash";
+const VERSION: u8 = 1;
+/// The header, the timestamp, the message, the location and the backtrace
+const MAX_LEN: usize = 3 + 9 + 1 + 128 + 2 + 64 + 1 + 16 * 4;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub enum ResetReason {
+    PowerOn,
+    /// The reset pin
+    External,
+    /// A restart requested by the application
+    Software,
+    DeepSleep,
+    Panic,
+    Watchdog,
+    Brownout,
+    Unknown,
+}
+
+impl ResetReason {
+    /// Whether the previous run ended with a crash rather than a power cycle or a requested restart
+    pub fn is_crash(&self) -> bool {
+        matches!(self, Self::Panic | Self::Watchdog | Self::Brownout)
+    }
+
+    fn to_u8(self) -> u8 {
+        self as _
+    }
+
+    fn from_u8(value: u8) -> Self {
+        match value {
+            0 => Self::PowerOn,
+            1 => Self::External,
+            2 => Self::Software,
+            3 => Self::DeepSleep,
+            4 => Self::Panic,
+            5 => Self::Watchdog,
+            6 => Self::Brownout,
+            _ => Self::Unknown,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub struct CrashReport {
+    pub reset_reason: ResetReason,
+    /// Seconds since the UNIX epoch, if the time was known when the crash happened
+    pub timestamp: Option<u64>,
+    /// Truncated to fit
+    pub message: heapless::String<128>,
+    /// `file:line:column` of the panic
+    pub location: Option<heapless::String<64>>,
+    /// The program counters of the stack frames, innermost first, as captured by the backend; to be
+    /// resolved offline, e.g. with `addr2line`
+    pub backtrace: heapless::Vec<u32, 16>,
+}
+
+impl CrashReport {
+    pub fn new(reset_reason: ResetReason) -> Self {
+        Self {
+            reset_reason,
+            timestamp: None,
+            message: heapless::String::new(),
+            location: None,
+            backtrace: heapless::Vec::new(),
+        }
+    }
+
+    /// E.g. with the `PanicInfo` of a `#[panic_handler]`
+    pub fn set_message<D>(&mut self, message: D)
+    where
+        D: Display,
+    {
+        self.message.clear();
+
+        let _ = write!(Truncating(&mut self.message), "{message}");
+    }
+
+    pub fn set_location(&mut self, location: &Location<'_>) {
+        let mut string = heapless::String::new();
+
+        let _ = write!(
+            Truncating(&mut string),
+            "{}:{}:{}",
+            location.file(),
+            location.line(),
+            location.column()
+        );
+
+        self.location = Some(string);
+    }
+
+    fn encode<'a>(&self, pending: bool, buf: &'a mut [u8; MAX_LEN]) -> &'a [u8] {
+        let mut len = 0;
+        let mut put = |data: &[u8]| {
+            buf[len..len + data.len()].copy_from_slice(data);
+            len += data.len();
+        };
+
+        put(&[VERSION, pending as u8, self.reset_reason.to_u8()]);
+
+        match self.timestamp {
+            Some(timestamp) => {
+                put(&[1]);
+                put(&timestamp.to_le_bytes());
+            }
+            None => put(&[0]),
+        }
+
+        put(&[self.message.len() as u8]);
+        put(self.message.as_bytes());
+
+        let location = self.location.as_deref().unwrap_or("");
+
+        put(&[self.location.is_some() as u8, location.len() as u8]);
+        put(location.as_bytes());
+
+        put(&[self.backtrace.len() as u8]);
+
+        for address in &self.backtrace {
+            put(&address.to_le_bytes());
+        }
+
+        &buf[..len]
+    }
+
+    /// Returns `None` if `data` is not a report of the current version
+    fn decode(data: &[u8]) -> Option<(Self, bool)> {
+        let mut reader = Reader(data);
+
+        if reader.u8()? != VERSION {
+            return None;
+        }
+
+        let pending = reader.u8()? != 0;
+        let mut report = Self::new(ResetReason::from_u8(reader.u8()?));
+
+        if reader.u8()? != 0 {
+            report.timestamp = Some(u64::from_le_bytes(reader.take(8)?.try_into().ok()?));
+        }
+
+        report.message = reader.str()?;
+
+        let has_location = reader.u8()? != 0;
+        let location = reader.str()?;
+
+        if has_location {
+            report.location = Some(location);
+        }
+
+        for _ in 0..reader.u8()? {
+            report
+                .backtrace
+                .push(u32::from_le_bytes(reader.take(4)?.try_into().ok()?))
+                .ok()?;
+        }
+
+        Some((report, pending))
+    }
+}
+
+/// Persists the last crash report in a `RawStorage`
+pub struct CrashStore<S> {
+    storage: S,
+}
+
+impl<S> CrashStore<S> {
+    pub const fn new(storage: S) -> Self {
+        Self { storage }
+    }
+
+    pub fn release(self) -> S {
+        self.storage
+    }
+}
+
+impl<S> CrashStore<S>
+where
+    S: RawStorage,
+{
+    /// Persists the report of a crash which is happening; to be called from the panic handler or the
+    /// panic hook, as there is no chance to do it later.
+    ///
+    /// The reset reason of the report is replaced by the one passed to `boot` if that one is a crash too,
+    /// e.g. a watchdog reset while the report was being recorded.
+    pub fn record(&mut self, report: &CrashReport) -> Result<(), S::Error> {
+        let mut buf = [0_u8; MAX_LEN];
+
+        self.storage.set_raw(NAME, report.encode(true, &mut buf))?;
+
+        Ok(())
+    }
+
+    /// To be called once per boot with the reset reason reported by the backend.
+    ///
+    /// Returns the crash which ended the previous run, if any: the one recorded while it happened, or a
+    /// report carrying just the reset reason if it could not be recorded, e.g. on a watchdog reset.
+    pub fn boot(&mut self, reset_reason: ResetReason) -> Result<Option<CrashReport>, S::Error> {
+        let mut report = match self.load()? {
+            Some((report, true)) => report,
+            _ if reset_reason.is_crash() => CrashReport::new(reset_reason),
+            _ => return Ok(None),
+        };
+
+        if reset_reason.is_crash() {
+            report.reset_reason = reset_reason;
+        }
+
+        let mut buf = [0_u8; MAX_LEN];
+
+        self.storage.set_raw(NAME, report.encode(false, &mut buf))?;
+
+        Ok(Some(report))
+    }
+
+    /// The last crash report, until it is cleared
+    pub fn last(&self) -> Result<Option<CrashReport>, S::Error> {
+        Ok(self.load()?.map(|(report, _)| report))
+    }
+
+    pub fn clear(&mut self) -> Result<bool, S::Error> {
+        self.storage.remove(NAME)
+    }
+
+    fn load(&self) -> Result<Option<(CrashReport, bool)>, S::Error> {
+        let mut buf = [0_u8; MAX_LEN];
+
+        Ok(self
+            .storage
+            .get_raw(NAME, &mut buf)?
+            .and_then(CrashReport::decode))
+    }
+}
+
+/// Installs a panic hook passing the report of the panic to `record`, typically a closure calling
+/// `CrashStore::record`, before chaining to the previous hook
+#[cfg(feature = "std")]
+pub fn install_panic_hook<F>(record: F)
+where
+    F: Fn(&CrashReport) + Send + Sync + 'static,
+{
+    let previous = std::panic::take_hook();
+
+    std::panic::set_hook(Box::new(move |info| {
+        let mut report = CrashReport::new(ResetReason::Panic);
+
+        if let Some(message) = info.payload().downcast_ref::<&str>() {
+            report.set_message(message);
+        } else if let Some(message) = info.payload().downcast_ref::<String>() {
+            report.set_message(message);
+        } else {
+            report.set_message(info);
+        }
+
+        if let Some(location) = info.location() {
+            report.set_location(location);
+        }
+
+        report.timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .ok()
+            .map(|duration| duration.as_secs());
+
+        record(&report);
+
+        previous(info);
+    }));
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum CrashError<P, S> {
+    PublishError(P),
+    SerdeError(S),
+}
+
+impl_error! {
+    CrashError<P: Display, S: Display> {
+        PublishError(e) => "Publish error: {e}"; e.error_kind(),
+        SerdeError(e) => "SerDe error: {e}"; e.error_kind(),
+    }
+}
+
+/// Writes as much as fits, cutting at a character boundary
+struct Truncating<'a, const N: usize>(&'a mut heapless::String<N>);
+
+impl<'a, const N: usize> fmt::Write for Truncating<'a, N> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for c in s.chars() {
+            self.0.push(c).map_err(|_| fmt::Error)?;
+        }
+
+        Ok(())
+    }
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        if self.0.len() < len {
+            return None;
+        }
+
+        let (data, rest) = self.0.split_at(len);
+
+        self.0 = rest;
+
+        Some(data)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        self.take(1).map(|data| data[0])
+    }
+
+    fn str<const N: usize>(&mut self) -> Option<heapless::String<N>> {
+        let len = self.u8()? as usize;
+        let data = core::str::from_utf8(self.take(len)?).ok()?;
+
+        let mut string = heapless::String::new();
+        string.push_str(data).ok()?;
+
+        Some(string)
+    }
+}
+
+#[cfg(feature = "use_serde")]
+pub mod mqtt {
+    use crate::mqtt::client::{MessageId, Publish, QoS};
+    use crate::storage::SerDe;
+
+    use super::{CrashError, CrashReport};
+
+    /// Publishes crash reports on a fixed topic, serialized with `S` into a buffer of `B` bytes.
+    ///
+    /// Reports are published with QoS 1 and retained, so that they are not lost if nobody is subscribed.
+    pub struct CrashPublisher<'a, P, S, const B: usize = 512> {
+        publisher: P,
+        serde: S,
+        topic: &'a str,
+    }
+
+    impl<'a, P, S, const B: usize> CrashPublisher<'a, P, S, B>
+    where
+        P: Publish,
+        S: SerDe,
+    {
+        pub const fn new(publisher: P, serde: S, topic: &'a str) -> Self {
+            Self {
+                publisher,
+                serde,
+                topic,
+            }
+        }
+
+        pub fn publish(
+            &mut self,
+            report: &CrashReport,
+        ) -> Result<MessageId, CrashError<P::Error, S::Error>> {
+            let mut buf = [0_u8; B];
+
+            let payload = self
+                .serde
+                .serialize(&mut buf, report)
+                .map_err(CrashError::SerdeError)?;
+
+            self.publisher
+                .publish(self.topic, QoS::AtLeastOnce, true, payload)
+                .map_err(CrashError::PublishError)
+        }
+
+        pub fn release(self) -> (P, S) {
+            (self.publisher, self.serde)
+        }
+    }
+}
+
+#[cfg(all(feature = "use_serde", feature = "experimental"))]
+pub mod server {
+    use core::fmt::Debug;
+
+    use crate::http::server::{Connection, Handler, HandlerResult, Request};
+    use crate::io::Write;
+    use crate::storage::SerDe;
+
+    use super::CrashReport;
+
+    /// Serves the report returned by `F`, typically `CrashStore::last`, serialized with `S` into a buffer of
+    /// `B` bytes.
+    ///
+    /// Responds with 404 when there is no report.
+    pub struct CrashHandler<F, S, const B: usize = 512> {
+        report: F,
+        serde: S,
+        content_type: &'static str,
+    }
+
+    impl<F, S, const B: usize> CrashHandler<F, S, B> {
+        pub const fn new(report: F, serde: S, content_type: &'static str) -> Self {
+            Self {
+                report,
+                serde,
+                content_type,
+            }
+        }
+    }
+
+    impl<C, F, E, S, const B: usize> Handler<C> for CrashHandler<F, S, B>
+    where
+        C: Connection,
+        F: Fn() -> Result<Option<CrashReport>, E> + Send,
+        E: Debug,
+        S: SerDe + Send,
+    {
+        fn handle(&self, connection: &mut C) -> HandlerResult {
+            let report = match (self.report)()? {
+                Some(report) => report,
+                None => {
+                    Request::wrap(connection).into_status_response(404)?;
+
+                    return Ok(());
+                }
+            };
+
+            let mut buf = [0_u8; B];
+            let payload = self.serde.serialize(&mut buf, &report)?;
+
+            Request::wrap(connection)
+                .into_response(200, None, &[("Content-Type", self.content_type)])?
+                .write_all(payload)?;
+
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,283 @@
+//! A coarse classification of errors, so that errors of any layer can be reported consistently, e.g. as the
+//! HTTP status of a failed request.
+
+use core::convert::Infallible;
+use core::fmt::{self, Debug, Display};
+
+#[cfg(feature = "use_serde")]
+use serde::{Deserialize, Serialize};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub enum ErrorKind {
+    Timeout,
+    Unauthorized,
+    NotFound,
+    InvalidInput,
+    /// A resource is exhausted or a peer cannot be reached; retrying later may succeed
+    Unavailable,
+    /// The implementation does not support the operation; retrying will not help
+    Unsupported,
+    Other,
+}
+
+impl ErrorKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Timeout => "timeout",
+            Self::Unauthorized => "unauthorized",
+            Self::NotFound => "not_found",
+            Self::InvalidInput => "invalid_input",
+            Self::Unavailable => "unavailable",
+            Self::Unsupported => "unsupported",
+            Self::Other => "other",
+        }
+    }
+
+    /// The HTTP status of a request failing with an error of this kind
+    pub fn status(&self) -> u16 {
+        match self {
+            Self::Timeout => 504,
+            Self::Unauthorized => 401,
+            Self::NotFound => 404,
+            Self::InvalidInput => 400,
+            Self::Unavailable => 503,
+            Self::Unsupported => 501,
+            Self::Other => 500,
+        }
+    }
+
+    /// The kind of the error reported by a peer with an unexpected HTTP status
+    pub fn from_status(status: u16) -> Self {
+        match status {
+            401 | 403 => Self::Unauthorized,
+            404 | 410 => Self::NotFound,
+            408 | 504 => Self::Timeout,
+            400 | 405..=407 | 409 | 411..=422 => Self::InvalidInput,
+            429 | 502 | 503 => Self::Unavailable,
+            501 => Self::Unsupported,
+            _ => Self::Other,
+        }
+    }
+}
+
+/// Implemented by the error types of the crate
+pub trait Classify {
+    fn error_kind(&self) -> ErrorKind;
+}
+
+impl<C> Classify for &C
+where
+    C: Classify,
+{
+    fn error_kind(&self) -> ErrorKind {
+        (*self).error_kind()
+    }
+}
+
+impl Classify for ErrorKind {
+    fn error_kind(&self) -> ErrorKind {
+        *self
+    }
+}
+
+impl Classify for Infallible {
+    fn error_kind(&self) -> ErrorKind {
+        match *self {}
+    }
+}
+
+#[cfg(feature = "std")]
+impl Classify for std::io::Error {
+    fn error_kind(&self) -> ErrorKind {
+        match self.kind() {
+            std::io::ErrorKind::TimedOut => ErrorKind::Timeout,
+            std::io::ErrorKind::NotFound => ErrorKind::NotFound,
+            std::io::ErrorKind::PermissionDenied => ErrorKind::Unauthorized,
+            std::io::ErrorKind::InvalidInput | std::io::ErrorKind::InvalidData => {
+                ErrorKind::InvalidInput
+            }
+            std::io::ErrorKind::Unsupported => ErrorKind::Unsupported,
+            std::io::ErrorKind::ConnectionRefused
+            | std::io::ErrorKind::ConnectionReset
+            | std::io::ErrorKind::ConnectionAborted
+            | std::io::ErrorKind::NotConnected
+            | std::io::ErrorKind::AddrInUse
+            | std::io::ErrorKind::AddrNotAvailable
+            | std::io::ErrorKind::BrokenPipe
+            | std::io::ErrorKind::WouldBlock => ErrorKind::Unavailable,
+            _ => ErrorKind::Other,
+        }
+    }
+}
+
+/// Implements `Display`, `std::error::Error` and `Classify` for an error enum of the crate, from one line per
+/// variant with its message and its kind:
+///
+/// ```ignore
+/// impl_error! {
+///     UploadError<H: Display, R: Display> {
+///         HttpError(e) => "HTTP error: {e}"; e.error_kind(),
+///         Status(status) => "Unexpected HTTP status: {status}"; ErrorKind::from_status(*status),
+///         TooLarge => "Upload too large"; ErrorKind::InvalidInput,
+///     }
+/// }
+/// ```
+///
+/// Each type parameter is the error of another layer, which the variants wrapping it are classified as; its
+/// bound, `Display` or `Debug`, is the one its message needs.
+macro_rules! impl_error {
+    (
+        $name:ident $(<$($param:ident: $bound:ident),+>)? {
+            $($variant:ident $(($($field:ident),+))? => $fmt:literal $(, $arg:expr)*; $kind:expr),+ $(,)?
+        }
+    ) => {
+        impl$(<$($param),+>)? core::fmt::Display for $name$(<$($param),+>)?
+        where
+            $($($param: core::fmt::$bound,)+)?
+        {
+            #[allow(unused_variables)]
+            fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+                match self {
+                    $(Self::$variant $(($($field),+))? => write!(f, $fmt $(, $arg)*),)+
+                }
+            }
+        }
+
+        #[cfg(feature = "std")]
+        impl$(<$($param),+>)? std::error::Error for $name$(<$($param),+>)?
+        where
+            $($($param: core::fmt::$bound + core::fmt::Debug,)+)?
+        {
+        }
+
+        impl$(<$($param),+>)? $crate::error::Classify for $name$(<$($param),+>)?
+        where
+            $($($param: $crate::error::Classify,)+)?
+        {
+            #[allow(unused_variables)]
+            fn error_kind(&self) -> $crate::error::ErrorKind {
+                match self {
+                    $(Self::$variant $(($($field),+))? => $kind,)+
+                }
+            }
+        }
+    };
+}
+
+pub(crate) use impl_error;
+
+/// The error of the optional operations of a trait, whose default implementations fail with
+/// `Unsupported`, so that adding operations to a trait does not break its existing implementations
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum PartialError<E> {
+    Unsupported,
+    Backend(E),
+}
+
+impl<E> PartialError<E> {
+    pub fn is_unsupported(&self) -> bool {
+        matches!(self, Self::Unsupported)
+    }
+}
+
+impl<E> From<E> for PartialError<E> {
+    fn from(e: E) -> Self {
+        Self::Backend(e)
+    }
+}
+
+impl<E> Display for PartialError<E>
+where
+    E: Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unsupported => write!(f, "Operation not supported"),
+            Self::Backend(e) => write!(f, "Backend error: {e:?}"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<E> std::error::Error for PartialError<E> where E: Debug {}
+
+impl<E> Classify for PartialError<E>
+where
+    E: Classify,
+{
+    fn error_kind(&self) -> ErrorKind {
+        match self {
+            Self::Unsupported => ErrorKind::Unsupported,
+            Self::Backend(e) => e.error_kind(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug)]
+    enum TestError<E> {
+        Wrapped(E),
+        Status(u16),
+        Invalid,
+    }
+
+    impl_error! {
+        TestError<E: Debug> {
+            Wrapped(e) => "Wrapped error: {e:?}"; e.error_kind(),
+            Status(status) => "Unexpected status: {status}"; ErrorKind::from_status(*status),
+            Invalid => "Invalid"; ErrorKind::InvalidInput,
+        }
+    }
+
+    #[test]
+    fn wrapped_kind() {
+        assert_eq!(
+            TestError::Wrapped(ErrorKind::Timeout).error_kind(),
+            ErrorKind::Timeout
+        );
+        assert_eq!(
+            TestError::<ErrorKind>::Status(404).error_kind(),
+            ErrorKind::NotFound
+        );
+        assert_eq!(
+            TestError::<ErrorKind>::Invalid.error_kind(),
+            ErrorKind::InvalidInput
+        );
+
+        assert_eq!(
+            PartialError::Backend(TestError::Wrapped(ErrorKind::Unavailable)).error_kind(),
+            ErrorKind::Unavailable
+        );
+        assert_eq!(
+            PartialError::<ErrorKind>::Unsupported.error_kind(),
+            ErrorKind::Unsupported
+        );
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!(
+            TestError::<ErrorKind>::Status(404).to_string(),
+            "Unexpected status: 404"
+        );
+        assert_eq!(TestError::<ErrorKind>::Invalid.to_string(), "Invalid");
+    }
+
+    #[test]
+    fn io_kind() {
+        let error = std::io::Error::from(std::io::ErrorKind::ConnectionRefused);
+
+        assert_eq!(error.error_kind(), ErrorKind::Unavailable);
+        assert_eq!(
+            std::io::Error::from(std::io::ErrorKind::UnexpectedEof).error_kind(),
+            ErrorKind::Other
+        );
+    }
+}
@@ -1,4 +1,15 @@
-use core::fmt::Debug;
+use core::convert::TryFrom;
+use core::fmt::{self, Debug, Display};
+use core::str::FromStr;
+
+#[cfg(feature = "use_serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::crypto::KeyHandle;
+use crate::ipv4;
+use crate::tls::X509;
+
+pub mod bond;
 
 pub trait Eth {
     type Error: Debug;
@@ -32,3 +43,316 @@ where
         (**self).is_up()
     }
 }
+
+/// An IEEE 802.1Q VLAN ID, from 1 to 4094; 0 and 4095 are reserved
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(
+    feature = "use_serde",
+    derive(Serialize, Deserialize),
+    serde(try_from = "u16", into = "u16")
+)]
+pub struct VlanId(u16);
+
+impl VlanId {
+    pub const MIN: u16 = 1;
+    pub const MAX: u16 = 4094;
+
+    pub const fn new(id: u16) -> Option<Self> {
+        if id >= Self::MIN && id <= Self::MAX {
+            Some(Self(id))
+        } else {
+            None
+        }
+    }
+
+    pub const fn get(&self) -> u16 {
+        self.0
+    }
+}
+
+impl TryFrom<u16> for VlanId {
+    type Error = &'static str;
+
+    fn try_from(id: u16) -> Result<Self, Self::Error> {
+        Self::new(id).ok_or("VLAN ID should be from 1 to 4094")
+    }
+}
+
+impl From<VlanId> for u16 {
+    fn from(id: VlanId) -> Self {
+        id.0
+    }
+}
+
+impl FromStr for VlanId {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let id: u16 = s.parse().map_err(|_| "Invalid VLAN ID")?;
+
+        Self::try_from(id)
+    }
+}
+
+impl Display for VlanId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// A logical interface on top of the Ethernet interface, sending and receiving frames tagged with its VLAN
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub struct VlanInterface {
+    pub id: VlanId,
+    /// The IEEE 802.1p priority code point of the frames sent, from 0 (best effort, the default) to 7
+    pub priority: u8,
+    pub ip: ipv4::Configuration,
+}
+
+impl VlanInterface {
+    pub fn new(id: VlanId, ip: ipv4::Configuration) -> Self {
+        Self {
+            id,
+            priority: 0,
+            ip,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub struct VlanConfiguration {
+    /// Whether untagged frames are still sent and received, by the Ethernet interface itself
+    pub untagged: bool,
+    pub interfaces: heapless::Vec<VlanInterface, 4>,
+}
+
+impl VlanConfiguration {
+    /// Checks that the priorities are from 0 to 7 and that no VLAN has several interfaces, as backends
+    /// do before applying the configuration
+    pub fn validate(&self) -> Result<(), &'static str> {
+        for (index, interface) in self.interfaces.iter().enumerate() {
+            if interface.priority > 7 {
+                return Err("VLAN priority should be from 0 to 7");
+            }
+
+            if self.interfaces[..index]
+                .iter()
+                .any(|other| other.id == interface.id)
+            {
+                return Err("Duplicate VLAN ID");
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn interface(&self, id: VlanId) -> Option<&VlanInterface> {
+        self.interfaces.iter().find(|interface| interface.id == id)
+    }
+}
+
+impl Default for VlanConfiguration {
+    fn default() -> Self {
+        Self {
+            untagged: true,
+            interfaces: heapless::Vec::new(),
+        }
+    }
+}
+
+/// Implemented by Ethernet drivers supporting VLANs; the IP configuration of each VLAN interface is applied
+/// along with it
+pub trait EthVlan: Eth {
+    fn get_vlan_configuration(&self) -> Result<VlanConfiguration, Self::Error>;
+
+    /// Returns an error if `conf` does not pass `VlanConfiguration::validate`, or has more interfaces than
+    /// the driver supports
+    fn set_vlan_configuration(&mut self, conf: &VlanConfiguration) -> Result<(), Self::Error>;
+}
+
+impl<E> EthVlan for &mut E
+where
+    E: EthVlan,
+{
+    fn get_vlan_configuration(&self) -> Result<VlanConfiguration, Self::Error> {
+        (**self).get_vlan_configuration()
+    }
+
+    fn set_vlan_configuration(&mut self, conf: &VlanConfiguration) -> Result<(), Self::Error> {
+        (*self).set_vlan_configuration(conf)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub enum EapMethod {
+    /// Authenticates with a client certificate
+    Tls,
+    /// Authenticates with a username and a password, in a TLS tunnel, with MSCHAPv2
+    Peap,
+    /// Authenticates with a username and a password, in a TLS tunnel, with the `Phase2Method`
+    Ttls,
+}
+
+impl Default for EapMethod {
+    fn default() -> Self {
+        Self::Peap
+    }
+}
+
+/// The inner authentication of EAP-TTLS
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub enum Phase2Method {
+    Eap,
+    MsChapV2,
+    MsChap,
+    Pap,
+    Chap,
+}
+
+impl Default for Phase2Method {
+    fn default() -> Self {
+        Self::MsChapV2
+    }
+}
+
+/// The IEEE 802.1X supplicant settings of an Ethernet port, with the fields of WPA2-Enterprise, for
+/// switches which only forward the traffic of authenticated ports
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct EapConfiguration<'a> {
+    pub method: EapMethod,
+    /// The outer identity, sent in the clear, e.g. `anonymous@example.com` with PEAP and TTLS to not
+    /// disclose the username
+    pub identity: &'a str,
+    /// With PEAP and TTLS
+    pub username: &'a str,
+    /// With PEAP and TTLS
+    pub password: &'a str,
+    /// With TTLS
+    pub phase2: Phase2Method,
+    /// Verifies the certificate of the authentication server; `None` accepts any server, which exposes
+    /// the password to rogue ones with PEAP and TTLS
+    pub ca_certificate: Option<X509<'a>>,
+    /// The name the server certificate must be for, if any
+    pub server_name: Option<&'a str>,
+    /// With TLS
+    pub client_certificate: Option<X509<'a>>,
+    /// With TLS
+    pub private_key: Option<X509<'a>>,
+    /// A private key held by the crypto provider (e.g. a secure element), used instead of `private_key`
+    pub private_key_handle: Option<KeyHandle>,
+}
+
+impl<'a> EapConfiguration<'a> {
+    /// Checks that the credentials of the method are set, as backends do before applying the
+    /// configuration
+    pub fn validate(&self) -> Result<(), &'static str> {
+        match self.method {
+            EapMethod::Tls => {
+                if self.client_certificate.is_none() {
+                    return Err("EAP-TLS requires a client certificate");
+                }
+
+                if self.private_key.is_none() && self.private_key_handle.is_none() {
+                    return Err("EAP-TLS requires a private key");
+                }
+            }
+            EapMethod::Peap | EapMethod::Ttls => {
+                if self.username.is_empty() || self.password.is_empty() {
+                    return Err("EAP-PEAP and EAP-TTLS require a username and a password");
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<'a> Debug for EapConfiguration<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EapConfiguration")
+            .field("method", &self.method)
+            .field("identity", &self.identity)
+            .field("username", &self.username)
+            .field("phase2", &self.phase2)
+            .field("ca_certificate", &self.ca_certificate)
+            .field("server_name", &self.server_name)
+            .field("client_certificate", &self.client_certificate)
+            .field("private_key_handle", &self.private_key_handle)
+            .finish()
+    }
+}
+
+/// Like `Debug`, leaves the password and the private key out
+#[cfg(feature = "defmt")]
+impl<'a> defmt::Format for EapConfiguration<'a> {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        defmt::write!(
+            f,
+            "EapConfiguration {{ method: {}, identity: {}, username: {}, phase2: {}, ca_certificate: {}, server_name: {}, client_certificate: {}, private_key_handle: {} }}",
+            self.method,
+            self.identity,
+            self.username,
+            self.phase2,
+            self.ca_certificate,
+            self.server_name,
+            self.client_certificate,
+            self.private_key_handle,
+        )
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub enum EapStatus {
+    /// 802.1X is not configured
+    Disabled,
+    /// The link is down, or the authentication is in progress
+    Authenticating,
+    Authorized,
+    /// The server rejected the credentials, or its certificate was not trusted; the supplicant retries
+    /// once the switch restarts the authentication, or the link comes up again
+    Failed,
+}
+
+/// Implemented by Ethernet drivers with an 802.1X supplicant.
+///
+/// The IP configuration of the interface, e.g. DHCP, only starts once the port is authorized.
+pub trait EthEap: Eth {
+    /// `None` disables 802.1X. Returns an error if `conf` does not pass `EapConfiguration::validate`.
+    fn set_eap_configuration(
+        &mut self,
+        conf: Option<&EapConfiguration<'_>>,
+    ) -> Result<(), Self::Error>;
+
+    fn get_eap_status(&self) -> Result<EapStatus, Self::Error>;
+}
+
+impl<E> EthEap for &mut E
+where
+    E: EthEap,
+{
+    fn set_eap_configuration(
+        &mut self,
+        conf: Option<&EapConfiguration<'_>>,
+    ) -> Result<(), Self::Error> {
+        (*self).set_eap_configuration(conf)
+    }
+
+    fn get_eap_status(&self) -> Result<EapStatus, Self::Error> {
+        (**self).get_eap_status()
+    }
+}
@@ -0,0 +1,346 @@
+//! Active-backup bonding of the Ethernet ports of devices with two PHYs: the ports share one logical
+//! interface, whose traffic goes through a single port at a time and fails over to the other one when
+//! its link goes down, e.g. for a device with redundant connections to two switches.
+//!
+//! The links are monitored by `ActiveBackup`, driven by the application from the callback of a timer
+//! armed with `ActiveBackup::arm`, which selects the port with `EthBond::set_active_port` and posts a
+//! `BondEvent` on each failover.
+
+use core::fmt::Debug;
+use core::time::Duration;
+
+#[cfg(feature = "use_serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::error::impl_error;
+use crate::event_bus::Postbox;
+use crate::timer::PeriodicTimer;
+
+/// Implemented by Ethernet drivers with several ports behind one logical interface
+pub trait EthBond {
+    type Error: Debug;
+
+    fn is_port_up(&self, port: u8) -> Result<bool, Self::Error>;
+
+    /// Sends and receives the traffic of the logical interface through `port`, which keeps its MAC
+    /// address, or through no port if `None`. Drivers send a gratuitous ARP through the new port, so that
+    /// the switches learn where the interface moved.
+    fn set_active_port(&mut self, port: Option<u8>) -> Result<(), Self::Error>;
+}
+
+impl<B> EthBond for &mut B
+where
+    B: EthBond,
+{
+    type Error = B::Error;
+
+    fn is_port_up(&self, port: u8) -> Result<bool, Self::Error> {
+        (**self).is_port_up(port)
+    }
+
+    fn set_active_port(&mut self, port: Option<u8>) -> Result<(), Self::Error> {
+        (*self).set_active_port(port)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub struct BondMember {
+    /// As numbered by the driver
+    pub port: u8,
+    /// Of the ports whose link is up, the one with the highest priority is active; the first listed one
+    /// on a tie
+    pub priority: u8,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub struct BondConfiguration {
+    pub members: [BondMember; 2],
+    /// How often the links are checked, which bounds how long the traffic is lost on a failover
+    pub link_monitor_interval: Duration,
+    /// How long a link must have been up before the traffic moves to its port, so that a flapping link is
+    /// not used; a link coming up while no port is active, or while the link of the active port is down, is
+    /// used right away
+    pub up_delay: Duration,
+    /// Whether the traffic moves back to the port of higher priority once its link is up again, rather
+    /// than staying on the active port until its link goes down
+    pub preempt: bool,
+}
+
+impl Default for BondConfiguration {
+    fn default() -> Self {
+        Self {
+            members: [
+                BondMember {
+                    port: 0,
+                    priority: 1,
+                },
+                BondMember {
+                    port: 1,
+                    priority: 0,
+                },
+            ],
+            link_monitor_interval: Duration::from_millis(100),
+            up_delay: Duration::from_secs(2),
+            preempt: true,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub enum BondEvent {
+    /// The traffic moved from port `from` to port `to`; `None` when no port was or is active, i.e. the
+    /// links were or are all down
+    Failover { from: Option<u8>, to: Option<u8> },
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum BondError<B, P> {
+    BondError(B),
+    PostError(P),
+}
+
+impl_error! {
+    BondError<B: Debug, P: Debug> {
+        BondError(e) => "Bond error: {e:?}"; e.error_kind(),
+        PostError(e) => "Post error: {e:?}"; e.error_kind(),
+    }
+}
+
+/// Monitors the links of the members of a bond, and fails over between them
+pub struct ActiveBackup<B, P> {
+    bond: B,
+    postbox: P,
+    configuration: BondConfiguration,
+    /// The number of consecutive checks each link was up
+    up_checks: [u32; 2],
+    active: Option<usize>,
+}
+
+impl<B, P> ActiveBackup<B, P>
+where
+    B: EthBond,
+    P: Postbox<BondEvent>,
+{
+    pub fn new(bond: B, postbox: P, configuration: BondConfiguration) -> Self {
+        Self {
+            bond,
+            postbox,
+            configuration,
+            up_checks: [0; 2],
+            active: None,
+        }
+    }
+
+    pub fn configuration(&self) -> &BondConfiguration {
+        &self.configuration
+    }
+
+    /// Schedules `timer` to fire at the link monitor interval
+    pub fn arm<O>(&self, timer: &mut O) -> Result<(), O::Error>
+    where
+        O: PeriodicTimer,
+    {
+        timer.every(self.configuration.link_monitor_interval)
+    }
+
+    /// The port the traffic goes through
+    pub fn active_port(&self) -> Option<u8> {
+        self.active
+            .map(|index| self.configuration.members[index].port)
+    }
+
+    /// Checks the links, and fails over if the active port is no longer the one to use; returns the
+    /// active port
+    pub fn check(&mut self) -> Result<Option<u8>, BondError<B::Error, P::Error>> {
+        let members = self.configuration.members;
+
+        for (member, up_checks) in members.iter().zip(self.up_checks.iter_mut()) {
+            *up_checks = if self
+                .bond
+                .is_port_up(member.port)
+                .map_err(BondError::BondError)?
+            {
+                up_checks.saturating_add(1)
+            } else {
+                0
+            };
+        }
+
+        let interval = self.configuration.link_monitor_interval.as_micros().max(1);
+        let up_delay_checks = (self.configuration.up_delay.as_micros() + interval - 1) / interval;
+
+        let usable = |index: usize| {
+            let up_checks = self.up_checks[index];
+
+            // The up delay is waived once the link of the active port is down, to fail over right away
+            up_checks > 0
+                && (self.active.is_none()
+                    || self.active == Some(index)
+                    || self
+                        .active
+                        .map_or(false, |active| self.up_checks[active] == 0)
+                    || up_checks as u128 > up_delay_checks)
+        };
+
+        let keep = !self.configuration.preempt && self.active.map_or(false, usable);
+
+        let active = if keep {
+            self.active
+        } else {
+            (0..members.len()).filter(|index| usable(*index)).fold(
+                None,
+                |best: Option<usize>, index| match best {
+                    Some(best) if members[best].priority >= members[index].priority => Some(best),
+                    _ => Some(index),
+                },
+            )
+        };
+
+        if active != self.active {
+            let to = active.map(|index| members[index].port);
+
+            self.bond
+                .set_active_port(to)
+                .map_err(BondError::BondError)?;
+
+            let event = BondEvent::Failover {
+                from: self.active_port(),
+                to,
+            };
+
+            self.active = active;
+
+            self.postbox
+                .post(&event, None)
+                .map_err(BondError::PostError)?;
+        }
+
+        Ok(self.active_port())
+    }
+
+    pub fn release(self) -> (B, P) {
+        (self.bond, self.postbox)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::RefCell;
+    use core::convert::Infallible;
+
+    use crate::event_bus::ErrorType;
+
+    use super::*;
+
+    struct Bond {
+        up: [bool; 2],
+        active: Option<u8>,
+    }
+
+    impl EthBond for Bond {
+        type Error = Infallible;
+
+        fn is_port_up(&self, port: u8) -> Result<bool, Self::Error> {
+            Ok(self.up[port as usize])
+        }
+
+        fn set_active_port(&mut self, port: Option<u8>) -> Result<(), Self::Error> {
+            self.active = port;
+
+            Ok(())
+        }
+    }
+
+    struct Events(RefCell<heapless::Vec<BondEvent, 8>>);
+
+    impl ErrorType for Events {
+        type Error = Infallible;
+    }
+
+    impl Postbox<BondEvent> for Events {
+        fn post(&self, payload: &BondEvent, _wait: Option<Duration>) -> Result<bool, Self::Error> {
+            self.0.borrow_mut().push(*payload).unwrap();
+
+            Ok(true)
+        }
+    }
+
+    fn bond(up: [bool; 2], preempt: bool) -> ActiveBackup<Bond, Events> {
+        ActiveBackup::new(
+            Bond { up, active: None },
+            Events(RefCell::new(heapless::Vec::new())),
+            BondConfiguration {
+                preempt,
+                ..Default::default()
+            },
+        )
+    }
+
+    fn set_up(bond: &mut ActiveBackup<Bond, Events>, up: [bool; 2]) {
+        bond.bond.up = up;
+    }
+
+    #[test]
+    fn failover_without_up_delay() {
+        for preempt in [false, true] {
+            let mut bond = bond([true, false], preempt);
+
+            assert_eq!(bond.check().unwrap(), Some(0));
+
+            // The backup link comes up as the active one goes down: the traffic moves right away, rather than
+            // being lost for the up delay
+            set_up(&mut bond, [false, true]);
+
+            assert_eq!(bond.check().unwrap(), Some(1));
+
+            let (bond, events) = bond.release();
+
+            assert_eq!(bond.active, Some(1));
+            assert_eq!(
+                events.0.borrow().as_slice(),
+                [
+                    BondEvent::Failover {
+                        from: None,
+                        to: Some(0)
+                    },
+                    BondEvent::Failover {
+                        from: Some(0),
+                        to: Some(1)
+                    },
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn up_delay() {
+        for preempt in [false, true] {
+            let mut bond = bond([false, true], preempt);
+
+            assert_eq!(bond.check().unwrap(), Some(1));
+
+            // The port of higher priority is used once its link was up for the 2 seconds of 20 checks, and
+            // only with preemption
+            set_up(&mut bond, [true, true]);
+
+            for _ in 0..20 {
+                assert_eq!(bond.check().unwrap(), Some(1));
+            }
+
+            assert_eq!(
+                bond.check().unwrap(),
+                if preempt { Some(0) } else { Some(1) }
+            );
+        }
+    }
+}
@@ -24,7 +24,15 @@ pub trait Spin: ErrorType {
     fn spin(&mut self, duration: Option<Duration>) -> Result<(), Self::Error>;
 }
 
+/// The sending side of an event bus.
+///
+/// Delivery guarantees, which all implementations should provide:
+/// - Payloads posted through the same postbox are delivered to each subscriber in the order they were posted
+/// - Each subscriber receives each payload at most once; a payload is dropped only when `post` returns `Ok(false)`
+/// - Subscription callbacks are never called from within `post`, but from the context of the event bus
 pub trait Postbox<P>: ErrorType {
+    /// Waits forever for room in the event bus queue when `wait` is `None`.
+    /// Returns `Ok(false)` if the payload could not be queued within `wait`.
     fn post(&self, payload: &P, wait: Option<Duration>) -> Result<bool, Self::Error>;
 }
 
@@ -46,6 +54,31 @@ where
     }
 }
 
+/// A postbox which can also be used from interrupt context (or from driver callbacks
+/// which must not block), with the same delivery guarantees as `Postbox`.
+pub trait IsrPostbox<P>: Postbox<P> {
+    /// Never blocks. Returns `Ok(false)` if the event bus queue is full.
+    fn post_from_isr(&self, payload: &P) -> Result<bool, Self::Error>;
+}
+
+impl<P, PB> IsrPostbox<P> for &mut PB
+where
+    PB: IsrPostbox<P> + ErrorType,
+{
+    fn post_from_isr(&self, payload: &P) -> Result<bool, Self::Error> {
+        (**self).post_from_isr(payload)
+    }
+}
+
+impl<P, PB> IsrPostbox<P> for &PB
+where
+    PB: IsrPostbox<P> + ErrorType,
+{
+    fn post_from_isr(&self, payload: &P) -> Result<bool, Self::Error> {
+        (*self).post_from_isr(payload)
+    }
+}
+
 pub trait EventBus<P>: ErrorType {
     type Subscription;
 
@@ -83,6 +116,8 @@ where
     }
 }
 
+/// Postboxes are independent of the subscriptions of the event bus, and are expected to be
+/// cheap to clone, so that these can be handed out to drivers and other threads.
 pub trait PostboxProvider<P>: ErrorType {
     type Postbox: Postbox<P, Error = Self::Error>;
 
@@ -111,6 +146,94 @@ where
     }
 }
 
+pub type EventId = u32;
+
+/// Marks a payload which is posted to an event bus shared with other event types.
+///
+/// The ID is used by the event bus for routing, so that subscribers only ever see (and clone)
+/// the payloads of the event type they subscribed for. It should therefore be stable across
+/// builds, and unique among the event types posted to the same event bus.
+pub trait Event: Clone + Send + 'static {
+    const ID: EventId;
+}
+
+pub trait TypedPostbox: ErrorType {
+    /// Same semantics as `Postbox::post`
+    fn post_typed<E>(&self, payload: &E, wait: Option<Duration>) -> Result<bool, Self::Error>
+    where
+        E: Event;
+}
+
+impl<PB> TypedPostbox for &mut PB
+where
+    PB: TypedPostbox,
+{
+    fn post_typed<E>(&self, payload: &E, wait: Option<Duration>) -> Result<bool, Self::Error>
+    where
+        E: Event,
+    {
+        (**self).post_typed(payload, wait)
+    }
+}
+
+impl<PB> TypedPostbox for &PB
+where
+    PB: TypedPostbox,
+{
+    fn post_typed<E>(&self, payload: &E, wait: Option<Duration>) -> Result<bool, Self::Error>
+    where
+        E: Event,
+    {
+        (*self).post_typed(payload, wait)
+    }
+}
+
+pub trait TypedEventBus: ErrorType {
+    type Subscription;
+
+    /// The callback is only called for payloads with ID `E::ID`
+    fn subscribe_typed<E>(
+        &self,
+        callback: impl for<'a> FnMut(&'a E) + Send + 'static,
+    ) -> Result<Self::Subscription, Self::Error>
+    where
+        E: Event;
+}
+
+impl<B> TypedEventBus for &mut B
+where
+    B: TypedEventBus,
+{
+    type Subscription = B::Subscription;
+
+    fn subscribe_typed<E>(
+        &self,
+        callback: impl for<'a> FnMut(&'a E) + Send + 'static,
+    ) -> Result<Self::Subscription, Self::Error>
+    where
+        E: Event,
+    {
+        (**self).subscribe_typed(callback)
+    }
+}
+
+impl<B> TypedEventBus for &B
+where
+    B: TypedEventBus,
+{
+    type Subscription = B::Subscription;
+
+    fn subscribe_typed<E>(
+        &self,
+        callback: impl for<'a> FnMut(&'a E) + Send + 'static,
+    ) -> Result<Self::Subscription, Self::Error>
+    where
+        E: Event,
+    {
+        (*self).subscribe_typed(callback)
+    }
+}
+
 #[cfg(all(feature = "nightly", feature = "experimental"))]
 pub mod asynch {
     pub use super::{ErrorType, Spin};
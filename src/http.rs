@@ -48,6 +48,55 @@ pub enum Method {
     MkCalendar,
     Link,
     Unlink,
+    /// Any other (e.g. extension) method; the name is sent verbatim
+    Custom(&'static str),
+}
+
+impl Method {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Delete => "DELETE",
+            Self::Get => "GET",
+            Self::Head => "HEAD",
+            Self::Post => "POST",
+            Self::Put => "PUT",
+            Self::Connect => "CONNECT",
+            Self::Options => "OPTIONS",
+            Self::Trace => "TRACE",
+            Self::Copy => "COPY",
+            Self::Lock => "LOCK",
+            Self::MkCol => "MKCOL",
+            Self::Move => "MOVE",
+            Self::Propfind => "PROPFIND",
+            Self::Proppatch => "PROPPATCH",
+            Self::Search => "SEARCH",
+            Self::Unlock => "UNLOCK",
+            Self::Bind => "BIND",
+            Self::Rebind => "REBIND",
+            Self::Unbind => "UNBIND",
+            Self::Acl => "ACL",
+            Self::Report => "REPORT",
+            Self::MkActivity => "MKACTIVITY",
+            Self::Checkout => "CHECKOUT",
+            Self::Merge => "MERGE",
+            Self::MSearch => "M-SEARCH",
+            Self::Notify => "NOTIFY",
+            Self::Subscribe => "SUBSCRIBE",
+            Self::Unsubscribe => "UNSUBSCRIBE",
+            Self::Patch => "PATCH",
+            Self::Purge => "PURGE",
+            Self::MkCalendar => "MKCALENDAR",
+            Self::Link => "LINK",
+            Self::Unlink => "UNLINK",
+            Self::Custom(name) => name,
+        }
+    }
+}
+
+impl core::fmt::Display for Method {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
 }
 
 pub trait Headers {
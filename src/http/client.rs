@@ -1,7 +1,36 @@
+use core::time::Duration;
+
 use crate::io::{Error, Io, Read, Write};
 
 pub use super::{Headers, Method, Status};
 
+pub mod proxy;
+
+/// Per-phase timeouts. Each timeout bounds the whole phase rather than a single I/O operation,
+/// so that e.g. a slow TLS handshake does not eat into the time allotted for reading the response.
+/// `None` leaves the backend default in place.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Timeouts {
+    /// Name resolution, TCP connect and TLS handshake
+    pub connect: Option<Duration>,
+    /// Sending the request headers and body
+    pub write: Option<Duration>,
+    /// Receiving the response headers, and then each read of the response body
+    pub read: Option<Duration>,
+}
+
+/// The configuration of the connections of a client, for backends which let the application provide it
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Configuration {
+    pub timeouts: Timeouts,
+    /// Connects through a proxy with `proxy::tunnel`; `None` connects directly
+    pub proxy: Option<proxy::Proxy>,
+}
+
 #[derive(Debug)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct Client<C>(C);
@@ -50,6 +79,14 @@ where
         self.request(Method::Delete, uri, &[])
     }
 
+    pub fn patch<'a>(
+        &'a mut self,
+        uri: &'a str,
+        headers: &'a [(&'a str, &'a str)],
+    ) -> Result<Request<&'a mut C>, C::Error> {
+        self.request(Method::Patch, uri, headers)
+    }
+
     pub fn request<'a>(
         &'a mut self,
         method: Method,
@@ -61,6 +98,11 @@ where
         Ok(Request::wrap(&mut self.0))
     }
 
+    /// Applies to all subsequent requests
+    pub fn set_timeouts(&mut self, timeouts: &Timeouts) -> Result<(), C::Error> {
+        self.0.set_timeouts(timeouts)
+    }
+
     pub fn raw_connection(&mut self) -> Result<&mut C::RawConnection, C::Error> {
         self.0.raw_connection()
     }
@@ -216,6 +258,8 @@ pub trait Connection: Status + Headers + Read + Write {
 
     fn split(&mut self) -> (&Self::Headers, &mut Self::Read);
 
+    fn set_timeouts(&mut self, timeouts: &Timeouts) -> Result<(), Self::Error>;
+
     fn raw_connection(&mut self) -> Result<&mut Self::RawConnection, Self::Error>;
 }
 
@@ -256,6 +300,10 @@ where
         (*self).split()
     }
 
+    fn set_timeouts(&mut self, timeouts: &Timeouts) -> Result<(), Self::Error> {
+        (*self).set_timeouts(timeouts)
+    }
+
     fn raw_connection(&mut self) -> Result<&mut Self::RawConnection, Self::Error> {
         (*self).raw_connection()
     }
@@ -271,6 +319,8 @@ pub mod asynch {
     pub use crate::http::asynch::*;
     pub use crate::http::{Headers, Method, Status};
 
+    pub use super::Timeouts;
+
     #[derive(Debug)]
     #[cfg_attr(feature = "defmt", derive(defmt::Format))]
     pub struct Client<C>(C);
@@ -322,6 +372,14 @@ pub mod asynch {
             self.request(Method::Delete, uri, &[]).await
         }
 
+        pub async fn patch<'a>(
+            &'a mut self,
+            uri: &'a str,
+            headers: &'a [(&'a str, &'a str)],
+        ) -> Result<Request<&'a mut C>, C::Error> {
+            self.request(Method::Patch, uri, headers).await
+        }
+
         pub async fn request<'a>(
             &'a mut self,
             method: Method,
@@ -333,6 +391,11 @@ pub mod asynch {
             Ok(Request::wrap(&mut self.0))
         }
 
+        /// Applies to all subsequent requests
+        pub fn set_timeouts(&mut self, timeouts: &Timeouts) -> Result<(), C::Error> {
+            self.0.set_timeouts(timeouts)
+        }
+
         pub fn raw_connection(&mut self) -> Result<&mut C::RawConnection, C::Error> {
             self.0.raw_connection()
         }
@@ -505,6 +568,8 @@ pub mod asynch {
 
         fn split(&mut self) -> (&Self::Headers, &mut Self::Read);
 
+        fn set_timeouts(&mut self, timeouts: &Timeouts) -> Result<(), Self::Error>;
+
         fn raw_connection(&mut self) -> Result<&mut Self::RawConnection, Self::Error>;
     }
 
@@ -551,6 +616,10 @@ pub mod asynch {
             (*self).split()
         }
 
+        fn set_timeouts(&mut self, timeouts: &Timeouts) -> Result<(), Self::Error> {
+            (*self).set_timeouts(timeouts)
+        }
+
         fn raw_connection(&mut self) -> Result<&mut Self::RawConnection, Self::Error> {
             (*self).raw_connection()
         }
@@ -683,6 +752,10 @@ pub mod asynch {
             (headers, &mut self.lended_read)
         }
 
+        fn set_timeouts(&mut self, timeouts: &Timeouts) -> Result<(), Self::Error> {
+            self.connection.set_timeouts(timeouts)
+        }
+
         fn raw_connection(&mut self) -> Result<&mut Self::RawConnection, Self::Error> {
             let connection = self.connection.raw_connection()?;
 
@@ -822,6 +895,10 @@ pub mod asynch {
             (headers, &mut self.lended_read)
         }
 
+        fn set_timeouts(&mut self, timeouts: &Timeouts) -> Result<(), Self::Error> {
+            self.connection.set_timeouts(timeouts)
+        }
+
         fn raw_connection(&mut self) -> Result<&mut Self::RawConnection, Self::Error> {
             let raw_connection = self.connection.raw_connection()?;
 
@@ -0,0 +1,652 @@
+//! Connecting through an HTTP (`CONNECT`, RFC 9110) or SOCKS5 (RFC 1928) proxy, as required on corporate
+//! networks which only reach the Internet through one.
+//!
+//! `tunnel` asks a proxy to connect to the destination over an established connection to the proxy, after
+//! which the connection carries the traffic to the destination, e.g. the TLS handshake. `ProxyStack`
+//! connects to the proxy with a `TcpStack` and tunnels right away.
+
+use core::fmt::{self, Debug, Write as _};
+use core::str;
+
+#[cfg(feature = "use_serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::error::{impl_error, ErrorKind};
+use crate::io::{Read, ReadExactError, Write};
+use crate::ipv4::{IpAddr, SocketAddrV4};
+use crate::net::tcp::TcpStack;
+use crate::utils::codec::base64;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub enum ProxyScheme {
+    /// Tunnels with the HTTP `CONNECT` method
+    Http,
+    Socks5,
+}
+
+impl Default for ProxyScheme {
+    fn default() -> Self {
+        Self::Http
+    }
+}
+
+#[derive(Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub struct ProxyAuth {
+    pub username: heapless::String<32>,
+    pub password: heapless::String<64>,
+}
+
+impl Debug for ProxyAuth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProxyAuth")
+            .field("username", &self.username)
+            .finish()
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub struct Proxy {
+    pub scheme: ProxyScheme,
+    /// The name or address of the proxy, which the application resolves to connect to it
+    pub host: heapless::String<64>,
+    pub port: u16,
+    /// Basic authentication for HTTP proxies, and username/password authentication (RFC 1929) for SOCKS5
+    /// proxies
+    pub auth: Option<ProxyAuth>,
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ProxyError<E> {
+    IoError(E),
+    /// The proxy closed the connection before answering
+    UnexpectedEof,
+    /// The destination host is empty or longer than 255 bytes
+    InvalidHost,
+    InvalidResponse,
+    /// The proxy requires credentials, or rejected them
+    Unauthorized,
+    /// The proxy did not connect to the destination; the HTTP status, or the SOCKS5 reply code
+    Refused(u16),
+}
+
+impl_error! {
+    ProxyError<E: Debug> {
+        IoError(e) => "IO error: {e:?}"; e.error_kind(),
+        UnexpectedEof => "Connection closed by the proxy"; ErrorKind::Unavailable,
+        InvalidHost => "Invalid host"; ErrorKind::InvalidInput,
+        InvalidResponse => "Invalid proxy response"; ErrorKind::Other,
+        Unauthorized => "Proxy authentication failed"; ErrorKind::Unauthorized,
+        Refused(code) => "Proxy refused the connection: {code}"; ErrorKind::Unavailable,
+    }
+}
+
+impl<E> From<ReadExactError<E>> for ProxyError<E> {
+    fn from(e: ReadExactError<E>) -> Self {
+        match e {
+            ReadExactError::UnexpectedEof => Self::UnexpectedEof,
+            ReadExactError::Other(e) => Self::IoError(e),
+        }
+    }
+}
+
+/// Asks `proxy` to connect to `host`, a name or an IP address, on `port`, over `connection`, which is
+/// connected to the proxy.
+///
+/// Nothing past the answer of the proxy is read, so that once this returns, `connection` carries the
+/// traffic to the destination.
+pub fn tunnel<C>(
+    connection: &mut C,
+    proxy: &Proxy,
+    host: &str,
+    port: u16,
+) -> Result<(), ProxyError<C::Error>>
+where
+    C: Read + Write,
+{
+    if host.is_empty() || host.len() > 255 {
+        return Err(ProxyError::InvalidHost);
+    }
+
+    match proxy.scheme {
+        ProxyScheme::Http => http_connect(connection, proxy.auth.as_ref(), host, port),
+        ProxyScheme::Socks5 => socks5_connect(connection, proxy.auth.as_ref(), host, port),
+    }
+}
+
+/// Connects through a proxy at a fixed address
+pub struct ProxyStack<T> {
+    stack: T,
+    proxy: Proxy,
+    address: SocketAddrV4,
+}
+
+impl<T> ProxyStack<T>
+where
+    T: TcpStack,
+{
+    /// `address` is the one `proxy.host` resolves to
+    pub fn new(stack: T, proxy: Proxy, address: SocketAddrV4) -> Self {
+        Self {
+            stack,
+            proxy,
+            address,
+        }
+    }
+
+    pub fn proxy(&self) -> &Proxy {
+        &self.proxy
+    }
+
+    /// Returns a socket carrying the traffic to `host` on `port`; the socket is closed if tunneling fails
+    pub fn connect(&mut self, host: &str, port: u16) -> Result<T::Socket, ProxyError<T::Error>> {
+        let mut socket = self
+            .stack
+            .connect(self.address)
+            .map_err(ProxyError::IoError)?;
+
+        tunnel(&mut socket, &self.proxy, host, port)?;
+
+        Ok(socket)
+    }
+
+    pub fn release(self) -> T {
+        self.stack
+    }
+}
+
+fn http_connect<C>(
+    connection: &mut C,
+    auth: Option<&ProxyAuth>,
+    host: &str,
+    port: u16,
+) -> Result<(), ProxyError<C::Error>>
+where
+    C: Read + Write,
+{
+    let mut authority = heapless::String::<264>::new();
+
+    // IPv6 addresses are bracketed, as in URIs
+    if host.contains(':') {
+        write!(&mut authority, "[{host}]:{port}").unwrap();
+    } else {
+        write!(&mut authority, "{host}:{port}").unwrap();
+    }
+
+    let mut credentials = heapless::String::<132>::new();
+
+    if let Some(auth) = auth {
+        let mut encoder = base64::Encoder::new(base64::STANDARD, true);
+
+        for part in [auth.username.as_bytes(), b":", auth.password.as_bytes()] {
+            encoder.update(part, &mut credentials).unwrap();
+        }
+
+        encoder.finish(&mut credentials).unwrap();
+    }
+
+    let authorization: [&str; 3] = if auth.is_some() {
+        ["Proxy-Authorization: Basic ", &credentials, "\r\n"]
+    } else {
+        [""; 3]
+    };
+
+    let request = [
+        "CONNECT ",
+        &authority,
+        " HTTP/1.1\r\nHost: ",
+        &authority,
+        "\r\n",
+    ];
+
+    for part in request
+        .iter()
+        .chain(authorization.iter())
+        .chain(["\r\n"].iter())
+    {
+        connection
+            .write_all(part.as_bytes())
+            .map_err(ProxyError::IoError)?;
+    }
+
+    connection.flush().map_err(ProxyError::IoError)?;
+
+    // The response is read byte by byte, so as not to read the data of the destination following it
+    let mut status_line = heapless::Vec::<u8, 64>::new();
+    let mut in_status_line = true;
+    let mut tail = [0_u8; 4];
+
+    for _ in 0..4096 {
+        let mut byte = [0_u8];
+        connection.read_exact(&mut byte)?;
+
+        tail.rotate_left(1);
+        tail[3] = byte[0];
+
+        if in_status_line {
+            if byte[0] == b'\n' {
+                in_status_line = false;
+            } else {
+                // Only the status code, at the start, is needed
+                let _ = status_line.push(byte[0]);
+            }
+        }
+
+        if &tail == b"\r\n\r\n" {
+            let status = parse_status(&status_line).ok_or(ProxyError::InvalidResponse)?;
+
+            return match status {
+                200..=299 => Ok(()),
+                407 => Err(ProxyError::Unauthorized),
+                status => Err(ProxyError::Refused(status)),
+            };
+        }
+    }
+
+    Err(ProxyError::InvalidResponse)
+}
+
+fn parse_status(status_line: &[u8]) -> Option<u16> {
+    let status_line = str::from_utf8(status_line).ok()?;
+
+    let mut parts = status_line.split(' ');
+
+    if !parts.next()?.starts_with("HTTP/1.") {
+        return None;
+    }
+
+    let status = parts.next()?.trim_end_matches('\r');
+
+    if status.len() == 3 && status.bytes().all(|b| b.is_ascii_digit()) {
+        status.parse().ok()
+    } else {
+        None
+    }
+}
+
+const SOCKS_VERSION: u8 = 5;
+
+const SOCKS_NO_AUTH: u8 = 0x00;
+const SOCKS_USERNAME_PASSWORD: u8 = 0x02;
+const SOCKS_NO_ACCEPTABLE_METHODS: u8 = 0xff;
+
+const SOCKS_CONNECT: u8 = 0x01;
+
+const SOCKS_IPV4: u8 = 0x01;
+const SOCKS_DOMAIN_NAME: u8 = 0x03;
+const SOCKS_IPV6: u8 = 0x04;
+
+fn socks5_connect<C>(
+    connection: &mut C,
+    auth: Option<&ProxyAuth>,
+    host: &str,
+    port: u16,
+) -> Result<(), ProxyError<C::Error>>
+where
+    C: Read + Write,
+{
+    let greeting: &[u8] = if auth.is_some() {
+        &[SOCKS_VERSION, 2, SOCKS_NO_AUTH, SOCKS_USERNAME_PASSWORD]
+    } else {
+        &[SOCKS_VERSION, 1, SOCKS_NO_AUTH]
+    };
+
+    connection
+        .write_all(greeting)
+        .map_err(ProxyError::IoError)?;
+    connection.flush().map_err(ProxyError::IoError)?;
+
+    let mut reply = [0_u8; 2];
+    connection.read_exact(&mut reply)?;
+
+    match (reply, auth) {
+        ([SOCKS_VERSION, SOCKS_NO_AUTH], _) => (),
+        ([SOCKS_VERSION, SOCKS_USERNAME_PASSWORD], Some(auth)) => {
+            socks5_authenticate(connection, auth)?
+        }
+        ([SOCKS_VERSION, SOCKS_NO_ACCEPTABLE_METHODS], _) => return Err(ProxyError::Unauthorized),
+        _ => return Err(ProxyError::InvalidResponse),
+    }
+
+    let mut request = heapless::Vec::<u8, 262>::new();
+    request
+        .extend_from_slice(&[SOCKS_VERSION, SOCKS_CONNECT, 0])
+        .unwrap();
+
+    match host.trim_start_matches('[').trim_end_matches(']').parse() {
+        Ok(IpAddr::V4(addr)) => {
+            request.push(SOCKS_IPV4).unwrap();
+            request.extend_from_slice(&addr.octets()).unwrap();
+        }
+        Ok(IpAddr::V6(addr)) => {
+            request.push(SOCKS_IPV6).unwrap();
+            request.extend_from_slice(&addr.octets()).unwrap();
+        }
+        // Resolved by the proxy, which is often the only one able to
+        Err(_) => {
+            request.push(SOCKS_DOMAIN_NAME).unwrap();
+            request.push(host.len() as u8).unwrap();
+            request.extend_from_slice(host.as_bytes()).unwrap();
+        }
+    }
+
+    request.extend_from_slice(&port.to_be_bytes()).unwrap();
+
+    connection
+        .write_all(&request)
+        .map_err(ProxyError::IoError)?;
+    connection.flush().map_err(ProxyError::IoError)?;
+
+    let mut reply = [0_u8; 4];
+    connection.read_exact(&mut reply)?;
+
+    if reply[0] != SOCKS_VERSION {
+        return Err(ProxyError::InvalidResponse);
+    }
+
+    // The address and port the proxy connected from, which are of no use here
+    let bound_len = match reply[3] {
+        SOCKS_IPV4 => 4,
+        SOCKS_IPV6 => 16,
+        SOCKS_DOMAIN_NAME => {
+            let mut len = [0_u8];
+            connection.read_exact(&mut len)?;
+
+            len[0] as usize
+        }
+        _ => return Err(ProxyError::InvalidResponse),
+    };
+
+    let mut bound = [0_u8; 255 + 2];
+    connection.read_exact(&mut bound[..bound_len + 2])?;
+
+    match reply[1] {
+        0 => Ok(()),
+        code => Err(ProxyError::Refused(code as u16)),
+    }
+}
+
+fn socks5_authenticate<C>(connection: &mut C, auth: &ProxyAuth) -> Result<(), ProxyError<C::Error>>
+where
+    C: Read + Write,
+{
+    let mut request = heapless::Vec::<u8, 99>::new();
+
+    request.push(1).unwrap();
+    request.push(auth.username.len() as u8).unwrap();
+    request.extend_from_slice(auth.username.as_bytes()).unwrap();
+    request.push(auth.password.len() as u8).unwrap();
+    request.extend_from_slice(auth.password.as_bytes()).unwrap();
+
+    connection
+        .write_all(&request)
+        .map_err(ProxyError::IoError)?;
+    connection.flush().map_err(ProxyError::IoError)?;
+
+    let mut reply = [0_u8; 2];
+    connection.read_exact(&mut reply)?;
+
+    match reply {
+        [1, 0] => Ok(()),
+        [1, _] => Err(ProxyError::Unauthorized),
+        _ => Err(ProxyError::InvalidResponse),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::convert::Infallible;
+
+    use crate::io::Io;
+
+    use super::*;
+
+    /// Answers with `rx`, and keeps what is sent
+    struct Connection<'a> {
+        rx: &'a [u8],
+        tx: heapless::Vec<u8, 256>,
+    }
+
+    impl<'a> Connection<'a> {
+        fn new(rx: &'a [u8]) -> Self {
+            Self {
+                rx,
+                tx: heapless::Vec::new(),
+            }
+        }
+    }
+
+    impl<'a> Io for Connection<'a> {
+        type Error = Infallible;
+    }
+
+    impl<'a> Read for Connection<'a> {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+            let len = buf.len().min(self.rx.len());
+
+            buf[..len].copy_from_slice(&self.rx[..len]);
+            self.rx = &self.rx[len..];
+
+            Ok(len)
+        }
+    }
+
+    impl<'a> Write for Connection<'a> {
+        fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+            self.tx.extend_from_slice(buf).unwrap();
+
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> Result<(), Self::Error> {
+            Ok(())
+        }
+    }
+
+    fn proxy(scheme: ProxyScheme, auth: bool) -> Proxy {
+        Proxy {
+            scheme,
+            host: "proxy.example.com".into(),
+            port: 3128,
+            auth: auth.then(|| ProxyAuth {
+                username: "user".into(),
+                password: "pass".into(),
+            }),
+        }
+    }
+
+    #[test]
+    fn http_connect() {
+        let mut connection = Connection::new(
+            b"HTTP/1.1 200 Connection established\r\nProxy-Agent: test\r\n\r\n\x16\x03\x03",
+        );
+
+        tunnel(
+            &mut connection,
+            &proxy(ProxyScheme::Http, true),
+            "example.com",
+            443,
+        )
+        .unwrap();
+
+        assert_eq!(
+            connection.tx,
+            b"CONNECT example.com:443 HTTP/1.1\r\nHost: example.com:443\r\n\
+            Proxy-Authorization: Basic dXNlcjpwYXNz\r\n\r\n"[..]
+        );
+        // The data of the destination is left to read
+        assert_eq!(connection.rx, b"\x16\x03\x03");
+
+        let mut connection = Connection::new(b"HTTP/1.0 200 OK\r\n\r\n");
+
+        tunnel(
+            &mut connection,
+            &proxy(ProxyScheme::Http, false),
+            "2001:db8::1",
+            8883,
+        )
+        .unwrap();
+
+        assert_eq!(
+            connection.tx,
+            b"CONNECT [2001:db8::1]:8883 HTTP/1.1\r\nHost: [2001:db8::1]:8883\r\n\r\n"[..]
+        );
+    }
+
+    #[test]
+    fn http_errors() {
+        let connect = |rx: &[u8]| {
+            tunnel(
+                &mut Connection::new(rx),
+                &proxy(ProxyScheme::Http, false),
+                "example.com",
+                443,
+            )
+        };
+
+        assert!(matches!(
+            connect(b"HTTP/1.1 407 Proxy Authentication Required\r\n\r\n"),
+            Err(ProxyError::Unauthorized)
+        ));
+        assert!(matches!(
+            connect(b"HTTP/1.1 403 Forbidden\r\n\r\n"),
+            Err(ProxyError::Refused(403))
+        ));
+        assert!(matches!(
+            connect(b"SSH-2.0-OpenSSH\r\n\r\n"),
+            Err(ProxyError::InvalidResponse)
+        ));
+        assert!(matches!(
+            connect(b"HTTP/1.1 200 OK\r\n"),
+            Err(ProxyError::UnexpectedEof)
+        ));
+        assert!(matches!(
+            tunnel(
+                &mut Connection::new(b""),
+                &proxy(ProxyScheme::Http, false),
+                "",
+                443
+            ),
+            Err(ProxyError::InvalidHost)
+        ));
+    }
+
+    #[test]
+    fn status() {
+        assert_eq!(
+            parse_status(b"HTTP/1.1 200 Connection established\r"),
+            Some(200)
+        );
+        assert_eq!(parse_status(b"HTTP/1.0 502\r"), Some(502));
+        assert_eq!(parse_status(b"HTTP/2 200\r"), None);
+        assert_eq!(parse_status(b"HTTP/1.1 20 OK\r"), None);
+        assert_eq!(parse_status(b"HTTP/1.1 2x0 OK\r"), None);
+    }
+
+    #[test]
+    fn socks5_connect() {
+        // No authentication; connected from 10.0.0.1:40000
+        let mut connection = Connection::new(&[
+            5, 0, //
+            5, 0, 0, 1, 10, 0, 0, 1, 0x9c, 0x40, //
+            0x16,
+        ]);
+
+        tunnel(
+            &mut connection,
+            &proxy(ProxyScheme::Socks5, false),
+            "example.com",
+            443,
+        )
+        .unwrap();
+
+        assert_eq!(
+            connection.tx,
+            [
+                &[5, 1, 0][..],
+                &[5, 1, 0, 3, 11],
+                b"example.com",
+                &[1, 0xbb],
+            ]
+            .concat()[..]
+        );
+        assert_eq!(connection.rx, [0x16]);
+
+        // Username/password authentication, and a domain name bound address
+        let mut connection = Connection::new(&[
+            5, 2, //
+            1, 0, //
+            5, 0, 0, 3, 5, b'p', b'r', b'o', b'x', b'y', 0x9c, 0x40,
+        ]);
+
+        tunnel(
+            &mut connection,
+            &proxy(ProxyScheme::Socks5, true),
+            "192.168.1.10",
+            1883,
+        )
+        .unwrap();
+
+        assert_eq!(
+            connection.tx,
+            [
+                &[5, 2, 0, 2][..],
+                &[1, 4],
+                b"user",
+                &[4],
+                b"pass",
+                &[5, 1, 0, 1, 192, 168, 1, 10, 0x07, 0x5b],
+            ]
+            .concat()[..]
+        );
+        assert!(connection.rx.is_empty());
+    }
+
+    #[test]
+    fn socks5_errors() {
+        let connect = |rx: &[u8], auth| {
+            tunnel(
+                &mut Connection::new(rx),
+                &proxy(ProxyScheme::Socks5, auth),
+                "example.com",
+                443,
+            )
+        };
+
+        assert!(matches!(
+            connect(&[5, 0xff], false),
+            Err(ProxyError::Unauthorized)
+        ));
+        // Username/password authentication, which was not offered
+        assert!(matches!(
+            connect(&[5, 2], false),
+            Err(ProxyError::InvalidResponse)
+        ));
+        assert!(matches!(
+            connect(&[5, 2, 1, 1], true),
+            Err(ProxyError::Unauthorized)
+        ));
+        // Connection refused
+        assert!(matches!(
+            connect(&[5, 0, 5, 5, 0, 1, 0, 0, 0, 0, 0, 0], false),
+            Err(ProxyError::Refused(5))
+        ));
+        assert!(matches!(
+            connect(&[4, 0], false),
+            Err(ProxyError::InvalidResponse)
+        ));
+        assert!(matches!(
+            connect(&[5, 0, 5, 0, 0, 1, 10], false),
+            Err(ProxyError::UnexpectedEof)
+        ));
+    }
+}
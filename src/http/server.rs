@@ -1,6 +1,8 @@
 use core::fmt::{self, Debug, Display, Write as _};
 
+use crate::error::{Classify, ErrorKind};
 use crate::io::{Error, Read, Write};
+use crate::utils::json;
 
 pub use super::{Headers, Method, Query, Status};
 pub use crate::io::Io;
@@ -158,6 +160,17 @@ pub trait Connection: Query + Headers + Read + Write {
     fn is_response_initiated(&self) -> bool;
 
     fn raw_connection(&mut self) -> Result<&mut Self::RawConnection, Self::Error>;
+
+    /// The SNI name the client asked for, when the request came over TLS
+    fn server_name(&self) -> Option<&'_ str> {
+        None
+    }
+
+    /// Whether the client closed the connection, polled by the handlers which park requests;
+    /// with connections not telling, parked long polls are only released at their timeout
+    fn is_closed(&self) -> bool {
+        false
+    }
 }
 
 impl<C> Connection for &mut C
@@ -192,21 +205,71 @@ where
     fn raw_connection(&mut self) -> Result<&mut Self::RawConnection, Self::Error> {
         (*self).raw_connection()
     }
+
+    fn server_name(&self) -> Option<&'_ str> {
+        (**self).server_name()
+    }
+
+    fn is_closed(&self) -> bool {
+        (**self).is_closed()
+    }
 }
 
-pub struct HandlerError(heapless::String<64>);
+/// Errors converted with `?` are of kind `ErrorKind::Other`; use `HandlerError::classified` to have the
+/// error reported with the status of its kind
+pub struct HandlerError {
+    message: heapless::String<64>,
+    kind: ErrorKind,
+}
 
 impl HandlerError {
     pub fn new(message: &str) -> Self {
-        Self(message.into())
+        Self::with_kind(ErrorKind::Other, message)
+    }
+
+    pub fn with_kind(kind: ErrorKind, message: &str) -> Self {
+        Self {
+            message: message.into(),
+            kind,
+        }
+    }
+
+    pub fn classified<E>(e: E) -> Self
+    where
+        E: Classify + Debug,
+    {
+        let mut error = Self::from_debug(&e);
+
+        error.kind = e.error_kind();
+        error
     }
 
     pub fn message(&self) -> &str {
-        &self.0
+        &self.message
+    }
+
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
     }
 
     pub fn release(self) -> heapless::String<64> {
-        self.0
+        self.message
+    }
+
+    fn from_debug<E>(e: &E) -> Self
+    where
+        E: Debug + ?Sized,
+    {
+        let mut message: heapless::String<64> = "".into();
+
+        if write!(&mut message, "{e:?}").is_err() {
+            message = "(Error string too big)".into();
+        }
+
+        Self {
+            message,
+            kind: ErrorKind::Other,
+        }
     }
 }
 
@@ -215,19 +278,19 @@ where
     E: Debug,
 {
     fn from(e: E) -> Self {
-        let mut string: heapless::String<64> = "".into();
-
-        if write!(&mut string, "{e:?}").is_err() {
-            string = "(Error string too big)".into();
-        }
-
-        Self(string)
+        Self::from_debug(&e)
     }
 }
 
 impl Display for HandlerError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.0)
+        write!(f, "{}", self.message)
+    }
+}
+
+impl Classify for HandlerError {
+    fn error_kind(&self) -> ErrorKind {
+        self.kind
     }
 }
 
@@ -314,6 +377,58 @@ where
     }
 }
 
+/// Responds to the requests failed by the handler with the status of the `ErrorKind` of the error, and a JSON
+/// body such as `{"error":"not_found","message":"No such sensor"}`.
+///
+/// Errors raised once the handler started its response are passed through.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct ErrorMiddleware;
+
+impl<C> Middleware<C> for ErrorMiddleware
+where
+    C: Connection,
+{
+    fn handle<'a, H>(&'a self, connection: &'a mut C, handler: &'a H) -> HandlerResult
+    where
+        H: Handler<C>,
+    {
+        match handler.handle(connection) {
+            Err(error) if !connection.is_response_initiated() => {
+                respond_with_error(connection, &error)?;
+
+                Ok(())
+            }
+            result => result,
+        }
+    }
+}
+
+/// Responds with the status of the `ErrorKind` of `error`, and a JSON body with the kind and the message
+pub fn respond_with_error<C>(connection: &mut C, error: &HandlerError) -> Result<(), C::Error>
+where
+    C: Connection,
+{
+    connection.initiate_response(
+        error.kind().status(),
+        None,
+        &[("Content-Type", "application/json")],
+    )?;
+
+    // Escaping a message of up to 64 characters takes at most 6 bytes per character
+    let mut body = heapless::String::<448>::new();
+
+    write!(
+        &mut body,
+        "{{\"error\":\"{}\",\"message\":\"{}\"}}",
+        error.kind().as_str(),
+        json::Escaped(error.message())
+    )
+    .unwrap();
+
+    connection.write_all(body.as_bytes())?;
+    connection.flush()
+}
+
 #[cfg(all(feature = "nightly", feature = "experimental"))]
 pub mod asynch {
     use core::future::Future;
@@ -490,6 +605,17 @@ pub mod asynch {
         fn is_response_initiated(&self) -> bool;
 
         fn raw_connection(&mut self) -> Result<&mut Self::RawConnection, Self::Error>;
+
+        /// The SNI name the client asked for, when the request came over TLS
+        fn server_name(&self) -> Option<&'_ str> {
+            None
+        }
+
+        /// Whether the client closed the connection, polled by the handlers which park requests;
+        /// with connections not telling, parked long polls are only released at their timeout
+        fn is_closed(&self) -> bool {
+            false
+        }
     }
 
     impl<C> Connection for &mut C
@@ -527,6 +653,14 @@ pub mod asynch {
         fn raw_connection(&mut self) -> Result<&mut Self::RawConnection, Self::Error> {
             (*self).raw_connection()
         }
+
+        fn server_name(&self) -> Option<&'_ str> {
+            (**self).server_name()
+        }
+
+        fn is_closed(&self) -> bool {
+            (**self).is_closed()
+        }
     }
 
     pub trait Handler<C>: Send
@@ -727,6 +861,14 @@ pub mod asynch {
 
             Ok(&mut self.lended_raw)
         }
+
+        fn server_name(&self) -> Option<&'_ str> {
+            self.connection.server_name()
+        }
+
+        fn is_closed(&self) -> bool {
+            self.connection.is_closed()
+        }
     }
 
     // // Implement a blocking handler on top of an async handler
@@ -867,6 +1009,14 @@ pub mod asynch {
 
             Ok(&mut self.lended_raw)
         }
+
+        fn server_name(&self) -> Option<&'_ str> {
+            self.connection.server_name()
+        }
+
+        fn is_closed(&self) -> bool {
+            self.connection.is_closed()
+        }
     }
 
     // // Implement an async handler on top of a blocking handler
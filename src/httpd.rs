@@ -1,6 +1,8 @@
 #![allow(deprecated)]
 
-use core::any::Any;
+use core::any::{Any, TypeId};
+use core::fmt::Debug;
+use core::time::Duration;
 
 extern crate alloc;
 use alloc::collections::BTreeMap;
@@ -92,6 +94,56 @@ impl From<fs::File> for Body {
 
 pub type StateMap = BTreeMap<String, Box<dyn Any>>;
 
+/// A map holding at most one value per type, e.g. for middlewares to pass
+/// the authenticated user or a parsed body to the handlers
+#[derive(Default)]
+pub struct Extensions(BTreeMap<TypeId, Box<dyn Any>>);
+
+impl Extensions {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Returns the value of the same type inserted previously, if any
+    pub fn insert<T: 'static>(&mut self, value: T) -> Option<T> {
+        self.0
+            .insert(TypeId::of::<T>(), Box::new(value))
+            .and_then(|previous| previous.downcast().ok())
+            .map(|previous| *previous)
+    }
+
+    pub fn get<T: 'static>(&self) -> Option<&T> {
+        self.0
+            .get(&TypeId::of::<T>())
+            .and_then(|value| value.downcast_ref())
+    }
+
+    pub fn get_mut<T: 'static>(&mut self) -> Option<&mut T> {
+        self.0
+            .get_mut(&TypeId::of::<T>())
+            .and_then(|value| value.downcast_mut())
+    }
+
+    pub fn remove<T: 'static>(&mut self) -> Option<T> {
+        self.0
+            .remove(&TypeId::of::<T>())
+            .and_then(|value| value.downcast().ok())
+            .map(|value| *value)
+    }
+
+    pub fn contains<T: 'static>(&self) -> bool {
+        self.0.contains_key(&TypeId::of::<T>())
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
 #[cfg(feature = "std")]
 pub type State = Arc<RwLock<StateMap>>;
 
@@ -104,6 +156,7 @@ pub trait RequestDelegate {
 pub struct Request {
     delegate: Box<dyn RequestDelegate>,
     attrs: StateMap,
+    extensions: Extensions,
 
     #[cfg(feature = "std")]
     session: Option<State>,
@@ -122,6 +175,7 @@ impl Request {
         Self {
             delegate,
             attrs: attribs,
+            extensions: Extensions::new(),
             #[cfg(feature = "std")]
             session,
             #[cfg(feature = "std")]
@@ -166,6 +220,19 @@ impl Request {
         &mut self.attrs
     }
 
+    pub fn extensions(&self) -> &Extensions {
+        &self.extensions
+    }
+
+    pub fn extensions_mut(&mut self) -> &mut Extensions {
+        &mut self.extensions
+    }
+
+    /// The application state of type `T` registered with `Registry::state`
+    pub fn state<T: 'static>(&self) -> Option<Arc<T>> {
+        self.extensions.get::<Arc<T>>().cloned()
+    }
+
     #[cfg(feature = "std")]
     pub fn session(&self) -> Option<&State> {
         self.session.as_ref()
@@ -437,6 +504,56 @@ impl Handler {
     }
 }
 
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub enum StopOutcome {
+    /// All in-flight requests completed before the deadline
+    Drained,
+    /// The deadline passed and this many requests were aborted
+    ForceClosed(usize),
+}
+
+/// Events reported by an HTTP server.
+///
+/// Backends deliver these over their `event_bus::EventBus<ServerEvent>` implementation.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub enum ServerEvent {
+    /// The server stopped accepting new connections and waits for these requests to complete
+    Draining(usize),
+    /// No connection is left open, so e.g. an OTA reboot no longer truncates client downloads
+    Stopped(StopOutcome),
+}
+
+pub trait Server {
+    type Error: Debug;
+
+    /// Stops accepting new connections, lets the in-flight requests complete during up to
+    /// `graceful_timeout`, then closes the remaining connections.
+    ///
+    /// Returns once all connections are closed; `drain::Drain` can help implementing this.
+    fn stop(&mut self, graceful_timeout: Duration) -> Result<StopOutcome, Self::Error>;
+
+    fn in_flight(&self) -> usize;
+}
+
+impl<S> Server for &mut S
+where
+    S: Server,
+{
+    type Error = S::Error;
+
+    fn stop(&mut self, graceful_timeout: Duration) -> Result<StopOutcome, Self::Error> {
+        (*self).stop(graceful_timeout)
+    }
+
+    fn in_flight(&self) -> usize {
+        (**self).in_flight()
+    }
+}
+
 pub mod registry {
     extern crate alloc;
 
@@ -460,6 +577,18 @@ pub mod registry {
         fn register<R: FnOnce(Self) -> Result<Self>>(self, register: R) -> Result<Self> {
             register(self)
         }
+
+        /// Makes `state` available to all handlers with `Request::state`, so that they can use
+        /// e.g. the storage or the Wifi driver without globals; wrap these in a `Mutex` to mutate them.
+        fn state<T: 'static>(self, state: T) -> Result<Self> {
+            let state = Arc::new(state);
+
+            self.middleware(Middleware::new("", move |mut request: Request, handler| {
+                request.extensions_mut().insert(state.clone());
+
+                handler(request)
+            }))
+        }
     }
 
     pub struct RegistryBuilder<RR> {
@@ -579,12 +708,10 @@ pub mod app {
         request: Request,
         handler: &dyn Fn(Request) -> Result<Response>,
     ) -> Result<Response> {
-        handler(Request::new(
-            request.delegate,
-            request.attrs,
-            request.session,
-            Some(app.clone()),
-        ))
+        handler(Request {
+            app: Some(app.clone()),
+            ..request
+        })
     }
 }
 
@@ -635,12 +762,7 @@ pub mod sessions {
                 .as_ref()
                 .and_then(|s| sessions.lock().unwrap().get(s.as_str()));
 
-            let response = handler(Request::new(
-                request.delegate,
-                request.attrs,
-                session,
-                request.app,
-            ))?;
+            let response = handler(Request { session, ..request })?;
 
             Ok(sessions
                 .lock()
@@ -804,3 +926,115 @@ pub mod sessions {
         data: State,
     }
 }
+
+pub mod drain {
+    use core::time::Duration;
+
+    extern crate alloc;
+    use alloc::sync::Arc;
+
+    use std::sync::{Condvar, Mutex};
+    use std::time::Instant;
+
+    use super::{Request, Response, Result, StopOutcome};
+
+    #[derive(Default)]
+    struct DrainState {
+        in_flight: usize,
+        stopping: bool,
+    }
+
+    /// Tracks the requests in flight through its middleware, so that a `Server` can be stopped gracefully.
+    ///
+    /// A request counts as in flight until its handler returns, i.e. a `Body::Read` body
+    /// is still being sent when the request is no longer counted.
+    #[derive(Clone, Default)]
+    pub struct Drain(Arc<(Mutex<DrainState>, Condvar)>);
+
+    impl Drain {
+        pub fn new() -> Self {
+            Default::default()
+        }
+
+        /// Counts the requests going through it, and responds with 503 to those arriving while stopping
+        pub fn middleware(
+            &self,
+        ) -> impl for<'r> Fn(Request, &'r dyn Fn(Request) -> Result<Response>) -> Result<Response>
+        {
+            let drain = self.clone();
+
+            move |request, handler| drain.handle(request, handler)
+        }
+
+        pub fn in_flight(&self) -> usize {
+            self.0 .0.lock().unwrap().in_flight
+        }
+
+        pub fn is_stopping(&self) -> bool {
+            self.0 .0.lock().unwrap().stopping
+        }
+
+        /// Rejects new requests, then waits up to `timeout` for the in-flight ones to complete
+        pub fn stop(&self, timeout: Duration) -> StopOutcome {
+            let (state, condvar) = &*self.0;
+
+            let deadline = Instant::now() + timeout;
+
+            let mut state = state.lock().unwrap();
+            state.stopping = true;
+
+            while state.in_flight > 0 {
+                let now = Instant::now();
+
+                if now >= deadline {
+                    return StopOutcome::ForceClosed(state.in_flight);
+                }
+
+                state = condvar.wait_timeout(state, deadline - now).unwrap().0;
+            }
+
+            StopOutcome::Drained
+        }
+
+        /// Accepts requests again, e.g. after the server is restarted
+        pub fn resume(&self) {
+            self.0 .0.lock().unwrap().stopping = false;
+        }
+
+        fn handle(
+            &self,
+            request: Request,
+            handler: &dyn Fn(Request) -> Result<Response>,
+        ) -> Result<Response> {
+            {
+                let mut state = self.0 .0.lock().unwrap();
+
+                if state.stopping {
+                    return Ok(Response::new(503).header("connection", "close"));
+                }
+
+                state.in_flight += 1;
+            }
+
+            let _guard = InFlight(self);
+
+            handler(request)
+        }
+    }
+
+    /// Decrements the requests in flight even if the handler panics
+    struct InFlight<'a>(&'a Drain);
+
+    impl<'a> Drop for InFlight<'a> {
+        fn drop(&mut self) {
+            let (state, condvar) = &*self.0 .0;
+
+            state
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .in_flight -= 1;
+
+            condvar.notify_all();
+        }
+    }
+}
@@ -1,5 +1,5 @@
 use core::convert::TryFrom;
-use core::fmt::Display;
+use core::fmt::{Display, Write as _};
 use core::str::FromStr;
 
 #[cfg(feature = "std")]
@@ -84,6 +84,36 @@ pub struct Subnet {
     pub mask: Mask,
 }
 
+impl Subnet {
+    pub fn contains(&self, ip: Ipv4Addr) -> bool {
+        let mask = u32::from(Ipv4Addr::from(self.mask));
+
+        u32::from(ip) & mask == u32::from(self.gateway) & mask
+    }
+
+    /// The subnet-directed broadcast address, e.g. 192.168.1.255 for 192.168.1.1/24
+    pub fn broadcast(&self) -> Ipv4Addr {
+        let mask = u32::from(Ipv4Addr::from(self.mask));
+
+        Ipv4Addr::from(u32::from(self.gateway) | !mask)
+    }
+
+    /// All host addresses of the subnet, i.e. without the network and the broadcast addresses
+    pub fn hosts(&self) -> impl Iterator<Item = Ipv4Addr> {
+        let mask = u32::from(Ipv4Addr::from(self.mask));
+        let network = u32::from(self.gateway) & mask;
+        let broadcast = u32::from(self.broadcast());
+
+        let (first, last) = if self.mask.0 >= 31 {
+            (network, broadcast)
+        } else {
+            (network + 1, broadcast - 1)
+        };
+
+        (first..=last).map(Ipv4Addr::from)
+    }
+}
+
 impl Display for Subnet {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         write!(f, "{}/{}", self.gateway, self.mask)
@@ -142,7 +172,77 @@ impl Default for ClientSettings {
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
 pub struct DHCPClientSettings {
-    pub hostname: Option<heapless::String<30>>,
+    pub hostname: Option<Hostname>,
+    /// Self-assign a link-local address, as with `ClientConfiguration::AutoIP`, when no DHCP server answers.
+    /// The backend keeps looking for a DHCP server, and switches to the leased address once it gets one.
+    pub autoip_fallback: bool,
+}
+
+/// Link-local addressing (RFC 3927), for networks without a DHCP server,
+/// e.g. a laptop connected directly to the device
+///
+/// The backend picks a candidate in 169.254.1.0 - 169.254.254.255 (see `autoip::candidate`) and
+/// probes it with `autoip::PROBE_NUM` ARP probes; if another host answers or probes the same address,
+/// it picks the next candidate. Once no conflict is seen, it announces the address with
+/// `autoip::ANNOUNCE_NUM` gratuitous ARPs and defends it afterwards: on a conflict, it
+/// defends the address once per `autoip::DEFEND_INTERVAL`, and gives it up if another
+/// conflict follows within that interval.
+///
+/// The resulting `IpInfo` has the 169.254.0.0/16 subnet, with the network address as the "gateway",
+/// as link-local addresses are not routed.
+#[derive(Default, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub struct AutoIPSettings {
+    /// Typically announced over mDNS, which is how peers find link-local devices
+    pub hostname: Option<Hostname>,
+    /// The address to probe first, e.g. the one claimed before the last reboot, so that it stays stable
+    #[cfg_attr(feature = "defmt", defmt(Debug2Format))]
+    pub preferred_ip: Option<Ipv4Addr>,
+}
+
+/// The RFC 3927 protocol constants, for backends implementing link-local addressing themselves
+pub mod autoip {
+    use core::time::Duration;
+
+    use super::{Ipv4Addr, Mac, Mask, Subnet};
+
+    pub const SUBNET: Subnet = Subnet {
+        gateway: Ipv4Addr::new(169, 254, 0, 0),
+        mask: Mask(16),
+    };
+
+    /// The maximum random delay before the first probe
+    pub const PROBE_WAIT: Duration = Duration::from_secs(1);
+    pub const PROBE_NUM: u8 = 3;
+    pub const PROBE_MIN: Duration = Duration::from_secs(1);
+    pub const PROBE_MAX: Duration = Duration::from_secs(2);
+    pub const ANNOUNCE_WAIT: Duration = Duration::from_secs(2);
+    pub const ANNOUNCE_NUM: u8 = 2;
+    pub const ANNOUNCE_INTERVAL: Duration = Duration::from_secs(2);
+    /// After this many conflicts, candidates are probed at most once per `RATE_LIMIT_INTERVAL`
+    pub const MAX_CONFLICTS: u8 = 10;
+    pub const RATE_LIMIT_INTERVAL: Duration = Duration::from_secs(60);
+    pub const DEFEND_INTERVAL: Duration = Duration::from_secs(10);
+
+    /// The `attempt`-th candidate address of the interface with `mac`.
+    ///
+    /// The sequence is derived from the MAC address, so that a device keeps getting the same
+    /// address across reboots, while devices on the same link most likely get different ones.
+    pub fn candidate(mac: &Mac, attempt: u32) -> Ipv4Addr {
+        // FNV-1a of the MAC address and the attempt
+        let hash = mac
+            .iter()
+            .chain(attempt.to_le_bytes().iter())
+            .fold(0x811c9dc5_u32, |hash, byte| {
+                (hash ^ *byte as u32).wrapping_mul(0x01000193)
+            });
+
+        // 169.254.0.x and 169.254.255.x are reserved
+        let host = 0x0100 + hash % 0xfe00;
+
+        Ipv4Addr::new(169, 254, (host >> 8) as u8, host as u8)
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -151,6 +251,7 @@ pub struct DHCPClientSettings {
 pub enum ClientConfiguration {
     DHCP(DHCPClientSettings),
     Fixed(ClientSettings),
+    AutoIP(AutoIPSettings),
 }
 
 impl ClientConfiguration {
@@ -188,6 +289,11 @@ pub struct RouterConfiguration {
     pub dns: Option<Ipv4Addr>,
     #[cfg_attr(feature = "defmt", defmt(Debug2Format))]
     pub secondary_dns: Option<Ipv4Addr>,
+    /// Served to the DHCP clients in addition to those derived from the settings above
+    pub dhcp_options: DhcpOptions,
+    /// When set, the requests of the DHCP clients are relayed to a DHCP server rather than served, and
+    /// `dhcp_enabled` and `dhcp_options` are ignored
+    pub dhcp_relay: Option<DhcpRelay>,
 }
 
 impl Default for RouterConfiguration {
@@ -200,7 +306,170 @@ impl Default for RouterConfiguration {
             dhcp_enabled: true,
             dns: Some(Ipv4Addr::new(8, 8, 8, 8)),
             secondary_dns: Some(Ipv4Addr::new(8, 8, 4, 4)),
+            dhcp_options: DhcpOptions::new(),
+            dhcp_relay: None,
+        }
+    }
+}
+
+/// A DHCP relay agent (RFC 1542), e.g. for a device bridging an isolated segment to a managed network
+/// whose DHCP server assigns the addresses of the segment.
+///
+/// Backends relay the requests with the gateway address of the `RouterConfiguration` subnet as `giaddr`,
+/// so that the server replies with a lease of a scope for that subnet.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub struct DhcpRelay {
+    /// Reached over the other interfaces of the device
+    #[cfg_attr(feature = "defmt", defmt(Debug2Format))]
+    pub server: Ipv4Addr,
+    /// Added to the relayed requests, and removed from the replies; `None` relays the requests untouched
+    pub agent_information: Option<RelayAgentInformation>,
+}
+
+/// The relay agent information option (option 82, RFC 3046), which tells the DHCP server which segment
+/// and relay a request came through, e.g. to assign addresses per segment or to log where devices are.
+///
+/// Empty sub-options are not sent.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub struct RelayAgentInformation {
+    /// Sub-option 1, identifying the segment, e.g. the name of the interface it is on
+    pub circuit_id: heapless::Vec<u8, 32>,
+    /// Sub-option 2, identifying the relay, e.g. its MAC address or serial number
+    pub remote_id: heapless::Vec<u8, 32>,
+}
+
+impl RelayAgentInformation {
+    pub const CODE: u8 = 82;
+
+    const CIRCUIT_ID: u8 = 1;
+    const REMOTE_ID: u8 = 2;
+
+    pub fn new(circuit_id: &[u8], remote_id: &[u8]) -> Result<Self, &'static str> {
+        Ok(Self {
+            circuit_id: heapless::Vec::from_slice(circuit_id).map_err(|_| "Circuit ID too long")?,
+            remote_id: heapless::Vec::from_slice(remote_id).map_err(|_| "Remote ID too long")?,
+        })
+    }
+
+    /// Identifies the relay by its `mac` address, as many DHCP servers expect
+    pub fn with_mac(circuit_id: &str, mac: &Mac) -> Result<Self, &'static str> {
+        Self::new(circuit_id.as_bytes(), mac)
+    }
+
+    /// The wire encoding of the option, including its code and length, for backends to append to the
+    /// options of the relayed requests; empty if both sub-options are, as the option cannot be
+    pub fn encode(&self) -> heapless::Vec<u8, 70> {
+        let mut option = heapless::Vec::new();
+
+        if self.circuit_id.is_empty() && self.remote_id.is_empty() {
+            return option;
+        }
+
+        option.push(Self::CODE).unwrap();
+        option.push(0).unwrap();
+
+        for (code, value) in [
+            (Self::CIRCUIT_ID, &self.circuit_id),
+            (Self::REMOTE_ID, &self.remote_id),
+        ] {
+            if !value.is_empty() {
+                option.push(code).unwrap();
+                option.push(value.len() as u8).unwrap();
+                option.extend_from_slice(value).unwrap();
+            }
+        }
+
+        option[1] = (option.len() - 2) as u8;
+
+        option
+    }
+}
+
+/// DHCP options (RFC 2132) served by an interface in the router role, kept in their wire encoding:
+/// the code, the length and the value of each option.
+///
+/// Backends serve the subnet mask, router, DNS server and lease time options themselves,
+/// so options with these codes are ignored.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub struct DhcpOptions(heapless::Vec<u8, 128>);
+
+impl DhcpOptions {
+    pub const NTP_SERVERS: u8 = 42;
+    pub const VENDOR_SPECIFIC: u8 = 43;
+    pub const TFTP_SERVER: u8 = 66;
+    pub const BOOT_FILE: u8 = 67;
+
+    pub const fn new() -> Self {
+        Self(heapless::Vec::new())
+    }
+
+    pub fn push(&mut self, code: u8, value: &[u8]) -> Result<&mut Self, &'static str> {
+        if code == 0 || code == 255 {
+            return Err("Pad and end are not options");
         }
+
+        if value.len() > u8::MAX as usize || self.0.capacity() - self.0.len() < 2 + value.len() {
+            return Err("Too many DHCP options");
+        }
+
+        self.0.push(code).unwrap();
+        self.0.push(value.len() as u8).unwrap();
+        self.0.extend_from_slice(value).unwrap();
+
+        Ok(self)
+    }
+
+    /// Option 42
+    pub fn push_ntp_servers(&mut self, servers: &[Ipv4Addr]) -> Result<&mut Self, &'static str> {
+        let mut value = heapless::Vec::<u8, 32>::new();
+
+        for server in servers {
+            value
+                .extend_from_slice(&server.octets())
+                .map_err(|_| "Too many NTP servers")?;
+        }
+
+        self.push(Self::NTP_SERVERS, &value)
+    }
+
+    /// Option 43, whose content is defined by the vendor of the clients
+    pub fn push_vendor_specific(&mut self, value: &[u8]) -> Result<&mut Self, &'static str> {
+        self.push(Self::VENDOR_SPECIFIC, value)
+    }
+
+    /// Option 66, the name or address of the TFTP server the clients boot from
+    pub fn push_tftp_server(&mut self, server: &str) -> Result<&mut Self, &'static str> {
+        self.push(Self::TFTP_SERVER, server.as_bytes())
+    }
+
+    /// Option 67
+    pub fn push_boot_file(&mut self, name: &str) -> Result<&mut Self, &'static str> {
+        self.push(Self::BOOT_FILE, name.as_bytes())
+    }
+
+    /// The `(code, value)` pairs
+    pub fn iter(&self) -> impl Iterator<Item = (u8, &[u8])> {
+        let mut data = self.0.as_slice();
+
+        core::iter::from_fn(move || {
+            let (code, len) = (*data.first()?, *data.get(1)? as usize);
+            let value = data.get(2..2 + len)?;
+
+            data = &data[2 + len..];
+
+            Some((code, value))
+        })
+    }
+
+    /// The wire encoding, to be appended to the options of the DHCP offers and acknowledgements
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
     }
 }
 
@@ -241,3 +510,190 @@ pub trait Interface {
 
     fn get_ip_info(&self) -> Result<IpInfo, Self::Error>;
 }
+
+pub type Mac = [u8; 6];
+
+/// A host name: a single RFC 1123 label, i.e. letters, digits and hyphens, neither starting
+/// nor ending with a hyphen, as used for DHCP, mDNS (with `.local` appended) and NetBIOS.
+///
+/// It is limited to `Hostname::MAX_LEN` characters, which is what most embedded IP stacks can hold.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(
+    feature = "use_serde",
+    derive(Serialize, Deserialize),
+    serde(try_from = "heapless::String<30>", into = "heapless::String<30>")
+)]
+pub struct Hostname(heapless::String<30>);
+
+impl Hostname {
+    pub const MAX_LEN: usize = 30;
+
+    /// A name unique enough for a LAN, from `prefix` and the last three bytes of `mac`, e.g. `device-a1b2c3`
+    pub fn from_mac(prefix: &str, mac: &Mac) -> Result<Self, &'static str> {
+        let mut name = heapless::String::<30>::new();
+
+        write!(
+            &mut name,
+            "{prefix}-{:02x}{:02x}{:02x}",
+            mac[3], mac[4], mac[5]
+        )
+        .map_err(|_| "Hostname prefix too long")?;
+
+        name.as_str().parse()
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl FromStr for Hostname {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() {
+            Err("Hostname is empty")
+        } else if s.len() > Self::MAX_LEN {
+            Err("Hostname too long")
+        } else if !s.bytes().all(|c| c.is_ascii_alphanumeric() || c == b'-') {
+            Err("Hostname should only contain letters, digits and hyphens")
+        } else if s.starts_with('-') || s.ends_with('-') {
+            Err("Hostname should not start or end with a hyphen")
+        } else {
+            Ok(Self(s.into()))
+        }
+    }
+}
+
+impl TryFrom<heapless::String<30>> for Hostname {
+    type Error = &'static str;
+
+    fn try_from(s: heapless::String<30>) -> Result<Self, Self::Error> {
+        s.as_str().parse()
+    }
+}
+
+impl From<Hostname> for heapless::String<30> {
+    fn from(hostname: Hostname) -> Self {
+        hostname.0
+    }
+}
+
+impl AsRef<str> for Hostname {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl Display for Hostname {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub struct DhcpLease {
+    pub mac: Mac,
+    /// As reported by the client, hence not necessarily a valid `Hostname`
+    pub hostname: Option<heapless::String<30>>,
+    #[cfg_attr(feature = "defmt", defmt(Debug2Format))]
+    pub ip: Ipv4Addr,
+}
+
+/// Events reported by an interface operating in the router role.
+///
+/// Backends deliver these over their `event_bus::EventBus<RouterEvent>` implementation,
+/// so that applications can keep a `RouterStatus` table up to date.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub enum RouterEvent {
+    StationConnected(Mac),
+    StationDisconnected(Mac),
+    LeaseGranted(DhcpLease),
+    LeaseExpired(DhcpLease),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub struct RouterClient {
+    pub mac: Mac,
+    /// As with `DhcpLease::hostname`
+    pub hostname: Option<heapless::String<30>>,
+    #[cfg_attr(feature = "defmt", defmt(Debug2Format))]
+    pub ip: Option<Ipv4Addr>,
+}
+
+/// The table of clients currently attached to an interface operating in the router role.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub struct RouterStatus<const N: usize = 8> {
+    pub clients: heapless::Vec<RouterClient, N>,
+}
+
+impl<const N: usize> RouterStatus<N> {
+    pub const fn new() -> Self {
+        Self {
+            clients: heapless::Vec::new(),
+        }
+    }
+
+    pub fn client(&self, mac: &Mac) -> Option<&RouterClient> {
+        self.clients.iter().find(|client| client.mac == *mac)
+    }
+
+    /// Applies a router event to the table.
+    ///
+    /// Returns `false` if the event concerns a new client but the table is full.
+    pub fn update(&mut self, event: &RouterEvent) -> bool {
+        match event {
+            RouterEvent::StationConnected(mac) => self.client_mut(mac).is_some(),
+            RouterEvent::StationDisconnected(mac) => {
+                self.clients.retain(|client| client.mac != *mac);
+                true
+            }
+            RouterEvent::LeaseGranted(lease) => {
+                if let Some(client) = self.client_mut(&lease.mac) {
+                    client.hostname = lease.hostname.clone();
+                    client.ip = Some(lease.ip);
+                    true
+                } else {
+                    false
+                }
+            }
+            RouterEvent::LeaseExpired(lease) => {
+                if let Some(client) = self
+                    .clients
+                    .iter_mut()
+                    .find(|client| client.mac == lease.mac && client.ip == Some(lease.ip))
+                {
+                    client.ip = None;
+                }
+
+                true
+            }
+        }
+    }
+
+    fn client_mut(&mut self, mac: &Mac) -> Option<&mut RouterClient> {
+        if let Some(index) = self.clients.iter().position(|client| client.mac == *mac) {
+            Some(&mut self.clients[index])
+        } else {
+            self.clients
+                .push(RouterClient {
+                    mac: *mac,
+                    hostname: None,
+                    ip: None,
+                })
+                .ok()?;
+
+            self.clients.last_mut()
+        }
+    }
+}
@@ -14,6 +14,14 @@ extern crate alloc;
 #[cfg(all(feature = "defmt", feature = "log"))]
 compile_error!("You must enable at most one of the following features: defmt, log");
 
+pub mod auth;
+pub mod capabilities;
+pub mod cloud;
+pub mod coap;
+pub mod codec;
+pub mod crypto;
+pub mod diag;
+pub mod error;
 pub mod eth;
 pub mod event_bus;
 pub mod executor;
@@ -24,14 +32,26 @@ pub mod http;
 pub mod httpd; // TODO: Retire
 pub mod io;
 pub mod ipv4;
+pub mod lwm2m;
 pub mod macros;
+pub mod mdns;
 pub mod mqtt;
+pub mod net;
+pub mod netbios;
+pub mod opcua;
 #[cfg(feature = "experimental")]
 pub mod ota;
 pub mod ping;
+pub mod rtc;
+pub mod service;
 pub mod storage;
+pub mod sys_info;
 pub mod sys_time;
+pub mod system;
+pub mod telemetry;
 pub mod timer;
+pub mod tls;
+pub mod upnp;
 pub mod utils;
 pub mod wifi;
 #[cfg(feature = "experimental")]
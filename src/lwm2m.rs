@@ -0,0 +1,1282 @@
+//! A minimal LwM2M 1.1 client over CoAP: the object/resource model, bootstrap and
+//! registration with a server, and observe/notify.
+//!
+//! Resources are read as plain text, opaque data or SenML JSON, and written as plain text
+//! or opaque data; TLV and CBOR are not supported.
+
+use core::fmt::{self, Write as _};
+use core::time::Duration;
+
+use crate::coap::{
+    self, content_format, option, Code, Message, MessageType, MessageWriter, Transport,
+};
+use crate::error::{self, impl_error};
+use crate::io::{Error, ErrorKind};
+use crate::sys_time::Instant;
+use crate::utils::codec::base64;
+
+pub const OBJECT_SECURITY: u16 = 0;
+pub const OBJECT_SERVER: u16 = 1;
+pub const OBJECT_DEVICE: u16 = 3;
+
+/// How long to wait for a separate response once the server acknowledged a request
+const SEPARATE_RESPONSE_TIMEOUT: Duration = Duration::from_secs(247);
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Value<'a> {
+    Integer(i64),
+    Float(f64),
+    Boolean(bool),
+    String(&'a str),
+    Opaque(&'a [u8]),
+    /// Seconds since the Unix epoch
+    Time(i64),
+}
+
+impl<'a> Value<'a> {
+    /// Opaque values have no plain text representation
+    fn write_text(&self, w: &mut impl fmt::Write) -> fmt::Result {
+        match self {
+            Self::Integer(value) | Self::Time(value) => write!(w, "{value}"),
+            Self::Float(value) => write!(w, "{value}"),
+            Self::Boolean(value) => w.write_str(if *value { "1" } else { "0" }),
+            Self::String(value) => w.write_str(value),
+            Self::Opaque(_) => Err(fmt::Error),
+        }
+    }
+
+    fn write_senml(&self, w: &mut impl fmt::Write) -> fmt::Result {
+        match self {
+            Self::Integer(value) | Self::Time(value) => write!(w, "\"v\":{value}"),
+            Self::Float(value) => write!(w, "\"v\":{value}"),
+            Self::Boolean(value) => write!(w, "\"vb\":{value}"),
+            Self::String(value) => {
+                w.write_str("\"vs\":")?;
+                write_json_str(w, value)
+            }
+            Self::Opaque(value) => {
+                w.write_str("\"vd\":\"")?;
+                base64::encode(value, base64::URL_SAFE, false, w)?;
+                w.write_char('"')
+            }
+        }
+    }
+}
+
+/// The errors reported by objects, and sent to the server as the response code
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Lwm2mError {
+    BadRequest,
+    Unauthorized,
+    NotFound,
+    MethodNotAllowed,
+    NotAcceptable,
+    UnsupportedContentFormat,
+    InternalError,
+}
+
+impl Lwm2mError {
+    pub const fn code(&self) -> Code {
+        match self {
+            Self::BadRequest => Code::BAD_REQUEST,
+            Self::Unauthorized => Code::UNAUTHORIZED,
+            Self::NotFound => Code::NOT_FOUND,
+            Self::MethodNotAllowed => Code::METHOD_NOT_ALLOWED,
+            Self::NotAcceptable => Code::NOT_ACCEPTABLE,
+            Self::UnsupportedContentFormat => Code::UNSUPPORTED_CONTENT_FORMAT,
+            Self::InternalError => Code::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+/// The response does not fit into the buffer
+impl From<fmt::Error> for Lwm2mError {
+    fn from(_: fmt::Error) -> Self {
+        Self::InternalError
+    }
+}
+
+impl_error! {
+    Lwm2mError {
+        BadRequest => "Bad request"; error::ErrorKind::InvalidInput,
+        Unauthorized => "Unauthorized"; error::ErrorKind::Unauthorized,
+        NotFound => "Not found"; error::ErrorKind::NotFound,
+        MethodNotAllowed => "Method not allowed"; error::ErrorKind::InvalidInput,
+        NotAcceptable => "Not acceptable"; error::ErrorKind::InvalidInput,
+        UnsupportedContentFormat => "Unsupported content format"; error::ErrorKind::InvalidInput,
+        InternalError => "Internal error"; error::ErrorKind::Other,
+    }
+}
+
+/// An LwM2M object, e.g. Device (3), along with its instances and their resources
+pub trait Object {
+    fn id(&self) -> u16;
+
+    /// The IDs of the existing instances
+    fn instances(&self) -> &[u16];
+
+    /// The IDs of the resources of the instances; `read` returns `Lwm2mError::NotFound`
+    /// for those an instance does not have
+    fn resources(&self) -> &[u16];
+
+    fn read(&self, instance: u16, resource: u16) -> Result<Value<'_>, Lwm2mError>;
+
+    /// Values written as plain text arrive as `Value::String`, to be parsed according to the resource type
+    fn write(
+        &mut self,
+        _instance: u16,
+        _resource: u16,
+        _value: Value<'_>,
+    ) -> Result<(), Lwm2mError> {
+        Err(Lwm2mError::MethodNotAllowed)
+    }
+
+    fn execute(
+        &mut self,
+        _instance: u16,
+        _resource: u16,
+        _arguments: &[u8],
+    ) -> Result<(), Lwm2mError> {
+        Err(Lwm2mError::MethodNotAllowed)
+    }
+}
+
+impl<O> Object for &mut O
+where
+    O: Object,
+{
+    fn id(&self) -> u16 {
+        (**self).id()
+    }
+
+    fn instances(&self) -> &[u16] {
+        (**self).instances()
+    }
+
+    fn resources(&self) -> &[u16] {
+        (**self).resources()
+    }
+
+    fn read(&self, instance: u16, resource: u16) -> Result<Value<'_>, Lwm2mError> {
+        (**self).read(instance, resource)
+    }
+
+    fn write(&mut self, instance: u16, resource: u16, value: Value<'_>) -> Result<(), Lwm2mError> {
+        (*self).write(instance, resource, value)
+    }
+
+    fn execute(
+        &mut self,
+        instance: u16,
+        resource: u16,
+        arguments: &[u8],
+    ) -> Result<(), Lwm2mError> {
+        (*self).execute(instance, resource, arguments)
+    }
+}
+
+/// An object, an object instance or a resource, e.g. `/3/0/9`
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Path {
+    pub object: u16,
+    pub instance: Option<u16>,
+    pub resource: Option<u16>,
+}
+
+impl Path {
+    pub const fn object(object: u16) -> Self {
+        Self {
+            object,
+            instance: None,
+            resource: None,
+        }
+    }
+
+    pub const fn instance(object: u16, instance: u16) -> Self {
+        Self {
+            object,
+            instance: Some(instance),
+            resource: None,
+        }
+    }
+
+    pub const fn resource(object: u16, instance: u16, resource: u16) -> Self {
+        Self {
+            object,
+            instance: Some(instance),
+            resource: Some(resource),
+        }
+    }
+
+    /// Parses the segments of a `Uri-Path`; resource instances are not supported
+    pub fn from_segments<'a>(mut segments: impl Iterator<Item = &'a str>) -> Option<Self> {
+        let object = segments.next()?.parse().ok()?;
+        let instance = segments.next().map(str::parse).transpose().ok()?;
+
+        let resource = match instance {
+            Some(_) => segments.next().map(str::parse).transpose().ok()?,
+            None => None,
+        };
+
+        if segments.next().is_some() {
+            return None;
+        }
+
+        Some(Self {
+            object,
+            instance,
+            resource,
+        })
+    }
+
+    /// Whether `other` is this path, or is below it
+    pub fn contains(&self, other: &Path) -> bool {
+        self.object == other.object
+            && (self.instance.is_none() || self.instance == other.instance)
+            && (self.resource.is_none() || self.resource == other.resource)
+    }
+}
+
+impl fmt::Display for Path {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "/{}", self.object)?;
+
+        if let Some(instance) = self.instance {
+            write!(f, "/{instance}")?;
+        }
+
+        if let Some(resource) = self.resource {
+            write!(f, "/{resource}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The objects served by a `Client`
+pub struct Registry<'a, const N: usize> {
+    objects: heapless::Vec<&'a mut dyn Object, N>,
+}
+
+impl<'a, const N: usize> Registry<'a, N> {
+    pub const fn new() -> Self {
+        Self {
+            objects: heapless::Vec::new(),
+        }
+    }
+
+    pub fn register(&mut self, object: &'a mut dyn Object) -> Result<(), &'static str> {
+        if self.get(object.id()).is_some() {
+            return Err("Object already registered");
+        }
+
+        self.objects
+            .push(object)
+            .map_err(|_| "Too many objects registered")
+    }
+
+    pub fn get(&self, id: u16) -> Option<&dyn Object> {
+        self.objects
+            .iter()
+            .find(|object| object.id() == id)
+            .map(|object| &**object as &dyn Object)
+    }
+
+    pub fn get_mut(&mut self, id: u16) -> Option<&mut (dyn Object + 'a)> {
+        self.objects
+            .iter_mut()
+            .find(|object| object.id() == id)
+            .map(|object| &mut **object)
+    }
+
+    /// Writes the objects and their instances in the CoRE link format, as sent on registration;
+    /// the Security object is never disclosed.
+    pub fn write_links(&self, w: &mut impl fmt::Write) -> fmt::Result {
+        let mut first = true;
+
+        for object in self
+            .objects
+            .iter()
+            .filter(|object| object.id() != OBJECT_SECURITY)
+        {
+            if object.instances().is_empty() {
+                write!(w, "{}</{}>", if first { "" } else { "," }, object.id())?;
+                first = false;
+            }
+
+            for instance in object.instances() {
+                write!(
+                    w,
+                    "{}</{}/{instance}>",
+                    if first { "" } else { "," },
+                    object.id()
+                )?;
+                first = false;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<'a, const N: usize> Default for Registry<'a, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Configuration<'a> {
+    /// The endpoint client name, unique among the clients of the server, e.g. a URN
+    /// with the serial number of the device
+    pub endpoint: &'a str,
+    /// The registration is updated before this expires
+    pub lifetime: Duration,
+    /// The minimum period between two notifications of an observation (`pmin`)
+    pub notify_min_period: Duration,
+    /// Observations are notified at least this often, even without changes (`pmax`)
+    pub notify_max_period: Option<Duration>,
+}
+
+impl<'a> Configuration<'a> {
+    pub const fn new(endpoint: &'a str) -> Self {
+        Self {
+            endpoint,
+            lifetime: Duration::from_secs(24 * 60 * 60),
+            notify_min_period: Duration::from_secs(1),
+            notify_max_period: None,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum State {
+    /// Neither bootstrapped nor registered, e.g. after a failure or after deregistration
+    Idle,
+    /// Waiting for the bootstrap server to provision the Security and Server objects
+    Bootstrapping,
+    Registering,
+    Registered,
+    Deregistering,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Event {
+    /// The bootstrap server is done; connect to the provisioned server and `register`
+    BootstrapFinished,
+    /// The server rejected the request with this code, or did not respond at all if `None`
+    BootstrapFailed(Option<Code>),
+    Registered,
+    RegistrationUpdated,
+    /// The registration failed or was lost; as with `BootstrapFailed`, the code is `None` on timeouts
+    RegistrationFailed(Option<Code>),
+    Deregistered,
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ClientError<E> {
+    TransportError(E),
+    /// A message does not fit into the buffer
+    EncodeError(&'static str),
+}
+
+impl_error! {
+    ClientError<E: Display> {
+        TransportError(e) => "Transport error: {e}"; e.error_kind(),
+        EncodeError(e) => "Encode error: {e}"; error::ErrorKind::InvalidInput,
+    }
+}
+
+impl<E> Error for ClientError<E>
+where
+    E: Error,
+{
+    fn kind(&self) -> ErrorKind {
+        match self {
+            Self::TransportError(e) => e.kind(),
+            Self::EncodeError(_) => ErrorKind::Other,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Request {
+    Bootstrap,
+    Register,
+    Update,
+    Deregister,
+}
+
+/// A confirmable request waiting for its response
+struct Exchange {
+    request: Request,
+    message_id: u16,
+    token: [u8; 4],
+    sent_at: Instant,
+    retransmissions: u8,
+    /// The server sent an empty ACK, so the response follows separately
+    acknowledged: bool,
+}
+
+struct Observation {
+    path: Path,
+    token: heapless::Vec<u8, 8>,
+    accept: Option<u16>,
+    sequence: u32,
+    changed: bool,
+    notified_at: Instant,
+    /// Of the last notification, which the server resets to cancel the observation
+    message_id: u16,
+}
+
+struct Response {
+    code: Code,
+    content_format: Option<u16>,
+    observe: Option<u32>,
+}
+
+impl Response {
+    const fn new(code: Code) -> Self {
+        Self {
+            code,
+            content_format: None,
+            observe: None,
+        }
+    }
+}
+
+/// An LwM2M client, serving the objects of its registry to a single server.
+///
+/// The client does not own the transport: the application receives the messages from the server
+/// and hands them to `process`, and calls `poll` periodically, e.g. whenever receiving times out.
+/// Both use `buf` to build the messages they send, and it holds the payload of the message too,
+/// so it should be twice as large as the largest message.
+///
+/// Bootstrapping and registering require separate transports, as the bootstrap server and
+/// the LwM2M server are separate peers.
+pub struct Client<'a, const N: usize = 8, const O: usize = 8> {
+    configuration: Configuration<'a>,
+    registry: Registry<'a, N>,
+    observations: heapless::Vec<Observation, O>,
+    state: State,
+    queued: Option<Request>,
+    exchange: Option<Exchange>,
+    location: heapless::String<64>,
+    registered_at: Instant,
+    message_id: u16,
+    token: u32,
+}
+
+impl<'a, const N: usize, const O: usize> Client<'a, N, O> {
+    /// Message IDs and tokens are derived from `seed`, which should be random,
+    /// so that they are not reused across reboots
+    pub fn new(configuration: Configuration<'a>, registry: Registry<'a, N>, seed: u32) -> Self {
+        Self {
+            configuration,
+            registry,
+            observations: heapless::Vec::new(),
+            state: State::Idle,
+            queued: None,
+            exchange: None,
+            location: heapless::String::new(),
+            registered_at: Instant::from_duration(Duration::ZERO),
+            message_id: seed as u16,
+            token: seed | 1,
+        }
+    }
+
+    pub fn state(&self) -> State {
+        self.state
+    }
+
+    pub fn registry(&self) -> &Registry<'a, N> {
+        &self.registry
+    }
+
+    pub fn registry_mut(&mut self) -> &mut Registry<'a, N> {
+        &mut self.registry
+    }
+
+    /// Requests provisioning from the bootstrap server on the next `poll`
+    pub fn bootstrap(&mut self) {
+        self.reset(State::Bootstrapping);
+        self.queued = Some(Request::Bootstrap);
+    }
+
+    /// Registers with the server on the next `poll`
+    pub fn register(&mut self) {
+        self.reset(State::Registering);
+        self.queued = Some(Request::Register);
+    }
+
+    pub fn deregister(&mut self) {
+        if self.state == State::Registered {
+            self.exchange = None;
+            self.state = State::Deregistering;
+            self.queued = Some(Request::Deregister);
+        }
+    }
+
+    /// Marks the observations of `path` - and of the instances and objects containing it,
+    /// or the resources it contains - as changed, so that `poll` notifies them
+    pub fn changed(&mut self, path: &Path) {
+        for observation in &mut self.observations {
+            if observation.path.contains(path) || path.contains(&observation.path) {
+                observation.changed = true;
+            }
+        }
+    }
+
+    /// Sends the queued request, retransmits the unacknowledged one, updates the registration
+    /// before it expires and sends the due notifications
+    pub fn poll<T>(
+        &mut self,
+        transport: &mut T,
+        now: Instant,
+        buf: &mut [u8],
+    ) -> Result<Option<Event>, ClientError<T::Error>>
+    where
+        T: Transport,
+    {
+        if let Some(exchange) = self.exchange.as_mut() {
+            let elapsed = now.duration_since(exchange.sent_at);
+
+            if exchange.acknowledged {
+                if elapsed >= SEPARATE_RESPONSE_TIMEOUT {
+                    let request = exchange.request;
+                    self.exchange = None;
+
+                    return Ok(Some(self.failed(request, None)));
+                }
+            } else if elapsed >= coap::ACK_TIMEOUT * (1 << exchange.retransmissions) {
+                if exchange.retransmissions < coap::MAX_RETRANSMIT {
+                    exchange.retransmissions += 1;
+                    exchange.sent_at = now;
+
+                    let (request, message_id, token) =
+                        (exchange.request, exchange.message_id, exchange.token);
+
+                    self.send_request(transport, request, message_id, &token, buf)?;
+                } else {
+                    let request = exchange.request;
+                    self.exchange = None;
+
+                    return Ok(Some(self.failed(request, None)));
+                }
+            }
+        } else {
+            let update_due = self.state == State::Registered
+                && now >= self.registered_at + self.configuration.lifetime * 9 / 10;
+
+            let request = self.queued.take().or(if update_due {
+                Some(Request::Update)
+            } else {
+                None
+            });
+
+            if let Some(request) = request {
+                let message_id = self.next_message_id();
+                let token = self.next_token();
+
+                self.send_request(transport, request, message_id, &token, buf)?;
+
+                self.exchange = Some(Exchange {
+                    request,
+                    message_id,
+                    token,
+                    sent_at: now,
+                    retransmissions: 0,
+                    acknowledged: false,
+                });
+            }
+        }
+
+        if self.state == State::Registered {
+            self.notify(transport, now, buf)?;
+        }
+
+        Ok(None)
+    }
+
+    /// Handles a message received from the server, returning the event it caused, if any
+    pub fn process<T>(
+        &mut self,
+        transport: &mut T,
+        message: &[u8],
+        now: Instant,
+        buf: &mut [u8],
+    ) -> Result<Option<Event>, ClientError<T::Error>>
+    where
+        T: Transport,
+    {
+        let message = match Message::parse(message) {
+            Some(message) => message,
+            None => return Ok(None),
+        };
+
+        if message.code.is_request() {
+            return self.handle_request(transport, &message, now, buf);
+        }
+
+        let exchange_message = self
+            .exchange
+            .as_ref()
+            .map(|exchange| exchange.message_id == message.message_id)
+            .unwrap_or(false);
+
+        match message.message_type {
+            MessageType::Reset => {
+                if exchange_message {
+                    let request = self.exchange.take().unwrap().request;
+
+                    return Ok(Some(self.failed(request, None)));
+                }
+
+                let index = self
+                    .observations
+                    .iter()
+                    .position(|observation| observation.message_id == message.message_id);
+
+                if let Some(index) = index {
+                    self.observations.swap_remove(index);
+                }
+
+                Ok(None)
+            }
+            MessageType::Acknowledgement if message.code == Code::EMPTY => {
+                if exchange_message {
+                    let exchange = self.exchange.as_mut().unwrap();
+
+                    exchange.acknowledged = true;
+                    exchange.sent_at = now;
+                }
+
+                Ok(None)
+            }
+            message_type => {
+                if message_type == MessageType::Confirmable {
+                    let ack = MessageWriter::new(
+                        buf,
+                        MessageType::Acknowledgement,
+                        Code::EMPTY,
+                        message.message_id,
+                        &[],
+                    )
+                    .map_err(ClientError::EncodeError)?;
+
+                    transport
+                        .send(ack.finish())
+                        .map_err(ClientError::TransportError)?;
+                }
+
+                let matches = self
+                    .exchange
+                    .as_ref()
+                    .map(|exchange| {
+                        exchange.token == message.token
+                            && (message_type != MessageType::Acknowledgement || exchange_message)
+                    })
+                    .unwrap_or(false);
+
+                if matches {
+                    let request = self.exchange.take().unwrap().request;
+
+                    Ok(self.response(request, &message, now))
+                } else {
+                    Ok(None)
+                }
+            }
+        }
+    }
+
+    fn response(&mut self, request: Request, message: &Message<'_>, now: Instant) -> Option<Event> {
+        match request {
+            // The bootstrap server now writes the objects, and finishes with a `POST /bs`
+            Request::Bootstrap if message.code.is_success() => None,
+            Request::Register if message.code == Code::CREATED => {
+                self.location.clear();
+
+                for segment in message.option_strs(option::LOCATION_PATH) {
+                    let separator = if self.location.is_empty() { "" } else { "/" };
+
+                    if write!(&mut self.location, "{separator}{segment}").is_err() {
+                        return Some(self.failed(request, Some(message.code)));
+                    }
+                }
+
+                self.state = State::Registered;
+                self.registered_at = now;
+
+                Some(Event::Registered)
+            }
+            Request::Update if message.code.is_success() => {
+                self.registered_at = now;
+
+                Some(Event::RegistrationUpdated)
+            }
+            // The server dropped the registration
+            Request::Update if message.code == Code::NOT_FOUND => {
+                self.register();
+
+                None
+            }
+            Request::Deregister => Some(self.deregistered()),
+            request => Some(self.failed(request, Some(message.code))),
+        }
+    }
+
+    fn handle_request<T>(
+        &mut self,
+        transport: &mut T,
+        message: &Message<'_>,
+        now: Instant,
+        buf: &mut [u8],
+    ) -> Result<Option<Event>, ClientError<T::Error>>
+    where
+        T: Transport,
+    {
+        let (buf, payload_buf) = buf.split_at_mut(buf.len() / 2);
+        let mut payload = Payload::new(payload_buf);
+        let mut event = None;
+
+        let bootstrap_finish = self.state == State::Bootstrapping
+            && message.code == Code::POST
+            && message
+                .option_strs(option::URI_PATH)
+                .eq(core::iter::once("bs"));
+
+        let result = if bootstrap_finish {
+            self.state = State::Idle;
+            event = Some(Event::BootstrapFinished);
+
+            Ok(Response::new(Code::CHANGED))
+        } else {
+            match Path::from_segments(message.option_strs(option::URI_PATH)) {
+                Some(path)
+                    if path.object == OBJECT_SECURITY && self.state != State::Bootstrapping =>
+                {
+                    Err(Lwm2mError::Unauthorized)
+                }
+                Some(path) => match message.code {
+                    Code::GET => self.handle_read(&path, message, now, &mut payload),
+                    Code::PUT => self.handle_write(&path, message),
+                    Code::POST => self.handle_execute(&path, message),
+                    // Instances cannot be deleted, but the bootstrap server usually
+                    // starts by deleting everything
+                    Code::DELETE if self.state == State::Bootstrapping => {
+                        Ok(Response::new(Code::DELETED))
+                    }
+                    _ => Err(Lwm2mError::MethodNotAllowed),
+                },
+                None => Err(Lwm2mError::NotFound),
+            }
+        };
+
+        let (message_type, message_id) = if message.message_type == MessageType::Confirmable {
+            (MessageType::Acknowledgement, message.message_id)
+        } else {
+            (MessageType::NonConfirmable, self.next_message_id())
+        };
+
+        let code = match &result {
+            Ok(response) => response.code,
+            Err(e) => e.code(),
+        };
+
+        let mut writer = MessageWriter::new(buf, message_type, code, message_id, message.token)
+            .map_err(ClientError::EncodeError)?;
+
+        if let Ok(response) = result {
+            if let Some(observe) = response.observe {
+                writer
+                    .option_uint(option::OBSERVE, observe)
+                    .map_err(ClientError::EncodeError)?;
+            }
+
+            if let Some(content_format) = response.content_format {
+                writer
+                    .option_uint(option::CONTENT_FORMAT, content_format as u32)
+                    .map_err(ClientError::EncodeError)?;
+            }
+
+            writer
+                .payload(payload.as_bytes())
+                .map_err(ClientError::EncodeError)?;
+        }
+
+        transport
+            .send(writer.finish())
+            .map_err(ClientError::TransportError)?;
+
+        Ok(event)
+    }
+
+    fn handle_read(
+        &mut self,
+        path: &Path,
+        message: &Message<'_>,
+        now: Instant,
+        payload: &mut Payload<'_>,
+    ) -> Result<Response, Lwm2mError> {
+        let accept = message
+            .option_uint(option::ACCEPT)
+            .map(|accept| accept as u16);
+
+        let content_format = self.read(path, accept, payload)?;
+
+        let mut response = Response::new(Code::CONTENT);
+        response.content_format = Some(content_format);
+
+        match message.option_uint(option::OBSERVE) {
+            Some(0) => {
+                let index = self
+                    .observations
+                    .iter()
+                    .position(|observation| observation.token == message.token);
+
+                if let Some(index) = index {
+                    self.observations.swap_remove(index);
+                }
+
+                let observation = Observation {
+                    path: *path,
+                    token: heapless::Vec::from_slice(message.token).unwrap(),
+                    accept,
+                    sequence: 0,
+                    changed: false,
+                    notified_at: now,
+                    message_id: 0,
+                };
+
+                // Beyond the capacity, the server just gets the value without observing it
+                if self.observations.push(observation).is_ok() {
+                    response.observe = Some(0);
+                }
+            }
+            Some(1) => {
+                self.observations
+                    .retain(|observation| observation.token != message.token);
+            }
+            _ => (),
+        }
+
+        Ok(response)
+    }
+
+    fn handle_write(&mut self, path: &Path, message: &Message<'_>) -> Result<Response, Lwm2mError> {
+        // Writing whole instances needs TLV or SenML
+        let (instance, resource) = match (path.instance, path.resource) {
+            (Some(instance), Some(resource)) => (instance, resource),
+            _ => return Err(Lwm2mError::UnsupportedContentFormat),
+        };
+
+        let content_format = message
+            .option_uint(option::CONTENT_FORMAT)
+            .map(|content_format| content_format as u16);
+
+        let value = match content_format {
+            None | Some(content_format::TEXT_PLAIN) => Value::String(
+                core::str::from_utf8(message.payload).map_err(|_| Lwm2mError::BadRequest)?,
+            ),
+            Some(content_format::OCTET_STREAM) => Value::Opaque(message.payload),
+            _ => return Err(Lwm2mError::UnsupportedContentFormat),
+        };
+
+        self.object_mut(path.object, instance)?
+            .write(instance, resource, value)?;
+
+        self.changed(path);
+
+        Ok(Response::new(Code::CHANGED))
+    }
+
+    fn handle_execute(
+        &mut self,
+        path: &Path,
+        message: &Message<'_>,
+    ) -> Result<Response, Lwm2mError> {
+        let (instance, resource) = match (path.instance, path.resource) {
+            (Some(instance), Some(resource)) => (instance, resource),
+            _ => return Err(Lwm2mError::MethodNotAllowed),
+        };
+
+        self.object_mut(path.object, instance)?
+            .execute(instance, resource, message.payload)?;
+
+        Ok(Response::new(Code::CHANGED))
+    }
+
+    /// Writes the value of `path` into `payload`, returning its content format
+    fn read(
+        &self,
+        path: &Path,
+        accept: Option<u16>,
+        payload: &mut Payload<'_>,
+    ) -> Result<u16, Lwm2mError> {
+        let object = self.registry.get(path.object).ok_or(Lwm2mError::NotFound)?;
+
+        if let Some(instance) = path.instance {
+            if !object.instances().contains(&instance) {
+                return Err(Lwm2mError::NotFound);
+            }
+        }
+
+        match (path.instance, path.resource) {
+            (Some(instance), Some(resource)) if accept != Some(content_format::SENML_JSON) => {
+                match (object.read(instance, resource)?, accept) {
+                    (Value::Opaque(data), None | Some(content_format::OCTET_STREAM)) => {
+                        payload.write_bytes(data)?;
+
+                        Ok(content_format::OCTET_STREAM)
+                    }
+                    (Value::Opaque(_), _) => Err(Lwm2mError::NotAcceptable),
+                    (value, None | Some(content_format::TEXT_PLAIN)) => {
+                        value.write_text(payload)?;
+
+                        Ok(content_format::TEXT_PLAIN)
+                    }
+                    _ => Err(Lwm2mError::NotAcceptable),
+                }
+            }
+            _ if accept.is_none() || accept == Some(content_format::SENML_JSON) => {
+                write_senml(object, path, payload)?;
+
+                Ok(content_format::SENML_JSON)
+            }
+            _ => Err(Lwm2mError::NotAcceptable),
+        }
+    }
+
+    fn notify<T>(
+        &mut self,
+        transport: &mut T,
+        now: Instant,
+        buf: &mut [u8],
+    ) -> Result<(), ClientError<T::Error>>
+    where
+        T: Transport,
+    {
+        let mut index = 0;
+
+        while index < self.observations.len() {
+            let observation = &self.observations[index];
+            let elapsed = now.duration_since(observation.notified_at);
+
+            let due = (observation.changed && elapsed >= self.configuration.notify_min_period)
+                || self
+                    .configuration
+                    .notify_max_period
+                    .map(|max| elapsed >= max)
+                    .unwrap_or(false);
+
+            if !due {
+                index += 1;
+                continue;
+            }
+
+            let (buf, payload_buf) = buf.split_at_mut(buf.len() / 2);
+            let mut payload = Payload::new(payload_buf);
+
+            let path = observation.path;
+            let accept = observation.accept;
+            let token = observation.token.clone();
+
+            let result = self.read(&path, accept, &mut payload);
+            let message_id = self.next_message_id();
+
+            let observation = &mut self.observations[index];
+            observation.sequence = (observation.sequence + 1) & 0x00ff_ffff;
+            observation.changed = false;
+            observation.notified_at = now;
+            observation.message_id = message_id;
+
+            let sequence = observation.sequence;
+
+            let code = match result {
+                Ok(_) => Code::CONTENT,
+                Err(e) => e.code(),
+            };
+
+            let mut writer =
+                MessageWriter::new(buf, MessageType::NonConfirmable, code, message_id, &token)
+                    .map_err(ClientError::EncodeError)?;
+
+            if let Ok(content_format) = result {
+                writer
+                    .option_uint(option::OBSERVE, sequence)
+                    .and_then(|writer| {
+                        writer.option_uint(option::CONTENT_FORMAT, content_format as u32)
+                    })
+                    .and_then(|writer| writer.payload(payload.as_bytes()))
+                    .map_err(ClientError::EncodeError)?;
+
+                index += 1;
+            } else {
+                // An error response ends the observation
+                self.observations.swap_remove(index);
+            }
+
+            transport
+                .send(writer.finish())
+                .map_err(ClientError::TransportError)?;
+        }
+
+        Ok(())
+    }
+
+    fn send_request<T>(
+        &self,
+        transport: &mut T,
+        request: Request,
+        message_id: u16,
+        token: &[u8],
+        buf: &mut [u8],
+    ) -> Result<(), ClientError<T::Error>>
+    where
+        T: Transport,
+    {
+        let (buf, payload_buf) = buf.split_at_mut(buf.len() / 2);
+
+        let code = match request {
+            Request::Deregister => Code::DELETE,
+            _ => Code::POST,
+        };
+
+        let mut writer = MessageWriter::new(buf, MessageType::Confirmable, code, message_id, token)
+            .map_err(ClientError::EncodeError)?;
+
+        match request {
+            Request::Bootstrap => {
+                writer
+                    .option_str(option::URI_PATH, "bs")
+                    .map_err(ClientError::EncodeError)?;
+
+                query(
+                    &mut writer,
+                    format_args!("ep={}", self.configuration.endpoint),
+                )?;
+            }
+            Request::Register => {
+                let mut payload = Payload::new(payload_buf);
+                self.registry
+                    .write_links(&mut payload)
+                    .map_err(|_| ClientError::EncodeError("Message too long"))?;
+
+                writer
+                    .option_str(option::URI_PATH, "rd")
+                    .and_then(|writer| {
+                        writer
+                            .option_uint(option::CONTENT_FORMAT, content_format::LINK_FORMAT as u32)
+                    })
+                    .map_err(ClientError::EncodeError)?;
+
+                query(
+                    &mut writer,
+                    format_args!("ep={}", self.configuration.endpoint),
+                )?;
+                query(
+                    &mut writer,
+                    format_args!("lt={}", self.configuration.lifetime.as_secs()),
+                )?;
+                query(&mut writer, format_args!("lwm2m=1.1"))?;
+                query(&mut writer, format_args!("b=U"))?;
+
+                writer
+                    .payload(payload.as_bytes())
+                    .map_err(ClientError::EncodeError)?;
+            }
+            Request::Update | Request::Deregister => {
+                for segment in self.location.split('/') {
+                    writer
+                        .option_str(option::URI_PATH, segment)
+                        .map_err(ClientError::EncodeError)?;
+                }
+            }
+        }
+
+        transport
+            .send(writer.finish())
+            .map_err(ClientError::TransportError)
+    }
+
+    fn object_mut(
+        &mut self,
+        object: u16,
+        instance: u16,
+    ) -> Result<&mut (dyn Object + 'a), Lwm2mError> {
+        let object = self.registry.get_mut(object).ok_or(Lwm2mError::NotFound)?;
+
+        if object.instances().contains(&instance) {
+            Ok(object)
+        } else {
+            Err(Lwm2mError::NotFound)
+        }
+    }
+
+    fn failed(&mut self, request: Request, code: Option<Code>) -> Event {
+        match request {
+            Request::Bootstrap => {
+                self.reset(State::Idle);
+
+                Event::BootstrapFailed(code)
+            }
+            Request::Register | Request::Update => {
+                self.reset(State::Idle);
+
+                Event::RegistrationFailed(code)
+            }
+            // The registration expires anyway
+            Request::Deregister => self.deregistered(),
+        }
+    }
+
+    fn deregistered(&mut self) -> Event {
+        self.reset(State::Idle);
+
+        Event::Deregistered
+    }
+
+    fn reset(&mut self, state: State) {
+        self.state = state;
+        self.queued = None;
+        self.exchange = None;
+        self.observations.clear();
+        self.location.clear();
+    }
+
+    fn next_message_id(&mut self) -> u16 {
+        self.message_id = self.message_id.wrapping_add(1);
+        self.message_id
+    }
+
+    fn next_token(&mut self) -> [u8; 4] {
+        // xorshift32
+        self.token ^= self.token << 13;
+        self.token ^= self.token >> 17;
+        self.token ^= self.token << 5;
+
+        self.token.to_be_bytes()
+    }
+}
+
+fn query<E>(
+    writer: &mut MessageWriter<'_>,
+    args: fmt::Arguments<'_>,
+) -> Result<(), ClientError<E>> {
+    let mut query = heapless::String::<128>::new();
+    query
+        .write_fmt(args)
+        .map_err(|_| ClientError::EncodeError("Query too long"))?;
+
+    writer
+        .option_str(option::URI_QUERY, &query)
+        .map_err(ClientError::EncodeError)?;
+
+    Ok(())
+}
+
+/// Writes the resources of `path` as a SenML JSON pack
+fn write_senml(
+    object: &dyn Object,
+    path: &Path,
+    w: &mut impl fmt::Write,
+) -> Result<(), Lwm2mError> {
+    w.write_char('[')?;
+
+    let mut first = true;
+
+    for instance in object
+        .instances()
+        .iter()
+        .filter(|instance| path.instance.map(|i| i == **instance).unwrap_or(true))
+    {
+        for resource in object
+            .resources()
+            .iter()
+            .filter(|resource| path.resource.map(|r| r == **resource).unwrap_or(true))
+        {
+            let value = match object.read(*instance, *resource) {
+                Ok(value) => value,
+                Err(Lwm2mError::NotFound) => continue,
+                Err(e) => return Err(e),
+            };
+
+            if first {
+                write!(w, "{{\"bn\":\"/{}/\",", object.id())?;
+            } else {
+                w.write_str(",{")?;
+            }
+
+            write!(w, "\"n\":\"{instance}/{resource}\",")?;
+            value.write_senml(w)?;
+            w.write_char('}')?;
+
+            first = false;
+        }
+    }
+
+    w.write_char(']')?;
+
+    Ok(())
+}
+
+fn write_json_str(w: &mut impl fmt::Write, s: &str) -> fmt::Result {
+    w.write_char('"')?;
+
+    for c in s.chars() {
+        match c {
+            '"' => w.write_str("\\\"")?,
+            '\\' => w.write_str("\\\\")?,
+            c if (c as u32) < 0x20 => write!(w, "\\u{:04x}", c as u32)?,
+            c => w.write_char(c)?,
+        }
+    }
+
+    w.write_char('"')
+}
+
+/// Writes into a buffer, failing once it is full
+struct Payload<'a> {
+    buf: &'a mut [u8],
+    len: usize,
+}
+
+impl<'a> Payload<'a> {
+    fn new(buf: &'a mut [u8]) -> Self {
+        Self { buf, len: 0 }
+    }
+
+    fn write_bytes(&mut self, data: &[u8]) -> fmt::Result {
+        let end = self.len + data.len();
+        if end > self.buf.len() {
+            return Err(fmt::Error);
+        }
+
+        self.buf[self.len..end].copy_from_slice(data);
+        self.len = end;
+
+        Ok(())
+    }
+
+    fn as_bytes(&self) -> &[u8] {
+        &self.buf[..self.len]
+    }
+}
+
+impl<'a> fmt::Write for Payload<'a> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.write_bytes(s.as_bytes())
+    }
+}
@@ -0,0 +1,370 @@
+use core::convert::TryFrom;
+use core::fmt::Debug;
+use core::time::Duration;
+
+#[cfg(feature = "use_serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::ipv4;
+
+pub const MAX_TXT_ENTRY_LEN: usize = 255;
+/// RFC 6763 recommends keys of no more than 9 characters
+pub const MAX_TXT_KEY_LEN: usize = 9;
+
+pub const TXT_VERS: &str = "txtvers";
+pub const TXT_PATH: &str = "path";
+pub const TXT_VERSION: &str = "version";
+pub const TXT_MODEL: &str = "model";
+pub const TXT_ID: &str = "id";
+
+/// DNS-SD TXT record data, kept in its wire format: a sequence of length-prefixed `key[=value]` entries.
+///
+/// Deserialized records are validated as with `parse`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+#[cfg_attr(
+    feature = "use_serde",
+    serde(try_from = "heapless::Vec<u8, N>", into = "heapless::Vec<u8, N>")
+)]
+pub struct TxtRecord<const N: usize = 256>(heapless::Vec<u8, N>);
+
+impl<const N: usize> TxtRecord<N> {
+    pub fn new() -> Self {
+        Self(heapless::Vec::new())
+    }
+
+    /// Validates the wire format; keys are not required to be unique, nor to be shorter than
+    /// `MAX_TXT_KEY_LEN`, as only the records built with `set` are held to it. Empty entries are skipped,
+    /// so the empty record sent on the wire, a single zero byte, is accepted.
+    pub fn parse(data: &[u8]) -> Result<Self, &'static str> {
+        let mut offset = 0;
+
+        while offset < data.len() {
+            let len = data[offset] as usize;
+            let entry = data
+                .get(offset + 1..offset + 1 + len)
+                .ok_or("Truncated TXT entry")?;
+
+            if !entry.is_empty() {
+                Self::validate_key(Self::entry_key(entry))?;
+            }
+
+            offset += 1 + len;
+        }
+
+        heapless::Vec::from_slice(data)
+            .map(Self)
+            .map_err(|_| "TXT record too long")
+    }
+
+    /// A `None` value creates a boolean attribute, i.e. a key without `=`.
+    /// Any previous entry with the same key is replaced.
+    pub fn set(&mut self, key: &str, value: Option<&[u8]>) -> Result<&mut Self, &'static str> {
+        Self::validate_key(key.as_bytes())?;
+
+        if key.len() > MAX_TXT_KEY_LEN {
+            return Err("TXT key too long");
+        }
+
+        let len = key.len() + value.map(|value| value.len() + 1).unwrap_or(0);
+        if len > MAX_TXT_ENTRY_LEN {
+            return Err("TXT entry too long");
+        }
+
+        let replaced = self.find(key).map(|(start, end)| end - start).unwrap_or(0);
+
+        if self.0.len() - replaced + 1 + len > N {
+            return Err("TXT record too long");
+        }
+
+        self.remove(key);
+
+        // Capacity was checked above
+        let _ = self.0.push(len as u8);
+        let _ = self.0.extend_from_slice(key.as_bytes());
+
+        if let Some(value) = value {
+            let _ = self.0.push(b'=');
+            let _ = self.0.extend_from_slice(value);
+        }
+
+        Ok(self)
+    }
+
+    pub fn set_str(&mut self, key: &str, value: &str) -> Result<&mut Self, &'static str> {
+        self.set(key, Some(value.as_bytes()))
+    }
+
+    pub fn remove(&mut self, key: &str) -> bool {
+        if let Some((start, end)) = self.find(key) {
+            self.0.copy_within(end.., start);
+            self.0.truncate(self.0.len() - (end - start));
+
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Keys are case-insensitive. Returns `Some(None)` for a boolean attribute.
+    pub fn get(&self, key: &str) -> Option<Option<&[u8]>> {
+        self.iter()
+            .find(|(entry_key, _)| entry_key.eq_ignore_ascii_case(key))
+            .map(|(_, value)| value)
+    }
+
+    pub fn get_str(&self, key: &str) -> Option<&str> {
+        self.get(key)
+            .flatten()
+            .and_then(|value| core::str::from_utf8(value).ok())
+    }
+
+    /// Skips the empty entries, and ends at the first malformed one
+    pub fn iter(&self) -> impl Iterator<Item = (&str, Option<&[u8]>)> {
+        TxtIterator(&self.0)
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// The range of the first entry with `key`, including its length byte
+    fn find(&self, key: &str) -> Option<(usize, usize)> {
+        let mut offset = 0;
+
+        while offset < self.0.len() {
+            let end = (offset + 1 + self.0[offset] as usize).min(self.0.len());
+
+            if Self::entry_key(&self.0[offset + 1..end]).eq_ignore_ascii_case(key.as_bytes()) {
+                return Some((offset, end));
+            }
+
+            offset = end;
+        }
+
+        None
+    }
+
+    fn entry_key(entry: &[u8]) -> &[u8] {
+        let key_len = entry.iter().position(|b| *b == b'=').unwrap_or(entry.len());
+
+        &entry[..key_len]
+    }
+
+    fn validate_key(key: &[u8]) -> Result<(), &'static str> {
+        if key.is_empty() {
+            Err("TXT key should not be empty")
+        } else if key.iter().any(|b| !(0x20..=0x7e).contains(b) || *b == b'=') {
+            Err("TXT key should only contain printable ASCII characters other than '='")
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl<const N: usize> TryFrom<heapless::Vec<u8, N>> for TxtRecord<N> {
+    type Error = &'static str;
+
+    fn try_from(data: heapless::Vec<u8, N>) -> Result<Self, Self::Error> {
+        Self::parse(&data)
+    }
+}
+
+impl<const N: usize> From<TxtRecord<N>> for heapless::Vec<u8, N> {
+    fn from(record: TxtRecord<N>) -> Self {
+        record.0
+    }
+}
+
+struct TxtIterator<'a>(&'a [u8]);
+
+impl<'a> Iterator for TxtIterator<'a> {
+    type Item = (&'a str, Option<&'a [u8]>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (len, rest) = self.0.split_first()?;
+            if *len as usize > rest.len() {
+                self.0 = &[];
+                return None;
+            }
+
+            let (entry, rest) = rest.split_at(*len as usize);
+
+            self.0 = rest;
+
+            if entry.is_empty() {
+                continue;
+            }
+
+            let key_len = entry.iter().position(|b| *b == b'=').unwrap_or(entry.len());
+            let key = match core::str::from_utf8(&entry[..key_len]) {
+                Ok(key) => key,
+                Err(_) => {
+                    self.0 = &[];
+                    return None;
+                }
+            };
+            let value = entry.get(key_len + 1..).filter(|_| key_len < entry.len());
+
+            return Some((key, value));
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub enum Protocol {
+    Tcp,
+    Udp,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub struct DiscoveredService<const N: usize = 256> {
+    pub instance_name: heapless::String<64>,
+    pub hostname: heapless::String<64>,
+    #[cfg_attr(feature = "defmt", defmt(Debug2Format))]
+    pub addr: Option<ipv4::Ipv4Addr>,
+    pub port: u16,
+    pub txt: TxtRecord<N>,
+}
+
+pub trait Mdns {
+    type Error: Debug;
+
+    fn set_hostname(&mut self, hostname: &ipv4::Hostname) -> Result<(), Self::Error>;
+
+    /// `service_type` is e.g. `_http`
+    fn add_service<const N: usize>(
+        &mut self,
+        instance_name: &str,
+        service_type: &str,
+        protocol: Protocol,
+        port: u16,
+        txt: &TxtRecord<N>,
+    ) -> Result<(), Self::Error>;
+
+    fn remove_service(&mut self, service_type: &str, protocol: Protocol)
+        -> Result<(), Self::Error>;
+
+    fn query_services<const M: usize, const N: usize>(
+        &mut self,
+        service_type: &str,
+        protocol: Protocol,
+        timeout: Duration,
+    ) -> Result<(heapless::Vec<DiscoveredService<N>, M>, usize), Self::Error>;
+}
+
+impl<M> Mdns for &mut M
+where
+    M: Mdns,
+{
+    type Error = M::Error;
+
+    fn set_hostname(&mut self, hostname: &ipv4::Hostname) -> Result<(), Self::Error> {
+        (*self).set_hostname(hostname)
+    }
+
+    fn add_service<const N: usize>(
+        &mut self,
+        instance_name: &str,
+        service_type: &str,
+        protocol: Protocol,
+        port: u16,
+        txt: &TxtRecord<N>,
+    ) -> Result<(), Self::Error> {
+        (*self).add_service(instance_name, service_type, protocol, port, txt)
+    }
+
+    fn remove_service(
+        &mut self,
+        service_type: &str,
+        protocol: Protocol,
+    ) -> Result<(), Self::Error> {
+        (*self).remove_service(service_type, protocol)
+    }
+
+    fn query_services<const Q: usize, const N: usize>(
+        &mut self,
+        service_type: &str,
+        protocol: Protocol,
+        timeout: Duration,
+    ) -> Result<(heapless::Vec<DiscoveredService<N>, Q>, usize), Self::Error> {
+        (*self).query_services(service_type, protocol, timeout)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_empty() {
+        assert_eq!(TxtRecord::<8>::parse(&[]).unwrap().iter().count(), 0);
+        assert_eq!(TxtRecord::<8>::parse(&[0]).unwrap().iter().count(), 0);
+    }
+
+    #[test]
+    fn parse() {
+        let record = TxtRecord::<32>::parse(b"\x09txtvers=1\x04auth\x0cmanufacturer").unwrap();
+
+        assert_eq!(record.get_str(TXT_VERS), Some("1"));
+        assert_eq!(record.get("AUTH"), Some(None));
+        // Longer than `MAX_TXT_KEY_LEN`, which is only enforced by `set`
+        assert_eq!(record.get("manufacturer"), Some(None));
+        assert_eq!(record.get("path"), None);
+    }
+
+    #[test]
+    fn parse_invalid() {
+        assert!(TxtRecord::<32>::parse(b"\x05ab").is_err());
+        assert!(TxtRecord::<32>::parse(b"\x02=1").is_err());
+        assert!(TxtRecord::<32>::parse(b"\x02\xc3\xa9").is_err());
+        assert!(TxtRecord::<4>::parse(b"\x04path").is_err());
+    }
+
+    #[test]
+    fn set() {
+        let mut record = TxtRecord::<32>::new();
+
+        record.set_str(TXT_PATH, "/").unwrap();
+        record.set(TXT_ID, None).unwrap();
+        record.set_str("PATH", "/api").unwrap();
+
+        assert_eq!(record.as_bytes(), b"\x02id\x09PATH=/api");
+        assert!(record.set("manufacturer", None).is_err());
+
+        let record = TxtRecord::<32>::parse(record.as_bytes()).unwrap();
+
+        assert_eq!(record.get_str(TXT_PATH), Some("/api"));
+        assert_eq!(record.get(TXT_ID), Some(None));
+    }
+
+    #[test]
+    fn set_over_capacity() {
+        let mut record = TxtRecord::<8>::new();
+
+        record.set_str(TXT_ID, "1234").unwrap();
+
+        assert!(record.set_str(TXT_ID, "12345").is_err());
+        assert_eq!(record.get_str(TXT_ID), Some("1234"));
+
+        record.set_str(TXT_ID, "4321").unwrap();
+
+        assert_eq!(record.get_str(TXT_ID), Some("4321"));
+    }
+
+    #[test]
+    fn remove() {
+        let mut record = TxtRecord::<32>::parse(b"\x03a=1\x03b=2\x03c=3").unwrap();
+
+        assert!(record.remove("b"));
+        assert!(!record.remove("b"));
+        assert_eq!(record.as_bytes(), b"\x03a=1\x03c=3");
+    }
+}
@@ -1 +1,6 @@
+pub mod bridge;
+pub mod broker;
 pub mod client;
+#[cfg(feature = "mqtt_sparkplug")]
+pub mod sparkplug;
+pub mod topic;
@@ -0,0 +1,306 @@
+//! Mirrors selected topics between a local MQTT connection (or broker) and a cloud one.
+//!
+//! The bridge does not own the connections' event loops: the received messages of each side
+//! are handed to `Bridge::forward`, and `Bridge::subscribe` is called whenever a side (re)connects.
+
+use core::fmt::Debug;
+
+#[cfg(feature = "use_serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::error::{impl_error, ErrorKind};
+use crate::mqtt::client::{Client, Publish, QoS};
+use crate::mqtt::topic;
+
+pub const MAX_TOPIC_LEN: usize = 128;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub enum Side {
+    Local,
+    Remote,
+}
+
+impl Side {
+    pub fn other(&self) -> Self {
+        match self {
+            Self::Local => Self::Remote,
+            Self::Remote => Self::Local,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub enum Direction {
+    /// From the local to the remote side
+    Out,
+    /// From the remote to the local side
+    In,
+    Both,
+}
+
+impl Direction {
+    pub fn forwards(&self, from: Side) -> bool {
+        matches!(
+            (self, from),
+            (Self::Both, _) | (Self::Out, Side::Local) | (Self::In, Side::Remote)
+        )
+    }
+}
+
+/// A mapping in the style of the Mosquitto `topic` bridge option: `filter` is relative to the prefixes,
+/// so messages on `{local_prefix}{topic}` are mirrored as `{remote_prefix}{topic}` and vice versa.
+///
+/// E.g. the filter `sensors/#` with the local prefix `""` and the remote prefix `"site-1/"`
+/// mirrors `sensors/temperature` as `site-1/sensors/temperature`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub struct Rule {
+    pub filter: heapless::String<64>,
+    pub direction: Direction,
+    /// Used both to subscribe and to republish
+    pub qos: QoS,
+    pub local_prefix: heapless::String<32>,
+    pub remote_prefix: heapless::String<32>,
+}
+
+impl Rule {
+    pub fn new(filter: &str, direction: Direction) -> Self {
+        Self {
+            filter: filter.into(),
+            direction,
+            qos: QoS::AtLeastOnce,
+            local_prefix: heapless::String::new(),
+            remote_prefix: heapless::String::new(),
+        }
+    }
+
+    pub fn prefix(&self, side: Side) -> &str {
+        match side {
+            Side::Local => &self.local_prefix,
+            Side::Remote => &self.remote_prefix,
+        }
+    }
+
+    /// The filter to subscribe to on `side`
+    pub fn subscription(
+        &self,
+        side: Side,
+    ) -> Result<heapless::String<MAX_TOPIC_LEN>, &'static str> {
+        let mut subscription = heapless::String::new();
+
+        subscription
+            .push_str(self.prefix(side))
+            .and_then(|_| subscription.push_str(&self.filter))
+            .map_err(|_| "Subscription too long")?;
+
+        if topic::is_valid_filter(&subscription) {
+            Ok(subscription)
+        } else {
+            Err("Invalid subscription")
+        }
+    }
+
+    /// The topic a message received on `from` with `topic` is republished as on the other side,
+    /// or `None` if the rule does not apply to it
+    pub fn map(&self, from: Side, topic: &str) -> Option<heapless::String<MAX_TOPIC_LEN>> {
+        if !self.direction.forwards(from) {
+            return None;
+        }
+
+        let relative = topic.strip_prefix(self.prefix(from))?;
+
+        if !topic::matches(&self.filter, relative) {
+            return None;
+        }
+
+        let mut mapped = heapless::String::new();
+
+        mapped.push_str(self.prefix(from.other())).ok()?;
+        mapped.push_str(relative).ok()?;
+
+        Some(mapped)
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub struct Configuration<const N: usize = 8> {
+    /// The first matching rule wins
+    pub rules: heapless::Vec<Rule, N>,
+    /// Whether the retain flag of mirrored messages is kept
+    pub retain: bool,
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum BridgeError<L, R> {
+    Local(L),
+    Remote(R),
+    ConfigurationError(&'static str),
+}
+
+impl_error! {
+    BridgeError<L: Display, R: Display> {
+        Local(e) => "Local connection error: {e}"; e.error_kind(),
+        Remote(e) => "Remote connection error: {e}"; e.error_kind(),
+        ConfigurationError(e) => "Configuration error: {e}"; ErrorKind::InvalidInput,
+    }
+}
+
+/// Bridges the `L` and `R` connections according to up to `N` rules.
+///
+/// Echoes are suppressed by remembering the last `H` messages the bridge published itself:
+/// when the broker delivers one of them back (because a rule in the other direction matches it),
+/// it is not mirrored again.
+pub struct Bridge<L, R, const N: usize = 8, const H: usize = 16> {
+    local: L,
+    remote: R,
+    configuration: Configuration<N>,
+    published: heapless::Deque<(Side, u32), H>,
+}
+
+impl<L, R, const N: usize, const H: usize> Bridge<L, R, N, H>
+where
+    L: Client + Publish,
+    R: Client + Publish,
+{
+    pub fn new(local: L, remote: R, configuration: Configuration<N>) -> Self {
+        Self {
+            local,
+            remote,
+            configuration,
+            published: heapless::Deque::new(),
+        }
+    }
+
+    pub fn configuration(&self) -> &Configuration<N> {
+        &self.configuration
+    }
+
+    /// Subscribes `side` to the topics of all rules forwarding from it.
+    /// To be called each time that side connects without a persistent session.
+    pub fn subscribe(&mut self, side: Side) -> Result<(), BridgeError<L::Error, R::Error>> {
+        for rule in &self.configuration.rules {
+            if rule.direction.forwards(side) {
+                let subscription = rule
+                    .subscription(side)
+                    .map_err(BridgeError::ConfigurationError)?;
+
+                match side {
+                    Side::Local => self
+                        .local
+                        .subscribe(&subscription, rule.qos)
+                        .map(|_| ())
+                        .map_err(BridgeError::Local)?,
+                    Side::Remote => self
+                        .remote
+                        .subscribe(&subscription, rule.qos)
+                        .map(|_| ())
+                        .map_err(BridgeError::Remote)?,
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Mirrors a message received on `from` to the other side.
+    ///
+    /// Returns `false` if no rule matched it, or if it is an echo of a message the bridge published.
+    pub fn forward(
+        &mut self,
+        from: Side,
+        topic: &str,
+        payload: &[u8],
+        retain: bool,
+    ) -> Result<bool, BridgeError<L::Error, R::Error>> {
+        let fingerprint = fingerprint(topic, payload);
+
+        if let Some(index) = self
+            .published
+            .iter()
+            .position(|published| *published == (from, fingerprint))
+        {
+            // Deque has no `remove`, so keep the other entries in order by rotating them out and back
+            for current in 0..self.published.len() {
+                let entry = self.published.pop_front();
+
+                if current != index {
+                    if let Some(entry) = entry {
+                        self.published.push_back(entry).ok();
+                    }
+                }
+            }
+
+            return Ok(false);
+        }
+
+        let (mapped, qos) = match self
+            .configuration
+            .rules
+            .iter()
+            .find_map(|rule| rule.map(from, topic).map(|mapped| (mapped, rule.qos)))
+        {
+            Some(mapped) => mapped,
+            None => return Ok(false),
+        };
+
+        let to = from.other();
+        let retain = retain && self.configuration.retain;
+
+        match to {
+            Side::Local => self
+                .local
+                .publish(&mapped, qos, retain, payload)
+                .map(|_| ())
+                .map_err(BridgeError::Local)?,
+            Side::Remote => self
+                .remote
+                .publish(&mapped, qos, retain, payload)
+                .map(|_| ())
+                .map_err(BridgeError::Remote)?,
+        }
+
+        if self.published.is_full() {
+            self.published.pop_front();
+        }
+
+        self.published
+            .push_back((to, self::fingerprint(&mapped, payload)))
+            .ok();
+
+        Ok(true)
+    }
+
+    pub fn local(&mut self) -> &mut L {
+        &mut self.local
+    }
+
+    pub fn remote(&mut self) -> &mut R {
+        &mut self.remote
+    }
+
+    pub fn release(self) -> (L, R) {
+        (self.local, self.remote)
+    }
+}
+
+/// FNV-1a over the topic and the payload
+fn fingerprint(topic: &str, payload: &[u8]) -> u32 {
+    topic
+        .as_bytes()
+        .iter()
+        .chain(&[0])
+        .chain(payload)
+        .fold(0x811c_9dc5, |hash, byte| {
+            (hash ^ *byte as u32).wrapping_mul(0x0100_0193)
+        })
+}
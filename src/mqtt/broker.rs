@@ -0,0 +1,1081 @@
+//! A minimal MQTT 3.1.1 broker, e.g. for a hub device in AP mode collecting the messages of local sensors.
+//!
+//! It supports QoS 0 and 1, retained messages, wildcard subscriptions and persistent sessions.
+//! It deliberately leaves out QoS 2, wills and the queueing of messages for disconnected clients:
+//! QoS 1 messages are delivered once to the connected subscribers and are not retransmitted.
+
+use core::time::Duration;
+
+#[cfg(feature = "use_serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::io::Read;
+use crate::mqtt::client::QoS;
+use crate::mqtt::topic;
+use crate::net::tcp::{TcpListener, TcpSocket};
+use crate::sys_time::{Instant, SystemTime};
+use crate::utils::io::try_read_full;
+
+pub const PORT: u16 = 1883;
+
+/// The longest client ID all brokers are required to accept
+pub const MAX_CLIENT_ID_LEN: usize = 23;
+pub const MAX_TOPIC_LEN: usize = 64;
+
+const CONNECT: u8 = 1;
+const PUBLISH: u8 = 3;
+const PUBACK: u8 = 4;
+const SUBSCRIBE: u8 = 8;
+const UNSUBSCRIBE: u8 = 10;
+const PINGREQ: u8 = 12;
+const DISCONNECT: u8 = 14;
+
+const CONNACK_ACCEPTED: u8 = 0;
+const CONNACK_UNACCEPTABLE_PROTOCOL: u8 = 1;
+const CONNACK_IDENTIFIER_REJECTED: u8 = 2;
+const CONNACK_SERVER_UNAVAILABLE: u8 = 3;
+const CONNACK_NOT_AUTHORIZED: u8 = 5;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub struct Configuration {
+    /// How long a new connection may take to send its CONNECT packet
+    pub connect_timeout: Duration,
+}
+
+impl Default for Configuration {
+    fn default() -> Self {
+        Self {
+            connect_timeout: Duration::from_secs(5),
+        }
+    }
+}
+
+/// Called with the client ID, the user name and the password of connecting clients
+pub type Authenticator = fn(&str, Option<&str>, Option<&[u8]>) -> bool;
+
+struct Subscription {
+    filter: heapless::String<MAX_TOPIC_LEN>,
+    qos: QoS,
+}
+
+struct Session<K, const S: usize, const B: usize> {
+    client_id: heapless::String<MAX_CLIENT_ID_LEN>,
+    socket: Option<K>,
+    clean: bool,
+    subscriptions: heapless::Vec<Subscription, S>,
+    keep_alive: Duration,
+    last_seen: Instant,
+    packet_id: u16,
+    rx: heapless::Vec<u8, B>,
+}
+
+struct Retained<const B: usize> {
+    topic: heapless::String<MAX_TOPIC_LEN>,
+    payload: heapless::Vec<u8, B>,
+    qos: QoS,
+}
+
+/// Serves up to `N` sessions with up to `S` subscriptions each, keeps up to `R` retained messages,
+/// and handles packets of up to `B` bytes.
+///
+/// The broker is driven by calling `poll` periodically, e.g. every few tens of milliseconds;
+/// only accepting a connection blocks, for up to `Configuration::connect_timeout`.
+pub struct Broker<
+    L,
+    T,
+    const N: usize = 4,
+    const S: usize = 8,
+    const R: usize = 8,
+    const B: usize = 512,
+> where
+    L: TcpListener,
+{
+    listener: L,
+    time: T,
+    configuration: Configuration,
+    authenticator: Option<Authenticator>,
+    sessions: heapless::Vec<Session<L::Socket, S, B>, N>,
+    retained: heapless::Vec<Retained<B>, R>,
+    next_client_id: u32,
+}
+
+impl<L, T, const N: usize, const S: usize, const R: usize, const B: usize> Broker<L, T, N, S, R, B>
+where
+    L: TcpListener,
+    T: SystemTime,
+{
+    pub fn new(listener: L, time: T, configuration: Configuration) -> Self {
+        Self {
+            listener,
+            time,
+            configuration,
+            authenticator: None,
+            sessions: heapless::Vec::new(),
+            retained: heapless::Vec::new(),
+            next_client_id: 0,
+        }
+    }
+
+    /// Without an authenticator, all clients are accepted
+    pub fn set_authenticator(&mut self, authenticator: Option<Authenticator>) {
+        self.authenticator = authenticator;
+    }
+
+    pub fn connected_clients(&self) -> impl Iterator<Item = &str> {
+        self.sessions
+            .iter()
+            .filter(|session| session.socket.is_some())
+            .map(|session| session.client_id.as_str())
+    }
+
+    /// Publishes a message from the device itself to the subscribed clients
+    pub fn publish(
+        &mut self,
+        topic: &str,
+        payload: &[u8],
+        qos: QoS,
+        retain: bool,
+    ) -> Result<(), &'static str> {
+        if !topic::is_valid_name(topic) || topic.len() > MAX_TOPIC_LEN {
+            return Err("Invalid topic");
+        }
+
+        if qos == QoS::ExactlyOnce {
+            return Err("QoS 2 is not supported");
+        }
+
+        if publish_len(topic, payload, qos) > B {
+            return Err("Payload too large");
+        }
+
+        self.route(topic, payload, qos, retain);
+
+        Ok(())
+    }
+
+    /// Accepts the pending connections and processes the packets received by the clients;
+    /// `on_publish` is called with the topic and payload of every message published by them.
+    pub fn poll<F>(&mut self, mut on_publish: F) -> Result<(), L::Error>
+    where
+        F: FnMut(&str, &[u8]),
+    {
+        let now = Instant::now(&self.time);
+
+        while let Some((socket, _)) = self.listener.accept(Some(Duration::ZERO))? {
+            self.accept(socket, now);
+        }
+
+        for index in 0..self.sessions.len() {
+            self.receive(index, now, &mut on_publish);
+        }
+
+        for session in &mut self.sessions {
+            // Clients are disconnected after one and a half keep alive periods without packets
+            if session.keep_alive > Duration::ZERO
+                && now.duration_since(session.last_seen) > session.keep_alive * 3 / 2
+            {
+                session.socket = None;
+            }
+        }
+
+        self.sessions
+            .retain(|session| session.socket.is_some() || !session.clean);
+
+        Ok(())
+    }
+
+    pub fn release(self) -> (L, T) {
+        (self.listener, self.time)
+    }
+
+    fn accept(&mut self, mut socket: L::Socket, now: Instant) {
+        let mut packet = [0_u8; B];
+
+        let len = match socket.wait_readable(Some(self.configuration.connect_timeout)) {
+            Ok(true) => read_packet(&mut socket, &mut packet),
+            _ => None,
+        };
+
+        let connect = match len.and_then(|len| Connect::parse(&packet[..len])) {
+            Some(connect) => connect,
+            None => return,
+        };
+
+        if connect.protocol_level != 4 && connect.protocol_level != 3 {
+            connack(&mut socket, false, CONNACK_UNACCEPTABLE_PROTOCOL);
+            return;
+        }
+
+        if let Some(authenticator) = self.authenticator {
+            if !authenticator(connect.client_id, connect.user_name, connect.password) {
+                connack(&mut socket, false, CONNACK_NOT_AUTHORIZED);
+                return;
+            }
+        }
+
+        let client_id: heapless::String<MAX_CLIENT_ID_LEN> = if connect.client_id.is_empty() {
+            if !connect.clean {
+                connack(&mut socket, false, CONNACK_IDENTIFIER_REJECTED);
+                return;
+            }
+
+            self.next_client_id += 1;
+
+            let mut client_id = heapless::String::new();
+            core::fmt::Write::write_fmt(
+                &mut client_id,
+                format_args!("auto-{}", self.next_client_id),
+            )
+            .ok();
+
+            client_id
+        } else if connect.client_id.len() > MAX_CLIENT_ID_LEN {
+            connack(&mut socket, false, CONNACK_IDENTIFIER_REJECTED);
+            return;
+        } else {
+            connect.client_id.into()
+        };
+
+        let keep_alive = Duration::from_secs(connect.keep_alive as _);
+
+        let session_present = match self
+            .sessions
+            .iter_mut()
+            .find(|session| session.client_id == client_id)
+        {
+            Some(session) => {
+                // A new connection with the same client ID takes over the session
+                let present = !connect.clean && !session.clean;
+
+                if !present {
+                    session.subscriptions.clear();
+                }
+
+                session.clean = connect.clean;
+                session.keep_alive = keep_alive;
+                session.last_seen = now;
+                session.rx.clear();
+
+                if !connack(&mut socket, present, CONNACK_ACCEPTED) {
+                    session.socket = None;
+                    return;
+                }
+
+                session.socket = Some(socket);
+
+                return;
+            }
+            None => false,
+        };
+
+        if self.sessions.is_full() {
+            connack(&mut socket, false, CONNACK_SERVER_UNAVAILABLE);
+            return;
+        }
+
+        if connack(&mut socket, session_present, CONNACK_ACCEPTED) {
+            self.sessions
+                .push(Session {
+                    client_id,
+                    socket: Some(socket),
+                    clean: connect.clean,
+                    subscriptions: heapless::Vec::new(),
+                    keep_alive,
+                    last_seen: now,
+                    packet_id: 0,
+                    rx: heapless::Vec::new(),
+                })
+                .ok();
+        }
+    }
+
+    fn receive<F>(&mut self, index: usize, now: Instant, on_publish: &mut F)
+    where
+        F: FnMut(&str, &[u8]),
+    {
+        let session = &mut self.sessions[index];
+
+        let socket = match session.socket.as_mut() {
+            Some(socket) => socket,
+            None => return,
+        };
+
+        match socket.wait_readable(Some(Duration::ZERO)) {
+            Ok(true) => (),
+            Ok(false) => return,
+            Err(_) => {
+                session.socket = None;
+                return;
+            }
+        }
+
+        let mut chunk = [0_u8; B];
+        let space = B - session.rx.len();
+
+        match socket.read(&mut chunk[..space]) {
+            // Also when the packet being received does not fit into the buffer
+            Ok(0) | Err(_) => {
+                session.socket = None;
+                return;
+            }
+            Ok(len) => {
+                session.rx.extend_from_slice(&chunk[..len]).ok();
+                session.last_seen = now;
+            }
+        }
+
+        loop {
+            let session = &mut self.sessions[index];
+
+            let len = match packet_len(&session.rx) {
+                Some(len) if len <= session.rx.len() => len,
+                _ => return,
+            };
+
+            let mut packet = [0_u8; B];
+            packet[..len].copy_from_slice(&session.rx[..len]);
+
+            let rest = session.rx.len() - len;
+            session.rx.copy_within(len.., 0);
+            session.rx.truncate(rest);
+
+            if !self.process(index, &packet[..len], on_publish) {
+                self.sessions[index].socket = None;
+                return;
+            }
+        }
+    }
+
+    /// Returns `false` if the client should be disconnected
+    fn process<F>(&mut self, index: usize, packet: &[u8], on_publish: &mut F) -> bool
+    where
+        F: FnMut(&str, &[u8]),
+    {
+        let header = packet[0];
+        let body = &packet[header_len(packet)..];
+
+        match header >> 4 {
+            PUBLISH => {
+                let qos = match (header >> 1) & 0x03 {
+                    0 => QoS::AtMostOnce,
+                    1 => QoS::AtLeastOnce,
+                    _ => return false,
+                };
+
+                let (topic, mut offset) = match read_str(body, 0) {
+                    Some(topic) => topic,
+                    None => return false,
+                };
+
+                if !topic::is_valid_name(topic) || topic.len() > MAX_TOPIC_LEN {
+                    return false;
+                }
+
+                if qos == QoS::AtLeastOnce {
+                    if body.len() < offset + 2 {
+                        return false;
+                    }
+
+                    let ack = [PUBACK << 4, 2, body[offset], body[offset + 1]];
+                    offset += 2;
+
+                    if !self.send(index, &ack) {
+                        return false;
+                    }
+                }
+
+                let payload = &body[offset..];
+
+                self.route(topic, payload, qos, header & 0x01 != 0);
+                on_publish(topic, payload);
+
+                true
+            }
+            PUBACK => true,
+            SUBSCRIBE if header & 0x0f == 0x02 && body.len() >= 2 => {
+                let mut ack = heapless::Vec::<u8, 64>::new();
+                ack.extend_from_slice(&[0x90, 0, body[0], body[1]]).ok();
+
+                let mut offset = 2;
+
+                // Retained messages are only sent for the filters of this packet
+                let mut granted = heapless::Vec::<Subscription, S>::new();
+
+                while offset < body.len() {
+                    let (filter, next) = match read_str(body, offset) {
+                        Some(entry) if next_byte(body, entry.1).is_some() => entry,
+                        _ => return false,
+                    };
+
+                    let requested = body[next] & 0x03;
+                    offset = next + 1;
+
+                    let code = if topic::is_valid_filter(filter) && filter.len() <= MAX_TOPIC_LEN {
+                        let qos = if requested == 0 {
+                            QoS::AtMostOnce
+                        } else {
+                            QoS::AtLeastOnce
+                        };
+
+                        if subscribe(&mut self.sessions[index].subscriptions, filter, qos) {
+                            // Cannot fail, as the session has room for all the filters granted
+                            subscribe(&mut granted, filter, qos);
+
+                            qos as u8
+                        } else {
+                            0x80
+                        }
+                    } else {
+                        0x80
+                    };
+
+                    if ack.push(code).is_err() {
+                        return false;
+                    }
+                }
+
+                ack[1] = (ack.len() - 2) as u8;
+
+                if !self.send(index, &ack) {
+                    return false;
+                }
+
+                self.send_retained(index, &granted)
+            }
+            UNSUBSCRIBE if header & 0x0f == 0x02 && body.len() >= 2 => {
+                let mut offset = 2;
+
+                while offset < body.len() {
+                    let (filter, next) = match read_str(body, offset) {
+                        Some(filter) => filter,
+                        None => return false,
+                    };
+
+                    offset = next;
+
+                    self.sessions[index]
+                        .subscriptions
+                        .retain(|subscription| subscription.filter != filter);
+                }
+
+                self.send(index, &[UNSUBSCRIBE << 4 | 0x01, 2, body[0], body[1]])
+            }
+            PINGREQ => self.send(index, &[0xd0, 0]),
+            DISCONNECT => {
+                if let Some(socket) = self.sessions[index].socket.as_mut() {
+                    socket.shutdown().ok();
+                }
+
+                false
+            }
+            // Including a second CONNECT, which is a protocol violation
+            _ => false,
+        }
+    }
+
+    fn send_retained(
+        &mut self,
+        index: usize,
+        subscriptions: &heapless::Vec<Subscription, S>,
+    ) -> bool {
+        let mut packet = [0_u8; B];
+
+        for retained in &self.retained {
+            let session = &mut self.sessions[index];
+
+            if let Some(granted) = granted_qos(subscriptions, &retained.topic) {
+                let qos = min_qos(retained.qos, granted);
+
+                let len = match encode_publish(
+                    &mut packet,
+                    &retained.topic,
+                    &retained.payload,
+                    qos,
+                    true,
+                    next_packet_id(&mut session.packet_id),
+                ) {
+                    Some(len) => len,
+                    None => continue,
+                };
+
+                if let Some(socket) = session.socket.as_mut() {
+                    if !write_packet(socket, &packet[..len]) {
+                        return false;
+                    }
+                }
+            }
+        }
+
+        true
+    }
+
+    fn route(&mut self, topic: &str, payload: &[u8], qos: QoS, retain: bool) {
+        if retain {
+            self.retain(topic, payload, qos);
+        }
+
+        let mut packet = [0_u8; B];
+
+        for session in &mut self.sessions {
+            let granted = match granted_qos(&session.subscriptions, topic) {
+                Some(granted) => granted,
+                None => continue,
+            };
+
+            if let Some(socket) = session.socket.as_mut() {
+                let qos = min_qos(qos, granted);
+
+                let len = match encode_publish(
+                    &mut packet,
+                    topic,
+                    payload,
+                    qos,
+                    false,
+                    next_packet_id(&mut session.packet_id),
+                ) {
+                    Some(len) => len,
+                    // Does not fit at this QoS, but may fit for the other subscribers
+                    None => continue,
+                };
+
+                if !write_packet(socket, &packet[..len]) {
+                    session.socket = None;
+                }
+            }
+        }
+    }
+
+    /// Messages are not retained when there is no room left for them
+    fn retain(&mut self, topic: &str, payload: &[u8], qos: QoS) {
+        let index = self
+            .retained
+            .iter()
+            .position(|retained| retained.topic == topic);
+
+        if payload.is_empty() {
+            if let Some(index) = index {
+                self.retained.swap_remove(index);
+            }
+        } else if let Ok(payload) = heapless::Vec::from_slice(payload) {
+            match index {
+                Some(index) => {
+                    self.retained[index].payload = payload;
+                    self.retained[index].qos = qos;
+                }
+                None => {
+                    self.retained
+                        .push(Retained {
+                            topic: topic.into(),
+                            payload,
+                            qos,
+                        })
+                        .ok();
+                }
+            }
+        }
+    }
+
+    fn send(&mut self, index: usize, packet: &[u8]) -> bool {
+        match self.sessions[index].socket.as_mut() {
+            Some(socket) => write_packet(socket, packet),
+            None => false,
+        }
+    }
+}
+
+struct Connect<'a> {
+    protocol_level: u8,
+    clean: bool,
+    keep_alive: u16,
+    client_id: &'a str,
+    user_name: Option<&'a str>,
+    password: Option<&'a [u8]>,
+}
+
+impl<'a> Connect<'a> {
+    fn parse(packet: &'a [u8]) -> Option<Self> {
+        if packet[0] != CONNECT << 4 {
+            return None;
+        }
+
+        let body = &packet[header_len(packet)..];
+
+        let (_protocol_name, offset) = read_str(body, 0)?;
+
+        let protocol_level = next_byte(body, offset)?;
+        let flags = next_byte(body, offset + 1)?;
+        let keep_alive =
+            u16::from_be_bytes([next_byte(body, offset + 2)?, next_byte(body, offset + 3)?]);
+
+        let (client_id, mut offset) = read_str(body, offset + 4)?;
+
+        if flags & 0x04 != 0 {
+            // The will topic and message, which are not supported
+            offset = read_bytes(body, offset)?.1;
+            offset = read_bytes(body, offset)?.1;
+        }
+
+        let user_name = if flags & 0x80 != 0 {
+            let (user_name, next) = read_str(body, offset)?;
+            offset = next;
+
+            Some(user_name)
+        } else {
+            None
+        };
+
+        let password = if flags & 0x40 != 0 {
+            Some(read_bytes(body, offset)?.0)
+        } else {
+            None
+        };
+
+        Some(Self {
+            protocol_level,
+            clean: flags & 0x02 != 0,
+            keep_alive,
+            client_id,
+            user_name,
+            password,
+        })
+    }
+}
+
+/// Adds `filter` to `subscriptions`, or updates its QoS if already there
+fn subscribe<const S: usize>(
+    subscriptions: &mut heapless::Vec<Subscription, S>,
+    filter: &str,
+    qos: QoS,
+) -> bool {
+    if let Some(subscription) = subscriptions
+        .iter_mut()
+        .find(|subscription| subscription.filter == filter)
+    {
+        subscription.qos = qos;
+        true
+    } else {
+        subscriptions
+            .push(Subscription {
+                filter: filter.into(),
+                qos,
+            })
+            .is_ok()
+    }
+}
+
+fn granted_qos<const S: usize>(
+    subscriptions: &heapless::Vec<Subscription, S>,
+    topic: &str,
+) -> Option<QoS> {
+    subscriptions
+        .iter()
+        .filter(|subscription| topic::matches(&subscription.filter, topic))
+        .map(|subscription| subscription.qos)
+        .fold(None, |granted, qos| match granted {
+            Some(granted) if granted >= qos => Some(granted),
+            _ => Some(qos),
+        })
+}
+
+fn min_qos(a: QoS, b: QoS) -> QoS {
+    if a <= b {
+        a
+    } else {
+        b
+    }
+}
+
+fn next_packet_id(packet_id: &mut u16) -> u16 {
+    *packet_id = packet_id.checked_add(1).unwrap_or(1);
+
+    *packet_id
+}
+
+fn encode_publish(
+    buf: &mut [u8],
+    topic: &str,
+    payload: &[u8],
+    qos: QoS,
+    retain: bool,
+    packet_id: u16,
+) -> Option<usize> {
+    let id_len = if qos == QoS::AtMostOnce { 0 } else { 2 };
+    let remaining = 2 + topic.len() + id_len + payload.len();
+
+    let mut offset = 0;
+
+    buf.get_mut(0)
+        .map(|byte| *byte = PUBLISH << 4 | (qos as u8) << 1 | retain as u8)?;
+    offset += 1;
+    offset += encode_len(buf.get_mut(offset..)?, remaining)?;
+
+    let buf = buf.get_mut(offset..offset + remaining)?;
+
+    buf[..2].copy_from_slice(&(topic.len() as u16).to_be_bytes());
+    buf[2..2 + topic.len()].copy_from_slice(topic.as_bytes());
+
+    let mut position = 2 + topic.len();
+
+    if id_len > 0 {
+        buf[position..position + 2].copy_from_slice(&packet_id.to_be_bytes());
+        position += 2;
+    }
+
+    buf[position..].copy_from_slice(payload);
+
+    Some(offset + remaining)
+}
+
+/// The length of the PUBLISH packet `encode_publish` encodes
+fn publish_len(topic: &str, payload: &[u8], qos: QoS) -> usize {
+    let id_len = if qos == QoS::AtMostOnce { 0 } else { 2 };
+    let remaining = 2 + topic.len() + id_len + payload.len();
+
+    let len_len = match remaining {
+        0..=127 => 1,
+        128..=16_383 => 2,
+        16_384..=2_097_151 => 3,
+        _ => 4,
+    };
+
+    1 + len_len + remaining
+}
+
+fn encode_len(buf: &mut [u8], mut len: usize) -> Option<usize> {
+    let mut offset = 0;
+
+    loop {
+        let mut byte = (len % 128) as u8;
+        len /= 128;
+
+        if len > 0 {
+            byte |= 0x80;
+        }
+
+        *buf.get_mut(offset)? = byte;
+        offset += 1;
+
+        if len == 0 {
+            return Some(offset);
+        }
+    }
+}
+
+/// The length of the whole packet at the start of `buf`, once its fixed header is complete
+fn packet_len(buf: &[u8]) -> Option<usize> {
+    let mut len = 0;
+
+    for index in 0..4 {
+        let byte = *buf.get(1 + index)?;
+
+        len |= ((byte & 0x7f) as usize) << (7 * index);
+
+        if byte & 0x80 == 0 {
+            return Some(2 + index + len);
+        }
+    }
+
+    // Invalid remaining length, which can never fit into the buffer
+    Some(usize::MAX)
+}
+
+/// The length of the fixed header of a complete packet
+fn header_len(packet: &[u8]) -> usize {
+    1 + packet[1..]
+        .iter()
+        .take(4)
+        .position(|byte| byte & 0x80 == 0)
+        .map(|index| index + 1)
+        .unwrap_or(4)
+}
+
+fn read_packet<K: TcpSocket>(socket: &mut K, buf: &mut [u8]) -> Option<usize> {
+    let mut len = 0;
+
+    // The fixed header is at most 5 bytes long
+    while packet_len(&buf[..len]).is_none() {
+        if len == buf.len().min(5) {
+            return None;
+        }
+
+        // The client closed the connection within the fixed header
+        match try_read_full(&mut *socket, &mut buf[len..len + 1]).ok()? {
+            0 => return None,
+            read => len += read,
+        }
+    }
+
+    let total = packet_len(&buf[..len])?;
+
+    if total > buf.len() {
+        return None;
+    }
+
+    if try_read_full(&mut *socket, &mut buf[len..total]).ok()? < total - len {
+        return None;
+    }
+
+    Some(total)
+}
+
+fn write_packet<K: TcpSocket>(socket: &mut K, packet: &[u8]) -> bool {
+    socket.write_all(packet).is_ok() && socket.flush().is_ok()
+}
+
+fn connack<K: TcpSocket>(socket: &mut K, session_present: bool, code: u8) -> bool {
+    write_packet(socket, &[0x20, 2, session_present as u8, code])
+}
+
+fn next_byte(buf: &[u8], offset: usize) -> Option<u8> {
+    buf.get(offset).copied()
+}
+
+fn read_bytes(buf: &[u8], offset: usize) -> Option<(&[u8], usize)> {
+    let len = u16::from_be_bytes([next_byte(buf, offset)?, next_byte(buf, offset + 1)?]) as usize;
+    let start = offset + 2;
+
+    Some((buf.get(start..start + len)?, start + len))
+}
+
+fn read_str(buf: &[u8], offset: usize) -> Option<(&str, usize)> {
+    let (bytes, next) = read_bytes(buf, offset)?;
+
+    Some((core::str::from_utf8(bytes).ok()?, next))
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::{Cell, RefCell};
+    use core::convert::Infallible;
+
+    use crate::io::{Io, Write};
+    use crate::ipv4::{Ipv4Addr, SocketAddrV4};
+
+    use super::*;
+
+    #[test]
+    fn publish_packet() {
+        let mut buf = [0_u8; 256];
+
+        let len = encode_publish(&mut buf, "a/b", b"hello", QoS::AtLeastOnce, true, 7).unwrap();
+
+        assert_eq!(
+            &buf[..len],
+            &[0x33, 12, 0, 3, b'a', b'/', b'b', 0, 7, b'h', b'e', b'l', b'l', b'o']
+        );
+        assert_eq!(len, publish_len("a/b", b"hello", QoS::AtLeastOnce));
+        assert_eq!(packet_len(&buf[..2]), Some(len));
+        assert_eq!(header_len(&buf[..len]), 2);
+        assert_eq!(read_str(&buf[2..len], 0), Some(("a/b", 5)));
+
+        let payload = [0_u8; 200];
+
+        let len = encode_publish(&mut buf, "a", &payload, QoS::AtMostOnce, false, 0).unwrap();
+
+        assert_eq!(&buf[..3], &[0x30, 203 & 0x7f | 0x80, 1]);
+        assert_eq!(len, publish_len("a", &payload, QoS::AtMostOnce));
+        assert_eq!(packet_len(&buf[..2]), None);
+        assert_eq!(packet_len(&buf[..3]), Some(len));
+        assert_eq!(header_len(&buf[..len]), 3);
+
+        assert_eq!(
+            encode_publish(
+                &mut buf[..len - 1],
+                "a",
+                &payload,
+                QoS::AtMostOnce,
+                false,
+                0
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn invalid_packet() {
+        assert_eq!(
+            packet_len(&[0x30, 0xff, 0xff, 0xff, 0xff]),
+            Some(usize::MAX)
+        );
+        assert_eq!(read_str(&[0, 3, b'a'], 0), None);
+        assert_eq!(read_str(&[0, 1, 0xff], 0), None);
+    }
+
+    #[test]
+    fn connect_packet() {
+        let packet = [
+            0x10, 22, 0, 4, b'M', b'Q', b'T', b'T', 4, 0xc2, 0, 60, 0, 2, b'i', b'd', 0, 1, b'u',
+            0, 3, b'p', b'w', b'd',
+        ];
+
+        let connect = Connect::parse(&packet).unwrap();
+
+        assert_eq!(connect.protocol_level, 4);
+        assert!(connect.clean);
+        assert_eq!(connect.keep_alive, 60);
+        assert_eq!(connect.client_id, "id");
+        assert_eq!(connect.user_name, Some("u"));
+        assert_eq!(connect.password, Some(&b"pwd"[..]));
+
+        // The password is missing
+        assert!(Connect::parse(&packet[..21]).is_none());
+        // Not a CONNECT packet
+        assert!(Connect::parse(&[0x20, 2, 0, 0]).is_none());
+    }
+
+    struct Client<'a> {
+        rx: &'a [u8],
+        tx: &'a RefCell<heapless::Vec<u8, 256>>,
+    }
+
+    impl<'a> Io for Client<'a> {
+        type Error = Infallible;
+    }
+
+    impl<'a> Read for Client<'a> {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+            let len = buf.len().min(self.rx.len());
+
+            buf[..len].copy_from_slice(&self.rx[..len]);
+            self.rx = &self.rx[len..];
+
+            Ok(len)
+        }
+    }
+
+    impl<'a> Write for Client<'a> {
+        fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+            self.tx.borrow_mut().extend_from_slice(buf).unwrap();
+
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> Result<(), Self::Error> {
+            Ok(())
+        }
+    }
+
+    impl<'a> TcpSocket for Client<'a> {
+        fn local_addr(&self) -> Result<SocketAddrV4, Self::Error> {
+            Ok(SocketAddrV4::new(Ipv4Addr::LOCALHOST, PORT))
+        }
+
+        fn peer_addr(&self) -> Result<SocketAddrV4, Self::Error> {
+            Ok(SocketAddrV4::new(Ipv4Addr::LOCALHOST, 50000))
+        }
+
+        fn wait_readable(&mut self, _timeout: Option<Duration>) -> Result<bool, Self::Error> {
+            Ok(!self.rx.is_empty())
+        }
+
+        fn shutdown(&mut self) -> Result<(), Self::Error> {
+            Ok(())
+        }
+    }
+
+    struct Listener<'a>(Option<Client<'a>>);
+
+    impl<'a> Io for Listener<'a> {
+        type Error = Infallible;
+    }
+
+    impl<'a> TcpListener for Listener<'a> {
+        type Socket = Client<'a>;
+
+        fn local_addr(&self) -> Result<SocketAddrV4, Self::Error> {
+            Ok(SocketAddrV4::new(Ipv4Addr::LOCALHOST, PORT))
+        }
+
+        fn accept(
+            &mut self,
+            _timeout: Option<Duration>,
+        ) -> Result<Option<(Self::Socket, SocketAddrV4)>, Self::Error> {
+            Ok(self
+                .0
+                .take()
+                .map(|client| (client, SocketAddrV4::new(Ipv4Addr::LOCALHOST, 50000))))
+        }
+    }
+
+    struct Clock(Cell<u64>);
+
+    impl SystemTime for Clock {
+        fn now(&self) -> Duration {
+            Duration::from_secs(self.0.get())
+        }
+    }
+
+    #[test]
+    fn retained_for_new_subscriptions() {
+        let rx = [
+            // CONNECT, with client ID "c"
+            0x10, 13, 0, 4, b'M', b'Q', b'T', b'T', 4, 0x02, 0, 0, 0, 1, b'c',
+            // SUBSCRIBE to "a", then to "b"
+            0x82, 6, 0, 1, 0, 1, b'a', 0, 0x82, 6, 0, 2, 0, 1, b'b', 0,
+        ];
+
+        let tx = RefCell::new(heapless::Vec::new());
+
+        let mut broker: Broker<_, _> = Broker::new(
+            Listener(Some(Client { rx: &rx, tx: &tx })),
+            Clock(Cell::new(0)),
+            Default::default(),
+        );
+
+        broker.publish("a", b"1", QoS::AtMostOnce, true).unwrap();
+        broker.publish("b", b"2", QoS::AtMostOnce, true).unwrap();
+
+        broker.poll(|_, _| ()).unwrap();
+
+        assert_eq!(
+            &tx.borrow()[..],
+            &[
+                // CONNACK
+                0x20, 2, 0, 0, //
+                // SUBACK and the message retained on "a"
+                0x90, 3, 0, 1, 0, 0x31, 4, 0, 1, b'a', b'1',
+                // SUBACK and only the message retained on "b"
+                0x90, 3, 0, 2, 0, 0x31, 4, 0, 1, b'b', b'2',
+            ][..]
+        );
+    }
+
+    #[test]
+    fn truncated_connect() {
+        // The client closes the connection within the fixed header, then within the variable header
+        for rx in [&[0x10][..], &[0x10, 13, 0, 4, b'M']] {
+            let tx = RefCell::new(heapless::Vec::new());
+
+            let mut broker: Broker<_, _> = Broker::new(
+                Listener(Some(Client { rx, tx: &tx })),
+                Clock(Cell::new(0)),
+                Default::default(),
+            );
+
+            broker.poll(|_, _| ()).unwrap();
+
+            assert!(tx.borrow().is_empty());
+        }
+    }
+
+    #[test]
+    fn publish_too_large() {
+        let mut broker: Broker<_, _, 4, 8, 8, 32> =
+            Broker::new(Listener(None), Clock(Cell::new(0)), Default::default());
+
+        assert!(broker
+            .publish("a", &[0; 25], QoS::AtLeastOnce, true)
+            .is_ok());
+        assert_eq!(
+            broker.publish("a", &[0; 26], QoS::AtLeastOnce, true),
+            Err("Payload too large")
+        );
+        assert_eq!(
+            broker.publish("a", &[0; 27], QoS::AtMostOnce, false),
+            Ok(())
+        );
+    }
+}
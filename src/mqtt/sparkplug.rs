@@ -0,0 +1,384 @@
+//! Sparkplug B (version 3.0) topics and payloads for edge nodes.
+//!
+//! Payloads are encoded directly into a caller-provided buffer as the `Payload` protobuf message of
+//! the Sparkplug B schema. Only the scalar metric types are supported; data sets, templates,
+//! metadata and properties are not.
+
+use core::fmt::Write;
+
+#[cfg(feature = "use_serde")]
+use serde::{Deserialize, Serialize};
+
+pub const NAMESPACE: &str = "spBv1.0";
+
+/// The metric which NBIRTH and NDEATH payloads carry, so that the host application can pair them
+pub const BD_SEQ: &str = "bdSeq";
+
+/// The metric with which the host application requests a rebirth in NCMD payloads
+pub const REBIRTH: &str = "Node Control/Rebirth";
+
+pub const MAX_TOPIC_LEN: usize = 128;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub enum MessageType {
+    NBirth,
+    NDeath,
+    DBirth,
+    DDeath,
+    NData,
+    DData,
+    NCmd,
+    DCmd,
+}
+
+impl MessageType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::NBirth => "NBIRTH",
+            Self::NDeath => "NDEATH",
+            Self::DBirth => "DBIRTH",
+            Self::DDeath => "DDEATH",
+            Self::NData => "NDATA",
+            Self::DData => "DDATA",
+            Self::NCmd => "NCMD",
+            Self::DCmd => "DCMD",
+        }
+    }
+
+    pub fn is_device(&self) -> bool {
+        matches!(self, Self::DBirth | Self::DDeath | Self::DData | Self::DCmd)
+    }
+}
+
+/// `spBv1.0/{group_id}/{message_type}/{edge_node_id}[/{device_id}]`; `device_id` is required
+/// exactly for the device message types
+pub fn topic(
+    group_id: &str,
+    message_type: MessageType,
+    edge_node_id: &str,
+    device_id: Option<&str>,
+) -> Result<heapless::String<MAX_TOPIC_LEN>, &'static str> {
+    let valid = |id: &str| !id.is_empty() && !id.contains(['/', '+', '#']);
+
+    if !valid(group_id) || !valid(edge_node_id) || !device_id.map(valid).unwrap_or(true) {
+        return Err("Invalid Sparkplug ID");
+    }
+
+    if message_type.is_device() != device_id.is_some() {
+        return Err("Device ID required exactly for device messages");
+    }
+
+    let mut topic = heapless::String::new();
+
+    write!(
+        &mut topic,
+        "{NAMESPACE}/{group_id}/{}/{edge_node_id}",
+        message_type.as_str()
+    )
+    .map_err(|_| "Topic too long")?;
+
+    if let Some(device_id) = device_id {
+        write!(&mut topic, "/{device_id}").map_err(|_| "Topic too long")?;
+    }
+
+    Ok(topic)
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+#[repr(u32)]
+pub enum DataType {
+    Int8 = 1,
+    Int16 = 2,
+    Int32 = 3,
+    Int64 = 4,
+    UInt8 = 5,
+    UInt16 = 6,
+    UInt32 = 7,
+    UInt64 = 8,
+    Float = 9,
+    Double = 10,
+    Boolean = 11,
+    String = 12,
+    DateTime = 13,
+    Text = 14,
+    Uuid = 15,
+    Bytes = 17,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Value<'a> {
+    Int8(i8),
+    Int16(i16),
+    Int32(i32),
+    Int64(i64),
+    UInt8(u8),
+    UInt16(u16),
+    UInt32(u32),
+    UInt64(u64),
+    Float(f32),
+    Double(f64),
+    Boolean(bool),
+    String(&'a str),
+    /// Milliseconds since the UNIX epoch
+    DateTime(u64),
+    Bytes(&'a [u8]),
+    /// A metric of the given type without a value, e.g. when a sensor is unavailable
+    Null(DataType),
+}
+
+impl<'a> Value<'a> {
+    pub fn data_type(&self) -> DataType {
+        match self {
+            Self::Int8(_) => DataType::Int8,
+            Self::Int16(_) => DataType::Int16,
+            Self::Int32(_) => DataType::Int32,
+            Self::Int64(_) => DataType::Int64,
+            Self::UInt8(_) => DataType::UInt8,
+            Self::UInt16(_) => DataType::UInt16,
+            Self::UInt32(_) => DataType::UInt32,
+            Self::UInt64(_) => DataType::UInt64,
+            Self::Float(_) => DataType::Float,
+            Self::Double(_) => DataType::Double,
+            Self::Boolean(_) => DataType::Boolean,
+            Self::String(_) => DataType::String,
+            Self::DateTime(_) => DataType::DateTime,
+            Self::Bytes(_) => DataType::Bytes,
+            Self::Null(data_type) => *data_type,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Metric<'a> {
+    pub name: &'a str,
+    /// Once announced along with the name in the birth certificate, data messages carry only the alias
+    pub alias: Option<u64>,
+    /// Milliseconds since the UNIX epoch, if different from the timestamp of the payload
+    pub timestamp: Option<u64>,
+    pub value: Value<'a>,
+}
+
+impl<'a> Metric<'a> {
+    pub const fn new(name: &'a str, value: Value<'a>) -> Self {
+        Self {
+            name,
+            alias: None,
+            timestamp: None,
+            value,
+        }
+    }
+
+    pub const fn with_alias(name: &'a str, alias: u64, value: Value<'a>) -> Self {
+        Self {
+            name,
+            alias: Some(alias),
+            timestamp: None,
+            value,
+        }
+    }
+}
+
+/// Tracks the `seq` and `bdSeq` numbers of an edge node.
+#[derive(Clone, Debug, Default)]
+pub struct EdgeNode {
+    seq: u8,
+    bd_seq: u64,
+}
+
+impl EdgeNode {
+    pub const fn new() -> Self {
+        Self { seq: 0, bd_seq: 0 }
+    }
+
+    /// The NDEATH payload to register as the will of a new MQTT session.
+    /// The NBIRTH payload published once connected announces the same `bdSeq`.
+    pub fn death<'b>(&mut self, buf: &'b mut [u8]) -> Result<&'b [u8], &'static str> {
+        let mut payload = PayloadEncoder::new(buf, None, None)?;
+
+        payload.metric(&Metric::new(BD_SEQ, Value::UInt64(self.bd_seq)), true)?;
+
+        Ok(payload.finish())
+    }
+
+    /// The NBIRTH payload, which restarts the sequence numbers; it should list all metrics of the node with their aliases
+    pub fn birth<'b>(
+        &mut self,
+        buf: &'b mut [u8],
+        timestamp: u64,
+        metrics: &[Metric<'_>],
+    ) -> Result<&'b [u8], &'static str> {
+        self.seq = 0;
+
+        let mut payload = PayloadEncoder::new(buf, Some(timestamp), Some(0))?;
+
+        payload.metric(&Metric::new(BD_SEQ, Value::UInt64(self.bd_seq)), true)?;
+
+        for metric in metrics {
+            payload.metric(metric, true)?;
+        }
+
+        self.bd_seq = (self.bd_seq + 1) % 256;
+
+        Ok(payload.finish())
+    }
+
+    /// An NDATA, DBIRTH, DDATA or DDEATH payload with the next sequence number; `birth` tells
+    /// whether metric names are to be included along with the aliases, i.e. for DBIRTH
+    pub fn payload<'b>(
+        &mut self,
+        buf: &'b mut [u8],
+        timestamp: u64,
+        metrics: &[Metric<'_>],
+        birth: bool,
+    ) -> Result<&'b [u8], &'static str> {
+        self.seq = self.seq.wrapping_add(1);
+
+        let mut payload = PayloadEncoder::new(buf, Some(timestamp), Some(self.seq as _))?;
+
+        for metric in metrics {
+            payload.metric(metric, birth)?;
+        }
+
+        Ok(payload.finish())
+    }
+
+    pub fn seq(&self) -> u8 {
+        self.seq
+    }
+
+    /// The `bdSeq` of the next session
+    pub fn bd_seq(&self) -> u64 {
+        self.bd_seq
+    }
+}
+
+/// Encodes a Sparkplug B `Payload` message metric by metric.
+pub struct PayloadEncoder<'b> {
+    buf: &'b mut [u8],
+    len: usize,
+}
+
+impl<'b> PayloadEncoder<'b> {
+    pub fn new(
+        buf: &'b mut [u8],
+        timestamp: Option<u64>,
+        seq: Option<u64>,
+    ) -> Result<Self, &'static str> {
+        let mut encoder = Self { buf, len: 0 };
+
+        if let Some(timestamp) = timestamp {
+            encoder.varint_field(1, timestamp)?;
+        }
+
+        if let Some(seq) = seq {
+            encoder.varint_field(3, seq)?;
+        }
+
+        Ok(encoder)
+    }
+
+    /// Appends a metric; its name is omitted when it has an alias, unless `birth` is set
+    pub fn metric(&mut self, metric: &Metric<'_>, birth: bool) -> Result<(), &'static str> {
+        self.varint(2 << 3 | 2)?;
+
+        // Encode the metric after room for its length, and move it into place once its length is known
+        let start = self.len;
+        self.len += 5;
+
+        if metric.alias.is_none() || birth {
+            self.bytes_field(1, metric.name.as_bytes())?;
+        }
+
+        if let Some(alias) = metric.alias {
+            self.varint_field(2, alias)?;
+        }
+
+        if let Some(timestamp) = metric.timestamp {
+            self.varint_field(3, timestamp)?;
+        }
+
+        self.varint_field(4, metric.value.data_type() as u32 as _)?;
+
+        match metric.value {
+            Value::Int8(value) => self.varint_field(10, value as i32 as u32 as _)?,
+            Value::Int16(value) => self.varint_field(10, value as i32 as u32 as _)?,
+            Value::Int32(value) => self.varint_field(10, value as u32 as _)?,
+            Value::UInt8(value) => self.varint_field(10, value as _)?,
+            Value::UInt16(value) => self.varint_field(10, value as _)?,
+            Value::UInt32(value) => self.varint_field(10, value as _)?,
+            Value::Int64(value) => self.varint_field(11, value as u64)?,
+            Value::UInt64(value) | Value::DateTime(value) => self.varint_field(11, value)?,
+            Value::Float(value) => {
+                self.varint(12 << 3 | 5)?;
+                self.raw(&value.to_le_bytes())?;
+            }
+            Value::Double(value) => {
+                self.varint(13 << 3 | 1)?;
+                self.raw(&value.to_le_bytes())?;
+            }
+            Value::Boolean(value) => self.varint_field(14, value as _)?,
+            Value::String(value) => self.bytes_field(15, value.as_bytes())?,
+            Value::Bytes(value) => self.bytes_field(16, value)?,
+            Value::Null(_) => self.varint_field(7, 1)?,
+        }
+
+        let content = start + 5..self.len;
+        let metric_len = content.len();
+
+        self.len = start;
+        self.varint(metric_len as _)?;
+
+        self.buf.copy_within(content, self.len);
+        self.len += metric_len;
+
+        Ok(())
+    }
+
+    pub fn finish(self) -> &'b [u8] {
+        &self.buf[..self.len]
+    }
+
+    fn varint_field(&mut self, field: u64, value: u64) -> Result<(), &'static str> {
+        self.varint(field << 3)?;
+        self.varint(value)
+    }
+
+    fn bytes_field(&mut self, field: u64, value: &[u8]) -> Result<(), &'static str> {
+        self.varint(field << 3 | 2)?;
+        self.varint(value.len() as _)?;
+        self.raw(value)
+    }
+
+    fn varint(&mut self, mut value: u64) -> Result<(), &'static str> {
+        loop {
+            let byte = (value & 0x7f) as u8;
+            value >>= 7;
+
+            if value == 0 {
+                return self.raw(&[byte]);
+            }
+
+            self.raw(&[byte | 0x80])?;
+        }
+    }
+
+    fn raw(&mut self, data: &[u8]) -> Result<(), &'static str> {
+        let buf = self
+            .buf
+            .get_mut(self.len..self.len + data.len())
+            .ok_or("Buffer too small")?;
+
+        buf.copy_from_slice(data);
+        self.len += data.len();
+
+        Ok(())
+    }
+}
@@ -0,0 +1,49 @@
+/// Whether `topic` matches `filter`, which may contain the `+` and `#` wildcards.
+///
+/// As per the MQTT specification, filters starting with a wildcard do not match topics starting with `$`.
+pub fn matches(filter: &str, topic: &str) -> bool {
+    if topic.starts_with('$') && (filter.starts_with('+') || filter.starts_with('#')) {
+        return false;
+    }
+
+    let mut filter_levels = filter.split('/');
+    let mut topic_levels = topic.split('/');
+
+    loop {
+        match (filter_levels.next(), topic_levels.next()) {
+            (Some("#"), _) => return true,
+            (Some("+"), Some(_)) => (),
+            (Some(filter_level), Some(topic_level)) if filter_level == topic_level => (),
+            (None, None) => return true,
+            _ => return false,
+        }
+    }
+}
+
+/// A topic to publish to: non-empty and without wildcards
+pub fn is_valid_name(topic: &str) -> bool {
+    !topic.is_empty() && !topic.contains(['+', '#', '\0'].as_ref())
+}
+
+/// A filter to subscribe with: non-empty, with `+` only as whole levels and `#` only as the whole last level
+pub fn is_valid_filter(filter: &str) -> bool {
+    let mut levels = filter.split('/').peekable();
+
+    if filter.is_empty() || filter.contains('\0') {
+        return false;
+    }
+
+    while let Some(level) = levels.next() {
+        let valid = match level {
+            "+" => true,
+            "#" => levels.peek().is_none(),
+            level => !level.contains(['+', '#'].as_ref()),
+        };
+
+        if !valid {
+            return false;
+        }
+    }
+
+    true
+}
@@ -0,0 +1,7 @@
+pub mod arp;
+pub mod diag;
+pub mod dtls;
+pub mod happy_eyeballs;
+pub mod stats;
+pub mod tcp;
+pub mod udp;
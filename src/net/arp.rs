@@ -0,0 +1,100 @@
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+use core::time::Duration;
+
+#[cfg(feature = "use_serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::io::Io;
+use crate::ipv4::{Ipv4Addr, Mac};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub enum NeighborState {
+    /// Being resolved; the MAC address is not known yet
+    Incomplete,
+    Reachable,
+    /// Not confirmed recently; it is resolved again on its next use
+    Stale,
+    /// Added with `add_static`, never expires
+    Static,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub struct Neighbor {
+    #[cfg_attr(feature = "defmt", defmt(Debug2Format))]
+    pub ip: Ipv4Addr,
+    pub mac: Mac,
+    pub state: NeighborState,
+    /// Since the entry was last confirmed
+    pub age: Duration,
+}
+
+/// The ARP cache of an interface.
+///
+/// Presence detection of LAN devices typically `resolve`s their addresses periodically,
+/// and checks whether their entries are `Reachable` shortly after.
+pub trait Arp: Io {
+    fn neighbors_n<const N: usize>(
+        &self,
+    ) -> Result<(heapless::Vec<Neighbor, N>, usize), Self::Error>;
+
+    #[cfg(feature = "alloc")]
+    fn neighbors(&self) -> Result<alloc::vec::Vec<Neighbor>, Self::Error>;
+
+    /// Looks `ip` up in the cache, without resolving it
+    fn lookup(&self, ip: Ipv4Addr) -> Result<Option<Neighbor>, Self::Error>;
+
+    /// Sends an ARP request for `ip`; the reply, if any, updates the cache
+    fn resolve(&mut self, ip: Ipv4Addr) -> Result<(), Self::Error>;
+
+    fn add_static(&mut self, ip: Ipv4Addr, mac: Mac) -> Result<(), Self::Error>;
+
+    fn remove(&mut self, ip: Ipv4Addr) -> Result<(), Self::Error>;
+
+    /// Sends a gratuitous ARP for the address of the interface, so that the neighbors update
+    /// their caches right away, e.g. when a static address moves over from another interface
+    fn announce(&mut self) -> Result<(), Self::Error>;
+}
+
+impl<A> Arp for &mut A
+where
+    A: Arp,
+{
+    fn neighbors_n<const N: usize>(
+        &self,
+    ) -> Result<(heapless::Vec<Neighbor, N>, usize), Self::Error> {
+        (**self).neighbors_n()
+    }
+
+    #[cfg(feature = "alloc")]
+    fn neighbors(&self) -> Result<alloc::vec::Vec<Neighbor>, Self::Error> {
+        (**self).neighbors()
+    }
+
+    fn lookup(&self, ip: Ipv4Addr) -> Result<Option<Neighbor>, Self::Error> {
+        (**self).lookup(ip)
+    }
+
+    fn resolve(&mut self, ip: Ipv4Addr) -> Result<(), Self::Error> {
+        (*self).resolve(ip)
+    }
+
+    fn add_static(&mut self, ip: Ipv4Addr, mac: Mac) -> Result<(), Self::Error> {
+        (*self).add_static(ip, mac)
+    }
+
+    fn remove(&mut self, ip: Ipv4Addr) -> Result<(), Self::Error> {
+        (*self).remove(ip)
+    }
+
+    fn announce(&mut self) -> Result<(), Self::Error> {
+        (*self).announce()
+    }
+}
@@ -0,0 +1,163 @@
+//! Connectivity diagnostics, e.g. for the status page of a device reporting whether
+//! its MQTT broker and NTP server can be reached
+
+use core::time::Duration;
+
+#[cfg(feature = "use_serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::io::Io;
+use crate::ipv4::SocketAddrV4;
+use crate::sys_time::SystemTime;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub enum Outcome {
+    Connected,
+    /// The host answered with a reset, i.e. nothing listens on the port
+    Refused,
+}
+
+/// Non-blocking TCP connection attempts, so that several ports can be probed at once
+pub trait TcpProbe: Io {
+    type Attempt;
+
+    fn start(&mut self, remote: SocketAddrV4) -> Result<Self::Attempt, Self::Error>;
+
+    /// Returns `None` while the attempt is in progress
+    fn poll(&mut self, attempt: &mut Self::Attempt) -> Result<Option<Outcome>, Self::Error>;
+
+    /// Closes the connection, or aborts the attempt if it is still in progress
+    fn finish(&mut self, attempt: Self::Attempt);
+
+    /// Waits up to `timeout` for any of the attempts in flight to progress, e.g. with `select()`
+    fn wait(&mut self, timeout: Duration) -> Result<(), Self::Error>;
+}
+
+impl<P> TcpProbe for &mut P
+where
+    P: TcpProbe,
+{
+    type Attempt = P::Attempt;
+
+    fn start(&mut self, remote: SocketAddrV4) -> Result<Self::Attempt, Self::Error> {
+        (*self).start(remote)
+    }
+
+    fn poll(&mut self, attempt: &mut Self::Attempt) -> Result<Option<Outcome>, Self::Error> {
+        (*self).poll(attempt)
+    }
+
+    fn finish(&mut self, attempt: Self::Attempt) {
+        (*self).finish(attempt)
+    }
+
+    fn wait(&mut self, timeout: Duration) -> Result<(), Self::Error> {
+        (*self).wait(timeout)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub enum Reachability {
+    /// Connected after this long, as measured with the granularity of `TcpProbe::wait`
+    Reachable(Duration),
+    Refused,
+    TimedOut,
+    /// The connection could not be attempted, e.g. because there is no route to the host
+    Failed,
+}
+
+impl Reachability {
+    pub fn is_reachable(&self) -> bool {
+        matches!(self, Self::Reachable(_))
+    }
+}
+
+/// Tests the TCP reachability of `targets`, with up to `N` attempts in flight,
+/// storing the result for each target into `results` at the same index.
+///
+/// Only the errors of `TcpProbe::wait` are returned; the failures of single
+/// attempts are reported as `Reachability::Failed`.
+pub fn check_reachability<P, T, const N: usize>(
+    probe: &mut P,
+    time: &T,
+    targets: &[SocketAddrV4],
+    timeout: Duration,
+    results: &mut [Reachability],
+) -> Result<(), P::Error>
+where
+    P: TcpProbe,
+    T: SystemTime,
+{
+    let count = targets.len().min(results.len());
+
+    let mut next = 0;
+    let mut in_flight = heapless::Vec::<(usize, P::Attempt, Duration), N>::new();
+
+    loop {
+        while next < count && !in_flight.is_full() {
+            match probe.start(targets[next]) {
+                Ok(attempt) => {
+                    if let Err((_, attempt, _)) = in_flight.push((next, attempt, time.now())) {
+                        probe.finish(attempt);
+                    }
+                }
+                Err(_) => results[next] = Reachability::Failed,
+            }
+
+            next += 1;
+        }
+
+        if in_flight.is_empty() {
+            break;
+        }
+
+        let now = time.now();
+
+        let mut index = 0;
+        while index < in_flight.len() {
+            let (target, attempt, started) = &mut in_flight[index];
+            let (target, elapsed) = (*target, now.saturating_sub(*started));
+
+            let result = match probe.poll(attempt) {
+                Ok(Some(Outcome::Connected)) => Some(Reachability::Reachable(elapsed)),
+                Ok(Some(Outcome::Refused)) => Some(Reachability::Refused),
+                Ok(None) if elapsed >= timeout => Some(Reachability::TimedOut),
+                Ok(None) => None,
+                Err(_) => Some(Reachability::Failed),
+            };
+
+            if let Some(result) = result {
+                results[target] = result;
+
+                let (_, attempt, _) = in_flight.swap_remove(index);
+                probe.finish(attempt);
+            } else {
+                index += 1;
+            }
+        }
+
+        // Wake up no later than the first attempt times out
+        let wait = in_flight
+            .iter()
+            .map(|(_, _, started)| (*started + timeout).saturating_sub(now))
+            .min();
+
+        if let Some(wait) = wait {
+            if let Err(e) = probe.wait(wait) {
+                for (_, attempt, _) in in_flight {
+                    probe.finish(attempt);
+                }
+
+                return Err(e);
+            }
+        }
+    }
+
+    Ok(())
+}
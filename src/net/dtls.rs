@@ -0,0 +1,153 @@
+use core::time::Duration;
+
+#[cfg(feature = "use_serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::io::Io;
+use crate::ipv4::SocketAddrV4;
+use crate::tls;
+
+use super::udp::UdpSocket;
+
+/// The transport parameters specific to DTLS; the security ones are shared with TLS
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub struct Configuration {
+    /// The initial handshake retransmission timeout, doubled on each retransmission
+    pub handshake_timeout_min: Duration,
+    /// The handshake fails once the retransmission timeout exceeds this
+    pub handshake_timeout_max: Duration,
+    /// The maximum size of the datagrams sent, including the record overhead
+    pub mtu: u16,
+    /// Negotiate RFC 9146 connection IDs, so that sessions survive NAT rebinding
+    pub connection_id: bool,
+}
+
+impl Default for Configuration {
+    fn default() -> Self {
+        Self {
+            handshake_timeout_min: Duration::from_secs(1),
+            handshake_timeout_max: Duration::from_secs(60),
+            mtu: 1280,
+            connection_id: false,
+        }
+    }
+}
+
+/// A DTLS association with a single peer
+pub trait DtlsConnection: Io {
+    fn peer_addr(&self) -> Result<SocketAddrV4, Self::Error>;
+
+    /// Runs the handshake to completion; `send` and `receive` do it implicitly when needed.
+    fn handshake(&mut self) -> Result<(), Self::Error>;
+
+    /// Renegotiates the session keys, e.g. after long periods of use
+    fn rehandshake(&mut self) -> Result<(), Self::Error>;
+
+    fn is_established(&self) -> bool;
+
+    /// Sends `buf` as a single record; it must fit into the MTU
+    fn send(&mut self, buf: &[u8]) -> Result<usize, Self::Error>;
+
+    /// Waits up to `timeout` (or forever, if `None`) for a record.
+    /// Returns `None` if no record was received in the meantime.
+    fn receive(
+        &mut self,
+        buf: &mut [u8],
+        timeout: Option<Duration>,
+    ) -> Result<Option<usize>, Self::Error>;
+
+    /// Sends a `close_notify` alert
+    fn close(&mut self) -> Result<(), Self::Error>;
+}
+
+impl<C> DtlsConnection for &mut C
+where
+    C: DtlsConnection,
+{
+    fn peer_addr(&self) -> Result<SocketAddrV4, Self::Error> {
+        (**self).peer_addr()
+    }
+
+    fn handshake(&mut self) -> Result<(), Self::Error> {
+        (*self).handshake()
+    }
+
+    fn rehandshake(&mut self) -> Result<(), Self::Error> {
+        (*self).rehandshake()
+    }
+
+    fn is_established(&self) -> bool {
+        (**self).is_established()
+    }
+
+    fn send(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        (*self).send(buf)
+    }
+
+    fn receive(
+        &mut self,
+        buf: &mut [u8],
+        timeout: Option<Duration>,
+    ) -> Result<Option<usize>, Self::Error> {
+        (*self).receive(buf, timeout)
+    }
+
+    fn close(&mut self) -> Result<(), Self::Error> {
+        (*self).close()
+    }
+}
+
+/// Runs DTLS over UDP sockets, e.g. those of a `UdpStack`
+pub trait Dtls<S>: Io
+where
+    S: UdpSocket,
+{
+    type Connection: DtlsConnection<Error = Self::Error>;
+
+    fn connect(
+        &mut self,
+        socket: S,
+        remote: SocketAddrV4,
+        configuration: &Configuration,
+        tls_configuration: &tls::ClientConfiguration<'_>,
+    ) -> Result<Self::Connection, Self::Error>;
+
+    /// Waits for a client on `socket`, answering with a cookie first (RFC 6347 4.2.1),
+    /// so that spoofed clients cannot make the server spend resources on handshakes
+    fn accept(
+        &mut self,
+        socket: S,
+        configuration: &Configuration,
+        tls_configuration: &tls::ServerConfiguration<'_>,
+    ) -> Result<Self::Connection, Self::Error>;
+}
+
+impl<D, S> Dtls<S> for &mut D
+where
+    D: Dtls<S>,
+    S: UdpSocket,
+{
+    type Connection = D::Connection;
+
+    fn connect(
+        &mut self,
+        socket: S,
+        remote: SocketAddrV4,
+        configuration: &Configuration,
+        tls_configuration: &tls::ClientConfiguration<'_>,
+    ) -> Result<Self::Connection, Self::Error> {
+        (*self).connect(socket, remote, configuration, tls_configuration)
+    }
+
+    fn accept(
+        &mut self,
+        socket: S,
+        configuration: &Configuration,
+        tls_configuration: &tls::ServerConfiguration<'_>,
+    ) -> Result<Self::Connection, Self::Error> {
+        (*self).accept(socket, configuration, tls_configuration)
+    }
+}
@@ -0,0 +1,247 @@
+//! Happy Eyeballs (RFC 8305): connecting to a dual-stack host by racing staggered connection attempts to its
+//! IPv6 and IPv4 addresses, so that a broken IPv6 path only delays the connection by the attempt delay
+//! rather than by a full connection timeout.
+//!
+//! The attempts are made with a `Connector`, which stacks supporting IPv6 implement next to `TcpStack`;
+//! stacks without IPv6 fail the attempts to IPv6 addresses when they are started, so that the IPv4 ones are
+//! tried right away.
+
+use core::fmt::Debug;
+use core::time::Duration;
+
+#[cfg(feature = "use_serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::error::{impl_error, ErrorKind};
+use crate::io::Io;
+use crate::ipv4::SocketAddr;
+use crate::sys_time::{Instant, SystemTime};
+
+/// Non-blocking connection attempts to IPv4 and IPv6 addresses
+pub trait Connector: Io {
+    type Socket;
+    type Attempt;
+
+    fn start(&mut self, remote: SocketAddr) -> Result<Self::Attempt, Self::Error>;
+
+    /// Returns `None` while the attempt is in progress, and an error if it failed
+    fn poll(&mut self, attempt: &mut Self::Attempt) -> Result<Option<Self::Socket>, Self::Error>;
+
+    /// Aborts the attempt, or releases it if it failed
+    fn abort(&mut self, attempt: Self::Attempt);
+
+    /// Waits up to `timeout` for any of the attempts in flight to progress, e.g. with `select()`
+    fn wait(&mut self, timeout: Duration) -> Result<(), Self::Error>;
+}
+
+impl<C> Connector for &mut C
+where
+    C: Connector,
+{
+    type Socket = C::Socket;
+    type Attempt = C::Attempt;
+
+    fn start(&mut self, remote: SocketAddr) -> Result<Self::Attempt, Self::Error> {
+        (*self).start(remote)
+    }
+
+    fn poll(&mut self, attempt: &mut Self::Attempt) -> Result<Option<Self::Socket>, Self::Error> {
+        (*self).poll(attempt)
+    }
+
+    fn abort(&mut self, attempt: Self::Attempt) {
+        (*self).abort(attempt)
+    }
+
+    fn wait(&mut self, timeout: Duration) -> Result<(), Self::Error> {
+        (*self).wait(timeout)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub struct Configuration {
+    /// How long an attempt runs before the next one is started; RFC 8305 recommends 250 ms, and no less
+    /// than 100 ms
+    pub attempt_delay: Duration,
+    /// For all the attempts together
+    pub timeout: Duration,
+    /// Whether the first attempt is made over IPv6, as RFC 8305 recommends
+    pub prefer_ipv6: bool,
+}
+
+impl Default for Configuration {
+    fn default() -> Self {
+        Self {
+            attempt_delay: Duration::from_millis(250),
+            timeout: Duration::from_secs(30),
+            prefer_ipv6: true,
+        }
+    }
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ConnectError<E> {
+    NoCandidates,
+    /// No attempt succeeded within the timeout
+    Timeout,
+    /// All the attempts failed; the error is the one of the last attempt
+    ConnectError(E),
+}
+
+impl_error! {
+    ConnectError<E: Debug> {
+        NoCandidates => "No candidate addresses"; ErrorKind::NotFound,
+        Timeout => "Connection timed out"; ErrorKind::Timeout,
+        ConnectError(e) => "Connect error: {e:?}"; e.error_kind(),
+    }
+}
+
+/// Orders up to `N` candidates as RFC 8305 section 4 does: alternating between the address families,
+/// starting with the preferred one, and otherwise in the order of `candidates`, e.g. as returned by DNS
+pub fn interleave<const N: usize>(
+    candidates: &[SocketAddr],
+    prefer_ipv6: bool,
+) -> heapless::Vec<SocketAddr, N> {
+    let mut preferred = candidates
+        .iter()
+        .filter(|addr| addr.is_ipv6() == prefer_ipv6);
+    let mut other = candidates
+        .iter()
+        .filter(|addr| addr.is_ipv6() != prefer_ipv6);
+
+    let mut ordered = heapless::Vec::new();
+
+    loop {
+        let (first, second) = (preferred.next(), other.next());
+
+        if first.is_none() && second.is_none() {
+            break;
+        }
+
+        for addr in first.into_iter().chain(second) {
+            if ordered.push(*addr).is_err() {
+                return ordered;
+            }
+        }
+    }
+
+    ordered
+}
+
+/// Connects to the first of up to `N` `candidates` which accepts the connection, racing the attempts as
+/// configured by `conf`, and returns the socket and the address it is connected to.
+///
+/// A new attempt is started every `attempt_delay` while the previous ones are in flight, and right away
+/// when an attempt fails; once one succeeds, the others are aborted.
+pub fn connect<C, T, const N: usize>(
+    connector: &mut C,
+    time: &T,
+    candidates: &[SocketAddr],
+    conf: &Configuration,
+) -> Result<(C::Socket, SocketAddr), ConnectError<C::Error>>
+where
+    C: Connector,
+    T: SystemTime,
+{
+    let candidates = interleave::<N>(candidates, conf.prefer_ipv6);
+
+    if candidates.is_empty() {
+        return Err(ConnectError::NoCandidates);
+    }
+
+    let started = Instant::now(time);
+
+    let mut attempts = heapless::Vec::<(C::Attempt, SocketAddr), N>::new();
+    let mut next = candidates.iter();
+    let mut last_started: Option<Instant> = None;
+    let mut last_error = None;
+
+    let result = loop {
+        let now = Instant::now(time);
+
+        let elapsed = now.duration_since(started);
+        if elapsed >= conf.timeout {
+            break Err(ConnectError::Timeout);
+        }
+
+        let due = last_started.map_or(true, |last| {
+            attempts.is_empty() || now.duration_since(last) >= conf.attempt_delay
+        });
+
+        if due {
+            if let Some(addr) = next.next() {
+                match connector.start(*addr) {
+                    Ok(attempt) => {
+                        // Cannot fail, as there are no more attempts than candidates
+                        let _ = attempts.push((attempt, *addr));
+
+                        last_started = Some(now);
+                    }
+                    Err(e) => {
+                        last_error = Some(e);
+                        last_started = None;
+
+                        continue;
+                    }
+                }
+            }
+        }
+
+        let mut index = 0;
+        let mut connected = None;
+
+        while index < attempts.len() {
+            match connector.poll(&mut attempts[index].0) {
+                Ok(Some(socket)) => {
+                    let (_, addr) = attempts.swap_remove(index);
+
+                    connected = Some((socket, addr));
+                    break;
+                }
+                Ok(None) => index += 1,
+                Err(e) => {
+                    let (attempt, _) = attempts.swap_remove(index);
+                    connector.abort(attempt);
+
+                    last_error = Some(e);
+                    last_started = None;
+                }
+            }
+        }
+
+        if let Some(connected) = connected {
+            break Ok(connected);
+        }
+
+        if attempts.is_empty() && next.len() == 0 {
+            break Err(last_error.map_or(ConnectError::Timeout, ConnectError::ConnectError));
+        }
+
+        // An attempt failed: the next one starts right away
+        if last_started.is_none() && next.len() > 0 {
+            continue;
+        }
+
+        let mut wait = conf.timeout - elapsed;
+
+        if next.len() > 0 {
+            let since = now.duration_since(last_started.unwrap_or(now));
+
+            wait = wait.min(conf.attempt_delay.saturating_sub(since));
+        }
+
+        if let Err(e) = connector.wait(wait) {
+            break Err(ConnectError::ConnectError(e));
+        }
+    };
+
+    for (attempt, _) in attempts {
+        connector.abort(attempt);
+    }
+
+    result
+}
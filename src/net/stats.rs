@@ -0,0 +1,219 @@
+//! Traffic counters of the network interfaces, e.g. to track the data used on a metered cellular link.
+//!
+//! `Sampler` turns the cumulative counters reported by the IP stack into the traffic of each sampling
+//! period, which it publishes over MQTT; each `Sample` can also be recorded into a
+//! `utils::telemetry::Telemetry` pipeline instead.
+
+use core::fmt::{Debug, Write as _};
+use core::time::Duration;
+
+use serde::Serialize;
+
+use crate::error::{impl_error, ErrorKind};
+use crate::mqtt::client::{Publish, QoS};
+use crate::storage::SerDe;
+use crate::timer::PeriodicTimer;
+
+/// Cumulative since the interface was brought up, or the differences between two readings
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct InterfaceCounters {
+    pub rx_bytes: u64,
+    pub tx_bytes: u64,
+    pub rx_packets: u64,
+    pub tx_packets: u64,
+    pub rx_errors: u64,
+    pub tx_errors: u64,
+    /// Packets dropped, e.g. for lack of buffers, rather than because they were malformed
+    pub rx_dropped: u64,
+    pub tx_dropped: u64,
+}
+
+impl InterfaceCounters {
+    /// The counts since `previous`; a counter lower than in `previous` was reset, e.g. because the
+    /// interface was brought down and up again, so it counts from zero
+    pub fn since(&self, previous: &Self) -> Self {
+        let since = |current: u64, previous: u64| {
+            if current >= previous {
+                current - previous
+            } else {
+                current
+            }
+        };
+
+        Self {
+            rx_bytes: since(self.rx_bytes, previous.rx_bytes),
+            tx_bytes: since(self.tx_bytes, previous.tx_bytes),
+            rx_packets: since(self.rx_packets, previous.rx_packets),
+            tx_packets: since(self.tx_packets, previous.tx_packets),
+            rx_errors: since(self.rx_errors, previous.rx_errors),
+            tx_errors: since(self.tx_errors, previous.tx_errors),
+            rx_dropped: since(self.rx_dropped, previous.rx_dropped),
+            tx_dropped: since(self.tx_dropped, previous.tx_dropped),
+        }
+    }
+}
+
+/// Implemented by the IP stack
+pub trait InterfaceStats {
+    type Error: Debug;
+
+    /// Returns `None` if there is no interface named `interface`, e.g. `wlan0` or `ppp0`, or it is down.
+    ///
+    /// Backends whose counters are 32 bits should extend them to 64 bits, so that they do not wrap.
+    fn counters(&self, interface: &str) -> Result<Option<InterfaceCounters>, Self::Error>;
+}
+
+impl<S> InterfaceStats for &S
+where
+    S: InterfaceStats,
+{
+    type Error = S::Error;
+
+    fn counters(&self, interface: &str) -> Result<Option<InterfaceCounters>, Self::Error> {
+        (**self).counters(interface)
+    }
+}
+
+impl<S> InterfaceStats for &mut S
+where
+    S: InterfaceStats,
+{
+    type Error = S::Error;
+
+    fn counters(&self, interface: &str) -> Result<Option<InterfaceCounters>, Self::Error> {
+        (**self).counters(interface)
+    }
+}
+
+/// The traffic of an interface during a sampling period
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Sample<'a> {
+    pub interface: &'a str,
+    /// The length of the period, in seconds
+    pub period: u64,
+    pub counters: InterfaceCounters,
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum StatsError<S, P, D> {
+    StatsError(S),
+    PublishError(P),
+    SerdeError(D),
+    /// `{prefix}/{interface}` is longer than 64 bytes
+    TopicTooLong,
+}
+
+impl_error! {
+    StatsError<S: Debug, P: Debug, D: Debug> {
+        StatsError(e) => "Stats error: {e:?}"; e.error_kind(),
+        PublishError(e) => "Publish error: {e:?}"; e.error_kind(),
+        SerdeError(e) => "SerDe error: {e:?}"; e.error_kind(),
+        TopicTooLong => "Topic too long"; ErrorKind::InvalidInput,
+    }
+}
+
+type PublishResult<S, P, D> = Result<usize, StatsError<S, P, D>>;
+
+/// Samples the counters of `N` interfaces at a fixed interval.
+///
+/// Sampling is driven by the application, typically from the callback of a timer armed with `arm`. The
+/// first reading of an interface, and the first after it came back up, only sets the baseline of the next
+/// period.
+pub struct Sampler<'a, S, const N: usize = 2> {
+    stats: S,
+    interfaces: [&'a str; N],
+    previous: [Option<InterfaceCounters>; N],
+    interval: Duration,
+}
+
+impl<'a, S, const N: usize> Sampler<'a, S, N>
+where
+    S: InterfaceStats,
+{
+    pub const fn new(stats: S, interfaces: [&'a str; N], interval: Duration) -> Self {
+        Self {
+            stats,
+            interfaces,
+            previous: [None; N],
+            interval,
+        }
+    }
+
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// Schedules `timer` to fire at the sampling interval
+    pub fn arm<O>(&self, timer: &mut O) -> Result<(), O::Error>
+    where
+        O: PeriodicTimer,
+    {
+        timer.every(self.interval)
+    }
+
+    /// Reads the counters, and returns the traffic of each interface since the previous call
+    pub fn sample(&mut self) -> Result<heapless::Vec<Sample<'a>, N>, S::Error> {
+        let mut samples = heapless::Vec::new();
+
+        for (interface, previous) in self.interfaces.iter().zip(self.previous.iter_mut()) {
+            let current = self.stats.counters(interface)?;
+
+            if let (Some(current), Some(previous)) = (&current, &previous) {
+                samples
+                    .push(Sample {
+                        interface,
+                        period: self.interval.as_secs(),
+                        counters: current.since(previous),
+                    })
+                    .unwrap();
+            }
+
+            *previous = current;
+        }
+
+        Ok(samples)
+    }
+
+    /// Samples the counters, and publishes the sample of each interface on `{prefix}/{interface}`,
+    /// encoded with `serde`.
+    ///
+    /// Returns the number of samples published.
+    pub fn publish<P, D>(
+        &mut self,
+        publisher: &mut P,
+        serde: &D,
+        prefix: &str,
+    ) -> PublishResult<S::Error, P::Error, D::Error>
+    where
+        P: Publish,
+        D: SerDe,
+    {
+        let samples = self.sample().map_err(StatsError::StatsError)?;
+
+        for sample in &samples {
+            let mut topic = heapless::String::<64>::new();
+            let mut buf = [0_u8; 256];
+
+            let payload = serde
+                .serialize(&mut buf, sample)
+                .map_err(StatsError::SerdeError)?;
+
+            write!(&mut topic, "{prefix}/{}", sample.interface)
+                .map_err(|_| StatsError::TopicTooLong)?;
+
+            publisher
+                .publish(&topic, QoS::AtMostOnce, false, payload)
+                .map_err(StatsError::PublishError)?;
+        }
+
+        Ok(samples.len())
+    }
+
+    pub fn release(self) -> S {
+        self.stats
+    }
+}
@@ -0,0 +1,172 @@
+use core::time::Duration;
+
+#[cfg(feature = "use_serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::io::{Io, Read, Write};
+use crate::ipv4::SocketAddrV4;
+
+/// TCP keepalive probes, sent once the connection has been idle for `idle`, then every `interval` until
+/// the peer answers; the connection is closed after `count` unanswered probes
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub struct Keepalive {
+    pub idle: Duration,
+    pub interval: Duration,
+    pub count: u8,
+}
+
+impl Keepalive {
+    /// Probes often enough to keep the mapping of a NAT which drops idle connections after `timeout`,
+    /// typically a few minutes on cellular networks
+    pub fn for_nat_timeout(timeout: Duration) -> Self {
+        let second = Duration::from_secs(1);
+
+        Self {
+            idle: (timeout / 2).max(second),
+            interval: (timeout / 10).max(second),
+            count: 4,
+        }
+    }
+}
+
+impl Default for Keepalive {
+    fn default() -> Self {
+        Self {
+            idle: Duration::from_secs(60),
+            interval: Duration::from_secs(10),
+            count: 5,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub struct TcpOptions {
+    /// `None` disables keepalive
+    pub keepalive: Option<Keepalive>,
+    /// Disables Nagle's algorithm, so that small writes are sent immediately
+    pub nodelay: bool,
+    /// How long closing waits for the unsent data to be sent; `None` closes in the background, and zero
+    /// resets the connection
+    pub linger: Option<Duration>,
+    /// In bytes; `None` keeps the default of the stack
+    pub rx_buffer_size: Option<usize>,
+    /// In bytes; `None` keeps the default of the stack
+    pub tx_buffer_size: Option<usize>,
+}
+
+pub trait TcpSocket: Read + Write {
+    fn local_addr(&self) -> Result<SocketAddrV4, Self::Error>;
+
+    fn peer_addr(&self) -> Result<SocketAddrV4, Self::Error>;
+
+    /// Waits up to `timeout` (or forever, if `None`) for the socket to become readable,
+    /// i.e. for `read` to return data, or 0 if the peer closed the connection, without blocking.
+    fn wait_readable(&mut self, timeout: Option<Duration>) -> Result<bool, Self::Error>;
+
+    /// Closes the sending side; the peer reads 0 once it received what was sent before
+    fn shutdown(&mut self) -> Result<(), Self::Error>;
+}
+
+impl<S> TcpSocket for &mut S
+where
+    S: TcpSocket,
+{
+    fn local_addr(&self) -> Result<SocketAddrV4, Self::Error> {
+        (**self).local_addr()
+    }
+
+    fn peer_addr(&self) -> Result<SocketAddrV4, Self::Error> {
+        (**self).peer_addr()
+    }
+
+    fn wait_readable(&mut self, timeout: Option<Duration>) -> Result<bool, Self::Error> {
+        (*self).wait_readable(timeout)
+    }
+
+    fn shutdown(&mut self) -> Result<(), Self::Error> {
+        (*self).shutdown()
+    }
+}
+
+/// Implemented by the sockets of stacks which allow tuning TCP
+pub trait TcpSocketOptions: TcpSocket {
+    fn options(&self) -> Result<TcpOptions, Self::Error>;
+
+    fn set_options(&mut self, options: &TcpOptions) -> Result<(), Self::Error>;
+}
+
+impl<S> TcpSocketOptions for &mut S
+where
+    S: TcpSocketOptions,
+{
+    fn options(&self) -> Result<TcpOptions, Self::Error> {
+        (**self).options()
+    }
+
+    fn set_options(&mut self, options: &TcpOptions) -> Result<(), Self::Error> {
+        (*self).set_options(options)
+    }
+}
+
+pub trait TcpListener: Io {
+    type Socket: TcpSocket<Error = Self::Error>;
+
+    fn local_addr(&self) -> Result<SocketAddrV4, Self::Error>;
+
+    /// Waits up to `timeout` (or forever, if `None`) for a connection.
+    /// Returns `None` if no connection arrived in the meantime.
+    fn accept(
+        &mut self,
+        timeout: Option<Duration>,
+    ) -> Result<Option<(Self::Socket, SocketAddrV4)>, Self::Error>;
+}
+
+impl<L> TcpListener for &mut L
+where
+    L: TcpListener,
+{
+    type Socket = L::Socket;
+
+    fn local_addr(&self) -> Result<SocketAddrV4, Self::Error> {
+        (**self).local_addr()
+    }
+
+    fn accept(
+        &mut self,
+        timeout: Option<Duration>,
+    ) -> Result<Option<(Self::Socket, SocketAddrV4)>, Self::Error> {
+        (*self).accept(timeout)
+    }
+}
+
+pub trait TcpStack: Io {
+    type Socket: TcpSocket<Error = Self::Error>;
+    type Listener: TcpListener<Socket = Self::Socket, Error = Self::Error>;
+
+    fn connect(&mut self, remote: SocketAddrV4) -> Result<Self::Socket, Self::Error>;
+
+    /// Listens on `local`; port 0 requests an ephemeral port
+    fn listen(&mut self, local: SocketAddrV4) -> Result<Self::Listener, Self::Error>;
+}
+
+impl<T> TcpStack for &mut T
+where
+    T: TcpStack,
+{
+    type Socket = T::Socket;
+    type Listener = T::Listener;
+
+    fn connect(&mut self, remote: SocketAddrV4) -> Result<Self::Socket, Self::Error> {
+        (*self).connect(remote)
+    }
+
+    fn listen(&mut self, local: SocketAddrV4) -> Result<Self::Listener, Self::Error> {
+        (*self).listen(local)
+    }
+}
@@ -0,0 +1,75 @@
+use core::time::Duration;
+
+use crate::io::Io;
+use crate::ipv4::{Ipv4Addr, SocketAddrV4};
+
+pub trait UdpSocket: Io {
+    fn local_addr(&self) -> Result<SocketAddrV4, Self::Error>;
+
+    fn send_to(&mut self, buf: &[u8], addr: SocketAddrV4) -> Result<usize, Self::Error>;
+
+    /// Waits up to `timeout` (or forever, if `None`) for a datagram.
+    /// Returns `None` if no datagram was received in the meantime.
+    fn receive_from(
+        &mut self,
+        buf: &mut [u8],
+        timeout: Option<Duration>,
+    ) -> Result<Option<(usize, SocketAddrV4)>, Self::Error>;
+
+    fn set_broadcast(&mut self, enabled: bool) -> Result<(), Self::Error>;
+
+    fn join_multicast(&mut self, group: Ipv4Addr) -> Result<(), Self::Error>;
+
+    fn leave_multicast(&mut self, group: Ipv4Addr) -> Result<(), Self::Error>;
+}
+
+impl<S> UdpSocket for &mut S
+where
+    S: UdpSocket,
+{
+    fn local_addr(&self) -> Result<SocketAddrV4, Self::Error> {
+        (**self).local_addr()
+    }
+
+    fn send_to(&mut self, buf: &[u8], addr: SocketAddrV4) -> Result<usize, Self::Error> {
+        (*self).send_to(buf, addr)
+    }
+
+    fn receive_from(
+        &mut self,
+        buf: &mut [u8],
+        timeout: Option<Duration>,
+    ) -> Result<Option<(usize, SocketAddrV4)>, Self::Error> {
+        (*self).receive_from(buf, timeout)
+    }
+
+    fn set_broadcast(&mut self, enabled: bool) -> Result<(), Self::Error> {
+        (*self).set_broadcast(enabled)
+    }
+
+    fn join_multicast(&mut self, group: Ipv4Addr) -> Result<(), Self::Error> {
+        (*self).join_multicast(group)
+    }
+
+    fn leave_multicast(&mut self, group: Ipv4Addr) -> Result<(), Self::Error> {
+        (*self).leave_multicast(group)
+    }
+}
+
+pub trait UdpStack: Io {
+    type Socket: UdpSocket<Error = Self::Error>;
+
+    /// Binds a new socket to `local`. Port 0 requests an ephemeral port.
+    fn bind(&mut self, local: SocketAddrV4) -> Result<Self::Socket, Self::Error>;
+}
+
+impl<U> UdpStack for &mut U
+where
+    U: UdpStack,
+{
+    type Socket = U::Socket;
+
+    fn bind(&mut self, local: SocketAddrV4) -> Result<Self::Socket, Self::Error> {
+        (*self).bind(local)
+    }
+}
@@ -0,0 +1,176 @@
+use core::convert::TryFrom;
+use core::fmt::{self, Debug, Display};
+use core::str::FromStr;
+use core::time::Duration;
+
+#[cfg(feature = "use_serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::ipv4;
+
+/// NetBIOS over TCP/IP name service (RFC 1001/1002)
+pub const PORT: u16 = 137;
+
+/// The last byte of a 16-byte NetBIOS name, telling which service it is registered for
+pub mod suffix {
+    pub const WORKSTATION: u8 = 0x00;
+    pub const FILE_SERVER: u8 = 0x20;
+}
+
+/// A NetBIOS name as sent on the wire: up to 15 characters, in uppercase.
+///
+/// Windows resolves e.g. `http://devicename/` with it when the host name is not in DNS
+/// and the system does not support mDNS.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(
+    feature = "use_serde",
+    derive(Serialize, Deserialize),
+    serde(try_from = "heapless::String<15>", into = "heapless::String<15>")
+)]
+pub struct Name(heapless::String<15>);
+
+impl Name {
+    pub const MAX_LEN: usize = 15;
+
+    /// Host names longer than `Name::MAX_LEN` are truncated, as Windows does for its own name
+    pub fn from_hostname(hostname: &ipv4::Hostname) -> Self {
+        Self(uppercase(hostname.as_str()))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// The RFC 1001 first-level encoding of the name padded with spaces and followed by `suffix`,
+    /// i.e. each nibble as a letter from `A` to `P`
+    pub fn encode(&self, suffix: u8) -> [u8; 32] {
+        let mut name = [b' '; 16];
+        name[..self.0.len()].copy_from_slice(self.0.as_bytes());
+        name[15] = suffix;
+
+        let mut encoded = [0_u8; 32];
+
+        for (index, byte) in name.iter().enumerate() {
+            encoded[index * 2] = b'A' + (byte >> 4);
+            encoded[index * 2 + 1] = b'A' + (byte & 0x0f);
+        }
+
+        encoded
+    }
+}
+
+impl FromStr for Name {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() {
+            Err("NetBIOS name is empty")
+        } else if s.len() > Self::MAX_LEN {
+            Err("NetBIOS name too long")
+        } else if !s
+            .bytes()
+            .all(|c| c.is_ascii_graphic() && !b"\\/:*?\"<>|.".contains(&c))
+        {
+            Err("NetBIOS name contains invalid characters")
+        } else {
+            Ok(Self(uppercase(s)))
+        }
+    }
+}
+
+impl TryFrom<heapless::String<15>> for Name {
+    type Error = &'static str;
+
+    fn try_from(s: heapless::String<15>) -> Result<Self, Self::Error> {
+        s.as_str().parse()
+    }
+}
+
+impl From<Name> for heapless::String<15> {
+    fn from(name: Name) -> Self {
+        name.0
+    }
+}
+
+impl AsRef<str> for Name {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl Display for Name {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+fn uppercase(s: &str) -> heapless::String<15> {
+    s.chars()
+        .take(Name::MAX_LEN)
+        .map(|c| c.to_ascii_uppercase())
+        .collect()
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub struct Configuration {
+    /// Answered for both the `WORKSTATION` and `FILE_SERVER` suffixes
+    pub name: Name,
+    /// Announced as the group the device belongs to, so that it shows up in the network browser
+    pub workgroup: Option<Name>,
+    /// How long peers should cache the answers for
+    pub ttl: Duration,
+}
+
+impl Configuration {
+    pub fn new(hostname: &ipv4::Hostname) -> Self {
+        Self {
+            name: Name::from_hostname(hostname),
+            workgroup: None,
+            ttl: Duration::from_secs(5 * 60),
+        }
+    }
+}
+
+/// A responder answering NetBIOS name queries broadcast on the LAN
+pub trait NetbiosNs {
+    type Error: Debug;
+
+    /// Starts answering for the configured names; restarts the responder when already started
+    fn start(&mut self, configuration: &Configuration) -> Result<(), Self::Error>;
+
+    /// Sends unsolicited name registrations, e.g. after the IP address changed
+    fn announce(&mut self) -> Result<(), Self::Error>;
+
+    /// Releases the names, so that peers drop them from their caches
+    fn stop(&mut self) -> Result<(), Self::Error>;
+
+    fn is_started(&self) -> bool;
+}
+
+impl<N> NetbiosNs for &mut N
+where
+    N: NetbiosNs,
+{
+    type Error = N::Error;
+
+    fn start(&mut self, configuration: &Configuration) -> Result<(), Self::Error> {
+        (*self).start(configuration)
+    }
+
+    fn announce(&mut self) -> Result<(), Self::Error> {
+        (*self).announce()
+    }
+
+    fn stop(&mut self) -> Result<(), Self::Error> {
+        (*self).stop()
+    }
+
+    fn is_started(&self) -> bool {
+        (**self).is_started()
+    }
+}
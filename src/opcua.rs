@@ -0,0 +1,1012 @@
+//! A minimal OPC UA client (the Nano Embedded Device profile, over UA TCP): reading and
+//! writing node values, and subscribing to their changes.
+//!
+//! Only the `None` security policy is supported, as the `Basic256Sha256` and later ones
+//! need RSA; gateways typically use it on a segregated OT network, or tunnel it over TLS.
+//! Values are limited to scalars, and messages to a single chunk.
+
+use core::fmt;
+use core::time::Duration;
+
+use crate::error::{self, impl_error};
+use crate::io::{Error, ErrorKind, Read, ReadExactError, Write};
+
+use self::binary::{Reader, Writer};
+
+mod binary;
+
+/// The minimum buffer size the specification allows
+pub const MIN_BUFFER_SIZE: usize = 8192;
+
+const SECURITY_POLICY_NONE: &str = "http://opcfoundation.org/UA/SecurityPolicy#None";
+
+const ATTRIBUTE_VALUE: u32 = 13;
+const TIMESTAMPS_BOTH: u32 = 2;
+const MONITORING_MODE_REPORTING: u32 = 2;
+
+/// The IDs of the default binary encodings of the services and structures used
+mod encoding {
+    pub const SERVICE_FAULT: u32 = 397;
+    pub const OPEN_SECURE_CHANNEL_REQUEST: u32 = 446;
+    pub const OPEN_SECURE_CHANNEL_RESPONSE: u32 = 449;
+    pub const CLOSE_SECURE_CHANNEL_REQUEST: u32 = 452;
+    pub const CREATE_SESSION_REQUEST: u32 = 461;
+    pub const CREATE_SESSION_RESPONSE: u32 = 464;
+    pub const ACTIVATE_SESSION_REQUEST: u32 = 467;
+    pub const ACTIVATE_SESSION_RESPONSE: u32 = 470;
+    pub const CLOSE_SESSION_REQUEST: u32 = 473;
+    pub const CLOSE_SESSION_RESPONSE: u32 = 476;
+    pub const READ_REQUEST: u32 = 631;
+    pub const READ_RESPONSE: u32 = 634;
+    pub const WRITE_REQUEST: u32 = 673;
+    pub const WRITE_RESPONSE: u32 = 676;
+    pub const CREATE_MONITORED_ITEMS_REQUEST: u32 = 751;
+    pub const CREATE_MONITORED_ITEMS_RESPONSE: u32 = 754;
+    pub const CREATE_SUBSCRIPTION_REQUEST: u32 = 787;
+    pub const CREATE_SUBSCRIPTION_RESPONSE: u32 = 790;
+    pub const DATA_CHANGE_NOTIFICATION: u32 = 811;
+    pub const PUBLISH_REQUEST: u32 = 826;
+    pub const PUBLISH_RESPONSE: u32 = 829;
+    pub const ANONYMOUS_IDENTITY_TOKEN: u32 = 321;
+    pub const USER_NAME_IDENTITY_TOKEN: u32 = 324;
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum NodeId<'a> {
+    Numeric { namespace: u16, id: u32 },
+    String { namespace: u16, id: &'a str },
+    Guid { namespace: u16, id: [u8; 16] },
+    Opaque { namespace: u16, id: &'a [u8] },
+}
+
+impl<'a> NodeId<'a> {
+    pub const fn numeric(namespace: u16, id: u32) -> Self {
+        Self::Numeric { namespace, id }
+    }
+
+    pub const fn string(namespace: u16, id: &'a str) -> Self {
+        Self::String { namespace, id }
+    }
+
+    /// Parses the numeric and string forms of the standard notation, e.g. `ns=2;i=1001` or `ns=3;s=Line1.Speed`
+    pub fn parse(s: &'a str) -> Option<Self> {
+        let (namespace, id) = match s.strip_prefix("ns=") {
+            Some(rest) => {
+                let (namespace, id) = rest.split_once(';')?;
+
+                (namespace.parse().ok()?, id)
+            }
+            None => (0, s),
+        };
+
+        if let Some(id) = id.strip_prefix("i=") {
+            Some(Self::numeric(namespace, id.parse().ok()?))
+        } else {
+            id.strip_prefix("s=").map(|id| Self::string(namespace, id))
+        }
+    }
+}
+
+impl<'a> fmt::Display for NodeId<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let namespace = match self {
+            Self::Numeric { namespace, .. }
+            | Self::String { namespace, .. }
+            | Self::Guid { namespace, .. }
+            | Self::Opaque { namespace, .. } => *namespace,
+        };
+
+        if namespace != 0 {
+            write!(f, "ns={namespace};")?;
+        }
+
+        match self {
+            Self::Numeric { id, .. } => write!(f, "i={id}"),
+            Self::String { id, .. } => write!(f, "s={id}"),
+            Self::Guid { id, .. } => {
+                // The first three fields are little endian
+                write!(
+                    f,
+                    "g={:08x}-{:04x}-{:04x}-",
+                    u32::from_le_bytes([id[0], id[1], id[2], id[3]]),
+                    u16::from_le_bytes([id[4], id[5]]),
+                    u16::from_le_bytes([id[6], id[7]])
+                )?;
+
+                for (index, byte) in id[8..].iter().enumerate() {
+                    if index == 2 {
+                        write!(f, "-")?;
+                    }
+
+                    write!(f, "{byte:02x}")?;
+                }
+
+                Ok(())
+            }
+            Self::Opaque { id, .. } => {
+                write!(f, "b=")?;
+
+                for byte in id.iter() {
+                    write!(f, "{byte:02x}")?;
+                }
+
+                Ok(())
+            }
+        }
+    }
+}
+
+/// A scalar value
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Variant<'a> {
+    Null,
+    Boolean(bool),
+    SByte(i8),
+    Byte(u8),
+    Int16(i16),
+    UInt16(u16),
+    Int32(i32),
+    UInt32(u32),
+    Int64(i64),
+    UInt64(u64),
+    Float(f32),
+    Double(f64),
+    String(&'a str),
+    /// 100 nanosecond intervals since 1601-01-01 UTC
+    DateTime(i64),
+    ByteString(&'a [u8]),
+}
+
+impl<'a> Variant<'a> {
+    const fn type_id(&self) -> u8 {
+        match self {
+            Self::Null => 0,
+            Self::Boolean(_) => 1,
+            Self::SByte(_) => 2,
+            Self::Byte(_) => 3,
+            Self::Int16(_) => 4,
+            Self::UInt16(_) => 5,
+            Self::Int32(_) => 6,
+            Self::UInt32(_) => 7,
+            Self::Int64(_) => 8,
+            Self::UInt64(_) => 9,
+            Self::Float(_) => 10,
+            Self::Double(_) => 11,
+            Self::String(_) => 12,
+            Self::DateTime(_) => 13,
+            Self::ByteString(_) => 15,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct StatusCode(pub u32);
+
+impl StatusCode {
+    pub const GOOD: Self = Self(0);
+    pub const BAD_UNEXPECTED_ERROR: Self = Self(0x8001_0000);
+    pub const BAD_TIMEOUT: Self = Self(0x800a_0000);
+    pub const BAD_SECURE_CHANNEL_ID_INVALID: Self = Self(0x8022_0000);
+    pub const BAD_SESSION_ID_INVALID: Self = Self(0x8025_0000);
+    pub const BAD_NODE_ID_UNKNOWN: Self = Self(0x8034_0000);
+    pub const BAD_TYPE_MISMATCH: Self = Self(0x8074_0000);
+    pub const BAD_USER_ACCESS_DENIED: Self = Self(0x801f_0000);
+
+    pub const fn is_good(&self) -> bool {
+        self.0 & 0xc000_0000 == 0
+    }
+
+    pub const fn is_uncertain(&self) -> bool {
+        self.0 & 0xc000_0000 == 0x4000_0000
+    }
+
+    pub const fn is_bad(&self) -> bool {
+        self.0 & 0x8000_0000 != 0
+    }
+}
+
+impl fmt::Display for StatusCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "0x{:08X}", self.0)
+    }
+}
+
+/// The value of a node attribute, along with its quality and timestamps
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct DataValue<'a> {
+    pub value: Option<Variant<'a>>,
+    pub status: StatusCode,
+    /// As with `Variant::DateTime`
+    pub source_timestamp: Option<i64>,
+    pub server_timestamp: Option<i64>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Hash))]
+pub enum Identity<'a> {
+    /// The policy IDs are those of the user token policies of the server endpoint
+    Anonymous { policy_id: &'a str },
+    /// The password is sent in the clear, as is everything else with the `None` security policy
+    UserName {
+        policy_id: &'a str,
+        user: &'a str,
+        password: &'a str,
+    },
+}
+
+impl<'a> fmt::Debug for Identity<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Anonymous { policy_id } => f
+                .debug_struct("Anonymous")
+                .field("policy_id", policy_id)
+                .finish(),
+            Self::UserName {
+                policy_id, user, ..
+            } => f
+                .debug_struct("UserName")
+                .field("policy_id", policy_id)
+                .field("user", user)
+                .finish(),
+        }
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl<'a> defmt::Format for Identity<'a> {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        match self {
+            Self::Anonymous { policy_id } => defmt::write!(f, "Anonymous {{ {} }}", policy_id),
+            Self::UserName {
+                policy_id, user, ..
+            } => defmt::write!(f, "UserName {{ {}, {} }}", policy_id, user),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Configuration<'a> {
+    /// E.g. `opc.tcp://192.168.1.10:4840`
+    pub endpoint_url: &'a str,
+    pub application_uri: &'a str,
+    pub application_name: &'a str,
+    pub session_name: &'a str,
+    pub identity: Identity<'a>,
+    /// How long the server keeps the secure channel token; renew it before it expires
+    pub channel_lifetime: Duration,
+    /// How long the server keeps the session without requests
+    pub session_timeout: Duration,
+    /// Passed to the server, which gives up on requests taking longer
+    pub request_timeout: Duration,
+}
+
+impl<'a> Configuration<'a> {
+    pub const fn new(endpoint_url: &'a str, application_uri: &'a str) -> Self {
+        Self {
+            endpoint_url,
+            application_uri,
+            application_name: "embedded-svc",
+            session_name: "embedded-svc",
+            identity: Identity::Anonymous {
+                policy_id: "anonymous",
+            },
+            channel_lifetime: Duration::from_secs(60 * 60),
+            session_timeout: Duration::from_secs(60),
+            request_timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+/// A value change of a monitored item, as delivered by `Client::publish`
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct DataChange<'a> {
+    pub subscription_id: u32,
+    /// As passed to `Client::monitor`
+    pub client_handle: u32,
+    pub value: DataValue<'a>,
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ClientError<E> {
+    IoError(E),
+    /// The server failed the request, or closed the connection with an `ERR` message
+    StatusError(StatusCode),
+    /// A message does not fit into the buffer, or into the buffer of the server
+    EncodeError(&'static str),
+    DecodeError(&'static str),
+}
+
+impl_error! {
+    ClientError<E: Display> {
+        IoError(e) => "IO error: {e}"; e.error_kind(),
+        StatusError(e) => "Status error: {e}"; error::ErrorKind::Other,
+        EncodeError(e) => "Encode error: {e}"; error::ErrorKind::InvalidInput,
+        DecodeError(e) => "Decode error: {e}"; error::ErrorKind::InvalidInput,
+    }
+}
+
+impl<E> Error for ClientError<E>
+where
+    E: Error,
+{
+    fn kind(&self) -> ErrorKind {
+        match self {
+            Self::IoError(e) => e.kind(),
+            _ => ErrorKind::Other,
+        }
+    }
+}
+
+/// An OPC UA session with a server, over a single secure channel.
+///
+/// `buf` holds the messages sent and received, so it bounds their size;
+/// it should be at least `MIN_BUFFER_SIZE` large.
+pub struct Client<'b, C> {
+    connection: C,
+    buf: &'b mut [u8],
+    /// The receive buffer size of the server
+    send_size: usize,
+    channel_id: u32,
+    token_id: u32,
+    channel_lifetime: Duration,
+    sequence_number: u32,
+    request_id: u32,
+    authentication_token: heapless::Vec<u8, 64>,
+    request_timeout: Duration,
+    /// The notifications to acknowledge with the next `publish`
+    acknowledgements: heapless::Vec<(u32, u32), 8>,
+}
+
+impl<'b, C> Client<'b, C>
+where
+    C: Read + Write,
+{
+    /// Opens a secure channel over `connection`, e.g. a TCP connection to port 4840,
+    /// then creates and activates a session
+    pub fn connect(
+        connection: C,
+        buf: &'b mut [u8],
+        configuration: &Configuration<'_>,
+    ) -> Result<Self, ClientError<C::Error>> {
+        let mut client = Self {
+            connection,
+            buf,
+            send_size: 0,
+            channel_id: 0,
+            token_id: 0,
+            channel_lifetime: configuration.channel_lifetime,
+            sequence_number: 0,
+            request_id: 0,
+            authentication_token: heapless::Vec::new(),
+            request_timeout: configuration.request_timeout,
+            acknowledgements: heapless::Vec::new(),
+        };
+
+        client.hello(configuration.endpoint_url)?;
+        client.open_secure_channel(false)?;
+        client.create_session(configuration)?;
+        client.activate_session(&configuration.identity)?;
+
+        Ok(client)
+    }
+
+    /// The lifetime of the secure channel token, as revised by the server
+    pub fn channel_lifetime(&self) -> Duration {
+        self.channel_lifetime
+    }
+
+    /// Renews the secure channel token; to be called once 75% of its lifetime have elapsed
+    pub fn renew_secure_channel(&mut self) -> Result<(), ClientError<C::Error>> {
+        self.open_secure_channel(true)
+    }
+
+    /// Reads the values of `nodes`, passing each one to `f` along with its index
+    pub fn read<F>(&mut self, nodes: &[NodeId<'_>], mut f: F) -> Result<(), ClientError<C::Error>>
+    where
+        F: FnMut(usize, DataValue<'_>),
+    {
+        let mut reader =
+            self.request(encoding::READ_REQUEST, encoding::READ_RESPONSE, |writer| {
+                // Max age, timestamps to return
+                writer.f64(0.0)?;
+                writer.u32(TIMESTAMPS_BOTH)?;
+
+                writer.array(nodes.len())?;
+                for node in nodes {
+                    write_read_value_id(writer, node)?;
+                }
+
+                Ok(())
+            })?;
+
+        for index in 0..decode(reader.array_len())? {
+            f(index, decode(reader.data_value())?);
+        }
+
+        Ok(())
+    }
+
+    pub fn read_value(
+        &mut self,
+        node: &NodeId<'_>,
+    ) -> Result<DataValue<'_>, ClientError<C::Error>> {
+        let mut reader =
+            self.request(encoding::READ_REQUEST, encoding::READ_RESPONSE, |writer| {
+                writer.f64(0.0)?;
+                writer.u32(TIMESTAMPS_BOTH)?;
+
+                writer.array(1)?;
+                write_read_value_id(writer, node)
+            })?;
+
+        if decode(reader.array_len())? != 1 {
+            return Err(ClientError::DecodeError("Unexpected number of results"));
+        }
+
+        decode(reader.data_value())
+    }
+
+    /// Writes the value of `node`, returning the status of the operation
+    pub fn write_value(
+        &mut self,
+        node: &NodeId<'_>,
+        value: &Variant<'_>,
+    ) -> Result<StatusCode, ClientError<C::Error>> {
+        let mut reader = self.request(
+            encoding::WRITE_REQUEST,
+            encoding::WRITE_RESPONSE,
+            |writer| {
+                writer.array(1)?;
+
+                writer.node_id(node)?;
+                writer.u32(ATTRIBUTE_VALUE)?;
+                // Index range
+                writer.string(None)?;
+                // A data value with just the value
+                writer.u8(0x01)?;
+                writer.variant(value)
+            },
+        )?;
+
+        if decode(reader.array_len())? != 1 {
+            return Err(ClientError::DecodeError("Unexpected number of results"));
+        }
+
+        Ok(StatusCode(decode(reader.u32())?))
+    }
+
+    /// Creates a subscription, with a keep-alive count of 10 and a lifetime count of 60
+    /// publishing intervals, returning its ID
+    pub fn create_subscription(
+        &mut self,
+        publishing_interval: Duration,
+    ) -> Result<u32, ClientError<C::Error>> {
+        let mut reader = self.request(
+            encoding::CREATE_SUBSCRIPTION_REQUEST,
+            encoding::CREATE_SUBSCRIPTION_RESPONSE,
+            |writer| {
+                writer.f64(millis(publishing_interval))?;
+                // Lifetime count, keep-alive count, max notifications per publish
+                writer.u32(60)?;
+                writer.u32(10)?;
+                writer.u32(0)?;
+                // Publishing enabled, priority
+                writer.bool(true)?;
+                writer.u8(0)
+            },
+        )?;
+
+        decode(reader.u32())
+    }
+
+    /// Monitors the value of `node` in `subscription_id`; its changes are reported by `publish`
+    /// along with `client_handle`. Returns the ID of the monitored item.
+    pub fn monitor(
+        &mut self,
+        subscription_id: u32,
+        node: &NodeId<'_>,
+        client_handle: u32,
+        sampling_interval: Duration,
+    ) -> Result<u32, ClientError<C::Error>> {
+        let mut reader = self.request(
+            encoding::CREATE_MONITORED_ITEMS_REQUEST,
+            encoding::CREATE_MONITORED_ITEMS_RESPONSE,
+            |writer| {
+                writer.u32(subscription_id)?;
+                writer.u32(TIMESTAMPS_BOTH)?;
+
+                writer.array(1)?;
+                write_read_value_id(writer, node)?;
+                writer.u32(MONITORING_MODE_REPORTING)?;
+
+                writer.u32(client_handle)?;
+                writer.f64(millis(sampling_interval))?;
+                // Filter, queue size, discard oldest
+                writer.null_extension_object()?;
+                writer.u32(1)?;
+                writer.bool(true)
+            },
+        )?;
+
+        if decode(reader.array_len())? != 1 {
+            return Err(ClientError::DecodeError("Unexpected number of results"));
+        }
+
+        let status = StatusCode(decode(reader.u32())?);
+        if status.is_bad() {
+            return Err(ClientError::StatusError(status));
+        }
+
+        decode(reader.u32())
+    }
+
+    /// Waits for the next notifications of any subscription, passing each data change to `f`,
+    /// and acknowledges them with the next call.
+    ///
+    /// The server holds the request until there are notifications, or until the keep-alive
+    /// count of the subscription elapses without any.
+    pub fn publish<F>(&mut self, mut f: F) -> Result<(), ClientError<C::Error>>
+    where
+        F: FnMut(DataChange<'_>),
+    {
+        let acknowledgements = core::mem::take(&mut self.acknowledgements);
+
+        let mut reader = self.request(
+            encoding::PUBLISH_REQUEST,
+            encoding::PUBLISH_RESPONSE,
+            |writer| {
+                writer.array(acknowledgements.len())?;
+
+                for (subscription_id, sequence_number) in &acknowledgements {
+                    writer.u32(*subscription_id)?;
+                    writer.u32(*sequence_number)?;
+                }
+
+                Ok(())
+            },
+        )?;
+
+        let subscription_id = decode(reader.u32())?;
+
+        // Available sequence numbers, more notifications
+        for _ in 0..decode(reader.array_len())? {
+            decode(reader.u32())?;
+        }
+
+        decode(reader.bool())?;
+
+        let sequence_number = decode(reader.u32())?;
+
+        // Publish time
+        decode(reader.i64())?;
+
+        let notifications = decode(reader.array_len())?;
+
+        for _ in 0..notifications {
+            let (type_id, body) = decode(reader.extension_object())?;
+
+            // Events and status changes are not supported
+            let body = match (type_id, body) {
+                (NodeId::Numeric { namespace: 0, id }, Some(body))
+                    if id == encoding::DATA_CHANGE_NOTIFICATION =>
+                {
+                    body
+                }
+                _ => continue,
+            };
+
+            let mut body = Reader::new(body);
+
+            for _ in 0..decode(body.array_len())? {
+                let client_handle = decode(body.u32())?;
+                let value = decode(body.data_value())?;
+
+                f(DataChange {
+                    subscription_id,
+                    client_handle,
+                    value,
+                });
+            }
+        }
+
+        // Keep-alive messages carry the next sequence number, which is not to be acknowledged
+        if notifications > 0 {
+            let _ = self
+                .acknowledgements
+                .push((subscription_id, sequence_number));
+        }
+
+        Ok(())
+    }
+
+    /// Closes the session, deleting its subscriptions, and the secure channel
+    pub fn close(mut self) -> Result<C, ClientError<C::Error>> {
+        self.request(
+            encoding::CLOSE_SESSION_REQUEST,
+            encoding::CLOSE_SESSION_RESPONSE,
+            |writer| writer.bool(true),
+        )?;
+
+        // The server closes the connection without responding
+        self.send(b"CLO", encoding::CLOSE_SECURE_CHANNEL_REQUEST, |_| Ok(()))?;
+
+        Ok(self.connection)
+    }
+
+    pub fn release(self) -> C {
+        self.connection
+    }
+
+    fn hello(&mut self, endpoint_url: &str) -> Result<(), ClientError<C::Error>> {
+        let size = self.buf.len() as u32;
+
+        let mut writer = Writer::new(self.buf);
+
+        let write = |writer: &mut Writer<'_>| {
+            writer.raw(b"HELF")?;
+            writer.u32(0)?;
+            // Protocol version, receive and send buffer sizes, max message size and max chunk count
+            writer.u32(0)?;
+            writer.u32(size)?;
+            writer.u32(size)?;
+            writer.u32(size)?;
+            writer.u32(1)?;
+            writer.string(Some(endpoint_url))
+        };
+
+        write(&mut writer).map_err(ClientError::EncodeError)?;
+
+        let len = writer.len();
+        writer.patch_u32(4, len as u32);
+
+        self.connection
+            .write_all(&self.buf[..len])
+            .map_err(ClientError::IoError)?;
+
+        let (message_type, mut reader) = Self::receive(&mut self.connection, self.buf)?;
+        if &message_type != b"ACK" {
+            return Err(ClientError::DecodeError("Unexpected message"));
+        }
+
+        // Protocol version, then the receive buffer size of the server
+        decode(reader.u32())?;
+        self.send_size = decode(reader.u32())? as usize;
+
+        Ok(())
+    }
+
+    fn open_secure_channel(&mut self, renew: bool) -> Result<(), ClientError<C::Error>> {
+        let lifetime = self.channel_lifetime.as_millis() as u32;
+
+        self.send(b"OPN", encoding::OPEN_SECURE_CHANNEL_REQUEST, |writer| {
+            // Client protocol version, request type (issue or renew), security mode (none)
+            writer.u32(0)?;
+            writer.u32(renew as u32)?;
+            writer.u32(1)?;
+            // Client nonce
+            writer.byte_string(None)?;
+            writer.u32(lifetime)
+        })?;
+
+        let mut reader = self.response(encoding::OPEN_SECURE_CHANNEL_RESPONSE)?;
+
+        // Server protocol version
+        decode(reader.u32())?;
+
+        let channel_id = decode(reader.u32())?;
+        let token_id = decode(reader.u32())?;
+
+        // Created at
+        decode(reader.i64())?;
+        let lifetime = decode(reader.u32())?;
+
+        self.channel_id = channel_id;
+        self.token_id = token_id;
+        self.channel_lifetime = Duration::from_millis(lifetime as u64);
+
+        Ok(())
+    }
+
+    fn create_session(
+        &mut self,
+        configuration: &Configuration<'_>,
+    ) -> Result<(), ClientError<C::Error>> {
+        let max_response_size = self.buf.len() as u32;
+
+        let mut reader = self.request(
+            encoding::CREATE_SESSION_REQUEST,
+            encoding::CREATE_SESSION_RESPONSE,
+            |writer| {
+                // The application description: URI, product URI, name (a localized text
+                // with just the text), type (client), gateway and discovery profile URIs,
+                // discovery URLs
+                writer.string(Some(configuration.application_uri))?;
+                writer.string(None)?;
+                writer.u8(0x02)?;
+                writer.string(Some(configuration.application_name))?;
+                writer.u32(1)?;
+                writer.string(None)?;
+                writer.string(None)?;
+                writer.array(0)?;
+
+                // Server URI
+                writer.string(None)?;
+                writer.string(Some(configuration.endpoint_url))?;
+                writer.string(Some(configuration.session_name))?;
+                // Client nonce and certificate
+                writer.byte_string(None)?;
+                writer.byte_string(None)?;
+                writer.f64(millis(configuration.session_timeout))?;
+                writer.u32(max_response_size)
+            },
+        )?;
+
+        // The session ID, then the token identifying the session in the requests
+        decode(reader.node_id())?;
+        let token = decode(reader.node_id_raw())?;
+
+        let token = heapless::Vec::from_slice(token)
+            .map_err(|_| ClientError::DecodeError("Authentication token too long"))?;
+
+        self.authentication_token = token;
+
+        Ok(())
+    }
+
+    fn activate_session(&mut self, identity: &Identity<'_>) -> Result<(), ClientError<C::Error>> {
+        self.request(
+            encoding::ACTIVATE_SESSION_REQUEST,
+            encoding::ACTIVATE_SESSION_RESPONSE,
+            |writer| {
+                // Client signature, software certificates, locale IDs
+                writer.string(None)?;
+                writer.byte_string(None)?;
+                writer.array(0)?;
+                writer.array(0)?;
+
+                match identity {
+                    Identity::Anonymous { policy_id } => {
+                        let position =
+                            writer.extension_object(encoding::ANONYMOUS_IDENTITY_TOKEN)?;
+                        writer.string(Some(policy_id))?;
+                        writer.end_extension_object(position);
+                    }
+                    Identity::UserName {
+                        policy_id,
+                        user,
+                        password,
+                    } => {
+                        let position =
+                            writer.extension_object(encoding::USER_NAME_IDENTITY_TOKEN)?;
+                        writer.string(Some(policy_id))?;
+                        writer.string(Some(user))?;
+                        writer.byte_string(Some(password.as_bytes()))?;
+                        // Encryption algorithm
+                        writer.string(None)?;
+                        writer.end_extension_object(position);
+                    }
+                }
+
+                // User token signature
+                writer.string(None)?;
+                writer.byte_string(None)
+            },
+        )?;
+
+        Ok(())
+    }
+
+    /// Sends a service request over the session, returning the reader of the response,
+    /// positioned after the response header
+    fn request<F>(
+        &mut self,
+        request_type: u32,
+        response_type: u32,
+        f: F,
+    ) -> Result<Reader<'_>, ClientError<C::Error>>
+    where
+        F: FnOnce(&mut Writer<'_>) -> Result<(), &'static str>,
+    {
+        self.send(b"MSG", request_type, f)?;
+        self.response(response_type)
+    }
+
+    /// Sends a single chunk message with the body written by `f`, preceded by the request header
+    fn send<F>(
+        &mut self,
+        message_type: &[u8; 3],
+        request_type: u32,
+        f: F,
+    ) -> Result<(), ClientError<C::Error>>
+    where
+        F: FnOnce(&mut Writer<'_>) -> Result<(), &'static str>,
+    {
+        self.sequence_number = self.sequence_number.wrapping_add(1);
+        self.request_id = self.request_id.wrapping_add(1);
+
+        let timeout = self.request_timeout.as_millis() as u32;
+        let channel_id = self.channel_id;
+        let token_id = self.token_id;
+        let sequence_number = self.sequence_number;
+        let request_id = self.request_id;
+        let authentication_token = &self.authentication_token;
+
+        let mut writer = Writer::new(self.buf);
+
+        let write = |writer: &mut Writer<'_>| {
+            writer.raw(message_type)?;
+            writer.u8(b'F')?;
+            writer.u32(0)?;
+            writer.u32(channel_id)?;
+
+            if message_type == b"OPN" {
+                // The asymmetric security header: policy URI, sender certificate, receiver thumbprint
+                writer.string(Some(SECURITY_POLICY_NONE))?;
+                writer.byte_string(None)?;
+                writer.byte_string(None)?;
+            } else {
+                writer.u32(token_id)?;
+            }
+
+            writer.u32(sequence_number)?;
+            writer.u32(request_id)?;
+            writer.node_id(&NodeId::numeric(0, request_type))?;
+
+            // The request header: authentication token, timestamp, request handle,
+            // return diagnostics, audit entry ID, timeout hint, additional header
+            if authentication_token.is_empty() {
+                writer.node_id(&NodeId::numeric(0, 0))?;
+            } else {
+                writer.raw(authentication_token)?;
+            }
+
+            writer.i64(0)?;
+            writer.u32(request_id)?;
+            writer.u32(0)?;
+            writer.string(None)?;
+            writer.u32(timeout)?;
+            writer.null_extension_object()?;
+
+            f(writer)
+        };
+
+        write(&mut writer).map_err(ClientError::EncodeError)?;
+
+        let len = writer.len();
+        if self.send_size > 0 && len > self.send_size {
+            return Err(ClientError::EncodeError("Message too long for the server"));
+        }
+
+        writer.patch_u32(4, len as u32);
+
+        self.connection
+            .write_all(&self.buf[..len])
+            .map_err(ClientError::IoError)?;
+
+        self.connection.flush().map_err(ClientError::IoError)
+    }
+
+    /// Receives the response to the last request, returning its reader positioned after the response header
+    fn response(&mut self, response_type: u32) -> Result<Reader<'_>, ClientError<C::Error>> {
+        let request_id = self.request_id;
+
+        let (message_type, mut reader) = Self::receive(&mut self.connection, self.buf)?;
+
+        if &message_type != b"MSG" && &message_type != b"OPN" {
+            return Err(ClientError::DecodeError("Unexpected message"));
+        }
+
+        // Secure channel ID, then the security header
+        decode(reader.u32())?;
+
+        if &message_type == b"OPN" {
+            decode(reader.string())?;
+            decode(reader.byte_string())?;
+            decode(reader.byte_string())?;
+        } else {
+            decode(reader.u32())?;
+        }
+
+        // Sequence number
+        decode(reader.u32())?;
+
+        if decode(reader.u32())? != request_id {
+            return Err(ClientError::DecodeError("Unexpected response"));
+        }
+
+        let type_id = decode(reader.node_id())?;
+
+        // The response header: timestamp, request handle, service result, diagnostics,
+        // string table, additional header
+        decode(reader.i64())?;
+        decode(reader.u32())?;
+
+        let status = StatusCode(decode(reader.u32())?);
+
+        decode(reader.skip_diagnostic_info())?;
+        decode(reader.skip_strings())?;
+        decode(reader.extension_object())?;
+
+        if status.is_bad() {
+            return Err(ClientError::StatusError(status));
+        }
+
+        match type_id {
+            NodeId::Numeric { namespace: 0, id } if id == response_type => Ok(reader),
+            NodeId::Numeric { namespace: 0, id } if id == encoding::SERVICE_FAULT => {
+                Err(ClientError::StatusError(StatusCode::BAD_UNEXPECTED_ERROR))
+            }
+            _ => Err(ClientError::DecodeError("Unexpected response")),
+        }
+    }
+
+    /// Receives a whole message, returning its type and the reader of the rest of it
+    fn receive<'r>(
+        connection: &mut C,
+        buf: &'r mut [u8],
+    ) -> Result<([u8; 3], Reader<'r>), ClientError<C::Error>> {
+        let mut header = [0_u8; 8];
+        read_exact(connection, &mut header)?;
+
+        let message_type = [header[0], header[1], header[2]];
+        let len = u32::from_le_bytes([header[4], header[5], header[6], header[7]]) as usize;
+
+        if len < header.len() || len - header.len() > buf.len() {
+            return Err(ClientError::DecodeError("Message too long"));
+        }
+
+        let body = &mut buf[..len - header.len()];
+        read_exact(connection, body)?;
+
+        let mut reader = Reader::new(body);
+
+        if &message_type == b"ERR" {
+            return Err(ClientError::StatusError(StatusCode(decode(reader.u32())?)));
+        }
+
+        if header[3] != b'F' {
+            return Err(ClientError::DecodeError(
+                "Chunked messages are not supported",
+            ));
+        }
+
+        Ok((message_type, reader))
+    }
+}
+
+fn write_read_value_id(writer: &mut Writer<'_>, node: &NodeId<'_>) -> Result<(), &'static str> {
+    writer.node_id(node)?;
+    writer.u32(ATTRIBUTE_VALUE)?;
+    // Index range, then the data encoding as a qualified name
+    writer.string(None)?;
+    writer.u16(0)?;
+    writer.string(None)
+}
+
+fn read_exact<C>(connection: &mut C, buf: &mut [u8]) -> Result<(), ClientError<C::Error>>
+where
+    C: Read,
+{
+    connection.read_exact(buf).map_err(|e| match e {
+        ReadExactError::UnexpectedEof => ClientError::DecodeError("Connection closed"),
+        ReadExactError::Other(e) => ClientError::IoError(e),
+    })
+}
+
+fn decode<T, E>(result: Result<T, &'static str>) -> Result<T, ClientError<E>> {
+    result.map_err(ClientError::DecodeError)
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
@@ -0,0 +1,408 @@
+//! The OPC UA Binary encoding (OPC 10000-6, 5.2) of the few types the client needs
+
+use core::convert::TryInto;
+
+use super::{DataValue, NodeId, StatusCode, Variant};
+
+const NULL_LEN: i32 = -1;
+
+pub struct Writer<'a> {
+    buf: &'a mut [u8],
+    len: usize,
+}
+
+impl<'a> Writer<'a> {
+    pub fn new(buf: &'a mut [u8]) -> Self {
+        Self { buf, len: 0 }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn raw(&mut self, data: &[u8]) -> Result<(), &'static str> {
+        let end = self.len + data.len();
+        if end > self.buf.len() {
+            return Err("Message too long");
+        }
+
+        self.buf[self.len..end].copy_from_slice(data);
+        self.len = end;
+
+        Ok(())
+    }
+
+    pub fn u8(&mut self, value: u8) -> Result<(), &'static str> {
+        self.raw(&[value])
+    }
+
+    pub fn u16(&mut self, value: u16) -> Result<(), &'static str> {
+        self.raw(&value.to_le_bytes())
+    }
+
+    pub fn u32(&mut self, value: u32) -> Result<(), &'static str> {
+        self.raw(&value.to_le_bytes())
+    }
+
+    pub fn i32(&mut self, value: i32) -> Result<(), &'static str> {
+        self.raw(&value.to_le_bytes())
+    }
+
+    pub fn i64(&mut self, value: i64) -> Result<(), &'static str> {
+        self.raw(&value.to_le_bytes())
+    }
+
+    pub fn f64(&mut self, value: f64) -> Result<(), &'static str> {
+        self.raw(&value.to_le_bytes())
+    }
+
+    pub fn bool(&mut self, value: bool) -> Result<(), &'static str> {
+        self.u8(value as u8)
+    }
+
+    pub fn string(&mut self, value: Option<&str>) -> Result<(), &'static str> {
+        self.byte_string(value.map(str::as_bytes))
+    }
+
+    pub fn byte_string(&mut self, value: Option<&[u8]>) -> Result<(), &'static str> {
+        match value {
+            Some(value) => {
+                self.i32(value.len() as i32)?;
+                self.raw(value)
+            }
+            None => self.i32(NULL_LEN),
+        }
+    }
+
+    /// Writes the length of an array, whose elements follow
+    pub fn array(&mut self, len: usize) -> Result<(), &'static str> {
+        self.i32(len as i32)
+    }
+
+    pub fn node_id(&mut self, node_id: &NodeId<'_>) -> Result<(), &'static str> {
+        match node_id {
+            NodeId::Numeric { namespace: 0, id } if *id < 0x100 => {
+                self.u8(0x00)?;
+                self.u8(*id as u8)
+            }
+            NodeId::Numeric { namespace, id } if *namespace < 0x100 && *id < 0x10000 => {
+                self.u8(0x01)?;
+                self.u8(*namespace as u8)?;
+                self.u16(*id as u16)
+            }
+            NodeId::Numeric { namespace, id } => {
+                self.u8(0x02)?;
+                self.u16(*namespace)?;
+                self.u32(*id)
+            }
+            NodeId::String { namespace, id } => {
+                self.u8(0x03)?;
+                self.u16(*namespace)?;
+                self.string(Some(id))
+            }
+            NodeId::Guid { namespace, id } => {
+                self.u8(0x04)?;
+                self.u16(*namespace)?;
+                self.raw(id)
+            }
+            NodeId::Opaque { namespace, id } => {
+                self.u8(0x05)?;
+                self.u16(*namespace)?;
+                self.byte_string(Some(id))
+            }
+        }
+    }
+
+    pub fn variant(&mut self, variant: &Variant<'_>) -> Result<(), &'static str> {
+        self.u8(variant.type_id())?;
+
+        match variant {
+            Variant::Null => Ok(()),
+            Variant::Boolean(value) => self.bool(*value),
+            Variant::SByte(value) => self.raw(&value.to_le_bytes()),
+            Variant::Byte(value) => self.u8(*value),
+            Variant::Int16(value) => self.raw(&value.to_le_bytes()),
+            Variant::UInt16(value) => self.u16(*value),
+            Variant::Int32(value) => self.i32(*value),
+            Variant::UInt32(value) => self.u32(*value),
+            Variant::Int64(value) => self.i64(*value),
+            Variant::UInt64(value) => self.raw(&value.to_le_bytes()),
+            Variant::Float(value) => self.raw(&value.to_le_bytes()),
+            Variant::Double(value) => self.f64(*value),
+            Variant::String(value) => self.string(Some(value)),
+            Variant::DateTime(value) => self.i64(*value),
+            Variant::ByteString(value) => self.byte_string(Some(value)),
+        }
+    }
+
+    /// An extension object without a body
+    pub fn null_extension_object(&mut self) -> Result<(), &'static str> {
+        self.raw(&[0x00, 0x00, 0x00])
+    }
+
+    /// Writes the header of an extension object whose binary body follows, returning
+    /// the position of its length, to be patched once the body is written
+    pub fn extension_object(&mut self, type_id: u32) -> Result<usize, &'static str> {
+        self.node_id(&NodeId::numeric(0, type_id))?;
+        self.u8(0x01)?;
+
+        let position = self.len;
+        self.i32(0)?;
+
+        Ok(position)
+    }
+
+    pub fn end_extension_object(&mut self, position: usize) {
+        let len = self.len - position - 4;
+
+        self.patch_u32(position, len as u32);
+    }
+
+    pub fn patch_u32(&mut self, position: usize, value: u32) {
+        self.buf[position..position + 4].copy_from_slice(&value.to_le_bytes());
+    }
+}
+
+pub struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    pub const fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    pub fn take(&mut self, len: usize) -> Result<&'a [u8], &'static str> {
+        if len > self.data.len() {
+            return Err("Truncated message");
+        }
+
+        let (taken, rest) = self.data.split_at(len);
+        self.data = rest;
+
+        Ok(taken)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], &'static str> {
+        Ok(self.take(N)?.try_into().unwrap())
+    }
+
+    pub fn u8(&mut self) -> Result<u8, &'static str> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub fn u16(&mut self) -> Result<u16, &'static str> {
+        Ok(u16::from_le_bytes(self.array()?))
+    }
+
+    pub fn u32(&mut self) -> Result<u32, &'static str> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    pub fn i32(&mut self) -> Result<i32, &'static str> {
+        Ok(i32::from_le_bytes(self.array()?))
+    }
+
+    pub fn i64(&mut self) -> Result<i64, &'static str> {
+        Ok(i64::from_le_bytes(self.array()?))
+    }
+
+    pub fn f64(&mut self) -> Result<f64, &'static str> {
+        Ok(f64::from_le_bytes(self.array()?))
+    }
+
+    pub fn bool(&mut self) -> Result<bool, &'static str> {
+        Ok(self.u8()? != 0)
+    }
+
+    pub fn string(&mut self) -> Result<Option<&'a str>, &'static str> {
+        self.byte_string()?
+            .map(|value| core::str::from_utf8(value).map_err(|_| "Invalid string"))
+            .transpose()
+    }
+
+    pub fn byte_string(&mut self) -> Result<Option<&'a [u8]>, &'static str> {
+        match self.i32()? {
+            len if len < 0 => Ok(None),
+            len => self.take(len as usize).map(Some),
+        }
+    }
+
+    /// The length of an array, null arrays being empty
+    pub fn array_len(&mut self) -> Result<usize, &'static str> {
+        Ok(self.i32()?.max(0) as usize)
+    }
+
+    pub fn skip_strings(&mut self) -> Result<(), &'static str> {
+        for _ in 0..self.array_len()? {
+            self.byte_string()?;
+        }
+
+        Ok(())
+    }
+
+    pub fn node_id(&mut self) -> Result<NodeId<'a>, &'static str> {
+        let encoding = self.u8()?;
+
+        let node_id = match encoding & 0x3f {
+            0x00 => NodeId::numeric(0, self.u8()? as u32),
+            0x01 => {
+                let namespace = self.u8()? as u16;
+
+                NodeId::numeric(namespace, self.u16()? as u32)
+            }
+            0x02 => {
+                let namespace = self.u16()?;
+
+                NodeId::numeric(namespace, self.u32()?)
+            }
+            0x03 => NodeId::String {
+                namespace: self.u16()?,
+                id: self.string()?.unwrap_or(""),
+            },
+            0x04 => NodeId::Guid {
+                namespace: self.u16()?,
+                id: self.array()?,
+            },
+            0x05 => NodeId::Opaque {
+                namespace: self.u16()?,
+                id: self.byte_string()?.unwrap_or(&[]),
+            },
+            _ => return Err("Invalid node ID"),
+        };
+
+        // The namespace URI and the server index of expanded node IDs
+        if encoding & 0x80 != 0 {
+            self.byte_string()?;
+        }
+
+        if encoding & 0x40 != 0 {
+            self.u32()?;
+        }
+
+        Ok(node_id)
+    }
+
+    /// A node ID, as encoded
+    pub fn node_id_raw(&mut self) -> Result<&'a [u8], &'static str> {
+        let data = self.data;
+
+        self.node_id()?;
+
+        Ok(&data[..data.len() - self.data.len()])
+    }
+
+    pub fn variant(&mut self) -> Result<Variant<'a>, &'static str> {
+        let encoding = self.u8()?;
+
+        if encoding & 0xc0 != 0 {
+            return Err("Array values are not supported");
+        }
+
+        Ok(match encoding {
+            0 => Variant::Null,
+            1 => Variant::Boolean(self.bool()?),
+            2 => Variant::SByte(i8::from_le_bytes(self.array()?)),
+            3 => Variant::Byte(self.u8()?),
+            4 => Variant::Int16(i16::from_le_bytes(self.array()?)),
+            5 => Variant::UInt16(self.u16()?),
+            6 => Variant::Int32(self.i32()?),
+            7 => Variant::UInt32(self.u32()?),
+            8 => Variant::Int64(self.i64()?),
+            9 => Variant::UInt64(u64::from_le_bytes(self.array()?)),
+            10 => Variant::Float(f32::from_le_bytes(self.array()?)),
+            11 => Variant::Double(self.f64()?),
+            12 => Variant::String(self.string()?.unwrap_or("")),
+            13 => Variant::DateTime(self.i64()?),
+            15 => Variant::ByteString(self.byte_string()?.unwrap_or(&[])),
+            _ => return Err("Unsupported value type"),
+        })
+    }
+
+    pub fn data_value(&mut self) -> Result<DataValue<'a>, &'static str> {
+        let mask = self.u8()?;
+
+        let value = if mask & 0x01 != 0 {
+            Some(self.variant()?)
+        } else {
+            None
+        };
+
+        let status = if mask & 0x02 != 0 {
+            StatusCode(self.u32()?)
+        } else {
+            StatusCode::GOOD
+        };
+
+        let source_timestamp = if mask & 0x04 != 0 {
+            Some(self.i64()?)
+        } else {
+            None
+        };
+
+        if mask & 0x10 != 0 {
+            self.u16()?;
+        }
+
+        let server_timestamp = if mask & 0x08 != 0 {
+            Some(self.i64()?)
+        } else {
+            None
+        };
+
+        if mask & 0x20 != 0 {
+            self.u16()?;
+        }
+
+        Ok(DataValue {
+            value,
+            status,
+            source_timestamp,
+            server_timestamp,
+        })
+    }
+
+    pub fn skip_diagnostic_info(&mut self) -> Result<(), &'static str> {
+        let mask = self.u8()?;
+
+        // The symbolic ID, namespace URI, locale and localized text indices
+        for bit in [0x01, 0x02, 0x04, 0x08] {
+            if mask & bit != 0 {
+                self.i32()?;
+            }
+        }
+
+        if mask & 0x10 != 0 {
+            self.byte_string()?;
+        }
+
+        if mask & 0x20 != 0 {
+            self.u32()?;
+        }
+
+        if mask & 0x40 != 0 {
+            self.skip_diagnostic_info()?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the type ID and the binary body, if any
+    pub fn extension_object(&mut self) -> Result<(NodeId<'a>, Option<&'a [u8]>), &'static str> {
+        let type_id = self.node_id()?;
+
+        let body = match self.u8()? {
+            0x00 => None,
+            0x01 => self.byte_string()?,
+            // XML bodies are skipped
+            0x02 => {
+                self.byte_string()?;
+
+                None
+            }
+            _ => return Err("Invalid extension object"),
+        };
+
+        Ok((type_id, body))
+    }
+}
@@ -3,6 +3,9 @@ use serde::{Deserialize, Serialize};
 
 use crate::io::{Io, Read, Write};
 use crate::utils::io::*;
+use crate::utils::version::Version;
+
+pub mod verify;
 
 #[derive(Clone, Debug)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
@@ -24,6 +27,13 @@ pub struct FirmwareInfo {
     pub download_id: Option<heapless::String<128>>,
 }
 
+impl FirmwareInfo {
+    /// Parses `version`, e.g. to refuse downgrades below a minimum version
+    pub fn semantic_version(&self) -> Result<Version, &'static str> {
+        self.version.parse()
+    }
+}
+
 #[derive(Clone, Debug)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
@@ -165,6 +175,19 @@ pub trait OtaUpdate: Write {
     }
 }
 
+impl<U> OtaUpdate for &mut U
+where
+    U: OtaUpdate,
+{
+    fn complete(&mut self) -> Result<(), Self::Error> {
+        (*self).complete()
+    }
+
+    fn abort(&mut self) -> Result<(), Self::Error> {
+        (*self).abort()
+    }
+}
+
 #[cfg(all(feature = "nightly", feature = "experimental"))]
 pub mod asynch {
     use core::future::Future;
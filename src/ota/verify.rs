@@ -0,0 +1,330 @@
+use core::convert::{Infallible, TryInto};
+use core::fmt;
+
+use crate::crypto::der;
+use crate::crypto::{
+    self, Digest, EcdsaP256PublicKey, EcdsaP256Signature, EcdsaP256Verify, Sha256,
+};
+use crate::error::{self, impl_error};
+use crate::io::{Error, ErrorKind, Io, Write};
+use crate::tls::Certificate;
+
+use super::OtaUpdate;
+
+/// Large enough for a DER-encoded ECDSA P-256 signature
+pub const MAX_SIGNATURE_LEN: usize = 72;
+
+const OID_EC_PUBLIC_KEY: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01];
+const OID_PRIME256V1: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07];
+const OID_ECDSA_WITH_SHA256: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x02];
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SignatureFormat {
+    /// The 64-byte `r || s` pair
+    Raw,
+    /// A DER-encoded `ECDSA-Sig-Value`, as produced by e.g. `openssl dgst -sha256 -sign`
+    Der,
+}
+
+/// Who is trusted to sign firmware images
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TrustAnchor<'a> {
+    /// Images signed with any of these keys are accepted, which allows for key rotation
+    PublicKeys(&'a [EcdsaP256PublicKey]),
+    /// Images are signed with the key of the last certificate of `chain`; the first one must be
+    /// signed by `root` and every other one by its predecessor.
+    ///
+    /// The certificates are DER-encoded and must use ECDSA P-256 with SHA-256.
+    /// Their validity period is not checked, as the device clock might not be synchronized yet.
+    CertificateChain {
+        root: &'a EcdsaP256PublicKey,
+        chain: &'a [&'a [u8]],
+    },
+}
+
+/// The signature policy enforced on firmware images: an ECDSA P-256 signature
+/// of the SHA-256 digest of the whole image
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Policy<'a> {
+    pub anchor: TrustAnchor<'a>,
+    pub format: SignatureFormat,
+}
+
+impl<'a> Policy<'a> {
+    /// Checks `signature` of the image with the SHA-256 `digest`
+    pub fn verify<H, V>(
+        &self,
+        verifier: &V,
+        digest: &Digest,
+        signature: &[u8],
+    ) -> Result<(), VerifyError<Infallible, V::Error>>
+    where
+        H: Sha256 + Default,
+        V: EcdsaP256Verify,
+    {
+        let signature = match self.format {
+            SignatureFormat::Raw => signature.try_into().ok(),
+            SignatureFormat::Der => crypto::ecdsa_p256_signature_from_der(signature),
+        }
+        .ok_or(VerifyError::InvalidSignature)?;
+
+        let accepted = match self.anchor {
+            TrustAnchor::PublicKeys(keys) => {
+                let mut accepted = false;
+
+                for key in keys {
+                    if verifier
+                        .verify(key, digest, &signature)
+                        .map_err(VerifyError::CryptoError)?
+                    {
+                        accepted = true;
+                        break;
+                    }
+                }
+
+                accepted
+            }
+            TrustAnchor::CertificateChain { root, chain } => {
+                let mut key = *root;
+
+                for certificate in chain {
+                    key = verify_certificate::<H, V>(verifier, &key, certificate)?;
+                }
+
+                verifier
+                    .verify(&key, digest, &signature)
+                    .map_err(VerifyError::CryptoError)?
+            }
+        };
+
+        if accepted {
+            Ok(())
+        } else {
+            Err(VerifyError::Rejected)
+        }
+    }
+}
+
+/// Checks that `certificate` is signed by `issuer`, returning its public key
+fn verify_certificate<H, V>(
+    verifier: &V,
+    issuer: &EcdsaP256PublicKey,
+    certificate: &[u8],
+) -> Result<EcdsaP256PublicKey, VerifyError<Infallible, V::Error>>
+where
+    H: Sha256 + Default,
+    V: EcdsaP256Verify,
+{
+    let (tbs, public_key, signature) =
+        parse_certificate(certificate).ok_or(VerifyError::InvalidCertificate)?;
+
+    let digest = crypto::sha256(H::default(), tbs);
+
+    if verifier
+        .verify(issuer, &digest, &signature)
+        .map_err(VerifyError::CryptoError)?
+    {
+        Ok(public_key)
+    } else {
+        Err(VerifyError::Rejected)
+    }
+}
+
+/// Returns the raw `TBSCertificate`, the subject public key and the signature of an X.509 certificate
+fn parse_certificate(
+    certificate: &[u8],
+) -> Option<(&[u8], EcdsaP256PublicKey, EcdsaP256Signature)> {
+    let certificate = Certificate::parse(certificate)?;
+
+    if certificate.signature_algorithm != OID_ECDSA_WITH_SHA256 {
+        return None;
+    }
+
+    Some((
+        certificate.tbs,
+        ec_public_key(certificate.public_key_info)?,
+        crypto::ecdsa_p256_signature_from_der(certificate.signature)?,
+    ))
+}
+
+fn ec_public_key(spki: &[u8]) -> Option<EcdsaP256PublicKey> {
+    let (spki, _) = der::expect(spki, der::SEQUENCE)?;
+    let (algorithm, rest) = der::expect(spki.value, der::SEQUENCE)?;
+    let (key, _) = der::expect(rest, der::BIT_STRING)?;
+
+    let (oid, rest) = der::expect(algorithm.value, der::OID)?;
+    let (curve, _) = der::expect(rest, der::OID)?;
+
+    if oid.value != OID_EC_PUBLIC_KEY || curve.value != OID_PRIME256V1 {
+        return None;
+    }
+
+    // No unused bits, followed by the uncompressed point
+    key.value.strip_prefix(&[0, 4])?.try_into().ok()
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum VerifyError<U, C> {
+    OtaError(U),
+    CryptoError(C),
+    /// No signature was provided for the image
+    Unsigned,
+    /// The signature is malformed
+    InvalidSignature,
+    /// A certificate of the chain is malformed or uses unsupported algorithms
+    InvalidCertificate,
+    /// The signature does not match any of the trusted keys
+    Rejected,
+}
+
+impl<U, C> VerifyError<U, C> {
+    fn map_ota<F, T>(self, f: F) -> VerifyError<T, C>
+    where
+        F: FnOnce(U) -> T,
+    {
+        match self {
+            Self::OtaError(e) => VerifyError::OtaError(f(e)),
+            Self::CryptoError(e) => VerifyError::CryptoError(e),
+            Self::Unsigned => VerifyError::Unsigned,
+            Self::InvalidSignature => VerifyError::InvalidSignature,
+            Self::InvalidCertificate => VerifyError::InvalidCertificate,
+            Self::Rejected => VerifyError::Rejected,
+        }
+    }
+}
+
+impl_error! {
+    VerifyError<U: Display, C: Display> {
+        OtaError(e) => "OTA error: {e}"; e.error_kind(),
+        CryptoError(e) => "Crypto error: {e}"; e.error_kind(),
+        Unsigned => "Firmware image is not signed"; error::ErrorKind::Unauthorized,
+        InvalidSignature => "Invalid firmware signature"; error::ErrorKind::InvalidInput,
+        InvalidCertificate => "Invalid signing certificate"; error::ErrorKind::InvalidInput,
+        Rejected => "Firmware signature rejected"; error::ErrorKind::Unauthorized,
+    }
+}
+
+impl<U, C> Error for VerifyError<U, C>
+where
+    U: Error,
+    C: fmt::Debug,
+{
+    fn kind(&self) -> ErrorKind {
+        match self {
+            Self::OtaError(e) => e.kind(),
+            _ => ErrorKind::Other,
+        }
+    }
+}
+
+/// Wraps an `OtaUpdate`, hashing the image as it is written and refusing to `complete`
+/// the update unless its signature satisfies the policy; the update is aborted otherwise.
+///
+/// Since the image is only activated by `complete`, a tampered or unsigned image is
+/// never booted, even when it was downloaded over plain HTTP.
+pub struct VerifyingUpdate<'a, U, H, V> {
+    update: U,
+    hasher: Option<H>,
+    verifier: V,
+    policy: Policy<'a>,
+    signature: heapless::Vec<u8, MAX_SIGNATURE_LEN>,
+}
+
+impl<'a, U, H, V> VerifyingUpdate<'a, U, H, V>
+where
+    U: OtaUpdate,
+    H: Sha256 + Default,
+    V: EcdsaP256Verify,
+{
+    pub fn new(update: U, verifier: V, policy: Policy<'a>) -> Self {
+        Self {
+            update,
+            hasher: Some(H::default()),
+            verifier,
+            policy,
+            signature: heapless::Vec::new(),
+        }
+    }
+
+    /// Sets the detached signature of the image, e.g. as received in the update manifest
+    pub fn set_signature(
+        &mut self,
+        signature: &[u8],
+    ) -> Result<(), VerifyError<U::Error, V::Error>> {
+        self.signature =
+            heapless::Vec::from_slice(signature).map_err(|_| VerifyError::InvalidSignature)?;
+
+        Ok(())
+    }
+
+    fn verify(&mut self) -> Result<(), VerifyError<U::Error, V::Error>> {
+        if self.signature.is_empty() {
+            return Err(VerifyError::Unsigned);
+        }
+
+        // A second `complete` after a failed verification fails as well
+        let digest = self.hasher.take().ok_or(VerifyError::Rejected)?.finish();
+
+        self.policy
+            .verify::<H, V>(&self.verifier, &digest, &self.signature)
+            .map_err(|e| e.map_ota(|e| match e {}))
+    }
+
+    pub fn release(self) -> U {
+        self.update
+    }
+}
+
+impl<'a, U, H, V> Io for VerifyingUpdate<'a, U, H, V>
+where
+    U: Io,
+    V: EcdsaP256Verify,
+{
+    type Error = VerifyError<U::Error, V::Error>;
+}
+
+impl<'a, U, H, V> Write for VerifyingUpdate<'a, U, H, V>
+where
+    U: OtaUpdate,
+    H: Sha256 + Default,
+    V: EcdsaP256Verify,
+{
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        let size = self.update.write(buf).map_err(VerifyError::OtaError)?;
+
+        if let Some(hasher) = self.hasher.as_mut() {
+            hasher.update(&buf[..size]);
+        }
+
+        Ok(size)
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.update.flush().map_err(VerifyError::OtaError)
+    }
+}
+
+impl<'a, U, H, V> OtaUpdate for VerifyingUpdate<'a, U, H, V>
+where
+    U: OtaUpdate,
+    H: Sha256 + Default,
+    V: EcdsaP256Verify,
+{
+    fn complete(&mut self) -> Result<(), Self::Error> {
+        match self.verify() {
+            Ok(()) => self.update.complete().map_err(VerifyError::OtaError),
+            Err(e) => {
+                self.update.abort().map_err(VerifyError::OtaError)?;
+
+                Err(e)
+            }
+        }
+    }
+
+    fn abort(&mut self) -> Result<(), Self::Error> {
+        self.update.abort().map_err(VerifyError::OtaError)
+    }
+}
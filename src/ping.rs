@@ -4,6 +4,7 @@ use core::time::Duration;
 #[cfg(feature = "use_serde")]
 use serde::{Deserialize, Serialize};
 
+use crate::error::PartialError;
 use crate::ipv4;
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -58,6 +59,17 @@ pub struct Summary {
     pub time: Duration,
 }
 
+/// A single hop of a traceroute. `addr` and `rtt` are `None` when the hop did not respond in time.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub struct Hop {
+    pub ttl: u8,
+    #[cfg_attr(feature = "defmt", defmt(Debug2Format))]
+    pub addr: Option<ipv4::Ipv4Addr>,
+    pub rtt: Option<Duration>,
+}
+
 pub trait Ping {
     type Error: Debug;
 
@@ -69,6 +81,37 @@ pub trait Ping {
         conf: &Configuration,
         reply_callback: &F,
     ) -> Result<Summary, Self::Error>;
+
+    /// Pings each of `ips` with `conf`, with all the requests in flight at once, and calls
+    /// `summary_callback` with the summary of each. The default implementation pings them in
+    /// sequence; implementations which can run several ping sessions at once override it.
+    fn ping_many<F: FnMut(ipv4::Ipv4Addr, &Summary)>(
+        &mut self,
+        ips: &[ipv4::Ipv4Addr],
+        conf: &Configuration,
+        summary_callback: &mut F,
+    ) -> Result<(), Self::Error> {
+        for ip in ips {
+            let summary = self.ping(*ip, conf)?;
+
+            summary_callback(*ip, &summary);
+        }
+
+        Ok(())
+    }
+
+    /// Stops at the destination, or after `max_hops` (at most `N`) hops.
+    /// `conf.count` is the number of probes sent per hop.
+    ///
+    /// Optional: the default implementation fails with `PartialError::Unsupported`.
+    fn traceroute<const N: usize>(
+        &mut self,
+        _ip: ipv4::Ipv4Addr,
+        _max_hops: u8,
+        _conf: &Configuration,
+    ) -> Result<heapless::Vec<Hop, N>, PartialError<Self::Error>> {
+        Err(PartialError::Unsupported)
+    }
 }
 
 impl<P> Ping for &mut P
@@ -89,16 +132,92 @@ where
     ) -> Result<Summary, Self::Error> {
         (*self).ping_details(ip, conf, reply_callback)
     }
+
+    fn ping_many<F: FnMut(ipv4::Ipv4Addr, &Summary)>(
+        &mut self,
+        ips: &[ipv4::Ipv4Addr],
+        conf: &Configuration,
+        summary_callback: &mut F,
+    ) -> Result<(), Self::Error> {
+        (*self).ping_many(ips, conf, summary_callback)
+    }
+
+    fn traceroute<const N: usize>(
+        &mut self,
+        ip: ipv4::Ipv4Addr,
+        max_hops: u8,
+        conf: &Configuration,
+    ) -> Result<heapless::Vec<Hop, N>, PartialError<Self::Error>> {
+        (*self).traceroute(ip, max_hops, conf)
+    }
+}
+
+/// The most hosts `ping_subnet` pings at once
+pub const MAX_CONCURRENCY: usize = 32;
+
+fn sweep_configuration(timeout: Duration) -> Configuration {
+    Configuration {
+        count: 1,
+        interval: Duration::from_secs(0),
+        timeout,
+        ..Default::default()
+    }
+}
+
+/// Pings all hosts of `subnet` once each, `concurrency` (at most `MAX_CONCURRENCY`) at a time with
+/// `Ping::ping_many`, and returns the first `N` hosts which responded, together with the total number
+/// of responding hosts.
+pub fn ping_subnet<P, const N: usize>(
+    ping: &mut P,
+    subnet: &ipv4::Subnet,
+    concurrency: usize,
+    timeout: Duration,
+) -> Result<(heapless::Vec<ipv4::Ipv4Addr, N>, usize), P::Error>
+where
+    P: Ping,
+{
+    let conf = sweep_configuration(timeout);
+    let concurrency = concurrency.clamp(1, MAX_CONCURRENCY);
+
+    let mut hosts = heapless::Vec::new();
+    let mut count = 0;
+
+    let mut on_summary = |ip, summary: &Summary| {
+        if summary.received > 0 {
+            let _ = hosts.push(ip);
+            count += 1;
+        }
+    };
+
+    let mut batch = heapless::Vec::<_, MAX_CONCURRENCY>::new();
+
+    for ip in subnet.hosts() {
+        let _ = batch.push(ip);
+
+        if batch.len() == concurrency {
+            ping.ping_many(&batch, &conf, &mut on_summary)?;
+            batch.clear();
+        }
+    }
+
+    if !batch.is_empty() {
+        ping.ping_many(&batch, &conf, &mut on_summary)?;
+    }
+
+    Ok((hosts, count))
 }
+
 #[cfg(all(feature = "nightly", feature = "experimental"))]
 pub mod asynch {
     use core::fmt::Debug;
     use core::future::Future;
 
+    use core::time::Duration;
+
     use crate::executor::asynch::{Blocker, Blocking};
     use crate::ipv4;
 
-    pub use super::{Configuration, Reply, Summary};
+    pub use super::{Configuration, Hop, Reply, Summary};
 
     pub trait Ping {
         type Error: Debug;
@@ -140,6 +259,41 @@ pub mod asynch {
         }
     }
 
+    /// The optional traceroute of `Ping`, implemented by the drivers which support it
+    pub trait Traceroute: Ping {
+        type TracerouteFuture<'a, const N: usize>: Future<
+            Output = Result<heapless::Vec<Hop, N>, Self::Error>,
+        >
+        where
+            Self: 'a;
+
+        /// Same as the blocking `Ping::traceroute`
+        fn traceroute<const N: usize>(
+            &mut self,
+            ip: ipv4::Ipv4Addr,
+            max_hops: u8,
+            conf: &Configuration,
+        ) -> Self::TracerouteFuture<'_, N>;
+    }
+
+    impl<P> Traceroute for &mut P
+    where
+        P: Traceroute,
+    {
+        type TracerouteFuture<'a, const N: usize> = P::TracerouteFuture<'a, N>
+        where
+            Self: 'a;
+
+        fn traceroute<const N: usize>(
+            &mut self,
+            ip: ipv4::Ipv4Addr,
+            max_hops: u8,
+            conf: &Configuration,
+        ) -> Self::TracerouteFuture<'_, N> {
+            (*self).traceroute(ip, max_hops, conf)
+        }
+    }
+
     impl<B, P> super::Ping for Blocking<B, P>
     where
         B: Blocker,
@@ -165,4 +319,29 @@ pub mod asynch {
                 .block_on(self.api.ping_details(ip, conf, reply_callback))
         }
     }
+
+    /// Like the blocking `ping_subnet`, but pings the hosts in sequence, as `ping` borrows `P`
+    /// mutably for the whole request
+    pub async fn ping_subnet<P, const N: usize>(
+        ping: &mut P,
+        subnet: &ipv4::Subnet,
+        timeout: Duration,
+    ) -> Result<(heapless::Vec<ipv4::Ipv4Addr, N>, usize), P::Error>
+    where
+        P: Ping,
+    {
+        let conf = super::sweep_configuration(timeout);
+
+        let mut hosts = heapless::Vec::new();
+        let mut count = 0;
+
+        for ip in subnet.hosts() {
+            if ping.ping(ip, &conf).await?.received > 0 {
+                let _ = hosts.push(ip);
+                count += 1;
+            }
+        }
+
+        Ok((hosts, count))
+    }
 }
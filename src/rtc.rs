@@ -0,0 +1,29 @@
+use core::fmt::Debug;
+
+use crate::sys_time::DateTime;
+
+/// A battery-backed real time clock keeping UTC time across power cycles and deep sleep, either internal to
+/// the SoC or external, e.g. a DS3231 or PCF8563 on an I2C bus driven with `embedded-hal`.
+pub trait Rtc {
+    type Error: Debug;
+
+    /// Returns `None` if the RTC lost the time, e.g. because it was never set or its backup battery ran out
+    fn get(&mut self) -> Result<Option<DateTime>, Self::Error>;
+
+    fn set(&mut self, time: &DateTime) -> Result<(), Self::Error>;
+}
+
+impl<R> Rtc for &mut R
+where
+    R: Rtc,
+{
+    type Error = R::Error;
+
+    fn get(&mut self) -> Result<Option<DateTime>, Self::Error> {
+        (*self).get()
+    }
+
+    fn set(&mut self, time: &DateTime) -> Result<(), Self::Error> {
+        (*self).set(time)
+    }
+}
@@ -0,0 +1,84 @@
+use core::fmt::Debug;
+use core::time::Duration;
+
+#[cfg(feature = "use_serde")]
+use serde::{Deserialize, Serialize};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub enum ServiceStatus {
+    Stopped,
+    Starting,
+    Started,
+    Stopping,
+    Failed,
+}
+
+pub trait ErrorType {
+    type Error: Debug;
+}
+
+impl<E> ErrorType for &E
+where
+    E: ErrorType,
+{
+    type Error = E::Error;
+}
+
+impl<E> ErrorType for &mut E
+where
+    E: ErrorType,
+{
+    type Error = E::Error;
+}
+
+/// A uniform lifecycle view over network interfaces, clients and servers,
+/// so that these can be managed by generic supervision code.
+///
+/// See `utils::service` for adapters turning the `Wifi` and `Eth` traits, HTTP servers and MQTT clients
+/// into a `Service`.
+pub trait Service: ErrorType {
+    fn start(&mut self) -> Result<(), Self::Error>;
+    fn stop(&mut self) -> Result<(), Self::Error>;
+
+    fn status(&self) -> Result<ServiceStatus, Self::Error>;
+
+    /// Returns `false` if `predicate` did not match the service status within `timeout`
+    fn wait_status_with_timeout<P>(
+        &self,
+        predicate: P,
+        timeout: Duration,
+    ) -> Result<bool, Self::Error>
+    where
+        P: Fn(ServiceStatus) -> bool;
+}
+
+impl<S> Service for &mut S
+where
+    S: Service,
+{
+    fn start(&mut self) -> Result<(), Self::Error> {
+        (*self).start()
+    }
+
+    fn stop(&mut self) -> Result<(), Self::Error> {
+        (*self).stop()
+    }
+
+    fn status(&self) -> Result<ServiceStatus, Self::Error> {
+        (**self).status()
+    }
+
+    fn wait_status_with_timeout<P>(
+        &self,
+        predicate: P,
+        timeout: Duration,
+    ) -> Result<bool, Self::Error>
+    where
+        P: Fn(ServiceStatus) -> bool,
+    {
+        (**self).wait_status_with_timeout(predicate, timeout)
+    }
+}
@@ -4,6 +4,8 @@ use core::fmt::{self, Debug};
 use serde::de::DeserializeOwned;
 use serde::Serialize;
 
+use crate::error::{Classify, ErrorKind};
+
 pub trait StorageBase {
     type Error: Debug;
 
@@ -160,6 +162,19 @@ where
 {
 }
 
+impl<R, S> Classify for StorageError<R, S>
+where
+    R: Classify,
+    S: Classify,
+{
+    fn error_kind(&self) -> ErrorKind {
+        match self {
+            Self::RawStorageError(e) => e.error_kind(),
+            Self::SerdeError(e) => e.error_kind(),
+        }
+    }
+}
+
 pub struct StorageImpl<const N: usize, R, S> {
     raw_storage: R,
     serde: S,
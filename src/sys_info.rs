@@ -0,0 +1,72 @@
+use core::fmt::Debug;
+
+#[cfg(feature = "use_serde")]
+use serde::{Deserialize, Serialize};
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub struct HeapInfo {
+    /// In bytes
+    pub free: usize,
+    /// The lowest free heap since boot, in bytes
+    pub min_free: usize,
+    /// The largest allocation that would currently succeed, in bytes; much lower than `free` when the heap
+    /// is fragmented
+    pub largest_free_block: usize,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub struct StackInfo {
+    pub task: heapless::String<16>,
+    /// The lowest free stack space of the task since it started, in bytes
+    pub high_watermark: usize,
+}
+
+pub trait SystemInfo {
+    type Error: Debug;
+
+    fn heap(&self) -> Result<HeapInfo, Self::Error>;
+
+    /// Returns the stacks of up to `N` tasks, and the total number of tasks
+    fn stacks_n<const N: usize>(&self)
+        -> Result<(heapless::Vec<StackInfo, N>, usize), Self::Error>;
+}
+
+impl<S> SystemInfo for &S
+where
+    S: SystemInfo,
+{
+    type Error = S::Error;
+
+    fn heap(&self) -> Result<HeapInfo, Self::Error> {
+        (**self).heap()
+    }
+
+    fn stacks_n<const N: usize>(
+        &self,
+    ) -> Result<(heapless::Vec<StackInfo, N>, usize), Self::Error> {
+        (**self).stacks_n()
+    }
+}
+
+impl<S> SystemInfo for &mut S
+where
+    S: SystemInfo,
+{
+    type Error = S::Error;
+
+    fn heap(&self) -> Result<HeapInfo, Self::Error> {
+        (**self).heap()
+    }
+
+    fn stacks_n<const N: usize>(
+        &self,
+    ) -> Result<(heapless::Vec<StackInfo, N>, usize), Self::Error> {
+        (**self).stacks_n()
+    }
+}
@@ -1,5 +1,9 @@
+use core::ops::{Add, AddAssign, Sub, SubAssign};
 use core::time::Duration;
 
+#[cfg(feature = "use_serde")]
+use serde::{Deserialize, Serialize};
+
 pub trait SystemTime {
     fn now(&self) -> Duration;
 }
@@ -12,3 +16,275 @@ where
         (*self).now()
     }
 }
+
+/// A point in time, measured as the duration since the (implementation-defined) epoch of a `SystemTime`.
+///
+/// Unlike `std::time::Instant`, this type is available on no_std too. Instants are only
+/// comparable when obtained from the same `SystemTime`.
+///
+/// The APIs of the crate take either an `Instant`, when they schedule against the current time, like
+/// the `poll` methods of the supervisor, the MQTT broker or the LwM2M client, or a relative
+/// `core::time::Duration`, like the timers, the HTTP timeouts, the MQTT keep-alive and the retry
+/// policies. Both are available on no_std.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub struct Instant(Duration);
+
+impl Instant {
+    pub const fn from_duration(since_epoch: Duration) -> Self {
+        Self(since_epoch)
+    }
+
+    pub fn now<T>(time: &T) -> Self
+    where
+        T: SystemTime,
+    {
+        Self(time.now())
+    }
+
+    pub const fn as_duration(&self) -> Duration {
+        self.0
+    }
+
+    /// Saturates to zero if `earlier` is actually later than `self`
+    pub fn duration_since(&self, earlier: Instant) -> Duration {
+        self.0.saturating_sub(earlier.0)
+    }
+
+    pub fn checked_duration_since(&self, earlier: Instant) -> Option<Duration> {
+        self.0.checked_sub(earlier.0)
+    }
+
+    pub fn elapsed<T>(&self, time: &T) -> Duration
+    where
+        T: SystemTime,
+    {
+        Self::now(time).duration_since(*self)
+    }
+
+    pub fn checked_add(&self, duration: Duration) -> Option<Instant> {
+        self.0.checked_add(duration).map(Self)
+    }
+
+    pub fn checked_sub(&self, duration: Duration) -> Option<Instant> {
+        self.0.checked_sub(duration).map(Self)
+    }
+}
+
+impl From<Duration> for Instant {
+    fn from(since_epoch: Duration) -> Self {
+        Self(since_epoch)
+    }
+}
+
+impl Add<Duration> for Instant {
+    type Output = Instant;
+
+    fn add(self, duration: Duration) -> Self::Output {
+        Self(self.0 + duration)
+    }
+}
+
+impl AddAssign<Duration> for Instant {
+    fn add_assign(&mut self, duration: Duration) {
+        self.0 += duration;
+    }
+}
+
+impl Sub<Duration> for Instant {
+    type Output = Instant;
+
+    fn sub(self, duration: Duration) -> Self::Output {
+        Self(self.0 - duration)
+    }
+}
+
+impl SubAssign<Duration> for Instant {
+    fn sub_assign(&mut self, duration: Duration) {
+        self.0 -= duration;
+    }
+}
+
+impl Sub<Instant> for Instant {
+    type Output = Duration;
+
+    fn sub(self, earlier: Instant) -> Self::Output {
+        self.duration_since(earlier)
+    }
+}
+
+/// A UTC date and time of the proleptic Gregorian calendar, for the wall clock times of schedules,
+/// RTCs and log timestamps.
+///
+/// `SystemTime` implementations synchronized with SNTP count from the Unix epoch.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub struct DateTime {
+    pub year: u16,
+    /// 1 to 12
+    pub month: u8,
+    /// 1 to 31
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+}
+
+impl DateTime {
+    const MAX_UNIX: u64 = 2_005_949_145_599;
+
+    /// Saturates at the end of year 65535
+    pub fn from_unix(secs: u64) -> Self {
+        let secs = secs.min(Self::MAX_UNIX);
+
+        let (year, month, day) = civil_from_days((secs / 86400) as _);
+        let time = secs % 86400;
+
+        Self {
+            year,
+            month,
+            day,
+            hour: (time / 3600) as _,
+            minute: (time % 3600 / 60) as _,
+            second: (time % 60) as _,
+        }
+    }
+
+    /// Returns `None` for dates before 1970 and for fields out of range
+    pub fn to_unix(&self) -> Option<u64> {
+        if !(1..=12).contains(&self.month)
+            || self.day < 1
+            || self.day > days_in_month(self.year, self.month)
+            || self.hour > 23
+            || self.minute > 59
+            || self.second > 59
+        {
+            return None;
+        }
+
+        let days = days_from_civil(self.year, self.month, self.day)?;
+
+        Some(
+            days as u64 * 86400
+                + self.hour as u64 * 3600
+                + self.minute as u64 * 60
+                + self.second as u64,
+        )
+    }
+
+    /// 0 is Sunday, as with cron
+    pub fn weekday(&self) -> u8 {
+        // Sakamoto's method
+        const OFFSETS: [u32; 12] = [0, 3, 2, 5, 0, 3, 5, 1, 4, 6, 2, 4];
+
+        let year = (self.year as u32).saturating_sub(if self.month < 3 { 1 } else { 0 });
+
+        ((year + year / 4 - year / 100
+            + year / 400
+            + OFFSETS[(self.month.clamp(1, 12) - 1) as usize]
+            + self.day as u32)
+            % 7) as _
+    }
+
+    pub fn is_leap_year(year: u16) -> bool {
+        year % 4 == 0 && (year % 100 != 0 || year % 400 == 0)
+    }
+}
+
+pub(crate) fn days_in_month(year: u16, month: u8) -> u8 {
+    match month {
+        2 if DateTime::is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// 0 is Sunday; the Unix epoch was a Thursday
+pub(crate) fn weekday(days: u32) -> u8 {
+    ((days + 4) % 7) as _
+}
+
+// Howard Hinnant's algorithms, see http://howardhinnant.github.io/date_algorithms.html,
+// restricted to dates since the Unix epoch
+
+pub(crate) fn days_from_civil(year: u16, month: u8, day: u8) -> Option<u32> {
+    let year = (year as u32).checked_sub(if month <= 2 { 1 } else { 0 })?;
+    let month = month as u32;
+
+    let era = year / 400;
+    let year_of_era = year - era * 400;
+    let day_of_year =
+        (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day as u32 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    (era * 146097 + day_of_era).checked_sub(719468)
+}
+
+pub(crate) fn civil_from_days(days: u32) -> (u16, u8, u8) {
+    let days = days + 719468;
+    let era = days / 146097;
+    let day_of_era = days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    (year as _, month as _, day as _)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn civil_days() {
+        assert_eq!(days_from_civil(1970, 1, 1), Some(0));
+        assert_eq!(days_from_civil(2000, 3, 1), Some(11017));
+        assert_eq!(days_from_civil(1969, 12, 31), None);
+        assert_eq!(days_from_civil(0, 1, 1), None);
+
+        for days in [0, 59, 11016, 11017, 19782, 2_932_896] {
+            let (year, month, day) = civil_from_days(days);
+
+            assert_eq!(days_from_civil(year, month, day), Some(days));
+        }
+
+        assert_eq!(civil_from_days(19782), (2024, 2, 29));
+    }
+
+    #[test]
+    fn date_time() {
+        let date = DateTime::from_unix(784111777);
+
+        assert_eq!(
+            date,
+            DateTime {
+                year: 1994,
+                month: 11,
+                day: 6,
+                hour: 8,
+                minute: 49,
+                second: 37,
+            }
+        );
+        assert_eq!(date.weekday(), 0);
+        assert_eq!(date.to_unix(), Some(784111777));
+
+        assert_eq!(DateTime::from_unix(u64::MAX).year, 65535);
+        assert_eq!(
+            DateTime {
+                month: 2,
+                day: 30,
+                ..date
+            }
+            .to_unix(),
+            None
+        );
+    }
+}
@@ -0,0 +1,70 @@
+use core::fmt::Debug;
+use core::time::Duration;
+
+#[cfg(feature = "use_serde")]
+use serde::{Deserialize, Serialize};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub enum RestartReason {
+    Requested,
+    FactoryReset,
+    Update,
+    Watchdog,
+    Other,
+}
+
+pub trait SystemControl {
+    type Error: Debug;
+
+    /// Only returns in case the restart failed
+    fn restart(&mut self, reason: RestartReason) -> Self::Error;
+
+    /// Restarts after `delay`, giving the application a chance to finish what it is doing.
+    /// Scheduling a new restart replaces the previously scheduled one.
+    fn schedule_restart(
+        &mut self,
+        reason: RestartReason,
+        delay: Duration,
+    ) -> Result<(), Self::Error>;
+    fn cancel_scheduled_restart(&mut self) -> Result<(), Self::Error>;
+
+    /// Only returns in case entering deep sleep failed
+    fn deep_sleep(&mut self, duration: Duration) -> Self::Error;
+
+    /// Only returns in case the shutdown failed
+    fn shutdown(&mut self) -> Self::Error;
+}
+
+impl<S> SystemControl for &mut S
+where
+    S: SystemControl,
+{
+    type Error = S::Error;
+
+    fn restart(&mut self, reason: RestartReason) -> Self::Error {
+        (*self).restart(reason)
+    }
+
+    fn schedule_restart(
+        &mut self,
+        reason: RestartReason,
+        delay: Duration,
+    ) -> Result<(), Self::Error> {
+        (*self).schedule_restart(reason, delay)
+    }
+
+    fn cancel_scheduled_restart(&mut self) -> Result<(), Self::Error> {
+        (*self).cancel_scheduled_restart()
+    }
+
+    fn deep_sleep(&mut self, duration: Duration) -> Self::Error {
+        (*self).deep_sleep(duration)
+    }
+
+    fn shutdown(&mut self) -> Self::Error {
+        (*self).shutdown()
+    }
+}
@@ -0,0 +1,167 @@
+use core::convert::Infallible;
+use core::fmt::Debug;
+use core::time::Duration;
+
+#[cfg(feature = "use_serde")]
+use serde::{Deserialize, Serialize};
+
+/// When a batch of samples is flushed: whichever limit is reached first
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub struct BatchPolicy {
+    pub max_samples: usize,
+    /// Counted from the first sample of the batch
+    pub max_age: Duration,
+    /// The size of the encoded samples, before compression
+    pub max_bytes: usize,
+}
+
+impl Default for BatchPolicy {
+    fn default() -> Self {
+        Self {
+            max_samples: 32,
+            max_age: Duration::from_secs(60),
+            max_bytes: 1024,
+        }
+    }
+}
+
+/// A batch of samples, each encoded with the codec of the pipeline and prefixed with its length as a varint
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Batch<'a> {
+    pub data: &'a [u8],
+    pub samples: usize,
+    /// Of the individual samples
+    pub content_type: &'static str,
+    /// `None` if the batch is not compressed
+    pub content_encoding: Option<&'static str>,
+}
+
+/// Where batches are delivered, e.g. an MQTT topic, an HTTP endpoint or a log file
+pub trait Sink {
+    type Error: Debug;
+
+    fn send(&mut self, batch: &Batch<'_>) -> Result<(), Self::Error>;
+}
+
+impl<S> Sink for &mut S
+where
+    S: Sink,
+{
+    type Error = S::Error;
+
+    fn send(&mut self, batch: &Batch<'_>) -> Result<(), Self::Error> {
+        (*self).send(batch)
+    }
+}
+
+/// A compression algorithm, e.g. heatshrink or deflate via miniz
+pub trait Compressor {
+    type Error: Debug;
+
+    /// The `Content-Encoding` of the compressed batches, or `None` if they are not compressed
+    fn content_encoding(&self) -> Option<&'static str>;
+
+    /// Returns the length of the compressed data
+    fn compress(&mut self, input: &[u8], output: &mut [u8]) -> Result<usize, Self::Error>;
+}
+
+impl<C> Compressor for &mut C
+where
+    C: Compressor,
+{
+    type Error = C::Error;
+
+    fn content_encoding(&self) -> Option<&'static str> {
+        (**self).content_encoding()
+    }
+
+    fn compress(&mut self, input: &[u8], output: &mut [u8]) -> Result<usize, Self::Error> {
+        (*self).compress(input, output)
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default)]
+pub struct NoCompression;
+
+impl Compressor for NoCompression {
+    type Error = &'static str;
+
+    fn content_encoding(&self) -> Option<&'static str> {
+        None
+    }
+
+    fn compress(&mut self, input: &[u8], output: &mut [u8]) -> Result<usize, Self::Error> {
+        output
+            .get_mut(..input.len())
+            .ok_or("Buffer too small")?
+            .copy_from_slice(input);
+
+        Ok(input.len())
+    }
+}
+
+/// A FIFO of the batches which could not be delivered, e.g. persisted to flash
+pub trait Backlog {
+    type Error: Debug;
+
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns `false` if the batch was dropped
+    fn push(&mut self, batch: &[u8]) -> Result<bool, Self::Error>;
+
+    fn front<'b>(&self, buf: &'b mut [u8]) -> Result<Option<&'b [u8]>, Self::Error>;
+
+    fn pop(&mut self) -> Result<(), Self::Error>;
+}
+
+impl<B> Backlog for &mut B
+where
+    B: Backlog,
+{
+    type Error = B::Error;
+
+    fn len(&self) -> usize {
+        (**self).len()
+    }
+
+    fn push(&mut self, batch: &[u8]) -> Result<bool, Self::Error> {
+        (*self).push(batch)
+    }
+
+    fn front<'b>(&self, buf: &'b mut [u8]) -> Result<Option<&'b [u8]>, Self::Error> {
+        (**self).front(buf)
+    }
+
+    fn pop(&mut self) -> Result<(), Self::Error> {
+        (*self).pop()
+    }
+}
+
+/// No backlog: batches which cannot be delivered are dropped
+impl Backlog for () {
+    type Error = Infallible;
+
+    fn len(&self) -> usize {
+        0
+    }
+
+    fn push(&mut self, _batch: &[u8]) -> Result<bool, Self::Error> {
+        Ok(false)
+    }
+
+    fn front<'b>(&self, _buf: &'b mut [u8]) -> Result<Option<&'b [u8]>, Self::Error> {
+        Ok(None)
+    }
+
+    fn pop(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
@@ -0,0 +1,748 @@
+use core::fmt::{self, Write as _};
+use core::str::{self, FromStr};
+
+#[cfg(feature = "use_serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::crypto::der;
+use crate::crypto::{Digest, KeyHandle, Sha256};
+use crate::ipv4::IpAddr;
+use crate::sys_time;
+use crate::utils::codec::{base64, hex};
+
+pub mod session;
+
+pub const MAX_NAME_LEN: usize = 128;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub enum X509Format {
+    Pem,
+    Der,
+}
+
+/// A certificate or private key, in the format expected by the TLS backend:
+/// either NUL-terminated PEM, or DER.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub struct X509<'a>(&'a [u8]);
+
+impl<'a> X509<'a> {
+    /// `bytes` must include the terminating NUL character, as most TLS backends expect it
+    pub const fn pem_until_nul(bytes: &'a [u8]) -> Self {
+        if bytes.is_empty() || bytes[bytes.len() - 1] != 0 {
+            panic!("PEM data is not NUL-terminated");
+        }
+
+        Self(bytes)
+    }
+
+    pub const fn der(bytes: &'a [u8]) -> Self {
+        Self(bytes)
+    }
+
+    pub const fn data(&self) -> &'a [u8] {
+        self.0
+    }
+
+    /// Detects whether `bytes` is DER or PEM, e.g. for certificates uploaded through a configuration UI
+    pub fn sniff(bytes: &'a [u8]) -> Result<Self, &'static str> {
+        if bytes.first() == Some(&der::SEQUENCE) {
+            Ok(Self::der(bytes))
+        } else if bytes
+            .iter()
+            .position(|byte| !byte.is_ascii_whitespace())
+            .map(|start| bytes[start..].starts_with(b"-----BEGIN "))
+            .unwrap_or(false)
+        {
+            if bytes.last() == Some(&0) {
+                Ok(Self(bytes))
+            } else {
+                Err("PEM data is not NUL-terminated")
+            }
+        } else {
+            Err("Neither PEM nor DER data")
+        }
+    }
+
+    pub fn format(&self) -> X509Format {
+        if self.0.first() == Some(&der::SEQUENCE) {
+            X509Format::Der
+        } else {
+            X509Format::Pem
+        }
+    }
+
+    /// Returns the DER encoding; PEM data is decoded into `buf`, and only its first block is considered
+    pub fn to_der<'b>(&'b self, buf: &'b mut [u8]) -> Result<&'b [u8], &'static str> {
+        if self.format() == X509Format::Der {
+            return Ok(self.0);
+        }
+
+        let pem = str::from_utf8(self.0.strip_suffix(&[0]).unwrap_or(self.0))
+            .map_err(|_| "Invalid PEM data")?;
+
+        let body = pem
+            .split_once("-----BEGIN ")
+            .and_then(|(_, rest)| rest.split_once('\n'))
+            .and_then(|(_, rest)| rest.split_once("-----END "))
+            .map(|(body, _)| body.trim())
+            .ok_or("Invalid PEM data")?;
+
+        let len =
+            base64::decode(body, base64::STANDARD, buf).ok_or("Invalid or too long PEM data")?;
+
+        Ok(&buf[..len])
+    }
+
+    /// The SHA-256 fingerprint of the DER encoding, as shown by browsers and `openssl x509 -fingerprint -sha256`
+    pub fn fingerprint<H>(&self, mut hasher: H, buf: &mut [u8]) -> Result<Fingerprint, &'static str>
+    where
+        H: Sha256,
+    {
+        hasher.update(self.to_der(buf)?);
+
+        Ok(Fingerprint(hasher.finish()))
+    }
+
+    /// The SHA-256 hash of the `SubjectPublicKeyInfo`, as used for public-key pinning; unlike the
+    /// fingerprint, it survives the renewal of a certificate with the same key pair
+    pub fn public_key_pin<H>(
+        &self,
+        mut hasher: H,
+        buf: &mut [u8],
+    ) -> Result<Fingerprint, &'static str>
+    where
+        H: Sha256,
+    {
+        let certificate = Certificate::parse(self.to_der(buf)?).ok_or("Invalid certificate")?;
+
+        hasher.update(certificate.public_key_info);
+
+        Ok(Fingerprint(hasher.finish()))
+    }
+
+    /// Extracts the details of a certificate worth displaying; `buf` is only used for PEM data
+    pub fn info<H>(&self, mut hasher: H, buf: &mut [u8]) -> Result<CertificateInfo, &'static str>
+    where
+        H: Sha256,
+    {
+        let der = self.to_der(buf)?;
+
+        hasher.update(der);
+        let fingerprint = Fingerprint(hasher.finish());
+
+        let certificate = Certificate::parse(der).ok_or("Invalid certificate")?;
+
+        let mut subject = heapless::String::new();
+        write_name(&mut subject, certificate.subject).map_err(|_| "Subject too long")?;
+
+        let mut issuer = heapless::String::new();
+        write_name(&mut issuer, certificate.issuer).map_err(|_| "Issuer too long")?;
+
+        Ok(CertificateInfo {
+            subject,
+            issuer,
+            not_before: certificate.not_before,
+            not_after: certificate.not_after,
+            fingerprint,
+        })
+    }
+}
+
+/// A SHA-256 fingerprint, displayed as colon-separated uppercase hex pairs
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub struct Fingerprint(pub Digest);
+
+impl FromStr for Fingerprint {
+    type Err = &'static str;
+
+    /// Accepts hex digits with or without colon separators
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut digest = [0_u8; 32];
+
+        match hex::decode(s, &mut digest) {
+            Some(32) => Ok(Self(digest)),
+            _ => Err("Invalid SHA-256 fingerprint"),
+        }
+    }
+}
+
+impl fmt::Display for Fingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, byte) in self.0.iter().enumerate() {
+            if index > 0 {
+                f.write_char(':')?;
+            }
+
+            write!(f, "{byte:02X}")?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub struct CertificateInfo {
+    /// E.g. `CN=device-a1b2c3, O=ACME`
+    pub subject: heapless::String<MAX_NAME_LEN>,
+    pub issuer: heapless::String<MAX_NAME_LEN>,
+    /// Seconds since the UNIX epoch
+    pub not_before: u64,
+    /// Seconds since the UNIX epoch
+    pub not_after: u64,
+    pub fingerprint: Fingerprint,
+}
+
+/// The parts of a DER-encoded X.509 certificate used by this crate
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Certificate<'a> {
+    /// The raw `TBSCertificate`, over which the signature is computed
+    pub tbs: &'a [u8],
+    /// The OID of the signature algorithm
+    pub signature_algorithm: &'a [u8],
+    /// The signature, without the unused bits prefix of the BIT STRING
+    pub signature: &'a [u8],
+    /// The contents of the issuer `Name`
+    pub issuer: &'a [u8],
+    pub not_before: u64,
+    pub not_after: u64,
+    /// The contents of the subject `Name`
+    pub subject: &'a [u8],
+    /// The raw `SubjectPublicKeyInfo`
+    pub public_key_info: &'a [u8],
+}
+
+impl<'a> Certificate<'a> {
+    pub fn parse(data: &'a [u8]) -> Option<Self> {
+        let (certificate, _) = der::expect(data, der::SEQUENCE)?;
+
+        let (tbs, rest) = der::expect(certificate.value, der::SEQUENCE)?;
+        let (algorithm, rest) = der::expect(rest, der::SEQUENCE)?;
+        let (signature, _) = der::expect(rest, der::BIT_STRING)?;
+
+        let (signature_algorithm, _) = der::expect(algorithm.value, der::OID)?;
+
+        let mut fields = tbs.value;
+
+        // The optional, explicitly tagged version
+        let (first, rest) = der::read(fields)?;
+        if first.tag == 0xa0 {
+            fields = rest;
+        }
+
+        // Serial number and signature algorithm
+        let (_, fields) = der::expect(fields, der::INTEGER)?;
+        let (_, fields) = der::expect(fields, der::SEQUENCE)?;
+
+        let (issuer, fields) = der::expect(fields, der::SEQUENCE)?;
+        let (validity, fields) = der::expect(fields, der::SEQUENCE)?;
+        let (subject, fields) = der::expect(fields, der::SEQUENCE)?;
+        let (public_key_info, _) = der::expect(fields, der::SEQUENCE)?;
+
+        let (not_before, rest) = der::read(validity.value)?;
+        let (not_after, _) = der::read(rest)?;
+
+        Some(Self {
+            tbs: tbs.raw,
+            signature_algorithm: signature_algorithm.value,
+            signature: signature.value.strip_prefix(&[0])?,
+            issuer: issuer.value,
+            not_before: parse_time(not_before)?,
+            not_after: parse_time(not_after)?,
+            subject: subject.value,
+            public_key_info: public_key_info.raw,
+        })
+    }
+}
+
+/// Formats a `Name` in the usual `CN=..., O=...` form
+fn write_name(w: &mut impl fmt::Write, name: &[u8]) -> fmt::Result {
+    let mut rdns = name;
+    let mut first = true;
+
+    while !rdns.is_empty() {
+        let (rdn, rest) = der::read(rdns).ok_or(fmt::Error)?;
+        rdns = rest;
+
+        let mut attributes = rdn.value;
+
+        while !attributes.is_empty() {
+            let (attribute, rest) = der::expect(attributes, der::SEQUENCE).ok_or(fmt::Error)?;
+            attributes = rest;
+
+            let (oid, rest) = der::expect(attribute.value, der::OID).ok_or(fmt::Error)?;
+            let (value, _) = der::read(rest).ok_or(fmt::Error)?;
+
+            if !first {
+                w.write_str(", ")?;
+            }
+
+            first = false;
+
+            match oid.value {
+                [0x55, 0x04, 0x03] => w.write_str("CN")?,
+                [0x55, 0x04, 0x06] => w.write_str("C")?,
+                [0x55, 0x04, 0x07] => w.write_str("L")?,
+                [0x55, 0x04, 0x08] => w.write_str("ST")?,
+                [0x55, 0x04, 0x0a] => w.write_str("O")?,
+                [0x55, 0x04, 0x0b] => w.write_str("OU")?,
+                oid => write_oid(w, oid)?,
+            }
+
+            w.write_char('=')?;
+            w.write_str(str::from_utf8(value.value).unwrap_or("?"))?;
+        }
+    }
+
+    Ok(())
+}
+
+fn write_oid(w: &mut impl fmt::Write, oid: &[u8]) -> fmt::Result {
+    let mut arc = 0_u64;
+    let mut first = true;
+
+    for byte in oid {
+        arc = (arc << 7) | (*byte & 0x7f) as u64;
+
+        if *byte & 0x80 == 0 {
+            if first {
+                // The first two arcs are encoded together
+                let root = (arc / 40).min(2);
+                write!(w, "{}.{}", root, arc - root * 40)?;
+
+                first = false;
+            } else {
+                write!(w, ".{arc}")?;
+            }
+
+            arc = 0;
+        }
+    }
+
+    Ok(())
+}
+
+/// Converts an `UTCTime` or a `GeneralizedTime` to seconds since the UNIX epoch
+fn parse_time(time: der::Tlv<'_>) -> Option<u64> {
+    let digits = time.value.strip_suffix(b"Z")?;
+
+    let (year, rest) = match time.tag {
+        0x17 => {
+            let year = number(digits.get(..2)?)?;
+
+            (
+                if year < 50 { 2000 + year } else { 1900 + year },
+                &digits[2..],
+            )
+        }
+        0x18 => (number(digits.get(..4)?)?, &digits[4..]),
+        _ => return None,
+    };
+
+    if rest.len() != 10 {
+        return None;
+    }
+
+    let month = number(&rest[0..2])?;
+    let day = number(&rest[2..4])?;
+    let hours = number(&rest[4..6])?;
+    let minutes = number(&rest[6..8])?;
+    let seconds = number(&rest[8..10])?;
+
+    if !(1..=12).contains(&month)
+        || !(1..=31).contains(&day)
+        || hours > 23
+        || minutes > 59
+        || seconds > 60
+    {
+        return None;
+    }
+
+    let days = sys_time::days_from_civil(year as u16, month as u8, day as u8)?;
+
+    Some(days as u64 * 86400 + hours * 3600 + minutes * 60 + seconds)
+}
+
+fn number(digits: &[u8]) -> Option<u64> {
+    digits.iter().try_fold(0, |number, digit| {
+        if digit.is_ascii_digit() {
+            Some(number * 10 + (digit - b'0') as u64)
+        } else {
+            None
+        }
+    })
+}
+
+/// A pre-shared key and the identity it is known by, for the `TLS_PSK_*` cipher suites
+#[derive(Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Hash))]
+pub struct Psk<'a> {
+    pub identity: &'a [u8],
+    /// Most backends limit the key to 32 bytes
+    pub key: &'a [u8],
+}
+
+impl<'a> fmt::Debug for Psk<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Psk")
+            .field("identity", &self.identity)
+            .finish()
+    }
+}
+
+/// Like `Debug`, leaves the key out
+#[cfg(feature = "defmt")]
+impl<'a> defmt::Format for Psk<'a> {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        defmt::write!(f, "Psk {{ identity: {=[u8]} }}", self.identity)
+    }
+}
+
+/// A custom server certificate check
+pub trait Verifier {
+    /// Called with the server certificate chain (leaf first) and whether it passed the
+    /// verification against the configured CAs and pins; returns whether to trust the server
+    fn verify(&self, chain: &[X509<'_>], trusted: bool) -> bool;
+}
+
+impl<F> Verifier for F
+where
+    F: Fn(&[X509<'_>], bool) -> bool,
+{
+    fn verify(&self, chain: &[X509<'_>], trusted: bool) -> bool {
+        self(chain, trusted)
+    }
+}
+
+/// How the validity periods (`notBefore` and `notAfter`) of the server certificates are checked.
+///
+/// Devices without a battery-backed RTC boot with a 1970 clock, so that strict checks fail until the time
+/// is synchronized, which in turn might need a TLS connection. The default is strict.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub struct ValidityPolicy {
+    /// Certificates are accepted up to this many seconds before `notBefore` or after `notAfter`, e.g. to
+    /// cope with a clock set coarsely or drifting
+    pub tolerated_skew_secs: u32,
+    /// Certificates are accepted regardless of their validity periods while the clock is not set.
+    ///
+    /// This opens an insecure window at boot, during which expired certificates are accepted too; backends
+    /// report each connection accepted this way with `Validity::Unchecked`.
+    pub insecure_until_time_set: bool,
+}
+
+impl ValidityPolicy {
+    pub fn is_strict(&self) -> bool {
+        self.tolerated_skew_secs == 0 && !self.insecure_until_time_set
+    }
+}
+
+/// The outcome of `ClientConfiguration::check_validity`, which backends post as a `TlsWarning` when the
+/// server is accepted only thanks to the `ValidityPolicy`
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub enum Validity {
+    Valid,
+    /// Outside of the validity period by this many seconds, within the tolerated skew
+    Skewed(u64),
+    /// Not checked, as the clock is not set: the connection is in the insecure window
+    Unchecked,
+    /// The clock is not set and the policy does not allow the insecure window
+    ClockNotSet,
+    NotYetValid,
+    Expired,
+}
+
+impl Validity {
+    pub fn is_accepted(&self) -> bool {
+        matches!(self, Self::Valid | Self::Skewed(_) | Self::Unchecked)
+    }
+
+    /// Whether the server is accepted only thanks to the `ValidityPolicy`
+    pub fn is_relaxed(&self) -> bool {
+        matches!(self, Self::Skewed(_) | Self::Unchecked)
+    }
+}
+
+/// What the name in the server certificate is verified against
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum HostnameVerification<'a> {
+    /// The SNI name, i.e. `server_name`, or else the host being connected to
+    Sni,
+    /// A fixed name, e.g. that of the certificate of a local broker connected to by IP address; the SNI
+    /// name is not affected
+    Name(&'a str),
+    /// Not verified, so that any server with a certificate from a trusted CA can impersonate the actual
+    /// one; backends post `TlsWarning::HostnameNotVerified` for each connection
+    Disabled,
+}
+
+impl<'a> Default for HostnameVerification<'a> {
+    fn default() -> Self {
+        Self::Sni
+    }
+}
+
+/// Posted by backends over their `event_bus::EventBus<TlsWarning>` implementation for each connection
+/// accepted only thanks to a relaxed setting of the `ClientConfiguration`, so that applications can
+/// report such settings left in place in production
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub enum TlsWarning {
+    /// A relaxed `Validity`
+    Validity(Validity),
+    /// With `HostnameVerification::Disabled`
+    HostnameNotVerified,
+}
+
+/// A reference to a `Verifier`; two of them are equal when they point to the same verifier
+#[derive(Copy, Clone)]
+pub struct CustomVerifier<'a>(pub &'a dyn Verifier);
+
+impl<'a> PartialEq for CustomVerifier<'a> {
+    fn eq(&self, other: &Self) -> bool {
+        core::ptr::eq(
+            self.0 as *const dyn Verifier as *const u8,
+            other.0 as *const dyn Verifier as *const u8,
+        )
+    }
+}
+
+impl<'a> Eq for CustomVerifier<'a> {}
+
+impl<'a> fmt::Debug for CustomVerifier<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("CustomVerifier")
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ClientConfiguration<'a> {
+    pub ca_certificate: Option<X509<'a>>,
+    /// Use the backend's global CA store instead of (or in addition to) `ca_certificate`
+    pub use_global_ca_store: bool,
+    pub client_certificate: Option<X509<'a>>,
+    pub private_key: Option<X509<'a>>,
+    /// A private key held by the crypto provider (e.g. a secure element), used instead of `private_key`
+    pub private_key_handle: Option<KeyHandle>,
+    pub alpn_protocols: &'a [&'a str],
+    /// The SNI name; defaults to the host being connected to
+    pub server_name: Option<&'a str>,
+    /// When not empty, the server certificate must have one of these SHA-256 fingerprints
+    pub pinned_certificates: &'a [Fingerprint],
+    /// When not empty, one of the certificates of the server chain must have one of these
+    /// public-key pins (see `X509::public_key_pin`). Pinning the key of a private CA this way
+    /// keeps working when the server certificates are renewed or when public CAs rotate.
+    pub pinned_public_keys: &'a [Fingerprint],
+    /// Offer the PSK cipher suites with this key; the certificates can then be omitted
+    pub psk: Option<Psk<'a>>,
+    /// Resume previous sessions, which saves a full handshake on every reconnect
+    pub session_resumption: Option<session::SessionResumption>,
+    /// Has the final say on whether the server is trusted
+    #[cfg_attr(feature = "defmt", defmt(Debug2Format))]
+    pub verifier: Option<CustomVerifier<'a>>,
+    /// How the validity periods of the server certificates are checked
+    pub validity: ValidityPolicy,
+    pub hostname_verification: HostnameVerification<'a>,
+}
+
+impl<'a> ClientConfiguration<'a> {
+    /// The SNI name when connecting to `host`: `server_name`, or else `host` unless it is an IP address,
+    /// which SNI does not allow
+    pub fn sni_name<'b>(&'b self, host: &'b str) -> Option<&'b str> {
+        match self.server_name {
+            Some(server_name) => Some(server_name),
+            None if host.parse::<IpAddr>().is_ok() => None,
+            None => Some(host),
+        }
+    }
+
+    /// The name the server certificate is verified against when connecting to `host`, or `None` if it is
+    /// not verified.
+    ///
+    /// With `HostnameVerification::Sni`, a `host` which is an IP address is verified against the IP
+    /// addresses of the certificate, unless `server_name` is set.
+    pub fn verified_hostname<'b>(&'b self, host: &'b str) -> Option<&'b str> {
+        match self.hostname_verification {
+            HostnameVerification::Sni => Some(self.server_name.unwrap_or(host)),
+            HostnameVerification::Name(name) => Some(name),
+            HostnameVerification::Disabled => None,
+        }
+    }
+
+    /// Checks the validity periods of the certificates of the server `chain` per `validity`, at `now`
+    /// (seconds since the Unix epoch), or `None` if the clock is not set yet; for backends to call from
+    /// their certificate verification callback, with their own validity checks disabled, unless the policy
+    /// is strict.
+    ///
+    /// The worst outcome over the chain is returned; `buf` is used to decode PEM certificates.
+    pub fn check_validity(
+        &self,
+        chain: &[X509<'_>],
+        now: Option<u64>,
+        buf: &mut [u8],
+    ) -> Result<Validity, &'static str> {
+        let now = match now {
+            Some(now) => now,
+            None if self.validity.insecure_until_time_set => return Ok(Validity::Unchecked),
+            None => return Ok(Validity::ClockNotSet),
+        };
+
+        let tolerance = self.validity.tolerated_skew_secs as u64;
+        let mut validity = Validity::Valid;
+
+        for certificate in chain {
+            let certificate =
+                Certificate::parse(certificate.to_der(buf)?).ok_or("Invalid certificate")?;
+
+            let skew = if now < certificate.not_before {
+                certificate.not_before - now
+            } else if now > certificate.not_after {
+                now - certificate.not_after
+            } else {
+                continue;
+            };
+
+            if skew > tolerance {
+                return Ok(if now < certificate.not_before {
+                    Validity::NotYetValid
+                } else {
+                    Validity::Expired
+                });
+            }
+
+            validity = match validity {
+                Validity::Skewed(worst) => Validity::Skewed(worst.max(skew)),
+                _ => Validity::Skewed(skew),
+            };
+        }
+
+        Ok(validity)
+    }
+
+    /// Checks the server certificate `chain` (leaf first) against the pins and the custom verifier;
+    /// for backends to call from their certificate verification callback.
+    ///
+    /// `trusted` is the outcome of the backend's own verification against the configured CAs,
+    /// and `buf` is used to decode PEM certificates.
+    pub fn verify_peer<H>(
+        &self,
+        chain: &[X509<'_>],
+        trusted: bool,
+        buf: &mut [u8],
+    ) -> Result<bool, &'static str>
+    where
+        H: Sha256 + Default,
+    {
+        let mut trusted = trusted;
+
+        if trusted && !self.pinned_certificates.is_empty() {
+            let leaf = chain.first().ok_or("Empty certificate chain")?;
+            let fingerprint = leaf.fingerprint(H::default(), buf)?;
+
+            trusted = self.pinned_certificates.contains(&fingerprint);
+        }
+
+        if trusted && !self.pinned_public_keys.is_empty() {
+            trusted = false;
+
+            for certificate in chain {
+                let pin = certificate.public_key_pin(H::default(), buf)?;
+
+                if self.pinned_public_keys.contains(&pin) {
+                    trusted = true;
+                    break;
+                }
+            }
+        }
+
+        Ok(match self.verifier {
+            Some(verifier) => verifier.0.verify(chain, trusted),
+            None => trusted,
+        })
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ServerConfiguration<'a> {
+    pub certificate: Option<X509<'a>>,
+    pub private_key: Option<X509<'a>>,
+    /// A private key held by the crypto provider (e.g. a secure element), used instead of `private_key`
+    pub private_key_handle: Option<KeyHandle>,
+    /// When set, clients must present a certificate signed by this CA
+    pub client_ca_certificate: Option<X509<'a>>,
+    pub alpn_protocols: &'a [&'a str],
+    /// Sent to clients to help them select which of their keys to use
+    pub psk_identity_hint: Option<&'a [u8]>,
+    /// The keys of the clients allowed to connect with the PSK cipher suites
+    pub psks: &'a [Psk<'a>],
+}
+
+impl<'a> ServerConfiguration<'a> {
+    /// The key of the client identifying itself as `identity`, for backends to call during the handshake
+    pub fn psk(&self, identity: &[u8]) -> Option<&Psk<'a>> {
+        self.psks.iter().find(|psk| psk.identity == identity)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time(tag: u8, value: &str) -> Option<u64> {
+        parse_time(der::Tlv {
+            tag,
+            value: value.as_bytes(),
+            raw: &[],
+        })
+    }
+
+    #[test]
+    fn utc_time() {
+        assert_eq!(time(0x17, "700101000000Z"), Some(0));
+        assert_eq!(time(0x17, "491231235959Z"), Some(2524607999));
+        assert_eq!(time(0x17, "240229120000Z"), Some(1709208000));
+        // Before the UNIX epoch
+        assert_eq!(time(0x17, "500101000000Z"), None);
+    }
+
+    #[test]
+    fn generalized_time() {
+        assert_eq!(time(0x18, "20380119031408Z"), Some(2147483648));
+        assert_eq!(time(0x18, "99991231235959Z"), Some(253402300799));
+        assert_eq!(time(0x18, "00000101000000Z"), None);
+        assert_eq!(time(0x18, "00000228000000Z"), None);
+    }
+
+    #[test]
+    fn invalid_time() {
+        assert_eq!(time(0x17, "700101000000"), None);
+        assert_eq!(time(0x17, "7001010000Z"), None);
+        assert_eq!(time(0x17, "701301000000Z"), None);
+        assert_eq!(time(0x17, "700100000000Z"), None);
+        assert_eq!(time(0x17, "700101240000Z"), None);
+        assert_eq!(time(0x17, "70010100006aZ"), None);
+        assert_eq!(time(0x04, "700101000000Z"), None);
+    }
+}
@@ -0,0 +1,219 @@
+use core::fmt::{Debug, Write as _};
+use core::time::Duration;
+
+#[cfg(feature = "use_serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::storage::RawStorage;
+use crate::sys_time::SystemTime;
+
+/// The expiry (8 bytes) followed by the server name
+const MAX_META_LEN: usize = 8 + 255;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub struct SessionResumption {
+    /// Use RFC 5077 session tickets rather than only session IDs
+    pub tickets: bool,
+    /// How many servers to keep sessions for
+    pub cache_size: u8,
+    /// Sessions older than this are not offered; servers usually accept them for a few hours at most
+    pub lifetime: Duration,
+}
+
+impl Default for SessionResumption {
+    fn default() -> Self {
+        Self {
+            tickets: true,
+            cache_size: 4,
+            lifetime: Duration::from_secs(2 * 60 * 60),
+        }
+    }
+}
+
+/// Where the TLS backend keeps the sessions it can resume,
+/// as opaque blobs serialized by the backend itself.
+pub trait SessionCache {
+    type Error: Debug;
+
+    fn load<'b>(
+        &self,
+        server_name: &str,
+        buf: &'b mut [u8],
+    ) -> Result<Option<&'b [u8]>, Self::Error>;
+
+    fn store(&mut self, server_name: &str, session: &[u8]) -> Result<(), Self::Error>;
+
+    /// Called when the server refused to resume the session
+    fn remove(&mut self, server_name: &str) -> Result<(), Self::Error>;
+}
+
+impl<C> SessionCache for &mut C
+where
+    C: SessionCache,
+{
+    type Error = C::Error;
+
+    fn load<'b>(
+        &self,
+        server_name: &str,
+        buf: &'b mut [u8],
+    ) -> Result<Option<&'b [u8]>, Self::Error> {
+        (**self).load(server_name, buf)
+    }
+
+    fn store(&mut self, server_name: &str, session: &[u8]) -> Result<(), Self::Error> {
+        (*self).store(server_name, session)
+    }
+
+    fn remove(&mut self, server_name: &str) -> Result<(), Self::Error> {
+        (*self).remove(server_name)
+    }
+}
+
+/// A `SessionCache` persisted in storage, so that sessions survive deep sleep and reboots.
+///
+/// Each server name maps to one of `cache_size` slots, stored as `tls_meta_N` (expiry and
+/// server name) and `tls_sess_N` (the session itself); storing the session of a server
+/// evicts the session of any other server mapped to the same slot.
+///
+/// Expiry is based on `time`, so the system time should be synchronized before sessions are stored.
+pub struct StorageSessionCache<S, T> {
+    storage: S,
+    time: T,
+    resumption: SessionResumption,
+}
+
+impl<S, T> StorageSessionCache<S, T>
+where
+    S: RawStorage,
+    T: SystemTime,
+{
+    pub const fn new(storage: S, time: T, resumption: SessionResumption) -> Self {
+        Self {
+            storage,
+            time,
+            resumption,
+        }
+    }
+
+    pub fn clear(&mut self) -> Result<(), S::Error> {
+        for slot in 0..self.resumption.cache_size {
+            self.storage.remove(&slot_name("tls_meta", slot))?;
+            self.storage.remove(&slot_name("tls_sess", slot))?;
+        }
+
+        Ok(())
+    }
+
+    pub fn release(self) -> (S, T) {
+        (self.storage, self.time)
+    }
+
+    /// The slot of `server_name`, along with the expiry of the session of that server if the slot holds one
+    fn lookup(&self, server_name: &str) -> Result<Option<(u8, Option<u64>)>, S::Error> {
+        if self.resumption.cache_size == 0 {
+            return Ok(None);
+        }
+
+        // FNV-1a
+        let hash = server_name.bytes().fold(0x811c9dc5_u32, |hash, byte| {
+            (hash ^ byte as u32).wrapping_mul(0x01000193)
+        });
+
+        let slot = (hash % self.resumption.cache_size as u32) as u8;
+
+        let mut buf = [0_u8; MAX_META_LEN];
+
+        let expiry = match self
+            .storage
+            .get_raw(&slot_name("tls_meta", slot), &mut buf)?
+        {
+            Some(meta) if meta.len() >= 8 && &meta[8..] == server_name.as_bytes() => {
+                let mut expiry = [0_u8; 8];
+                expiry.copy_from_slice(&meta[..8]);
+
+                Some(u64::from_le_bytes(expiry))
+            }
+            _ => None,
+        };
+
+        Ok(Some((slot, expiry)))
+    }
+}
+
+impl<S, T> SessionCache for StorageSessionCache<S, T>
+where
+    S: RawStorage,
+    T: SystemTime,
+{
+    type Error = S::Error;
+
+    fn load<'b>(
+        &self,
+        server_name: &str,
+        buf: &'b mut [u8],
+    ) -> Result<Option<&'b [u8]>, Self::Error> {
+        match self.lookup(server_name)? {
+            Some((slot, Some(expiry))) if self.time.now().as_secs() < expiry => {
+                let name = slot_name("tls_sess", slot);
+
+                let fits = self
+                    .storage
+                    .len(&name)?
+                    .map(|len| len <= buf.len())
+                    .unwrap_or(false);
+
+                if fits {
+                    self.storage.get_raw(&name, buf)
+                } else {
+                    Ok(None)
+                }
+            }
+            _ => Ok(None),
+        }
+    }
+
+    fn store(&mut self, server_name: &str, session: &[u8]) -> Result<(), Self::Error> {
+        if server_name.len() > MAX_META_LEN - 8 {
+            return Ok(());
+        }
+
+        if let Some((slot, _)) = self.lookup(server_name)? {
+            let expiry = (self.time.now() + self.resumption.lifetime).as_secs();
+
+            let mut meta = [0_u8; MAX_META_LEN];
+            meta[..8].copy_from_slice(&expiry.to_le_bytes());
+            meta[8..8 + server_name.len()].copy_from_slice(server_name.as_bytes());
+
+            // Invalidate the slot first, so that an interrupted update never pairs
+            // the server name with the session of another server
+            self.storage.remove(&slot_name("tls_meta", slot))?;
+            self.storage
+                .set_raw(&slot_name("tls_sess", slot), session)?;
+            self.storage
+                .set_raw(&slot_name("tls_meta", slot), &meta[..8 + server_name.len()])?;
+        }
+
+        Ok(())
+    }
+
+    fn remove(&mut self, server_name: &str) -> Result<(), Self::Error> {
+        if let Some((slot, Some(_))) = self.lookup(server_name)? {
+            self.storage.remove(&slot_name("tls_meta", slot))?;
+            self.storage.remove(&slot_name("tls_sess", slot))?;
+        }
+
+        Ok(())
+    }
+}
+
+fn slot_name(prefix: &str, slot: u8) -> heapless::String<16> {
+    let mut name = heapless::String::new();
+
+    write!(&mut name, "{prefix}_{slot}").unwrap();
+
+    name
+}
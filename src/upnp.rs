@@ -0,0 +1,602 @@
+#[cfg(feature = "experimental")]
+pub mod igd;
+
+use core::fmt;
+use core::iter::once;
+use core::str;
+use core::time::Duration;
+
+use crate::error::{impl_error, ErrorKind};
+use crate::ipv4::{Ipv4Addr, SocketAddrV4};
+use crate::net::udp::UdpSocket;
+use crate::sys_time::{Instant, SystemTime};
+
+pub const SSDP_MULTICAST_ADDR: Ipv4Addr = Ipv4Addr::new(239, 255, 255, 250);
+pub const SSDP_PORT: u16 = 1900;
+
+pub const ST_ALL: &str = "ssdp:all";
+pub const ST_ROOT_DEVICE: &str = "upnp:rootdevice";
+pub const ST_INTERNET_GATEWAY_DEVICE: &str = "urn:schemas-upnp-org:device:InternetGatewayDevice:1";
+
+const MAX_MESSAGE_LEN: usize = 512;
+
+/// The most distinct devices `discover` counts, including those not returned
+pub const MAX_COUNTED_DEVICES: usize = 64;
+
+pub fn ssdp_addr() -> SocketAddrV4 {
+    SocketAddrV4::new(SSDP_MULTICAST_ADDR, SSDP_PORT)
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SearchRequest<'a> {
+    pub search_target: &'a str,
+    /// Maximum wait time in seconds, as requested in the MX header
+    pub mx: u8,
+}
+
+/// The common part of NOTIFY messages and M-SEARCH responses.
+/// For search responses, `notification_type` carries the ST header.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Advertisement<'a> {
+    pub notification_type: &'a str,
+    pub usn: &'a str,
+    pub location: Option<&'a str>,
+    pub server: Option<&'a str>,
+    pub max_age: Option<u32>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SsdpMessage<'a> {
+    Search(SearchRequest<'a>),
+    Alive(Advertisement<'a>),
+    ByeBye(Advertisement<'a>),
+    Response(Advertisement<'a>),
+}
+
+impl<'a> SsdpMessage<'a> {
+    pub fn parse(data: &'a [u8]) -> Result<Self, &'static str> {
+        let data = str::from_utf8(data).map_err(|_| "Message is not valid UTF-8")?;
+
+        let mut lines = data.lines().map(|line| line.trim_end_matches('\r'));
+
+        let start = lines.next().ok_or("Empty message")?;
+
+        let header = |name: &str| {
+            data.lines()
+                .skip(1)
+                .map(|line| line.trim_end_matches('\r'))
+                .take_while(|line| !line.is_empty())
+                .filter_map(|line| line.split_once(':'))
+                .find(|(key, _)| key.trim().eq_ignore_ascii_case(name))
+                .map(|(_, value)| value.trim())
+        };
+
+        let advertisement = |notification_type: &'a str| -> Result<_, &'static str> {
+            Ok(Advertisement {
+                notification_type,
+                usn: header("USN").ok_or("Missing USN header")?,
+                location: header("LOCATION"),
+                server: header("SERVER"),
+                max_age: header("CACHE-CONTROL").and_then(parse_max_age),
+            })
+        };
+
+        if start.starts_with("M-SEARCH ") {
+            if header("MAN").map(|man| man.trim_matches('"')) != Some("ssdp:discover") {
+                return Err("Missing or invalid MAN header");
+            }
+
+            Ok(Self::Search(SearchRequest {
+                search_target: header("ST").ok_or("Missing ST header")?,
+                mx: header("MX")
+                    .and_then(|mx| mx.parse::<u8>().ok())
+                    .unwrap_or(1),
+            }))
+        } else if start.starts_with("NOTIFY ") {
+            let notification_type = header("NT").ok_or("Missing NT header")?;
+
+            match header("NTS") {
+                Some("ssdp:alive") => Ok(Self::Alive(advertisement(notification_type)?)),
+                Some("ssdp:byebye") => Ok(Self::ByeBye(advertisement(notification_type)?)),
+                _ => Err("Missing or unsupported NTS header"),
+            }
+        } else if start.starts_with("HTTP/1.1 200") || start.starts_with("HTTP/1.0 200") {
+            Ok(Self::Response(advertisement(
+                header("ST").ok_or("Missing ST header")?,
+            )?))
+        } else {
+            Err("Unsupported SSDP message")
+        }
+    }
+}
+
+fn parse_max_age(cache_control: &str) -> Option<u32> {
+    cache_control
+        .split(',')
+        .filter_map(|directive| directive.split_once('='))
+        .find(|(key, _)| key.trim().eq_ignore_ascii_case("max-age"))
+        .and_then(|(_, value)| value.trim().parse::<u32>().ok())
+}
+
+pub fn write_search(w: &mut impl fmt::Write, search_target: &str, mx: u8) -> fmt::Result {
+    write!(
+        w,
+        "M-SEARCH * HTTP/1.1\r\nHOST: {SSDP_MULTICAST_ADDR}:{SSDP_PORT}\r\nMAN: \"ssdp:discover\"\r\nMX: {mx}\r\nST: {search_target}\r\n\r\n"
+    )
+}
+
+/// Multicasts an M-SEARCH request; responses should then be collected with `receive_from`,
+/// or by using `discover` instead.
+pub fn search<S>(socket: &mut S, search_target: &str, mx: u8) -> Result<(), SsdpError<S::Error>>
+where
+    S: UdpSocket,
+{
+    let mut message = heapless::String::<MAX_MESSAGE_LEN>::new();
+    write_search(&mut message, search_target, mx).map_err(|_| SsdpError::MessageTooLong)?;
+
+    socket
+        .send_to(message.as_bytes(), ssdp_addr())
+        .map_err(SsdpError::Io)?;
+
+    Ok(())
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct DiscoveredDevice {
+    #[cfg_attr(feature = "defmt", defmt(Debug2Format))]
+    pub addr: SocketAddrV4,
+    pub search_target: heapless::String<128>,
+    pub usn: heapless::String<128>,
+    pub location: heapless::String<128>,
+    pub server: Option<heapless::String<64>>,
+    pub max_age: Option<u32>,
+}
+
+impl DiscoveredDevice {
+    fn new(addr: SocketAddrV4, advertisement: &Advertisement<'_>) -> Option<Self> {
+        let server = match advertisement.server {
+            Some(server) => Some(to_string(server)?),
+            None => None,
+        };
+
+        Some(Self {
+            addr,
+            search_target: to_string(advertisement.notification_type)?,
+            usn: to_string(advertisement.usn)?,
+            location: to_string(advertisement.location?)?,
+            server,
+            max_age: advertisement.max_age,
+        })
+    }
+}
+
+fn to_string<const N: usize>(s: &str) -> Option<heapless::String<N>> {
+    let mut string = heapless::String::new();
+    string.push_str(s).ok()?;
+
+    Some(string)
+}
+
+/// Sends an M-SEARCH request and collects the distinct (by USN) devices which responded within `timeout`.
+///
+/// Responses without a LOCATION header, or with fields which do not fit in `DiscoveredDevice`, are skipped.
+/// Like the other `_n`-style APIs, the second element of the result is the total number of distinct devices
+/// found, which might be larger than `N`, up to `MAX_COUNTED_DEVICES`.
+pub fn discover<S, T, const N: usize>(
+    socket: &mut S,
+    clock: &T,
+    search_target: &str,
+    timeout: Duration,
+) -> Result<(heapless::Vec<DiscoveredDevice, N>, usize), SsdpError<S::Error>>
+where
+    S: UdpSocket,
+    T: SystemTime,
+{
+    let mx = timeout.as_secs().clamp(1, 5) as u8;
+
+    search(socket, search_target, mx)?;
+
+    let deadline = Instant::now(clock) + timeout;
+
+    let mut devices = heapless::Vec::new();
+    // The hashes of the USNs of the devices found, as those not in `devices` are not kept
+    let mut found = heapless::Vec::<u32, MAX_COUNTED_DEVICES>::new();
+
+    let mut buf = [0_u8; MAX_MESSAGE_LEN];
+
+    while let Some(remaining) = deadline.checked_duration_since(Instant::now(clock)) {
+        if remaining.is_zero() {
+            break;
+        }
+
+        let (len, addr) = match socket
+            .receive_from(&mut buf, Some(remaining))
+            .map_err(SsdpError::Io)?
+        {
+            Some(received) => received,
+            None => break,
+        };
+
+        if let Ok(SsdpMessage::Response(advertisement)) = SsdpMessage::parse(&buf[..len]) {
+            if let Some(device) = DiscoveredDevice::new(addr, &advertisement) {
+                if devices
+                    .iter()
+                    .any(|known: &DiscoveredDevice| known.usn == device.usn)
+                {
+                    continue;
+                }
+
+                let hash = usn_hash(&device.usn);
+
+                if devices.len() < N || !found.contains(&hash) {
+                    let _ = found.push(hash);
+                    let _ = devices.push(device);
+                }
+            }
+        }
+    }
+
+    let found = found.len().max(devices.len());
+
+    Ok((devices, found))
+}
+
+/// FNV-1a
+fn usn_hash(usn: &str) -> u32 {
+    usn.bytes().fold(0x811c_9dc5, |hash, byte| {
+        (hash ^ byte as u32).wrapping_mul(0x0100_0193)
+    })
+}
+
+/// A local UPnP root device, as advertised over SSDP.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct LocalDevice<'a> {
+    /// The device UUID, without the `uuid:` prefix
+    pub uuid: &'a str,
+    pub device_type: &'a str,
+    pub service_types: &'a [&'a str],
+    /// URL of the device description document
+    pub location: &'a str,
+    pub server: &'a str,
+    pub max_age: u32,
+}
+
+impl<'a> LocalDevice<'a> {
+    /// The NT values the device needs to advertise, as mandated by the UPnP Device Architecture
+    pub fn targets(&self) -> impl Iterator<Item = Target<'a>> + 'a {
+        once(Target::RootDevice)
+            .chain(once(Target::Uuid(self.uuid)))
+            .chain(once(Target::Type(self.device_type)))
+            .chain(
+                self.service_types
+                    .iter()
+                    .map(|service| Target::Type(service)),
+            )
+    }
+
+    pub fn usn(&self, target: Target<'a>) -> Usn<'a> {
+        Usn {
+            uuid: self.uuid,
+            target,
+        }
+    }
+
+    pub fn write_alive(
+        &self,
+        w: &mut impl fmt::Write,
+        notification_type: &Target<'_>,
+        usn: &Usn<'_>,
+    ) -> fmt::Result {
+        write!(
+            w,
+            "NOTIFY * HTTP/1.1\r\nHOST: {SSDP_MULTICAST_ADDR}:{SSDP_PORT}\r\nCACHE-CONTROL: max-age={}\r\nLOCATION: {}\r\nNT: {notification_type}\r\nNTS: ssdp:alive\r\nSERVER: {}\r\nUSN: {usn}\r\n\r\n",
+            self.max_age, self.location, self.server
+        )
+    }
+
+    pub fn write_byebye(
+        &self,
+        w: &mut impl fmt::Write,
+        notification_type: &Target<'_>,
+        usn: &Usn<'_>,
+    ) -> fmt::Result {
+        write!(
+            w,
+            "NOTIFY * HTTP/1.1\r\nHOST: {SSDP_MULTICAST_ADDR}:{SSDP_PORT}\r\nNT: {notification_type}\r\nNTS: ssdp:byebye\r\nUSN: {usn}\r\n\r\n"
+        )
+    }
+
+    pub fn write_response(
+        &self,
+        w: &mut impl fmt::Write,
+        search_target: &Target<'_>,
+        usn: &Usn<'_>,
+    ) -> fmt::Result {
+        write!(
+            w,
+            "HTTP/1.1 200 OK\r\nCACHE-CONTROL: max-age={}\r\nEXT:\r\nLOCATION: {}\r\nSERVER: {}\r\nST: {search_target}\r\nUSN: {usn}\r\n\r\n",
+            self.max_age, self.location, self.server
+        )
+    }
+
+    /// Multicasts `ssdp:alive` notifications for all targets.
+    /// Should be called on startup and then periodically, well within `max_age`.
+    pub fn announce<S>(&self, socket: &mut S) -> Result<(), SsdpError<S::Error>>
+    where
+        S: UdpSocket,
+    {
+        for target in self.targets() {
+            let usn = self.usn(target);
+
+            send(socket, ssdp_addr(), |w| self.write_alive(w, &target, &usn))?;
+        }
+
+        Ok(())
+    }
+
+    /// Multicasts `ssdp:byebye` notifications for all targets, e.g. before a restart.
+    pub fn byebye<S>(&self, socket: &mut S) -> Result<(), SsdpError<S::Error>>
+    where
+        S: UdpSocket,
+    {
+        for target in self.targets() {
+            let usn = self.usn(target);
+
+            send(socket, ssdp_addr(), |w| self.write_byebye(w, &target, &usn))?;
+        }
+
+        Ok(())
+    }
+
+    /// Responds to `request` for all matching targets. Returns the number of responses sent.
+    pub fn respond<S>(
+        &self,
+        socket: &mut S,
+        request: &SearchRequest<'_>,
+        addr: SocketAddrV4,
+    ) -> Result<usize, SsdpError<S::Error>>
+    where
+        S: UdpSocket,
+    {
+        let mut sent = 0;
+
+        for target in self.targets() {
+            if request.search_target == ST_ALL || target.matches(request.search_target) {
+                let usn = self.usn(target);
+
+                send(socket, addr, |w| self.write_response(w, &target, &usn))?;
+
+                sent += 1;
+            }
+        }
+
+        Ok(sent)
+    }
+
+    /// Waits up to `timeout` for a single SSDP message on `socket` (which should be bound to `SSDP_PORT`
+    /// and joined to `SSDP_MULTICAST_ADDR`), and responds to it if it is a matching M-SEARCH request.
+    pub fn process<S>(
+        &self,
+        socket: &mut S,
+        timeout: Option<Duration>,
+    ) -> Result<usize, SsdpError<S::Error>>
+    where
+        S: UdpSocket,
+    {
+        let mut buf = [0_u8; MAX_MESSAGE_LEN];
+
+        if let Some((len, addr)) = socket
+            .receive_from(&mut buf, timeout)
+            .map_err(SsdpError::Io)?
+        {
+            if let Ok(SsdpMessage::Search(request)) = SsdpMessage::parse(&buf[..len]) {
+                return self.respond(socket, &request, addr);
+            }
+        }
+
+        Ok(0)
+    }
+}
+
+fn send<S, F>(socket: &mut S, addr: SocketAddrV4, f: F) -> Result<(), SsdpError<S::Error>>
+where
+    S: UdpSocket,
+    F: FnOnce(&mut heapless::String<MAX_MESSAGE_LEN>) -> fmt::Result,
+{
+    let mut message = heapless::String::new();
+    f(&mut message).map_err(|_| SsdpError::MessageTooLong)?;
+
+    socket
+        .send_to(message.as_bytes(), addr)
+        .map_err(SsdpError::Io)?;
+
+    Ok(())
+}
+
+/// An SSDP NT/ST value
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Target<'a> {
+    RootDevice,
+    /// A device UUID, without the `uuid:` prefix
+    Uuid(&'a str),
+    /// A device or service type URN
+    Type(&'a str),
+}
+
+impl<'a> Target<'a> {
+    pub fn matches(&self, search_target: &str) -> bool {
+        match self {
+            Self::RootDevice => search_target == ST_ROOT_DEVICE,
+            Self::Uuid(uuid) => search_target.strip_prefix("uuid:") == Some(*uuid),
+            Self::Type(urn) => search_target == *urn,
+        }
+    }
+}
+
+impl<'a> fmt::Display for Target<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::RootDevice => write!(f, "{ST_ROOT_DEVICE}"),
+            Self::Uuid(uuid) => write!(f, "uuid:{uuid}"),
+            Self::Type(urn) => write!(f, "{urn}"),
+        }
+    }
+}
+
+/// An SSDP USN value: `uuid:<uuid>`, or `uuid:<uuid>::<target>` for all other targets
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Usn<'a> {
+    pub uuid: &'a str,
+    pub target: Target<'a>,
+}
+
+impl<'a> fmt::Display for Usn<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.target {
+            Target::Uuid(_) => write!(f, "uuid:{}", self.uuid),
+            target => write!(f, "uuid:{}::{target}", self.uuid),
+        }
+    }
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SsdpError<E> {
+    Io(E),
+    MessageTooLong,
+}
+
+impl_error! {
+    SsdpError<E: Display> {
+        Io(e) => "IO error: {e}"; e.error_kind(),
+        MessageTooLong => "SSDP message too long"; ErrorKind::InvalidInput,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::convert::Infallible;
+    use core::fmt::Write;
+
+    use super::*;
+
+    struct Responses<'a>(&'a [&'a str]);
+
+    impl<'a> embedded_io::Io for Responses<'a> {
+        type Error = Infallible;
+    }
+
+    impl<'a> UdpSocket for Responses<'a> {
+        fn local_addr(&self) -> Result<SocketAddrV4, Self::Error> {
+            Ok(SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, 0))
+        }
+
+        fn send_to(&mut self, buf: &[u8], _addr: SocketAddrV4) -> Result<usize, Self::Error> {
+            Ok(buf.len())
+        }
+
+        fn receive_from(
+            &mut self,
+            buf: &mut [u8],
+            _timeout: Option<Duration>,
+        ) -> Result<Option<(usize, SocketAddrV4)>, Self::Error> {
+            Ok(self.0.split_first().map(|(usn, rest)| {
+                self.0 = rest;
+
+                let response = response(usn);
+                buf[..response.len()].copy_from_slice(response.as_bytes());
+
+                (
+                    response.len(),
+                    SocketAddrV4::new(Ipv4Addr::new(192, 168, 1, 2), SSDP_PORT),
+                )
+            }))
+        }
+
+        fn set_broadcast(&mut self, _enabled: bool) -> Result<(), Self::Error> {
+            Ok(())
+        }
+
+        fn join_multicast(&mut self, _group: Ipv4Addr) -> Result<(), Self::Error> {
+            Ok(())
+        }
+
+        fn leave_multicast(&mut self, _group: Ipv4Addr) -> Result<(), Self::Error> {
+            Ok(())
+        }
+    }
+
+    struct Stopped;
+
+    impl SystemTime for Stopped {
+        fn now(&self) -> Duration {
+            Duration::ZERO
+        }
+    }
+
+    fn response(usn: &str) -> heapless::String<256> {
+        let mut response = heapless::String::new();
+
+        write!(
+            response,
+            "HTTP/1.1 200 OK\r\nCACHE-CONTROL: max-age=1800\r\nLOCATION: http://192.168.1.2/desc.xml\r\nST: {ST_ROOT_DEVICE}\r\nUSN: {usn}\r\n\r\n"
+        )
+        .unwrap();
+
+        response
+    }
+
+    #[test]
+    fn parse_response() {
+        let response = response("uuid:a::upnp:rootdevice");
+
+        assert_eq!(
+            SsdpMessage::parse(response.as_bytes()),
+            Ok(SsdpMessage::Response(Advertisement {
+                notification_type: ST_ROOT_DEVICE,
+                usn: "uuid:a::upnp:rootdevice",
+                location: Some("http://192.168.1.2/desc.xml"),
+                server: None,
+                max_age: Some(1800),
+            }))
+        );
+    }
+
+    #[test]
+    fn parse_search() {
+        let mut search = heapless::String::<MAX_MESSAGE_LEN>::new();
+        write_search(&mut search, ST_ALL, 3).unwrap();
+
+        assert_eq!(
+            SsdpMessage::parse(search.as_bytes()),
+            Ok(SsdpMessage::Search(SearchRequest {
+                search_target: ST_ALL,
+                mx: 3,
+            }))
+        );
+    }
+
+    #[test]
+    fn discover_counts_distinct_devices() {
+        let mut socket = Responses(&["uuid:a", "uuid:b", "uuid:a", "uuid:c", "uuid:b", "uuid:c"]);
+
+        let (devices, found) = discover::<_, _, 1>(
+            &mut socket,
+            &Stopped,
+            ST_ROOT_DEVICE,
+            Duration::from_secs(1),
+        )
+        .unwrap();
+
+        assert_eq!(devices.len(), 1);
+        assert_eq!(devices[0].usn, "uuid:a");
+        assert_eq!(found, 3);
+    }
+}
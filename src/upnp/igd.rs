@@ -0,0 +1,348 @@
+use core::fmt::{self, Write as _};
+use core::str;
+
+#[cfg(feature = "use_serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::error::{impl_error, ErrorKind};
+use crate::http::client::{Client, Connection};
+use crate::http::Status;
+use crate::io::Write;
+use crate::ipv4::Ipv4Addr;
+use crate::utils::io::try_read_full;
+
+pub const SERVICE_WAN_IP_CONNECTION_2: &str = "urn:schemas-upnp-org:service:WANIPConnection:2";
+pub const SERVICE_WAN_IP_CONNECTION_1: &str = "urn:schemas-upnp-org:service:WANIPConnection:1";
+pub const SERVICE_WAN_PPP_CONNECTION_1: &str = "urn:schemas-upnp-org:service:WANPPPConnection:1";
+
+/// The UPnP error code returned by gateways when the requested mapping conflicts with an existing one
+pub const ERROR_CONFLICT_IN_MAPPING_ENTRY: u16 = 718;
+
+const SERVICE_TYPES: &[&str] = &[
+    SERVICE_WAN_IP_CONNECTION_2,
+    SERVICE_WAN_IP_CONNECTION_1,
+    SERVICE_WAN_PPP_CONNECTION_1,
+];
+
+const MAX_ENVELOPE_LEN: usize = 1024;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub enum PortMappingProtocol {
+    Tcp,
+    Udp,
+}
+
+impl fmt::Display for PortMappingProtocol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Tcp => write!(f, "TCP"),
+            Self::Udp => write!(f, "UDP"),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct PortMapping<'a> {
+    pub external_port: u16,
+    pub protocol: PortMappingProtocol,
+    #[cfg_attr(feature = "defmt", defmt(Debug2Format))]
+    pub internal_client: Ipv4Addr,
+    pub internal_port: u16,
+    pub description: &'a str,
+    /// Lease duration in seconds; 0 requests a permanent mapping, which some gateways reject
+    pub lease_duration: u32,
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum IgdError<E> {
+    HttpError(E),
+    /// The gateway responded with an unexpected HTTP status
+    Status(u16),
+    /// The gateway responded with a SOAP fault carrying this UPnP error code
+    Fault(u16),
+    InvalidResponse(&'static str),
+}
+
+impl_error! {
+    IgdError<E: Display> {
+        HttpError(e) => "HTTP error: {e}"; e.error_kind(),
+        Status(status) => "Unexpected HTTP status: {status}"; ErrorKind::from_status(*status),
+        Fault(code) => "UPnP error: {code}"; fault_kind(*code),
+        InvalidResponse(e) => "Invalid response: {e}"; ErrorKind::Other,
+    }
+}
+
+fn fault_kind(code: u16) -> ErrorKind {
+    match code {
+        // Invalid Action, Invalid Args
+        401 | 402 => ErrorKind::InvalidInput,
+        // Action not authorized
+        606 => ErrorKind::Unauthorized,
+        _ => ErrorKind::Other,
+    }
+}
+
+/// The WAN connection service of an Internet Gateway Device.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Gateway<const N: usize = 128> {
+    pub control_url: heapless::String<N>,
+    pub service_type: &'static str,
+}
+
+impl<const N: usize> Gateway<N> {
+    pub const fn new(control_url: heapless::String<N>, service_type: &'static str) -> Self {
+        Self {
+            control_url,
+            service_type,
+        }
+    }
+
+    /// Fetches the device description document at `location` (as discovered via SSDP)
+    /// and locates the WAN connection service in it.
+    ///
+    /// `buf` should be large enough to hold the whole description document;
+    /// the document is truncated otherwise. 4-8KB is typically enough.
+    pub fn from_location<C>(
+        client: &mut Client<C>,
+        location: &str,
+        buf: &mut [u8],
+    ) -> Result<Self, IgdError<C::Error>>
+    where
+        C: Connection,
+    {
+        let mut response = client
+            .get(location)
+            .and_then(|request| request.submit())
+            .map_err(IgdError::HttpError)?;
+
+        if response.status() != 200 {
+            return Err(IgdError::Status(response.status()));
+        }
+
+        let len = try_read_full(&mut response, buf).map_err(|(e, _)| IgdError::HttpError(e))?;
+
+        let description = str::from_utf8(&buf[..len])
+            .map_err(|_| IgdError::InvalidResponse("Description is not valid UTF-8"))?;
+
+        let (service_type, control_url) = find_service(description)
+            .ok_or(IgdError::InvalidResponse("No WAN connection service"))?;
+
+        let base = element(description, "URLBase").unwrap_or(location);
+
+        let mut url = heapless::String::new();
+        resolve(base, control_url, &mut url)
+            .map_err(|_| IgdError::InvalidResponse("Control URL too long"))?;
+
+        Ok(Self::new(url, service_type))
+    }
+
+    pub fn external_ip_address<C>(
+        &self,
+        client: &mut Client<C>,
+    ) -> Result<Ipv4Addr, IgdError<C::Error>>
+    where
+        C: Connection,
+    {
+        let mut buf = [0_u8; MAX_ENVELOPE_LEN];
+
+        let response = self.invoke(client, "GetExternalIPAddress", |_| Ok(()), &mut buf)?;
+
+        element(response, "NewExternalIPAddress")
+            .and_then(|addr| addr.trim().parse().ok())
+            .ok_or(IgdError::InvalidResponse(
+                "Missing or invalid external IP address",
+            ))
+    }
+
+    pub fn add_port_mapping<C>(
+        &self,
+        client: &mut Client<C>,
+        mapping: &PortMapping<'_>,
+    ) -> Result<(), IgdError<C::Error>>
+    where
+        C: Connection,
+    {
+        let mut buf = [0_u8; MAX_ENVELOPE_LEN];
+
+        self.invoke(
+            client,
+            "AddPortMapping",
+            |w| {
+                write!(
+                    w,
+                    "<NewRemoteHost></NewRemoteHost><NewExternalPort>{}</NewExternalPort><NewProtocol>{}</NewProtocol><NewInternalPort>{}</NewInternalPort><NewInternalClient>{}</NewInternalClient><NewEnabled>1</NewEnabled><NewPortMappingDescription>",
+                    mapping.external_port, mapping.protocol, mapping.internal_port, mapping.internal_client
+                )?;
+
+                escape(w, mapping.description)?;
+
+                write!(
+                    w,
+                    "</NewPortMappingDescription><NewLeaseDuration>{}</NewLeaseDuration>",
+                    mapping.lease_duration
+                )
+            },
+            &mut buf,
+        )?;
+
+        Ok(())
+    }
+
+    pub fn delete_port_mapping<C>(
+        &self,
+        client: &mut Client<C>,
+        external_port: u16,
+        protocol: PortMappingProtocol,
+    ) -> Result<(), IgdError<C::Error>>
+    where
+        C: Connection,
+    {
+        let mut buf = [0_u8; MAX_ENVELOPE_LEN];
+
+        self.invoke(
+            client,
+            "DeletePortMapping",
+            |w| {
+                write!(
+                    w,
+                    "<NewRemoteHost></NewRemoteHost><NewExternalPort>{external_port}</NewExternalPort><NewProtocol>{protocol}</NewProtocol>"
+                )
+            },
+            &mut buf,
+        )?;
+
+        Ok(())
+    }
+
+    fn invoke<'b, C, F>(
+        &self,
+        client: &mut Client<C>,
+        action: &str,
+        arguments: F,
+        buf: &'b mut [u8],
+    ) -> Result<&'b str, IgdError<C::Error>>
+    where
+        C: Connection,
+        F: FnOnce(&mut heapless::String<MAX_ENVELOPE_LEN>) -> fmt::Result,
+    {
+        let mut envelope = heapless::String::<MAX_ENVELOPE_LEN>::new();
+
+        write!(
+            &mut envelope,
+            "<?xml version=\"1.0\"?><s:Envelope xmlns:s=\"http://schemas.xmlsoap.org/soap/envelope/\" s:encodingStyle=\"http://schemas.xmlsoap.org/soap/encoding/\"><s:Body><u:{action} xmlns:u=\"{}\">",
+            self.service_type
+        )
+        .and_then(|_| arguments(&mut envelope))
+        .and_then(|_| write!(&mut envelope, "</u:{action}></s:Body></s:Envelope>"))
+        .map_err(|_| IgdError::InvalidResponse("SOAP request too long"))?;
+
+        let mut soap_action = heapless::String::<128>::new();
+        write!(&mut soap_action, "\"{}#{action}\"", self.service_type)
+            .map_err(|_| IgdError::InvalidResponse("SOAP action too long"))?;
+
+        let mut content_len = heapless::String::<10>::new();
+        write!(&mut content_len, "{}", envelope.len()).unwrap();
+
+        let headers = [
+            ("Content-Type", "text/xml; charset=\"utf-8\""),
+            ("Content-Length", content_len.as_str()),
+            ("SOAPAction", soap_action.as_str()),
+        ];
+
+        let mut request = client
+            .post(&self.control_url, &headers)
+            .map_err(IgdError::HttpError)?;
+
+        request
+            .write_all(envelope.as_bytes())
+            .map_err(IgdError::HttpError)?;
+
+        let mut response = request.submit().map_err(IgdError::HttpError)?;
+
+        let status = response.status();
+
+        let len = try_read_full(&mut response, buf).map_err(|(e, _)| IgdError::HttpError(e))?;
+
+        let body = str::from_utf8(&buf[..len])
+            .map_err(|_| IgdError::InvalidResponse("Response is not valid UTF-8"))?;
+
+        if status == 200 {
+            Ok(body)
+        } else if let Some(code) = element(body, "errorCode").and_then(|code| code.parse().ok()) {
+            Err(IgdError::Fault(code))
+        } else {
+            Err(IgdError::Status(status))
+        }
+    }
+}
+
+/// Finds the first supported WAN connection service in a device description document
+fn find_service(description: &str) -> Option<(&'static str, &str)> {
+    description.split("<service>").skip(1).find_map(|service| {
+        let service_type = element(service, "serviceType")?.trim();
+
+        let service_type = SERVICE_TYPES
+            .iter()
+            .find(|supported| **supported == service_type)?;
+
+        Some((*service_type, element(service, "controlURL")?.trim()))
+    })
+}
+
+/// Returns the text content of the first element named `name` (with or without a namespace prefix).
+/// This is by no means a general XML parser, but it is sufficient for UPnP descriptions and SOAP responses.
+fn element<'a>(xml: &'a str, name: &str) -> Option<&'a str> {
+    xml.match_indices(name).find_map(|(index, _)| {
+        let before = xml[..index].chars().next_back()?;
+        let after = &xml[index + name.len()..];
+
+        if (before == '<' || before == ':') && after.starts_with('>') {
+            let content = &after[1..];
+
+            content.find('<').map(|end| &content[..end])
+        } else {
+            None
+        }
+    })
+}
+
+fn resolve(base: &str, url: &str, w: &mut impl fmt::Write) -> fmt::Result {
+    if url.starts_with("http://") || url.starts_with("https://") {
+        return write!(w, "{url}");
+    }
+
+    let authority_end = base
+        .find("://")
+        .map(|scheme_end| {
+            base[scheme_end + 3..]
+                .find('/')
+                .map(|path_start| scheme_end + 3 + path_start)
+                .unwrap_or(base.len())
+        })
+        .unwrap_or(base.len());
+
+    let separator = if url.starts_with('/') { "" } else { "/" };
+
+    write!(w, "{}{separator}{url}", &base[..authority_end])
+}
+
+fn escape(w: &mut impl fmt::Write, s: &str) -> fmt::Result {
+    for c in s.chars() {
+        match c {
+            '&' => w.write_str("&amp;")?,
+            '<' => w.write_str("&lt;")?,
+            '>' => w.write_str("&gt;")?,
+            '"' => w.write_str("&quot;")?,
+            '\'' => w.write_str("&apos;")?,
+            c => w.write_char(c)?,
+        }
+    }
+
+    Ok(())
+}
@@ -1,7 +1,36 @@
 #[cfg(feature = "experimental")]
 pub mod asyncify;
+pub mod audit;
+#[cfg(feature = "experimental")]
+pub mod bootstrap;
+pub mod checkpoint;
+pub mod codec;
+#[cfg(feature = "experimental")]
+pub mod connectivity;
+pub mod factory_reset;
+pub mod health;
 #[cfg(feature = "experimental")]
 pub mod http;
 pub mod io;
+pub mod json;
+pub mod memory;
 pub mod mqtt;
 pub mod mutex;
+#[cfg(all(feature = "experimental", feature = "use_serde"))]
+pub mod onboarding;
+#[cfg(feature = "experimental")]
+pub mod remote_config;
+pub mod rpc;
+pub mod rtc;
+pub mod schedule;
+pub mod service;
+pub mod shadow;
+pub mod sntp;
+pub mod supervisor;
+pub mod telemetry;
+pub mod time;
+pub mod uuid;
+pub mod version;
+pub mod wol;
+#[cfg(feature = "experimental")]
+pub mod ws;
@@ -7,6 +7,8 @@ use core::task::{Context, Poll, Waker};
 extern crate alloc;
 use alloc::sync::Arc;
 
+use futures::Stream;
+
 use crate::utils::mutex::{Condvar, Mutex, RawCondvar};
 
 #[cfg(all(feature = "nightly", feature = "experimental"))]
@@ -78,6 +80,38 @@ where
     pub async fn recv(&mut self) -> P {
         NextFuture(self).await
     }
+
+    fn poll_recv(&self, cx: &mut Context<'_>) -> Poll<P> {
+        let mut state = self.0 .0.lock();
+
+        let value = mem::replace(&mut state.value, None);
+
+        if let Some(value) = value {
+            self.0 .1.notify_all();
+
+            Poll::Ready(value)
+        } else {
+            state.waker = Some(cx.waker().clone());
+
+            self.0 .1.notify_all();
+
+            Poll::Pending
+        }
+    }
+}
+
+impl<CV, P, S> Stream for AsyncSubscription<CV, P, S>
+where
+    CV: RawCondvar + Send + Sync,
+    CV::RawMutex: Send + Sync,
+    S: Send,
+    P: Clone + Send,
+{
+    type Item = P;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.poll_recv(cx).map(Some)
+    }
 }
 
 pub struct NextFuture<'a, CV, P, S>(&'a AsyncSubscription<CV, P, S>)
@@ -110,24 +144,104 @@ where
     type Output = P;
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        let mut state = self.0 .0 .0.lock();
+        self.0.poll_recv(cx)
+    }
+}
 
-        let value = mem::replace(&mut state.value, None);
+pub struct QueuedSubscriptionState<P, S, const N: usize> {
+    subscription: Option<S>,
+    queue: heapless::Deque<P, N>,
+    lost: usize,
+    waker: Option<Waker>,
+}
 
-        if let Some(value) = value {
-            self.0 .0 .1.notify_all();
+/// Like `AsyncSubscription`, but buffers up to `N` payloads, so that the event bus is never blocked:
+/// once the queue is full, the oldest payload is dropped to make room for the new one.
+pub struct AsyncQueuedSubscription<CV, P, S, const N: usize>(
+    Arc<Mutex<CV::RawMutex, QueuedSubscriptionState<P, S, N>>>,
+)
+where
+    CV: RawCondvar,
+    P: Send,
+    S: Send;
+
+impl<CV, P, S, const N: usize> AsyncQueuedSubscription<CV, P, S, N>
+where
+    CV: RawCondvar + Send + Sync,
+    CV::RawMutex: Send + Sync,
+    S: Send,
+    P: Clone + Send,
+{
+    pub async fn recv(&mut self) -> P {
+        QueuedNextFuture(self).await
+    }
+
+    /// The number of payloads dropped so far because the queue was full
+    pub fn lost(&self) -> usize {
+        self.0.lock().lost
+    }
+
+    fn poll_recv(&self, cx: &mut Context<'_>) -> Poll<P> {
+        let mut state = self.0.lock();
 
+        if let Some(value) = state.queue.pop_front() {
             Poll::Ready(value)
         } else {
             state.waker = Some(cx.waker().clone());
 
-            self.0 .0 .1.notify_all();
-
             Poll::Pending
         }
     }
 }
 
+impl<CV, P, S, const N: usize> Stream for AsyncQueuedSubscription<CV, P, S, N>
+where
+    CV: RawCondvar + Send + Sync,
+    CV::RawMutex: Send + Sync,
+    S: Send,
+    P: Clone + Send,
+{
+    type Item = P;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.poll_recv(cx).map(Some)
+    }
+}
+
+pub struct QueuedNextFuture<'a, CV, P, S, const N: usize>(&'a AsyncQueuedSubscription<CV, P, S, N>)
+where
+    CV: RawCondvar + Send + Sync,
+    CV::RawMutex: Send + Sync,
+    P: Clone + Send,
+    S: Send;
+
+impl<'a, CV, P, S, const N: usize> Drop for QueuedNextFuture<'a, CV, P, S, N>
+where
+    CV: RawCondvar + Send + Sync,
+    CV::RawMutex: Send + Sync,
+    P: Clone + Send,
+    S: Send,
+{
+    fn drop(&mut self) {
+        let mut state = self.0 .0.lock();
+        state.waker = None;
+    }
+}
+
+impl<'a, CV, P, S, const N: usize> Future for QueuedNextFuture<'a, CV, P, S, N>
+where
+    CV: RawCondvar + Send + Sync,
+    CV::RawMutex: Send + Sync,
+    P: Clone + Send,
+    S: Send,
+{
+    type Output = P;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.0.poll_recv(cx)
+    }
+}
+
 pub struct AsyncEventBus<U, CV, E> {
     unblocker: U,
     event_bus: E,
@@ -192,6 +306,49 @@ where
 
         Ok(AsyncSubscription(state))
     }
+
+    pub fn subscribe_queued<P, const N: usize>(
+        &self,
+    ) -> Result<AsyncQueuedSubscription<CV, P, E::Subscription, N>, E::Error>
+    where
+        P: Clone + Send + 'static,
+        E: crate::event_bus::EventBus<P>,
+        E::Subscription: Send + 'static,
+    {
+        let state = Arc::new(Mutex::new(QueuedSubscriptionState {
+            subscription: None,
+            queue: heapless::Deque::new(),
+            lost: 0,
+            waker: None,
+        }));
+
+        let subscription_state = Arc::downgrade(&state);
+
+        let subscription = self.event_bus.subscribe(move |payload| {
+            if let Some(state) = subscription_state.upgrade() {
+                let state: &Mutex<CV::RawMutex, QueuedSubscriptionState<P, _, N>> = &state;
+
+                let mut state = state.lock();
+
+                if state.queue.is_full() {
+                    state.queue.pop_front();
+                    state.lost = state.lost.wrapping_add(1);
+                }
+
+                if state.queue.push_back(payload.clone()).is_err() {
+                    unreachable!();
+                }
+
+                if let Some(waker) = state.waker.take() {
+                    waker.wake();
+                }
+            }
+        })?;
+
+        state.lock().subscription = Some(subscription);
+
+        Ok(AsyncQueuedSubscription(state))
+    }
 }
 
 impl<CV, E> AsyncEventBus<(), CV, E>
@@ -234,7 +391,10 @@ mod async_traits_impl {
     use crate::utils::asyncify::{AsyncWrapper, UnblockingAsyncWrapper};
     use crate::utils::mutex::RawCondvar;
 
-    use super::{AsyncEventBus, AsyncPostbox, AsyncSubscription, NextFuture};
+    use super::{
+        AsyncEventBus, AsyncPostbox, AsyncQueuedSubscription, AsyncSubscription, NextFuture,
+        QueuedNextFuture,
+    };
 
     impl<U, P, PB> Sender for AsyncPostbox<U, P, PB>
     where
@@ -300,6 +460,25 @@ mod async_traits_impl {
         }
     }
 
+    impl<CV, P, S, const N: usize> Receiver for AsyncQueuedSubscription<CV, P, S, N>
+    where
+        CV: RawCondvar + Send + Sync,
+        CV::RawMutex: Send + Sync,
+        S: Send,
+        P: Clone + Send,
+    {
+        type Data = P;
+
+        type RecvFuture<'a>
+            = QueuedNextFuture<'a, CV, P, S, N>
+        where
+            Self: 'a;
+
+        fn recv(&self) -> Self::RecvFuture<'_> {
+            QueuedNextFuture(self)
+        }
+    }
+
     impl<U, CV, E> UnblockingAsyncWrapper<U, E> for AsyncEventBus<U, CV, E> {
         fn new(unblocker: U, sync: E) -> Self {
             AsyncEventBus::new(unblocker, sync)
@@ -8,6 +8,8 @@ pub mod client {
     extern crate alloc;
     use alloc::sync::Arc;
 
+    use futures::Stream;
+
     use crate::mqtt::client::{Event, MessageId, QoS};
     use crate::utils::mqtt::client::ConnStateGuard;
     use crate::utils::mutex::RawCondvar;
@@ -276,6 +278,21 @@ pub mod client {
         }
     }
 
+    /// Yields the events of the connection, until it is closed
+    impl<CV, M, E> Stream for AsyncConnection<CV, M, E>
+    where
+        CV: RawCondvar + Send + Sync + 'static,
+        CV::RawMutex: Sync + 'static,
+        M: Send,
+        E: Debug + Send + 'static,
+    {
+        type Item = Result<Event<M>, E>;
+
+        fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+            Pin::new(&mut NextFuture(&self.0)).poll(cx)
+        }
+    }
+
     #[cfg(all(feature = "nightly", feature = "experimental"))]
     mod async_traits_impl {
         use core::fmt::Debug;
@@ -10,6 +10,7 @@ extern crate alloc;
 use alloc::sync::Arc;
 
 use futures::task::AtomicWaker;
+use futures::Stream;
 
 #[cfg(all(feature = "nightly", feature = "experimental"))]
 pub use async_traits_impl::*;
@@ -54,6 +55,8 @@ pub struct AsyncTimer<T> {
     timer: T,
     signal: Arc<TimerSignal>,
     duration: Option<Duration>,
+    /// Whether the timer was scheduled for the next item of the stream
+    armed: bool,
 }
 
 impl<T> AsyncTimer<T>
@@ -65,6 +68,7 @@ where
 
         self.signal.reset();
         self.duration = None;
+        self.armed = false;
 
         Ok(TimerFuture(self, Some(duration)))
     }
@@ -74,17 +78,50 @@ where
 
         self.signal.reset();
         self.duration = Some(duration);
+        self.armed = false;
 
         Ok(self)
     }
 
     pub fn tick(&mut self) -> TimerFuture<'_, T> {
         self.signal.reset();
+        self.armed = false;
 
         TimerFuture(self, self.duration)
     }
 }
 
+/// The fields of the timer are never pinned
+impl<T> Unpin for AsyncTimer<T> {}
+
+/// Yields each tick of a timer set up with `every`, and ends right away otherwise
+impl<T> Stream for AsyncTimer<T>
+where
+    T: crate::timer::OnceTimer + Send + 'static,
+{
+    type Item = ();
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let duration = match self.duration {
+            Some(duration) => duration,
+            None => return Poll::Ready(None),
+        };
+
+        if !self.armed {
+            self.timer.after(duration).unwrap();
+            self.armed = true;
+        }
+
+        if self.signal.poll_wait(cx).is_ready() {
+            self.armed = false;
+
+            Poll::Ready(Some(()))
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
 pub struct TimerFuture<'a, T>(&'a mut AsyncTimer<T>, Option<Duration>)
 where
     T: crate::timer::Timer;
@@ -156,6 +193,7 @@ where
             timer,
             signal,
             duration: None,
+            armed: false,
         })
     }
 }
@@ -0,0 +1,507 @@
+//! An audit log of the configuration changes: who changed which setting when, and from where.
+//!
+//! Values are not logged, only hashes of them, so that the log does not leak secrets such as passwords,
+//! while still telling apart changes which restored a previous value.
+
+use core::fmt::{self, Debug, Display, Write as _};
+
+#[cfg(feature = "use_serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::error::{impl_error, ErrorKind};
+use crate::io::Write;
+use crate::storage::{RawStorage, StorageBase};
+use crate::sys_time::SystemTime;
+use crate::utils::io::CopyError;
+use crate::utils::json;
+
+/// Where a change comes from
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub enum Source {
+    Web,
+    Mqtt,
+    Ble,
+    /// On the device itself, e.g. a button or the serial console
+    Local,
+    Other,
+}
+
+impl Source {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Web => "web",
+            Self::Mqtt => "mqtt",
+            Self::Ble => "ble",
+            Self::Local => "local",
+            Self::Other => "other",
+        }
+    }
+
+    fn from_u8(value: u8) -> Self {
+        match value {
+            0 => Self::Web,
+            1 => Self::Mqtt,
+            2 => Self::Ble,
+            3 => Self::Local,
+            _ => Self::Other,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub struct AuditEntry {
+    pub sequence: u32,
+    /// Seconds since the UNIX epoch
+    pub timestamp: u64,
+    pub source: Source,
+    pub user: heapless::String<16>,
+    /// The name of the setting
+    pub key: heapless::String<32>,
+    /// `None` if the setting was created
+    pub old_hash: Option<u32>,
+    /// `None` if the setting was removed
+    pub new_hash: Option<u32>,
+}
+
+impl AuditEntry {
+    const MAX_LEN: usize = 4 + 8 + 2 + 8 + 1 + 16 + 1 + 32;
+
+    /// FNV-1a of `value`; not a cryptographic hash
+    pub fn hash(value: &[u8]) -> u32 {
+        value.iter().fold(0x811c_9dc5, |hash, byte| {
+            (hash ^ *byte as u32).wrapping_mul(0x0100_0193)
+        })
+    }
+
+    fn encode<'a>(&self, buf: &'a mut [u8; Self::MAX_LEN]) -> &'a [u8] {
+        let mut len = 0;
+        let mut put = |data: &[u8]| {
+            buf[len..len + data.len()].copy_from_slice(data);
+            len += data.len();
+        };
+
+        put(&self.sequence.to_le_bytes());
+        put(&self.timestamp.to_le_bytes());
+        put(&[
+            self.source as u8,
+            self.old_hash.is_some() as u8 | (self.new_hash.is_some() as u8) << 1,
+        ]);
+        put(&self.old_hash.unwrap_or(0).to_le_bytes());
+        put(&self.new_hash.unwrap_or(0).to_le_bytes());
+        put(&[self.user.len() as u8]);
+        put(self.user.as_bytes());
+        put(&[self.key.len() as u8]);
+        put(self.key.as_bytes());
+
+        &buf[..len]
+    }
+
+    fn decode(data: &[u8]) -> Option<Self> {
+        let fixed = data.get(..22)?;
+        let word = |offset: usize| {
+            u32::from_le_bytes([
+                fixed[offset],
+                fixed[offset + 1],
+                fixed[offset + 2],
+                fixed[offset + 3],
+            ])
+        };
+
+        let flags = fixed[13];
+
+        let (user, rest) = decode_str(&data[22..])?;
+        let (key, _) = decode_str(rest)?;
+
+        Some(Self {
+            sequence: word(0),
+            timestamp: word(4) as u64 | (word(8) as u64) << 32,
+            source: Source::from_u8(fixed[12]),
+            user,
+            key,
+            old_hash: (flags & 1 != 0).then(|| word(14)),
+            new_hash: (flags & 2 != 0).then(|| word(18)),
+        })
+    }
+}
+
+/// As a JSON object
+impl Display for AuditEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{{\"sequence\":{},\"timestamp\":{},\"source\":\"{}\",\"user\":\"{}\",\"key\":\"{}\",\"old\":",
+            self.sequence,
+            self.timestamp,
+            self.source.as_str(),
+            json::Escaped(&self.user),
+            json::Escaped(&self.key)
+        )?;
+
+        write_hash(f, self.old_hash)?;
+        f.write_str(",\"new\":")?;
+        write_hash(f, self.new_hash)?;
+
+        f.write_str("}")
+    }
+}
+
+fn write_hash(f: &mut fmt::Formatter<'_>, hash: Option<u32>) -> fmt::Result {
+    match hash {
+        Some(hash) => write!(f, "\"{hash:08x}\""),
+        None => f.write_str("null"),
+    }
+}
+
+fn decode_str<const N: usize>(data: &[u8]) -> Option<(heapless::String<N>, &[u8])> {
+    let len = *data.first()? as usize;
+    let value = core::str::from_utf8(data.get(1..1 + len)?).ok()?;
+
+    let mut string = heapless::String::new();
+    string.push_str(value).ok()?;
+
+    Some((string, &data[1 + len..]))
+}
+
+/// Truncates `value` at a character boundary
+fn truncated<const N: usize>(value: &str) -> heapless::String<N> {
+    let mut string = heapless::String::new();
+
+    for c in value.chars() {
+        if string.push(c).is_err() {
+            break;
+        }
+    }
+
+    string
+}
+
+/// The last `N` audit entries in `RawStorage`, each stored as a separate blob; once `N` entries are stored,
+/// the oldest one is dropped.
+pub struct AuditLog<S, T, const N: usize = 64> {
+    storage: S,
+    time: T,
+    head: u32,
+    tail: u32,
+}
+
+impl<S, T, const N: usize> AuditLog<S, T, N>
+where
+    S: RawStorage,
+    T: SystemTime,
+{
+    const META: &'static str = "audit";
+
+    /// Picks up the entries stored before a reboot
+    pub fn new(storage: S, time: T) -> Result<Self, S::Error> {
+        let mut meta = [0_u8; 8];
+
+        let (head, tail) = match storage.get_raw(Self::META, &mut meta)? {
+            Some(meta) if meta.len() == 8 => (
+                u32::from_le_bytes([meta[0], meta[1], meta[2], meta[3]]),
+                u32::from_le_bytes([meta[4], meta[5], meta[6], meta[7]]),
+            ),
+            _ => (0, 0),
+        };
+
+        // The log might have been written with a larger `N`
+        let head = head.max(tail.saturating_sub(N as _));
+
+        Ok(Self {
+            storage,
+            time,
+            head,
+            tail,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        (self.tail - self.head) as _
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Logs a change of the setting `key`, `old` and `new` being its values before and after the
+    /// change, if any.
+    ///
+    /// `user` and `key` are truncated to fit. Returns the sequence number of the entry.
+    pub fn record(
+        &mut self,
+        source: Source,
+        user: &str,
+        key: &str,
+        old: Option<&[u8]>,
+        new: Option<&[u8]>,
+    ) -> Result<u32, S::Error> {
+        let entry = AuditEntry {
+            sequence: self.tail,
+            timestamp: self.time.now().as_secs(),
+            source,
+            user: truncated(user),
+            key: truncated(key),
+            old_hash: old.map(AuditEntry::hash),
+            new_hash: new.map(AuditEntry::hash),
+        };
+
+        if N == 0 {
+            return Ok(entry.sequence);
+        }
+
+        if self.len() >= N {
+            self.storage.remove(&Self::slot(self.head))?;
+            self.head += 1;
+        }
+
+        let mut buf = [0_u8; AuditEntry::MAX_LEN];
+
+        self.storage
+            .set_raw(&Self::slot(self.tail), entry.encode(&mut buf))?;
+        self.tail += 1;
+
+        self.save()?;
+
+        Ok(entry.sequence)
+    }
+
+    /// Returns `None` if the entry was dropped already
+    pub fn get(&self, sequence: u32) -> Result<Option<AuditEntry>, S::Error> {
+        if !(self.head..self.tail).contains(&sequence) {
+            return Ok(None);
+        }
+
+        let mut buf = [0_u8; AuditEntry::MAX_LEN];
+
+        Ok(self
+            .storage
+            .get_raw(&Self::slot(sequence), &mut buf)?
+            .and_then(AuditEntry::decode)
+            .filter(|entry| entry.sequence == sequence))
+    }
+
+    /// Calls `f` with each entry, oldest first
+    pub fn for_each<F>(&self, mut f: F) -> Result<(), S::Error>
+    where
+        F: FnMut(&AuditEntry),
+    {
+        for sequence in self.head..self.tail {
+            if let Some(entry) = self.get(sequence)? {
+                f(&entry);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Writes the entries as a JSON array, oldest first
+    pub fn export<W>(&self, mut write: W) -> Result<(), CopyError<S::Error, W::Error>>
+    where
+        W: Write,
+    {
+        write.write_all(b"[").map_err(CopyError::Write)?;
+
+        let mut first = true;
+
+        for sequence in self.head..self.tail {
+            if let Some(entry) = self.get(sequence).map_err(CopyError::Read)? {
+                let mut buf = heapless::String::<512>::new();
+
+                if !first {
+                    buf.push(',').unwrap();
+                }
+
+                write!(&mut buf, "{entry}").unwrap();
+
+                write.write_all(buf.as_bytes()).map_err(CopyError::Write)?;
+
+                first = false;
+            }
+        }
+
+        write.write_all(b"]").map_err(CopyError::Write)?;
+        write.flush().map_err(CopyError::Write)
+    }
+
+    pub fn clear(&mut self) -> Result<(), S::Error> {
+        for sequence in self.head..self.tail {
+            self.storage.remove(&Self::slot(sequence))?;
+        }
+
+        self.head = self.tail;
+
+        self.save()
+    }
+
+    pub fn release(self) -> (S, T) {
+        (self.storage, self.time)
+    }
+
+    fn save(&mut self) -> Result<(), S::Error> {
+        let mut meta = [0_u8; 8];
+
+        meta[..4].copy_from_slice(&self.head.to_le_bytes());
+        meta[4..].copy_from_slice(&self.tail.to_le_bytes());
+
+        self.storage.set_raw(Self::META, &meta)?;
+
+        Ok(())
+    }
+
+    fn slot(sequence: u32) -> heapless::String<24> {
+        let mut name = heapless::String::new();
+
+        write!(&mut name, "{}_{}", Self::META, sequence % N.max(1) as u32).unwrap();
+
+        name
+    }
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum AuditError<R, S> {
+    StorageError(R),
+    LogError(S),
+    /// The previous value of a setting does not fit into the buffer, so it cannot be hashed
+    TooLarge,
+}
+
+impl_error! {
+    AuditError<R: Debug, S: Debug> {
+        StorageError(e) => "Storage error: {e:?}"; e.error_kind(),
+        LogError(e) => "Audit log error: {e:?}"; e.error_kind(),
+        TooLarge => "Value too large"; ErrorKind::InvalidInput,
+    }
+}
+
+/// Wraps the `RawStorage` of the settings, logging each change made through it on behalf of `user`.
+///
+/// Previous values of up to `B` bytes can be hashed; changing larger values fails with
+/// `AuditError::TooLarge`. Setting a value to what it already is is not logged.
+pub struct AuditedStorage<'a, R, S, T, const N: usize, const B: usize = 256> {
+    storage: R,
+    log: &'a mut AuditLog<S, T, N>,
+    source: Source,
+    user: &'a str,
+}
+
+impl<'a, R, S, T, const N: usize, const B: usize> AuditedStorage<'a, R, S, T, N, B>
+where
+    R: RawStorage,
+    S: RawStorage,
+    T: SystemTime,
+{
+    pub fn new(storage: R, log: &'a mut AuditLog<S, T, N>, source: Source, user: &'a str) -> Self {
+        Self {
+            storage,
+            log,
+            source,
+            user,
+        }
+    }
+
+    pub fn release(self) -> R {
+        self.storage
+    }
+
+    fn record(
+        &mut self,
+        name: &str,
+        old: Option<&[u8]>,
+        new: Option<&[u8]>,
+    ) -> Result<(), AuditError<R::Error, S::Error>> {
+        self.log
+            .record(self.source, self.user, name, old, new)
+            .map_err(AuditError::LogError)?;
+
+        Ok(())
+    }
+}
+
+impl<'a, R, S, T, const N: usize, const B: usize> StorageBase for AuditedStorage<'a, R, S, T, N, B>
+where
+    R: RawStorage,
+    S: RawStorage,
+    T: SystemTime,
+{
+    type Error = AuditError<R::Error, S::Error>;
+
+    fn contains(&self, name: &str) -> Result<bool, Self::Error> {
+        self.storage
+            .contains(name)
+            .map_err(AuditError::StorageError)
+    }
+
+    fn remove(&mut self, name: &str) -> Result<bool, Self::Error> {
+        let mut buf = [0_u8; B];
+        let old = get_old(&self.storage, name, &mut buf)?;
+
+        let removed = self
+            .storage
+            .remove(name)
+            .map_err(AuditError::StorageError)?;
+
+        if removed && old.is_some() {
+            self.record(name, old, None)?;
+        }
+
+        Ok(removed)
+    }
+}
+
+impl<'a, R, S, T, const N: usize, const B: usize> RawStorage for AuditedStorage<'a, R, S, T, N, B>
+where
+    R: RawStorage,
+    S: RawStorage,
+    T: SystemTime,
+{
+    fn len(&self, name: &str) -> Result<Option<usize>, Self::Error> {
+        self.storage.len(name).map_err(AuditError::StorageError)
+    }
+
+    fn get_raw<'b>(&self, name: &str, buf: &'b mut [u8]) -> Result<Option<&'b [u8]>, Self::Error> {
+        self.storage
+            .get_raw(name, buf)
+            .map_err(AuditError::StorageError)
+    }
+
+    fn set_raw(&mut self, name: &str, buf: &[u8]) -> Result<bool, Self::Error> {
+        let mut old_buf = [0_u8; B];
+        let old = get_old(&self.storage, name, &mut old_buf)?;
+
+        if old == Some(buf) {
+            return Ok(true);
+        }
+
+        let stored = self
+            .storage
+            .set_raw(name, buf)
+            .map_err(AuditError::StorageError)?;
+
+        if stored {
+            self.record(name, old, Some(buf))?;
+        }
+
+        Ok(stored)
+    }
+}
+
+fn get_old<'b, R, S>(
+    storage: &R,
+    name: &str,
+    buf: &'b mut [u8],
+) -> Result<Option<&'b [u8]>, AuditError<R::Error, S>>
+where
+    R: RawStorage,
+{
+    match storage.len(name).map_err(AuditError::StorageError)? {
+        Some(len) if len > buf.len() => Err(AuditError::TooLarge),
+        Some(_) => storage.get_raw(name, buf).map_err(AuditError::StorageError),
+        None => Ok(None),
+    }
+}
@@ -0,0 +1,151 @@
+//! A coarse time and region at first boot, before SNTP is configured.
+//!
+//! A device without a battery-backed RTC starts with a 1970 clock, so TLS certificates are not yet valid and
+//! the very connections needed to configure the device fail. The `Date` header of an HTTP response gives
+//! the time within a few seconds, which is plenty for certificate validity checks; the DHCP options and the
+//! Wi-Fi configuration hint at the country and the time zone.
+//!
+//! The `Date` header is typically fetched over plain HTTP, as TLS cannot be validated yet, so it is not
+//! authenticated: it is only meant to get the clock close enough until the time is synchronized with SNTP.
+
+use core::str::FromStr;
+use core::time::Duration;
+
+use crate::error::{impl_error, ErrorKind};
+use crate::http::client::{Client, Connection};
+use crate::http::{Headers, Method};
+use crate::sys_time::DateTime;
+use crate::utils::time::TimeZone;
+use crate::wifi;
+
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// Parses an HTTP date in the preferred IMF-fixdate format (RFC 9110), e.g.
+/// `Sun, 06 Nov 1994 08:49:37 GMT`, into seconds since the Unix epoch
+pub fn parse_http_date(date: &str) -> Option<u64> {
+    let mut fields = date.split_ascii_whitespace();
+
+    let _weekday = fields.next()?.strip_suffix(',')?;
+    let day = fields.next()?.parse().ok()?;
+    let month = fields.next()?;
+    let year = fields.next()?.parse().ok()?;
+
+    let mut time = fields.next()?.split(':');
+
+    let hour = time.next()?.parse().ok()?;
+    let minute = time.next()?.parse().ok()?;
+    let second = time.next()?.parse().ok()?;
+
+    if time.next().is_some() || fields.next() != Some("GMT") || fields.next().is_some() {
+        return None;
+    }
+
+    DateTime {
+        year,
+        month: MONTHS.iter().position(|name| *name == month)? as u8 + 1,
+        day,
+        hour,
+        minute,
+        second,
+    }
+    .to_unix()
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum BootstrapError<H> {
+    HttpError(H),
+    /// The response has no `Date` header, or its date cannot be parsed
+    NoDate,
+}
+
+impl_error! {
+    BootstrapError<H: Display> {
+        HttpError(e) => "HTTP error: {e}"; e.error_kind(),
+        NoDate => "No valid Date header"; ErrorKind::InvalidInput,
+    }
+}
+
+/// Returns the UTC time since the Unix epoch reported by the `Date` header of a HEAD request to `uri`.
+///
+/// Any status will do, as servers send the header with error responses too. Feed the result to
+/// `WallClock::approximate`.
+pub fn fetch_http_date<C>(
+    client: &mut Client<C>,
+    uri: &str,
+) -> Result<Duration, BootstrapError<C::Error>>
+where
+    C: Connection,
+{
+    let response = client
+        .request(Method::Head, uri, &[])
+        .and_then(|request| request.submit())
+        .map_err(BootstrapError::HttpError)?;
+
+    response
+        .header("Date")
+        .and_then(parse_http_date)
+        .map(Duration::from_secs)
+        .ok_or(BootstrapError::NoDate)
+}
+
+/// Hints about where the device is, to preset the regulatory domain and the local time until the user
+/// or the fleet configures them
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct RegionHints {
+    /// ISO 3166-1 alpha-2 code
+    pub country_code: Option<heapless::String<2>>,
+    /// From the DHCP PCode option (RFC 4833)
+    pub time_zone: Option<TimeZone>,
+    /// The IANA time zone name, e.g. `Europe/Berlin`, from the DHCP TCode option (RFC 4833)
+    pub time_zone_name: Option<heapless::String<32>>,
+}
+
+impl RegionHints {
+    /// The DHCP option carrying a POSIX TZ string
+    pub const PCODE: u8 = 100;
+    /// The DHCP option carrying an IANA time zone name
+    pub const TCODE: u8 = 101;
+
+    /// Picks the time zone options among the `(code, value)` options received from a DHCP server
+    pub fn from_dhcp_options<'a, I>(options: I) -> Self
+    where
+        I: IntoIterator<Item = (u8, &'a [u8])>,
+    {
+        let mut hints = Self::default();
+
+        for (code, value) in options {
+            let value = match core::str::from_utf8(value) {
+                Ok(value) => value.trim_end_matches('\0'),
+                Err(_) => continue,
+            };
+
+            match code {
+                Self::PCODE => hints.time_zone = TimeZone::from_str(value).ok(),
+                Self::TCODE => {
+                    let mut name = heapless::String::new();
+
+                    hints.time_zone_name = name.push_str(value).is_ok().then(|| name);
+                }
+                _ => (),
+            }
+        }
+
+        hints
+    }
+
+    /// Adopts the country of the Wi-Fi configuration, e.g. as advertised by the access point, unless it is
+    /// the world-safe mode
+    pub fn with_wifi(mut self, configuration: &wifi::Configuration) -> Self {
+        if let Some(country) = configuration.country() {
+            if country.country_code.as_str() != "01" {
+                self.country_code = Some(country.country_code.clone());
+            }
+        }
+
+        self
+    }
+}
@@ -0,0 +1,146 @@
+use core::fmt::Debug;
+use core::time::Duration;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::error::impl_error;
+use crate::storage::{RawStorage, SerDe, StorageError};
+use crate::sys_time::SystemTime;
+use crate::system::SystemControl;
+
+use super::schedule::Scheduler;
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum CheckpointError<S, C> {
+    StorageError(S),
+    SystemError(C),
+}
+
+impl_error! {
+    CheckpointError<S: Display, C: Debug> {
+        StorageError(e) => "Storage error: {e}"; e.error_kind(),
+        SystemError(e) => "System error: {e:?}"; e.error_kind(),
+    }
+}
+
+/// Keeps the state of the application across deep sleep, e.g. in RTC memory exposed as `RawStorage`,
+/// along with when the tasks of a `Scheduler` are due next.
+///
+/// The state is serialized with `serde` and should fit into `N` bytes.
+///
+/// A typical measurement loop restores the state with `wake`, runs the due tasks with `Scheduler::poll`,
+/// and calls `sleep` to deep sleep until the next task is due.
+pub struct Checkpoint<R, D, const N: usize = 256> {
+    storage: R,
+    serde: D,
+}
+
+impl<R, D, const N: usize> Checkpoint<R, D, N>
+where
+    R: RawStorage,
+    D: SerDe,
+{
+    const NAME: &'static str = "checkpoint";
+
+    pub const fn new(storage: R, serde: D) -> Self {
+        Self { storage, serde }
+    }
+
+    pub fn save<T>(&mut self, state: &T) -> Result<(), StorageError<R::Error, D::Error>>
+    where
+        T: Serialize,
+    {
+        let mut buf = [0_u8; N];
+
+        let data = self
+            .serde
+            .serialize(&mut buf, state)
+            .map_err(StorageError::SerdeError)?;
+
+        self.storage
+            .set_raw(Self::NAME, data)
+            .map_err(StorageError::RawStorageError)?;
+
+        Ok(())
+    }
+
+    /// Returns `None` on a cold boot, i.e. if no state was saved
+    pub fn restore<T>(&self) -> Result<Option<T>, StorageError<R::Error, D::Error>>
+    where
+        T: DeserializeOwned,
+    {
+        let mut buf = [0_u8; N];
+
+        match self
+            .storage
+            .get_raw(Self::NAME, &mut buf)
+            .map_err(StorageError::RawStorageError)?
+        {
+            Some(data) => self
+                .serde
+                .deserialize(data)
+                .map(Some)
+                .map_err(StorageError::SerdeError),
+            None => Ok(None),
+        }
+    }
+
+    pub fn clear(&mut self) -> Result<(), StorageError<R::Error, D::Error>> {
+        self.storage
+            .remove(Self::NAME)
+            .map_err(StorageError::RawStorageError)?;
+
+        Ok(())
+    }
+
+    /// Restores the state and when the tasks of `scheduler` are due, once they were added to it
+    pub fn wake<T, S, const M: usize>(
+        &self,
+        scheduler: &mut Scheduler<S, M>,
+    ) -> Result<Option<T>, StorageError<R::Error, D::Error>>
+    where
+        T: DeserializeOwned,
+        S: SystemTime,
+    {
+        scheduler
+            .restore(&self.storage)
+            .map_err(StorageError::RawStorageError)?;
+
+        self.restore()
+    }
+
+    /// Saves the state and when the tasks of `scheduler` are due, and deep sleeps until the next task is due,
+    /// for at most `max`.
+    ///
+    /// Only returns in case saving or entering deep sleep failed.
+    pub fn sleep<T, S, C, const M: usize>(
+        &mut self,
+        system: &mut C,
+        scheduler: &Scheduler<S, M>,
+        state: &T,
+        max: Duration,
+    ) -> CheckpointError<StorageError<R::Error, D::Error>, C::Error>
+    where
+        T: Serialize,
+        S: SystemTime,
+        C: SystemControl,
+    {
+        if let Err(e) = scheduler.save(&mut self.storage) {
+            return CheckpointError::StorageError(StorageError::RawStorageError(e));
+        }
+
+        if let Err(e) = self.save(state) {
+            return CheckpointError::StorageError(e);
+        }
+
+        let duration = scheduler.next_due().unwrap_or(max).min(max);
+
+        CheckpointError::SystemError(system.deep_sleep(duration))
+    }
+
+    pub fn release(self) -> (R, D) {
+        (self.storage, self.serde)
+    }
+}
@@ -0,0 +1,8 @@
+//! Allocation-free text encodings of binary data.
+//!
+//! Encoders write into any `fmt::Write`, e.g. a `heapless::String` or a `fmt::Formatter`, and decoders into
+//! a caller-provided buffer. Both can be fed incrementally, e.g. with the chunks of a body as it is
+//! received.
+
+pub mod base64;
+pub mod hex;
@@ -0,0 +1,267 @@
+//! Base64 (RFC 4648), with the standard and the URL-safe alphabets, padded or not.
+
+use core::fmt;
+
+pub const STANDARD: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Also known as base64url, as used by JWTs
+pub const URL_SAFE: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+/// The length of the encoding of `len` bytes
+pub const fn encoded_len(len: usize, pad: bool) -> usize {
+    if pad {
+        (len + 2) / 3 * 4
+    } else {
+        (len * 4 + 2) / 3
+    }
+}
+
+/// The maximum length of the data decoded from `len` characters
+pub const fn decoded_len(len: usize) -> usize {
+    len * 3 / 4
+}
+
+pub fn encode(data: &[u8], alphabet: &[u8; 64], pad: bool, w: &mut impl fmt::Write) -> fmt::Result {
+    let mut encoder = Encoder::new(alphabet, pad);
+
+    encoder.update(data, w)?;
+    encoder.finish(w)
+}
+
+/// Decodes `data` into `buf`, skipping whitespace, e.g. the line breaks of PEM.
+///
+/// Returns the length of the decoded data, or `None` if `data` is invalid or `buf` is too short.
+pub fn decode(data: &str, alphabet: &[u8; 64], buf: &mut [u8]) -> Option<usize> {
+    let mut decoder = Decoder::new(alphabet);
+
+    let len = decoder.update(data.as_bytes(), buf)?;
+
+    decoder.finish().then(|| len)
+}
+
+/// Encodes data fed in chunks of any length
+#[derive(Clone, Debug)]
+pub struct Encoder<'a> {
+    alphabet: &'a [u8; 64],
+    pad: bool,
+    pending: [u8; 3],
+    pending_len: usize,
+}
+
+impl<'a> Encoder<'a> {
+    pub const fn new(alphabet: &'a [u8; 64], pad: bool) -> Self {
+        Self {
+            alphabet,
+            pad,
+            pending: [0; 3],
+            pending_len: 0,
+        }
+    }
+
+    pub fn update(&mut self, mut data: &[u8], w: &mut impl fmt::Write) -> fmt::Result {
+        if self.pending_len > 0 {
+            let len = data.len().min(3 - self.pending_len);
+
+            self.pending[self.pending_len..self.pending_len + len].copy_from_slice(&data[..len]);
+            self.pending_len += len;
+            data = &data[len..];
+
+            if self.pending_len < 3 {
+                return Ok(());
+            }
+
+            self.pending_len = 0;
+            self.quantum(&self.pending, w)?;
+        }
+
+        let mut chunks = data.chunks_exact(3);
+
+        for chunk in &mut chunks {
+            self.quantum(chunk, w)?;
+        }
+
+        let rest = chunks.remainder();
+
+        self.pending[..rest.len()].copy_from_slice(rest);
+        self.pending_len = rest.len();
+
+        Ok(())
+    }
+
+    /// Writes the last, partial quantum and its padding
+    pub fn finish(self, w: &mut impl fmt::Write) -> fmt::Result {
+        self.quantum(&self.pending[..self.pending_len], w)
+    }
+
+    fn quantum(&self, chunk: &[u8], w: &mut impl fmt::Write) -> fmt::Result {
+        if chunk.is_empty() {
+            return Ok(());
+        }
+
+        let bits = chunk.iter().enumerate().fold(0_u32, |bits, (index, byte)| {
+            bits | ((*byte as u32) << (16 - index * 8))
+        });
+
+        for index in 0..4 {
+            if index <= chunk.len() {
+                let sextet = (bits >> (18 - index * 6)) & 0x3f;
+
+                w.write_char(self.alphabet[sextet as usize] as char)?;
+            } else if self.pad {
+                w.write_char('=')?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Decodes data fed in chunks of any length, padded or not, skipping whitespace
+#[derive(Clone, Debug)]
+pub struct Decoder<'a> {
+    alphabet: &'a [u8; 64],
+    bits: u32,
+    bit_count: u32,
+    padded: bool,
+}
+
+impl<'a> Decoder<'a> {
+    pub const fn new(alphabet: &'a [u8; 64]) -> Self {
+        Self {
+            alphabet,
+            bits: 0,
+            bit_count: 0,
+            padded: false,
+        }
+    }
+
+    /// Decodes `data` into `buf`, which needs at most `decoded_len(data.len()) + 1` bytes.
+    ///
+    /// Returns the length of the decoded data, or `None` if `data` is invalid or `buf` is too short.
+    pub fn update(&mut self, data: &[u8], buf: &mut [u8]) -> Option<usize> {
+        let mut len = 0;
+
+        for byte in data {
+            if byte.is_ascii_whitespace() {
+                continue;
+            }
+
+            if *byte == b'=' {
+                self.padded = true;
+                continue;
+            }
+
+            if self.padded {
+                return None;
+            }
+
+            let sextet = self.alphabet.iter().position(|c| c == byte)? as u32;
+
+            self.bits = (self.bits << 6) | sextet;
+            self.bit_count += 6;
+
+            if self.bit_count >= 8 {
+                self.bit_count -= 8;
+
+                *buf.get_mut(len)? = (self.bits >> self.bit_count) as u8;
+                len += 1;
+            }
+        }
+
+        Some(len)
+    }
+
+    /// Returns `false` if the data ended with a single character of a quantum, which cannot encode a byte
+    pub fn finish(self) -> bool {
+        self.bit_count != 6
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The test vectors of RFC 4648
+    const VECTORS: [(&str, &str); 7] = [
+        ("", ""),
+        ("f", "Zg=="),
+        ("fo", "Zm8="),
+        ("foo", "Zm9v"),
+        ("foob", "Zm9vYg=="),
+        ("fooba", "Zm9vYmE="),
+        ("foobar", "Zm9vYmFy"),
+    ];
+
+    #[test]
+    fn encoding() {
+        for (data, encoded) in VECTORS {
+            let mut padded = heapless::String::<16>::new();
+            encode(data.as_bytes(), STANDARD, true, &mut padded).unwrap();
+
+            let mut unpadded = heapless::String::<16>::new();
+            encode(data.as_bytes(), STANDARD, false, &mut unpadded).unwrap();
+
+            assert_eq!(padded, encoded);
+            assert_eq!(unpadded, encoded.trim_end_matches('='));
+            assert_eq!(encoded_len(data.len(), true), padded.len());
+            assert_eq!(encoded_len(data.len(), false), unpadded.len());
+        }
+
+        let mut url_safe = heapless::String::<4>::new();
+        encode(&[0xfb, 0xff], URL_SAFE, false, &mut url_safe).unwrap();
+
+        assert_eq!(url_safe, "-_8");
+    }
+
+    #[test]
+    fn chunks() {
+        let mut encoded = heapless::String::<16>::new();
+        let mut encoder = Encoder::new(STANDARD, true);
+
+        for chunk in [&b"f"[..], b"", b"oob", b"ar"] {
+            encoder.update(chunk, &mut encoded).unwrap();
+        }
+
+        encoder.finish(&mut encoded).unwrap();
+
+        assert_eq!(encoded, "Zm9vYmFy");
+
+        let mut buf = [0; 8];
+        let mut decoder = Decoder::new(STANDARD);
+        let mut len = 0;
+
+        for chunk in ["Zm", "9vYg", "="] {
+            len += decoder.update(chunk.as_bytes(), &mut buf[len..]).unwrap();
+        }
+
+        assert!(decoder.finish());
+        assert_eq!(&buf[..len], b"foob");
+    }
+
+    #[test]
+    fn decoding() {
+        for (data, encoded) in VECTORS {
+            let mut buf = [0; 8];
+
+            assert_eq!(decode(encoded, STANDARD, &mut buf), Some(data.len()));
+            assert_eq!(&buf[..data.len()], data.as_bytes());
+
+            assert_eq!(
+                decode(encoded.trim_end_matches('='), STANDARD, &mut buf),
+                Some(data.len())
+            );
+        }
+
+        let mut buf = [0; 8];
+
+        assert_eq!(decode("Zm9v\r\nYmFy\n", STANDARD, &mut buf), Some(6));
+        assert_eq!(decode("-_8", URL_SAFE, &mut buf), Some(2));
+        assert_eq!(&buf[..2], [0xfb, 0xff]);
+
+        // Invalid characters, data past the padding, a lone character and a too short buffer
+        assert_eq!(decode("-_8", STANDARD, &mut buf), None);
+        assert_eq!(decode("Zg==Zg==", STANDARD, &mut buf), None);
+        assert_eq!(decode("Zm9vY", STANDARD, &mut buf), None);
+        assert_eq!(decode("Zm9vYmFy", STANDARD, &mut buf[..5]), None);
+    }
+}
@@ -0,0 +1,131 @@
+//! Hexadecimal (base16), e.g. of digests, keys and device identifiers.
+//!
+//! Encoding is stateless, so data can be encoded in chunks by calling `encode` for each of them.
+
+use core::fmt;
+
+/// Lowercase, as most protocols and tools use
+pub const LOWER: &[u8; 16] = b"0123456789abcdef";
+
+pub const UPPER: &[u8; 16] = b"0123456789ABCDEF";
+
+pub fn encode(data: &[u8], alphabet: &[u8; 16], w: &mut impl fmt::Write) -> fmt::Result {
+    for byte in data {
+        w.write_char(alphabet[(byte >> 4) as usize] as char)?;
+        w.write_char(alphabet[(byte & 0x0f) as usize] as char)?;
+    }
+
+    Ok(())
+}
+
+/// Decodes `data` into `buf`, accepting either case and skipping whitespace and the `:` separators of
+/// fingerprints.
+///
+/// Returns the length of the decoded data, or `None` if `data` is invalid or `buf` is too short.
+pub fn decode(data: &str, buf: &mut [u8]) -> Option<usize> {
+    let mut decoder = Decoder::new();
+
+    let len = decoder.update(data.as_bytes(), buf)?;
+
+    decoder.finish().then(|| len)
+}
+
+/// Displays bytes as lowercase hex digits
+#[derive(Copy, Clone, Debug)]
+pub struct Hex<'a>(pub &'a [u8]);
+
+impl<'a> fmt::Display for Hex<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        encode(self.0, LOWER, f)
+    }
+}
+
+/// Decodes data fed in chunks of any length
+#[derive(Clone, Debug, Default)]
+pub struct Decoder {
+    high: Option<u8>,
+}
+
+impl Decoder {
+    pub const fn new() -> Self {
+        Self { high: None }
+    }
+
+    /// Decodes `data` into `buf`, which needs at most `data.len() / 2 + 1` bytes.
+    ///
+    /// Returns the length of the decoded data, or `None` if `data` is invalid or `buf` is too short.
+    pub fn update(&mut self, data: &[u8], buf: &mut [u8]) -> Option<usize> {
+        let mut len = 0;
+
+        for byte in data {
+            if byte.is_ascii_whitespace() || *byte == b':' {
+                continue;
+            }
+
+            let digit = (*byte as char).to_digit(16)? as u8;
+
+            match self.high.take() {
+                Some(high) => {
+                    *buf.get_mut(len)? = (high << 4) | digit;
+                    len += 1;
+                }
+                None => self.high = Some(digit),
+            }
+        }
+
+        Some(len)
+    }
+
+    /// Returns `false` if the data ended with a single digit
+    pub fn finish(self) -> bool {
+        self.high.is_none()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::fmt::Write;
+
+    use super::*;
+
+    #[test]
+    fn encoding() {
+        let mut encoded = heapless::String::<16>::new();
+
+        encode(&[0x00, 0x1f, 0xab], LOWER, &mut encoded).unwrap();
+        encode(&[0xcd], UPPER, &mut encoded).unwrap();
+
+        assert_eq!(encoded, "001fabCD");
+
+        encoded.clear();
+        write!(&mut encoded, "{}", Hex(&[0xde, 0xad])).unwrap();
+
+        assert_eq!(encoded, "dead");
+    }
+
+    #[test]
+    fn decoding() {
+        let mut buf = [0; 4];
+
+        assert_eq!(decode("001fAbcd", &mut buf), Some(4));
+        assert_eq!(buf, [0x00, 0x1f, 0xab, 0xcd]);
+
+        assert_eq!(decode("de:ad be\nef", &mut buf), Some(4));
+        assert_eq!(buf, [0xde, 0xad, 0xbe, 0xef]);
+
+        // A digit split across chunks
+        let mut decoder = Decoder::new();
+
+        assert_eq!(decoder.update(b"a", &mut buf), Some(0));
+        assert_eq!(decoder.update(b"bc", &mut buf), Some(1));
+        assert!(!decoder.clone().finish());
+        assert_eq!(decoder.update(b"d", &mut buf[1..]), Some(1));
+        assert!(decoder.finish());
+        assert_eq!(buf[..2], [0xab, 0xcd]);
+
+        // A lone digit, an invalid one and a too short buffer
+        assert_eq!(decode("abc", &mut buf), None);
+        assert_eq!(decode("0g", &mut buf), None);
+        assert_eq!(decode("0011223344", &mut buf), None);
+    }
+}
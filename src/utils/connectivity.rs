@@ -0,0 +1,84 @@
+use core::time::Duration;
+
+#[cfg(feature = "use_serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::event_bus::Postbox;
+use crate::http::client::{Client, Connection};
+use crate::http::Status;
+
+pub const DEFAULT_PROBE_URI: &str = "http://connectivitycheck.gstatic.com/generate_204";
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub enum Connectivity {
+    Online,
+    /// The probe was answered, but not with the expected 204 status,
+    /// which typically means that a captive portal intercepted it
+    CaptivePortal,
+    DnsFailure,
+    NoRoute,
+}
+
+/// Performs a generate_204-style probe against `uri`.
+///
+/// Since the HTTP client errors are backend-specific, `classify_error` is used to tell
+/// DNS failures apart from other connection failures.
+pub fn probe<C, F>(client: &mut Client<C>, uri: &str, classify_error: F) -> Connectivity
+where
+    C: Connection,
+    F: FnOnce(&C::Error) -> Connectivity,
+{
+    match client.get(uri).and_then(|request| request.submit()) {
+        Ok(response) if response.status() == 204 => Connectivity::Online,
+        Ok(_) => Connectivity::CaptivePortal,
+        Err(e) => classify_error(&e),
+    }
+}
+
+/// Probes periodically (as driven by the application) and reports connectivity changes on the event bus.
+pub struct ConnectivityMonitor<'a, P> {
+    postbox: P,
+    uri: &'a str,
+    last: Option<Connectivity>,
+}
+
+impl<'a, P> ConnectivityMonitor<'a, P>
+where
+    P: Postbox<Connectivity>,
+{
+    pub const fn new(postbox: P, uri: &'a str) -> Self {
+        Self {
+            postbox,
+            uri,
+            last: None,
+        }
+    }
+
+    /// The last connectivity state which was successfully posted
+    pub fn last(&self) -> Option<Connectivity> {
+        self.last
+    }
+
+    /// Posts the result of the probe whenever it differs from the result of the previous probe
+    pub fn check<C, F>(
+        &mut self,
+        client: &mut Client<C>,
+        classify_error: F,
+        wait: Option<Duration>,
+    ) -> Result<Connectivity, P::Error>
+    where
+        C: Connection,
+        F: FnOnce(&C::Error) -> Connectivity,
+    {
+        let connectivity = probe(client, self.uri, classify_error);
+
+        if self.last != Some(connectivity) && self.postbox.post(&connectivity, wait)? {
+            self.last = Some(connectivity);
+        }
+
+        Ok(connectivity)
+    }
+}
@@ -0,0 +1,124 @@
+use core::time::Duration;
+
+use crate::storage::StorageBase;
+use crate::sys_time::Instant;
+use crate::system::{RestartReason, SystemControl};
+use crate::wifi::{Configuration, Wifi};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum FactoryResetEvent {
+    StorageProgress { done: usize, total: usize },
+    WifiCleared,
+    OtaRollbackCleared,
+    Restarting,
+}
+
+/// Performs a factory reset step by step, reporting the progress to a callback
+/// which would typically forward the events to an event bus postbox or drive a status LED.
+///
+/// The usual sequence is to call `wipe_storage` once per storage namespace, then optionally
+/// `clear_wifi` and `clear_ota_rollback`, and finally `restart`.
+pub struct FactoryReset<F> {
+    progress: F,
+}
+
+impl<F> FactoryReset<F>
+where
+    F: FnMut(FactoryResetEvent),
+{
+    pub const fn new(progress: F) -> Self {
+        Self { progress }
+    }
+
+    /// Removes all `names` from `storage`, skipping the ones which are not present
+    pub fn wipe_storage<S>(&mut self, storage: &mut S, names: &[&str]) -> Result<(), S::Error>
+    where
+        S: StorageBase,
+    {
+        let total = names.len();
+
+        for (index, name) in names.iter().enumerate() {
+            storage.remove(name)?;
+
+            (self.progress)(FactoryResetEvent::StorageProgress {
+                done: index + 1,
+                total,
+            });
+        }
+
+        Ok(())
+    }
+
+    pub fn clear_wifi<W>(&mut self, wifi: &mut W) -> Result<(), W::Error>
+    where
+        W: Wifi,
+    {
+        wifi.set_configuration(&Configuration::None)?;
+
+        (self.progress)(FactoryResetEvent::WifiCleared);
+
+        Ok(())
+    }
+
+    /// Marks the running slot as valid, so that no rollback happens on the next boot
+    #[cfg(feature = "experimental")]
+    pub fn clear_ota_rollback<O>(&mut self, ota: &mut O) -> Result<(), O::Error>
+    where
+        O: crate::ota::Ota,
+    {
+        ota.mark_running_slot_valid()?;
+
+        (self.progress)(FactoryResetEvent::OtaRollbackCleared);
+
+        Ok(())
+    }
+
+    /// Only returns in case the restart failed
+    pub fn restart<C>(mut self, system: &mut C) -> C::Error
+    where
+        C: SystemControl,
+    {
+        (self.progress)(FactoryResetEvent::Restarting);
+
+        system.restart(RestartReason::FactoryReset)
+    }
+}
+
+/// Detects the long press of a button, which is the usual trigger of a factory reset.
+#[derive(Clone, Debug)]
+pub struct LongPress {
+    duration: Duration,
+    pressed_since: Option<Instant>,
+    fired: bool,
+}
+
+impl LongPress {
+    pub const fn new(duration: Duration) -> Self {
+        Self {
+            duration,
+            pressed_since: None,
+            fired: false,
+        }
+    }
+
+    /// Returns `true` once per press, as soon as the button had been held for long enough
+    pub fn update(&mut self, pressed: bool, now: Instant) -> bool {
+        if !pressed {
+            self.pressed_since = None;
+            self.fired = false;
+
+            return false;
+        }
+
+        let pressed_since = *self.pressed_since.get_or_insert(now);
+
+        if !self.fired && now.duration_since(pressed_since) >= self.duration {
+            self.fired = true;
+
+            true
+        } else {
+            false
+        }
+    }
+}
@@ -0,0 +1,157 @@
+#[cfg(feature = "use_serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::error::impl_error;
+use crate::ipv4;
+use crate::service::ServiceStatus;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub struct ServiceHealth {
+    pub name: heapless::String<16>,
+    pub status: ServiceStatus,
+}
+
+/// A uniform health payload, meant to be served over HTTP or published over MQTT.
+///
+/// Fields which do not apply to a product are left as `None`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub struct HealthReport<const N: usize = 4> {
+    pub uptime_secs: u64,
+    pub wifi_connected: Option<bool>,
+    #[cfg_attr(feature = "defmt", defmt(Debug2Format))]
+    pub ip: Option<ipv4::Ipv4Addr>,
+    pub mqtt_connected: Option<bool>,
+    /// Seconds since the UNIX epoch
+    pub last_time_sync: Option<u64>,
+    pub free_heap: Option<u32>,
+    pub min_free_heap: Option<u32>,
+    pub services: heapless::Vec<ServiceHealth, N>,
+}
+
+impl<const N: usize> HealthReport<N> {
+    pub fn is_healthy(&self) -> bool {
+        self.wifi_connected != Some(false)
+            && self.mqtt_connected != Some(false)
+            && self
+                .services
+                .iter()
+                .all(|service| service.status == ServiceStatus::Started)
+    }
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum HealthError<P, S> {
+    PublishError(P),
+    SerdeError(S),
+}
+
+impl_error! {
+    HealthError<P: Display, S: Display> {
+        PublishError(e) => "Publish error: {e}"; e.error_kind(),
+        SerdeError(e) => "SerDe error: {e}"; e.error_kind(),
+    }
+}
+
+#[cfg(feature = "use_serde")]
+pub mod mqtt {
+    use crate::mqtt::client::{MessageId, Publish, QoS};
+    use crate::storage::SerDe;
+
+    use super::{HealthError, HealthReport};
+
+    /// Publishes health reports on a fixed topic, serialized with `S` into a buffer of `B` bytes.
+    pub struct HealthPublisher<'a, P, S, const B: usize = 512> {
+        publisher: P,
+        serde: S,
+        topic: &'a str,
+    }
+
+    impl<'a, P, S, const B: usize> HealthPublisher<'a, P, S, B>
+    where
+        P: Publish,
+        S: SerDe,
+    {
+        pub const fn new(publisher: P, serde: S, topic: &'a str) -> Self {
+            Self {
+                publisher,
+                serde,
+                topic,
+            }
+        }
+
+        pub fn publish<const N: usize>(
+            &mut self,
+            report: &HealthReport<N>,
+        ) -> Result<MessageId, HealthError<P::Error, S::Error>> {
+            let mut buf = [0_u8; B];
+
+            let payload = self
+                .serde
+                .serialize(&mut buf, report)
+                .map_err(HealthError::SerdeError)?;
+
+            self.publisher
+                .publish(self.topic, QoS::AtMostOnce, true, payload)
+                .map_err(HealthError::PublishError)
+        }
+
+        pub fn release(self) -> (P, S) {
+            (self.publisher, self.serde)
+        }
+    }
+}
+
+#[cfg(all(feature = "use_serde", feature = "experimental"))]
+pub mod server {
+    use crate::http::server::{Connection, Handler, HandlerResult, Request};
+    use crate::io::Write;
+    use crate::storage::SerDe;
+
+    use super::HealthReport;
+
+    /// Serves the report produced by `F`, serialized with `S` into a buffer of `B` bytes.
+    ///
+    /// Responds with 200 when the report is healthy and with 503 otherwise.
+    pub struct HealthHandler<F, S, const B: usize = 512> {
+        report: F,
+        serde: S,
+        content_type: &'static str,
+    }
+
+    impl<F, S, const B: usize> HealthHandler<F, S, B> {
+        pub const fn new(report: F, serde: S, content_type: &'static str) -> Self {
+            Self {
+                report,
+                serde,
+                content_type,
+            }
+        }
+    }
+
+    impl<C, F, S, const N: usize, const B: usize> Handler<C> for HealthHandler<F, S, B>
+    where
+        C: Connection,
+        F: Fn() -> HealthReport<N> + Send,
+        S: SerDe + Send,
+    {
+        fn handle(&self, connection: &mut C) -> HandlerResult {
+            let report = (self.report)();
+
+            let mut buf = [0_u8; B];
+            let payload = self.serde.serialize(&mut buf, &report)?;
+
+            let status = if report.is_healthy() { 200 } else { 503 };
+
+            Request::wrap(connection)
+                .into_response(status, None, &[("Content-Type", self.content_type)])?
+                .write_all(payload)?;
+
+            Ok(())
+        }
+    }
+}
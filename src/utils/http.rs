@@ -1,3 +1,6 @@
+pub mod download;
+pub mod upload;
+
 use core::str;
 
 #[derive(Debug)]
@@ -264,6 +267,14 @@ pub mod cookies {
 }
 
 pub mod server {
+    pub mod conditional;
+    pub mod host;
+    pub mod long_poll;
+    #[cfg(feature = "http_openapi")]
+    pub mod openapi;
+    pub mod path;
+    pub mod range;
+
     pub mod registration {
         use crate::http::Method;
 
@@ -365,6 +376,7 @@ pub mod server {
         use core::fmt;
         use core::time::Duration;
 
+        use crate::error::{Classify, ErrorKind};
         use crate::http::server::*;
 
         use crate::utils::http::cookies::*;
@@ -389,6 +401,12 @@ pub mod server {
         #[cfg(feature = "std")]
         impl std::error::Error for SessionError {}
 
+        impl Classify for SessionError {
+            fn error_kind(&self) -> ErrorKind {
+                ErrorKind::Unavailable
+            }
+        }
+
         pub trait Session: Send {
             type SessionData;
 
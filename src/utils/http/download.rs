@@ -0,0 +1,317 @@
+use core::fmt::Write as _;
+
+use crate::crypto::Sha256;
+use crate::error::{impl_error, ErrorKind};
+use crate::http::client::{Client, Connection};
+use crate::http::{Headers, Status};
+use crate::io::{Read, Write};
+use crate::storage::RawStorage;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum DownloadEvent<'a> {
+    /// The response headers were received; `resumed` is true when the server honoured the `Range` request
+    Started {
+        offset: u64,
+        total: Option<u64>,
+        resumed: bool,
+        etag: Option<&'a str>,
+    },
+    Progress {
+        downloaded: u64,
+        total: Option<u64>,
+    },
+    Completed {
+        len: u64,
+    },
+}
+
+/// Resumption of an interrupted download, via a `Range` request
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Resume<'a> {
+    /// Number of bytes which were already written to the sink (and fed to the hasher)
+    pub offset: u64,
+    /// The ETag reported when the download was started; used with `If-Range` so that
+    /// the server sends the whole document if it has changed in the meantime
+    pub etag: Option<&'a str>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Downloaded {
+    pub len: u64,
+    pub sha256: [u8; 32],
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum DownloadError<H, W> {
+    HttpError(H),
+    WriteError(W),
+    Status(u16),
+    /// The server sent the whole document, or another range than the requested one, in response
+    /// to a `Range` request and it cannot be restarted, because the sink already contains data
+    ResumeRejected,
+    TooLarge,
+    ChecksumMismatch,
+}
+
+impl_error! {
+    DownloadError<H: Display, W: Display> {
+        HttpError(e) => "HTTP error: {e}"; e.error_kind(),
+        WriteError(e) => "Write error: {e}"; e.error_kind(),
+        Status(status) => "Unexpected HTTP status: {status}"; ErrorKind::from_status(*status),
+        ResumeRejected => "Download cannot be resumed"; ErrorKind::Other,
+        TooLarge => "Download too large"; ErrorKind::InvalidInput,
+        ChecksumMismatch => "SHA-256 checksum mismatch"; ErrorKind::InvalidInput,
+    }
+}
+
+/// Streams the document at `uri` into `write`, hashing it on the fly and verifying it against
+/// `expected_sha256`, if provided.
+///
+/// When resuming, the data already in the sink must have been fed to `hasher` beforehand,
+/// so that the checksum covers the whole document. If the server ignores the `Range` request, or
+/// answers it with a range not starting at the offset, the download fails with `DownloadError::ResumeRejected` and should be restarted from scratch.
+#[allow(clippy::too_many_arguments)]
+pub fn download<C, W, H, F>(
+    client: &mut Client<C>,
+    uri: &str,
+    resume: Option<&Resume<'_>>,
+    mut write: W,
+    mut hasher: H,
+    expected_sha256: Option<&[u8; 32]>,
+    buf: &mut [u8],
+    mut progress: F,
+) -> Result<Downloaded, DownloadError<C::Error, W::Error>>
+where
+    C: Connection,
+    W: Write,
+    H: Sha256,
+    F: FnMut(DownloadEvent<'_>),
+{
+    let offset = resume.map(|resume| resume.offset).unwrap_or(0);
+
+    let mut range = heapless::String::<32>::new();
+    let mut headers = heapless::Vec::<_, 2>::new();
+
+    if let Some(resume) = resume.filter(|resume| resume.offset > 0) {
+        write!(&mut range, "bytes={}-", resume.offset).unwrap();
+        headers.push(("Range", range.as_str())).unwrap();
+
+        if let Some(etag) = resume.etag {
+            headers.push(("If-Range", etag)).unwrap();
+        }
+    }
+
+    let mut response = client
+        .request(crate::http::Method::Get, uri, &headers)
+        .and_then(|request| request.submit())
+        .map_err(DownloadError::HttpError)?;
+
+    let resumed = match response.status() {
+        200 if offset > 0 => return Err(DownloadError::ResumeRejected),
+        200 => false,
+        206 if offset > 0 => {
+            // A server might serve another range than the requested one, e.g. one aligned to its blocks
+            if response
+                .header("Content-Range")
+                .and_then(content_range_start)
+                != Some(offset)
+            {
+                return Err(DownloadError::ResumeRejected);
+            }
+
+            true
+        }
+        status => return Err(DownloadError::Status(status)),
+    };
+
+    let total = total_len(&response, offset);
+
+    progress(DownloadEvent::Started {
+        offset,
+        total,
+        resumed,
+        etag: response.header("ETag"),
+    });
+
+    let mut downloaded = offset;
+
+    loop {
+        let len = response.read(buf).map_err(DownloadError::HttpError)?;
+        if len == 0 {
+            break;
+        }
+
+        hasher.update(&buf[..len]);
+        write
+            .write_all(&buf[..len])
+            .map_err(DownloadError::WriteError)?;
+
+        downloaded += len as u64;
+
+        progress(DownloadEvent::Progress { downloaded, total });
+    }
+
+    write.flush().map_err(DownloadError::WriteError)?;
+
+    let downloaded = verify(downloaded, hasher, expected_sha256)?;
+
+    progress(DownloadEvent::Completed {
+        len: downloaded.len,
+    });
+
+    Ok(downloaded)
+}
+
+/// Downloads the document at `uri` into `buf` and then stores it as a single blob named `name`.
+///
+/// Since `RawStorage` blobs are written atomically, the blob is only replaced once the download
+/// is complete and verified; `buf` must therefore be large enough for the whole document.
+#[allow(clippy::too_many_arguments)]
+pub fn download_to_storage<C, S, H, F>(
+    client: &mut Client<C>,
+    uri: &str,
+    storage: &mut S,
+    name: &str,
+    mut hasher: H,
+    expected_sha256: Option<&[u8; 32]>,
+    buf: &mut [u8],
+    mut progress: F,
+) -> Result<Downloaded, DownloadError<C::Error, S::Error>>
+where
+    C: Connection,
+    S: RawStorage,
+    H: Sha256,
+    F: FnMut(DownloadEvent<'_>),
+{
+    let mut response = client
+        .get(uri)
+        .and_then(|request| request.submit())
+        .map_err(DownloadError::HttpError)?;
+
+    if response.status() != 200 {
+        return Err(DownloadError::Status(response.status()));
+    }
+
+    let total = total_len(&response, 0);
+
+    if total.map(|total| total > buf.len() as u64).unwrap_or(false) {
+        return Err(DownloadError::TooLarge);
+    }
+
+    progress(DownloadEvent::Started {
+        offset: 0,
+        total,
+        resumed: false,
+        etag: response.header("ETag"),
+    });
+
+    let mut downloaded = 0;
+
+    loop {
+        if downloaded == buf.len() {
+            // Make sure the document is not larger than the buffer
+            let mut probe = [0_u8; 1];
+
+            if response
+                .read(&mut probe)
+                .map_err(DownloadError::HttpError)?
+                > 0
+            {
+                return Err(DownloadError::TooLarge);
+            }
+
+            break;
+        }
+
+        let len = response
+            .read(&mut buf[downloaded..])
+            .map_err(DownloadError::HttpError)?;
+        if len == 0 {
+            break;
+        }
+
+        hasher.update(&buf[downloaded..downloaded + len]);
+
+        downloaded += len;
+
+        progress(DownloadEvent::Progress {
+            downloaded: downloaded as u64,
+            total,
+        });
+    }
+
+    let data = &buf[..downloaded];
+    let downloaded = verify(downloaded as u64, hasher, expected_sha256)?;
+
+    storage
+        .set_raw(name, data)
+        .map_err(DownloadError::WriteError)?;
+
+    progress(DownloadEvent::Completed {
+        len: downloaded.len,
+    });
+
+    Ok(downloaded)
+}
+
+fn total_len<H>(headers: &H, offset: u64) -> Option<u64>
+where
+    H: Headers,
+{
+    headers
+        .header("Content-Range")
+        .and_then(|range| range.rsplit_once('/'))
+        .and_then(|(_, total)| total.trim().parse::<u64>().ok())
+        .or_else(|| headers.content_len().map(|len| offset + len))
+}
+
+/// The first byte of a `Content-Range: bytes <start>-<end>/<total>` header
+fn content_range_start(range: &str) -> Option<u64> {
+    let (unit, range) = range.trim().split_once(' ')?;
+    if !unit.eq_ignore_ascii_case("bytes") {
+        return None;
+    }
+
+    let (start, _) = range.trim_start().split_once('-')?;
+
+    start.parse::<u64>().ok()
+}
+
+fn verify<H, E1, E2>(
+    len: u64,
+    hasher: H,
+    expected_sha256: Option<&[u8; 32]>,
+) -> Result<Downloaded, DownloadError<E1, E2>>
+where
+    H: Sha256,
+{
+    let sha256 = hasher.finish();
+
+    if expected_sha256
+        .map(|expected| *expected != sha256)
+        .unwrap_or(false)
+    {
+        Err(DownloadError::ChecksumMismatch)
+    } else {
+        Ok(Downloaded { len, sha256 })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn content_range() {
+        assert_eq!(content_range_start("bytes 1024-2047/4096"), Some(1024));
+        assert_eq!(content_range_start("bytes 0-99/*"), Some(0));
+        assert_eq!(content_range_start(" Bytes  512-1023/1024"), Some(512));
+        assert_eq!(content_range_start("bytes */4096"), None);
+        assert_eq!(content_range_start("items 0-9/10"), None);
+        assert_eq!(content_range_start("bytes"), None);
+    }
+}
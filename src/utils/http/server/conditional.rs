@@ -0,0 +1,271 @@
+use core::fmt::{self, Display, Write as _};
+use core::hash::{Hash, Hasher};
+
+use crate::http::server::{Connection, HandlerError, HandlerResult};
+use crate::http::Headers;
+use crate::sys_time;
+
+const DAYS: [&str; 7] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// A weak ETag for `value`; it changes whenever the hash of `value` does, and is stable across restarts
+/// and builds of the firmware, as it is computed with FNV-1a
+pub fn etag<T: Hash + ?Sized>(value: &T) -> heapless::String<20> {
+    let mut hasher = Fnv1a::default();
+
+    value.hash(&mut hasher);
+
+    let mut etag = heapless::String::new();
+
+    // `W/"` followed by 16 hex digits and `"` is exactly 20 bytes
+    write!(&mut etag, "W/\"{:016x}\"", hasher.finish()).unwrap();
+
+    etag
+}
+
+/// Whether the `If-None-Match` header lists `etag`, using the weak comparison
+pub fn if_none_match<H: Headers>(headers: &H, etag: &str) -> bool {
+    headers
+        .header("If-None-Match")
+        .map(|tags| {
+            tags.split(',').any(|tag| {
+                let tag = tag.trim();
+
+                tag == "*" || strip_weak(tag) == strip_weak(etag)
+            })
+        })
+        .unwrap_or(false)
+}
+
+/// Whether the resource changed after the `If-Modified-Since` header;
+/// `true` without the header or when it cannot be parsed
+pub fn is_modified_since<H: Headers>(headers: &H, last_modified: u64) -> bool {
+    headers
+        .header("If-Modified-Since")
+        .and_then(parse_http_date)
+        .map(|since| last_modified > since)
+        .unwrap_or(true)
+}
+
+/// Whether the client has the current representation, evaluating the conditional headers of a `GET`
+/// or `HEAD` request as per RFC 7232 section 6, where `If-None-Match` takes precedence
+pub fn is_fresh<H: Headers>(headers: &H, etag: Option<&str>, last_modified: Option<u64>) -> bool {
+    if headers.header("If-None-Match").is_some() {
+        etag.map(|etag| if_none_match(headers, etag))
+            .unwrap_or(false)
+    } else {
+        last_modified
+            .map(|last_modified| !is_modified_since(headers, last_modified))
+            .unwrap_or(false)
+    }
+}
+
+/// Responds with 304 if the client has the current representation, see `is_fresh`,
+/// returning whether it did
+pub fn not_modified<C: Connection>(
+    connection: &mut C,
+    etag: Option<&str>,
+    last_modified: Option<u64>,
+) -> Result<bool, HandlerError> {
+    if !is_fresh(connection, etag, last_modified) {
+        return Ok(false);
+    }
+
+    let mut date = heapless::String::<32>::new();
+
+    if let Some(last_modified) = last_modified {
+        write!(&mut date, "{}", HttpDate(last_modified))?;
+    }
+
+    let headers = [
+        ("ETag", etag),
+        ("Last-Modified", last_modified.map(|_| &*date)),
+    ];
+    let headers = headers
+        .iter()
+        .filter_map(|(name, value)| value.map(|value| (*name, value)))
+        .collect::<heapless::Vec<_, 2>>();
+
+    connection.initiate_response(304, None, &headers)?;
+
+    Ok(true)
+}
+
+/// Responds with 304 if the client already has the representation of `state`, and with `f` otherwise,
+/// which gets the ETag of `state` to send along with its response.
+///
+/// Meant for frequently polled endpoints, as `f` e.g. serializes `state` only when it changed.
+pub fn respond<C, T, F>(connection: &mut C, state: &T, f: F) -> HandlerResult
+where
+    C: Connection,
+    T: Hash + ?Sized,
+    F: FnOnce(&mut C, &str) -> HandlerResult,
+{
+    let etag = etag(state);
+
+    if not_modified(connection, Some(&etag), None)? {
+        Ok(())
+    } else {
+        f(connection, &etag)
+    }
+}
+
+/// Seconds since the Unix epoch, displayed as an IMF-fixdate, e.g. `Sun, 06 Nov 1994 08:49:37 GMT`
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct HttpDate(pub u64);
+
+impl Display for HttpDate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let date = sys_time::DateTime::from_unix(self.0);
+
+        write!(
+            f,
+            "{}, {:02} {} {} {:02}:{:02}:{:02} GMT",
+            DAYS[date.weekday() as usize],
+            date.day,
+            MONTHS[date.month as usize - 1],
+            date.year,
+            date.hour,
+            date.minute,
+            date.second
+        )
+    }
+}
+
+/// Parses an IMF-fixdate into seconds since the Unix epoch; the obsolete formats are not supported
+pub fn parse_http_date(date: &str) -> Option<u64> {
+    let mut parts = date.trim().split(' ');
+
+    let _day_name = parts.next()?.strip_suffix(',')?;
+    let day = parts.next()?.parse().ok()?;
+    let month = parts.next()?;
+    let month = MONTHS.iter().position(|name| *name == month)? as u8 + 1;
+    let year = parts.next()?.parse().ok()?;
+
+    let mut time = parts.next()?.split(':');
+    let hour = time.next()?.parse().ok()?;
+    let minute = time.next()?.parse().ok()?;
+    let second = time.next()?.parse().ok()?;
+
+    if time.next().is_some() || parts.next()? != "GMT" || parts.next().is_some() {
+        return None;
+    }
+
+    // Validates the day against the month, so that e.g. 31 Feb is refused rather than rolled over
+    sys_time::DateTime {
+        year,
+        month,
+        day,
+        hour,
+        minute,
+        second,
+    }
+    .to_unix()
+}
+
+fn strip_weak(etag: &str) -> &str {
+    etag.strip_prefix("W/").unwrap_or(etag)
+}
+
+struct Fnv1a(u64);
+
+impl Default for Fnv1a {
+    fn default() -> Self {
+        Self(0xcbf29ce484222325)
+    }
+}
+
+impl Hasher for Fnv1a {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 = (self.0 ^ *byte as u64).wrapping_mul(0x100000001b3);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Request(&'static [(&'static str, &'static str)]);
+
+    impl Headers for Request {
+        fn header(&self, name: &str) -> Option<&'_ str> {
+            self.0
+                .iter()
+                .find(|(header, _)| header.eq_ignore_ascii_case(name))
+                .map(|(_, value)| *value)
+        }
+    }
+
+    #[test]
+    fn http_date() {
+        let mut formatted = heapless::String::<32>::new();
+
+        write!(&mut formatted, "{}", HttpDate(784111777)).unwrap();
+
+        assert_eq!(formatted, "Sun, 06 Nov 1994 08:49:37 GMT");
+        assert_eq!(
+            parse_http_date("Sun, 06 Nov 1994 08:49:37 GMT"),
+            Some(784111777)
+        );
+        assert_eq!(
+            parse_http_date("Thu, 29 Feb 2024 00:00:00 GMT"),
+            Some(1709164800)
+        );
+
+        for date in [
+            "Fri, 31 Feb 2023 00:00:00 GMT",
+            "Wed, 29 Feb 2023 00:00:00 GMT",
+            "Sat, 31 Jun 2023 00:00:00 GMT",
+            "Sat, 31 Dec 2016 23:59:60 GMT",
+            "Sun, 06 Nov 1994 24:00:00 GMT",
+            "Sun, 06 Nov 1994 08:49:37:00 GMT",
+            "Sun, 06 Nov 1994 08:49:37 UTC",
+        ] {
+            assert_eq!(parse_http_date(date), None, "{}", date);
+        }
+    }
+
+    #[test]
+    fn fresh() {
+        let etag = etag("state");
+
+        assert_eq!(etag.len(), 20);
+        assert_ne!(etag, super::etag("other state"));
+
+        assert!(!is_fresh(&Request(&[]), Some(&etag), Some(784111777)));
+        assert!(is_fresh(
+            &Request(&[("if-none-match", "\"a\", \"b\"")]),
+            Some("W/\"b\""),
+            None
+        ));
+        assert!(is_fresh(
+            &Request(&[("If-Modified-Since", "Sun, 06 Nov 1994 08:49:37 GMT")]),
+            None,
+            Some(784111777)
+        ));
+        assert!(!is_fresh(
+            &Request(&[("If-Modified-Since", "Sun, 06 Nov 1994 08:49:37 GMT")]),
+            None,
+            Some(784111778)
+        ));
+
+        // If-None-Match takes precedence
+        assert!(!is_fresh(
+            &Request(&[
+                ("If-None-Match", "\"a\""),
+                ("If-Modified-Since", "Sun, 06 Nov 1994 08:49:37 GMT")
+            ]),
+            Some("\"b\""),
+            Some(784111777)
+        ));
+    }
+}
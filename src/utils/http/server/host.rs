@@ -0,0 +1,108 @@
+use crate::http::server::{Connection, Handler, HandlerResult, Middleware};
+
+/// The host the request is for, without the port: from the `Host` header or, for clients
+/// not sending one, from the SNI name
+pub fn host<C>(connection: &C) -> Option<&str>
+where
+    C: Connection,
+{
+    connection
+        .header("Host")
+        .map(strip_port)
+        .or_else(|| connection.server_name())
+}
+
+/// Whether the request is for `expected`, compared case-insensitively
+pub fn is_for<C>(connection: &C, expected: &str) -> bool
+where
+    C: Connection,
+{
+    host(connection)
+        .map(|host| host.eq_ignore_ascii_case(expected))
+        .unwrap_or(false)
+}
+
+/// Serves the requests for `host` with the handler, and those for any other host with `fallback`,
+/// e.g. to serve a captive portal and the device pages at the same URI of the same listener.
+///
+/// Nest these to serve more hosts, i.e. `HostHandler::new("a.local", a, HostHandler::new("b.local", b, other))`
+pub struct HostHandler<H, F> {
+    host: &'static str,
+    handler: H,
+    fallback: F,
+}
+
+impl<H, F> HostHandler<H, F> {
+    pub const fn new(host: &'static str, handler: H, fallback: F) -> Self {
+        Self {
+            host,
+            handler,
+            fallback,
+        }
+    }
+}
+
+impl<C, H, F> Handler<C> for HostHandler<H, F>
+where
+    C: Connection,
+    H: Handler<C>,
+    F: Handler<C>,
+{
+    fn handle(&self, connection: &mut C) -> HandlerResult {
+        if is_for(connection, self.host) {
+            self.handler.handle(connection)
+        } else {
+            self.fallback.handle(connection)
+        }
+    }
+}
+
+/// Applies the middleware to the requests for `host` only, passing the others straight to the handler
+pub struct HostMiddleware<M> {
+    host: &'static str,
+    middleware: M,
+}
+
+impl<M> HostMiddleware<M> {
+    pub const fn new(host: &'static str, middleware: M) -> Self {
+        Self { host, middleware }
+    }
+}
+
+impl<C, M> Middleware<C> for HostMiddleware<M>
+where
+    C: Connection,
+    M: Middleware<C>,
+{
+    fn handle<'a, H>(&'a self, connection: &'a mut C, handler: &'a H) -> HandlerResult
+    where
+        H: Handler<C>,
+    {
+        if is_for(connection, self.host) {
+            self.middleware.handle(connection, handler)
+        } else {
+            handler.handle(connection)
+        }
+    }
+}
+
+fn strip_port(host: &str) -> &str {
+    match host.rfind(':') {
+        // The colons of an IPv6 literal are within its brackets
+        Some(index) if !host[index..].contains(']') => &host[..index],
+        _ => host,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn port() {
+        assert_eq!(strip_port("portal.local"), "portal.local");
+        assert_eq!(strip_port("portal.local:8080"), "portal.local");
+        assert_eq!(strip_port("[fe80::1]"), "[fe80::1]");
+        assert_eq!(strip_port("[fe80::1]:80"), "[fe80::1]");
+    }
+}
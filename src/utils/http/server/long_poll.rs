@@ -0,0 +1,298 @@
+use core::fmt::Write as _;
+use core::ops::Deref;
+use core::time::Duration;
+
+use serde::Serialize;
+
+use crate::event_bus::EventBus;
+use crate::http::server::{Connection, Handler, HandlerResult};
+use crate::storage::SerDe;
+use crate::utils::mutex::{Condvar, Mutex, RawCondvar};
+
+/// The header carrying the sequence number of the event in the response,
+/// which clients pass back with the `since` query parameter
+pub const SEQUENCE_HEADER: &str = "X-Sequence";
+
+/// How often parked requests check whether their client went away
+const CLOSE_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Wait<T> {
+    /// The latest event and its sequence number
+    Event(u64, T),
+    /// No new event arrived before the timeout; carries the current sequence number
+    TimedOut(u64),
+    Closed,
+}
+
+struct Slot<T> {
+    sequence: u64,
+    value: Option<T>,
+    closed: bool,
+}
+
+/// Parks long-poll requests until an event newer than what the client has seen arrives.
+///
+/// Only the latest event is kept, so clients polling less often than events arrive skip events.
+pub struct LongPoll<CV, T>
+where
+    CV: RawCondvar,
+{
+    slot: Mutex<CV::RawMutex, Slot<T>>,
+    notified: Condvar<CV>,
+}
+
+impl<CV, T> LongPoll<CV, T>
+where
+    CV: RawCondvar,
+    T: Clone,
+{
+    pub fn new() -> Self {
+        Self {
+            slot: Mutex::new(Slot {
+                sequence: 0,
+                value: None,
+                closed: false,
+            }),
+            notified: Condvar::new(),
+        }
+    }
+
+    /// Wakes up the parked requests with `value`; also usable to signal a condition becoming true
+    pub fn notify(&self, value: T) {
+        let mut slot = self.slot.lock();
+
+        slot.sequence += 1;
+        slot.value = Some(value);
+
+        self.notified.notify_all();
+    }
+
+    /// Notifies every event posted to `bus` to `long_poll`, e.g. an `Arc<LongPoll>` or a `&'static LongPoll`,
+    /// for as long as the returned subscription is kept
+    pub fn subscribe<L, B>(long_poll: L, bus: &B) -> Result<B::Subscription, B::Error>
+    where
+        L: Deref<Target = Self> + Send + 'static,
+        B: EventBus<T>,
+    {
+        bus.subscribe(move |event: &T| long_poll.notify(event.clone()))
+    }
+
+    /// Releases the parked requests and makes new ones return immediately, e.g. before the server stops
+    pub fn close(&self) {
+        self.slot.lock().closed = true;
+
+        self.notified.notify_all();
+    }
+
+    /// Waits up to `timeout` for an event with a sequence number greater than `since`
+    pub fn wait(&self, since: u64, timeout: Duration) -> Wait<T> {
+        // Without a client to lose, the wait cannot be abandoned
+        self.wait_or_abandon(since, timeout, || false)
+            .unwrap_or(Wait::Closed)
+    }
+
+    /// Like `wait`, but returns `None` as soon as `abandoned` returns true, which is checked
+    /// every `CLOSE_POLL_INTERVAL`
+    fn wait_or_abandon<A>(&self, since: u64, timeout: Duration, abandoned: A) -> Option<Wait<T>>
+    where
+        A: Fn() -> bool,
+    {
+        let mut remaining = timeout;
+
+        let mut slot = self.slot.lock();
+
+        loop {
+            if slot.closed {
+                return Some(Wait::Closed);
+            }
+
+            if slot.sequence > since {
+                if let Some(value) = &slot.value {
+                    return Some(Wait::Event(slot.sequence, value.clone()));
+                }
+            }
+
+            if remaining == Duration::ZERO {
+                return Some(Wait::TimedOut(slot.sequence));
+            }
+
+            if abandoned() {
+                return None;
+            }
+
+            let interval = remaining.min(CLOSE_POLL_INTERVAL);
+
+            let (guard, timed_out) = self.notified.wait_timeout(slot, interval);
+            slot = guard;
+
+            // Without a clock, the time spent in spurious wakeups is not accounted for
+            if timed_out {
+                remaining -= interval;
+            }
+        }
+    }
+}
+
+impl<CV, T> Default for LongPoll<CV, T>
+where
+    CV: RawCondvar,
+    T: Clone,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Parks the requests up to `timeout`, then responds with the new event serialized with `S` into
+/// a buffer of `B` bytes, with 204 if there is none, or with 503 and `Connection: close` once closed.
+///
+/// Clients pass the `SEQUENCE_HEADER` of the previous response as the `since` query parameter;
+/// without it they get the latest event right away, if there is one. The timeout should be below
+/// the idle timeout of the proxies between the clients and the device. Requests whose client closes
+/// the connection while they are parked are released right away, see `Connection::is_closed`.
+pub struct LongPollHandler<L, S, const B: usize = 512> {
+    long_poll: L,
+    timeout: Duration,
+    serde: S,
+    content_type: &'static str,
+}
+
+impl<L, S, const B: usize> LongPollHandler<L, S, B> {
+    pub const fn new(
+        long_poll: L,
+        timeout: Duration,
+        serde: S,
+        content_type: &'static str,
+    ) -> Self {
+        Self {
+            long_poll,
+            timeout,
+            serde,
+            content_type,
+        }
+    }
+}
+
+impl<C, L, CV, T, S, const B: usize> Handler<C> for LongPollHandler<L, S, B>
+where
+    C: Connection,
+    L: Deref<Target = LongPoll<CV, T>> + Send,
+    CV: RawCondvar,
+    T: Clone + Serialize,
+    S: SerDe + Send,
+{
+    fn handle(&self, connection: &mut C) -> HandlerResult {
+        let since = since(connection.uri()).unwrap_or(0);
+
+        let wait = self
+            .long_poll
+            .wait_or_abandon(since, self.timeout, || connection.is_closed());
+
+        let mut sequence = heapless::String::<20>::new();
+
+        match wait {
+            // The response cannot be delivered anyway
+            None => connection.initiate_response(204, None, &[("Connection", "close")])?,
+            Some(Wait::Event(current, value)) => {
+                write!(&mut sequence, "{}", current)?;
+
+                let mut buf = [0_u8; B];
+                let payload = self.serde.serialize(&mut buf, &value)?;
+
+                connection.initiate_response(
+                    200,
+                    None,
+                    &[
+                        ("Content-Type", self.content_type),
+                        (SEQUENCE_HEADER, &sequence),
+                        ("Cache-Control", "no-store"),
+                    ],
+                )?;
+                connection.write_all(payload)?;
+            }
+            Some(Wait::TimedOut(current)) => {
+                write!(&mut sequence, "{}", current)?;
+
+                connection.initiate_response(
+                    204,
+                    None,
+                    &[(SEQUENCE_HEADER, &sequence), ("Cache-Control", "no-store")],
+                )?;
+            }
+            Some(Wait::Closed) => connection.initiate_response(
+                503,
+                None,
+                &[("Connection", "close"), ("Cache-Control", "no-store")],
+            )?,
+        }
+
+        Ok(())
+    }
+}
+
+fn since(uri: &str) -> Option<u64> {
+    uri.split_once('?')?
+        .1
+        .split('&')
+        .find_map(|param| param.strip_prefix("since="))
+        .and_then(|since| since.parse().ok())
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use std::sync::Arc;
+    use std::thread;
+
+    use crate::utils::mutex::StdRawCondvar;
+
+    use super::*;
+
+    const TIMEOUT: Duration = Duration::from_millis(20);
+
+    #[test]
+    fn wait() {
+        let long_poll = Arc::new(LongPoll::<StdRawCondvar, u32>::new());
+
+        assert_eq!(long_poll.wait(0, TIMEOUT), Wait::TimedOut(0));
+
+        long_poll.notify(7);
+
+        assert_eq!(long_poll.wait(0, TIMEOUT), Wait::Event(1, 7));
+        assert_eq!(long_poll.wait(1, TIMEOUT), Wait::TimedOut(1));
+
+        let notifier = {
+            let long_poll = long_poll.clone();
+
+            thread::spawn(move || {
+                thread::sleep(TIMEOUT);
+                long_poll.notify(8);
+            })
+        };
+
+        assert_eq!(
+            long_poll.wait(1, Duration::from_secs(60)),
+            Wait::Event(2, 8)
+        );
+
+        notifier.join().unwrap();
+
+        assert_eq!(
+            long_poll.wait_or_abandon(2, Duration::from_secs(60), || true),
+            None
+        );
+
+        long_poll.close();
+
+        assert_eq!(long_poll.wait(0, TIMEOUT), Wait::Closed);
+    }
+
+    #[test]
+    fn query() {
+        assert_eq!(since("/events"), None);
+        assert_eq!(since("/events?since=12"), Some(12));
+        assert_eq!(since("/events?verbose&since=12"), Some(12));
+        assert_eq!(since("/events?since=twelve"), None);
+    }
+}
@@ -0,0 +1,356 @@
+use core::fmt::{self, Write};
+
+use crate::http::server::{Connection, Handler, HandlerError, HandlerResult, Request};
+use crate::http::Method;
+use crate::io::Write as _;
+use crate::utils::json::Escaped;
+
+use super::path::{ParamType, PathTemplate, TemplateError};
+
+pub const URI: &str = "/api/openapi.json";
+
+/// Types describing themselves with a JSON Schema object, for the request and response bodies
+pub trait Schema {
+    fn write_schema(f: &mut dyn Write) -> fmt::Result;
+}
+
+pub type SchemaHook = fn(&mut dyn Write) -> fmt::Result;
+
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Info {
+    pub title: &'static str,
+    pub version: &'static str,
+    pub description: Option<&'static str>,
+}
+
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Operation {
+    pub summary: Option<&'static str>,
+    pub description: Option<&'static str>,
+    pub tags: &'static [&'static str],
+    pub request_schema: Option<SchemaHook>,
+    pub response_schema: Option<SchemaHook>,
+}
+
+impl Operation {
+    pub fn new(summary: &'static str) -> Self {
+        Self {
+            summary: Some(summary),
+            ..Default::default()
+        }
+    }
+
+    #[must_use]
+    pub fn description(mut self, description: &'static str) -> Self {
+        self.description = Some(description);
+
+        self
+    }
+
+    #[must_use]
+    pub fn tags(mut self, tags: &'static [&'static str]) -> Self {
+        self.tags = tags;
+
+        self
+    }
+
+    /// The request body is JSON, described by the schema of `T`
+    #[must_use]
+    pub fn request<T: Schema>(mut self) -> Self {
+        self.request_schema = Some(T::write_schema);
+
+        self
+    }
+
+    /// The response body is JSON, described by the schema of `T`
+    #[must_use]
+    pub fn response<T: Schema>(mut self) -> Self {
+        self.response_schema = Some(T::write_schema);
+
+        self
+    }
+}
+
+/// A route of the device API, as registered with the server, and its description
+#[derive(Copy, Clone, Debug)]
+pub struct Route {
+    pub method: Method,
+    pub template: PathTemplate,
+    pub operation: Operation,
+}
+
+impl Route {
+    pub fn new(
+        method: Method,
+        template: &'static str,
+        operation: Operation,
+    ) -> Result<Self, TemplateError> {
+        Ok(Self {
+            method,
+            template: PathTemplate::parse(template)?,
+            operation,
+        })
+    }
+}
+
+/// Serves the OpenAPI 3.0 document of `routes`, written into a buffer of `B` bytes; register it at `URI`
+pub struct OpenApiHandler<'a, const B: usize = 4096> {
+    info: Info,
+    routes: &'a [Route],
+}
+
+impl<'a, const B: usize> OpenApiHandler<'a, B> {
+    pub const fn new(info: Info, routes: &'a [Route]) -> Self {
+        Self { info, routes }
+    }
+}
+
+impl<'a, C, const B: usize> Handler<C> for OpenApiHandler<'a, B>
+where
+    C: Connection,
+{
+    fn handle(&self, connection: &mut C) -> HandlerResult {
+        let mut document = heapless::String::<B>::new();
+
+        write_document(&mut document, &self.info, self.routes)
+            .map_err(|_| HandlerError::new("OpenAPI document too large"))?;
+
+        Request::wrap(connection)
+            .into_response(200, None, &[("Content-Type", "application/json")])?
+            .write_all(document.as_bytes())?;
+
+        Ok(())
+    }
+}
+
+/// Writes the OpenAPI 3.0 document of `routes`, with the routes of the same template grouped under one path
+pub fn write_document(f: &mut dyn Write, info: &Info, routes: &[Route]) -> fmt::Result {
+    write!(
+        f,
+        "{{\"openapi\":\"3.0.3\",\"info\":{{\"title\":\"{}\",\"version\":\"{}\"",
+        Escaped(info.title),
+        Escaped(info.version)
+    )?;
+
+    if let Some(description) = info.description {
+        write!(f, ",\"description\":\"{}\"", Escaped(description))?;
+    }
+
+    write!(f, "}},\"paths\":{{")?;
+
+    let documented = |route: &&Route| operation_method(route.method).is_some();
+
+    for (index, route) in routes.iter().filter(documented).enumerate() {
+        let template = route.template;
+
+        // Written with the first route of its template
+        if routes
+            .iter()
+            .filter(documented)
+            .take(index)
+            .any(|route| route.template == template)
+        {
+            continue;
+        }
+
+        if index > 0 {
+            write!(f, ",")?;
+        }
+
+        write!(f, "\"")?;
+        write_path(f, &template)?;
+        write!(f, "\":{{")?;
+
+        let operations = routes.iter().filter_map(|route| {
+            operation_method(route.method)
+                .filter(|_| route.template == template)
+                .map(|method| (method, &route.operation))
+        });
+
+        for (index, (method, operation)) in operations.enumerate() {
+            if index > 0 {
+                write!(f, ",")?;
+            }
+
+            write!(f, "\"{}\":", method)?;
+            write_operation(f, &template, operation)?;
+        }
+
+        write!(f, "}}")?;
+    }
+
+    write!(f, "}}}}")
+}
+
+fn operation_method(method: Method) -> Option<&'static str> {
+    Some(match method {
+        Method::Get => "get",
+        Method::Put => "put",
+        Method::Post => "post",
+        Method::Delete => "delete",
+        Method::Options => "options",
+        Method::Head => "head",
+        Method::Patch => "patch",
+        Method::Trace => "trace",
+        _ => return None,
+    })
+}
+
+/// The template without the parameter types, e.g. `/api/sensors/{id}`
+fn write_path(f: &mut dyn Write, template: &PathTemplate) -> fmt::Result {
+    for (index, segment) in template.as_str().split('/').enumerate() {
+        if index > 0 {
+            f.write_char('/')?;
+        }
+
+        match segment.strip_prefix('{').and_then(|s| s.strip_suffix('}')) {
+            Some(param) => write!(
+                f,
+                "{{{}}}",
+                Escaped(param.split(':').next().unwrap_or(param))
+            )?,
+            None => write!(f, "{}", Escaped(segment))?,
+        }
+    }
+
+    Ok(())
+}
+
+fn write_operation(
+    f: &mut dyn Write,
+    template: &PathTemplate,
+    operation: &Operation,
+) -> fmt::Result {
+    write!(f, "{{")?;
+
+    if let Some(summary) = operation.summary {
+        write!(f, "\"summary\":\"{}\",", Escaped(summary))?;
+    }
+
+    if let Some(description) = operation.description {
+        write!(f, "\"description\":\"{}\",", Escaped(description))?;
+    }
+
+    if !operation.tags.is_empty() {
+        write!(f, "\"tags\":[")?;
+
+        for (index, tag) in operation.tags.iter().enumerate() {
+            if index > 0 {
+                write!(f, ",")?;
+            }
+
+            write!(f, "\"{}\"", Escaped(tag))?;
+        }
+
+        write!(f, "],")?;
+    }
+
+    if template.params().next().is_some() {
+        write!(f, "\"parameters\":[")?;
+
+        for (index, (name, param_type)) in template.params().enumerate() {
+            if index > 0 {
+                write!(f, ",")?;
+            }
+
+            write!(
+                f,
+                "{{\"name\":\"{}\",\"in\":\"path\",\"required\":true,\"schema\":{}}}",
+                Escaped(name),
+                match param_type {
+                    ParamType::Int => "{\"type\":\"integer\",\"format\":\"int64\"}",
+                    ParamType::Uuid => "{\"type\":\"string\",\"format\":\"uuid\"}",
+                    ParamType::String => "{\"type\":\"string\"}",
+                }
+            )?;
+        }
+
+        write!(f, "],")?;
+    }
+
+    if let Some(schema) = operation.request_schema {
+        write!(
+            f,
+            "\"requestBody\":{{\"required\":true,\"content\":{{\"application/json\":{{\"schema\":"
+        )?;
+        schema(f)?;
+        write!(f, "}}}}}},")?;
+    }
+
+    write!(f, "\"responses\":{{\"200\":{{\"description\":\"OK\"")?;
+
+    if let Some(schema) = operation.response_schema {
+        write!(f, ",\"content\":{{\"application/json\":{{\"schema\":")?;
+        schema(f)?;
+        write!(f, "}}}}")?;
+    }
+
+    write!(f, "}}}}}}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Config;
+
+    impl Schema for Config {
+        fn write_schema(f: &mut dyn Write) -> fmt::Result {
+            write!(f, "{{\"type\":\"object\"}}")
+        }
+    }
+
+    #[test]
+    fn document() {
+        let routes = [
+            Route::new(
+                Method::Get,
+                "/api/sensors/{id:int}",
+                Operation::new("Sensor \"config\"").response::<Config>(),
+            )
+            .unwrap(),
+            Route::new(Method::Custom("PROPFIND"), "/dav", Operation::default()).unwrap(),
+            Route::new(Method::Get, "/api/status", Operation::default()).unwrap(),
+            Route::new(
+                Method::Put,
+                "/api/sensors/{id:int}",
+                Operation::default().tags(&["sensors"]).request::<Config>(),
+            )
+            .unwrap(),
+        ];
+
+        let mut document = heapless::String::<1024>::new();
+
+        write_document(
+            &mut document,
+            &Info {
+                title: "Device",
+                version: "1.0",
+                description: None,
+            },
+            &routes,
+        )
+        .unwrap();
+
+        assert_eq!(
+            document,
+            concat!(
+                "{\"openapi\":\"3.0.3\",\"info\":{\"title\":\"Device\",\"version\":\"1.0\"},\"paths\":{",
+                "\"/api/sensors/{id}\":{",
+                "\"get\":{\"summary\":\"Sensor \\\"config\\\"\",",
+                "\"parameters\":[{\"name\":\"id\",\"in\":\"path\",\"required\":true,",
+                "\"schema\":{\"type\":\"integer\",\"format\":\"int64\"}}],",
+                "\"responses\":{\"200\":{\"description\":\"OK\",",
+                "\"content\":{\"application/json\":{\"schema\":{\"type\":\"object\"}}}}}},",
+                "\"put\":{\"tags\":[\"sensors\"],",
+                "\"parameters\":[{\"name\":\"id\",\"in\":\"path\",\"required\":true,",
+                "\"schema\":{\"type\":\"integer\",\"format\":\"int64\"}}],",
+                "\"requestBody\":{\"required\":true,",
+                "\"content\":{\"application/json\":{\"schema\":{\"type\":\"object\"}}}},",
+                "\"responses\":{\"200\":{\"description\":\"OK\"}}}},",
+                "\"/api/status\":{\"get\":{\"responses\":{\"200\":{\"description\":\"OK\"}}}}",
+                "}}"
+            )
+        );
+    }
+}
@@ -0,0 +1,377 @@
+use core::str::FromStr;
+
+use crate::error::{impl_error, ErrorKind};
+use crate::http::server::{Connection, Handler, HandlerResult, Request};
+
+/// The maximum number of parameters of a template
+pub const MAX_PARAMS: usize = 4;
+
+/// The maximum length of a parameter value; longer values do not have the declared type
+pub const MAX_VALUE_LEN: usize = 64;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ParamType {
+    /// A signed 64-bit integer
+    Int,
+    /// A UUID in its hyphenated form, e.g. `67e55044-10b1-426f-9247-bb680e5fe0c8`
+    Uuid,
+    /// Any non-empty segment
+    String,
+}
+
+impl ParamType {
+    pub fn accepts(&self, value: &str) -> bool {
+        match self {
+            Self::Int => value.parse::<i64>().is_ok(),
+            Self::Uuid => {
+                value.len() == 36
+                    && value.char_indices().all(|(index, c)| match index {
+                        8 | 13 | 18 | 23 => c == '-',
+                        _ => c.is_ascii_hexdigit(),
+                    })
+            }
+            Self::String => !value.is_empty(),
+        }
+    }
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum TemplateError {
+    UnknownType,
+    Unnamed,
+    /// A parameter does not span a whole segment, as in `/api/sensor-{id}`
+    PartialSegment,
+    TooManyParams,
+}
+
+impl_error! {
+    TemplateError {
+        UnknownType => "Unknown parameter type in path template"; ErrorKind::InvalidInput,
+        Unnamed => "Unnamed parameter in path template"; ErrorKind::InvalidInput,
+        PartialSegment => "Parameters should span whole path segments"; ErrorKind::InvalidInput,
+        TooManyParams => "Too many parameters in path template"; ErrorKind::InvalidInput,
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Segment {
+    Literal(&'static str),
+    Param(&'static str, ParamType),
+}
+
+impl Segment {
+    fn parse(segment: &'static str) -> Result<Self, TemplateError> {
+        if let Some(param) = segment
+            .strip_prefix('{')
+            .and_then(|segment| segment.strip_suffix('}'))
+        {
+            let (name, param_type) = match param.split_once(':') {
+                Some((name, "int")) => (name, ParamType::Int),
+                Some((name, "uuid")) => (name, ParamType::Uuid),
+                Some((name, "string")) => (name, ParamType::String),
+                None => (param, ParamType::String),
+                Some(_) => return Err(TemplateError::UnknownType),
+            };
+
+            if name.is_empty() {
+                Err(TemplateError::Unnamed)
+            } else {
+                Ok(Self::Param(name, param_type))
+            }
+        } else if segment.contains('{') || segment.contains('}') {
+            Err(TemplateError::PartialSegment)
+        } else {
+            Ok(Self::Literal(segment))
+        }
+    }
+}
+
+/// A path like `/api/sensors/{id:int}/config`, where each parameter spans a whole segment
+/// and is typed with `:int`, `:uuid` or `:string` (the default)
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct PathTemplate(&'static str);
+
+impl PathTemplate {
+    pub fn parse(template: &'static str) -> Result<Self, TemplateError> {
+        let mut params = 0;
+
+        for segment in template.split('/') {
+            if let Segment::Param(..) = Segment::parse(segment)? {
+                params += 1;
+            }
+        }
+
+        if params > MAX_PARAMS {
+            Err(TemplateError::TooManyParams)
+        } else {
+            Ok(Self(template))
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        self.0
+    }
+
+    pub fn params(&self) -> impl Iterator<Item = (&'static str, ParamType)> {
+        self.segments().filter_map(|segment| match segment {
+            Segment::Param(name, param_type) => Some((name, param_type)),
+            _ => None,
+        })
+    }
+
+    /// The literal prefix of the template, up to its first parameter, e.g. `/api/sensors/`;
+    /// register the handler at the prefix followed by `*` with servers matching such URIs as prefixes
+    pub fn prefix(&self) -> &'static str {
+        match self.0.find('{') {
+            Some(index) => &self.0[..index],
+            None => self.0,
+        }
+    }
+
+    /// Matches the path of `uri`, ignoring its query string
+    pub fn matches(&self, uri: &str) -> PathMatch {
+        let path = uri.split('?').next().unwrap_or("");
+
+        let mut params = PathParams::default();
+        let mut invalid = None;
+
+        let mut values = path.split('/');
+
+        for segment in self.segments() {
+            let value = match values.next() {
+                Some(value) => value,
+                None => return PathMatch::NoMatch,
+            };
+
+            match segment {
+                Segment::Literal(literal) => {
+                    if literal != value {
+                        return PathMatch::NoMatch;
+                    }
+                }
+                Segment::Param(name, param_type) => {
+                    if value.is_empty() {
+                        return PathMatch::NoMatch;
+                    }
+
+                    if value.len() > MAX_VALUE_LEN || !param_type.accepts(value) {
+                        invalid.get_or_insert(name);
+                    } else {
+                        // The template has at most `MAX_PARAMS` parameters
+                        params.0.push((name, value.into())).unwrap();
+                    }
+                }
+            }
+        }
+
+        if values.next().is_some() {
+            PathMatch::NoMatch
+        } else if let Some(name) = invalid {
+            PathMatch::Invalid(name)
+        } else {
+            PathMatch::Matched(params)
+        }
+    }
+
+    fn segments(&self) -> impl Iterator<Item = Segment> {
+        // Validated when parsed
+        self.0
+            .split('/')
+            .map(|segment| Segment::parse(segment).unwrap_or(Segment::Literal(segment)))
+    }
+}
+
+#[allow(clippy::large_enum_variant)]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PathMatch {
+    Matched(PathParams),
+    /// The path has the shape of the template, but this parameter is not of the declared type
+    Invalid(&'static str),
+    NoMatch,
+}
+
+/// The parameter values extracted from a path, as they appear in it, i.e. still percent-encoded
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PathParams(heapless::Vec<(&'static str, heapless::String<MAX_VALUE_LEN>), MAX_PARAMS>);
+
+impl PathParams {
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|(param, _)| *param == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn parse<T: FromStr>(&self, name: &str) -> Option<T> {
+        self.get(name).and_then(|value| value.parse().ok())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.0.iter().map(|(name, value)| (*name, value.as_str()))
+    }
+}
+
+/// Serves the requests whose path matches the template with `f`, which gets the parameters,
+/// and the other requests with the handler of the routes registered before it.
+///
+/// Routes are tried from the last registered one on; a path of the shape of a template except for
+/// the type of a parameter is answered with 400, without trying the other routes. `NotFoundHandler`,
+/// which the routes are registered on by default, answers the paths no template matches with 404.
+pub struct PathHandler<F, N = NotFoundHandler> {
+    template: PathTemplate,
+    f: F,
+    next: N,
+}
+
+impl<F> PathHandler<F> {
+    pub fn new(template: &'static str, f: F) -> Result<Self, TemplateError> {
+        Self::chain(template, f, NotFoundHandler)
+    }
+}
+
+impl<F, N> PathHandler<F, N> {
+    pub fn chain(template: &'static str, f: F, next: N) -> Result<Self, TemplateError> {
+        Ok(Self {
+            template: PathTemplate::parse(template)?,
+            f,
+            next,
+        })
+    }
+
+    pub fn route<F2>(
+        self,
+        template: &'static str,
+        f: F2,
+    ) -> Result<PathHandler<F2, Self>, TemplateError> {
+        PathHandler::chain(template, f, self)
+    }
+
+    pub fn template(&self) -> &PathTemplate {
+        &self.template
+    }
+}
+
+impl<C, F, N> Handler<C> for PathHandler<F, N>
+where
+    C: Connection,
+    F: Fn(Request<&mut C>, &PathParams) -> HandlerResult + Send,
+    N: Handler<C>,
+{
+    fn handle(&self, connection: &mut C) -> HandlerResult {
+        match self.template.matches(connection.uri()) {
+            PathMatch::Matched(params) => (self.f)(Request::wrap(connection), &params),
+            PathMatch::Invalid(_) => {
+                connection.initiate_response(400, None, &[])?;
+
+                Ok(())
+            }
+            PathMatch::NoMatch => self.next.handle(connection),
+        }
+    }
+}
+
+/// Responds with 404
+#[derive(Copy, Clone, Debug, Default)]
+pub struct NotFoundHandler;
+
+impl<C> Handler<C> for NotFoundHandler
+where
+    C: Connection,
+{
+    fn handle(&self, connection: &mut C) -> HandlerResult {
+        connection.initiate_response(404, None, &[])?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UUID: &str = "67e55044-10b1-426f-9247-bb680e5fe0c8";
+
+    #[test]
+    fn parse() {
+        assert!(PathTemplate::parse("/api/sensors/{id:int}/config").is_ok());
+        assert!(PathTemplate::parse("/api/{a}/{b}/{c}/{d}").is_ok());
+
+        assert!(matches!(
+            PathTemplate::parse("/api/{id:float}"),
+            Err(TemplateError::UnknownType)
+        ));
+        assert!(matches!(
+            PathTemplate::parse("/api/{:int}"),
+            Err(TemplateError::Unnamed)
+        ));
+        assert!(matches!(
+            PathTemplate::parse("/api/sensor-{id}"),
+            Err(TemplateError::PartialSegment)
+        ));
+        assert!(matches!(
+            PathTemplate::parse("/api/{a}/{b}/{c}/{d}/{e}"),
+            Err(TemplateError::TooManyParams)
+        ));
+    }
+
+    #[test]
+    fn matches() {
+        let template = PathTemplate::parse("/api/sensors/{id:int}/config/{key}").unwrap();
+
+        assert_eq!(template.prefix(), "/api/sensors/");
+        assert_eq!(
+            template.params().collect::<heapless::Vec<_, 2>>(),
+            [("id", ParamType::Int), ("key", ParamType::String)]
+        );
+
+        match template.matches("/api/sensors/-12/config/rate?verbose") {
+            PathMatch::Matched(params) => {
+                assert_eq!(params.parse::<i64>("id"), Some(-12));
+                assert_eq!(params.get("key"), Some("rate"));
+                assert_eq!(params.get("other"), None);
+            }
+            other => panic!("{:?}", other),
+        }
+
+        assert_eq!(
+            template.matches("/api/sensors/twelve/config/rate"),
+            PathMatch::Invalid("id")
+        );
+
+        for uri in [
+            "/api/sensors/12/config",
+            "/api/sensors/12/config/",
+            "/api/sensors/12/config/rate/more",
+            "/api/actuators/12/config/rate",
+            "/api/sensors//config/rate",
+        ] {
+            assert_eq!(template.matches(uri), PathMatch::NoMatch, "{}", uri);
+        }
+    }
+
+    #[test]
+    fn uuid() {
+        let template = PathTemplate::parse("/devices/{id:uuid}").unwrap();
+
+        assert!(matches!(
+            template.matches(&format!("/devices/{}", UUID)),
+            PathMatch::Matched(_)
+        ));
+        assert_eq!(
+            template.matches(&format!("/devices/{}", &UUID[1..])),
+            PathMatch::Invalid("id")
+        );
+
+        let long = "x".repeat(MAX_VALUE_LEN + 1);
+
+        assert_eq!(
+            PathTemplate::parse("/devices/{name}")
+                .unwrap()
+                .matches(&format!("/devices/{}", long)),
+            PathMatch::Invalid("name")
+        );
+    }
+}
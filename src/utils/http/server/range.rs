@@ -0,0 +1,288 @@
+use core::fmt::Write as _;
+
+use crate::http::server::{Connection, HandlerResult};
+use crate::io::{Read, Seek, SeekFrom};
+use crate::utils::io::copy_len;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ByteRange {
+    /// The first and the last byte of the range
+    Satisfiable(u64, u64),
+    Unsatisfiable,
+}
+
+/// The single `bytes` range of a `Range` header, for a representation of `len` bytes;
+/// `None` for anything else, to be served in full
+pub fn parse_range(range: &str, len: u64) -> Option<ByteRange> {
+    let range = range.trim().strip_prefix("bytes=")?;
+
+    if range.contains(',') {
+        return None;
+    }
+
+    let (start, end) = range.split_once('-')?;
+    let (start, end) = (start.trim(), end.trim());
+
+    let (start, end) = if start.is_empty() {
+        let suffix: u64 = end.parse().ok()?;
+
+        if suffix == 0 || len == 0 {
+            return Some(ByteRange::Unsatisfiable);
+        }
+
+        (len.saturating_sub(suffix), len - 1)
+    } else {
+        let start: u64 = start.parse().ok()?;
+
+        let end = if end.is_empty() {
+            len.saturating_sub(1)
+        } else {
+            let end: u64 = end.parse().ok()?;
+
+            if end < start {
+                return None;
+            }
+
+            end.min(len.saturating_sub(1))
+        };
+
+        (start, end)
+    };
+
+    if start >= len {
+        Some(ByteRange::Unsatisfiable)
+    } else {
+        Some(ByteRange::Satisfiable(start, end))
+    }
+}
+
+/// Serves `content`, of `len` bytes, honoring a single `Range` with a 206 response, unless an
+/// `If-Range` does not match `etag`; other ranges are served in full
+pub fn serve<C, R>(
+    connection: &mut C,
+    mut content: R,
+    len: u64,
+    etag: &str,
+    content_type: &str,
+) -> HandlerResult
+where
+    C: Connection,
+    R: Read + Seek,
+{
+    let range = connection
+        .header("Range")
+        .filter(|_| {
+            connection
+                .header("If-Range")
+                .map(|if_range| if_range == etag)
+                .unwrap_or(true)
+        })
+        .and_then(|range| parse_range(range, len));
+
+    let mut content_range = heapless::String::<64>::new();
+    let mut content_len = heapless::String::<20>::new();
+
+    let mut buf = [0_u8; 512];
+
+    match range {
+        Some(ByteRange::Satisfiable(start, end)) => {
+            content.seek(SeekFrom::Start(start))?;
+
+            let range_len = end - start + 1;
+
+            write!(&mut content_range, "bytes {}-{}/{}", start, end, len)?;
+            write!(&mut content_len, "{}", range_len)?;
+
+            connection.initiate_response(
+                206,
+                None,
+                &[
+                    ("Content-Type", content_type),
+                    ("Content-Length", &content_len),
+                    ("Content-Range", &content_range),
+                    ("Accept-Ranges", "bytes"),
+                    ("ETag", etag),
+                ],
+            )?;
+
+            copy_len(content, &mut *connection, &mut buf, range_len)?;
+        }
+        Some(ByteRange::Unsatisfiable) => {
+            write!(&mut content_range, "bytes */{}", len)?;
+
+            connection.initiate_response(
+                416,
+                None,
+                &[
+                    ("Accept-Ranges", "bytes"),
+                    ("Content-Range", &content_range),
+                ],
+            )?;
+        }
+        None => {
+            write!(&mut content_len, "{}", len)?;
+
+            connection.initiate_response(
+                200,
+                None,
+                &[
+                    ("Content-Type", content_type),
+                    ("Content-Length", &content_len),
+                    ("Accept-Ranges", "bytes"),
+                    ("ETag", etag),
+                ],
+            )?;
+
+            copy_len(content, &mut *connection, &mut buf, len)?;
+        }
+    }
+
+    Ok(())
+}
+
+pub fn content_type(path: &str) -> &'static str {
+    let extension = match path.rsplit_once('.') {
+        Some((_, extension)) if !extension.contains('/') => extension,
+        _ => "",
+    };
+
+    [
+        ("html", "text/html"),
+        ("htm", "text/html"),
+        ("css", "text/css"),
+        ("js", "application/javascript"),
+        ("json", "application/json"),
+        ("txt", "text/plain"),
+        ("log", "text/plain"),
+        ("svg", "image/svg+xml"),
+        ("png", "image/png"),
+        ("jpg", "image/jpeg"),
+        ("jpeg", "image/jpeg"),
+        ("gif", "image/gif"),
+        ("ico", "image/x-icon"),
+        ("mp3", "audio/mpeg"),
+        ("wav", "audio/wav"),
+        ("mp4", "video/mp4"),
+        ("webm", "video/webm"),
+        ("wasm", "application/wasm"),
+    ]
+    .iter()
+    .find(|(known, _)| known.eq_ignore_ascii_case(extension))
+    .map(|(_, content_type)| *content_type)
+    .unwrap_or("application/octet-stream")
+}
+
+#[cfg(feature = "std")]
+pub mod files {
+    use core::fmt::Write as _;
+
+    use std::fs;
+    use std::path::{Component, Path};
+    use std::time::UNIX_EPOCH;
+
+    use crate::http::server::{Connection, Handler, HandlerResult};
+    use crate::io::adapters::FromStd;
+
+    use super::super::path::NotFoundHandler;
+
+    /// Serves the files below `root`, mapping the request path without `prefix` to a file, with `serve`;
+    /// register it at a wildcard URI like `/assets/*`
+    pub struct FileHandler<P> {
+        prefix: &'static str,
+        root: P,
+    }
+
+    impl<P> FileHandler<P> {
+        pub const fn new(prefix: &'static str, root: P) -> Self {
+            Self { prefix, root }
+        }
+    }
+
+    impl<C, P> Handler<C> for FileHandler<P>
+    where
+        C: Connection,
+        P: AsRef<Path> + Send,
+    {
+        fn handle(&self, connection: &mut C) -> HandlerResult {
+            let path = connection.uri().split('?').next().unwrap_or("");
+
+            let relative = match path.strip_prefix(self.prefix) {
+                Some(relative) => Path::new(relative.trim_start_matches('/')),
+                None => return NotFoundHandler.handle(connection),
+            };
+
+            if !relative
+                .components()
+                .all(|component| matches!(component, Component::Normal(_)))
+            {
+                return NotFoundHandler.handle(connection);
+            }
+
+            let path = self.root.as_ref().join(relative);
+
+            if !path.is_file() {
+                return NotFoundHandler.handle(connection);
+            }
+
+            let file = fs::File::open(&path)?;
+            let metadata = file.metadata()?;
+
+            let len = metadata.len();
+            let modified = metadata
+                .modified()
+                .ok()
+                .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+                .map(|modified| modified.as_secs())
+                .unwrap_or(0);
+
+            let mut etag = heapless::String::<40>::new();
+
+            write!(&mut etag, "\"{:x}-{:x}\"", len, modified)?;
+
+            super::serve(
+                connection,
+                FromStd::new(file),
+                len,
+                &etag,
+                super::content_type(path.to_str().unwrap_or("")),
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn range() {
+        for (range, expected) in [
+            ("bytes=0-99", Some(ByteRange::Satisfiable(0, 99))),
+            ("bytes=900-", Some(ByteRange::Satisfiable(900, 999))),
+            ("bytes=-100", Some(ByteRange::Satisfiable(900, 999))),
+            ("bytes=-5000", Some(ByteRange::Satisfiable(0, 999))),
+            ("bytes=990-5000", Some(ByteRange::Satisfiable(990, 999))),
+            ("bytes=1000-", Some(ByteRange::Unsatisfiable)),
+            ("bytes=-0", Some(ByteRange::Unsatisfiable)),
+            ("bytes=100-99", None),
+            ("bytes=0-1,5-6", None),
+            ("items=0-99", None),
+            ("bytes=a-b", None),
+        ] {
+            assert_eq!(parse_range(range, 1000), expected, "{}", range);
+        }
+
+        assert_eq!(parse_range("bytes=0-", 0), Some(ByteRange::Unsatisfiable));
+    }
+
+    #[test]
+    fn content_types() {
+        assert_eq!(content_type("/assets/index.HTML"), "text/html");
+        assert_eq!(content_type("/logs/boot.log"), "text/plain");
+        assert_eq!(
+            content_type("/assets.d/firmware"),
+            "application/octet-stream"
+        );
+    }
+}
@@ -0,0 +1,361 @@
+use core::convert::Infallible;
+use core::fmt::{self, Write as _};
+use core::time::Duration;
+
+use crate::error::{impl_error, ErrorKind};
+use crate::http::client::{Client, Connection};
+use crate::http::{headers, status, Method, Status};
+use crate::io::{Read, Write};
+use crate::storage::RawStorage;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Encoding<'a> {
+    /// The data is sent as-is as the request body
+    Raw { content_type: &'a str },
+    /// The data is sent as the single file part of a `multipart/form-data` body
+    Multipart {
+        boundary: &'a str,
+        field_name: &'a str,
+        file_name: &'a str,
+        content_type: &'a str,
+    },
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Upload<'a> {
+    /// Typically `Method::Put` for raw uploads and `Method::Post` for multipart ones
+    pub method: Method,
+    pub uri: &'a str,
+    pub encoding: Encoding<'a>,
+    /// Total number of attempts, including the first one
+    pub attempts: u8,
+    /// Delay before the first retry; doubled on each subsequent retry
+    pub retry_delay: Duration,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum UploadEvent {
+    Started { attempt: u8, total: u64 },
+    Progress { uploaded: u64, total: u64 },
+    Retrying { attempt: u8, delay: Duration },
+    Completed { status: u16 },
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Uploaded {
+    pub len: u64,
+    pub status: u16,
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum UploadError<H, R> {
+    HttpError(H),
+    ReadError(R),
+    Status(u16),
+    NotFound,
+    /// The source yielded less data than announced
+    Truncated,
+    TooLarge,
+}
+
+impl_error! {
+    UploadError<H: Display, R: Display> {
+        HttpError(e) => "HTTP error: {e}"; e.error_kind(),
+        ReadError(e) => "Read error: {e}"; e.error_kind(),
+        Status(status) => "Unexpected HTTP status: {status}"; ErrorKind::from_status(*status),
+        NotFound => "Upload source not found"; ErrorKind::NotFound,
+        Truncated => "Upload source truncated"; ErrorKind::Other,
+        TooLarge => "Upload too large"; ErrorKind::InvalidInput,
+    }
+}
+
+impl<H, R> UploadError<H, R> {
+    /// Transport errors, server errors and throttling are assumed to be transient
+    fn is_transient(&self) -> bool {
+        match self {
+            Self::HttpError(_) => true,
+            Self::Status(status) => {
+                *status == 408 || *status == 429 || status::SERVER_ERROR.contains(status)
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Streams `len` bytes as the body of the `upload` request, retrying transient failures.
+///
+/// Since the body has to be resent from the start on each attempt, `open` is called
+/// once per attempt to (re)open the source, e.g. a file.
+pub fn upload<C, R, O, D, F>(
+    client: &mut Client<C>,
+    upload: &Upload<'_>,
+    len: u64,
+    mut open: O,
+    delay: &D,
+    buf: &mut [u8],
+    mut progress: F,
+) -> Result<Uploaded, UploadError<C::Error, R::Error>>
+where
+    C: Connection,
+    R: Read,
+    O: FnMut() -> Result<R, R::Error>,
+    D: Fn(Duration),
+    F: FnMut(UploadEvent),
+{
+    with_retry(upload, delay, &mut progress, |progress, attempt| {
+        let read = open().map_err(UploadError::ReadError)?;
+
+        send(
+            client,
+            upload,
+            attempt,
+            len,
+            ReadBody(read, &mut *buf),
+            progress,
+            |e| e,
+        )
+    })
+}
+
+/// Uploads the storage blob named `name`, which is read in full into `buf` first.
+pub fn upload_from_storage<C, S, D, F>(
+    client: &mut Client<C>,
+    upload: &Upload<'_>,
+    storage: &S,
+    name: &str,
+    delay: &D,
+    buf: &mut [u8],
+    mut progress: F,
+) -> Result<Uploaded, UploadError<C::Error, S::Error>>
+where
+    C: Connection,
+    S: RawStorage,
+    D: Fn(Duration),
+    F: FnMut(UploadEvent),
+{
+    let len = storage
+        .len(name)
+        .map_err(UploadError::ReadError)?
+        .ok_or(UploadError::NotFound)?;
+
+    if len > buf.len() {
+        return Err(UploadError::TooLarge);
+    }
+
+    let data = storage
+        .get_raw(name, buf)
+        .map_err(UploadError::ReadError)?
+        .ok_or(UploadError::NotFound)?;
+
+    with_retry(upload, delay, &mut progress, |progress, attempt| {
+        send(
+            client,
+            upload,
+            attempt,
+            data.len() as u64,
+            SliceBody(data),
+            progress,
+            |e: Infallible| match e {},
+        )
+    })
+}
+
+fn with_retry<H, R, D, F, A>(
+    upload: &Upload<'_>,
+    delay: &D,
+    progress: &mut F,
+    mut attempt: A,
+) -> Result<Uploaded, UploadError<H, R>>
+where
+    D: Fn(Duration),
+    F: FnMut(UploadEvent),
+    A: FnMut(&mut F, u8) -> Result<Uploaded, UploadError<H, R>>,
+{
+    let mut retry_delay = upload.retry_delay;
+    let mut number = 1;
+
+    loop {
+        match attempt(progress, number) {
+            Err(e) if e.is_transient() && number < upload.attempts => {
+                progress(UploadEvent::Retrying {
+                    attempt: number + 1,
+                    delay: retry_delay,
+                });
+
+                delay(retry_delay);
+
+                retry_delay *= 2;
+                number += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// The source of the body of an upload, yielding it chunk by chunk
+trait Body {
+    type Error;
+
+    /// Returns at most `max` bytes, or none at the end of the source
+    fn next(&mut self, max: u64) -> Result<&[u8], Self::Error>;
+}
+
+/// Reads the body into a buffer
+struct ReadBody<'a, R>(R, &'a mut [u8]);
+
+impl<'a, R> Body for ReadBody<'a, R>
+where
+    R: Read,
+{
+    type Error = R::Error;
+
+    fn next(&mut self, max: u64) -> Result<&[u8], Self::Error> {
+        let max = self.1.len().min(max.min(usize::MAX as u64) as usize);
+
+        let size = self.0.read(&mut self.1[..max])?;
+
+        Ok(&self.1[..size])
+    }
+}
+
+/// Writes the body straight from memory
+struct SliceBody<'a>(&'a [u8]);
+
+impl<'a> Body for SliceBody<'a> {
+    type Error = Infallible;
+
+    fn next(&mut self, max: u64) -> Result<&[u8], Self::Error> {
+        let (chunk, rest) = self
+            .0
+            .split_at(self.0.len().min(max.min(usize::MAX as u64) as usize));
+
+        self.0 = rest;
+
+        Ok(chunk)
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn send<C, B, E, F, M>(
+    client: &mut Client<C>,
+    upload: &Upload<'_>,
+    attempt: u8,
+    len: u64,
+    mut body: B,
+    progress: &mut F,
+    map_err: M,
+) -> Result<Uploaded, UploadError<C::Error, E>>
+where
+    C: Connection,
+    B: Body,
+    F: FnMut(UploadEvent),
+    M: Fn(B::Error) -> E,
+{
+    let mut preamble = heapless::String::<256>::new();
+    let mut epilogue = heapless::String::<80>::new();
+    let mut content_type = heapless::String::<128>::new();
+
+    match upload.encoding {
+        Encoding::Raw {
+            content_type: raw_content_type,
+        } => content_type.push_str(raw_content_type).map_err(|_| fmt::Error),
+        Encoding::Multipart {
+            boundary,
+            field_name,
+            file_name,
+            content_type: file_content_type,
+        } => write!(
+            &mut preamble,
+            "--{boundary}\r\nContent-Disposition: form-data; name=\"{field_name}\"; filename=\"{file_name}\"\r\nContent-Type: {file_content_type}\r\n\r\n"
+        )
+        .and_then(|_| write!(&mut epilogue, "\r\n--{boundary}--\r\n"))
+        .and_then(|_| write!(&mut content_type, "multipart/form-data; boundary={boundary}")),
+    }
+    .map_err(|_| UploadError::TooLarge)?;
+
+    let total = preamble.len() as u64 + len + epilogue.len() as u64;
+
+    let mut content_len_buf = headers::ContentLenParseBuf::new();
+
+    let request_headers = [
+        headers::content_type(&content_type),
+        headers::content_len(total, &mut content_len_buf),
+    ];
+
+    progress(UploadEvent::Started { attempt, total });
+
+    let mut request = client
+        .request(upload.method, upload.uri, &request_headers)
+        .map_err(UploadError::HttpError)?;
+
+    request
+        .write_all(preamble.as_bytes())
+        .map_err(UploadError::HttpError)?;
+
+    let mut uploaded = preamble.len() as u64;
+    let mut remaining = len;
+
+    while remaining > 0 {
+        let chunk = body
+            .next(remaining)
+            .map_err(|e| UploadError::ReadError(map_err(e)))?;
+        if chunk.is_empty() {
+            return Err(UploadError::Truncated);
+        }
+
+        request.write_all(chunk).map_err(UploadError::HttpError)?;
+
+        let size = chunk.len();
+
+        remaining -= size as u64;
+        uploaded += size as u64;
+
+        progress(UploadEvent::Progress { uploaded, total });
+    }
+
+    request
+        .write_all(epilogue.as_bytes())
+        .map_err(UploadError::HttpError)?;
+
+    let response = request.submit().map_err(UploadError::HttpError)?;
+
+    let status = response.status();
+
+    if status::OK.contains(&status) {
+        progress(UploadEvent::Completed { status });
+
+        Ok(Uploaded { len, status })
+    } else {
+        Err(UploadError::Status(status))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slice_body() {
+        let mut body = SliceBody(b"abcde");
+
+        assert_eq!(body.next(3), Ok(&b"abc"[..]));
+        assert_eq!(body.next(u64::MAX), Ok(&b"de"[..]));
+        assert_eq!(body.next(u64::MAX), Ok(&b""[..]));
+    }
+
+    #[test]
+    fn read_body() {
+        let mut buf = [0_u8; 2];
+        let mut body = ReadBody(&b"abcde"[..], &mut buf);
+
+        assert_eq!(body.next(u64::MAX), Ok(&b"ab"[..]));
+        assert_eq!(body.next(1), Ok(&b"c"[..]));
+        assert_eq!(body.next(u64::MAX), Ok(&b"de"[..]));
+        assert_eq!(body.next(u64::MAX), Ok(&b""[..]));
+    }
+}
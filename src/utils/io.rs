@@ -1,4 +1,8 @@
-use crate::io::{Error, Read, Write};
+use core::time::Duration;
+
+use crate::error::{Classify, ErrorKind};
+use crate::io::{Error, Io, Read, Write};
+use crate::sys_time::{Instant, SystemTime};
 
 pub fn try_read_full<R: Read>(mut read: R, buf: &mut [u8]) -> Result<usize, (R::Error, usize)> {
     let mut offset = 0;
@@ -38,6 +42,19 @@ where
     }
 }
 
+impl<R, W> Classify for CopyError<R, W>
+where
+    R: Classify,
+    W: Classify,
+{
+    fn error_kind(&self) -> ErrorKind {
+        match self {
+            Self::Read(e) => e.error_kind(),
+            Self::Write(e) => e.error_kind(),
+        }
+    }
+}
+
 pub fn copy<R, W>(read: R, write: W, buf: &mut [u8]) -> Result<u64, CopyError<R::Error, W::Error>>
 where
     R: Read,
@@ -94,6 +111,153 @@ where
     Ok(copied)
 }
 
+/// A token bucket: up to `rate` bytes per second on average, in bursts of up to `burst` bytes
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct TokenBucket {
+    rate: u32,
+    burst: u32,
+    tokens: u32,
+    refilled: Option<Instant>,
+}
+
+impl TokenBucket {
+    /// The bucket starts full; `rate` and `burst` are at least 1
+    pub const fn new(rate: u32, burst: u32) -> Self {
+        let burst = if burst > 0 { burst } else { 1 };
+
+        Self {
+            rate: if rate > 0 { rate } else { 1 },
+            burst,
+            tokens: burst,
+            refilled: None,
+        }
+    }
+
+    pub fn rate(&self) -> u32 {
+        self.rate
+    }
+
+    pub fn burst(&self) -> u32 {
+        self.burst
+    }
+
+    /// Takes up to `len` tokens, and returns how many were taken
+    pub fn take(&mut self, now: Instant, len: usize) -> usize {
+        self.refill(now);
+
+        let taken = self.tokens.min(len.min(u32::MAX as _) as u32);
+
+        self.tokens -= taken;
+
+        taken as _
+    }
+
+    /// How long until `len` tokens are available, `len` being capped to `burst`
+    pub fn wait_for(&mut self, now: Instant, len: usize) -> Duration {
+        self.refill(now);
+
+        let needed = (len.min(self.burst as _) as u32).saturating_sub(self.tokens);
+
+        Duration::from_micros((needed as u64 * 1_000_000 + self.rate as u64 - 1) / self.rate as u64)
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let refilled = *self.refilled.get_or_insert(now);
+
+        let added = now.duration_since(refilled).as_micros() * self.rate as u128 / 1_000_000;
+
+        // The fraction of a token not added yet is kept for the next refill, unless the bucket is full
+        if added > 0 || self.tokens == self.burst {
+            self.tokens = (self.tokens as u128 + added).min(self.burst as _) as _;
+            self.refilled = Some(now);
+        }
+    }
+}
+
+/// Limits the throughput of `W` with a `TokenBucket`, sleeping with `delay` when the bucket is empty, so
+/// that bulk transfers do not saturate a narrow link shared with latency-sensitive traffic.
+///
+/// Writes are split into chunks of at most `burst` bytes, so `burst` should be at least the size of the
+/// packets of the transport, e.g. the TCP MSS.
+pub struct Throttled<W, T, D> {
+    write: W,
+    bucket: TokenBucket,
+    time: T,
+    delay: D,
+}
+
+impl<W, T, D> Throttled<W, T, D>
+where
+    W: Write,
+    T: SystemTime,
+    D: Fn(Duration),
+{
+    pub const fn new(write: W, bucket: TokenBucket, time: T, delay: D) -> Self {
+        Self {
+            write,
+            bucket,
+            time,
+            delay,
+        }
+    }
+
+    pub fn bucket(&self) -> &TokenBucket {
+        &self.bucket
+    }
+
+    /// Changes the limit, e.g. when the link changes from Wi-Fi to cellular
+    pub fn set_bucket(&mut self, bucket: TokenBucket) {
+        self.bucket = bucket;
+    }
+
+    pub fn release(self) -> W {
+        self.write
+    }
+}
+
+impl<W, T, D> Io for Throttled<W, T, D>
+where
+    W: Io,
+{
+    type Error = W::Error;
+}
+
+impl<W, T, D> Write for Throttled<W, T, D>
+where
+    W: Write,
+    T: SystemTime,
+    D: Fn(Duration),
+{
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        loop {
+            let wait = self.bucket.wait_for(Instant::now(&self.time), buf.len());
+
+            if wait == Duration::ZERO {
+                break;
+            }
+
+            (self.delay)(wait);
+        }
+
+        let len = self.bucket.take(Instant::now(&self.time), buf.len());
+        let written = self.write.write(&buf[..len])?;
+
+        // Tokens of the bytes the transport did not accept are returned
+        self.bucket.tokens = (self.bucket.tokens + (len - written) as u32).min(self.bucket.burst);
+
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.write.flush()
+    }
+}
+
 #[cfg(all(feature = "nightly", feature = "experimental"))]
 pub mod asynch {
     use crate::io::asynch::{Read, Write};
@@ -0,0 +1,928 @@
+//! Pull-based, streaming JSON, for extracting a few fields out of payloads too large to be deserialized
+//! as a whole, and for producing documents directly into a connection or a buffer.
+//!
+//! Neither the reader nor the writer allocates: the reader only buffers the key, string or number at
+//! hand, and both only track the nesting of the containers.
+
+use core::fmt::{self, Debug, Display, Write as _};
+
+use crate::error::{self, impl_error};
+use crate::io::{self, ErrorKind, Read, Write, WriteFmtError};
+
+const READ_AHEAD: usize = 32;
+
+/// Displays a string escaped for use within a JSON string literal
+pub struct Escaped<'a>(pub &'a str);
+
+impl<'a> Display for Escaped<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for c in self.0.chars() {
+            match c {
+                '"' => f.write_str("\\\"")?,
+                '\\' => f.write_str("\\\\")?,
+                '\n' => f.write_str("\\n")?,
+                '\r' => f.write_str("\\r")?,
+                '\t' => f.write_str("\\t")?,
+                c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+                c => f.write_char(c)?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum JsonError<E> {
+    IoError(E),
+    InvalidData(&'static str),
+    /// The document ended within a value
+    UnexpectedEof,
+    /// A key, string or number does not fit into the buffer of the reader
+    TooLong,
+    /// The containers are nested deeper than the reader or the writer supports
+    TooDeep,
+}
+
+impl_error! {
+    JsonError<E: Display> {
+        IoError(e) => "IO error: {e}"; e.error_kind(),
+        InvalidData(e) => "Invalid JSON: {e}"; error::ErrorKind::InvalidInput,
+        UnexpectedEof => "Unexpected end of JSON"; error::ErrorKind::InvalidInput,
+        TooLong => "JSON token too long"; error::ErrorKind::InvalidInput,
+        TooDeep => "JSON nested too deep"; error::ErrorKind::InvalidInput,
+    }
+}
+
+impl<E> io::Error for JsonError<E>
+where
+    E: io::Error,
+{
+    fn kind(&self) -> ErrorKind {
+        match self {
+            Self::IoError(e) => e.kind(),
+            _ => ErrorKind::Other,
+        }
+    }
+}
+
+impl<E> From<WriteFmtError<E>> for JsonError<E> {
+    fn from(e: WriteFmtError<E>) -> Self {
+        match e {
+            WriteFmtError::Other(e) => Self::IoError(e),
+            WriteFmtError::FmtError => Self::InvalidData("Formatting error"),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Event<'a> {
+    BeginObject,
+    EndObject,
+    BeginArray,
+    EndArray,
+    /// Unescaped
+    Key(&'a str),
+    /// Unescaped
+    String(&'a str),
+    /// As it appears in the document, e.g. `-1.5e3`; parse it into the type expected
+    Number(&'a str),
+    Bool(bool),
+    Null,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Container {
+    Object,
+    Array,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Expect {
+    Value,
+    /// After `[`
+    ValueOrEnd,
+    /// After `{`
+    KeyOrEnd,
+    /// After a `,` within an object
+    Key,
+    CommaOrEnd,
+    Done,
+}
+
+/// Reads a JSON document as a sequence of `Event`s.
+///
+/// Keys, strings and numbers longer than `N` bytes fail with `JsonError::TooLong`, unless they are skipped
+/// with `skip_value`; containers may be nested `D` deep. The document is validated as it is read, and
+/// anything but whitespace after it is an error.
+pub struct JsonReader<R, const N: usize = 64, const D: usize = 16> {
+    read: R,
+    ahead: [u8; READ_AHEAD],
+    offset: usize,
+    len: usize,
+    text: heapless::Vec<u8, N>,
+    stack: heapless::Vec<Container, D>,
+    expect: Expect,
+    discard: bool,
+}
+
+impl<R, const N: usize, const D: usize> JsonReader<R, N, D>
+where
+    R: Read,
+{
+    pub fn new(read: R) -> Self {
+        Self {
+            read,
+            ahead: [0; READ_AHEAD],
+            offset: 0,
+            len: 0,
+            text: heapless::Vec::new(),
+            stack: heapless::Vec::new(),
+            expect: Expect::Value,
+            discard: false,
+        }
+    }
+
+    /// The number of containers the reader is within
+    pub fn depth(&self) -> usize {
+        self.stack.len()
+    }
+
+    /// Returns the next event, or `None` at the end of the document
+    pub fn next_event(&mut self) -> Result<Option<Event<'_>>, JsonError<R::Error>> {
+        loop {
+            self.skip_whitespace()?;
+
+            let byte = match (self.peek()?, self.expect) {
+                (None, Expect::Done) => return Ok(None),
+                (Some(_), Expect::Done) => return Err(JsonError::InvalidData("Trailing data")),
+                (None, _) => return Err(JsonError::UnexpectedEof),
+                (Some(byte), _) => byte,
+            };
+
+            match (self.expect, byte) {
+                (Expect::ValueOrEnd, b']') | (Expect::KeyOrEnd, b'}') => {
+                    return self.end(byte).map(Some)
+                }
+                (Expect::CommaOrEnd, b']' | b'}') => return self.end(byte).map(Some),
+                (Expect::CommaOrEnd, b',') => {
+                    self.bump();
+
+                    self.expect = match self.stack.last() {
+                        Some(Container::Object) => Expect::Key,
+                        _ => Expect::Value,
+                    };
+                }
+                (Expect::CommaOrEnd, _) => {
+                    return Err(JsonError::InvalidData(
+                        "Expected , or the end of a container",
+                    ))
+                }
+                (Expect::KeyOrEnd | Expect::Key, b'"') => {
+                    self.bump();
+                    self.read_string()?;
+
+                    self.skip_whitespace()?;
+
+                    if self.take()? != b':' {
+                        return Err(JsonError::InvalidData("Expected :"));
+                    }
+
+                    self.expect = Expect::Value;
+
+                    return Ok(Some(Event::Key(self.text())));
+                }
+                (Expect::KeyOrEnd | Expect::Key, _) => {
+                    return Err(JsonError::InvalidData("Expected a key"))
+                }
+                _ => return self.value(byte).map(Some),
+            }
+        }
+    }
+
+    /// Skips the next value, including the containers nested within it.
+    ///
+    /// Returns `false` if the enclosing container ends instead, having consumed its end, or if the document
+    /// ended.
+    pub fn skip_value(&mut self) -> Result<bool, JsonError<R::Error>> {
+        self.discard = true;
+
+        let skipped = self.skip();
+
+        self.discard = false;
+
+        skipped
+    }
+
+    /// Reads up to the value at `path`, a sequence of keys of nested objects starting with the next value,
+    /// so that the next event is the value itself.
+    ///
+    /// Returns `false` if one of the keys is missing or a value along the path is not an object; the
+    /// reader is then left somewhere within the document. An empty path matches the next value.
+    pub fn seek(&mut self, path: &[&str]) -> Result<bool, JsonError<R::Error>> {
+        for key in path {
+            if self.next_event()? != Some(Event::BeginObject) {
+                return Ok(false);
+            }
+
+            loop {
+                match self.next_event()? {
+                    Some(Event::Key(name)) if name == *key => break,
+                    Some(Event::Key(_)) => {
+                        self.skip_value()?;
+                    }
+                    _ => return Ok(false),
+                }
+            }
+        }
+
+        Ok(true)
+    }
+
+    pub fn release(self) -> R {
+        self.read
+    }
+
+    fn skip(&mut self) -> Result<bool, JsonError<R::Error>> {
+        let depth = match self.next_event()? {
+            Some(Event::BeginObject | Event::BeginArray) => self.depth(),
+            Some(Event::EndObject | Event::EndArray) | None => return Ok(false),
+            Some(_) => return Ok(true),
+        };
+
+        while self.depth() >= depth {
+            self.next_event()?;
+        }
+
+        Ok(true)
+    }
+
+    fn value(&mut self, byte: u8) -> Result<Event<'_>, JsonError<R::Error>> {
+        let event = match byte {
+            b'{' | b'[' => {
+                let (container, event, expect) = if byte == b'{' {
+                    (Container::Object, Event::BeginObject, Expect::KeyOrEnd)
+                } else {
+                    (Container::Array, Event::BeginArray, Expect::ValueOrEnd)
+                };
+
+                self.bump();
+                self.stack.push(container).map_err(|_| JsonError::TooDeep)?;
+                self.expect = expect;
+
+                return Ok(event);
+            }
+            b'"' => {
+                self.bump();
+                self.read_string()?;
+
+                None
+            }
+            b'-' | b'0'..=b'9' => {
+                self.read_number()?;
+
+                None
+            }
+            b't' => Some(self.literal("true", Event::Bool(true))?),
+            b'f' => Some(self.literal("false", Event::Bool(false))?),
+            b'n' => Some(self.literal("null", Event::Null)?),
+            _ => return Err(JsonError::InvalidData("Expected a value")),
+        };
+
+        self.expect = self.after_value();
+
+        Ok(match event {
+            Some(event) => event,
+            None if byte == b'"' => Event::String(self.text()),
+            None => Event::Number(self.text()),
+        })
+    }
+
+    fn end(&mut self, byte: u8) -> Result<Event<'static>, JsonError<R::Error>> {
+        self.bump();
+
+        let event = match (self.stack.pop(), byte) {
+            (Some(Container::Object), b'}') => Event::EndObject,
+            (Some(Container::Array), b']') => Event::EndArray,
+            _ => return Err(JsonError::InvalidData("Mismatched end of container")),
+        };
+
+        self.expect = self.after_value();
+
+        Ok(event)
+    }
+
+    fn after_value(&self) -> Expect {
+        if self.stack.is_empty() {
+            Expect::Done
+        } else {
+            Expect::CommaOrEnd
+        }
+    }
+
+    fn literal(
+        &mut self,
+        literal: &str,
+        event: Event<'static>,
+    ) -> Result<Event<'static>, JsonError<R::Error>> {
+        for expected in literal.bytes() {
+            if self.take()? != expected {
+                return Err(JsonError::InvalidData("Invalid literal"));
+            }
+        }
+
+        Ok(event)
+    }
+
+    fn read_string(&mut self) -> Result<(), JsonError<R::Error>> {
+        self.text.clear();
+
+        loop {
+            match self.take()? {
+                b'"' => break,
+                b'\\' => {
+                    let unescaped = match self.take()? {
+                        b'u' => {
+                            let mut code = self.read_hex()?;
+
+                            if (0xd800..0xdc00).contains(&code) {
+                                if self.take()? != b'\\' || self.take()? != b'u' {
+                                    return Err(JsonError::InvalidData("Unpaired surrogate"));
+                                }
+
+                                let low = self.read_hex()?;
+
+                                if !(0xdc00..0xe000).contains(&low) {
+                                    return Err(JsonError::InvalidData("Unpaired surrogate"));
+                                }
+
+                                code = 0x10000 + ((code - 0xd800) << 10) + (low - 0xdc00);
+                            }
+
+                            let c = char::from_u32(code)
+                                .ok_or(JsonError::InvalidData("Unpaired surrogate"))?;
+
+                            let mut utf8 = [0; 4];
+
+                            for byte in c.encode_utf8(&mut utf8).bytes() {
+                                self.store(byte)?;
+                            }
+
+                            continue;
+                        }
+                        b'"' => b'"',
+                        b'\\' => b'\\',
+                        b'/' => b'/',
+                        b'b' => 0x08,
+                        b'f' => 0x0c,
+                        b'n' => b'\n',
+                        b'r' => b'\r',
+                        b't' => b'\t',
+                        _ => return Err(JsonError::InvalidData("Invalid escape sequence")),
+                    };
+
+                    self.store(unescaped)?;
+                }
+                byte if byte < 0x20 => {
+                    return Err(JsonError::InvalidData("Control character in string"))
+                }
+                byte => self.store(byte)?,
+            }
+        }
+
+        if !self.discard && core::str::from_utf8(&self.text).is_err() {
+            return Err(JsonError::InvalidData("Invalid UTF-8"));
+        }
+
+        Ok(())
+    }
+
+    fn read_hex(&mut self) -> Result<u32, JsonError<R::Error>> {
+        let mut code = 0;
+
+        for _ in 0..4 {
+            let digit = (self.take()? as char)
+                .to_digit(16)
+                .ok_or(JsonError::InvalidData("Invalid escape sequence"))?;
+
+            code = (code << 4) | digit;
+        }
+
+        Ok(code)
+    }
+
+    fn read_number(&mut self) -> Result<(), JsonError<R::Error>> {
+        self.text.clear();
+
+        if self.peek()? == Some(b'-') {
+            self.bump();
+            self.store(b'-')?;
+        }
+
+        if self.peek()? == Some(b'0') {
+            self.bump();
+            self.store(b'0')?;
+        } else {
+            self.read_digits()?;
+        }
+
+        if self.peek()? == Some(b'.') {
+            self.bump();
+            self.store(b'.')?;
+            self.read_digits()?;
+        }
+
+        if let Some(byte @ (b'e' | b'E')) = self.peek()? {
+            self.bump();
+            self.store(byte)?;
+
+            if let Some(byte @ (b'+' | b'-')) = self.peek()? {
+                self.bump();
+                self.store(byte)?;
+            }
+
+            self.read_digits()?;
+        }
+
+        Ok(())
+    }
+
+    fn read_digits(&mut self) -> Result<(), JsonError<R::Error>> {
+        let mut count = 0;
+
+        while let Some(byte @ b'0'..=b'9') = self.peek()? {
+            self.bump();
+            self.store(byte)?;
+
+            count += 1;
+        }
+
+        if count > 0 {
+            Ok(())
+        } else {
+            Err(JsonError::InvalidData("Invalid number"))
+        }
+    }
+
+    fn text(&self) -> &str {
+        core::str::from_utf8(&self.text).unwrap_or_default()
+    }
+
+    fn store(&mut self, byte: u8) -> Result<(), JsonError<R::Error>> {
+        if self.discard {
+            Ok(())
+        } else {
+            self.text.push(byte).map_err(|_| JsonError::TooLong)
+        }
+    }
+
+    fn skip_whitespace(&mut self) -> Result<(), JsonError<R::Error>> {
+        while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.peek()? {
+            self.bump();
+        }
+
+        Ok(())
+    }
+
+    fn take(&mut self) -> Result<u8, JsonError<R::Error>> {
+        let byte = self.peek()?.ok_or(JsonError::UnexpectedEof)?;
+
+        self.bump();
+
+        Ok(byte)
+    }
+
+    fn peek(&mut self) -> Result<Option<u8>, JsonError<R::Error>> {
+        if self.offset == self.len {
+            self.len = self
+                .read
+                .read(&mut self.ahead)
+                .map_err(JsonError::IoError)?;
+            self.offset = 0;
+        }
+
+        Ok((self.offset < self.len).then(|| self.ahead[self.offset]))
+    }
+
+    fn bump(&mut self) {
+        self.offset += 1;
+    }
+}
+
+/// Writes a JSON document, inserting the separators between keys and values.
+///
+/// Containers may be nested `D` deep. Misplaced keys and values, e.g. a value within an object without
+/// a key, fail with `JsonError::InvalidData`.
+pub struct JsonWriter<W, const D: usize = 16> {
+    write: W,
+    /// The open containers, and whether they have any element yet
+    stack: heapless::Vec<(Container, bool), D>,
+    keyed: bool,
+    done: bool,
+}
+
+impl<W, const D: usize> JsonWriter<W, D>
+where
+    W: Write,
+{
+    pub const fn new(write: W) -> Self {
+        Self {
+            write,
+            stack: heapless::Vec::new(),
+            keyed: false,
+            done: false,
+        }
+    }
+
+    /// Whether the document is complete, i.e. a value was written and all its containers are closed
+    pub fn is_complete(&self) -> bool {
+        self.done
+    }
+
+    pub fn begin_object(&mut self) -> Result<&mut Self, JsonError<W::Error>> {
+        self.begin(Container::Object, b"{")
+    }
+
+    pub fn end_object(&mut self) -> Result<&mut Self, JsonError<W::Error>> {
+        self.end(Container::Object, b"}")
+    }
+
+    pub fn begin_array(&mut self) -> Result<&mut Self, JsonError<W::Error>> {
+        self.begin(Container::Array, b"[")
+    }
+
+    pub fn end_array(&mut self) -> Result<&mut Self, JsonError<W::Error>> {
+        self.end(Container::Array, b"]")
+    }
+
+    pub fn key(&mut self, key: &str) -> Result<&mut Self, JsonError<W::Error>> {
+        match self.stack.last_mut() {
+            Some((Container::Object, has_elements)) if !self.keyed => {
+                if *has_elements {
+                    self.write.write_all(b",").map_err(JsonError::IoError)?;
+                }
+
+                *has_elements = true;
+            }
+            _ => return Err(JsonError::InvalidData("Key outside of an object")),
+        }
+
+        write!(self.write, "\"{}\":", Escaped(key))?;
+
+        self.keyed = true;
+
+        Ok(self)
+    }
+
+    pub fn string(&mut self, value: &str) -> Result<&mut Self, JsonError<W::Error>> {
+        self.separate()?;
+
+        write!(self.write, "\"{}\"", Escaped(value))?;
+
+        Ok(self)
+    }
+
+    pub fn int(&mut self, value: i64) -> Result<&mut Self, JsonError<W::Error>> {
+        self.separate()?;
+
+        write!(self.write, "{value}")?;
+
+        Ok(self)
+    }
+
+    pub fn uint(&mut self, value: u64) -> Result<&mut Self, JsonError<W::Error>> {
+        self.separate()?;
+
+        write!(self.write, "{value}")?;
+
+        Ok(self)
+    }
+
+    /// NaN and infinities have no JSON representation and fail with `JsonError::InvalidData`
+    pub fn float(&mut self, value: f64) -> Result<&mut Self, JsonError<W::Error>> {
+        if !value.is_finite() {
+            return Err(JsonError::InvalidData("Non-finite number"));
+        }
+
+        self.separate()?;
+
+        write!(self.write, "{value}")?;
+
+        Ok(self)
+    }
+
+    pub fn bool(&mut self, value: bool) -> Result<&mut Self, JsonError<W::Error>> {
+        self.raw(if value { b"true" } else { b"false" })
+    }
+
+    pub fn null(&mut self) -> Result<&mut Self, JsonError<W::Error>> {
+        self.raw(b"null")
+    }
+
+    pub fn release(self) -> W {
+        self.write
+    }
+
+    fn begin(
+        &mut self,
+        container: Container,
+        token: &[u8],
+    ) -> Result<&mut Self, JsonError<W::Error>> {
+        self.separate()?;
+
+        self.stack
+            .push((container, false))
+            .map_err(|_| JsonError::TooDeep)?;
+        self.done = false;
+
+        self.write.write_all(token).map_err(JsonError::IoError)?;
+
+        Ok(self)
+    }
+
+    fn end(
+        &mut self,
+        container: Container,
+        token: &[u8],
+    ) -> Result<&mut Self, JsonError<W::Error>> {
+        if self.keyed || self.stack.last().map(|(open, _)| *open) != Some(container) {
+            return Err(JsonError::InvalidData("Mismatched end of container"));
+        }
+
+        self.stack.pop();
+        self.done = self.stack.is_empty();
+
+        self.write.write_all(token).map_err(JsonError::IoError)?;
+
+        Ok(self)
+    }
+
+    fn raw(&mut self, token: &[u8]) -> Result<&mut Self, JsonError<W::Error>> {
+        self.separate()?;
+
+        self.write.write_all(token).map_err(JsonError::IoError)?;
+
+        Ok(self)
+    }
+
+    /// Writes the `,` before a value if needed, and checks that a value is allowed here
+    fn separate(&mut self) -> Result<(), JsonError<W::Error>> {
+        match self.stack.last_mut() {
+            None if self.done => return Err(JsonError::InvalidData("Trailing value")),
+            None => self.done = true,
+            Some((Container::Object, _)) if !self.keyed => {
+                return Err(JsonError::InvalidData("Value without a key"))
+            }
+            Some((Container::Object, _)) => self.keyed = false,
+            Some((Container::Array, has_elements)) => {
+                if *has_elements {
+                    self.write.write_all(b",").map_err(JsonError::IoError)?;
+                }
+
+                *has_elements = true;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::convert::Infallible;
+
+    use super::*;
+
+    /// Reads `data` in chunks of `chunk` bytes, to cross the read-ahead buffer of the reader
+    struct Chunks<'a> {
+        data: &'a [u8],
+        chunk: usize,
+    }
+
+    impl<'a> io::Io for Chunks<'a> {
+        type Error = Infallible;
+    }
+
+    impl<'a> Read for Chunks<'a> {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+            let len = buf.len().min(self.chunk).min(self.data.len());
+
+            buf[..len].copy_from_slice(&self.data[..len]);
+            self.data = &self.data[len..];
+
+            Ok(len)
+        }
+    }
+
+    struct Buffer(heapless::Vec<u8, 128>);
+
+    impl io::Io for Buffer {
+        type Error = Infallible;
+    }
+
+    impl Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+            self.0.extend_from_slice(buf).unwrap();
+
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> Result<(), Self::Error> {
+            Ok(())
+        }
+    }
+
+    fn read<const N: usize, const D: usize>(
+        json: &str,
+        chunk: usize,
+    ) -> JsonReader<Chunks<'_>, N, D> {
+        JsonReader::new(Chunks {
+            data: json.as_bytes(),
+            chunk,
+        })
+    }
+
+    const DOCUMENT: &str = r#" {"id": "sensor-1", "values": [1, -0.5, 2e3, true, null],
+        "nested": {"deep": [[], {}]}, "text": "a\"é😀\n"} "#;
+
+    #[test]
+    fn events() {
+        for chunk in [1, 3, DOCUMENT.len()] {
+            let mut reader = read::<64, 4>(DOCUMENT, chunk);
+
+            for expected in [
+                Event::BeginObject,
+                Event::Key("id"),
+                Event::String("sensor-1"),
+                Event::Key("values"),
+                Event::BeginArray,
+                Event::Number("1"),
+                Event::Number("-0.5"),
+                Event::Number("2e3"),
+                Event::Bool(true),
+                Event::Null,
+                Event::EndArray,
+                Event::Key("nested"),
+                Event::BeginObject,
+                Event::Key("deep"),
+                Event::BeginArray,
+                Event::BeginArray,
+                Event::EndArray,
+                Event::BeginObject,
+                Event::EndObject,
+                Event::EndArray,
+                Event::EndObject,
+                Event::Key("text"),
+                Event::String("a\"\u{e9}\u{1f600}\n"),
+                Event::EndObject,
+            ] {
+                assert_eq!(reader.next_event().unwrap(), Some(expected));
+            }
+
+            assert_eq!(reader.next_event().unwrap(), None);
+            assert_eq!(reader.depth(), 0);
+        }
+    }
+
+    #[test]
+    fn seek() {
+        let mut reader = read::<8, 4>(DOCUMENT, 5);
+
+        // The values skipped on the way are not buffered, so they may be longer than the buffer
+        assert!(reader.seek(&["nested", "deep"]).unwrap());
+        assert_eq!(reader.next_event().unwrap(), Some(Event::BeginArray));
+        assert!(reader.skip_value().unwrap());
+        assert!(reader.skip_value().unwrap());
+        assert!(!reader.skip_value().unwrap());
+        assert_eq!(reader.depth(), 2);
+
+        let mut reader = read::<64, 4>(DOCUMENT, 5);
+
+        assert!(!reader.seek(&["id", "missing"]).unwrap());
+
+        let mut reader = read::<64, 4>(DOCUMENT, 5);
+
+        assert!(!reader.seek(&["missing"]).unwrap());
+    }
+
+    #[test]
+    fn reader_errors() {
+        fn error(json: &str) -> JsonError<Infallible> {
+            let mut reader = read::<8, 2>(json, 4);
+
+            loop {
+                match reader.next_event() {
+                    Ok(Some(_)) => (),
+                    Ok(None) => panic!("{} is valid", json),
+                    Err(e) => return e,
+                }
+            }
+        }
+
+        assert!(matches!(error("[1] 2"), JsonError::InvalidData(_)));
+        assert!(matches!(error("[1}"), JsonError::InvalidData(_)));
+        assert!(matches!(error("[1 2]"), JsonError::InvalidData(_)));
+        assert!(matches!(error("{1: 2}"), JsonError::InvalidData(_)));
+        assert!(matches!(error(r#"{"a" 2}"#), JsonError::InvalidData(_)));
+        assert!(matches!(error("01"), JsonError::InvalidData(_)));
+        assert!(matches!(error("-"), JsonError::InvalidData(_)));
+        assert!(matches!(error("1."), JsonError::InvalidData(_)));
+        assert!(matches!(error("tru"), JsonError::UnexpectedEof));
+        assert!(matches!(error("nul1"), JsonError::InvalidData(_)));
+        assert!(matches!(error(r#""\x""#), JsonError::InvalidData(_)));
+        assert!(matches!(error(r#""\ud83d""#), JsonError::InvalidData(_)));
+        assert!(matches!(error("\"a\nb\""), JsonError::InvalidData(_)));
+        assert!(matches!(error(r#"["abc"#), JsonError::UnexpectedEof));
+        assert!(matches!(error(""), JsonError::UnexpectedEof));
+        assert!(matches!(error(r#""123456789""#), JsonError::TooLong));
+        assert!(matches!(error("[[[]]]"), JsonError::TooDeep));
+    }
+
+    #[test]
+    fn writer() {
+        let mut writer = JsonWriter::<_, 4>::new(Buffer(heapless::Vec::new()));
+
+        writer
+            .begin_object()
+            .unwrap()
+            .key("id")
+            .unwrap()
+            .string("sensor \"1\"\n")
+            .unwrap()
+            .key("values")
+            .unwrap()
+            .begin_array()
+            .unwrap()
+            .int(-1)
+            .unwrap()
+            .uint(2)
+            .unwrap()
+            .float(0.5)
+            .unwrap()
+            .bool(false)
+            .unwrap()
+            .null()
+            .unwrap()
+            .begin_object()
+            .unwrap()
+            .end_object()
+            .unwrap()
+            .end_array()
+            .unwrap();
+
+        assert!(!writer.is_complete());
+
+        writer.end_object().unwrap();
+
+        assert!(writer.is_complete());
+        assert_eq!(
+            writer.release().0,
+            br#"{"id":"sensor \"1\"\n","values":[-1,2,0.5,false,null,{}]}"#[..]
+        );
+    }
+
+    #[test]
+    fn writer_errors() {
+        let mut writer = JsonWriter::<_, 2>::new(Buffer(heapless::Vec::new()));
+
+        assert!(matches!(writer.key("a"), Err(JsonError::InvalidData(_))));
+
+        writer.begin_object().unwrap();
+
+        assert!(matches!(writer.int(1), Err(JsonError::InvalidData(_))));
+        assert!(matches!(writer.end_array(), Err(JsonError::InvalidData(_))));
+
+        writer.key("a").unwrap();
+
+        assert!(matches!(writer.key("b"), Err(JsonError::InvalidData(_))));
+        assert!(matches!(
+            writer.end_object(),
+            Err(JsonError::InvalidData(_))
+        ));
+        assert!(matches!(
+            writer.float(f64::NAN),
+            Err(JsonError::InvalidData(_))
+        ));
+
+        writer.begin_array().unwrap();
+
+        assert!(matches!(writer.begin_array(), Err(JsonError::TooDeep)));
+
+        writer.end_array().unwrap().end_object().unwrap();
+
+        assert!(matches!(writer.null(), Err(JsonError::InvalidData(_))));
+        assert_eq!(writer.release().0, br#"{"a":[]}"#[..]);
+    }
+
+    #[test]
+    fn escaped() {
+        let mut escaped = heapless::String::<32>::new();
+
+        write!(&mut escaped, "{}", Escaped("a\"b\\c\t\u{1}é")).unwrap();
+
+        assert_eq!(escaped, r#"a\"b\\c\t\u0001é"#);
+    }
+}
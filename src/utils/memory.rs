@@ -0,0 +1,225 @@
+//! Monitoring of the heap and of the task stacks, to catch leaks and stack overflows in long-running
+//! deployments before they crash the device.
+
+use core::fmt::Debug;
+use core::time::Duration;
+
+#[cfg(feature = "use_serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::error::impl_error;
+use crate::event_bus::Postbox;
+use crate::sys_info::{HeapInfo, StackInfo, SystemInfo};
+use crate::sys_time::{Instant, SystemTime};
+use crate::timer::PeriodicTimer;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub struct Thresholds {
+    pub min_free_heap: usize,
+    pub min_largest_free_block: usize,
+    /// The lowest acceptable high watermark of any task stack
+    pub min_stack: usize,
+}
+
+impl Default for Thresholds {
+    fn default() -> Self {
+        Self {
+            min_free_heap: 16 * 1024,
+            min_largest_free_block: 4 * 1024,
+            min_stack: 256,
+        }
+    }
+}
+
+/// Posted when a value drops below its threshold; posted again only once it went back above it
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub enum MemoryAlert {
+    LowHeap(HeapInfo),
+    Fragmented(HeapInfo),
+    LowStack(StackInfo),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub struct Sample {
+    pub at: Instant,
+    pub heap: HeapInfo,
+    /// The lowest high watermark of the task stacks, if any task is known
+    pub min_stack: Option<usize>,
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum MemoryError<I, P> {
+    InfoError(I),
+    PostError(P),
+}
+
+impl_error! {
+    MemoryError<I: Debug, P: Debug> {
+        InfoError(e) => "System info error: {e:?}"; e.error_kind(),
+        PostError(e) => "Post error: {e:?}"; e.error_kind(),
+    }
+}
+
+/// Samples the heap and the watermarks of up to `N` task stacks, keeping the last `H` samples, and posts a
+/// `MemoryAlert` whenever a threshold is crossed.
+///
+/// Sampling is driven by the application, typically from the callback of a timer armed with `arm`.
+pub struct MemoryMonitor<I, T, P, const H: usize = 16, const N: usize = 16> {
+    info: I,
+    time: T,
+    postbox: P,
+    interval: Duration,
+    thresholds: Thresholds,
+    history: heapless::HistoryBuffer<Sample, H>,
+    low_heap: bool,
+    fragmented: bool,
+    low_stacks: heapless::Vec<heapless::String<16>, N>,
+}
+
+impl<I, T, P, const H: usize, const N: usize> MemoryMonitor<I, T, P, H, N>
+where
+    I: SystemInfo,
+    T: SystemTime,
+    P: Postbox<MemoryAlert>,
+{
+    pub fn new(info: I, time: T, postbox: P, interval: Duration, thresholds: Thresholds) -> Self {
+        Self {
+            info,
+            time,
+            postbox,
+            interval,
+            thresholds,
+            history: heapless::HistoryBuffer::new(),
+            low_heap: false,
+            fragmented: false,
+            low_stacks: heapless::Vec::new(),
+        }
+    }
+
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    pub fn thresholds(&self) -> &Thresholds {
+        &self.thresholds
+    }
+
+    /// Takes effect with the next sample
+    pub fn set_thresholds(&mut self, thresholds: Thresholds) {
+        self.thresholds = thresholds;
+    }
+
+    /// Schedules `timer` to fire at the sampling interval
+    pub fn arm<O>(&self, timer: &mut O) -> Result<(), O::Error>
+    where
+        O: PeriodicTimer,
+    {
+        timer.every(self.interval)
+    }
+
+    pub fn latest(&self) -> Option<&Sample> {
+        self.history.recent()
+    }
+
+    /// The samples kept, oldest first
+    pub fn history(&self) -> impl Iterator<Item = &Sample> {
+        self.history.oldest_ordered()
+    }
+
+    /// The change of the free heap over the samples kept, in bytes per hour; a steadily negative value hints
+    /// at a leak.
+    ///
+    /// Returns `None` until two samples were taken.
+    pub fn heap_trend(&self) -> Option<i64> {
+        let oldest = self.history.oldest_ordered().next()?;
+        let latest = self.history.recent()?;
+
+        let elapsed = latest.at.duration_since(oldest.at).as_secs() as i64;
+
+        if elapsed == 0 {
+            return None;
+        }
+
+        Some((latest.heap.free as i64 - oldest.heap.free as i64) * 3600 / elapsed)
+    }
+
+    /// Takes a sample, posting an alert for each threshold crossed since the previous one.
+    ///
+    /// Alerts which could not be posted within `wait` are posted with the next sample.
+    pub fn sample(
+        &mut self,
+        wait: Option<Duration>,
+    ) -> Result<Sample, MemoryError<I::Error, P::Error>> {
+        let heap = self.info.heap().map_err(MemoryError::InfoError)?;
+        let (stacks, _) = self.info.stacks_n::<N>().map_err(MemoryError::InfoError)?;
+
+        let sample = Sample {
+            at: Instant::now(&self.time),
+            heap,
+            min_stack: stacks.iter().map(|stack| stack.high_watermark).min(),
+        };
+
+        self.history.write(sample);
+
+        self.low_heap = self.check(
+            heap.free < self.thresholds.min_free_heap,
+            self.low_heap,
+            MemoryAlert::LowHeap(heap),
+            wait,
+        )?;
+
+        self.fragmented = self.check(
+            heap.largest_free_block < self.thresholds.min_largest_free_block,
+            self.fragmented,
+            MemoryAlert::Fragmented(heap),
+            wait,
+        )?;
+
+        let mut low_stacks = heapless::Vec::new();
+
+        for stack in stacks {
+            let low = stack.high_watermark < self.thresholds.min_stack;
+            let alerted = self.low_stacks.contains(&stack.task);
+            let task = stack.task.clone();
+
+            if self.check(low, alerted, MemoryAlert::LowStack(stack), wait)? {
+                let _ = low_stacks.push(task);
+            }
+        }
+
+        self.low_stacks = low_stacks;
+
+        Ok(sample)
+    }
+
+    pub fn release(self) -> (I, T, P) {
+        (self.info, self.time, self.postbox)
+    }
+
+    /// Returns whether the alert is raised after the check
+    fn check(
+        &self,
+        low: bool,
+        alerted: bool,
+        alert: MemoryAlert,
+        wait: Option<Duration>,
+    ) -> Result<bool, MemoryError<I::Error, P::Error>> {
+        if !low {
+            Ok(false)
+        } else if alerted {
+            Ok(true)
+        } else {
+            self.postbox
+                .post(&alert, wait)
+                .map_err(MemoryError::PostError)
+        }
+    }
+}
@@ -151,3 +151,675 @@ pub mod client {
         }
     }
 }
+
+#[cfg(feature = "experimental")]
+pub mod ws {
+    use crate::io::{Io, Read, Write};
+    use crate::ws::{FrameType, MessageError, Receiver, Sender};
+
+    /// The WebSocket subprotocol to request when connecting to the broker
+    pub const SUBPROTOCOL: &str = "mqtt";
+
+    /// Carries the MQTT byte stream in WebSocket binary frames (MQTT 3.1.1 section 6), so that
+    /// MQTT clients running over `Read` + `Write` can reach brokers which only expose WebSocket endpoints.
+    ///
+    /// The connection should be opened with the `SUBPROTOCOL`. Writes are buffered into frames
+    /// of up to `W` bytes, sent when the buffer is full and on `flush`; received frames should fit into `R` bytes.
+    pub struct WsTransport<C, const R: usize = 1024, const W: usize = 1024> {
+        connection: C,
+        rx: [u8; R],
+        rx_offset: usize,
+        rx_len: usize,
+        tx: [u8; W],
+        tx_len: usize,
+        closed: bool,
+    }
+
+    impl<C, const R: usize, const W: usize> WsTransport<C, R, W>
+    where
+        C: Sender + Receiver,
+    {
+        pub const fn new(connection: C) -> Self {
+            Self {
+                connection,
+                rx: [0; R],
+                rx_offset: 0,
+                rx_len: 0,
+                tx: [0; W],
+                tx_len: 0,
+                closed: false,
+            }
+        }
+
+        pub fn release(self) -> C {
+            self.connection
+        }
+
+        fn send_buffered(&mut self) -> Result<(), MessageError<C::Error>> {
+            if self.tx_len > 0 {
+                self.connection
+                    .send(FrameType::Binary(false), &self.tx[..self.tx_len])
+                    .map_err(MessageError::WsError)?;
+
+                self.tx_len = 0;
+            }
+
+            Ok(())
+        }
+    }
+
+    impl<C, const R: usize, const W: usize> Io for WsTransport<C, R, W>
+    where
+        C: Sender + Receiver,
+    {
+        type Error = MessageError<C::Error>;
+    }
+
+    impl<C, const R: usize, const W: usize> Read for WsTransport<C, R, W>
+    where
+        C: Sender + Receiver,
+    {
+        /// Returns 0 once the broker closed the connection
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+            while self.rx_offset == self.rx_len {
+                if self.closed {
+                    return Ok(0);
+                }
+
+                let (frame_type, len) = self
+                    .connection
+                    .recv(&mut self.rx)
+                    .map_err(MessageError::WsError)?;
+
+                match frame_type {
+                    FrameType::Binary(_) | FrameType::Continue(_) => {
+                        self.rx_offset = 0;
+                        self.rx_len = len;
+                    }
+                    FrameType::Ping => {
+                        self.connection
+                            .send(FrameType::Pong, &self.rx[..len])
+                            .map_err(MessageError::WsError)?;
+                    }
+                    FrameType::Pong => (),
+                    FrameType::Close | FrameType::SocketClose => self.closed = true,
+                    FrameType::Text(_) => return Err(MessageError::UnexpectedFrame(frame_type)),
+                }
+            }
+
+            let len = buf.len().min(self.rx_len - self.rx_offset);
+
+            buf[..len].copy_from_slice(&self.rx[self.rx_offset..self.rx_offset + len]);
+            self.rx_offset += len;
+
+            Ok(len)
+        }
+    }
+
+    impl<C, const R: usize, const W: usize> Write for WsTransport<C, R, W>
+    where
+        C: Sender + Receiver,
+    {
+        fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+            if self.closed {
+                return Err(MessageError::Closed);
+            }
+
+            if self.tx_len == W {
+                self.send_buffered()?;
+            }
+
+            let len = buf.len().min(W - self.tx_len);
+
+            self.tx[self.tx_len..self.tx_len + len].copy_from_slice(&buf[..len]);
+            self.tx_len += len;
+
+            Ok(len)
+        }
+
+        fn flush(&mut self) -> Result<(), Self::Error> {
+            self.send_buffered()
+        }
+    }
+}
+
+pub mod spool {
+    use core::fmt::{Debug, Write as _};
+
+    #[cfg(feature = "use_serde")]
+    use serde::{Deserialize, Serialize};
+
+    use crate::error::impl_error;
+    use crate::mqtt::client::{MessageId, Publish, QoS};
+    use crate::storage::RawStorage;
+
+    const META: &str = "mqtt_spool";
+
+    /// The QoS, the retain flag and the topic length preceding the topic and the payload of each record
+    const HEADER_LEN: usize = 4;
+
+    /// What to do when a message does not fit into the spool anymore
+    #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+    #[cfg_attr(feature = "std", derive(Hash))]
+    #[cfg_attr(feature = "defmt", derive(defmt::Format))]
+    #[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+    pub enum Eviction {
+        /// Keeps the most recent telemetry
+        DropOldest,
+        /// Keeps the messages which were spooled first
+        DropNewest,
+    }
+
+    #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+    #[cfg_attr(feature = "std", derive(Hash))]
+    #[cfg_attr(feature = "defmt", derive(defmt::Format))]
+    #[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+    pub struct Configuration {
+        /// Each message is stored as a separate blob, so this also caps the number of blobs used
+        pub max_messages: u16,
+        pub max_bytes: usize,
+        pub eviction: Eviction,
+    }
+
+    impl Default for Configuration {
+        fn default() -> Self {
+            Self {
+                max_messages: 32,
+                max_bytes: 8192,
+                eviction: Eviction::DropOldest,
+            }
+        }
+    }
+
+    #[derive(Debug)]
+    #[cfg_attr(feature = "defmt", derive(defmt::Format))]
+    pub enum SpoolError<S, P> {
+        StorageError(S),
+        PublishError(P),
+    }
+
+    impl_error! {
+        SpoolError<S: Debug, P: Display> {
+            StorageError(e) => "Storage error: {e:?}"; e.error_kind(),
+            PublishError(e) => "Publish error: {e}"; e.error_kind(),
+        }
+    }
+
+    struct InFlight {
+        id: MessageId,
+        sequence: u32,
+        acknowledged: bool,
+    }
+
+    /// Keeps QoS 1 publishes in `RawStorage` until the broker acknowledged them,
+    /// so that they survive both connectivity gaps and reboots.
+    ///
+    /// The spooled messages are replayed in order; up to `W` of them are in flight at a time,
+    /// and each of them should fit into `B` bytes along with its topic.
+    ///
+    /// Delivery is at least once: messages in flight when the connection drops are published again.
+    pub struct Spool<S, const B: usize = 512, const W: usize = 4> {
+        storage: S,
+        configuration: Configuration,
+        head: u32,
+        tail: u32,
+        sent: u32,
+        bytes: usize,
+        /// The number of slots the spooled messages are stored in, which is persisted along with them,
+        /// as `max_messages` might change between reboots
+        slots: u16,
+        in_flight: heapless::Vec<InFlight, W>,
+    }
+
+    impl<S, const B: usize, const W: usize> Spool<S, B, W>
+    where
+        S: RawStorage,
+    {
+        /// Picks up the messages spooled before a reboot
+        pub fn new(storage: S, configuration: Configuration) -> Result<Self, S::Error> {
+            let mut meta = [0_u8; 10];
+
+            let (head, tail, slots) = match storage.get_raw(META, &mut meta)? {
+                Some(meta) if meta.len() >= 8 => (
+                    u32::from_le_bytes([meta[0], meta[1], meta[2], meta[3]]),
+                    u32::from_le_bytes([meta[4], meta[5], meta[6], meta[7]]),
+                    // Spools saved without their slots used `max_messages` slots
+                    match meta.get(8..10) {
+                        Some(slots) => u16::from_le_bytes([slots[0], slots[1]]),
+                        None => configuration.max_messages,
+                    },
+                ),
+                _ => (0, 0, configuration.max_messages),
+            };
+
+            // Older messages were overwritten
+            let head = head.max(tail.saturating_sub(slots as _));
+
+            let mut spool = Self {
+                storage,
+                configuration,
+                head,
+                tail,
+                sent: head,
+                bytes: 0,
+                slots,
+                in_flight: heapless::Vec::new(),
+            };
+
+            for sequence in head..tail {
+                spool.bytes += spool.storage.len(&spool.slot(sequence))?.unwrap_or(0);
+            }
+
+            Ok(spool)
+        }
+
+        pub fn len(&self) -> usize {
+            (self.tail - self.head) as _
+        }
+
+        pub fn is_empty(&self) -> bool {
+            self.head == self.tail
+        }
+
+        pub fn bytes(&self) -> usize {
+            self.bytes
+        }
+
+        /// Publishes a message through the spool.
+        ///
+        /// QoS 0 messages are published right away if `connected` and nothing is spooled, and dropped otherwise;
+        /// all other messages are spooled first and replayed if `connected`.
+        ///
+        /// Returns `false` if the message was dropped.
+        pub fn publish<P>(
+            &mut self,
+            publisher: &mut P,
+            connected: bool,
+            topic: &str,
+            qos: QoS,
+            retain: bool,
+            payload: &[u8],
+        ) -> Result<bool, SpoolError<S::Error, P::Error>>
+        where
+            P: Publish,
+        {
+            if qos == QoS::AtMostOnce {
+                if connected && self.is_empty() {
+                    publisher
+                        .publish(topic, qos, retain, payload)
+                        .map_err(SpoolError::PublishError)?;
+
+                    Ok(true)
+                } else {
+                    Ok(false)
+                }
+            } else {
+                let spooled = self
+                    .push(topic, qos, retain, payload)
+                    .map_err(SpoolError::StorageError)?;
+
+                if connected {
+                    self.replay(publisher)?;
+                }
+
+                Ok(spooled)
+            }
+        }
+
+        /// Appends a message to the spool, evicting older messages if `Eviction::DropOldest` is configured.
+        ///
+        /// Returns `false` if the message was dropped instead.
+        pub fn push(
+            &mut self,
+            topic: &str,
+            qos: QoS,
+            retain: bool,
+            payload: &[u8],
+        ) -> Result<bool, S::Error> {
+            let len = HEADER_LEN + topic.len() + payload.len();
+
+            if len > B || len > self.configuration.max_bytes || self.configuration.max_messages == 0
+            {
+                return Ok(false);
+            }
+
+            if self.is_empty() {
+                // Switches to the slots of the configuration once the messages stored in the
+                // previous ones are gone
+                self.slots = self.configuration.max_messages;
+            }
+
+            while self.len() >= self.configuration.max_messages.min(self.slots) as _
+                || self.bytes + len > self.configuration.max_bytes
+            {
+                match self.configuration.eviction {
+                    Eviction::DropOldest => self.evict()?,
+                    Eviction::DropNewest => return Ok(false),
+                }
+            }
+
+            let mut record = [0_u8; B];
+
+            record[0] = qos as u8;
+            record[1] = retain as u8;
+            record[2..4].copy_from_slice(&(topic.len() as u16).to_le_bytes());
+            record[4..4 + topic.len()].copy_from_slice(topic.as_bytes());
+            record[4 + topic.len()..len].copy_from_slice(payload);
+
+            self.storage
+                .set_raw(&self.slot(self.tail), &record[..len])?;
+
+            self.tail += 1;
+            self.bytes += len;
+
+            self.save()?;
+
+            Ok(true)
+        }
+
+        /// Publishes the spooled messages not yet in flight, in order.
+        /// To be called on reconnect, and after acknowledgements to keep the spool draining.
+        ///
+        /// Returns the number of messages published.
+        pub fn replay<P>(
+            &mut self,
+            publisher: &mut P,
+        ) -> Result<usize, SpoolError<S::Error, P::Error>>
+        where
+            P: Publish,
+        {
+            let mut published = 0;
+            let mut buf = [0_u8; B];
+
+            while self.sent < self.tail && !self.in_flight.is_full() {
+                let record = self
+                    .storage
+                    .get_raw(&self.slot(self.sent), &mut buf)
+                    .map_err(SpoolError::StorageError)?;
+
+                let in_flight = match record.and_then(parse) {
+                    Some((topic, qos, retain, payload)) => {
+                        let id = publisher
+                            .publish(topic, qos, retain, payload)
+                            .map_err(SpoolError::PublishError)?;
+
+                        published += 1;
+
+                        InFlight {
+                            id,
+                            sequence: self.sent,
+                            acknowledged: qos == QoS::AtMostOnce,
+                        }
+                    }
+                    // A damaged record, which is skipped
+                    None => InFlight {
+                        id: 0,
+                        sequence: self.sent,
+                        acknowledged: true,
+                    },
+                };
+
+                self.in_flight.push(in_flight).ok();
+                self.sent += 1;
+
+                self.advance().map_err(SpoolError::StorageError)?;
+            }
+
+            Ok(published)
+        }
+
+        /// Removes a message from the spool once the broker acknowledged it, i.e. on `Event::Published`
+        ///
+        /// Returns `false` if the message is not one of the spooled messages in flight.
+        pub fn acknowledge(&mut self, id: MessageId) -> Result<bool, S::Error> {
+            match self
+                .in_flight
+                .iter_mut()
+                .find(|in_flight| in_flight.id == id && !in_flight.acknowledged)
+            {
+                Some(in_flight) => {
+                    in_flight.acknowledged = true;
+                    self.advance()?;
+
+                    Ok(true)
+                }
+                None => Ok(false),
+            }
+        }
+
+        /// Marks the messages in flight for replay, e.g. on `Event::Disconnected`
+        pub fn disconnected(&mut self) {
+            self.in_flight.clear();
+            self.sent = self.head;
+        }
+
+        pub fn clear(&mut self) -> Result<(), S::Error> {
+            for sequence in self.head..self.tail {
+                self.storage.remove(&self.slot(sequence))?;
+            }
+
+            self.head = self.tail;
+            self.sent = self.tail;
+            self.bytes = 0;
+            self.in_flight.clear();
+
+            self.save()
+        }
+
+        pub fn release(self) -> S {
+            self.storage
+        }
+
+        /// Drops the acknowledged messages at the head of the spool
+        fn advance(&mut self) -> Result<(), S::Error> {
+            let mut advanced = false;
+
+            while self
+                .in_flight
+                .first()
+                .map(|in_flight| in_flight.acknowledged)
+                .unwrap_or(false)
+            {
+                let in_flight = self.in_flight.remove(0);
+
+                self.remove(in_flight.sequence)?;
+                self.head = in_flight.sequence + 1;
+
+                advanced = true;
+            }
+
+            if advanced {
+                self.save()?;
+            }
+
+            Ok(())
+        }
+
+        fn evict(&mut self) -> Result<(), S::Error> {
+            self.remove(self.head)?;
+
+            self.head += 1;
+            self.sent = self.sent.max(self.head);
+
+            let head = self.head;
+            self.in_flight
+                .retain(|in_flight| in_flight.sequence >= head);
+
+            Ok(())
+        }
+
+        fn remove(&mut self, sequence: u32) -> Result<(), S::Error> {
+            let slot = self.slot(sequence);
+
+            self.bytes -= self.storage.len(&slot)?.unwrap_or(0).min(self.bytes);
+            self.storage.remove(&slot)?;
+
+            Ok(())
+        }
+
+        fn save(&mut self) -> Result<(), S::Error> {
+            let mut meta = [0_u8; 10];
+
+            meta[..4].copy_from_slice(&self.head.to_le_bytes());
+            meta[4..8].copy_from_slice(&self.tail.to_le_bytes());
+            meta[8..].copy_from_slice(&self.slots.to_le_bytes());
+
+            self.storage.set_raw(META, &meta)?;
+
+            Ok(())
+        }
+
+        fn slot(&self, sequence: u32) -> heapless::String<24> {
+            let mut name = heapless::String::new();
+
+            write!(&mut name, "{META}_{}", sequence % self.slots.max(1) as u32).unwrap();
+
+            name
+        }
+    }
+
+    fn parse(record: &[u8]) -> Option<(&str, QoS, bool, &[u8])> {
+        let qos = match *record.first()? {
+            0 => QoS::AtMostOnce,
+            1 => QoS::AtLeastOnce,
+            2 => QoS::ExactlyOnce,
+            _ => return None,
+        };
+
+        let retain = *record.get(1)? != 0;
+        let topic_len = u16::from_le_bytes([*record.get(2)?, *record.get(3)?]) as usize;
+
+        let topic = core::str::from_utf8(record.get(HEADER_LEN..HEADER_LEN + topic_len)?).ok()?;
+
+        Some((topic, qos, retain, &record[HEADER_LEN + topic_len..]))
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use core::convert::Infallible;
+
+        use crate::mqtt::client::ErrorType;
+        use crate::storage::StorageBase;
+
+        use super::*;
+
+        #[derive(Default)]
+        struct Blobs(heapless::Vec<(heapless::String<24>, heapless::Vec<u8, 32>), 8>);
+
+        impl Blobs {
+            fn position(&self, name: &str) -> Option<usize> {
+                self.0.iter().position(|(blob, _)| blob == name)
+            }
+        }
+
+        impl StorageBase for Blobs {
+            type Error = Infallible;
+
+            fn contains(&self, name: &str) -> Result<bool, Self::Error> {
+                Ok(self.position(name).is_some())
+            }
+
+            fn remove(&mut self, name: &str) -> Result<bool, Self::Error> {
+                Ok(self
+                    .position(name)
+                    .map(|index| self.0.swap_remove(index))
+                    .is_some())
+            }
+        }
+
+        impl RawStorage for Blobs {
+            fn len(&self, name: &str) -> Result<Option<usize>, Self::Error> {
+                Ok(self.position(name).map(|index| self.0[index].1.len()))
+            }
+
+            fn get_raw<'a>(
+                &self,
+                name: &str,
+                buf: &'a mut [u8],
+            ) -> Result<Option<&'a [u8]>, Self::Error> {
+                Ok(self.position(name).map(move |index| {
+                    let blob = &self.0[index].1;
+                    buf[..blob.len()].copy_from_slice(blob);
+
+                    &buf[..blob.len()]
+                }))
+            }
+
+            fn set_raw(&mut self, name: &str, buf: &[u8]) -> Result<bool, Self::Error> {
+                self.remove(name)?;
+                self.0
+                    .push((name.into(), heapless::Vec::from_slice(buf).unwrap()))
+                    .unwrap();
+
+                Ok(true)
+            }
+        }
+
+        #[derive(Default)]
+        struct Publisher(heapless::Vec<heapless::Vec<u8, 8>, 8>);
+
+        impl ErrorType for Publisher {
+            type Error = Infallible;
+        }
+
+        impl Publish for Publisher {
+            fn publish<'a>(
+                &'a mut self,
+                _topic: &'a str,
+                _qos: QoS,
+                _retain: bool,
+                payload: &'a [u8],
+            ) -> Result<MessageId, Self::Error> {
+                self.0
+                    .push(heapless::Vec::from_slice(payload).unwrap())
+                    .unwrap();
+
+                Ok(self.0.len() as _)
+            }
+        }
+
+        fn configuration(max_messages: u16) -> Configuration {
+            Configuration {
+                max_messages,
+                ..Default::default()
+            }
+        }
+
+        #[test]
+        fn replay() {
+            let mut spool: Spool<_, 64> = Spool::new(Blobs::default(), configuration(4)).unwrap();
+
+            for payload in [b"0", b"1", b"2"] {
+                assert_eq!(spool.push("t", QoS::AtLeastOnce, false, payload), Ok(true));
+            }
+
+            let mut publisher = Publisher::default();
+
+            assert_eq!(spool.replay(&mut publisher).ok(), Some(3));
+            assert_eq!(spool.acknowledge(1), Ok(true));
+            assert_eq!(spool.len(), 2);
+            assert!(publisher.0.iter().eq([b"0", b"1", b"2"].iter().copied()));
+        }
+
+        #[test]
+        fn reopen_with_fewer_messages() {
+            let mut spool: Spool<_, 64> = Spool::new(Blobs::default(), configuration(4)).unwrap();
+
+            for payload in [b"0", b"1", b"2"] {
+                assert_eq!(spool.push("t", QoS::AtLeastOnce, false, payload), Ok(true));
+            }
+
+            // The spooled messages stay in the slots they were stored in
+            let mut spool: Spool<_, 64> = Spool::new(spool.release(), configuration(2)).unwrap();
+
+            assert_eq!(spool.len(), 3);
+            assert_eq!(spool.push("t", QoS::AtLeastOnce, false, b"3"), Ok(true));
+            assert_eq!(spool.len(), 2);
+
+            let mut publisher = Publisher::default();
+
+            assert_eq!(spool.replay(&mut publisher).ok(), Some(2));
+            assert!(publisher.0.iter().eq([b"2", b"3"].iter().copied()));
+        }
+    }
+}
@@ -0,0 +1,349 @@
+//! Onboarding of a device into a fleet: the device creates its identity, registers it with a provisioning
+//! endpoint, and persists the credentials issued once it is claimed.
+//!
+//! The identity is a P-256 key generated by the `KeyStore` provider, so that the private key never leaves
+//! it, and a device ID derived from the public key. The registration is a POST of
+//! `{"device_id":..,"public_key":..,"claim_token":..,"signature":..}`, where the keys and signatures are
+//! hex-encoded, and the signature is made over the SHA-256 of the device ID followed by the claim token, as
+//! a proof of possession of the key. The endpoint responds with:
+//! - 200 or 201 and a `Credentials` document, once the device is claimed;
+//! - 202 while it waits for the device to be claimed, e.g. by its owner entering the claim token in the
+//!   fleet console; the registration should be retried later.
+
+use core::convert::Infallible;
+use core::fmt::Debug;
+
+use serde::{Deserialize, Serialize};
+
+use crate::crypto::{EcdsaP256PublicKey, EcdsaP256Sign, KeyHandle, KeyStore, Sha256};
+use crate::error::{impl_error, ErrorKind};
+use crate::http::client::{Client, Connection};
+use crate::http::{headers, Method, Status};
+use crate::io::Write;
+use crate::storage::{RawStorage, SerDe};
+use crate::utils::codec::hex;
+use crate::utils::io::try_read_full;
+
+const IDENTITY: &str = "onb_id";
+const ENDPOINT: &str = "onb_endpoint";
+const CERTIFICATE: &str = "onb_cert";
+const CA_CERTIFICATE: &str = "onb_ca";
+
+/// Version, key handle, public key and device ID
+const IDENTITY_LEN: usize = 1 + 2 + 64 + 24;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Identity {
+    /// The first 12 bytes of the SHA-256 of the public key, hex-encoded
+    pub device_id: heapless::String<24>,
+    pub key: KeyHandle,
+    pub public_key: EcdsaP256PublicKey,
+}
+
+/// The credentials issued to a claimed device, with PEM certificates of up to `N` bytes
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Credentials<const N: usize = 2048> {
+    /// The broker or cloud endpoint assigned to the device
+    pub endpoint: heapless::String<128>,
+    /// The client certificate of the device key, signed by the fleet CA
+    pub certificate: heapless::String<N>,
+    /// The CA certificate of the endpoint, if it is not signed by a well-known CA
+    #[serde(default)]
+    pub ca_certificate: Option<heapless::String<N>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Registration<const N: usize = 2048> {
+    /// The device is not claimed yet
+    Pending,
+    Provisioned(Credentials<N>),
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum OnboardingError<H, S, K, D> {
+    HttpError(H),
+    StorageError(S),
+    CryptoError(K),
+    SerdeError(D),
+    Status(u16),
+    /// The response does not fit into the buffer
+    TooLarge,
+    /// `register` was called before `create_identity`
+    NoIdentity,
+}
+
+impl_error! {
+    OnboardingError<H: Display, S: Debug, K: Debug, D: Debug> {
+        HttpError(e) => "HTTP error: {e}"; e.error_kind(),
+        StorageError(e) => "Storage error: {e:?}"; e.error_kind(),
+        CryptoError(e) => "Crypto error: {e:?}"; e.error_kind(),
+        SerdeError(e) => "SerDe error: {e:?}"; e.error_kind(),
+        Status(status) => "Unexpected HTTP status: {status}"; ErrorKind::from_status(*status),
+        TooLarge => "Response too large"; ErrorKind::InvalidInput,
+        NoIdentity => "No device identity"; ErrorKind::NotFound,
+    }
+}
+
+#[derive(Serialize)]
+struct RegistrationRequest<'a> {
+    device_id: &'a str,
+    public_key: &'a str,
+    claim_token: Option<&'a str>,
+    signature: &'a str,
+}
+
+type OnboardingResult<T, H, R, K, D> = Result<T, OnboardingError<H, R, K, D>>;
+
+/// Runs the claim flow, persisting the identity and the credentials in a `RawStorage`.
+///
+/// `K` provides both the key generation and the signing, e.g. a secure element. The response of the
+/// provisioning endpoint must fit into a buffer of `B` bytes.
+pub struct Onboarding<S, K, H, const B: usize = 4096> {
+    storage: S,
+    keys: K,
+    hasher: H,
+}
+
+impl<S, K, H, const B: usize> Onboarding<S, K, H, B>
+where
+    S: RawStorage,
+    K: KeyStore + EcdsaP256Sign<Error = <K as KeyStore>::Error>,
+    H: Sha256 + Clone,
+{
+    pub const fn new(storage: S, keys: K, hasher: H) -> Self {
+        Self {
+            storage,
+            keys,
+            hasher,
+        }
+    }
+
+    pub fn identity(&self) -> Result<Option<Identity>, S::Error> {
+        let mut buf = [0_u8; IDENTITY_LEN];
+
+        let data = match self.storage.get_raw(IDENTITY, &mut buf)? {
+            Some(data) if data.len() == IDENTITY_LEN && data[0] == 1 => data,
+            _ => return Ok(None),
+        };
+
+        let mut public_key = [0_u8; 64];
+        public_key.copy_from_slice(&data[3..67]);
+
+        let device_id = match core::str::from_utf8(&data[67..]) {
+            Ok(device_id) => device_id.into(),
+            Err(_) => return Ok(None),
+        };
+
+        Ok(Some(Identity {
+            device_id,
+            key: KeyHandle(u16::from_le_bytes([data[1], data[2]])),
+            public_key,
+        }))
+    }
+
+    /// Generates a key pair in `key` and derives the device ID from it, unless the device has an identity
+    /// already
+    pub fn create_identity(
+        &mut self,
+        key: KeyHandle,
+    ) -> OnboardingResult<Identity, Infallible, S::Error, <K as KeyStore>::Error, Infallible> {
+        if let Some(identity) = self.identity().map_err(OnboardingError::StorageError)? {
+            return Ok(identity);
+        }
+
+        let public_key = self
+            .keys
+            .generate_ecdsa_p256(key)
+            .map_err(OnboardingError::CryptoError)?;
+
+        let digest = crate::crypto::sha256(self.hasher.clone(), &public_key);
+
+        let mut device_id = heapless::String::new();
+        hex::encode(&digest[..12], hex::LOWER, &mut device_id).unwrap();
+
+        let mut buf = [0_u8; IDENTITY_LEN];
+
+        buf[0] = 1;
+        buf[1..3].copy_from_slice(&key.0.to_le_bytes());
+        buf[3..67].copy_from_slice(&public_key);
+        buf[67..].copy_from_slice(device_id.as_bytes());
+
+        self.storage
+            .set_raw(IDENTITY, &buf)
+            .map_err(OnboardingError::StorageError)?;
+
+        Ok(Identity {
+            device_id,
+            key,
+            public_key,
+        })
+    }
+
+    /// Registers the device with the provisioning endpoint at `uri`, encoding the request and decoding the
+    /// response with `serde`.
+    ///
+    /// The credentials are persisted once issued, so that `credentials` returns them from then on.
+    pub fn register<C, D, const N: usize>(
+        &mut self,
+        client: &mut Client<C>,
+        serde: D,
+        uri: &str,
+        claim_token: Option<&str>,
+    ) -> OnboardingResult<Registration<N>, C::Error, S::Error, <K as KeyStore>::Error, D::Error>
+    where
+        C: Connection,
+        D: SerDe,
+    {
+        let identity = self
+            .identity()
+            .map_err(OnboardingError::StorageError)?
+            .ok_or(OnboardingError::NoIdentity)?;
+
+        let mut hasher = self.hasher.clone();
+        hasher.update(identity.device_id.as_bytes());
+        hasher.update(claim_token.unwrap_or("").as_bytes());
+
+        let signature = self
+            .keys
+            .sign(identity.key, &hasher.finish())
+            .map_err(OnboardingError::CryptoError)?;
+
+        let public_key = hex(&identity.public_key);
+        let signature = hex(&signature);
+
+        let mut buf = [0_u8; B];
+
+        let len = serde
+            .serialize(
+                &mut buf,
+                &RegistrationRequest {
+                    device_id: &identity.device_id,
+                    public_key: &public_key,
+                    claim_token,
+                    signature: &signature,
+                },
+            )
+            .map_err(OnboardingError::SerdeError)?
+            .len();
+
+        let mut content_len_buf = headers::ContentLenParseBuf::new();
+
+        let request_headers = [headers::content_len(len as u64, &mut content_len_buf)];
+
+        let mut request = client
+            .request(Method::Post, uri, &request_headers)
+            .map_err(OnboardingError::HttpError)?;
+
+        request
+            .write_all(&buf[..len])
+            .map_err(OnboardingError::HttpError)?;
+
+        let mut response = request.submit().map_err(OnboardingError::HttpError)?;
+
+        match response.status() {
+            200 | 201 => (),
+            202 => return Ok(Registration::Pending),
+            status => return Err(OnboardingError::Status(status)),
+        }
+
+        let len = try_read_full(&mut response, &mut buf)
+            .map_err(|(e, _)| OnboardingError::HttpError(e))?;
+
+        if len == B {
+            return Err(OnboardingError::TooLarge);
+        }
+
+        let credentials: Credentials<N> = serde
+            .deserialize(&buf[..len])
+            .map_err(OnboardingError::SerdeError)?;
+
+        self.store(&credentials)
+            .map_err(OnboardingError::StorageError)?;
+
+        Ok(Registration::Provisioned(credentials))
+    }
+
+    pub fn is_provisioned(&self) -> Result<bool, S::Error> {
+        self.storage.contains(CERTIFICATE)
+    }
+
+    pub fn credentials<const N: usize>(&self) -> Result<Option<Credentials<N>>, S::Error> {
+        let endpoint = self.load(ENDPOINT)?;
+        let certificate = self.load(CERTIFICATE)?;
+
+        Ok(match (endpoint, certificate) {
+            (Some(endpoint), Some(certificate)) => Some(Credentials {
+                endpoint,
+                certificate,
+                ca_certificate: self.load(CA_CERTIFICATE)?,
+            }),
+            _ => None,
+        })
+    }
+
+    /// Removes the credentials and the identity, and erases the key, so that the device can be onboarded
+    /// again, e.g. into another fleet
+    pub fn reset(
+        &mut self,
+    ) -> OnboardingResult<(), Infallible, S::Error, <K as KeyStore>::Error, Infallible> {
+        if let Some(identity) = self.identity().map_err(OnboardingError::StorageError)? {
+            self.keys
+                .erase(identity.key)
+                .map_err(OnboardingError::CryptoError)?;
+        }
+
+        for name in [CA_CERTIFICATE, CERTIFICATE, ENDPOINT, IDENTITY] {
+            self.storage
+                .remove(name)
+                .map_err(OnboardingError::StorageError)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn release(self) -> (S, K, H) {
+        (self.storage, self.keys, self.hasher)
+    }
+
+    fn store<const N: usize>(&mut self, credentials: &Credentials<N>) -> Result<(), S::Error> {
+        match &credentials.ca_certificate {
+            Some(ca_certificate) => self
+                .storage
+                .set_raw(CA_CERTIFICATE, ca_certificate.as_bytes())?,
+            None => self.storage.remove(CA_CERTIFICATE)?,
+        };
+
+        self.storage
+            .set_raw(ENDPOINT, credentials.endpoint.as_bytes())?;
+
+        // Written last, as its presence tells that the device is provisioned
+        self.storage
+            .set_raw(CERTIFICATE, credentials.certificate.as_bytes())?;
+
+        Ok(())
+    }
+
+    fn load<const L: usize>(&self, name: &str) -> Result<Option<heapless::String<L>>, S::Error> {
+        let mut buf = [0_u8; L];
+
+        Ok(self
+            .storage
+            .get_raw(name, &mut buf)?
+            .and_then(|data| core::str::from_utf8(data).ok())
+            .map(Into::into))
+    }
+}
+
+fn hex(data: &[u8]) -> heapless::String<128> {
+    let mut encoded = heapless::String::new();
+
+    hex::encode(data, hex::LOWER, &mut encoded).unwrap();
+
+    encoded
+}
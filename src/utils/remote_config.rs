@@ -0,0 +1,257 @@
+//! Configuration pulled from a fleet server: the device periodically fetches its configuration document
+//! with a conditional GET, and applies it when it changed.
+
+use core::fmt::Debug;
+use core::time::Duration;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::error::{impl_error, ErrorKind};
+use crate::http::client::{Client, Connection};
+use crate::http::{Headers, Method, Status};
+use crate::mqtt::client::{Publish, QoS};
+use crate::storage::SerDe;
+use crate::timer::PeriodicTimer;
+use crate::utils::io::try_read_full;
+
+/// The part of the device configured by the fleet server
+pub trait ConfigTarget {
+    /// The configuration document; its type is the schema the document is checked against when decoded
+    type Config: DeserializeOwned;
+
+    type Error: Debug;
+
+    /// Checks the constraints of `config` which its type cannot express, e.g. ranges of values.
+    ///
+    /// Returns the reason for rejecting the document, if any.
+    fn validate(&self, config: &Self::Config) -> Result<(), &'static str>;
+
+    fn apply(&mut self, config: Self::Config) -> Result<(), Self::Error>;
+}
+
+impl<T> ConfigTarget for &mut T
+where
+    T: ConfigTarget,
+{
+    type Config = T::Config;
+
+    type Error = T::Error;
+
+    fn validate(&self, config: &Self::Config) -> Result<(), &'static str> {
+        (**self).validate(config)
+    }
+
+    fn apply(&mut self, config: Self::Config) -> Result<(), Self::Error> {
+        (*self).apply(config)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Outcome {
+    /// The document did not change since it was last fetched
+    NotModified,
+    Applied,
+    /// The document could not be decoded or failed validation; it will not be fetched again until it
+    /// changes
+    Rejected(&'static str),
+}
+
+/// Published after each new document, so that the fleet server knows which configuration is in effect
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Report<'a> {
+    /// The ETag of the document
+    pub version: &'a str,
+    pub applied: bool,
+    /// Why the document was rejected
+    pub reason: Option<&'a str>,
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum RemoteConfigError<H, A, P, S> {
+    HttpError(H),
+    Status(u16),
+    /// The document does not fit into the buffer
+    TooLarge,
+    ApplyError(A),
+    PublishError(P),
+    SerdeError(S),
+}
+
+impl_error! {
+    RemoteConfigError<H: Display, A: Debug, P: Debug, S: Debug> {
+        HttpError(e) => "HTTP error: {e}"; e.error_kind(),
+        Status(status) => "Unexpected HTTP status: {status}"; ErrorKind::from_status(*status),
+        TooLarge => "Configuration too large"; ErrorKind::InvalidInput,
+        ApplyError(e) => "Apply error: {e:?}"; e.error_kind(),
+        PublishError(e) => "Publish error: {e:?}"; e.error_kind(),
+        SerdeError(e) => "SerDe error: {e:?}"; e.error_kind(),
+    }
+}
+
+type PollResult<H, A, P, S> = Result<Outcome, RemoteConfigError<H, A, P, S>>;
+
+/// Polls the configuration document at `uri`, shorter than `B` bytes and decoded with `S`, applies it to the
+/// target and publishes a `Report` of the outcome on `topic`, retained.
+///
+/// The ETag of the last document fetched is sent with `If-None-Match`, so that unchanged documents are
+/// neither downloaded nor applied again. Polling is driven by the application, typically from the callback
+/// of a timer armed with `arm`; to avoid fetching the document again after a restart, persist `version`
+/// and restore it with `set_version`.
+pub struct RemoteConfig<'a, T, S, P, const B: usize = 2048> {
+    target: T,
+    serde: S,
+    publisher: P,
+    uri: &'a str,
+    topic: &'a str,
+    interval: Duration,
+    version: Option<heapless::String<64>>,
+}
+
+impl<'a, T, S, P, const B: usize> RemoteConfig<'a, T, S, P, B>
+where
+    T: ConfigTarget,
+    S: SerDe,
+    P: Publish,
+{
+    pub const fn new(
+        target: T,
+        serde: S,
+        publisher: P,
+        uri: &'a str,
+        topic: &'a str,
+        interval: Duration,
+    ) -> Self {
+        Self {
+            target,
+            serde,
+            publisher,
+            uri,
+            topic,
+            interval,
+            version: None,
+        }
+    }
+
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// Schedules `timer` to fire at the polling interval
+    pub fn arm<O>(&self, timer: &mut O) -> Result<(), O::Error>
+    where
+        O: PeriodicTimer,
+    {
+        timer.every(self.interval)
+    }
+
+    /// The ETag of the last document fetched, whether it was applied or rejected
+    pub fn version(&self) -> Option<&str> {
+        self.version.as_deref()
+    }
+
+    /// Returns `false` if `version` is longer than 64 bytes
+    pub fn set_version(&mut self, version: Option<&str>) -> bool {
+        match version {
+            Some(version) => {
+                let mut stored = heapless::String::new();
+
+                let fits = stored.push_str(version).is_ok();
+
+                self.version = fits.then(|| stored);
+
+                fits
+            }
+            None => {
+                self.version = None;
+                true
+            }
+        }
+    }
+
+    /// Fetches the document if it changed, and applies it unless it is rejected.
+    ///
+    /// Documents without an ETag are applied every time they are fetched.
+    pub fn poll<C>(
+        &mut self,
+        client: &mut Client<C>,
+    ) -> PollResult<C::Error, T::Error, P::Error, S::Error>
+    where
+        C: Connection,
+    {
+        let current = self.version.clone();
+        let mut headers = heapless::Vec::<_, 1>::new();
+
+        if let Some(version) = &current {
+            headers.push(("If-None-Match", version.as_str())).unwrap();
+        }
+
+        let mut response = client
+            .request(Method::Get, self.uri, &headers)
+            .and_then(|request| request.submit())
+            .map_err(RemoteConfigError::HttpError)?;
+
+        match response.status() {
+            200 => (),
+            304 => return Ok(Outcome::NotModified),
+            status => return Err(RemoteConfigError::Status(status)),
+        }
+
+        let mut version = heapless::String::<64>::new();
+        let versioned = response
+            .header("ETag")
+            .map_or(false, |etag| version.push_str(etag).is_ok());
+
+        let mut buf = [0_u8; B];
+        let len = try_read_full(&mut response, &mut buf)
+            .map_err(|(e, _)| RemoteConfigError::HttpError(e))?;
+
+        if len == B {
+            return Err(RemoteConfigError::TooLarge);
+        }
+
+        let outcome = match self.serde.deserialize::<T::Config>(&buf[..len]) {
+            Ok(config) => match self.target.validate(&config) {
+                Ok(()) => {
+                    self.target
+                        .apply(config)
+                        .map_err(RemoteConfigError::ApplyError)?;
+
+                    Outcome::Applied
+                }
+                Err(reason) => Outcome::Rejected(reason),
+            },
+            Err(_) => Outcome::Rejected("Invalid document"),
+        };
+
+        self.version = versioned.then(|| version);
+
+        let report = Report {
+            version: self.version().unwrap_or(""),
+            applied: outcome == Outcome::Applied,
+            reason: match outcome {
+                Outcome::Rejected(reason) => Some(reason),
+                _ => None,
+            },
+        };
+
+        let mut buf = [0_u8; 128];
+        let payload = self
+            .serde
+            .serialize(&mut buf, &report)
+            .map_err(RemoteConfigError::SerdeError)?;
+
+        self.publisher
+            .publish(self.topic, QoS::AtLeastOnce, true, payload)
+            .map_err(RemoteConfigError::PublishError)?;
+
+        Ok(outcome)
+    }
+
+    pub fn release(self) -> (T, S, P) {
+        (self.target, self.serde, self.publisher)
+    }
+}
@@ -0,0 +1,418 @@
+//! Named commands invoked remotely, served over MQTT and HTTP alike.
+//!
+//! Commands are registered with a `Dispatcher` together with the role they require. `mqtt::RpcTransport`
+//! serves them on request/response topics and `server::RpcHandler` as HTTP POST endpoints.
+
+use core::marker::PhantomData;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::auth::Role;
+use crate::error::{impl_error, Classify, ErrorKind};
+use crate::storage::SerDe;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum RpcError {
+    UnknownCommand,
+    /// The command requires a role, and the caller is not authenticated
+    Unauthenticated,
+    /// The caller is not granted the role required by the command
+    Forbidden,
+    InvalidRequest,
+    /// The response could not be encoded, e.g. because it does not fit into the buffer
+    InvalidResponse,
+    /// The command failed with an error of this kind
+    Failed(ErrorKind),
+}
+
+impl RpcError {
+    /// The error code reported to the caller
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::UnknownCommand => "unknown_command",
+            Self::Unauthenticated => "unauthenticated",
+            Self::Forbidden => "forbidden",
+            Self::InvalidRequest => "invalid_request",
+            Self::InvalidResponse => "invalid_response",
+            Self::Failed(kind) => kind.as_str(),
+        }
+    }
+
+    /// The HTTP status of a request failing with this error
+    pub fn status(&self) -> u16 {
+        match self {
+            Self::Forbidden => 403,
+            _ => self.error_kind().status(),
+        }
+    }
+}
+
+impl_error! {
+    RpcError {
+        UnknownCommand => "Unknown command"; ErrorKind::NotFound,
+        Unauthenticated => "Not authenticated"; ErrorKind::Unauthorized,
+        Forbidden => "Forbidden"; ErrorKind::Unauthorized,
+        InvalidRequest => "Invalid request"; ErrorKind::InvalidInput,
+        InvalidResponse => "Invalid response"; ErrorKind::Other,
+        Failed(kind) => "Command failed: {}", kind.as_str(); *kind,
+    }
+}
+
+pub trait Command {
+    /// Decodes `request`, runs the command and encodes its response into `response`.
+    ///
+    /// Returns the length of the response.
+    fn invoke(&self, request: &[u8], response: &mut [u8]) -> Result<usize, RpcError>;
+}
+
+impl<C> Command for &C
+where
+    C: Command,
+{
+    fn invoke(&self, request: &[u8], response: &mut [u8]) -> Result<usize, RpcError> {
+        (**self).invoke(request, response)
+    }
+}
+
+/// A command taking a request of type `Q` and returning a response of type `R`, both serialized with `S`
+pub struct Typed<S, F, Q, R> {
+    serde: S,
+    f: F,
+    _payloads: PhantomData<fn(Q) -> R>,
+}
+
+impl<S, F, Q, R> Typed<S, F, Q, R> {
+    pub const fn new(serde: S, f: F) -> Self {
+        Self {
+            serde,
+            f,
+            _payloads: PhantomData,
+        }
+    }
+}
+
+impl<S, F, Q, R, E> Command for Typed<S, F, Q, R>
+where
+    S: SerDe,
+    F: Fn(Q) -> Result<R, E>,
+    Q: DeserializeOwned,
+    R: Serialize,
+    E: Classify,
+{
+    fn invoke(&self, request: &[u8], response: &mut [u8]) -> Result<usize, RpcError> {
+        let request = self
+            .serde
+            .deserialize(request)
+            .map_err(|_| RpcError::InvalidRequest)?;
+
+        let result = (self.f)(request).map_err(|e| RpcError::Failed(e.error_kind()))?;
+
+        self.serde
+            .serialize(response, &result)
+            .map(|response| response.len())
+            .map_err(|_| RpcError::InvalidResponse)
+    }
+}
+
+struct Entry<'a> {
+    name: &'a str,
+    required: Option<Role>,
+    command: &'a (dyn Command + Sync),
+}
+
+/// Up to `N` named commands
+pub struct Dispatcher<'a, const N: usize = 16> {
+    commands: heapless::Vec<Entry<'a>, N>,
+}
+
+impl<'a, const N: usize> Dispatcher<'a, N> {
+    pub const fn new() -> Self {
+        Self {
+            commands: heapless::Vec::new(),
+        }
+    }
+
+    /// Registers `command` as `name`, callable only by callers granted `required`, if any.
+    ///
+    /// Returns `false` if a command with the same name is registered already, or if there is no room left.
+    /// Names may not contain `/`.
+    pub fn register(
+        &mut self,
+        name: &'a str,
+        required: Option<Role>,
+        command: &'a (dyn Command + Sync),
+    ) -> bool {
+        if name.is_empty() || name.contains('/') || self.find(name).is_some() {
+            return false;
+        }
+
+        self.commands
+            .push(Entry {
+                name,
+                required,
+                command,
+            })
+            .is_ok()
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.commands.iter().map(|entry| entry.name)
+    }
+
+    /// Runs the command `name` on behalf of a caller granted `caller`, or of an anonymous caller.
+    ///
+    /// Returns the length of the response encoded into `response`.
+    pub fn dispatch(
+        &self,
+        name: &str,
+        caller: Option<Role>,
+        request: &[u8],
+        response: &mut [u8],
+    ) -> Result<usize, RpcError> {
+        let entry = self.find(name).ok_or(RpcError::UnknownCommand)?;
+
+        match (entry.required, caller) {
+            (None, _) => (),
+            (Some(_), None) => return Err(RpcError::Unauthenticated),
+            (Some(required), Some(caller)) if !caller.allows(required) => {
+                return Err(RpcError::Forbidden)
+            }
+            _ => (),
+        }
+
+        entry.command.invoke(request, response)
+    }
+
+    fn find(&self, name: &str) -> Option<&Entry<'a>> {
+        self.commands.iter().find(|entry| entry.name == name)
+    }
+}
+
+impl<'a, const N: usize> Default for Dispatcher<'a, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub mod mqtt {
+    use core::fmt::Write as _;
+
+    use crate::auth::Role;
+    use crate::mqtt::client::{Client, MessageId, Publish, QoS};
+
+    use super::Dispatcher;
+
+    /// Serves the commands of a `Dispatcher` over MQTT, with responses encoded into a buffer of `B` bytes.
+    ///
+    /// Requests arrive on `<prefix>/request/<command>[/<id>]`. The response is published on
+    /// `<prefix>/response/<command>[/<id>]`, or the error code on `<prefix>/error/<command>[/<id>]`, so that
+    /// callers can match responses to their requests with a unique id.
+    ///
+    /// Callers are authenticated by the broker, so all of them are granted the same role, if any.
+    pub struct RpcTransport<'a, 'd, P, const N: usize = 16, const B: usize = 512> {
+        publisher: P,
+        dispatcher: &'a Dispatcher<'d, N>,
+        prefix: &'a str,
+        role: Option<Role>,
+    }
+
+    impl<'a, 'd, P, const N: usize, const B: usize> RpcTransport<'a, 'd, P, N, B>
+    where
+        P: Publish,
+    {
+        pub const fn new(
+            publisher: P,
+            dispatcher: &'a Dispatcher<'d, N>,
+            prefix: &'a str,
+            role: Option<Role>,
+        ) -> Self {
+            Self {
+                publisher,
+                dispatcher,
+                prefix,
+                role,
+            }
+        }
+
+        pub fn subscribe<C>(&self, client: &mut C) -> Result<MessageId, C::Error>
+        where
+            C: Client,
+        {
+            let mut filter = heapless::String::<64>::new();
+
+            write!(&mut filter, "{}/request/#", self.prefix).unwrap();
+
+            client.subscribe(&filter, QoS::AtLeastOnce)
+        }
+
+        /// Runs the command requested by a received MQTT message, and publishes its response.
+        ///
+        /// Returns `Ok(false)` if the message is not a request. Requests on topics longer than 128 bytes are
+        /// ignored.
+        pub fn receive(&mut self, topic: &str, data: &[u8]) -> Result<bool, P::Error> {
+            let request = match topic
+                .strip_prefix(self.prefix)
+                .and_then(|topic| topic.strip_prefix("/request/"))
+            {
+                Some(request) => request,
+                None => return Ok(false),
+            };
+
+            let name = request.split('/').next().unwrap_or(request);
+
+            let mut buf = [0_u8; B];
+            let mut reply = heapless::String::<128>::new();
+
+            let payload = match self.dispatcher.dispatch(name, self.role, data, &mut buf) {
+                Ok(len) => {
+                    if write!(&mut reply, "{}/response/{request}", self.prefix).is_err() {
+                        return Ok(true);
+                    }
+
+                    &buf[..len]
+                }
+                Err(e) => {
+                    if write!(&mut reply, "{}/error/{request}", self.prefix).is_err() {
+                        return Ok(true);
+                    }
+
+                    e.as_str().as_bytes()
+                }
+            };
+
+            self.publisher
+                .publish(&reply, QoS::AtLeastOnce, false, payload)?;
+
+            Ok(true)
+        }
+
+        pub fn release(self) -> P {
+            self.publisher
+        }
+    }
+}
+
+#[cfg(feature = "experimental")]
+pub mod server {
+    use core::fmt::Write as _;
+
+    use crate::auth::server::bearer_token;
+    use crate::auth::token::TokenValidator;
+    use crate::http::server::{Connection, Handler, HandlerResult};
+    use crate::http::{Headers, Method};
+    use crate::utils::io::try_read_full;
+
+    use super::{Dispatcher, RpcError};
+
+    /// Serves the commands of a `Dispatcher` as `POST <prefix>/<command>`, with request and response bodies
+    /// of up to `B` bytes.
+    ///
+    /// Callers authenticate with an `Authorization: Bearer` header; the callers without a valid token may
+    /// invoke only the commands which require no role. Errors are reported with their HTTP status and a
+    /// JSON body with the error code.
+    pub struct RpcHandler<'a, 'd, V, const N: usize = 16, const B: usize = 512> {
+        dispatcher: &'a Dispatcher<'d, N>,
+        validator: V,
+        prefix: &'a str,
+        content_type: &'static str,
+    }
+
+    impl<'a, 'd, V, const N: usize, const B: usize> RpcHandler<'a, 'd, V, N, B> {
+        pub const fn new(
+            dispatcher: &'a Dispatcher<'d, N>,
+            validator: V,
+            prefix: &'a str,
+            content_type: &'static str,
+        ) -> Self {
+            Self {
+                dispatcher,
+                validator,
+                prefix,
+                content_type,
+            }
+        }
+    }
+
+    impl<'a, 'd, C, V, const N: usize, const B: usize> Handler<C> for RpcHandler<'a, 'd, V, N, B>
+    where
+        C: Connection,
+        V: TokenValidator + Send,
+    {
+        fn handle(&self, connection: &mut C) -> HandlerResult {
+            if connection.method() != Method::Post {
+                connection.initiate_response(405, None, &[("Allow", "POST")])?;
+
+                return Ok(());
+            }
+
+            let mut name = heapless::String::<32>::new();
+
+            let path = connection.uri().split('?').next().unwrap_or("");
+
+            let known = path
+                .strip_prefix(self.prefix)
+                .map(|name| name.trim_matches('/'))
+                .map_or(false, |command| name.push_str(command).is_ok());
+
+            if !known {
+                return respond(connection, RpcError::UnknownCommand);
+            }
+
+            if connection.content_len().map_or(false, |len| len > B as u64) {
+                connection.initiate_response(413, None, &[])?;
+
+                return Ok(());
+            }
+
+            let caller = match connection.header("Authorization").and_then(bearer_token) {
+                Some(token) => self.validator.validate(token)?,
+                None => None,
+            };
+
+            let mut request = [0_u8; B];
+            let len = try_read_full(&mut *connection, &mut request).map_err(|(e, _)| e)?;
+
+            let mut response = [0_u8; B];
+
+            match self
+                .dispatcher
+                .dispatch(&name, caller, &request[..len], &mut response)
+            {
+                Ok(len) => {
+                    connection.initiate_response(
+                        200,
+                        None,
+                        &[("Content-Type", self.content_type)],
+                    )?;
+
+                    connection.write_all(&response[..len])?;
+
+                    Ok(())
+                }
+                Err(e) => respond(connection, e),
+            }
+        }
+    }
+
+    fn respond<C>(connection: &mut C, error: RpcError) -> HandlerResult
+    where
+        C: Connection,
+    {
+        let mut body = heapless::String::<48>::new();
+
+        write!(&mut body, "{{\"error\":\"{}\"}}", error.as_str()).unwrap();
+
+        connection.initiate_response(
+            error.status(),
+            None,
+            &[("Content-Type", "application/json")],
+        )?;
+
+        connection.write_all(body.as_bytes())?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,174 @@
+use core::time::Duration;
+
+#[cfg(feature = "use_serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::rtc::Rtc;
+use crate::sys_time::{DateTime, SystemTime};
+
+/// Where the time of a `WallClock` comes from
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub enum Source {
+    /// The time is counted from the Unix epoch at boot
+    None,
+    Rtc,
+    /// A coarse time, e.g. from the `Date` header of an HTTP response
+    Approximate,
+    Sntp,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub struct Configuration {
+    /// RTC times before this year are not plausible, e.g. the reset value of the RTC
+    pub min_year: u16,
+    /// The RTC is only corrected with the SNTP time once it drifted further
+    pub max_drift: Duration,
+}
+
+impl Default for Configuration {
+    fn default() -> Self {
+        Self {
+            min_year: 2024,
+            max_drift: Duration::from_secs(2),
+        }
+    }
+}
+
+/// A `SystemTime` returning UTC time since the Unix epoch, from a monotonic `SystemTime` counting from boot.
+///
+/// The clock starts from the RTC time if it is plausible, so that timestamps are meaningful before the
+/// network is up, and is set from SNTP once synchronized, which in turn corrects the RTC.
+pub struct WallClock<T, R> {
+    time: T,
+    rtc: R,
+    configuration: Configuration,
+    /// The UTC time at boot
+    offset: Duration,
+    source: Source,
+    /// The UTC time the clock was last set to
+    set_at: Option<Duration>,
+}
+
+impl<T, R> WallClock<T, R>
+where
+    T: SystemTime,
+    R: Rtc,
+{
+    pub fn new(time: T, rtc: R, configuration: Configuration) -> Self {
+        Self {
+            time,
+            rtc,
+            configuration,
+            offset: Duration::ZERO,
+            source: Source::None,
+            set_at: None,
+        }
+    }
+
+    /// Starts the clock from the RTC time, to be called at boot.
+    ///
+    /// Returns `false` if the RTC time is not plausible.
+    pub fn start(&mut self) -> Result<bool, R::Error> {
+        if self.source == Source::Sntp {
+            return Ok(true);
+        }
+
+        match self.rtc.get()?.filter(|time| self.is_plausible(time)) {
+            Some(time) => {
+                let utc = Duration::from_secs(time.to_unix().unwrap());
+
+                self.offset = utc.saturating_sub(self.time.now());
+                self.source = Source::Rtc;
+                self.set_at = Some(utc);
+
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Sets the clock from a coarse UTC time, e.g. from `utils::bootstrap::fetch_http_date`, so that TLS
+    /// certificates can be validated before SNTP is configured.
+    ///
+    /// The RTC is left alone. Returns `false` if the clock is synchronized with SNTP already, or if `utc`
+    /// is not plausible.
+    pub fn approximate(&mut self, utc: Duration) -> bool {
+        if self.source == Source::Sntp || !self.is_plausible(&DateTime::from_unix(utc.as_secs())) {
+            return false;
+        }
+
+        self.offset = utc.saturating_sub(self.time.now());
+        self.source = Source::Approximate;
+        self.set_at = Some(utc);
+
+        true
+    }
+
+    /// Sets the clock from the UTC time received from SNTP, and corrects the RTC if it drifted
+    /// further than `Configuration::max_drift` or lost the time.
+    ///
+    /// Returns `true` if the RTC was set.
+    pub fn synchronized(&mut self, utc: Duration) -> Result<bool, R::Error> {
+        self.offset = utc.saturating_sub(self.time.now());
+        self.source = Source::Sntp;
+        self.set_at = Some(utc);
+
+        let drifted = match self.rtc.get()?.and_then(|time| time.to_unix()) {
+            Some(rtc) => {
+                let rtc = Duration::from_secs(rtc);
+                let drift = if rtc > utc { rtc - utc } else { utc - rtc };
+
+                drift > self.configuration.max_drift
+            }
+            None => true,
+        };
+
+        if drifted {
+            self.rtc.set(&DateTime::from_unix(utc.as_secs()))?;
+        }
+
+        Ok(drifted)
+    }
+
+    pub fn source(&self) -> Source {
+        self.source
+    }
+
+    /// The UTC time the clock was last started, approximated or synchronized at, or `None` if it is not set
+    pub fn set_at(&self) -> Option<Duration> {
+        self.set_at
+    }
+
+    /// Whether timestamps are plausible, i.e. the clock was started from the RTC, approximated or synchronized
+    /// with SNTP
+    pub fn is_set(&self) -> bool {
+        self.source != Source::None
+    }
+
+    pub fn configuration(&self) -> &Configuration {
+        &self.configuration
+    }
+
+    pub fn release(self) -> (T, R) {
+        (self.time, self.rtc)
+    }
+
+    fn is_plausible(&self, time: &DateTime) -> bool {
+        time.year >= self.configuration.min_year && time.to_unix().is_some()
+    }
+}
+
+impl<T, R> SystemTime for WallClock<T, R>
+where
+    T: SystemTime,
+{
+    fn now(&self) -> Duration {
+        self.offset + self.time.now()
+    }
+}
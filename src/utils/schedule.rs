@@ -0,0 +1,614 @@
+//! Scheduling of periodic tasks, e.g. measurements and reports, by wall clock time.
+//!
+//! The times are seconds since the Unix epoch in UTC, as returned by a `SystemTime` synchronized with SNTP.
+//! Daily and cron schedules are evaluated in the local time of the `TimeZone` of the scheduler, UTC by default.
+
+use core::convert::TryInto;
+use core::fmt::{self, Display, Write as _};
+use core::str::FromStr;
+use core::time::Duration;
+
+#[cfg(feature = "use_serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::storage::RawStorage;
+use crate::sys_time::{self, DateTime, SystemTime};
+use crate::timer::OnceTimer;
+
+use super::time::TimeZone;
+
+/// The number of days searched for the next occurrence of a cron expression,
+/// which covers expressions matching only on February 29th
+const MAX_DAYS: u32 = 8 * 366;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub enum Schedule {
+    /// Aligned to the epoch, so that e.g. a 10 minute interval runs at :00, :10, :20 and so on
+    Interval(Duration),
+    /// Every day at the given time of day
+    Daily {
+        hour: u8,
+        minute: u8,
+        second: u8,
+    },
+    Cron(Cron),
+}
+
+impl Schedule {
+    /// The next occurrence strictly after `after`, or `None` if there is none
+    pub fn next_after(&self, after: u64) -> Option<u64> {
+        match self {
+            Self::Interval(interval) => {
+                let interval = interval.as_secs().max(1);
+
+                (after / interval + 1).checked_mul(interval)
+            }
+            Self::Daily {
+                hour,
+                minute,
+                second,
+            } => {
+                if *hour > 23 || *minute > 59 || *second > 59 {
+                    return None;
+                }
+
+                let time_of_day = *hour as u64 * 3600 + *minute as u64 * 60 + *second as u64;
+                let today = after / 86400 * 86400 + time_of_day;
+
+                Some(if today > after { today } else { today + 86400 })
+            }
+            Self::Cron(cron) => cron.next_after(after),
+        }
+    }
+
+    /// As `next_after`, evaluating daily and cron schedules in the local time of `time_zone`.
+    ///
+    /// Occurrences skipped when DST starts are moved forward by the DST offset, and occurrences repeated when
+    /// DST ends run once.
+    pub fn next_after_in(&self, after: u64, time_zone: &TimeZone) -> Option<u64> {
+        if let Self::Interval(_) = self {
+            return self.next_after(after);
+        }
+
+        let mut local = time_zone.local(after);
+
+        loop {
+            local = self.next_after(local)?;
+
+            let utc = time_zone.utc_from_local(local);
+            if utc > after {
+                return Some(utc);
+            }
+        }
+    }
+}
+
+impl FromStr for Schedule {
+    type Err = &'static str;
+
+    fn from_str(schedule: &str) -> Result<Self, Self::Err> {
+        schedule.parse().map(Self::Cron)
+    }
+}
+
+/// A subset of the cron syntax: the minute, hour, day of month, month and day of week fields, each being
+/// `*` or a list of values, ranges (`a-b`) and steps (`*/n`, `a/n`, `a-b/n`).
+///
+/// Days of week count from 0 (Sunday) to 6, with 7 accepted as Sunday too. Names of months and days are not
+/// supported, but the `@hourly`, `@daily`, `@weekly`, `@monthly` and `@yearly` shorthands are.
+///
+/// As with Vixie cron, if both the day of month and the day of week are restricted, either of them matches.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Cron {
+    minutes: u64,
+    hours: u32,
+    days: u32,
+    months: u16,
+    weekdays: u8,
+    /// Whether the day of month field is `*`
+    any_day: bool,
+    /// Whether the day of week field is `*`
+    any_weekday: bool,
+}
+
+impl Cron {
+    /// The next occurrence strictly after `after`
+    pub fn next_after(&self, after: u64) -> Option<u64> {
+        let next = after / 60 * 60 + 60;
+
+        let mut days = (next / 86400) as u32;
+        let mut minute_of_day = (next % 86400 / 60) as u32;
+
+        for _ in 0..MAX_DAYS {
+            let (year, month, day) = sys_time::civil_from_days(days);
+
+            if self.matches_day(month, day, sys_time::weekday(days)) {
+                for hour in minute_of_day / 60..24 {
+                    if self.hours & (1 << hour) == 0 {
+                        continue;
+                    }
+
+                    let from = if hour == minute_of_day / 60 {
+                        minute_of_day % 60
+                    } else {
+                        0
+                    };
+
+                    if let Some(minute) = first_set(self.minutes, from, 59) {
+                        return Some(days as u64 * 86400 + hour as u64 * 3600 + minute as u64 * 60);
+                    }
+                }
+            }
+
+            if year == u16::MAX && month == 12 && day == 31 {
+                break;
+            }
+
+            days += 1;
+            minute_of_day = 0;
+        }
+
+        None
+    }
+
+    pub fn matches(&self, time: &DateTime) -> bool {
+        self.minutes & (1 << time.minute) != 0
+            && self.hours & (1 << time.hour) != 0
+            && self.matches_day(time.month, time.day, time.weekday())
+    }
+
+    fn matches_day(&self, month: u8, day: u8, weekday: u8) -> bool {
+        let day_matches = self.days & (1 << day) != 0;
+        let weekday_matches = self.weekdays & (1 << weekday) != 0;
+
+        self.months & (1 << month) != 0
+            && match (self.any_day, self.any_weekday) {
+                (false, false) => day_matches || weekday_matches,
+                _ => day_matches && weekday_matches,
+            }
+    }
+}
+
+impl FromStr for Cron {
+    type Err = &'static str;
+
+    fn from_str(expression: &str) -> Result<Self, Self::Err> {
+        let expression = match expression.trim() {
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            "@monthly" => "0 0 1 * *",
+            "@weekly" => "0 0 * * 0",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@hourly" => "0 * * * *",
+            expression => expression,
+        };
+
+        let mut fields = expression.split_ascii_whitespace();
+        let mut field = || fields.next().ok_or("Missing cron field");
+
+        let minutes = parse_field(field()?, 0, 59)?;
+        let hours = parse_field(field()?, 0, 23)?;
+        let days = field()?;
+        let months = parse_field(field()?, 1, 12)?;
+        let weekdays = field()?;
+
+        if fields.next().is_some() {
+            return Err("Too many cron fields");
+        }
+
+        // Sunday may be given as 7 too
+        let weekdays_mask = parse_field(weekdays, 0, 7)?;
+        let weekdays_mask = (weekdays_mask | (weekdays_mask >> 7)) & 0x7f;
+
+        Ok(Self {
+            minutes,
+            hours: hours as _,
+            days: parse_field(days, 1, 31)? as _,
+            months: months as _,
+            weekdays: weekdays_mask as _,
+            any_day: days == "*",
+            any_weekday: weekdays == "*",
+        })
+    }
+}
+
+impl Display for Cron {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let weekdays = self.weekdays as u64;
+
+        format_field(f, self.minutes, 0, 59)?;
+        f.write_char(' ')?;
+        format_field(f, self.hours as _, 0, 23)?;
+        f.write_char(' ')?;
+        format_field(f, self.days as _, 1, 31)?;
+        f.write_char(' ')?;
+        format_field(f, self.months as _, 1, 12)?;
+        f.write_char(' ')?;
+        format_field(f, weekdays, 0, 6)
+    }
+}
+
+#[cfg(feature = "use_serde")]
+impl Serialize for Cron {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "use_serde")]
+impl<'de> Deserialize<'de> for Cron {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        struct Visitor;
+
+        impl<'de> serde::de::Visitor<'de> for Visitor {
+            type Value = Cron;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "a cron expression")
+            }
+
+            fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                value.parse().map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_str(Visitor)
+    }
+}
+
+/// Returns the bitmask of the values of the field
+fn parse_field(field: &str, min: u8, max: u8) -> Result<u64, &'static str> {
+    let mut mask = 0;
+
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (
+                range,
+                step.parse::<u8>()
+                    .ok()
+                    .filter(|step| *step > 0)
+                    .ok_or("Invalid cron step")?,
+            ),
+            None => (part, 1),
+        };
+
+        let (from, to) = if range == "*" {
+            (min, max)
+        } else if let Some((from, to)) = range.split_once('-') {
+            (parse_value(from, min, max)?, parse_value(to, min, max)?)
+        } else {
+            let from = parse_value(range, min, max)?;
+
+            // `a/n` runs from `a` to the end of the range
+            (from, if part.contains('/') { max } else { from })
+        };
+
+        if from > to {
+            return Err("Invalid cron range");
+        }
+
+        for value in (from..=to).step_by(step as _) {
+            mask |= 1 << value;
+        }
+    }
+
+    Ok(mask)
+}
+
+fn parse_value(value: &str, min: u8, max: u8) -> Result<u8, &'static str> {
+    value
+        .parse::<u8>()
+        .ok()
+        .filter(|value| (min..=max).contains(value))
+        .ok_or("Cron value out of range")
+}
+
+fn format_field(f: &mut fmt::Formatter<'_>, mask: u64, min: u8, max: u8) -> fmt::Result {
+    if (min..=max).all(|value| mask & (1 << value) != 0) {
+        return f.write_char('*');
+    }
+
+    let mut first = true;
+    let mut value = min;
+
+    while value <= max {
+        if mask & (1 << value) == 0 {
+            value += 1;
+            continue;
+        }
+
+        let mut end = value;
+        while end < max && mask & (1 << (end + 1)) != 0 {
+            end += 1;
+        }
+
+        if !first {
+            f.write_char(',')?;
+        }
+
+        if end > value {
+            write!(f, "{value}-{end}")?;
+        } else {
+            write!(f, "{value}")?;
+        }
+
+        first = false;
+        value = end + 1;
+    }
+
+    Ok(())
+}
+
+fn first_set(mask: u64, from: u32, to: u32) -> Option<u32> {
+    (from..=to).find(|bit| mask & (1 << bit) != 0)
+}
+
+/// What to do with the occurrences of a task which were missed, e.g. during deep sleep or while the device
+/// was off
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub enum CatchUp {
+    /// The task runs again at its next occurrence
+    Skip,
+    /// All missed occurrences are coalesced into a single run
+    Once,
+    /// The task runs once per missed occurrence, up to `MAX_CATCH_UP` times
+    All,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub struct Task {
+    pub id: u16,
+    pub schedule: Schedule,
+    pub catch_up: CatchUp,
+}
+
+struct Entry {
+    task: Task,
+    next: Option<u64>,
+}
+
+/// Runs tasks at the occurrences of their schedules.
+///
+/// The scheduler does not run anything by itself: `arm` sets a one-shot timer for the next due task, which
+/// should then call `poll`. When the device deep sleeps between occurrences instead, `save` persists when
+/// each task is due next, and `next_due` tells how long to sleep; `restore` picks up from there on wake.
+pub struct Scheduler<T, const N: usize = 8> {
+    time: T,
+    time_zone: TimeZone,
+    grace: Duration,
+    entries: heapless::Vec<Entry, N>,
+}
+
+impl<T, const N: usize> Scheduler<T, N>
+where
+    T: SystemTime,
+{
+    pub const MAX_CATCH_UP: u32 = 1024;
+
+    const PREFIX: &'static str = "schedule";
+
+    /// An occurrence is missed once it is more than `grace` overdue
+    pub fn new(time: T, grace: Duration) -> Self {
+        Self {
+            time,
+            time_zone: TimeZone::utc(),
+            grace,
+            entries: heapless::Vec::new(),
+        }
+    }
+
+    pub fn time_zone(&self) -> &TimeZone {
+        &self.time_zone
+    }
+
+    /// Reschedules the tasks per the local time of `time_zone`
+    pub fn set_time_zone(&mut self, time_zone: TimeZone) {
+        let now = self.now();
+
+        for entry in &mut self.entries {
+            entry.next = entry.task.schedule.next_after_in(now, &time_zone);
+        }
+
+        self.time_zone = time_zone;
+    }
+
+    pub fn add(&mut self, task: Task) -> Result<(), &'static str> {
+        if self.entries.iter().any(|entry| entry.task.id == task.id) {
+            return Err("Duplicate task");
+        }
+
+        let next = task.schedule.next_after_in(self.now(), &self.time_zone);
+
+        self.entries
+            .push(Entry { task, next })
+            .map_err(|_| "Too many tasks")
+    }
+
+    pub fn remove(&mut self, id: u16) -> bool {
+        match self.entries.iter().position(|entry| entry.task.id == id) {
+            Some(index) => {
+                self.entries.remove(index);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn tasks(&self) -> impl Iterator<Item = &Task> {
+        self.entries.iter().map(|entry| &entry.task)
+    }
+
+    /// The time of the next occurrence of the task
+    pub fn next(&self, id: u16) -> Option<u64> {
+        self.entries
+            .iter()
+            .find(|entry| entry.task.id == id)
+            .and_then(|entry| entry.next)
+    }
+
+    /// The delay until the next task is due, zero if one is overdue, or `None` if no task is scheduled
+    pub fn next_due(&self) -> Option<Duration> {
+        let now = self.now();
+
+        self.entries
+            .iter()
+            .filter_map(|entry| entry.next)
+            .min()
+            .map(|next| Duration::from_secs(next.saturating_sub(now)))
+    }
+
+    /// Schedules `timer` to fire when the next task is due, or cancels it if no task is scheduled.
+    ///
+    /// Returns `false` if no task is scheduled.
+    pub fn arm<O>(&self, timer: &mut O) -> Result<bool, O::Error>
+    where
+        O: OnceTimer,
+    {
+        match self.next_due() {
+            Some(delay) => {
+                timer.after(delay)?;
+
+                Ok(true)
+            }
+            None => {
+                timer.cancel()?;
+
+                Ok(false)
+            }
+        }
+    }
+
+    /// Runs the due tasks, calling `run` with the ID of each of them and the number of times it should run,
+    /// per its `CatchUp` policy.
+    ///
+    /// Returns the number of tasks run.
+    pub fn poll<F>(&mut self, mut run: F) -> usize
+    where
+        F: FnMut(u16, u32),
+    {
+        let now = self.now();
+        let grace = self.grace.as_secs();
+
+        let mut count = 0;
+
+        for entry in &mut self.entries {
+            let mut next = match entry.next {
+                Some(next) if next <= now => next,
+                _ => continue,
+            };
+
+            let mut due = 0;
+            let mut missed = 0;
+
+            while next <= now {
+                if next + grace < now {
+                    missed += 1;
+                } else {
+                    due += 1;
+                }
+
+                match entry.task.schedule.next_after_in(next, &self.time_zone) {
+                    Some(after) if due + missed < Self::MAX_CATCH_UP => next = after,
+                    Some(_) => {
+                        next = entry
+                            .task
+                            .schedule
+                            .next_after_in(now, &self.time_zone)
+                            .unwrap_or(u64::MAX);
+                        break;
+                    }
+                    None => {
+                        next = u64::MAX;
+                        break;
+                    }
+                }
+            }
+
+            entry.next = if next == u64::MAX { None } else { Some(next) };
+
+            let runs = match entry.task.catch_up {
+                CatchUp::Skip => due.min(1),
+                CatchUp::Once => (due + missed).min(1),
+                CatchUp::All => due + missed,
+            };
+
+            if runs > 0 {
+                run(entry.task.id, runs);
+                count += 1;
+            }
+        }
+
+        count
+    }
+
+    /// Persists when each task is due next, e.g. before deep sleep
+    pub fn save<S>(&self, storage: &mut S) -> Result<(), S::Error>
+    where
+        S: RawStorage,
+    {
+        for entry in &self.entries {
+            let name = Self::slot(entry.task.id);
+
+            match entry.next {
+                Some(next) => storage.set_raw(&name, &next.to_le_bytes())?,
+                None => storage.remove(&name)?,
+            };
+        }
+
+        Ok(())
+    }
+
+    /// Restores when each task is due next, so that the occurrences missed since `save` are caught up with.
+    /// To be called after adding the tasks.
+    pub fn restore<S>(&mut self, storage: &S) -> Result<(), S::Error>
+    where
+        S: RawStorage,
+    {
+        for entry in &mut self.entries {
+            let mut buf = [0_u8; 8];
+
+            if let Some(next) = storage.get_raw(&Self::slot(entry.task.id), &mut buf)? {
+                if let Ok(next) = next.try_into() {
+                    entry.next = Some(u64::from_le_bytes(next));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn release(self) -> T {
+        self.time
+    }
+
+    fn now(&self) -> u64 {
+        self.time.now().as_secs()
+    }
+
+    fn slot(id: u16) -> heapless::String<16> {
+        let mut name = heapless::String::new();
+
+        write!(&mut name, "{}_{id}", Self::PREFIX).unwrap();
+
+        name
+    }
+}
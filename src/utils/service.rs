@@ -0,0 +1,429 @@
+use core::fmt::Debug;
+use core::marker::PhantomData;
+#[cfg(target_has_atomic = "8")]
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::time::Duration;
+
+use crate::eth::Eth;
+#[cfg(target_has_atomic = "8")]
+use crate::mqtt::client::Event;
+use crate::service::{ErrorType, Service, ServiceStatus};
+use crate::sys_time::{Instant, SystemTime};
+use crate::wifi::Wifi;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Implements `Service::wait_status_with_timeout` by polling the status of the service,
+/// sleeping with `delay` in-between.
+pub fn poll_status<S, T, D, P>(
+    service: &S,
+    time: &T,
+    delay: &D,
+    predicate: P,
+    timeout: Duration,
+) -> Result<bool, S::Error>
+where
+    S: Service,
+    T: SystemTime,
+    D: Fn(Duration),
+    P: Fn(ServiceStatus) -> bool,
+{
+    let started = Instant::now(time);
+
+    loop {
+        if predicate(service.status()?) {
+            return Ok(true);
+        }
+
+        let elapsed = started.elapsed(time);
+        if elapsed >= timeout {
+            return Ok(false);
+        }
+
+        delay(POLL_INTERVAL.min(timeout - elapsed));
+    }
+}
+
+/// A `Service` on top of a `Wifi` implementation.
+///
+/// When a client configuration is set, the service is considered started only once connected.
+pub struct WifiService<W, T, D> {
+    wifi: W,
+    time: T,
+    delay: D,
+}
+
+impl<W, T, D> WifiService<W, T, D>
+where
+    W: Wifi,
+    T: SystemTime,
+    D: Fn(Duration),
+{
+    pub const fn new(wifi: W, time: T, delay: D) -> Self {
+        Self { wifi, time, delay }
+    }
+
+    pub fn wifi(&self) -> &W {
+        &self.wifi
+    }
+
+    pub fn wifi_mut(&mut self) -> &mut W {
+        &mut self.wifi
+    }
+
+    pub fn release(self) -> W {
+        self.wifi
+    }
+
+    fn is_client(&self) -> Result<bool, W::Error> {
+        Ok(self
+            .wifi
+            .get_configuration()?
+            .as_client_conf_ref()
+            .is_some())
+    }
+}
+
+impl<W, T, D> ErrorType for WifiService<W, T, D>
+where
+    W: Wifi,
+{
+    type Error = W::Error;
+}
+
+impl<W, T, D> Service for WifiService<W, T, D>
+where
+    W: Wifi,
+    T: SystemTime,
+    D: Fn(Duration),
+{
+    fn start(&mut self) -> Result<(), Self::Error> {
+        self.wifi.start()?;
+
+        if self.is_client()? {
+            self.wifi.connect()?;
+        }
+
+        Ok(())
+    }
+
+    fn stop(&mut self) -> Result<(), Self::Error> {
+        if self.is_client()? && self.wifi.is_connected()? {
+            self.wifi.disconnect()?;
+        }
+
+        self.wifi.stop()
+    }
+
+    fn status(&self) -> Result<ServiceStatus, Self::Error> {
+        let status = if !self.wifi.is_started()? {
+            ServiceStatus::Stopped
+        } else if self.is_client()? && !self.wifi.is_connected()? {
+            ServiceStatus::Starting
+        } else {
+            ServiceStatus::Started
+        };
+
+        Ok(status)
+    }
+
+    fn wait_status_with_timeout<P>(
+        &self,
+        predicate: P,
+        timeout: Duration,
+    ) -> Result<bool, Self::Error>
+    where
+        P: Fn(ServiceStatus) -> bool,
+    {
+        poll_status(self, &self.time, &self.delay, predicate, timeout)
+    }
+}
+
+/// A `Service` on top of an `Eth` implementation.
+///
+/// The service is considered started only once the link is up.
+pub struct EthService<E, T, D> {
+    eth: E,
+    time: T,
+    delay: D,
+}
+
+impl<E, T, D> EthService<E, T, D>
+where
+    E: Eth,
+    T: SystemTime,
+    D: Fn(Duration),
+{
+    pub const fn new(eth: E, time: T, delay: D) -> Self {
+        Self { eth, time, delay }
+    }
+
+    pub fn eth(&self) -> &E {
+        &self.eth
+    }
+
+    pub fn eth_mut(&mut self) -> &mut E {
+        &mut self.eth
+    }
+
+    pub fn release(self) -> E {
+        self.eth
+    }
+}
+
+impl<E, T, D> ErrorType for EthService<E, T, D>
+where
+    E: Eth,
+{
+    type Error = E::Error;
+}
+
+impl<E, T, D> Service for EthService<E, T, D>
+where
+    E: Eth,
+    T: SystemTime,
+    D: Fn(Duration),
+{
+    fn start(&mut self) -> Result<(), Self::Error> {
+        self.eth.start()
+    }
+
+    fn stop(&mut self) -> Result<(), Self::Error> {
+        self.eth.stop()
+    }
+
+    fn status(&self) -> Result<ServiceStatus, Self::Error> {
+        let status = if !self.eth.is_started()? {
+            ServiceStatus::Stopped
+        } else if !self.eth.is_up()? {
+            ServiceStatus::Starting
+        } else {
+            ServiceStatus::Started
+        };
+
+        Ok(status)
+    }
+
+    fn wait_status_with_timeout<P>(
+        &self,
+        predicate: P,
+        timeout: Duration,
+    ) -> Result<bool, Self::Error>
+    where
+        P: Fn(ServiceStatus) -> bool,
+    {
+        poll_status(self, &self.time, &self.delay, predicate, timeout)
+    }
+}
+
+/// A `Service` on top of an HTTP server, which serves while it exists: `start` creates the server with
+/// `factory`, which registers its handlers, and `stop` drops it.
+pub struct HttpServerService<F, S, E> {
+    factory: F,
+    server: Option<S>,
+    _error: PhantomData<fn() -> E>,
+}
+
+impl<F, S, E> HttpServerService<F, S, E>
+where
+    F: FnMut() -> Result<S, E>,
+    E: Debug,
+{
+    pub const fn new(factory: F) -> Self {
+        Self {
+            factory,
+            server: None,
+            _error: PhantomData,
+        }
+    }
+
+    /// The server, while the service is started
+    pub fn server(&self) -> Option<&S> {
+        self.server.as_ref()
+    }
+
+    pub fn server_mut(&mut self) -> Option<&mut S> {
+        self.server.as_mut()
+    }
+
+    pub fn release(self) -> F {
+        self.factory
+    }
+}
+
+impl<F, S, E> ErrorType for HttpServerService<F, S, E>
+where
+    E: Debug,
+{
+    type Error = E;
+}
+
+impl<F, S, E> Service for HttpServerService<F, S, E>
+where
+    F: FnMut() -> Result<S, E>,
+    E: Debug,
+{
+    fn start(&mut self) -> Result<(), Self::Error> {
+        if self.server.is_none() {
+            self.server = Some((self.factory)()?);
+        }
+
+        Ok(())
+    }
+
+    fn stop(&mut self) -> Result<(), Self::Error> {
+        self.server = None;
+
+        Ok(())
+    }
+
+    fn status(&self) -> Result<ServiceStatus, Self::Error> {
+        let status = if self.server.is_some() {
+            ServiceStatus::Started
+        } else {
+            ServiceStatus::Stopped
+        };
+
+        Ok(status)
+    }
+
+    /// The status only changes with `start` and `stop`, so there is nothing to wait for
+    fn wait_status_with_timeout<P>(
+        &self,
+        predicate: P,
+        _timeout: Duration,
+    ) -> Result<bool, Self::Error>
+    where
+        P: Fn(ServiceStatus) -> bool,
+    {
+        Ok(predicate(self.status()?))
+    }
+}
+
+/// Whether an MQTT client is connected to its broker, as the events of its connection tell; the
+/// application updates it from the loop processing the connection, and shares it with the
+/// `MqttService` of the client.
+#[cfg(target_has_atomic = "8")]
+#[derive(Debug, Default)]
+pub struct MqttConnectionState(AtomicBool);
+
+#[cfg(target_has_atomic = "8")]
+impl MqttConnectionState {
+    pub const fn new() -> Self {
+        Self(AtomicBool::new(false))
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+
+    pub fn update<M>(&self, event: &Event<M>) {
+        match event {
+            Event::Connected(_) => self.0.store(true, Ordering::SeqCst),
+            Event::BeforeConnect | Event::Disconnected => self.0.store(false, Ordering::SeqCst),
+            _ => (),
+        }
+    }
+}
+
+/// A `Service` on top of an MQTT client, which connects while it exists: `start` creates the client
+/// with `factory`, e.g. with its connection, and `stop` drops it.
+///
+/// The service is considered started only once `state` reports the client as connected.
+#[cfg(target_has_atomic = "8")]
+pub struct MqttService<'a, F, C, E, T, D> {
+    factory: F,
+    client: Option<C>,
+    state: &'a MqttConnectionState,
+    time: T,
+    delay: D,
+    _error: PhantomData<fn() -> E>,
+}
+
+#[cfg(target_has_atomic = "8")]
+impl<'a, F, C, E, T, D> MqttService<'a, F, C, E, T, D>
+where
+    F: FnMut() -> Result<C, E>,
+    E: Debug,
+    T: SystemTime,
+    D: Fn(Duration),
+{
+    pub const fn new(factory: F, state: &'a MqttConnectionState, time: T, delay: D) -> Self {
+        Self {
+            factory,
+            client: None,
+            state,
+            time,
+            delay,
+            _error: PhantomData,
+        }
+    }
+
+    /// The client, while the service is started
+    pub fn client(&self) -> Option<&C> {
+        self.client.as_ref()
+    }
+
+    pub fn client_mut(&mut self) -> Option<&mut C> {
+        self.client.as_mut()
+    }
+
+    pub fn release(self) -> F {
+        self.factory
+    }
+}
+
+#[cfg(target_has_atomic = "8")]
+impl<'a, F, C, E, T, D> ErrorType for MqttService<'a, F, C, E, T, D>
+where
+    E: Debug,
+{
+    type Error = E;
+}
+
+#[cfg(target_has_atomic = "8")]
+impl<'a, F, C, E, T, D> Service for MqttService<'a, F, C, E, T, D>
+where
+    F: FnMut() -> Result<C, E>,
+    E: Debug,
+    T: SystemTime,
+    D: Fn(Duration),
+{
+    fn start(&mut self) -> Result<(), Self::Error> {
+        if self.client.is_none() {
+            self.client = Some((self.factory)()?);
+        }
+
+        Ok(())
+    }
+
+    fn stop(&mut self) -> Result<(), Self::Error> {
+        self.client = None;
+        self.state.0.store(false, Ordering::SeqCst);
+
+        Ok(())
+    }
+
+    fn status(&self) -> Result<ServiceStatus, Self::Error> {
+        let status = if self.client.is_none() {
+            ServiceStatus::Stopped
+        } else if !self.state.is_connected() {
+            ServiceStatus::Starting
+        } else {
+            ServiceStatus::Started
+        };
+
+        Ok(status)
+    }
+
+    fn wait_status_with_timeout<P>(
+        &self,
+        predicate: P,
+        timeout: Duration,
+    ) -> Result<bool, Self::Error>
+    where
+        P: Fn(ServiceStatus) -> bool,
+    {
+        poll_status(self, &self.time, &self.delay, predicate, timeout)
+    }
+}
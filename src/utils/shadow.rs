@@ -0,0 +1,259 @@
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::error::impl_error;
+use crate::mqtt::client::{Client, MessageId, Publish, QoS};
+use crate::storage::{SerDe, Storage};
+
+/// A device state which can be mirrored in a shadow (digital twin).
+///
+/// `Delta` is a partial version of the state, typically the same struct with all fields wrapped in `Option`.
+pub trait ShadowState: Clone + PartialEq + Serialize + DeserializeOwned {
+    type Delta: Serialize + DeserializeOwned;
+
+    /// The changes needed to turn `other` into `self`; `None` if there are none
+    fn delta(&self, other: &Self) -> Option<Self::Delta>;
+
+    fn apply(&mut self, delta: &Self::Delta);
+}
+
+/// The desired and reported halves of a shadow, as tracked on the device.
+///
+/// The desired state is changed by the cloud (or the local UI); the application acts on `pending`
+/// and then reports the resulting state, which is published by a `ShadowTransport`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Shadow<T> {
+    pub desired: T,
+    pub reported: T,
+    /// The version of the last desired state change received, if the transport provides one
+    pub version: u64,
+}
+
+impl<T> Shadow<T>
+where
+    T: ShadowState,
+{
+    pub fn new(state: T) -> Self {
+        Self {
+            desired: state.clone(),
+            reported: state,
+            version: 0,
+        }
+    }
+
+    /// Loads the shadow persisted under `name`, or creates a new one from `default`
+    pub fn load<S>(storage: &S, name: &str, default: T) -> Result<Self, S::Error>
+    where
+        S: Storage,
+    {
+        Ok(storage.get(name)?.unwrap_or_else(|| Self::new(default)))
+    }
+
+    pub fn save<S>(&self, storage: &mut S, name: &str) -> Result<bool, S::Error>
+    where
+        S: Storage,
+    {
+        storage.set(name, self)
+    }
+
+    /// The changes the application still needs to act on
+    pub fn pending(&self) -> Option<T::Delta> {
+        self.desired.delta(&self.reported)
+    }
+
+    pub fn is_in_sync(&self) -> bool {
+        self.desired == self.reported
+    }
+
+    /// Applies a desired state change. Changes with an older version than the last one received are ignored.
+    pub fn update_desired(&mut self, delta: &T::Delta, version: Option<u64>) -> bool {
+        if let Some(version) = version {
+            if version < self.version {
+                return false;
+            }
+
+            self.version = version;
+        }
+
+        self.desired.apply(delta);
+
+        true
+    }
+
+    /// Records the actual state of the device.
+    /// Returns the changes compared to the previously reported state, which should be published.
+    pub fn update_reported(&mut self, reported: T) -> Option<T::Delta> {
+        let delta = reported.delta(&self.reported);
+
+        self.reported = reported;
+
+        delta
+    }
+}
+
+/// How shadow documents are wrapped on the wire
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Envelope {
+    /// The reported state and the desired deltas are sent as-is, e.g. Azure IoT Hub twins
+    /// (reported properties PATCH and desired properties notifications)
+    Plain,
+    /// `{"state":{"reported":...}}` for reports and `{"state":...,"version":N}` for deltas,
+    /// as used by AWS IoT device shadows
+    State,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ShadowTopics<'a> {
+    /// Where the reported state is published
+    pub reported: &'a str,
+    /// Where desired state changes arrive; may end with the `#` wildcard
+    pub desired: &'a str,
+}
+
+impl<'a> ShadowTopics<'a> {
+    pub fn is_desired(&self, topic: &str) -> bool {
+        match self.desired.strip_suffix('#') {
+            Some(prefix) => topic.starts_with(prefix),
+            None => topic == self.desired,
+        }
+    }
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ShadowError<P, S> {
+    PublishError(P),
+    SerdeError(S),
+}
+
+impl_error! {
+    ShadowError<P: Display, S: Display> {
+        PublishError(e) => "Publish error: {e}"; e.error_kind(),
+        SerdeError(e) => "SerDe error: {e}"; e.error_kind(),
+    }
+}
+
+#[derive(Serialize)]
+struct ReportedEnvelope<'a, R> {
+    state: ReportedState<'a, R>,
+}
+
+#[derive(Serialize)]
+struct ReportedState<'a, R> {
+    reported: &'a R,
+}
+
+#[derive(Deserialize)]
+struct DeltaEnvelope<D> {
+    state: D,
+    #[serde(default)]
+    version: Option<u64>,
+}
+
+/// Carries a `Shadow` over MQTT, with documents serialized with `S` into a buffer of `B` bytes.
+pub struct ShadowTransport<'a, P, S, const B: usize = 1024> {
+    publisher: P,
+    serde: S,
+    topics: ShadowTopics<'a>,
+    envelope: Envelope,
+}
+
+impl<'a, P, S, const B: usize> ShadowTransport<'a, P, S, B>
+where
+    P: Publish,
+    S: SerDe,
+{
+    pub const fn new(publisher: P, serde: S, topics: ShadowTopics<'a>, envelope: Envelope) -> Self {
+        Self {
+            publisher,
+            serde,
+            topics,
+            envelope,
+        }
+    }
+
+    pub fn subscribe<C>(&self, client: &mut C) -> Result<MessageId, C::Error>
+    where
+        C: Client,
+    {
+        client.subscribe(self.topics.desired, QoS::AtLeastOnce)
+    }
+
+    /// Publishes `reported`, which might be either the full state or a delta
+    pub fn publish_reported<R>(
+        &mut self,
+        reported: &R,
+    ) -> Result<MessageId, ShadowError<P::Error, S::Error>>
+    where
+        R: Serialize,
+    {
+        let mut buf = [0_u8; B];
+
+        let payload = match self.envelope {
+            Envelope::Plain => self.serde.serialize(&mut buf, reported),
+            Envelope::State => self.serde.serialize(
+                &mut buf,
+                &ReportedEnvelope {
+                    state: ReportedState { reported },
+                },
+            ),
+        }
+        .map_err(ShadowError::SerdeError)?;
+
+        self.publisher
+            .publish(self.topics.reported, QoS::AtLeastOnce, false, payload)
+            .map_err(ShadowError::PublishError)
+    }
+
+    /// Feeds a received MQTT message into `shadow`.
+    ///
+    /// Returns `Ok(false)` if the message is not a desired state change, or if it was outdated.
+    pub fn receive<T>(
+        &self,
+        shadow: &mut Shadow<T>,
+        topic: &str,
+        data: &[u8],
+    ) -> Result<bool, S::Error>
+    where
+        T: ShadowState,
+    {
+        if !self.topics.is_desired(topic) {
+            return Ok(false);
+        }
+
+        match self.envelope {
+            Envelope::Plain => {
+                let delta: T::Delta = self.serde.deserialize(data)?;
+
+                Ok(shadow.update_desired(&delta, None))
+            }
+            Envelope::State => {
+                let envelope: DeltaEnvelope<T::Delta> = self.serde.deserialize(data)?;
+
+                Ok(shadow.update_desired(&envelope.state, envelope.version))
+            }
+        }
+    }
+
+    /// Reports the actual state of the device, publishing only what changed
+    pub fn report<T>(
+        &mut self,
+        shadow: &mut Shadow<T>,
+        reported: T,
+    ) -> Result<Option<MessageId>, ShadowError<P::Error, S::Error>>
+    where
+        T: ShadowState,
+    {
+        match shadow.update_reported(reported) {
+            Some(delta) => self.publish_reported(&delta).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    pub fn release(self) -> (P, S) {
+        (self.publisher, self.serde)
+    }
+}
@@ -0,0 +1,384 @@
+//! An SNTP (RFC 4330) server, serving the time of the device to the hosts behind it, e.g. to sensors
+//! connected to its access point, which have no Internet access of their own.
+//!
+//! The server answers on the socket it is given, so bind it to the address of the interface the clients
+//! are on, on `PORT`, to not serve time to the other networks of the device.
+
+use core::time::Duration;
+
+#[cfg(feature = "use_serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::ipv4::SocketAddrV4;
+use crate::net::udp::UdpSocket;
+use crate::rtc::Rtc;
+use crate::sys_time::SystemTime;
+
+use super::rtc::{Source, WallClock};
+
+pub const PORT: u16 = 123;
+
+/// The seconds from the NTP epoch (1900) to the Unix epoch
+const NTP_TO_UNIX_SECS: u64 = 2_208_988_800;
+
+const PACKET_LEN: usize = 48;
+
+const MODE_CLIENT: u8 = 3;
+const MODE_SERVER: u8 = 4;
+
+/// The clock is not synchronized
+const LEAP_ALARM: u8 = 3;
+
+/// About a millisecond, as log2 seconds
+const PRECISION: i8 = -10;
+
+/// The stratum which clients are asked not to synchronize with
+const STRATUM_UNSYNCHRONIZED: u8 = 16;
+
+/// A clock which can tell how and when it was set
+pub trait ReferenceClock: SystemTime {
+    fn source(&self) -> Source;
+
+    /// The UTC time the clock was last set or corrected at, served as the reference timestamp
+    fn set_at(&self) -> Option<Duration>;
+}
+
+impl<C> ReferenceClock for &C
+where
+    C: ReferenceClock,
+{
+    fn source(&self) -> Source {
+        (**self).source()
+    }
+
+    fn set_at(&self) -> Option<Duration> {
+        (**self).set_at()
+    }
+}
+
+impl<T, R> ReferenceClock for WallClock<T, R>
+where
+    T: SystemTime,
+    R: Rtc,
+{
+    fn source(&self) -> Source {
+        WallClock::source(self)
+    }
+
+    fn set_at(&self) -> Option<Duration> {
+        WallClock::set_at(self)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub struct Configuration {
+    /// Served while the clock is synchronized with SNTP; one more than the stratum of the upstream server,
+    /// e.g. 3 with the `pool.ntp.org` servers, which are mostly stratum 2
+    pub stratum: u8,
+    /// Whether the time is served while the clock was only set from the RTC or approximated. It is then
+    /// served as stratum 15, the lowest quality clients accept; otherwise, and while the clock is not set,
+    /// clients are answered that the server is not synchronized.
+    pub serve_unsynchronized: bool,
+}
+
+impl Configuration {
+    /// Checks that the stratum is from 1 to 15, the strata of synchronized servers
+    pub fn validate(&self) -> Result<(), &'static str> {
+        if !(1..STRATUM_UNSYNCHRONIZED).contains(&self.stratum) {
+            return Err("SNTP stratum should be from 1 to 15");
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for Configuration {
+    fn default() -> Self {
+        Self {
+            stratum: 3,
+            serve_unsynchronized: false,
+        }
+    }
+}
+
+pub struct SntpServer<S> {
+    socket: S,
+    configuration: Configuration,
+}
+
+impl<S> SntpServer<S>
+where
+    S: UdpSocket,
+{
+    /// `socket` is bound to `PORT`; fails if the configuration is not valid
+    pub fn new(socket: S, configuration: Configuration) -> Result<Self, &'static str> {
+        configuration.validate()?;
+
+        Ok(Self {
+            socket,
+            configuration,
+        })
+    }
+
+    pub fn configuration(&self) -> &Configuration {
+        &self.configuration
+    }
+
+    /// Waits up to `timeout` (or forever, if `None`) for a request, and answers it with the time of
+    /// `clock`, which returns UTC time since the Unix epoch.
+    ///
+    /// Returns the client answered, or `None` if no request was received; datagrams which are not SNTP
+    /// client requests are ignored.
+    pub fn handle<C>(
+        &mut self,
+        clock: &C,
+        timeout: Option<Duration>,
+    ) -> Result<Option<SocketAddrV4>, S::Error>
+    where
+        C: ReferenceClock,
+    {
+        let mut packet = [0_u8; 64];
+
+        let (len, client) = match self.socket.receive_from(&mut packet, timeout)? {
+            Some(received) => received,
+            None => return Ok(None),
+        };
+
+        let received = clock.now();
+
+        let request = &packet[..len];
+
+        let version = request.first().map_or(0, |byte| (byte >> 3) & 0x07);
+        let mode = request.first().map_or(0, |byte| byte & 0x07);
+
+        if len < PACKET_LEN || mode != MODE_CLIENT || !(1..=4).contains(&version) {
+            return Ok(None);
+        }
+
+        let (leap, stratum) = match clock.source() {
+            Source::Sntp => (0, self.configuration.stratum),
+            Source::Rtc | Source::Approximate if self.configuration.serve_unsynchronized => (0, 15),
+            _ => (LEAP_ALARM, STRATUM_UNSYNCHRONIZED),
+        };
+
+        let mut reply = [0_u8; PACKET_LEN];
+
+        reply[0] = (leap << 6) | (version << 3) | MODE_SERVER;
+        reply[1] = stratum;
+        // The poll interval of the client
+        reply[2] = request[2];
+        reply[3] = PRECISION as u8;
+        // The root delay and dispersion, and the reference ID, are left to zero: they are not known, as the
+        // clock only keeps the time it was last set to
+
+        if let Some(set_at) = clock.set_at() {
+            reply[16..24].copy_from_slice(&ntp_timestamp(set_at));
+        }
+        // The origin timestamp is the transmit timestamp of the request
+        reply[24..32].copy_from_slice(&request[40..48]);
+        reply[32..40].copy_from_slice(&ntp_timestamp(received));
+        reply[40..48].copy_from_slice(&ntp_timestamp(clock.now()));
+
+        self.socket.send_to(&reply, client)?;
+
+        Ok(Some(client))
+    }
+
+    pub fn release(self) -> S {
+        self.socket
+    }
+}
+
+/// Seconds since 1900 and their fraction in units of 2^-32 seconds, which wraps in 2036 into era 1, as
+/// RFC 4330 specifies
+fn ntp_timestamp(utc: Duration) -> [u8; 8] {
+    let secs = (utc.as_secs() + NTP_TO_UNIX_SECS) as u32;
+    let fraction = ((utc.subsec_nanos() as u64) << 32) / 1_000_000_000;
+
+    let mut timestamp = [0; 8];
+
+    timestamp[..4].copy_from_slice(&secs.to_be_bytes());
+    timestamp[4..].copy_from_slice(&(fraction as u32).to_be_bytes());
+
+    timestamp
+}
+
+#[cfg(test)]
+mod tests {
+    use core::convert::Infallible;
+
+    use crate::io::Io;
+    use crate::ipv4::Ipv4Addr;
+
+    use super::*;
+
+    const CLIENT: SocketAddrV4 = SocketAddrV4::new(Ipv4Addr::new(192, 168, 71, 2), 50123);
+
+    /// Receives one request, and keeps the reply
+    struct Socket {
+        request: Option<[u8; PACKET_LEN]>,
+        reply: Option<[u8; PACKET_LEN]>,
+    }
+
+    impl Io for Socket {
+        type Error = Infallible;
+    }
+
+    impl UdpSocket for Socket {
+        fn local_addr(&self) -> Result<SocketAddrV4, Self::Error> {
+            Ok(SocketAddrV4::new(Ipv4Addr::new(192, 168, 71, 1), PORT))
+        }
+
+        fn send_to(&mut self, buf: &[u8], _addr: SocketAddrV4) -> Result<usize, Self::Error> {
+            let mut reply = [0; PACKET_LEN];
+            reply.copy_from_slice(buf);
+
+            self.reply = Some(reply);
+
+            Ok(buf.len())
+        }
+
+        fn receive_from(
+            &mut self,
+            buf: &mut [u8],
+            _timeout: Option<Duration>,
+        ) -> Result<Option<(usize, SocketAddrV4)>, Self::Error> {
+            Ok(self.request.take().map(|request| {
+                buf[..PACKET_LEN].copy_from_slice(&request);
+
+                (PACKET_LEN, CLIENT)
+            }))
+        }
+
+        fn set_broadcast(&mut self, _enabled: bool) -> Result<(), Self::Error> {
+            Ok(())
+        }
+
+        fn join_multicast(&mut self, _group: Ipv4Addr) -> Result<(), Self::Error> {
+            Ok(())
+        }
+
+        fn leave_multicast(&mut self, _group: Ipv4Addr) -> Result<(), Self::Error> {
+            Ok(())
+        }
+    }
+
+    struct Clock {
+        now: Duration,
+        source: Source,
+        set_at: Option<Duration>,
+    }
+
+    impl SystemTime for Clock {
+        fn now(&self) -> Duration {
+            self.now
+        }
+    }
+
+    impl ReferenceClock for Clock {
+        fn source(&self) -> Source {
+            self.source
+        }
+
+        fn set_at(&self) -> Option<Duration> {
+            self.set_at
+        }
+    }
+
+    fn request() -> [u8; PACKET_LEN] {
+        let mut request = [0; PACKET_LEN];
+
+        // Version 4, client
+        request[0] = (4 << 3) | MODE_CLIENT;
+        request[2] = 6;
+        request[40..48].copy_from_slice(&[1, 2, 3, 4, 5, 6, 7, 8]);
+
+        request
+    }
+
+    fn reply(configuration: Configuration, clock: &Clock) -> [u8; PACKET_LEN] {
+        let socket = Socket {
+            request: Some(request()),
+            reply: None,
+        };
+
+        let mut server = SntpServer::new(socket, configuration).unwrap();
+
+        assert_eq!(server.handle(clock, None), Ok(Some(CLIENT)));
+
+        server.release().reply.unwrap()
+    }
+
+    #[test]
+    fn synchronized() {
+        let clock = Clock {
+            now: Duration::from_millis(1_700_000_100_500),
+            source: Source::Sntp,
+            set_at: Some(Duration::from_secs(1_700_000_000)),
+        };
+
+        let reply = reply(Default::default(), &clock);
+
+        assert_eq!(reply[0], (4 << 3) | MODE_SERVER);
+        assert_eq!(reply[1], 3);
+        assert_eq!(reply[2], 6);
+        assert_eq!(
+            reply[16..24],
+            ntp_timestamp(Duration::from_secs(1_700_000_000))
+        );
+        assert_eq!(reply[24..32], [1, 2, 3, 4, 5, 6, 7, 8]);
+        assert_eq!(reply[32..40], ntp_timestamp(clock.now));
+        assert_eq!(reply[40..48], ntp_timestamp(clock.now));
+    }
+
+    #[test]
+    fn unsynchronized() {
+        let clock = Clock {
+            now: Duration::from_secs(5),
+            source: Source::None,
+            set_at: None,
+        };
+
+        let reply = reply(Default::default(), &clock);
+
+        assert_eq!(reply[0] >> 6, LEAP_ALARM);
+        assert_eq!(reply[1], STRATUM_UNSYNCHRONIZED);
+        assert_eq!(reply[16..24], [0; 8]);
+    }
+
+    #[test]
+    fn invalid_stratum() {
+        for stratum in [0, 16] {
+            let configuration = Configuration {
+                stratum,
+                ..Default::default()
+            };
+
+            assert!(configuration.validate().is_err());
+        }
+
+        assert!(SntpServer::new(
+            Socket {
+                request: None,
+                reply: None,
+            },
+            Configuration {
+                stratum: 0,
+                ..Default::default()
+            },
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn timestamp() {
+        // 2024-01-01T00:00:00.5Z
+        assert_eq!(
+            ntp_timestamp(Duration::from_millis(1_704_067_200_500)),
+            [0xe9, 0x3c, 0x7f, 0x00, 0x80, 0, 0, 0]
+        );
+    }
+}
@@ -0,0 +1,331 @@
+use core::time::Duration;
+
+#[cfg(all(feature = "alloc", target_has_atomic = "ptr"))]
+extern crate alloc;
+#[cfg(all(feature = "alloc", target_has_atomic = "ptr"))]
+use alloc::sync::Arc;
+
+#[cfg(feature = "use_serde")]
+use serde::{Deserialize, Serialize};
+
+#[cfg(all(feature = "alloc", target_has_atomic = "ptr"))]
+use crate::event_bus::EventBus;
+use crate::service::ServiceStatus;
+use crate::sys_time::Instant;
+#[cfg(all(feature = "alloc", target_has_atomic = "ptr"))]
+use crate::sys_time::SystemTime;
+#[cfg(all(feature = "alloc", target_has_atomic = "ptr"))]
+use crate::utils::mutex::{Mutex, RawMutex};
+
+pub type ServiceId = usize;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Action {
+    Start(ServiceId),
+    Stop(ServiceId),
+}
+
+/// A status change of a supervised service, as posted on the event bus by the application, e.g. when
+/// translating the events of the Wi-Fi or MQTT drivers
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub struct ServiceEvent {
+    pub id: ServiceId,
+    pub status: ServiceStatus,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct RestartPolicy {
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    pub multiplier: u32,
+}
+
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        Self {
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(60),
+            multiplier: 2,
+        }
+    }
+}
+
+struct Entry<const N: usize> {
+    dependencies: heapless::Vec<ServiceId, N>,
+    status: ServiceStatus,
+    stopping: bool,
+    backoff: Duration,
+    restart_at: Option<Instant>,
+}
+
+/// Orchestrates the lifecycle of up to `N` services which depend on each other.
+///
+/// The supervisor does not own the services. Instead, it computes the next `Action` the
+/// application should execute against the service with the given ID. The application is expected to:
+/// - Report every status change of the supervised services with `update`, typically by posting
+///   them as `ServiceEvent`s on an event bus the supervisor is subscribed to with `subscribe` (or
+///   with `Failed` when executing an `Action` failed)
+/// - Execute the actions returned by `poll` until there are none left, and then
+///   call `poll` again on each status update or once `next_deadline` is reached
+///
+/// Services are started in the order they are added, and stopped in the reverse order.
+/// A service is only started once all of its dependencies are started, and it is stopped when
+/// any of them goes down. Services which stop or fail unexpectedly are restarted with an
+/// exponential backoff, as configured by the `RestartPolicy`.
+pub struct Supervisor<const N: usize = 8> {
+    services: heapless::Vec<Entry<N>, N>,
+    policy: RestartPolicy,
+    running: bool,
+}
+
+impl<const N: usize> Supervisor<N> {
+    pub fn new(policy: RestartPolicy) -> Self {
+        Self {
+            services: heapless::Vec::new(),
+            policy,
+            running: false,
+        }
+    }
+
+    /// All dependencies should be added before the services depending on them
+    pub fn add(&mut self, dependencies: &[ServiceId]) -> Result<ServiceId, &'static str> {
+        if dependencies.iter().any(|id| *id >= self.services.len()) {
+            return Err("Unknown dependency");
+        }
+
+        let id = self.services.len();
+
+        self.services
+            .push(Entry {
+                dependencies: heapless::Vec::from_slice(dependencies)
+                    .map_err(|_| "Too many dependencies")?,
+                status: ServiceStatus::Stopped,
+                stopping: false,
+                backoff: self.policy.initial_backoff,
+                restart_at: None,
+            })
+            .map_err(|_| "Too many services")?;
+
+        Ok(id)
+    }
+
+    pub fn start(&mut self) {
+        self.running = true;
+    }
+
+    pub fn stop(&mut self) {
+        self.running = false;
+
+        for entry in &mut self.services {
+            entry.restart_at = None;
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running
+    }
+
+    pub fn status(&self, id: ServiceId) -> Option<ServiceStatus> {
+        self.services.get(id).map(|entry| entry.status)
+    }
+
+    pub fn update(&mut self, id: ServiceId, status: ServiceStatus, now: Instant) {
+        let policy = self.policy;
+        let running = self.running;
+
+        if let Some(entry) = self.services.get_mut(id) {
+            let unexpected = status == ServiceStatus::Failed
+                || (status == ServiceStatus::Stopped
+                    && !entry.stopping
+                    && matches!(
+                        entry.status,
+                        ServiceStatus::Starting | ServiceStatus::Started
+                    ));
+
+            entry.status = status;
+
+            match status {
+                ServiceStatus::Started => {
+                    entry.backoff = policy.initial_backoff;
+                    entry.restart_at = None;
+                }
+                ServiceStatus::Stopped | ServiceStatus::Failed => {
+                    entry.stopping = false;
+
+                    if unexpected && running {
+                        // A backoff past the end of time restarts the service at the latest instant
+                        entry.restart_at = Some(
+                            now.checked_add(entry.backoff)
+                                .unwrap_or(Instant::from_duration(Duration::MAX)),
+                        );
+                        entry.backoff = entry
+                            .backoff
+                            .checked_mul(policy.multiplier)
+                            .unwrap_or(policy.max_backoff)
+                            .min(policy.max_backoff);
+                    }
+                }
+                _ => (),
+            }
+        }
+    }
+
+    /// Same as `update`, for a status change received from the event bus
+    pub fn handle(&mut self, event: &ServiceEvent, now: Instant) {
+        self.update(event.id, event.status, now);
+    }
+
+    pub fn poll(&mut self, now: Instant) -> Option<Action> {
+        let healthy = self.healthy();
+
+        for id in (0..self.services.len()).rev() {
+            let entry = &self.services[id];
+
+            if Self::is_active(entry.status)
+                && !entry.stopping
+                && (!self.running || !Self::dependencies_healthy(entry, &healthy))
+                && !self.has_active_dependents(id)
+            {
+                let entry = &mut self.services[id];
+
+                entry.status = ServiceStatus::Stopping;
+                entry.stopping = true;
+
+                return Some(Action::Stop(id));
+            }
+        }
+
+        if self.running {
+            for id in 0..self.services.len() {
+                let entry = &self.services[id];
+
+                if matches!(entry.status, ServiceStatus::Stopped | ServiceStatus::Failed)
+                    && Self::dependencies_healthy(entry, &healthy)
+                    && entry.restart_at.map(|at| now >= at).unwrap_or(true)
+                {
+                    let entry = &mut self.services[id];
+
+                    entry.status = ServiceStatus::Starting;
+                    entry.restart_at = None;
+
+                    return Some(Action::Start(id));
+                }
+            }
+        }
+
+        None
+    }
+
+    /// The earliest time at which a pending restart becomes due
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.services
+            .iter()
+            .filter_map(|entry| entry.restart_at)
+            .min()
+    }
+
+    fn healthy(&self) -> [bool; N] {
+        let mut healthy = [false; N];
+
+        for (id, entry) in self.services.iter().enumerate() {
+            healthy[id] = entry.status == ServiceStatus::Started
+                && !entry.stopping
+                && Self::dependencies_healthy(entry, &healthy);
+        }
+
+        healthy
+    }
+
+    fn has_active_dependents(&self, id: ServiceId) -> bool {
+        self.services
+            .iter()
+            .any(|entry| entry.dependencies.contains(&id) && Self::is_active(entry.status))
+    }
+
+    fn dependencies_healthy(entry: &Entry<N>, healthy: &[bool; N]) -> bool {
+        entry.dependencies.iter().all(|id| healthy[*id])
+    }
+
+    fn is_active(status: ServiceStatus) -> bool {
+        matches!(
+            status,
+            ServiceStatus::Starting | ServiceStatus::Started | ServiceStatus::Stopping
+        )
+    }
+}
+
+impl<const N: usize> Default for Supervisor<N> {
+    fn default() -> Self {
+        Self::new(Default::default())
+    }
+}
+
+/// Subscribes `supervisor` to the `ServiceEvent`s posted on `bus`, timestamped with `time`; `on_update`
+/// is called with the supervisor after each of them, e.g. to wake up the task executing the actions
+/// returned by `Supervisor::poll`
+#[cfg(all(feature = "alloc", target_has_atomic = "ptr"))]
+pub fn subscribe<B, R, T, F, const N: usize>(
+    bus: &B,
+    supervisor: Arc<Mutex<R, Supervisor<N>>>,
+    time: T,
+    mut on_update: F,
+) -> Result<B::Subscription, B::Error>
+where
+    B: EventBus<ServiceEvent>,
+    R: RawMutex + Send + Sync + 'static,
+    T: SystemTime + Send + 'static,
+    F: FnMut(&mut Supervisor<N>) + Send + 'static,
+{
+    bus.subscribe(move |event| {
+        let mut supervisor = supervisor.lock();
+
+        supervisor.handle(event, Instant::now(&time));
+
+        on_update(&mut supervisor);
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_saturates() {
+        let half = Duration::from_secs(u64::MAX / 2);
+
+        let mut supervisor = Supervisor::<2>::new(RestartPolicy {
+            initial_backoff: half,
+            max_backoff: half + Duration::from_secs(1),
+            multiplier: 4,
+        });
+
+        let id = supervisor.add(&[]).unwrap();
+
+        supervisor.start();
+
+        let mut now = Instant::from(Duration::ZERO);
+
+        for deadline in [half, Duration::from_secs(u64::MAX), Duration::MAX] {
+            assert_eq!(supervisor.poll(now), Some(Action::Start(id)));
+
+            supervisor.handle(
+                &ServiceEvent {
+                    id,
+                    status: ServiceStatus::Failed,
+                },
+                now,
+            );
+
+            now = Instant::from(deadline);
+
+            assert_eq!(supervisor.next_deadline(), Some(now));
+        }
+
+        assert_eq!(supervisor.poll(now), Some(Action::Start(id)));
+    }
+}
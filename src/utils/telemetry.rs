@@ -0,0 +1,559 @@
+use core::fmt::{Debug, Write as _};
+use core::marker::PhantomData;
+
+use crate::codec::framing::{self, FrameError, LengthPrefix};
+use crate::codec::BodyCodec;
+use crate::error::impl_error;
+use crate::io::Write;
+use crate::mqtt::client::{Publish, QoS};
+use crate::storage::RawStorage;
+use crate::sys_time::{Instant, SystemTime};
+use crate::telemetry::{Backlog, Batch, BatchPolicy, Compressor, Sink};
+
+/// The sample count preceding each batch in the backlog
+const HEADER_LEN: usize = 2;
+
+/// What happened to a batch on flush
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Flush {
+    /// There was nothing to flush
+    Empty,
+    Sent,
+    /// The sink failed, or older batches are still waiting in the backlog
+    Stored,
+    /// The sink failed and the backlog did not take the batch
+    Dropped,
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum TelemetryError<C, Z, Q> {
+    CodecError(C),
+    CompressionError(Z),
+    StorageError(Q),
+}
+
+impl_error! {
+    TelemetryError<C: Debug, Z: Debug, Q: Debug> {
+        CodecError(e) => "Codec error: {e:?}"; e.error_kind(),
+        CompressionError(e) => "Compression error: {e:?}"; e.error_kind(),
+        StorageError(e) => "Storage error: {e:?}"; e.error_kind(),
+    }
+}
+
+type Error<S, C, Z, Q> =
+    TelemetryError<<C as BodyCodec<S>>::Error, <Z as Compressor>::Error, <Q as Backlog>::Error>;
+
+/// Batches samples of type `S` and delivers them to a sink.
+///
+/// Each sample is encoded with `codec` and appended to the batch with a varint length prefix. The batch is
+/// flushed as soon as one of the limits of the `BatchPolicy` is reached, ages being checked on `poll`.
+/// Flushed batches are compressed, and kept in the backlog if the sink fails, to be retried in order
+/// before any newer batch.
+///
+/// Both the batch and its compressed form should fit into `B` bytes.
+pub struct Telemetry<S, C, Z, K, Q, T, const B: usize = 1024> {
+    codec: C,
+    compressor: Z,
+    sink: K,
+    backlog: Q,
+    time: T,
+    policy: BatchPolicy,
+    batch: [u8; B],
+    len: usize,
+    samples: usize,
+    started: Option<Instant>,
+    scratch: [u8; B],
+    _sample: PhantomData<fn(&S)>,
+}
+
+impl<S, C, Z, K, Q, T, const B: usize> Telemetry<S, C, Z, K, Q, T, B>
+where
+    C: BodyCodec<S>,
+    Z: Compressor,
+    K: Sink,
+    Q: Backlog,
+    T: SystemTime,
+{
+    pub fn new(codec: C, compressor: Z, sink: K, backlog: Q, time: T, policy: BatchPolicy) -> Self {
+        Self {
+            codec,
+            compressor,
+            sink,
+            backlog,
+            time,
+            policy,
+            batch: [0; B],
+            len: 0,
+            samples: 0,
+            started: None,
+            scratch: [0; B],
+            _sample: PhantomData,
+        }
+    }
+
+    pub fn policy(&self) -> &BatchPolicy {
+        &self.policy
+    }
+
+    /// The number of samples in the current batch
+    pub fn samples(&self) -> usize {
+        self.samples
+    }
+
+    /// The number of batches waiting in the backlog
+    pub fn pending(&self) -> usize {
+        self.backlog.len()
+    }
+
+    /// Appends a sample to the current batch, flushing it if it is full.
+    ///
+    /// Since the codec does not tell a full buffer from other failures, a sample which cannot be encoded
+    /// flushes the batch once, and is then encoded again into the empty buffer.
+    pub fn record(&mut self, sample: &S) -> Result<Flush, Error<S, C, Z, Q>> {
+        let mut flush = Flush::Empty;
+
+        let len = match self.encode(sample) {
+            Ok(len) => len,
+            Err(_) if self.samples > 0 => {
+                flush = self.flush()?;
+                self.encode(sample).map_err(TelemetryError::CodecError)?
+            }
+            Err(e) => return Err(TelemetryError::CodecError(e)),
+        };
+
+        let mut prefix = [0_u8; LengthPrefix::MAX_LEN];
+        let prefix_len = LengthPrefix::Varint.encode(len, &mut prefix).unwrap();
+
+        let start = self.len + LengthPrefix::MAX_LEN;
+
+        self.batch
+            .copy_within(start..start + len, self.len + prefix_len);
+        self.batch[self.len..self.len + prefix_len].copy_from_slice(&prefix[..prefix_len]);
+
+        self.len += prefix_len + len;
+        self.samples += 1;
+
+        if self.started.is_none() {
+            self.started = Some(Instant::now(&self.time));
+        }
+
+        if self.samples >= self.policy.max_samples
+            || self.len
+                >= self
+                    .policy
+                    .max_bytes
+                    .min(B - HEADER_LEN - LengthPrefix::MAX_LEN)
+        {
+            self.flush()
+        } else {
+            Ok(flush)
+        }
+    }
+
+    /// Flushes the current batch once it is older than `BatchPolicy::max_age`, and retries the backlog.
+    /// To be called periodically.
+    pub fn poll(&mut self) -> Result<Flush, Error<S, C, Z, Q>> {
+        let expired = self
+            .started
+            .map(|started| started.elapsed(&self.time) >= self.policy.max_age)
+            .unwrap_or(false);
+
+        if expired {
+            self.flush()
+        } else {
+            self.drain()?;
+
+            Ok(Flush::Empty)
+        }
+    }
+
+    /// Compresses and sends the current batch, after the batches waiting in the backlog.
+    ///
+    /// If compression fails, the batch is discarded.
+    pub fn flush(&mut self) -> Result<Flush, Error<S, C, Z, Q>> {
+        let drained = self.drain()?;
+
+        if self.samples == 0 {
+            return Ok(Flush::Empty);
+        }
+
+        let batch_len = self.len;
+        let samples = self.samples.min(u16::MAX as _);
+
+        self.len = 0;
+        self.samples = 0;
+        self.started = None;
+
+        let (header, data) = self.scratch.split_at_mut(HEADER_LEN);
+
+        let len = self
+            .compressor
+            .compress(&self.batch[..batch_len], data)
+            .map_err(TelemetryError::CompressionError)?;
+
+        header.copy_from_slice(&(samples as u16).to_le_bytes());
+
+        let sent = drained
+            && self
+                .sink
+                .send(&Batch {
+                    data: &data[..len],
+                    samples,
+                    content_type: self.codec.content_type(),
+                    content_encoding: self.compressor.content_encoding(),
+                })
+                .is_ok();
+
+        if sent {
+            Ok(Flush::Sent)
+        } else if self
+            .backlog
+            .push(&self.scratch[..HEADER_LEN + len])
+            .map_err(TelemetryError::StorageError)?
+        {
+            Ok(Flush::Stored)
+        } else {
+            Ok(Flush::Dropped)
+        }
+    }
+
+    pub fn release(self) -> (C, Z, K, Q, T) {
+        (
+            self.codec,
+            self.compressor,
+            self.sink,
+            self.backlog,
+            self.time,
+        )
+    }
+
+    /// Encodes the sample past the room reserved for its length prefix
+    fn encode(&mut self, sample: &S) -> Result<usize, C::Error> {
+        // Leaves room for the sample count of the backlog records in the scratch buffer
+        let end = B - HEADER_LEN;
+        let start = (self.len + LengthPrefix::MAX_LEN).min(end);
+
+        self.codec.encode(sample, &mut self.batch[start..end])
+    }
+
+    /// Sends the batches waiting in the backlog, returning `false` if the sink failed
+    fn drain(&mut self) -> Result<bool, Error<S, C, Z, Q>> {
+        while !self.backlog.is_empty() {
+            let record = self
+                .backlog
+                .front(&mut self.scratch)
+                .map_err(TelemetryError::StorageError)?;
+
+            if let Some(record) = record.filter(|record| record.len() >= HEADER_LEN) {
+                let batch = Batch {
+                    data: &record[HEADER_LEN..],
+                    samples: u16::from_le_bytes([record[0], record[1]]) as _,
+                    content_type: self.codec.content_type(),
+                    content_encoding: self.compressor.content_encoding(),
+                };
+
+                if self.sink.send(&batch).is_err() {
+                    return Ok(false);
+                }
+            }
+
+            // Damaged records are dropped as well
+            self.backlog.pop().map_err(TelemetryError::StorageError)?;
+        }
+
+        Ok(true)
+    }
+}
+
+/// Publishes each batch as a single MQTT message
+pub struct MqttSink<'a, P> {
+    publisher: P,
+    topic: &'a str,
+    qos: QoS,
+}
+
+impl<'a, P> MqttSink<'a, P>
+where
+    P: Publish,
+{
+    pub fn new(publisher: P, topic: &'a str, qos: QoS) -> Self {
+        Self {
+            publisher,
+            topic,
+            qos,
+        }
+    }
+
+    pub fn release(self) -> P {
+        self.publisher
+    }
+}
+
+impl<'a, P> Sink for MqttSink<'a, P>
+where
+    P: Publish,
+{
+    type Error = P::Error;
+
+    fn send(&mut self, batch: &Batch<'_>) -> Result<(), Self::Error> {
+        self.publisher
+            .publish(self.topic, self.qos, false, batch.data)
+            .map(|_| ())
+    }
+}
+
+/// Appends each batch to a stream, e.g. a log file, as a length-prefixed frame
+pub struct WriteSink<W> {
+    writer: W,
+    prefix: LengthPrefix,
+}
+
+impl<W> WriteSink<W>
+where
+    W: Write,
+{
+    pub fn new(writer: W, prefix: LengthPrefix) -> Self {
+        Self { writer, prefix }
+    }
+
+    pub fn release(self) -> W {
+        self.writer
+    }
+}
+
+impl<W> Sink for WriteSink<W>
+where
+    W: Write,
+{
+    type Error = FrameError<W::Error>;
+
+    fn send(&mut self, batch: &Batch<'_>) -> Result<(), Self::Error> {
+        framing::write_frame(&mut self.writer, self.prefix, batch.data)?;
+
+        self.writer.flush().map_err(FrameError::IoError)
+    }
+}
+
+#[cfg(feature = "experimental")]
+pub use http::*;
+
+#[cfg(feature = "experimental")]
+mod http {
+    use core::fmt::Debug;
+
+    use crate::error::{impl_error, ErrorKind};
+    use crate::http::client::{Client, Connection};
+    use crate::http::{headers, status, Status};
+    use crate::io::Write;
+    use crate::telemetry::{Batch, Sink};
+
+    #[derive(Debug)]
+    #[cfg_attr(feature = "defmt", derive(defmt::Format))]
+    pub enum HttpSinkError<E> {
+        HttpError(E),
+        Status(u16),
+    }
+
+    impl_error! {
+        HttpSinkError<E: Display> {
+            HttpError(e) => "HTTP error: {e}"; e.error_kind(),
+            Status(status) => "Unexpected HTTP status: {status}"; ErrorKind::from_status(*status),
+        }
+    }
+
+    /// POSTs each batch to `uri`, with the `Content-Type` of the samples and the `Content-Encoding`
+    /// of the compression
+    pub struct HttpSink<'a, C> {
+        client: Client<C>,
+        uri: &'a str,
+    }
+
+    impl<'a, C> HttpSink<'a, C>
+    where
+        C: Connection,
+    {
+        pub fn new(client: Client<C>, uri: &'a str) -> Self {
+            Self { client, uri }
+        }
+
+        pub fn release(self) -> Client<C> {
+            self.client
+        }
+    }
+
+    impl<'a, C> Sink for HttpSink<'a, C>
+    where
+        C: Connection,
+    {
+        type Error = HttpSinkError<C::Error>;
+
+        fn send(&mut self, batch: &Batch<'_>) -> Result<(), Self::Error> {
+            let mut content_len_buf = headers::ContentLenParseBuf::new();
+
+            let content_type = headers::content_type(batch.content_type);
+            let content_len = headers::content_len(batch.data.len() as _, &mut content_len_buf);
+
+            let mut request_headers = heapless::Vec::<_, 3>::new();
+
+            request_headers.push(content_type).ok();
+            request_headers.push(content_len).ok();
+
+            if let Some(encoding) = batch.content_encoding {
+                request_headers
+                    .push(headers::content_encoding(encoding))
+                    .ok();
+            }
+
+            let mut request = self
+                .client
+                .post(self.uri, &request_headers)
+                .map_err(HttpSinkError::HttpError)?;
+
+            request
+                .write_all(batch.data)
+                .map_err(HttpSinkError::HttpError)?;
+            request.flush().map_err(HttpSinkError::HttpError)?;
+
+            let response = request.submit().map_err(HttpSinkError::HttpError)?;
+
+            let status = response.status();
+
+            if status::OK.contains(&status) {
+                Ok(())
+            } else {
+                Err(HttpSinkError::Status(status))
+            }
+        }
+    }
+}
+
+/// A backlog of batches in `RawStorage`, so that they survive reboots.
+///
+/// Each batch is stored as a separate blob of up to `B` bytes. Once `max_batches` are stored,
+/// the oldest batch is dropped.
+pub struct StorageBacklog<S, const B: usize = 1024> {
+    storage: S,
+    max_batches: u16,
+    head: u32,
+    tail: u32,
+}
+
+impl<S, const B: usize> StorageBacklog<S, B>
+where
+    S: RawStorage,
+{
+    const META: &'static str = "telemetry";
+
+    /// Picks up the batches stored before a reboot
+    pub fn new(storage: S, max_batches: u16) -> Result<Self, S::Error> {
+        let mut meta = [0_u8; 8];
+
+        let (head, tail) = match storage.get_raw(Self::META, &mut meta)? {
+            Some(meta) if meta.len() == 8 => (
+                u32::from_le_bytes([meta[0], meta[1], meta[2], meta[3]]),
+                u32::from_le_bytes([meta[4], meta[5], meta[6], meta[7]]),
+            ),
+            _ => (0, 0),
+        };
+
+        // The backlog might have been written with a larger `max_batches`
+        let head = head.max(tail.saturating_sub(max_batches as _));
+
+        Ok(Self {
+            storage,
+            max_batches,
+            head,
+            tail,
+        })
+    }
+
+    pub fn clear(&mut self) -> Result<(), S::Error> {
+        for sequence in self.head..self.tail {
+            self.storage.remove(&self.slot(sequence))?;
+        }
+
+        self.head = self.tail;
+
+        self.save()
+    }
+
+    pub fn release(self) -> S {
+        self.storage
+    }
+
+    fn save(&mut self) -> Result<(), S::Error> {
+        let mut meta = [0_u8; 8];
+
+        meta[..4].copy_from_slice(&self.head.to_le_bytes());
+        meta[4..].copy_from_slice(&self.tail.to_le_bytes());
+
+        self.storage.set_raw(Self::META, &meta)?;
+
+        Ok(())
+    }
+
+    fn slot(&self, sequence: u32) -> heapless::String<24> {
+        let mut name = heapless::String::new();
+
+        write!(
+            &mut name,
+            "{}_{}",
+            Self::META,
+            sequence % self.max_batches.max(1) as u32
+        )
+        .unwrap();
+
+        name
+    }
+}
+
+impl<S, const B: usize> Backlog for StorageBacklog<S, B>
+where
+    S: RawStorage,
+{
+    type Error = S::Error;
+
+    fn len(&self) -> usize {
+        (self.tail - self.head) as _
+    }
+
+    fn push(&mut self, batch: &[u8]) -> Result<bool, Self::Error> {
+        if batch.len() > B || self.max_batches == 0 {
+            return Ok(false);
+        }
+
+        if self.len() >= self.max_batches as _ {
+            self.storage.remove(&self.slot(self.head))?;
+            self.head += 1;
+        }
+
+        self.storage.set_raw(&self.slot(self.tail), batch)?;
+        self.tail += 1;
+
+        self.save()?;
+
+        Ok(true)
+    }
+
+    fn front<'b>(&self, buf: &'b mut [u8]) -> Result<Option<&'b [u8]>, Self::Error> {
+        if self.is_empty() {
+            Ok(None)
+        } else {
+            self.storage.get_raw(&self.slot(self.head), buf)
+        }
+    }
+
+    fn pop(&mut self) -> Result<(), Self::Error> {
+        if !self.is_empty() {
+            self.storage.remove(&self.slot(self.head))?;
+            self.head += 1;
+
+            self.save()?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,605 @@
+//! Local time from the UTC time of a `SystemTime` synchronized with SNTP, per POSIX TZ strings,
+//! e.g. `CET-1CEST,M3.5.0,M10.5.0/3` for Central Europe or `AEST-10AEDT,M10.1.0,M4.1.0/3` for Sydney.
+//!
+//! The TZ strings of the IANA time zones are listed in the `zones.csv` file of the
+//! [posix_tz_db](https://github.com/nayarsystems/posix_tz_db) project.
+
+use core::fmt::{self, Display};
+use core::str::FromStr;
+
+#[cfg(feature = "use_serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::sys_time::{self, DateTime, SystemTime};
+
+const MAX_OFFSET: i32 = 24 * 3600;
+
+/// When a transition between standard and daylight saving time occurs in a year
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+enum Rule {
+    /// `Jn`: day 1 to 365, February 29th not being counted
+    Julian(u16),
+    /// `n`: day 0 to 365, February 29th being counted
+    Day(u16),
+    /// `Mm.w.d`: day `d` (0 is Sunday) of week `w` (5 is the last one) of month `m`
+    Month { month: u8, week: u8, weekday: u8 },
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+struct Transition {
+    rule: Rule,
+    /// Local time of day, which may be negative or exceed 24 hours
+    time: i32,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+struct Dst {
+    name: heapless::String<8>,
+    offset: i32,
+    start: Transition,
+    end: Transition,
+}
+
+/// A time zone given as a POSIX TZ string, which (de)serializes as that string.
+///
+/// Unlike in the TZ string, offsets are reported in seconds east of UTC.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct TimeZone {
+    tz: heapless::String<64>,
+    name: heapless::String<8>,
+    offset: i32,
+    dst: Option<Dst>,
+}
+
+impl TimeZone {
+    pub fn utc() -> Self {
+        Self {
+            tz: "UTC0".into(),
+            name: "UTC".into(),
+            offset: 0,
+            dst: None,
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.tz
+    }
+
+    pub fn has_dst(&self) -> bool {
+        self.dst.is_some()
+    }
+
+    pub fn is_dst(&self, utc: u64) -> bool {
+        match &self.dst {
+            Some(dst) => {
+                let utc = utc as i64;
+                let year = DateTime::from_unix((utc + self.offset as i64).max(0) as _).year;
+
+                match (
+                    transition(year, &dst.start).map(|start| start - self.offset as i64),
+                    transition(year, &dst.end).map(|end| end - dst.offset as i64),
+                ) {
+                    (Some(start), Some(end)) if start < end => start <= utc && utc < end,
+                    // Southern hemisphere
+                    (Some(start), Some(end)) => utc < end || start <= utc,
+                    _ => false,
+                }
+            }
+            None => false,
+        }
+    }
+
+    /// The offset of the local time in seconds east of UTC
+    pub fn offset_at(&self, utc: u64) -> i32 {
+        match &self.dst {
+            Some(dst) if self.is_dst(utc) => dst.offset,
+            _ => self.offset,
+        }
+    }
+
+    /// The abbreviation of the local time, e.g. `CET` or `CEST`
+    pub fn name_at(&self, utc: u64) -> &str {
+        match &self.dst {
+            Some(dst) if self.is_dst(utc) => &dst.name,
+            _ => &self.name,
+        }
+    }
+
+    /// The local time as seconds since the Unix epoch, as if the local time were UTC
+    pub fn local(&self, utc: u64) -> u64 {
+        (utc as i64 + self.offset_at(utc) as i64).max(0) as _
+    }
+
+    pub fn to_local(&self, utc: u64) -> DateTime {
+        DateTime::from_unix(self.local(utc))
+    }
+
+    pub fn now<T>(&self, time: &T) -> DateTime
+    where
+        T: SystemTime,
+    {
+        self.to_local(time.now().as_secs())
+    }
+
+    /// The inverse of `local`.
+    ///
+    /// Local times skipped when DST starts are moved forward by the DST offset, and local times repeated when
+    /// DST ends resolve to their first occurrence.
+    pub fn utc_from_local(&self, local: u64) -> u64 {
+        let local = local as i64;
+        let standard = (local - self.offset as i64).max(0) as u64;
+
+        match &self.dst {
+            Some(dst) => {
+                let daylight = (local - dst.offset as i64).max(0) as u64;
+
+                if self.is_dst(daylight) {
+                    daylight
+                } else {
+                    standard
+                }
+            }
+            None => standard,
+        }
+    }
+
+    pub fn to_utc(&self, local: &DateTime) -> Option<u64> {
+        local.to_unix().map(|local| self.utc_from_local(local))
+    }
+}
+
+impl Default for TimeZone {
+    fn default() -> Self {
+        Self::utc()
+    }
+}
+
+impl Display for TimeZone {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.tz)
+    }
+}
+
+impl FromStr for TimeZone {
+    type Err = &'static str;
+
+    fn from_str(tz: &str) -> Result<Self, Self::Err> {
+        let tz = tz.trim();
+        let mut parser = Parser(tz.as_bytes());
+
+        let name = parser.name()?;
+        let offset = -parser
+            .offset()
+            .filter(|offset| offset.abs() <= MAX_OFFSET)
+            .ok_or("Invalid UTC offset")?;
+
+        let dst = if parser.0.is_empty() {
+            None
+        } else {
+            let dst_name = parser.name()?;
+            let dst_offset = match parser.offset() {
+                Some(offset) if offset.abs() <= MAX_OFFSET => -offset,
+                Some(_) => return Err("Invalid DST offset"),
+                None => offset + 3600,
+            };
+
+            let (start, end) = if parser.0.is_empty() {
+                // The US rules, as with glibc
+                (
+                    Transition {
+                        rule: Rule::Month {
+                            month: 3,
+                            week: 2,
+                            weekday: 0,
+                        },
+                        time: 7200,
+                    },
+                    Transition {
+                        rule: Rule::Month {
+                            month: 11,
+                            week: 1,
+                            weekday: 0,
+                        },
+                        time: 7200,
+                    },
+                )
+            } else {
+                parser.expect(b',')?;
+                let start = parser.transition()?;
+                parser.expect(b',')?;
+                let end = parser.transition()?;
+
+                (start, end)
+            };
+
+            Some(Dst {
+                name: dst_name,
+                offset: dst_offset,
+                start,
+                end,
+            })
+        };
+
+        if !parser.0.is_empty() {
+            return Err("Trailing characters in TZ string");
+        }
+
+        let mut source = heapless::String::new();
+        source.push_str(tz).map_err(|_| "TZ string too long")?;
+
+        Ok(Self {
+            tz: source,
+            name,
+            offset,
+            dst,
+        })
+    }
+}
+
+#[cfg(feature = "use_serde")]
+impl Serialize for TimeZone {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(&self.tz)
+    }
+}
+
+#[cfg(feature = "use_serde")]
+impl<'de> Deserialize<'de> for TimeZone {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        struct Visitor;
+
+        impl<'de> serde::de::Visitor<'de> for Visitor {
+            type Value = TimeZone;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "a POSIX TZ string")
+            }
+
+            fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                value.parse().map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_str(Visitor)
+    }
+}
+
+/// The local time of the transition in `year`, as seconds since the Unix epoch
+fn transition(year: u16, transition: &Transition) -> Option<i64> {
+    let jan1 = sys_time::days_from_civil(year, 1, 1)?;
+
+    let day = match transition.rule {
+        Rule::Julian(day) => {
+            let leap = DateTime::is_leap_year(year) && day >= 60;
+
+            jan1 + day as u32 - 1 + leap as u32
+        }
+        Rule::Day(day) => jan1 + day as u32,
+        Rule::Month {
+            month,
+            week,
+            weekday,
+        } => {
+            let first = sys_time::days_from_civil(year, month, 1)?;
+            let first_weekday = sys_time::weekday(first);
+
+            let mut day = 1 + (weekday + 7 - first_weekday) % 7 + (week - 1) * 7;
+            while day > sys_time::days_in_month(year, month) {
+                day -= 7;
+            }
+
+            first + day as u32 - 1
+        }
+    };
+
+    Some(day as i64 * 86400 + transition.time as i64)
+}
+
+struct Parser<'a>(&'a [u8]);
+
+impl<'a> Parser<'a> {
+    fn name(&mut self) -> Result<heapless::String<8>, &'static str> {
+        let (name, rest) = if let Some(quoted) = self.0.strip_prefix(b"<") {
+            let end = quoted
+                .iter()
+                .position(|c| *c == b'>')
+                .ok_or("Unterminated time zone name")?;
+
+            if !quoted[..end]
+                .iter()
+                .all(|c| c.is_ascii_alphanumeric() || *c == b'+' || *c == b'-')
+            {
+                return Err("Invalid time zone name");
+            }
+
+            (&quoted[..end], &quoted[end + 1..])
+        } else {
+            let end = self
+                .0
+                .iter()
+                .position(|c| !c.is_ascii_alphabetic())
+                .unwrap_or(self.0.len());
+
+            self.0.split_at(end)
+        };
+
+        if name.len() < 3 || name.len() > 8 {
+            return Err("Invalid time zone name");
+        }
+
+        self.0 = rest;
+
+        // Only ASCII characters were accepted
+        Ok(core::str::from_utf8(name).unwrap().into())
+    }
+
+    /// `[+-]hh[:mm[:ss]]` in seconds, or `None` if there is no offset
+    fn offset(&mut self) -> Option<i32> {
+        let sign = match self.0.first() {
+            Some(b'-') => -1,
+            Some(b'+') => 1,
+            _ => 0,
+        };
+
+        if sign != 0 {
+            self.0 = &self.0[1..];
+        }
+
+        self.time().map(|time| time * if sign < 0 { -1 } else { 1 })
+    }
+
+    fn time(&mut self) -> Option<i32> {
+        let hours = self.number(3)?;
+        let mut time = hours as i32 * 3600;
+
+        for factor in [60, 1] {
+            match self.0.strip_prefix(b":") {
+                Some(rest) => {
+                    self.0 = rest;
+                    time += self.number(2).filter(|value| *value < 60)? as i32 * factor;
+                }
+                None => break,
+            }
+        }
+
+        Some(time)
+    }
+
+    fn transition(&mut self) -> Result<Transition, &'static str> {
+        let rule = match self.0.first() {
+            Some(b'J') => {
+                self.0 = &self.0[1..];
+
+                Rule::Julian(
+                    self.number(3)
+                        .filter(|day| (1..=365).contains(day))
+                        .ok_or("Invalid Julian day")?,
+                )
+            }
+            Some(b'M') => {
+                self.0 = &self.0[1..];
+
+                let month = self.number(2).filter(|month| (1..=12).contains(month));
+                self.expect(b'.')?;
+                let week = self.number(1).filter(|week| (1..=5).contains(week));
+                self.expect(b'.')?;
+                let weekday = self.number(1).filter(|weekday| *weekday <= 6);
+
+                match (month, week, weekday) {
+                    (Some(month), Some(week), Some(weekday)) => Rule::Month {
+                        month: month as _,
+                        week: week as _,
+                        weekday: weekday as _,
+                    },
+                    _ => return Err("Invalid month rule"),
+                }
+            }
+            _ => Rule::Day(
+                self.number(3)
+                    .filter(|day| *day <= 365)
+                    .ok_or("Invalid day")?,
+            ),
+        };
+
+        let time = match self.0.strip_prefix(b"/") {
+            Some(rest) => {
+                self.0 = rest;
+                self.offset()
+                    .filter(|time| time.abs() < 168 * 3600)
+                    .ok_or("Invalid transition time")?
+            }
+            None => 7200,
+        };
+
+        Ok(Transition { rule, time })
+    }
+
+    fn number(&mut self, max_digits: usize) -> Option<u16> {
+        let digits = self
+            .0
+            .iter()
+            .take(max_digits)
+            .take_while(|c| c.is_ascii_digit())
+            .count();
+
+        if digits == 0 {
+            return None;
+        }
+
+        let (number, rest) = self.0.split_at(digits);
+        self.0 = rest;
+
+        Some(
+            number
+                .iter()
+                .fold(0, |value, digit| value * 10 + (digit - b'0') as u16),
+        )
+    }
+
+    fn expect(&mut self, c: u8) -> Result<(), &'static str> {
+        match self.0.strip_prefix(&[c]) {
+            Some(rest) => {
+                self.0 = rest;
+                Ok(())
+            }
+            None => Err("Invalid TZ string"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 2024-03-31T01:00:00Z and 2024-10-27T01:00:00Z
+    const CET_DST: (u64, u64) = (1_711_846_800, 1_729_990_800);
+
+    #[test]
+    fn parse() {
+        let tz: TimeZone = "CET-1CEST,M3.5.0,M10.5.0/3".parse().unwrap();
+
+        assert_eq!(tz.as_str(), "CET-1CEST,M3.5.0,M10.5.0/3");
+        assert_eq!(tz.offset, 3600);
+        assert_eq!(
+            tz.dst,
+            Some(Dst {
+                name: "CEST".into(),
+                offset: 7200,
+                start: Transition {
+                    rule: Rule::Month {
+                        month: 3,
+                        week: 5,
+                        weekday: 0
+                    },
+                    time: 7200,
+                },
+                end: Transition {
+                    rule: Rule::Month {
+                        month: 10,
+                        week: 5,
+                        weekday: 0
+                    },
+                    time: 3 * 3600,
+                },
+            })
+        );
+
+        let tz: TimeZone = "<+0545>-5:45".parse().unwrap();
+
+        assert_eq!(tz.name, "+0545");
+        assert_eq!(tz.offset, 5 * 3600 + 45 * 60);
+        assert!(!tz.has_dst());
+
+        let tz: TimeZone = "<-03>3<-02>,J60/-1:30,300".parse().unwrap();
+
+        let dst = tz.dst.unwrap();
+        assert_eq!(tz.offset, -3 * 3600);
+        assert_eq!(dst.offset, -2 * 3600);
+        assert_eq!(dst.start.rule, Rule::Julian(60));
+        assert_eq!(dst.start.time, -5400);
+        assert_eq!(dst.end.rule, Rule::Day(300));
+        assert_eq!(dst.end.time, 7200);
+    }
+
+    #[test]
+    fn invalid() {
+        for tz in [
+            "",
+            "CE-1",
+            "CET",
+            "CET-25",
+            "CET-1CEST,M3.5.0",
+            "CET-1CEST,M13.5.0,M10.5.0",
+            "CET-1CEST,M3.6.0,M10.5.0",
+            "CET-1CEST,J0,J365",
+            "<+0545-5:45",
+            "CET-1 CEST",
+        ] {
+            assert!(tz.parse::<TimeZone>().is_err(), "{}", tz);
+        }
+    }
+
+    #[test]
+    fn dst() {
+        let tz: TimeZone = "CET-1CEST,M3.5.0,M10.5.0/3".parse().unwrap();
+
+        assert!(!tz.is_dst(CET_DST.0 - 1));
+        assert!(tz.is_dst(CET_DST.0));
+        assert!(tz.is_dst(CET_DST.1 - 1));
+        assert!(!tz.is_dst(CET_DST.1));
+
+        assert_eq!(tz.name_at(CET_DST.0 - 1), "CET");
+        assert_eq!(tz.name_at(CET_DST.0), "CEST");
+        assert_eq!(tz.offset_at(CET_DST.0), 7200);
+
+        assert_eq!(
+            tz.to_local(CET_DST.0),
+            DateTime {
+                year: 2024,
+                month: 3,
+                day: 31,
+                hour: 3,
+                minute: 0,
+                second: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn southern_dst() {
+        let tz: TimeZone = "AEST-10AEDT,M10.1.0,M4.1.0/3".parse().unwrap();
+
+        // 2024-04-06T16:00:00Z and 2024-10-05T16:00:00Z
+        assert!(tz.is_dst(1_712_419_200 - 1));
+        assert!(!tz.is_dst(1_712_419_200));
+        assert!(!tz.is_dst(1_728_144_000 - 1));
+        assert!(tz.is_dst(1_728_144_000));
+    }
+
+    #[test]
+    fn us_rules() {
+        let tz: TimeZone = "EST5EDT".parse().unwrap();
+
+        // 2024-03-10T07:00:00Z and 2024-11-03T06:00:00Z
+        assert!(!tz.is_dst(1_710_054_000 - 1));
+        assert!(tz.is_dst(1_710_054_000));
+        assert!(tz.is_dst(1_730_613_600 - 1));
+        assert!(!tz.is_dst(1_730_613_600));
+        assert_eq!(tz.offset_at(1_710_054_000), -4 * 3600);
+    }
+
+    #[test]
+    fn utc_from_local() {
+        let tz: TimeZone = "CET-1CEST,M3.5.0,M10.5.0/3".parse().unwrap();
+
+        // 2024-07-01T14:00:00 CEST
+        assert_eq!(tz.utc_from_local(1_719_842_400), 1_719_835_200);
+
+        // 02:30, skipped when DST starts, is 03:30 CEST
+        assert_eq!(tz.utc_from_local(CET_DST.0 + 3600 + 1800), CET_DST.0 + 1800);
+
+        // 02:30, repeated when DST ends, is the first one, in CEST
+        assert_eq!(tz.utc_from_local(CET_DST.1 + 3600 + 1800), CET_DST.1 - 1800);
+
+        for utc in [CET_DST.0 - 1, CET_DST.0, CET_DST.1 - 1, CET_DST.1 + 3600] {
+            assert_eq!(tz.utc_from_local(tz.local(utc)), utc);
+        }
+    }
+}
@@ -0,0 +1,276 @@
+//! UUIDs (RFC 9562): random ones, and name-based ones deriving a stable device ID from its MAC address.
+//!
+//! Name-based UUIDs are hashed with SHA-256 rather than the SHA-1 of UUIDv5, as SHA-256 is what the crypto
+//! backends provide, so they are version 8 UUIDs, as specified by RFC 9562 for this purpose.
+
+use core::fmt::{self, Display};
+use core::str::FromStr;
+
+#[cfg(feature = "use_serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::crypto::{Digest, Rng, Sha256};
+use crate::ipv4::Mac;
+
+use super::codec::hex;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Uuid(pub [u8; 16]);
+
+impl Uuid {
+    pub const NIL: Self = Self([0; 16]);
+
+    /// The namespace of the device IDs derived by `device_id`
+    pub const DEVICE_NAMESPACE: Self = Self([
+        0x9b, 0xbc, 0x26, 0xbc, 0x3d, 0x9c, 0x46, 0x77, 0xbe, 0x69, 0x8e, 0xd9, 0x99, 0x26, 0x2e,
+        0x61,
+    ]);
+
+    /// A random, version 4 UUID
+    pub fn new_v4<R>(rng: &mut R) -> Result<Self, R::Error>
+    where
+        R: Rng,
+    {
+        let mut bytes = [0; 16];
+        rng.fill_bytes(&mut bytes)?;
+
+        Ok(Self::with_version(bytes, 4))
+    }
+
+    /// A version 8 UUID from the SHA-256 of `namespace` followed by `name`, the same for the same name
+    pub fn from_name<H>(mut hasher: H, namespace: &Self, name: &[u8]) -> Self
+    where
+        H: Sha256,
+    {
+        hasher.update(&namespace.0);
+        hasher.update(name);
+
+        Self::from_digest(&hasher.finish())
+    }
+
+    /// A stable ID of the device from its factory MAC address and `salt`, typically the name of the product,
+    /// so that the MAC address cannot be read back from the ID and different products of the same device
+    /// get different IDs.
+    ///
+    /// This is `from_name` in `DEVICE_NAMESPACE` with `salt` followed by `mac` as the name.
+    pub fn device_id<H>(mut hasher: H, mac: &Mac, salt: &[u8]) -> Self
+    where
+        H: Sha256,
+    {
+        hasher.update(&Self::DEVICE_NAMESPACE.0);
+        hasher.update(salt);
+        hasher.update(mac);
+
+        Self::from_digest(&hasher.finish())
+    }
+
+    pub fn version(&self) -> u8 {
+        self.0[6] >> 4
+    }
+
+    pub fn is_nil(&self) -> bool {
+        *self == Self::NIL
+    }
+
+    /// The first 12 hex digits, all random or hashed, for names with tight length limits, e.g.
+    /// `{product}-{short}` for MQTT 3.1 client IDs of at most 23 characters or mDNS instance names
+    pub fn short(&self) -> heapless::String<12> {
+        let mut short = heapless::String::new();
+
+        hex::encode(&self.0[..6], hex::LOWER, &mut short).unwrap();
+
+        short
+    }
+
+    fn from_digest(digest: &Digest) -> Self {
+        let mut bytes = [0; 16];
+        bytes.copy_from_slice(&digest[..16]);
+
+        Self::with_version(bytes, 8)
+    }
+
+    fn with_version(mut bytes: [u8; 16], version: u8) -> Self {
+        bytes[6] = (bytes[6] & 0x0f) | (version << 4);
+        // The RFC 9562 variant
+        bytes[8] = (bytes[8] & 0x3f) | 0x80;
+
+        Self(bytes)
+    }
+}
+
+/// The hyphenated form, e.g. `9bbc26bc-3d9c-4677-be69-8ed999262e61`
+impl Display for Uuid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, group) in [0..4, 4..6, 6..8, 8..10, 10..16].iter().enumerate() {
+            if index > 0 {
+                f.write_str("-")?;
+            }
+
+            hex::encode(&self.0[group.clone()], hex::LOWER, f)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Accepts the hyphenated form, in either case
+impl FromStr for Uuid {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let hyphens = s
+            .bytes()
+            .enumerate()
+            .all(|(index, byte)| (byte == b'-') == matches!(index, 8 | 13 | 18 | 23));
+
+        if s.len() != 36 || !hyphens {
+            return Err("Invalid UUID");
+        }
+
+        let mut bytes = [0; 16];
+
+        let mut decoder = hex::Decoder::new();
+        let mut len = 0;
+
+        for group in s.split('-') {
+            len += decoder
+                .update(group.as_bytes(), &mut bytes[len..])
+                .ok_or("Invalid UUID")?;
+        }
+
+        if len == 16 {
+            Ok(Self(bytes))
+        } else {
+            Err("Invalid UUID")
+        }
+    }
+}
+
+#[cfg(feature = "use_serde")]
+impl Serialize for Uuid {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "use_serde")]
+impl<'de> Deserialize<'de> for Uuid {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        struct Visitor;
+
+        impl<'de> serde::de::Visitor<'de> for Visitor {
+            type Value = Uuid;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "a hyphenated UUID")
+            }
+
+            fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                value.parse().map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_str(Visitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::crypto::Digest;
+
+    use super::*;
+
+    /// Keeps the first 32 bytes it is given, which is enough to check what is hashed
+    #[derive(Default)]
+    struct Hasher(heapless::Vec<u8, 32>);
+
+    impl Sha256 for Hasher {
+        fn update(&mut self, data: &[u8]) {
+            for byte in data {
+                let _ = self.0.push(*byte);
+            }
+        }
+
+        fn finish(self) -> Digest {
+            let mut digest = [0; 32];
+            digest[..self.0.len()].copy_from_slice(&self.0);
+
+            digest
+        }
+    }
+
+    struct Counter(u8);
+
+    impl Rng for Counter {
+        type Error = ();
+
+        fn fill_bytes(&mut self, buf: &mut [u8]) -> Result<(), Self::Error> {
+            for byte in buf {
+                *byte = self.0;
+                self.0 = self.0.wrapping_add(1);
+            }
+
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn versions() {
+        let uuid = Uuid::new_v4(&mut Counter(0xf0)).unwrap();
+
+        assert_eq!(uuid.version(), 4);
+        assert_eq!(uuid.to_string(), "f0f1f2f3-f4f5-46f7-b8f9-fafbfcfdfeff");
+
+        let uuid = Uuid::from_name(Hasher::default(), &Uuid::NIL, b"name");
+
+        assert_eq!(uuid.version(), 8);
+        assert_eq!(uuid.to_string(), "00000000-0000-8000-8000-000000000000");
+    }
+
+    #[test]
+    fn device_id() {
+        let mac = [0x24, 0x0a, 0xc4, 0x12, 0x34, 0x56];
+
+        let uuid = Uuid::device_id(Hasher::default(), &mac, b"x");
+
+        // The namespace, then the salt, then the MAC address, all past the first 16 bytes
+        assert_eq!(uuid, Uuid::with_version(Uuid::DEVICE_NAMESPACE.0, 8));
+        assert_eq!(uuid.short(), "9bbc26bc3d9c");
+    }
+
+    #[test]
+    fn parse() {
+        let uuid: Uuid = "9bbc26bc-3d9c-4677-be69-8ed999262e61".parse().unwrap();
+
+        assert_eq!(uuid, Uuid::DEVICE_NAMESPACE);
+        assert_eq!("9BBC26BC-3D9C-4677-BE69-8ED999262E61".parse(), Ok(uuid));
+        assert_eq!(uuid.to_string(), "9bbc26bc-3d9c-4677-be69-8ed999262e61");
+
+        assert!("00000000-0000-0000-0000-000000000000"
+            .parse::<Uuid>()
+            .unwrap()
+            .is_nil());
+
+        for uuid in [
+            "",
+            "9bbc26bc3d9c4677be698ed999262e61",
+            "9bbc26bc-3d9c-4677-be69-8ed999262e6",
+            "9bbc26bc-3d9c-4677-be69-8ed999262e611",
+            "9bbc26b-c3d9c-4677-be69-8ed999262e61",
+            "9bbc26bc-3d9c-4677-be69-8ed999262e6g",
+        ] {
+            assert!(uuid.parse::<Uuid>().is_err(), "{}", uuid);
+        }
+    }
+}
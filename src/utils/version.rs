@@ -0,0 +1,220 @@
+//! Semantic versions (semver 2.0) of firmware and configuration documents, e.g. `1.4.2+20240611.a1b2c3`.
+//!
+//! Pre-release versions, e.g. `1.4.2-rc.1`, are not supported: use build metadata instead, which is
+//! ordered after the version core here, rather than being ignored as for semver precedence.
+
+use core::fmt::{self, Display};
+use core::str::FromStr;
+
+#[cfg(feature = "use_serde")]
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Version {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+    /// Empty if none
+    pub build: heapless::String<24>,
+}
+
+impl Version {
+    pub const fn new(major: u32, minor: u32, patch: u32) -> Self {
+        Self {
+            major,
+            minor,
+            patch,
+            build: heapless::String::new(),
+        }
+    }
+
+    /// Returns an error if `build` is longer than 24 bytes or not made of dot-separated identifiers of
+    /// ASCII alphanumerics and hyphens
+    pub fn with_build(mut self, build: &str) -> Result<Self, &'static str> {
+        let valid = build.split('.').all(|identifier| {
+            !identifier.is_empty()
+                && identifier
+                    .bytes()
+                    .all(|byte| byte.is_ascii_alphanumeric() || byte == b'-')
+        });
+
+        self.build.clear();
+
+        if !valid || self.build.push_str(build).is_err() {
+            return Err("Invalid build metadata");
+        }
+
+        Ok(self)
+    }
+
+    pub fn build(&self) -> Option<&str> {
+        (!self.build.is_empty()).then(|| self.build.as_str())
+    }
+
+    /// Whether `self` and `other` differ only in their version core, ignoring the build metadata
+    pub fn same_core(&self, other: &Self) -> bool {
+        (self.major, self.minor, self.patch) == (other.major, other.minor, other.patch)
+    }
+
+    /// Whether data of version `other` can be handled by `self`, i.e. they share the major version and
+    /// `other` is not newer
+    pub fn is_compatible_with(&self, other: &Self) -> bool {
+        self.major == other.major && (other.minor, other.patch) <= (self.minor, self.patch)
+    }
+}
+
+impl Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)?;
+
+        if let Some(build) = self.build() {
+            write!(f, "+{build}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Accepts a leading `v`, as in git tags
+impl FromStr for Version {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.strip_prefix('v').unwrap_or(s);
+
+        let (core, build) = match s.split_once('+') {
+            Some((core, build)) => (core, Some(build)),
+            None => (s, None),
+        };
+
+        if core.contains('-') {
+            return Err("Pre-release versions are not supported");
+        }
+
+        let mut numbers = core.split('.').map(|number| {
+            let leading_zero = number.len() > 1 && number.starts_with('0');
+
+            if number.is_empty() || leading_zero || !number.bytes().all(|b| b.is_ascii_digit()) {
+                Err("Invalid version")
+            } else {
+                number.parse().map_err(|_| "Invalid version")
+            }
+        });
+
+        let major = numbers.next().ok_or("Invalid version")??;
+        let minor = numbers.next().ok_or("Invalid version")??;
+        let patch = numbers.next().ok_or("Invalid version")??;
+
+        if numbers.next().is_some() {
+            return Err("Invalid version");
+        }
+
+        let version = Self::new(major, minor, patch);
+
+        match build {
+            Some(build) => version.with_build(build),
+            None => Ok(version),
+        }
+    }
+}
+
+#[cfg(feature = "use_serde")]
+impl Serialize for Version {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "use_serde")]
+impl<'de> Deserialize<'de> for Version {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        struct Visitor;
+
+        impl<'de> serde::de::Visitor<'de> for Visitor {
+            type Value = Version;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "a semantic version")
+            }
+
+            fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                value.parse().map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_str(Visitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        assert_eq!("1.4.2".parse(), Ok(Version::new(1, 4, 2)));
+        assert_eq!("v0.10.0".parse(), Ok(Version::new(0, 10, 0)));
+
+        let version: Version = "1.4.2+20240611.a1b2c3".parse().unwrap();
+
+        assert!(version.same_core(&Version::new(1, 4, 2)));
+        assert_eq!(version.build(), Some("20240611.a1b2c3"));
+        assert_eq!(version.to_string(), "1.4.2+20240611.a1b2c3");
+        assert_eq!(Version::new(1, 4, 2).build(), None);
+        assert_eq!(Version::new(1, 4, 2).to_string(), "1.4.2");
+    }
+
+    #[test]
+    fn invalid() {
+        for version in [
+            "",
+            "1.4",
+            "1.4.2.1",
+            "01.4.2",
+            "1.4.x",
+            "1..2",
+            "1.4.2-rc.1",
+            "1.4.2+",
+            "1.4.2+a..b",
+            "1.4.2+a_b",
+            "1.4.2+1234567890123456789012345",
+            "4294967296.0.0",
+        ] {
+            assert!(version.parse::<Version>().is_err(), "{}", version);
+        }
+    }
+
+    #[test]
+    fn order() {
+        let version = Version::new(1, 4, 2);
+        let build = version.clone().with_build("1").unwrap();
+
+        assert!(Version::new(1, 4, 10) > version);
+        assert!(Version::new(2, 0, 0) > Version::new(1, 99, 99));
+        assert!(build > version);
+        assert!(build < Version::new(1, 4, 3));
+    }
+
+    #[test]
+    fn compatible() {
+        let version = Version::new(1, 4, 2);
+
+        assert!(version.is_compatible_with(&Version::new(1, 4, 2)));
+        assert!(version.is_compatible_with(&Version::new(1, 3, 9)));
+        assert!(!version.is_compatible_with(&Version::new(1, 4, 3)));
+        assert!(!version.is_compatible_with(&Version::new(1, 5, 0)));
+        assert!(!version.is_compatible_with(&Version::new(0, 1, 0)));
+        assert!(!version.is_compatible_with(&Version::new(2, 0, 0)));
+    }
+}
@@ -0,0 +1,88 @@
+//! Wake-on-LAN magic packets
+
+use crate::ipv4::{Ipv4Addr, Mac, SocketAddrV4, Subnet};
+use crate::net::udp::UdpSocket;
+
+/// The discard port, which is what most tools send to; NICs look at the payload only
+pub const PORT: u16 = 9;
+
+/// The SecureOn password some NICs require after the MAC address repetitions
+pub type Password = [u8; 6];
+
+/// Six 0xFF bytes, the MAC address 16 times, then the optional password
+pub struct MagicPacket {
+    data: [u8; 108],
+    len: usize,
+}
+
+impl MagicPacket {
+    pub fn new(mac: &Mac, password: Option<&Password>) -> Self {
+        let mut data = [0xff_u8; 108];
+
+        for chunk in data[6..102].chunks_mut(6) {
+            chunk.copy_from_slice(mac);
+        }
+
+        let len = if let Some(password) = password {
+            data[102..].copy_from_slice(password);
+
+            108
+        } else {
+            102
+        };
+
+        Self { data, len }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.data[..self.len]
+    }
+}
+
+/// Sends the magic packet waking the device with `mac` to `target`, typically a broadcast address
+pub fn wake<S>(
+    socket: &mut S,
+    mac: &Mac,
+    password: Option<&Password>,
+    target: SocketAddrV4,
+) -> Result<(), S::Error>
+where
+    S: UdpSocket,
+{
+    socket.set_broadcast(true)?;
+    socket.send_to(MagicPacket::new(mac, password).as_bytes(), target)?;
+
+    Ok(())
+}
+
+/// Wakes the device with `mac` on `subnet`, with a subnet-directed broadcast,
+/// which routers can be configured to forward unlike 255.255.255.255
+pub fn wake_subnet<S>(
+    socket: &mut S,
+    mac: &Mac,
+    password: Option<&Password>,
+    subnet: &Subnet,
+) -> Result<(), S::Error>
+where
+    S: UdpSocket,
+{
+    wake(
+        socket,
+        mac,
+        password,
+        SocketAddrV4::new(subnet.broadcast(), PORT),
+    )
+}
+
+/// Wakes the device with `mac` on the local link
+pub fn wake_local<S>(socket: &mut S, mac: &Mac, password: Option<&Password>) -> Result<(), S::Error>
+where
+    S: UdpSocket,
+{
+    wake(
+        socket,
+        mac,
+        password,
+        SocketAddrV4::new(Ipv4Addr::new(255, 255, 255, 255), PORT),
+    )
+}
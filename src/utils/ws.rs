@@ -0,0 +1,584 @@
+use core::time::Duration;
+
+use crate::error::{impl_error, ErrorKind};
+use crate::sys_time::{Instant, SystemTime};
+use crate::utils::supervisor::RestartPolicy;
+use crate::ws::client::Connector;
+use crate::ws::{ErrorType, FrameType, Receiver, Sender};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Hash))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum OverflowPolicy {
+    /// Evict the oldest queued message to make room
+    DropOldest,
+    /// Silently discard the message being sent
+    DropNewest,
+    /// Fail sending with `ClientError::QueueFull`
+    Reject,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Configuration {
+    pub reconnect: RestartPolicy,
+    /// How often to ping the server when no frame was received in the meantime
+    pub ping_interval: Duration,
+    /// The connection is considered dead when no frame arrives within this time after a ping
+    pub pong_timeout: Duration,
+    pub overflow: OverflowPolicy,
+}
+
+impl Default for Configuration {
+    fn default() -> Self {
+        Self {
+            reconnect: Default::default(),
+            ping_interval: Duration::from_secs(30),
+            pong_timeout: Duration::from_secs(10),
+            overflow: OverflowPolicy::DropOldest,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ClientError<E> {
+    IoError(E),
+    QueueFull,
+    /// The message does not fit into a queue slot
+    TooLarge,
+}
+
+impl_error! {
+    ClientError<E: Display> {
+        IoError(e) => "IO error: {e}"; e.error_kind(),
+        QueueFull => "Queue full"; ErrorKind::Unavailable,
+        TooLarge => "Message too large"; ErrorKind::InvalidInput,
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum State {
+    Connected,
+    /// Waiting for the backoff to elapse before reconnecting
+    Disconnected,
+}
+
+/// A WebSocket client which keeps a connection opened by its `Connector` alive: it pings the server,
+/// reconnects with an exponential backoff when the connection drops, and queues up to `Q` outgoing
+/// messages of up to `B` bytes each while disconnected.
+///
+/// The application is expected to call `poll` periodically (at least every `next_deadline`),
+/// and `recv` to receive the data frames; `recv` answers the pings of the server on its own.
+pub struct ReconnectingClient<C, T, const Q: usize = 8, const B: usize = 256>
+where
+    C: Connector,
+{
+    connector: C,
+    time: T,
+    configuration: Configuration,
+    connection: Option<C::Connection>,
+    queue: heapless::Deque<(FrameType, heapless::Vec<u8, B>), Q>,
+    backoff: Duration,
+    reconnect_at: Option<Instant>,
+    last_received: Instant,
+    ping_sent: Option<Instant>,
+}
+
+impl<C, T, const Q: usize, const B: usize> ReconnectingClient<C, T, Q, B>
+where
+    C: Connector,
+    T: SystemTime,
+{
+    /// Connects on the first `poll`
+    pub fn new(connector: C, time: T, configuration: Configuration) -> Self {
+        let now = Instant::now(&time);
+
+        Self {
+            connector,
+            time,
+            backoff: configuration.reconnect.initial_backoff,
+            configuration,
+            connection: None,
+            queue: heapless::Deque::new(),
+            reconnect_at: Some(now),
+            last_received: now,
+            ping_sent: None,
+        }
+    }
+
+    pub fn state(&self) -> State {
+        if self.connection.is_some() {
+            State::Connected
+        } else {
+            State::Disconnected
+        }
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.state() == State::Connected
+    }
+
+    pub fn queued(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// When `poll` should be called next at the latest
+    pub fn next_deadline(&self) -> Option<Instant> {
+        if self.connection.is_some() {
+            Some(match self.ping_sent {
+                Some(ping_sent) => ping_sent + self.configuration.pong_timeout,
+                None => self.last_received + self.configuration.ping_interval,
+            })
+        } else {
+            self.reconnect_at
+        }
+    }
+
+    /// Reconnects when due, pings the server and sends the queued messages.
+    ///
+    /// Errors are returned after the connection is dropped and the reconnection scheduled.
+    pub fn poll(&mut self) -> Result<State, ClientError<C::Error>> {
+        let now = Instant::now(&self.time);
+
+        if self.connection.is_none() {
+            match self.reconnect_at {
+                Some(reconnect_at) if now >= reconnect_at => match self.connector.connect() {
+                    Ok(connection) => {
+                        self.connection = Some(connection);
+                        self.backoff = self.configuration.reconnect.initial_backoff;
+                        self.reconnect_at = None;
+                        self.last_received = now;
+                        self.ping_sent = None;
+                    }
+                    Err(e) => {
+                        self.disconnect(now);
+                        return Err(ClientError::IoError(e));
+                    }
+                },
+                _ => return Ok(State::Disconnected),
+            }
+        }
+
+        match self.ping_sent {
+            Some(ping_sent) if now.duration_since(ping_sent) >= self.configuration.pong_timeout => {
+                self.disconnect(now);
+                return Ok(State::Disconnected);
+            }
+            None if now.duration_since(self.last_received) >= self.configuration.ping_interval => {
+                self.transmit(FrameType::Ping, &[], now)?;
+                self.ping_sent = Some(now);
+            }
+            _ => (),
+        }
+
+        self.flush(now)?;
+
+        Ok(self.state())
+    }
+
+    /// Sends right away when connected and nothing is queued, and queues the message otherwise
+    pub fn send(
+        &mut self,
+        frame_type: FrameType,
+        frame_data: &[u8],
+    ) -> Result<(), ClientError<C::Error>> {
+        let now = Instant::now(&self.time);
+
+        if self.connection.is_some() && self.queue.is_empty() {
+            return self.transmit(frame_type, frame_data, now);
+        }
+
+        let message = heapless::Vec::from_slice(frame_data).map_err(|_| ClientError::TooLarge)?;
+
+        if self.queue.is_full() {
+            match self.configuration.overflow {
+                OverflowPolicy::DropOldest => {
+                    self.queue.pop_front();
+                }
+                OverflowPolicy::DropNewest => return Ok(()),
+                OverflowPolicy::Reject => return Err(ClientError::QueueFull),
+            }
+        }
+
+        self.queue.push_back((frame_type, message)).ok();
+
+        if self.connection.is_some() {
+            self.flush(now)?;
+        }
+
+        Ok(())
+    }
+
+    /// Receives the next data frame; returns `None` when disconnected, or when the server
+    /// sent a control frame, which is handled here
+    pub fn recv(
+        &mut self,
+        frame_data_buf: &mut [u8],
+    ) -> Result<Option<(FrameType, usize)>, ClientError<C::Error>> {
+        let result = match self.connection.as_mut() {
+            Some(connection) => connection.recv(frame_data_buf),
+            None => return Ok(None),
+        };
+
+        let now = Instant::now(&self.time);
+
+        match result {
+            Ok((frame_type, len)) => {
+                self.last_received = now;
+                self.ping_sent = None;
+
+                match frame_type {
+                    FrameType::Ping => {
+                        self.transmit(FrameType::Pong, &frame_data_buf[..len], now)?;
+                        Ok(None)
+                    }
+                    FrameType::Pong => Ok(None),
+                    FrameType::Close | FrameType::SocketClose => {
+                        self.disconnect(now);
+                        Ok(None)
+                    }
+                    _ => Ok(Some((frame_type, len))),
+                }
+            }
+            Err(e) => {
+                self.disconnect(now);
+                Err(ClientError::IoError(e))
+            }
+        }
+    }
+
+    /// Closes the connection; the client reconnects on the next `poll` once the backoff elapsed
+    pub fn close(&mut self) -> Result<(), ClientError<C::Error>> {
+        let now = Instant::now(&self.time);
+
+        let result = match self.connection.as_mut() {
+            Some(connection) => connection.send(FrameType::Close, &[]),
+            None => Ok(()),
+        };
+
+        self.disconnect(now);
+
+        result.map_err(ClientError::IoError)
+    }
+
+    pub fn release(self) -> (C, T) {
+        (self.connector, self.time)
+    }
+
+    fn flush(&mut self, now: Instant) -> Result<(), ClientError<C::Error>> {
+        while let Some((frame_type, message)) = self.queue.front() {
+            let frame_type = *frame_type;
+
+            let result = match self.connection.as_mut() {
+                Some(connection) => connection.send(frame_type, message),
+                None => return Ok(()),
+            };
+
+            if let Err(e) = result {
+                // The message stays queued, to be sent again after reconnecting
+                self.disconnect(now);
+                return Err(ClientError::IoError(e));
+            }
+
+            self.queue.pop_front();
+        }
+
+        Ok(())
+    }
+
+    fn transmit(
+        &mut self,
+        frame_type: FrameType,
+        frame_data: &[u8],
+        now: Instant,
+    ) -> Result<(), ClientError<C::Error>> {
+        let result = match self.connection.as_mut() {
+            Some(connection) => connection.send(frame_type, frame_data),
+            None => return Ok(()),
+        };
+
+        result.map_err(|e| {
+            self.disconnect(now);
+            ClientError::IoError(e)
+        })
+    }
+
+    fn disconnect(&mut self, now: Instant) {
+        let policy = &self.configuration.reconnect;
+
+        self.connection = None;
+        self.ping_sent = None;
+        self.reconnect_at = Some(now + self.backoff);
+        self.backoff = self
+            .backoff
+            .checked_mul(policy.multiplier)
+            .unwrap_or(policy.max_backoff)
+            .min(policy.max_backoff);
+    }
+}
+
+impl<C, T, const Q: usize, const B: usize> ErrorType for ReconnectingClient<C, T, Q, B>
+where
+    C: Connector,
+{
+    type Error = ClientError<C::Error>;
+}
+
+impl<C, T, const Q: usize, const B: usize> Sender for ReconnectingClient<C, T, Q, B>
+where
+    C: Connector,
+    T: SystemTime,
+{
+    fn send(&mut self, frame_type: FrameType, frame_data: &[u8]) -> Result<(), Self::Error> {
+        ReconnectingClient::send(self, frame_type, frame_data)
+    }
+}
+
+struct Session<K, S> {
+    key: K,
+    sender: S,
+    last_seen: Instant,
+}
+
+/// Tracks up to `N` server-side WebSocket sessions, e.g. the viewers of a live dashboard,
+/// keyed by e.g. the `callback_server::SessionProvider::Session` of their connection and
+/// holding the detached `Sender` created for it by a `callback_server::SenderFactory`.
+///
+/// Sessions are evicted when sending to them fails, and by `evict_idle` when no activity
+/// was reported with `touch` during the idle timeout; `ping_idle` helps keeping active sessions alive.
+pub struct SessionRegistry<K, S, T, const N: usize = 8> {
+    sessions: heapless::Vec<Session<K, S>, N>,
+    time: T,
+    idle_timeout: Duration,
+}
+
+impl<K, S, T, const N: usize> SessionRegistry<K, S, T, N>
+where
+    K: PartialEq,
+    S: Sender,
+    T: SystemTime,
+{
+    pub fn new(time: T, idle_timeout: Duration) -> Self {
+        Self {
+            sessions: heapless::Vec::new(),
+            time,
+            idle_timeout,
+        }
+    }
+
+    /// Replaces the sender of an already registered session
+    pub fn add(&mut self, key: K, sender: S) -> Result<(), &'static str> {
+        let last_seen = Instant::now(&self.time);
+
+        if let Some(session) = self.sessions.iter_mut().find(|session| session.key == key) {
+            session.sender = sender;
+            session.last_seen = last_seen;
+
+            Ok(())
+        } else {
+            self.sessions
+                .push(Session {
+                    key,
+                    sender,
+                    last_seen,
+                })
+                .map_err(|_| "Too many sessions")
+        }
+    }
+
+    pub fn remove(&mut self, key: &K) -> Option<S> {
+        let index = self
+            .sessions
+            .iter()
+            .position(|session| session.key == *key)?;
+
+        Some(self.sessions.swap_remove(index).sender)
+    }
+
+    /// Records activity on the session, e.g. a received frame
+    pub fn touch(&mut self, key: &K) -> bool {
+        let now = Instant::now(&self.time);
+
+        match self.sessions.iter_mut().find(|session| session.key == *key) {
+            Some(session) => {
+                session.last_seen = now;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn contains(&self, key: &K) -> bool {
+        self.sessions.iter().any(|session| session.key == *key)
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &K> {
+        self.sessions.iter().map(|session| &session.key)
+    }
+
+    pub fn len(&self) -> usize {
+        self.sessions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sessions.is_empty()
+    }
+
+    /// Evicts the session if sending fails
+    pub fn send(
+        &mut self,
+        key: &K,
+        frame_type: FrameType,
+        frame_data: &[u8],
+    ) -> Result<(), S::Error> {
+        if let Some(index) = self.sessions.iter().position(|session| session.key == *key) {
+            if let Err(e) = self.sessions[index].sender.send(frame_type, frame_data) {
+                self.sessions.swap_remove(index);
+
+                return Err(e);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Sends the frame to all sessions; returns to how many it was sent
+    pub fn broadcast(&mut self, frame_type: FrameType, frame_data: &[u8]) -> usize {
+        self.broadcast_filtered(frame_type, frame_data, |_| true)
+    }
+
+    /// Sends the frame to the sessions for which `filter` returns `true`, evicting those
+    /// it cannot be sent to; returns to how many it was sent
+    pub fn broadcast_filtered<F>(
+        &mut self,
+        frame_type: FrameType,
+        frame_data: &[u8],
+        filter: F,
+    ) -> usize
+    where
+        F: Fn(&K) -> bool,
+    {
+        let mut sent = 0;
+
+        self.sessions.retain_mut(|session| {
+            if !filter(&session.key) {
+                true
+            } else if session.sender.send(frame_type, frame_data).is_ok() {
+                sent += 1;
+                true
+            } else {
+                false
+            }
+        });
+
+        sent
+    }
+
+    /// Pings the sessions idle for more than half of the idle timeout, so that the responses
+    /// of the live ones can be reported with `touch` before they are evicted
+    pub fn ping_idle(&mut self) -> usize {
+        let now = Instant::now(&self.time);
+        let threshold = self.idle_timeout / 2;
+
+        let mut sent = 0;
+
+        self.sessions.retain_mut(|session| {
+            if now.duration_since(session.last_seen) <= threshold {
+                true
+            } else if session.sender.send(FrameType::Ping, &[]).is_ok() {
+                sent += 1;
+                true
+            } else {
+                false
+            }
+        });
+
+        sent
+    }
+
+    /// Evicts the sessions without activity during the idle timeout; returns how many were evicted
+    pub fn evict_idle(&mut self) -> usize {
+        let now = Instant::now(&self.time);
+        let idle_timeout = self.idle_timeout;
+
+        let len = self.sessions.len();
+
+        self.sessions
+            .retain(|session| now.duration_since(session.last_seen) < idle_timeout);
+
+        len - self.sessions.len()
+    }
+
+    pub fn release(self) -> T {
+        self.time
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::Cell;
+
+    use super::*;
+
+    struct Unreachable;
+
+    impl ErrorType for Unreachable {
+        type Error = &'static str;
+    }
+
+    impl Sender for Unreachable {
+        fn send(&mut self, _frame_type: FrameType, _frame_data: &[u8]) -> Result<(), Self::Error> {
+            Ok(())
+        }
+    }
+
+    impl Receiver for Unreachable {
+        fn recv(&mut self, _frame_data_buf: &mut [u8]) -> Result<(FrameType, usize), Self::Error> {
+            Ok((FrameType::Pong, 0))
+        }
+    }
+
+    impl Connector for Unreachable {
+        type Connection = Self;
+
+        fn connect(&mut self) -> Result<Self::Connection, Self::Error> {
+            Err("Unreachable")
+        }
+    }
+
+    struct Clock<'a>(&'a Cell<Duration>);
+
+    impl<'a> SystemTime for Clock<'a> {
+        fn now(&self) -> Duration {
+            self.0.get()
+        }
+    }
+
+    #[test]
+    fn backoff_saturates() {
+        let half = Duration::from_secs(u64::MAX / 2);
+
+        let now = Cell::new(Duration::ZERO);
+
+        let mut client = ReconnectingClient::<_, _, 1, 1>::new(
+            Unreachable,
+            Clock(&now),
+            Configuration {
+                reconnect: RestartPolicy {
+                    initial_backoff: half,
+                    max_backoff: half + Duration::from_secs(1),
+                    multiplier: 4,
+                },
+                ..Default::default()
+            },
+        );
+
+        for deadline in [half, Duration::from_secs(u64::MAX)] {
+            assert!(client.poll().is_err());
+            assert_eq!(client.next_deadline(), Some(Instant::from(deadline)));
+
+            now.set(deadline);
+        }
+    }
+}
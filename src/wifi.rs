@@ -6,6 +6,9 @@ extern crate alloc;
 
 use enumset::*;
 
+use crate::error::PartialError;
+use crate::ipv4;
+
 #[cfg(feature = "use_serde")]
 use serde::{Deserialize, Serialize};
 
@@ -15,6 +18,9 @@ use strum_macros::{Display, EnumIter, EnumMessage, EnumString, EnumVariantNames,
 #[cfg(feature = "use_numenum")]
 use num_enum::TryFromPrimitive;
 
+pub type Ssid = heapless::String<32>;
+pub type Password = heapless::String<64>;
+
 #[derive(EnumSetType, Debug, PartialOrd)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
@@ -137,11 +143,53 @@ impl Default for SecondaryChannel {
     }
 }
 
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+#[cfg_attr(
+    feature = "use_strum",
+    derive(EnumString, Display, EnumMessage, EnumIter, EnumVariantNames, FromRepr)
+)]
+#[cfg_attr(feature = "use_numenum", derive(TryFromPrimitive))]
+#[cfg_attr(feature = "use_numenum", repr(u8))]
+pub enum Bandwidth {
+    #[cfg_attr(feature = "use_strum", strum(serialize = "20mhz", message = "20 MHz"))]
+    Mhz20,
+    #[cfg_attr(feature = "use_strum", strum(serialize = "40mhz", message = "40 MHz"))]
+    Mhz40,
+}
+
+impl Default for Bandwidth {
+    fn default() -> Self {
+        Bandwidth::Mhz20
+    }
+}
+
+fn default_protocols() -> EnumSet<Protocol> {
+    Protocol::P802D11B | Protocol::P802D11BG | Protocol::P802D11BGN
+}
+
+fn validate_protocols(
+    protocols: EnumSet<Protocol>,
+    bandwidth: Bandwidth,
+) -> Result<(), &'static str> {
+    if protocols.is_empty() {
+        Err("At least one protocol should be selected")
+    } else if bandwidth == Bandwidth::Mhz40
+        && !protocols.contains(Protocol::P802D11BGN)
+        && !protocols.contains(Protocol::P802D11BGNLR)
+    {
+        Err("40 MHz bandwidth requires an 802.11N protocol")
+    } else {
+        Ok(())
+    }
+}
+
 #[derive(Clone, Debug, Default, PartialEq, Eq)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
 pub struct AccessPointInfo {
-    pub ssid: heapless::String<32>,
+    pub ssid: Ssid,
     pub bssid: [u8; 6],
     pub channel: u8,
     pub secondary_channel: SecondaryChannel,
@@ -151,19 +199,87 @@ pub struct AccessPointInfo {
     pub auth_method: AuthMethod,
 }
 
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub struct StationInfo {
+    pub mac: [u8; 6],
+    pub signal_strength: i8,
+    #[cfg_attr(feature = "defmt", defmt(Debug2Format))]
+    pub ip: Option<ipv4::Ipv4Addr>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+#[cfg_attr(
+    feature = "use_strum",
+    derive(EnumString, Display, EnumMessage, EnumIter, EnumVariantNames, FromRepr)
+)]
+#[cfg_attr(feature = "use_numenum", derive(TryFromPrimitive))]
+#[cfg_attr(feature = "use_numenum", repr(u8))]
+pub enum CountryPolicy {
+    /// Adopt the country information advertised by the access point the client is connected to
+    #[cfg_attr(feature = "use_strum", strum(serialize = "auto", message = "Auto"))]
+    Auto,
+    /// Always use the configured country information
+    #[cfg_attr(feature = "use_strum", strum(serialize = "manual", message = "Manual"))]
+    Manual,
+}
+
+impl Default for CountryPolicy {
+    fn default() -> Self {
+        CountryPolicy::Auto
+    }
+}
+
+/// Regulatory domain settings.
+///
+/// These are global to the radio. When both a client and an access point configuration
+/// carry a country configuration, the one of the access point takes precedence.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub struct CountryConfiguration {
+    /// ISO 3166-1 alpha-2 code, or "01" for the world-safe mode
+    pub country_code: heapless::String<2>,
+    pub start_channel: u8,
+    pub channel_count: u8,
+    /// Maximum transmit power, in dBm
+    pub max_tx_power: i8,
+    pub policy: CountryPolicy,
+}
+
+impl Default for CountryConfiguration {
+    fn default() -> Self {
+        Self {
+            country_code: "01".into(),
+            start_channel: 1,
+            channel_count: 11,
+            max_tx_power: 20,
+            policy: Default::default(),
+        }
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
 pub struct AccessPointConfiguration {
-    pub ssid: heapless::String<32>,
+    pub ssid: Ssid,
     pub ssid_hidden: bool,
     pub channel: u8,
     pub secondary_channel: Option<u8>,
     #[cfg_attr(feature = "defmt", defmt(Debug2Format))]
+    #[cfg_attr(feature = "use_serde", serde(default = "default_protocols"))]
     pub protocols: EnumSet<Protocol>,
+    #[cfg_attr(feature = "use_serde", serde(default))]
+    pub bandwidth: Bandwidth,
     pub auth_method: AuthMethod,
-    pub password: heapless::String<64>,
+    pub password: Password,
     pub max_connections: u16,
+    #[cfg_attr(feature = "use_serde", serde(default))]
+    pub country: Option<CountryConfiguration>,
 }
 
 impl Default for AccessPointConfiguration {
@@ -173,24 +289,75 @@ impl Default for AccessPointConfiguration {
             ssid_hidden: false,
             channel: 1,
             secondary_channel: None,
-            protocols: Protocol::P802D11B | Protocol::P802D11BG | Protocol::P802D11BGN,
+            protocols: default_protocols(),
+            bandwidth: Default::default(),
             auth_method: AuthMethod::None,
             password: "".into(),
             max_connections: 255,
+            country: None,
         }
     }
 }
 
+impl AccessPointConfiguration {
+    pub fn validate(&self) -> Result<(), &'static str> {
+        validate_protocols(self.protocols, self.bandwidth)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+#[cfg_attr(
+    feature = "use_strum",
+    derive(EnumString, Display, EnumMessage, EnumIter, EnumVariantNames, FromRepr)
+)]
+#[cfg_attr(feature = "use_numenum", derive(TryFromPrimitive))]
+#[cfg_attr(feature = "use_numenum", repr(u8))]
+pub enum PowerSave {
+    #[cfg_attr(feature = "use_strum", strum(serialize = "none", message = "None"))]
+    None,
+    /// The modem wakes up for every DTIM beacon
+    #[cfg_attr(
+        feature = "use_strum",
+        strum(serialize = "minmodem", message = "Minimum Modem Sleep")
+    )]
+    MinModem,
+    /// The modem wakes up every `ClientConfiguration::listen_interval` beacons
+    #[cfg_attr(
+        feature = "use_strum",
+        strum(serialize = "maxmodem", message = "Maximum Modem Sleep")
+    )]
+    MaxModem,
+}
+
+impl Default for PowerSave {
+    fn default() -> Self {
+        PowerSave::MinModem
+    }
+}
+
 #[derive(Clone, PartialEq, Eq)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
 pub struct ClientConfiguration {
-    pub ssid: heapless::String<32>,
+    pub ssid: Ssid,
     pub bssid: Option<[u8; 6]>,
-    //pub protocol: Protocol,
+    #[cfg_attr(feature = "defmt", defmt(Debug2Format))]
+    #[cfg_attr(feature = "use_serde", serde(default = "default_protocols"))]
+    pub protocols: EnumSet<Protocol>,
+    #[cfg_attr(feature = "use_serde", serde(default))]
+    pub bandwidth: Bandwidth,
     pub auth_method: AuthMethod,
-    pub password: heapless::String<64>,
+    pub password: Password,
     pub channel: Option<u8>,
+    #[cfg_attr(feature = "use_serde", serde(default))]
+    pub country: Option<CountryConfiguration>,
+    #[cfg_attr(feature = "use_serde", serde(default))]
+    pub power_save: PowerSave,
+    /// In beacon intervals; only relevant with `PowerSave::MaxModem`
+    #[cfg_attr(feature = "use_serde", serde(default = "default_listen_interval"))]
+    pub listen_interval: u16,
 }
 
 impl Debug for ClientConfiguration {
@@ -198,8 +365,13 @@ impl Debug for ClientConfiguration {
         f.debug_struct("ClientConfiguration")
             .field("ssid", &self.ssid)
             .field("bssid", &self.bssid)
+            .field("protocols", &self.protocols)
+            .field("bandwidth", &self.bandwidth)
             .field("auth_method", &self.auth_method)
             .field("channel", &self.channel)
+            .field("country", &self.country)
+            .field("power_save", &self.power_save)
+            .field("listen_interval", &self.listen_interval)
             .finish()
     }
 }
@@ -209,13 +381,28 @@ impl Default for ClientConfiguration {
         ClientConfiguration {
             ssid: "".into(),
             bssid: None,
+            protocols: default_protocols(),
+            bandwidth: Default::default(),
             auth_method: Default::default(),
             password: "".into(),
             channel: None,
+            country: None,
+            power_save: Default::default(),
+            listen_interval: default_listen_interval(),
         }
     }
 }
 
+fn default_listen_interval() -> u16 {
+    3
+}
+
+impl ClientConfiguration {
+    pub fn validate(&self) -> Result<(), &'static str> {
+        validate_protocols(self.protocols, self.bandwidth)
+    }
+}
+
 #[derive(EnumSetType, Debug, PartialOrd)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
@@ -238,6 +425,179 @@ pub enum Capability {
         strum(serialize = "mixed", message = "Client & Access Point")
     )]
     Mixed,
+    #[cfg_attr(
+        feature = "use_strum",
+        strum(serialize = "csi", message = "Channel State Information")
+    )]
+    Csi,
+    #[cfg_attr(
+        feature = "use_strum",
+        strum(serialize = "sniffer", message = "Sniffer")
+    )]
+    Sniffer,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub struct CsiConfiguration {
+    pub lltf: bool,
+    pub htltf: bool,
+    pub stbc_htltf: bool,
+    pub ltf_merge: bool,
+    pub channel_filter: bool,
+}
+
+impl Default for CsiConfiguration {
+    fn default() -> Self {
+        Self {
+            lltf: true,
+            htltf: true,
+            stbc_htltf: true,
+            ltf_merge: true,
+            channel_filter: true,
+        }
+    }
+}
+
+/// Channel state information captured from a single received frame.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct CsiFrame<'a> {
+    /// Source MAC address of the frame
+    pub mac: [u8; 6],
+    pub signal_strength: i8,
+    pub channel: u8,
+    pub secondary_channel: SecondaryChannel,
+    /// Receive timestamp, in microseconds
+    pub timestamp: u32,
+    /// Pairs of imaginary and real parts, one pair per subcarrier
+    pub data: &'a [i8],
+}
+
+#[derive(EnumSetType, Debug, PartialOrd)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+#[cfg_attr(
+    feature = "use_strum",
+    derive(EnumString, Display, EnumMessage, EnumIter, EnumVariantNames, FromRepr)
+)]
+#[cfg_attr(feature = "use_numenum", derive(TryFromPrimitive))]
+#[cfg_attr(feature = "use_numenum", repr(u8))]
+pub enum FrameType {
+    #[cfg_attr(
+        feature = "use_strum",
+        strum(serialize = "management", message = "Management")
+    )]
+    Management,
+    #[cfg_attr(
+        feature = "use_strum",
+        strum(serialize = "control", message = "Control")
+    )]
+    Control,
+    #[cfg_attr(feature = "use_strum", strum(serialize = "data", message = "Data"))]
+    Data,
+    #[cfg_attr(
+        feature = "use_strum",
+        strum(serialize = "extension", message = "Extension")
+    )]
+    Extension,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub struct SnifferConfiguration {
+    pub channel: u8,
+    pub secondary_channel: SecondaryChannel,
+    /// Only frames of these types are passed to the sniffer callback
+    #[cfg_attr(feature = "defmt", defmt(Debug2Format))]
+    pub filter: EnumSet<FrameType>,
+}
+
+impl Default for SnifferConfiguration {
+    fn default() -> Self {
+        Self {
+            channel: 1,
+            secondary_channel: Default::default(),
+            filter: FrameType::Management | FrameType::Data,
+        }
+    }
+}
+
+/// The MAC header of an 802.11 frame.
+///
+/// Control frames carry fewer addresses and no sequence control field, hence the optional fields.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub struct FrameHeader {
+    pub frame_type: FrameType,
+    pub subtype: u8,
+    pub to_ds: bool,
+    pub from_ds: bool,
+    pub duration: u16,
+    pub addr1: [u8; 6],
+    pub addr2: Option<[u8; 6]>,
+    pub addr3: Option<[u8; 6]>,
+    pub sequence_number: Option<u16>,
+}
+
+impl FrameHeader {
+    pub fn parse(frame: &[u8]) -> Result<Self, &'static str> {
+        if frame.len() < 10 {
+            return Err("Frame too short");
+        }
+
+        if frame[0] & 0x03 != 0 {
+            return Err("Unsupported protocol version");
+        }
+
+        let frame_type = match (frame[0] >> 2) & 0x03 {
+            0 => FrameType::Management,
+            1 => FrameType::Control,
+            2 => FrameType::Data,
+            _ => FrameType::Extension,
+        };
+
+        let addr = |offset: usize| {
+            frame.get(offset..offset + 6).map(|slice| {
+                let mut addr = [0; 6];
+                addr.copy_from_slice(slice);
+
+                addr
+            })
+        };
+
+        let full = frame.len() >= 24 && frame_type != FrameType::Control;
+
+        Ok(Self {
+            frame_type,
+            subtype: frame[0] >> 4,
+            to_ds: frame[1] & 0x01 != 0,
+            from_ds: frame[1] & 0x02 != 0,
+            duration: u16::from_le_bytes([frame[2], frame[3]]),
+            addr1: addr(4).unwrap(),
+            addr2: addr(10),
+            addr3: if full { addr(16) } else { None },
+            sequence_number: if full {
+                Some(u16::from_le_bytes([frame[22], frame[23]]) >> 4)
+            } else {
+                None
+            },
+        })
+    }
+}
+
+/// A frame received while the sniffer is enabled.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SnifferFrame<'a> {
+    pub header: FrameHeader,
+    pub signal_strength: i8,
+    pub channel: u8,
+    /// The complete frame, including the MAC header
+    pub data: &'a [u8],
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -265,6 +625,27 @@ impl Configuration {
         }
     }
 
+    pub fn validate(&self) -> Result<(), &'static str> {
+        if let Some(client_conf) = self.as_client_conf_ref() {
+            client_conf.validate()?;
+        }
+
+        if let Some(ap_conf) = self.as_ap_conf_ref() {
+            ap_conf.validate()?;
+        }
+
+        Ok(())
+    }
+
+    pub fn country(&self) -> Option<&CountryConfiguration> {
+        self.as_ap_conf_ref()
+            .and_then(|ap_conf| ap_conf.country.as_ref())
+            .or_else(|| {
+                self.as_client_conf_ref()
+                    .and_then(|client_conf| client_conf.country.as_ref())
+            })
+    }
+
     pub fn as_client_conf_mut(&mut self) -> &mut ClientConfiguration {
         match self {
             Self::Client(client_conf) => client_conf,
@@ -344,6 +725,120 @@ impl Default for Configuration {
     }
 }
 
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+#[cfg_attr(
+    feature = "use_strum",
+    derive(EnumString, Display, EnumMessage, EnumIter, EnumVariantNames, FromRepr)
+)]
+#[cfg_attr(feature = "use_numenum", derive(TryFromPrimitive))]
+#[cfg_attr(feature = "use_numenum", repr(u8))]
+pub enum WpsMode {
+    #[cfg_attr(
+        feature = "use_strum",
+        strum(serialize = "pushbutton", message = "Push Button")
+    )]
+    PushButton,
+    #[cfg_attr(feature = "use_strum", strum(serialize = "pin", message = "PIN"))]
+    Pin,
+}
+
+impl Default for WpsMode {
+    fn default() -> Self {
+        WpsMode::PushButton
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub struct WpsConfiguration {
+    pub mode: WpsMode,
+    pub manufacturer: heapless::String<64>,
+    pub model_number: heapless::String<32>,
+    pub model_name: heapless::String<32>,
+    pub device_name: heapless::String<32>,
+}
+
+/// Events reported by the driver while a WPS session started with `Wifi::start_wps` is in progress.
+///
+/// Backends deliver these over their `event_bus::EventBus<WpsEvent>` implementation.
+#[allow(clippy::large_enum_variant)]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub enum WpsEvent {
+    /// The registrar sent the credentials of one or more access points
+    Success(heapless::Vec<ClientConfiguration, 3>),
+    /// PIN mode only: the PIN which should be entered on the registrar
+    Pin(heapless::String<8>),
+    Timeout,
+    /// Push-button mode only: more than one registrar was found in push-button mode
+    Overlap,
+    Failed,
+}
+
+/// Events reported by the driver while an RSSI monitor started with `Wifi::start_rssi_monitor` is
+/// active. The payload is the signal strength which triggered the event, in dBm.
+///
+/// Backends deliver these over their `event_bus::EventBus<RssiEvent>` implementation.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub enum RssiEvent {
+    /// The signal strength dropped below the threshold
+    Low(i8),
+    /// The signal strength recovered above the threshold plus the hysteresis
+    Recovered(i8),
+}
+
+/// Threshold tracking for backends which have to implement `Wifi::start_rssi_monitor`
+/// by sampling the signal strength themselves.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub struct RssiMonitor {
+    threshold: i8,
+    hysteresis: u8,
+    low: bool,
+}
+
+impl RssiMonitor {
+    pub const fn new(threshold: i8, hysteresis: u8) -> Self {
+        Self {
+            threshold,
+            hysteresis,
+            low: false,
+        }
+    }
+
+    pub fn is_low(&self) -> bool {
+        self.low
+    }
+
+    pub fn update(&mut self, signal_strength: i8) -> Option<RssiEvent> {
+        if self.low {
+            if signal_strength as i16 >= self.threshold as i16 + self.hysteresis as i16 {
+                self.low = false;
+
+                Some(RssiEvent::Recovered(signal_strength))
+            } else {
+                None
+            }
+        } else if signal_strength < self.threshold {
+            self.low = true;
+
+            Some(RssiEvent::Low(signal_strength))
+        } else {
+            None
+        }
+    }
+}
+
+/// The operations after `scan` are optional: their default implementations fail with
+/// `PartialError::Unsupported`, so that implementations which predate them, or which run on
+/// hardware without the feature, need not implement them.
 pub trait Wifi {
     type Error: Debug;
 
@@ -368,6 +863,83 @@ pub trait Wifi {
 
     #[cfg(feature = "alloc")]
     fn scan(&mut self) -> Result<alloc::vec::Vec<AccessPointInfo>, Self::Error>;
+
+    fn get_connected_stations_n<const N: usize>(
+        &self,
+    ) -> Result<(heapless::Vec<StationInfo, N>, usize), PartialError<Self::Error>> {
+        Err(PartialError::Unsupported)
+    }
+
+    #[cfg(feature = "alloc")]
+    fn get_connected_stations(
+        &self,
+    ) -> Result<alloc::vec::Vec<StationInfo>, PartialError<Self::Error>> {
+        Err(PartialError::Unsupported)
+    }
+
+    fn deauth(&mut self, _mac: &[u8; 6]) -> Result<(), PartialError<Self::Error>> {
+        Err(PartialError::Unsupported)
+    }
+
+    fn start_wps(&mut self, _conf: &WpsConfiguration) -> Result<(), PartialError<Self::Error>> {
+        Err(PartialError::Unsupported)
+    }
+
+    fn stop_wps(&mut self) -> Result<(), PartialError<Self::Error>> {
+        Err(PartialError::Unsupported)
+    }
+
+    fn get_power_save(&self) -> Result<PowerSave, PartialError<Self::Error>> {
+        Err(PartialError::Unsupported)
+    }
+
+    fn set_power_save(&mut self, _power_save: PowerSave) -> Result<(), PartialError<Self::Error>> {
+        Err(PartialError::Unsupported)
+    }
+
+    fn start_rssi_monitor(
+        &mut self,
+        _threshold: i8,
+        _hysteresis: u8,
+    ) -> Result<(), PartialError<Self::Error>> {
+        Err(PartialError::Unsupported)
+    }
+
+    fn stop_rssi_monitor(&mut self) -> Result<(), PartialError<Self::Error>> {
+        Err(PartialError::Unsupported)
+    }
+
+    /// Only supported when `get_capabilities` reports `Capability::Csi`
+    fn enable_csi<F>(
+        &mut self,
+        _conf: &CsiConfiguration,
+        _callback: F,
+    ) -> Result<(), PartialError<Self::Error>>
+    where
+        F: FnMut(&CsiFrame) + Send + 'static,
+    {
+        Err(PartialError::Unsupported)
+    }
+
+    fn disable_csi(&mut self) -> Result<(), PartialError<Self::Error>> {
+        Err(PartialError::Unsupported)
+    }
+
+    /// Only supported when `get_capabilities` reports `Capability::Sniffer`
+    fn enable_sniffer<F>(
+        &mut self,
+        _conf: &SnifferConfiguration,
+        _callback: F,
+    ) -> Result<(), PartialError<Self::Error>>
+    where
+        F: FnMut(&SnifferFrame) + Send + 'static,
+    {
+        Err(PartialError::Unsupported)
+    }
+
+    fn disable_sniffer(&mut self) -> Result<(), PartialError<Self::Error>> {
+        Err(PartialError::Unsupported)
+    }
 }
 
 impl<W> Wifi for &mut W
@@ -422,12 +994,185 @@ where
     fn scan(&mut self) -> Result<alloc::vec::Vec<AccessPointInfo>, Self::Error> {
         (*self).scan()
     }
-}
 
-#[cfg(all(feature = "nightly", feature = "experimental"))]
+    fn get_connected_stations_n<const N: usize>(
+        &self,
+    ) -> Result<(heapless::Vec<StationInfo, N>, usize), PartialError<Self::Error>> {
+        (**self).get_connected_stations_n()
+    }
+
+    #[cfg(feature = "alloc")]
+    fn get_connected_stations(
+        &self,
+    ) -> Result<alloc::vec::Vec<StationInfo>, PartialError<Self::Error>> {
+        (**self).get_connected_stations()
+    }
+
+    fn deauth(&mut self, mac: &[u8; 6]) -> Result<(), PartialError<Self::Error>> {
+        (*self).deauth(mac)
+    }
+
+    fn start_wps(&mut self, conf: &WpsConfiguration) -> Result<(), PartialError<Self::Error>> {
+        (*self).start_wps(conf)
+    }
+
+    fn stop_wps(&mut self) -> Result<(), PartialError<Self::Error>> {
+        (*self).stop_wps()
+    }
+
+    fn get_power_save(&self) -> Result<PowerSave, PartialError<Self::Error>> {
+        (**self).get_power_save()
+    }
+
+    fn set_power_save(&mut self, power_save: PowerSave) -> Result<(), PartialError<Self::Error>> {
+        (*self).set_power_save(power_save)
+    }
+
+    fn start_rssi_monitor(
+        &mut self,
+        threshold: i8,
+        hysteresis: u8,
+    ) -> Result<(), PartialError<Self::Error>> {
+        (*self).start_rssi_monitor(threshold, hysteresis)
+    }
+
+    fn stop_rssi_monitor(&mut self) -> Result<(), PartialError<Self::Error>> {
+        (*self).stop_rssi_monitor()
+    }
+
+    fn enable_csi<F>(
+        &mut self,
+        conf: &CsiConfiguration,
+        callback: F,
+    ) -> Result<(), PartialError<Self::Error>>
+    where
+        F: FnMut(&CsiFrame) + Send + 'static,
+    {
+        (*self).enable_csi(conf, callback)
+    }
+
+    fn disable_csi(&mut self) -> Result<(), PartialError<Self::Error>> {
+        (*self).disable_csi()
+    }
+
+    fn enable_sniffer<F>(
+        &mut self,
+        conf: &SnifferConfiguration,
+        callback: F,
+    ) -> Result<(), PartialError<Self::Error>>
+    where
+        F: FnMut(&SnifferFrame) + Send + 'static,
+    {
+        (*self).enable_sniffer(conf, callback)
+    }
+
+    fn disable_sniffer(&mut self) -> Result<(), PartialError<Self::Error>> {
+        (*self).disable_sniffer()
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+#[cfg_attr(
+    feature = "use_strum",
+    derive(EnumString, Display, EnumMessage, EnumIter, EnumVariantNames, FromRepr)
+)]
+#[cfg_attr(feature = "use_numenum", derive(TryFromPrimitive))]
+#[cfg_attr(feature = "use_numenum", repr(u8))]
+pub enum SmartConfigType {
+    #[cfg_attr(
+        feature = "use_strum",
+        strum(serialize = "esptouch", message = "ESP-Touch")
+    )]
+    EspTouch,
+    #[cfg_attr(
+        feature = "use_strum",
+        strum(serialize = "airkiss", message = "AirKiss")
+    )]
+    AirKiss,
+    #[cfg_attr(
+        feature = "use_strum",
+        strum(serialize = "esptouchairkiss", message = "ESP-Touch & AirKiss")
+    )]
+    EspTouchAirKiss,
+    #[cfg_attr(
+        feature = "use_strum",
+        strum(serialize = "esptouchv2", message = "ESP-Touch V2")
+    )]
+    EspTouchV2,
+}
+
+impl Default for SmartConfigType {
+    fn default() -> Self {
+        SmartConfigType::EspTouch
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub struct SmartConfigConfiguration {
+    pub smart_config_type: SmartConfigType,
+    /// Whether to acknowledge the reception of the credentials to the provisioning phone app
+    pub send_ack: bool,
+    /// ESP-Touch V2 only: the key used to decrypt the credentials
+    pub key: Option<heapless::String<16>>,
+}
+
+impl Default for SmartConfigConfiguration {
+    fn default() -> Self {
+        Self {
+            smart_config_type: Default::default(),
+            send_ack: true,
+            key: None,
+        }
+    }
+}
+
+/// Events reported by the driver while SmartConfig provisioning is in progress.
+///
+/// Backends deliver these over their `event_bus::EventBus<SmartConfigEvent>` implementation.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub enum SmartConfigEvent {
+    ScanDone,
+    ChannelFound(u8),
+    /// The credentials were decoded; the configuration can be passed as-is to
+    /// `Wifi::set_configuration`
+    Received(ClientConfiguration),
+    AckSent,
+}
+
+pub trait SmartConfig {
+    type Error: Debug;
+
+    fn start_smart_config(&mut self, conf: &SmartConfigConfiguration) -> Result<(), Self::Error>;
+    fn stop_smart_config(&mut self) -> Result<(), Self::Error>;
+}
+
+impl<S> SmartConfig for &mut S
+where
+    S: SmartConfig,
+{
+    type Error = S::Error;
+
+    fn start_smart_config(&mut self, conf: &SmartConfigConfiguration) -> Result<(), Self::Error> {
+        (*self).start_smart_config(conf)
+    }
+
+    fn stop_smart_config(&mut self) -> Result<(), Self::Error> {
+        (*self).stop_smart_config()
+    }
+}
+
+#[cfg(all(feature = "nightly", feature = "experimental"))]
 pub mod asynch {
     use futures::Future;
 
+    use crate::executor::asynch::{Blocker, Blocking};
+
     use super::*;
 
     pub trait Wifi {
@@ -603,4 +1348,372 @@ pub mod asynch {
             (**self).scan()
         }
     }
+
+    /// The optional operations of `Wifi`, implemented by the drivers which support any of them; the
+    /// futures of the unsupported ones resolve to `PartialError::Unsupported`, e.g. as a
+    /// `core::future::Ready`.
+    ///
+    /// `Blocking` does not forward these operations, so the blocking `Wifi` it implements reports
+    /// them as unsupported.
+    pub trait WifiExt: Wifi {
+        type GetConnectedStationsNFuture<'a, const N: usize>: Future<
+            Output = Result<(heapless::Vec<StationInfo, N>, usize), PartialError<Self::Error>>,
+        >
+        where
+            Self: 'a;
+
+        #[cfg(feature = "alloc")]
+        type GetConnectedStationsFuture<'a>: Future<
+            Output = Result<alloc::vec::Vec<StationInfo>, PartialError<Self::Error>>,
+        >
+        where
+            Self: 'a;
+
+        type DeauthFuture<'a>: Future<Output = Result<(), PartialError<Self::Error>>>
+        where
+            Self: 'a;
+
+        type StartWpsFuture<'a>: Future<Output = Result<(), PartialError<Self::Error>>>
+        where
+            Self: 'a;
+
+        type StopWpsFuture<'a>: Future<Output = Result<(), PartialError<Self::Error>>>
+        where
+            Self: 'a;
+
+        type GetPowerSaveFuture<'a>: Future<Output = Result<PowerSave, PartialError<Self::Error>>>
+        where
+            Self: 'a;
+
+        type SetPowerSaveFuture<'a>: Future<Output = Result<(), PartialError<Self::Error>>>
+        where
+            Self: 'a;
+
+        type StartRssiMonitorFuture<'a>: Future<Output = Result<(), PartialError<Self::Error>>>
+        where
+            Self: 'a;
+
+        type StopRssiMonitorFuture<'a>: Future<Output = Result<(), PartialError<Self::Error>>>
+        where
+            Self: 'a;
+
+        fn get_connected_stations_n<const N: usize>(
+            &self,
+        ) -> Self::GetConnectedStationsNFuture<'_, N>;
+
+        #[cfg(feature = "alloc")]
+        fn get_connected_stations(&self) -> Self::GetConnectedStationsFuture<'_>;
+
+        fn deauth<'a>(&'a mut self, mac: &'a [u8; 6]) -> Self::DeauthFuture<'a>;
+
+        fn start_wps<'a>(&'a mut self, conf: &'a WpsConfiguration) -> Self::StartWpsFuture<'a>;
+        fn stop_wps(&mut self) -> Self::StopWpsFuture<'_>;
+
+        fn get_power_save(&self) -> Self::GetPowerSaveFuture<'_>;
+        fn set_power_save(&mut self, power_save: PowerSave) -> Self::SetPowerSaveFuture<'_>;
+
+        fn start_rssi_monitor(
+            &mut self,
+            threshold: i8,
+            hysteresis: u8,
+        ) -> Self::StartRssiMonitorFuture<'_>;
+        fn stop_rssi_monitor(&mut self) -> Self::StopRssiMonitorFuture<'_>;
+
+        /// Only supported when `get_capabilities` reports `Capability::Csi`
+        fn enable_csi<F>(
+            &mut self,
+            _conf: &CsiConfiguration,
+            _callback: F,
+        ) -> Result<(), PartialError<Self::Error>>
+        where
+            F: FnMut(&CsiFrame) + Send + 'static,
+        {
+            Err(PartialError::Unsupported)
+        }
+
+        fn disable_csi(&mut self) -> Result<(), PartialError<Self::Error>> {
+            Err(PartialError::Unsupported)
+        }
+
+        /// Only supported when `get_capabilities` reports `Capability::Sniffer`
+        fn enable_sniffer<F>(
+            &mut self,
+            _conf: &SnifferConfiguration,
+            _callback: F,
+        ) -> Result<(), PartialError<Self::Error>>
+        where
+            F: FnMut(&SnifferFrame) + Send + 'static,
+        {
+            Err(PartialError::Unsupported)
+        }
+
+        fn disable_sniffer(&mut self) -> Result<(), PartialError<Self::Error>> {
+            Err(PartialError::Unsupported)
+        }
+    }
+
+    impl<W> WifiExt for &mut W
+    where
+        W: WifiExt,
+    {
+        type GetConnectedStationsNFuture<'a, const N: usize> = W::GetConnectedStationsNFuture<'a, N>
+        where
+            Self: 'a;
+
+        #[cfg(feature = "alloc")]
+        type GetConnectedStationsFuture<'a> = W::GetConnectedStationsFuture<'a>
+        where
+            Self: 'a;
+
+        type DeauthFuture<'a> = W::DeauthFuture<'a>
+        where
+            Self: 'a;
+
+        type StartWpsFuture<'a> = W::StartWpsFuture<'a>
+        where
+            Self: 'a;
+
+        type StopWpsFuture<'a> = W::StopWpsFuture<'a>
+        where
+            Self: 'a;
+
+        type GetPowerSaveFuture<'a> = W::GetPowerSaveFuture<'a>
+        where
+            Self: 'a;
+
+        type SetPowerSaveFuture<'a> = W::SetPowerSaveFuture<'a>
+        where
+            Self: 'a;
+
+        type StartRssiMonitorFuture<'a> = W::StartRssiMonitorFuture<'a>
+        where
+            Self: 'a;
+
+        type StopRssiMonitorFuture<'a> = W::StopRssiMonitorFuture<'a>
+        where
+            Self: 'a;
+
+        fn get_connected_stations_n<const N: usize>(
+            &self,
+        ) -> Self::GetConnectedStationsNFuture<'_, N> {
+            (**self).get_connected_stations_n()
+        }
+
+        #[cfg(feature = "alloc")]
+        fn get_connected_stations(&self) -> Self::GetConnectedStationsFuture<'_> {
+            (**self).get_connected_stations()
+        }
+
+        fn deauth<'a>(&'a mut self, mac: &'a [u8; 6]) -> Self::DeauthFuture<'a> {
+            (**self).deauth(mac)
+        }
+
+        fn start_wps<'a>(&'a mut self, conf: &'a WpsConfiguration) -> Self::StartWpsFuture<'a> {
+            (**self).start_wps(conf)
+        }
+
+        fn stop_wps(&mut self) -> Self::StopWpsFuture<'_> {
+            (**self).stop_wps()
+        }
+
+        fn get_power_save(&self) -> Self::GetPowerSaveFuture<'_> {
+            (**self).get_power_save()
+        }
+
+        fn set_power_save(&mut self, power_save: PowerSave) -> Self::SetPowerSaveFuture<'_> {
+            (**self).set_power_save(power_save)
+        }
+
+        fn start_rssi_monitor(
+            &mut self,
+            threshold: i8,
+            hysteresis: u8,
+        ) -> Self::StartRssiMonitorFuture<'_> {
+            (**self).start_rssi_monitor(threshold, hysteresis)
+        }
+
+        fn stop_rssi_monitor(&mut self) -> Self::StopRssiMonitorFuture<'_> {
+            (**self).stop_rssi_monitor()
+        }
+
+        fn enable_csi<F>(
+            &mut self,
+            conf: &CsiConfiguration,
+            callback: F,
+        ) -> Result<(), PartialError<Self::Error>>
+        where
+            F: FnMut(&CsiFrame) + Send + 'static,
+        {
+            (**self).enable_csi(conf, callback)
+        }
+
+        fn disable_csi(&mut self) -> Result<(), PartialError<Self::Error>> {
+            (**self).disable_csi()
+        }
+
+        fn enable_sniffer<F>(
+            &mut self,
+            conf: &SnifferConfiguration,
+            callback: F,
+        ) -> Result<(), PartialError<Self::Error>>
+        where
+            F: FnMut(&SnifferFrame) + Send + 'static,
+        {
+            (**self).enable_sniffer(conf, callback)
+        }
+
+        fn disable_sniffer(&mut self) -> Result<(), PartialError<Self::Error>> {
+            (**self).disable_sniffer()
+        }
+    }
+
+    impl<B, W> super::Wifi for Blocking<B, W>
+    where
+        B: Blocker,
+        W: Wifi,
+    {
+        type Error = W::Error;
+
+        fn get_capabilities(&self) -> Result<EnumSet<Capability>, Self::Error> {
+            self.blocker.block_on(self.api.get_capabilities())
+        }
+
+        fn get_configuration(&self) -> Result<Configuration, Self::Error> {
+            self.blocker.block_on(self.api.get_configuration())
+        }
+
+        fn set_configuration(&mut self, conf: &Configuration) -> Result<(), Self::Error> {
+            self.blocker.block_on(self.api.set_configuration(conf))
+        }
+
+        fn start(&mut self) -> Result<(), Self::Error> {
+            self.blocker.block_on(self.api.start())
+        }
+
+        fn stop(&mut self) -> Result<(), Self::Error> {
+            self.blocker.block_on(self.api.stop())
+        }
+
+        fn connect(&mut self) -> Result<(), Self::Error> {
+            self.blocker.block_on(self.api.connect())
+        }
+
+        fn disconnect(&mut self) -> Result<(), Self::Error> {
+            self.blocker.block_on(self.api.disconnect())
+        }
+
+        fn is_started(&self) -> Result<bool, Self::Error> {
+            self.blocker.block_on(self.api.is_started())
+        }
+
+        fn is_connected(&self) -> Result<bool, Self::Error> {
+            self.blocker.block_on(self.api.is_connected())
+        }
+
+        fn scan_n<const N: usize>(
+            &mut self,
+        ) -> Result<(heapless::Vec<AccessPointInfo, N>, usize), Self::Error> {
+            self.blocker.block_on(self.api.scan_n())
+        }
+
+        #[cfg(feature = "alloc")]
+        fn scan(&mut self) -> Result<alloc::vec::Vec<AccessPointInfo>, Self::Error> {
+            self.blocker.block_on(self.api.scan())
+        }
+    }
+
+    pub trait SmartConfig {
+        type Error: Debug;
+
+        type StartSmartConfigFuture<'a>: Future<Output = Result<(), Self::Error>>
+        where
+            Self: 'a;
+
+        type StopSmartConfigFuture<'a>: Future<Output = Result<(), Self::Error>>
+        where
+            Self: 'a;
+
+        fn start_smart_config<'a>(
+            &'a mut self,
+            conf: &'a SmartConfigConfiguration,
+        ) -> Self::StartSmartConfigFuture<'a>;
+        fn stop_smart_config(&mut self) -> Self::StopSmartConfigFuture<'_>;
+    }
+
+    impl<S> SmartConfig for &mut S
+    where
+        S: SmartConfig,
+    {
+        type Error = S::Error;
+
+        type StartSmartConfigFuture<'a> = S::StartSmartConfigFuture<'a>
+        where
+            Self: 'a;
+
+        type StopSmartConfigFuture<'a> = S::StopSmartConfigFuture<'a>
+        where
+            Self: 'a;
+
+        fn start_smart_config<'a>(
+            &'a mut self,
+            conf: &'a SmartConfigConfiguration,
+        ) -> Self::StartSmartConfigFuture<'a> {
+            (**self).start_smart_config(conf)
+        }
+
+        fn stop_smart_config(&mut self) -> Self::StopSmartConfigFuture<'_> {
+            (**self).stop_smart_config()
+        }
+    }
+
+    impl<B, S> super::SmartConfig for Blocking<B, S>
+    where
+        B: Blocker,
+        S: SmartConfig,
+    {
+        type Error = S::Error;
+
+        fn start_smart_config(
+            &mut self,
+            conf: &SmartConfigConfiguration,
+        ) -> Result<(), Self::Error> {
+            self.blocker.block_on(self.api.start_smart_config(conf))
+        }
+
+        fn stop_smart_config(&mut self) -> Result<(), Self::Error> {
+            self.blocker.block_on(self.api.stop_smart_config())
+        }
+    }
+}
+
+#[cfg(all(test, feature = "use_serde"))]
+mod tests {
+    use serde::de::value::{Error, MapDeserializer};
+
+    use super::*;
+
+    #[test]
+    fn saved_client_configuration() {
+        // As saved before the protocols, bandwidth, country and power save settings were added
+        let saved = [
+            ("ssid", "home"),
+            ("auth_method", "WPA2Personal"),
+            ("password", "secret"),
+        ];
+
+        let configuration = ClientConfiguration::deserialize(MapDeserializer::<_, Error>::new(
+            saved.iter().copied(),
+        ))
+        .unwrap();
+
+        assert_eq!(
+            configuration,
+            ClientConfiguration {
+                ssid: "home".into(),
+                auth_method: AuthMethod::WPA2Personal,
+                password: "secret".into(),
+                ..Default::default()
+            }
+        );
+    }
 }
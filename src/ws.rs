@@ -1,5 +1,8 @@
 use core::fmt::Debug;
 
+use crate::error::{self, impl_error};
+use crate::io::{self, ErrorKind, Io, Read, Write};
+
 pub trait ErrorType {
     type Error: Debug;
 }
@@ -77,6 +80,210 @@ where
     }
 }
 
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum MessageError<E> {
+    WsError(E),
+    /// A data frame arrived in the middle of a fragmented message, or a continuation outside of one
+    UnexpectedFrame(FrameType),
+    Closed,
+}
+
+impl_error! {
+    MessageError<E: Display> {
+        WsError(e) => "WebSocket error: {e}"; e.error_kind(),
+        UnexpectedFrame(frame_type) => "Unexpected frame: {frame_type:?}"; error::ErrorKind::InvalidInput,
+        Closed => "Connection closed"; error::ErrorKind::Unavailable,
+    }
+}
+
+impl<E> io::Error for MessageError<E>
+where
+    E: Debug,
+{
+    fn kind(&self) -> ErrorKind {
+        ErrorKind::Other
+    }
+}
+
+/// Sends a message of arbitrary size as fragments of up to `buf.len()` bytes.
+///
+/// A message which fits into `buf` is sent as a single, unfragmented frame.
+pub struct MessageWriter<'b, S> {
+    sender: S,
+    binary: bool,
+    buf: &'b mut [u8],
+    len: usize,
+    started: bool,
+}
+
+impl<'b, S> MessageWriter<'b, S>
+where
+    S: Sender,
+{
+    pub fn new(sender: S, binary: bool, buf: &'b mut [u8]) -> Self {
+        Self {
+            sender,
+            binary,
+            buf,
+            len: 0,
+            started: false,
+        }
+    }
+
+    /// Sends the last fragment; the message is incomplete for the peer until then
+    pub fn finish(mut self) -> Result<S, MessageError<S::Error>> {
+        self.send_fragment(true)?;
+
+        Ok(self.sender)
+    }
+
+    fn send_fragment(&mut self, final_: bool) -> Result<(), MessageError<S::Error>> {
+        let frame_type = match (self.started, self.binary) {
+            (false, false) => FrameType::Text(!final_),
+            (false, true) => FrameType::Binary(!final_),
+            (true, _) => FrameType::Continue(final_),
+        };
+
+        self.sender
+            .send(frame_type, &self.buf[..self.len])
+            .map_err(MessageError::WsError)?;
+
+        self.started = true;
+        self.len = 0;
+
+        Ok(())
+    }
+}
+
+impl<'b, S> Io for MessageWriter<'b, S>
+where
+    S: Sender,
+{
+    type Error = MessageError<S::Error>;
+}
+
+impl<'b, S> Write for MessageWriter<'b, S>
+where
+    S: Sender,
+{
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        if self.len == self.buf.len() {
+            self.send_fragment(false)?;
+        }
+
+        let len = buf.len().min(self.buf.len() - self.len);
+
+        self.buf[self.len..self.len + len].copy_from_slice(&buf[..len]);
+        self.len += len;
+
+        Ok(len)
+    }
+
+    /// Sends the buffered data as a (non-final) fragment
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        if self.len > 0 {
+            self.send_fragment(false)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Reads a possibly fragmented message frame by frame; `buf` should fit the largest fragment.
+///
+/// Reading returns 0 once the final fragment is consumed. Pings and pongs received in
+/// the middle of the message are skipped, so the `Receiver` should answer pings on its own.
+pub struct MessageReader<'b, R> {
+    receiver: R,
+    buf: &'b mut [u8],
+    offset: usize,
+    len: usize,
+    first: Option<FrameType>,
+    complete: bool,
+}
+
+impl<'b, R> MessageReader<'b, R>
+where
+    R: Receiver,
+{
+    pub fn new(receiver: R, buf: &'b mut [u8]) -> Self {
+        Self {
+            receiver,
+            buf,
+            offset: 0,
+            len: 0,
+            first: None,
+            complete: false,
+        }
+    }
+
+    /// `Text` or `Binary`, once the first fragment is received
+    pub fn frame_type(&self) -> Option<FrameType> {
+        self.first
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.complete
+    }
+
+    pub fn release(self) -> R {
+        self.receiver
+    }
+
+    fn receive(&mut self) -> Result<(), MessageError<R::Error>> {
+        loop {
+            let (frame_type, len) = self
+                .receiver
+                .recv(self.buf)
+                .map_err(MessageError::WsError)?;
+
+            match (frame_type, self.first) {
+                (FrameType::Text(_) | FrameType::Binary(_), None) => self.first = Some(frame_type),
+                (FrameType::Continue(_), Some(_)) => (),
+                (FrameType::Ping | FrameType::Pong, _) => continue,
+                (FrameType::Close | FrameType::SocketClose, _) => return Err(MessageError::Closed),
+                _ => return Err(MessageError::UnexpectedFrame(frame_type)),
+            }
+
+            self.offset = 0;
+            self.len = len;
+            self.complete = frame_type.is_final();
+
+            return Ok(());
+        }
+    }
+}
+
+impl<'b, R> Io for MessageReader<'b, R>
+where
+    R: Receiver,
+{
+    type Error = MessageError<R::Error>;
+}
+
+impl<'b, R> Read for MessageReader<'b, R>
+where
+    R: Receiver,
+{
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        while self.offset == self.len {
+            if self.complete {
+                return Ok(0);
+            }
+
+            self.receive()?;
+        }
+
+        let len = buf.len().min(self.len - self.offset);
+
+        buf[..len].copy_from_slice(&self.buf[self.offset..self.offset + len]);
+        self.offset += len;
+
+        Ok(len)
+    }
+}
+
 pub mod server {
     pub use super::*;
 
@@ -98,6 +305,28 @@ pub mod server {
     }
 }
 
+pub mod client {
+    pub use super::*;
+
+    /// Opens client connections to a server configured upfront, e.g. with its URI and subprotocols
+    pub trait Connector: ErrorType {
+        type Connection: Sender<Error = Self::Error> + Receiver<Error = Self::Error>;
+
+        fn connect(&mut self) -> Result<Self::Connection, Self::Error>;
+    }
+
+    impl<C> Connector for &mut C
+    where
+        C: Connector,
+    {
+        type Connection = C::Connection;
+
+        fn connect(&mut self) -> Result<Self::Connection, Self::Error> {
+            (*self).connect()
+        }
+    }
+}
+
 pub mod callback_server {
     pub use super::*;
 